@@ -0,0 +1,92 @@
+mod datasets_paths;
+mod utils;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId};
+use milli::update::Settings;
+use utils::Conf;
+
+#[global_allocator]
+static ALLOC: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
+fn enable_compression(builder: &mut Settings) {
+    builder.set_documents_compression(true);
+}
+
+fn bench_documents_retrieval(c: &mut criterion::Criterion) {
+    let confs = &[
+        utils::Conf {
+            group_name: "uncompressed",
+            dataset: datasets_paths::SMOL_WIKI_ARTICLES,
+            ..Conf::BASE
+        },
+        utils::Conf {
+            group_name: "compressed",
+            dataset: datasets_paths::SMOL_WIKI_ARTICLES,
+            configure: enable_compression,
+            ..Conf::BASE
+        },
+    ];
+
+    let mut group = c.benchmark_group("documents retrieval");
+    for conf in confs {
+        let index = utils::base_setup(conf);
+
+        let rtxn = index.read_txn().unwrap();
+        let documents_ids: Vec<_> = index.documents_ids(&rtxn).unwrap().iter().take(20).collect();
+        group.bench_with_input(
+            BenchmarkId::from_parameter(conf.group_name),
+            &documents_ids,
+            |b, documents_ids| {
+                b.iter(|| index.documents(&rtxn, documents_ids.iter().copied()).unwrap())
+            },
+        );
+        drop(rtxn);
+
+        index.prepare_for_closing().wait();
+    }
+    group.finish();
+}
+
+fn bench_external_ids_resolution(c: &mut criterion::Criterion) {
+    let conf = utils::Conf {
+        group_name: "external ids resolution",
+        dataset: datasets_paths::SMOL_WIKI_ARTICLES,
+        ..Conf::BASE
+    };
+
+    let mut group = c.benchmark_group(conf.group_name);
+    let index = utils::base_setup(&conf);
+
+    let rtxn = index.read_txn().unwrap();
+    let documents_ids: Vec<_> = index.documents_ids(&rtxn).unwrap().iter().take(10_000).collect();
+    let primary_key = index.primary_key(&rtxn).unwrap().unwrap().to_string();
+
+    // Resolves the external ids one document at a time, as done by the hosts.
+    group.bench_with_input("one by one", &documents_ids, |b, documents_ids| {
+        b.iter(|| {
+            let fields_ids_map = index.fields_ids_map(&rtxn).unwrap();
+            let id_field = fields_ids_map.id(&primary_key).unwrap();
+            documents_ids
+                .iter()
+                .map(|&id| {
+                    let (_, document) = index.documents(&rtxn, Some(id)).unwrap().pop().unwrap();
+                    let value = document.as_obkv().get(id_field).unwrap();
+                    match serde_json::from_slice(value).unwrap() {
+                        serde_json::Value::String(external_id) => (id, external_id),
+                        external_id => (id, external_id.to_string()),
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_with_input("in bulk", &documents_ids, |b, documents_ids| {
+        b.iter(|| index.external_ids_of_slice(&rtxn, documents_ids).unwrap())
+    });
+    drop(rtxn);
+
+    index.prepare_for_closing().wait();
+    group.finish();
+}
+
+criterion_group!(benches, bench_documents_retrieval, bench_external_ids_resolution);
+criterion_main!(benches);
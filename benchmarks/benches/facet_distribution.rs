@@ -0,0 +1,61 @@
+mod datasets_paths;
+mod utils;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId};
+use milli::update::Settings;
+use roaring::RoaringBitmap;
+use utils::Conf;
+
+#[global_allocator]
+static ALLOC: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
+fn base_conf(builder: &mut Settings) {
+    let faceted_fields = ["released-timestamp", "duration-float", "genre", "country", "artist"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    builder.set_filterable_fields(faceted_fields);
+}
+
+const BASE_CONF: Conf = Conf {
+    dataset: datasets_paths::SMOL_SONGS,
+    configure: base_conf,
+    primary_key: Some("id"),
+    ..Conf::BASE
+};
+
+/// Compares the distribution computed from the facet values of each candidate
+/// with the one computed from the facet levels, for different numbers of candidates.
+fn bench_facet_distribution(c: &mut criterion::Criterion) {
+    let index = utils::base_setup(&BASE_CONF);
+    let rtxn = index.read_txn().unwrap();
+    let documents_ids = index.documents_ids(&rtxn).unwrap();
+
+    let strategies = [("from documents", u64::MAX), ("from facet levels", 0)];
+    for (name, threshold) in strategies {
+        let mut group = c.benchmark_group(format!("smol-songs: facet distribution {}", name));
+        for count in [5, 100, 1_000, 10_000, 100_000] {
+            let step = (documents_ids.len() as usize / count).max(1);
+            let candidates: RoaringBitmap =
+                documents_ids.iter().step_by(step).take(count).collect();
+            group.bench_with_input(
+                BenchmarkId::from_parameter(count),
+                &candidates,
+                |b, candidates| {
+                    b.iter(|| {
+                        let mut distribution = index.facets_distribution(&rtxn);
+                        distribution.candidates(candidates.clone()).candidates_threshold(threshold);
+                        distribution.execute().unwrap()
+                    });
+                },
+            );
+        }
+        group.finish();
+    }
+
+    drop(rtxn);
+    index.prepare_for_closing().wait();
+}
+
+criterion_group!(benches, bench_facet_distribution);
+criterion_main!(benches);
@@ -0,0 +1,237 @@
+mod utils;
+
+use std::alloc::{GlobalAlloc, Layout};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::measurement::{Measurement, ValueFormatter};
+use criterion::{
+    criterion_group, criterion_main, BenchmarkId, Criterion, SamplingMode, Throughput,
+};
+use milli::documents::DocumentsBatchBuilder;
+use milli::heed::EnvOpenOptions;
+use milli::update::{Facets, IndexDocuments, IndexDocumentsConfig, IndexerConfig, Settings};
+use milli::{Filter, Index};
+use roaring::RoaringBitmap;
+use tempfile::TempDir;
+
+#[global_allocator]
+static ALLOC: PeakAllocator = PeakAllocator;
+
+/// The number of distinct facet values of the synthetic fields.
+const CARDINALITIES: &[usize] = &[250_000, 1_000_000];
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+/// Keeps track of the allocated memory and of its peak on top of mimalloc.
+struct PeakAllocator;
+
+unsafe impl GlobalAlloc for PeakAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = mimalloc::MiMalloc.alloc(layout);
+        if !ptr.is_null() {
+            grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        mimalloc::MiMalloc.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = mimalloc::MiMalloc.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            if new_size > layout.size() {
+                grow(new_size - layout.size());
+            } else {
+                ALLOCATED.fetch_sub(layout.size() - new_size, Ordering::Relaxed);
+            }
+        }
+        new_ptr
+    }
+}
+
+fn grow(size: usize) {
+    let allocated = ALLOCATED.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK.fetch_max(allocated, Ordering::Relaxed);
+}
+
+/// Measures the peak of the memory allocated by a routine, above the memory
+/// that was already allocated when it started.
+struct PeakMemory;
+
+impl Measurement for PeakMemory {
+    type Intermediate = usize;
+    type Value = usize;
+
+    fn start(&self) -> usize {
+        let allocated = ALLOCATED.load(Ordering::Relaxed);
+        PEAK.store(allocated, Ordering::Relaxed);
+        allocated
+    }
+
+    fn end(&self, allocated: usize) -> usize {
+        PEAK.load(Ordering::Relaxed).saturating_sub(allocated)
+    }
+
+    fn add(&self, v1: &usize, v2: &usize) -> usize {
+        v1 + v2
+    }
+
+    fn zero(&self) -> usize {
+        0
+    }
+
+    fn to_f64(&self, value: &usize) -> f64 {
+        *value as f64
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &BytesFormatter
+    }
+}
+
+struct BytesFormatter;
+
+impl ValueFormatter for BytesFormatter {
+    fn scale_values(&self, typical_value: f64, values: &mut [f64]) -> &'static str {
+        let (factor, unit) = if typical_value < 1024.0 {
+            (1.0, "B")
+        } else if typical_value < 1024.0 * 1024.0 {
+            (1024.0, "KiB")
+        } else if typical_value < 1024.0 * 1024.0 * 1024.0 {
+            (1024.0 * 1024.0, "MiB")
+        } else {
+            (1024.0 * 1024.0 * 1024.0, "GiB")
+        };
+        values.iter_mut().for_each(|value| *value /= factor);
+        unit
+    }
+
+    fn scale_throughputs(
+        &self,
+        _typical_value: f64,
+        _throughput: &Throughput,
+        _values: &mut [f64],
+    ) -> &'static str {
+        unreachable!("the peak memory benchmarks don't declare any throughput")
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "B"
+    }
+}
+
+/// Indexes documents with a string and a number faceted field, every document
+/// has its own value in both fields.
+fn setup_index(dir: &TempDir, cardinality: usize) -> Index {
+    let mut options = EnvOpenOptions::new();
+    options.map_size(100 * 1024 * 1024 * 1024); // 100 GB
+    let index = Index::new(options, dir.path()).unwrap();
+
+    let config = IndexerConfig::default();
+    let mut wtxn = index.write_txn().unwrap();
+    let mut builder = Settings::new(&mut wtxn, &index, &config);
+    builder.set_primary_key("id".to_owned());
+    let filterable_fields = ["string", "number"].iter().map(|s| s.to_string()).collect();
+    builder.set_filterable_fields(filterable_fields);
+    builder.execute(|_| ()).unwrap();
+
+    let mut documents = DocumentsBatchBuilder::new(Vec::new());
+    for i in 0..cardinality {
+        let document = serde_json::json!({
+            "id": i,
+            "string": format!("value-{:08}", i),
+            "number": i as f64 / 10.0,
+        });
+        documents.append_json_object(document.as_object().unwrap()).unwrap();
+    }
+    let documents = documents.into_inner().unwrap();
+
+    let builder = IndexDocuments::new(
+        &mut wtxn,
+        &index,
+        &config,
+        IndexDocumentsConfig::default(),
+        |_| (),
+        || false,
+    )
+    .unwrap();
+    let (builder, user_error) = builder.add_documents(utils::documents_reader(&documents)).unwrap();
+    user_error.unwrap();
+    builder.execute().unwrap();
+    wtxn.commit().unwrap();
+
+    index
+}
+
+/// Measures the peak memory used to build the facet levels from scratch, it must
+/// not grow with the number of distinct facet values.
+fn bench_facet_levels_peak_memory(c: &mut Criterion<PeakMemory>) {
+    let mut group = c.benchmark_group("facet levels peak memory");
+    group.sample_size(10);
+    group.sampling_mode(SamplingMode::Flat);
+
+    for &cardinality in CARDINALITIES {
+        let dir = TempDir::new().unwrap();
+        let index = setup_index(&dir, cardinality);
+
+        group.bench_function(BenchmarkId::from_parameter(cardinality), |b| {
+            b.iter_custom(|iters| {
+                let mut total = PeakMemory.zero();
+                for _ in 0..iters {
+                    let mut wtxn = index.write_txn().unwrap();
+                    let start = PeakMemory.start();
+                    Facets::new(&mut wtxn, &index).execute(|_| (), || false).unwrap();
+                    total = PeakMemory.add(&total, &PeakMemory.end(start));
+                    wtxn.abort().unwrap();
+                }
+                total
+            })
+        });
+
+        index.prepare_for_closing().wait();
+    }
+    group.finish();
+}
+
+/// Compares a range filter over most of the values of a large field, evaluated by
+/// descending the facet levels, with the union of the level 0 entries in the range.
+fn bench_wide_range_filter(c: &mut Criterion) {
+    let dir = TempDir::new().unwrap();
+    let index = setup_index(&dir, *CARDINALITIES.last().unwrap());
+    let (low, high) = (1_000.0, 90_000.0);
+
+    let rtxn = index.read_txn().unwrap();
+    let field_id = index.fields_ids_map(&rtxn).unwrap().id("number").unwrap();
+    let expression = format!("number {} TO {}", low, high);
+    let filter = Filter::from_str(&expression).unwrap().unwrap();
+    let level_0_scan = || {
+        let range = (field_id, 0, low, low)..=(field_id, 0, high, high);
+        let mut docids = RoaringBitmap::new();
+        for result in index.facet_id_f64_docids.range(&rtxn, &range).unwrap() {
+            let (_key, bitmap) = result.unwrap();
+            docids |= bitmap;
+        }
+        docids
+    };
+    assert_eq!(filter.evaluate(&rtxn, &index).unwrap(), level_0_scan());
+
+    let mut group = c.benchmark_group("wide range filter");
+    group.bench_function("facet levels", |b| b.iter(|| filter.evaluate(&rtxn, &index).unwrap()));
+    group.bench_function("level 0 scan", |b| b.iter(level_0_scan));
+    group.finish();
+
+    drop(rtxn);
+    index.prepare_for_closing().wait();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().with_measurement(PeakMemory);
+    targets = bench_facet_levels_peak_memory
+}
+criterion_group!(filter_benches, bench_wide_range_filter);
+criterion_main!(benches, filter_benches);
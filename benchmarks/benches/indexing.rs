@@ -6,6 +6,7 @@ use std::path::Path;
 
 use criterion::{criterion_group, criterion_main, Criterion};
 use milli::heed::{EnvOpenOptions, RwTxn};
+use milli::position_bucketing::PositionBucketing;
 use milli::update::{
     DeleteDocuments, IndexDocuments, IndexDocumentsConfig, IndexerConfig, Settings,
 };
@@ -108,6 +109,29 @@ fn choose_document_ids_from_index_batched(
         .collect()
 }
 
+fn songs_conf(builder: &mut Settings) {
+    let searchable_fields = ["title", "album", "artist"].iter().map(|s| s.to_string()).collect();
+    builder.set_searchable_fields(searchable_fields);
+
+    let filterable_fields = ["released-timestamp", "duration-float", "genre", "country", "artist"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    builder.set_filterable_fields(filterable_fields);
+}
+
+fn indexing_songs_from_conf(c: &mut Criterion) {
+    let confs = &[utils::Conf {
+        group_name: "default",
+        dataset: datasets_paths::SMOL_SONGS,
+        configure: songs_conf,
+        primary_key: Some("id"),
+        ..utils::Conf::BASE
+    }];
+
+    utils::run_indexing_benches(c, confs);
+}
+
 fn indexing_songs_default(c: &mut Criterion) {
     let mut group = c.benchmark_group("indexing");
     group.sample_size(BENCHMARK_ITERATION);
@@ -131,9 +155,15 @@ fn indexing_songs_default(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let indexing_config = IndexDocumentsConfig::default();
                 let mut wtxn = index.write_txn().unwrap();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents = utils::documents_from(datasets_paths::SMOL_SONGS, "csv");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
@@ -169,9 +199,15 @@ fn reindexing_songs_default(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let indexing_config = IndexDocumentsConfig::default();
                 let mut wtxn = index.write_txn().unwrap();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents = utils::documents_from(datasets_paths::SMOL_SONGS, "csv");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
@@ -185,9 +221,15 @@ fn reindexing_songs_default(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let indexing_config = IndexDocumentsConfig::default();
                 let mut wtxn = index.write_txn().unwrap();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents = utils::documents_from(datasets_paths::SMOL_SONGS, "csv");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
@@ -225,9 +267,15 @@ fn deleting_songs_in_batches_default(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let mut wtxn = index.write_txn().unwrap();
                 let indexing_config = IndexDocumentsConfig::default();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
                 let documents = utils::documents_from(datasets_paths::SMOL_SONGS, "csv");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
                 user_error.unwrap();
@@ -282,9 +330,15 @@ fn indexing_songs_in_three_batches_default(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let mut wtxn = index.write_txn().unwrap();
                 let indexing_config = IndexDocumentsConfig::default();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
                 let documents = utils::documents_from(datasets_paths::SMOL_SONGS_1_2, "csv");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
                 user_error.unwrap();
@@ -298,18 +352,30 @@ fn indexing_songs_in_three_batches_default(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let indexing_config = IndexDocumentsConfig::default();
                 let mut wtxn = index.write_txn().unwrap();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
                 let documents = utils::documents_from(datasets_paths::SMOL_SONGS_3_4, "csv");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
                 user_error.unwrap();
                 builder.execute().unwrap();
 
                 let indexing_config = IndexDocumentsConfig::default();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
                 let documents = utils::documents_from(datasets_paths::SMOL_SONGS_4_4, "csv");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
                 user_error.unwrap();
@@ -345,9 +411,15 @@ fn indexing_songs_without_faceted_numbers(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let indexing_config = IndexDocumentsConfig::default();
                 let mut wtxn = index.write_txn().unwrap();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents = utils::documents_from(datasets_paths::SMOL_SONGS, "csv");
 
@@ -384,9 +456,15 @@ fn indexing_songs_without_faceted_fields(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let indexing_config = IndexDocumentsConfig::default();
                 let mut wtxn = index.write_txn().unwrap();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents = utils::documents_from(datasets_paths::SMOL_SONGS, "csv");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
@@ -423,9 +501,114 @@ fn indexing_wiki(c: &mut Criterion) {
                 let indexing_config =
                     IndexDocumentsConfig { autogenerate_docids: true, ..Default::default() };
                 let mut wtxn = index.write_txn().unwrap();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
+
+                let documents = utils::documents_from(datasets_paths::SMOL_WIKI_ARTICLES, "csv");
+                let (builder, user_error) = builder.add_documents(documents).unwrap();
+                user_error.unwrap();
+                builder.execute().unwrap();
+                wtxn.commit().unwrap();
+
+                index.prepare_for_closing().wait();
+            },
+        )
+    });
+}
+
+fn indexing_wiki_max_proximity_4(c: &mut Criterion) {
+    let mut group = c.benchmark_group("indexing");
+    group.sample_size(BENCHMARK_ITERATION);
+    group.bench_function("Indexing wiki with a max proximity of 4", |b| {
+        b.iter_with_setup(
+            move || {
+                let primary_key = "id";
+                let searchable_fields = ["title", "body"];
+                let filterable_fields = [];
+                let sortable_fields = [];
+
+                setup_index_with_settings(
+                    &primary_key,
+                    &searchable_fields,
+                    &filterable_fields,
+                    &sortable_fields,
+                )
+            },
+            move |index| {
+                let config = IndexerConfig { max_proximity: Some(4), ..Default::default() };
+                let indexing_config =
+                    IndexDocumentsConfig { autogenerate_docids: true, ..Default::default() };
+                let mut wtxn = index.write_txn().unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
+
+                let documents = utils::documents_from(datasets_paths::SMOL_WIKI_ARTICLES, "csv");
+                let (builder, user_error) = builder.add_documents(documents).unwrap();
+                user_error.unwrap();
+                builder.execute().unwrap();
+                wtxn.commit().unwrap();
+
+                index.prepare_for_closing().wait();
+            },
+        )
+    });
+}
+
+fn indexing_wiki_with_position_bucketing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("indexing");
+    group.sample_size(BENCHMARK_ITERATION);
+    group.bench_function("Indexing wiki with the positions after the 16th in buckets", |b| {
+        b.iter_with_setup(
+            move || {
+                let primary_key = "id";
+                let searchable_fields = ["title", "body"];
+                let filterable_fields = [];
+                let sortable_fields = [];
+
+                let index = setup_index_with_settings(
+                    &primary_key,
+                    &searchable_fields,
+                    &filterable_fields,
+                    &sortable_fields,
+                );
+
+                let config = IndexerConfig::default();
+                let mut wtxn = index.write_txn().unwrap();
+                let mut builder = Settings::new(&mut wtxn, &index, &config);
+                builder.set_position_bucketing(PositionBucketing::Logarithmic { exact: 16 });
+                builder.execute(|_| ()).unwrap();
+                wtxn.commit().unwrap();
+
+                index
+            },
+            move |index| {
+                let config = IndexerConfig::default();
+                let indexing_config =
+                    IndexDocumentsConfig { autogenerate_docids: true, ..Default::default() };
+                let mut wtxn = index.write_txn().unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents = utils::documents_from(datasets_paths::SMOL_WIKI_ARTICLES, "csv");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
@@ -461,9 +644,15 @@ fn reindexing_wiki(c: &mut Criterion) {
                 let indexing_config =
                     IndexDocumentsConfig { autogenerate_docids: true, ..Default::default() };
                 let mut wtxn = index.write_txn().unwrap();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents = utils::documents_from(datasets_paths::SMOL_WIKI_ARTICLES, "csv");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
@@ -478,9 +667,15 @@ fn reindexing_wiki(c: &mut Criterion) {
                 let indexing_config =
                     IndexDocumentsConfig { autogenerate_docids: true, ..Default::default() };
                 let mut wtxn = index.write_txn().unwrap();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents = utils::documents_from(datasets_paths::SMOL_WIKI_ARTICLES, "csv");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
@@ -518,9 +713,15 @@ fn deleting_wiki_in_batches_default(c: &mut Criterion) {
                 let mut wtxn = index.write_txn().unwrap();
                 let indexing_config =
                     IndexDocumentsConfig { autogenerate_docids: true, ..Default::default() };
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
                 let documents = utils::documents_from(datasets_paths::SMOL_WIKI_ARTICLES, "csv");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
                 user_error.unwrap();
@@ -576,9 +777,15 @@ fn indexing_wiki_in_three_batches(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let indexing_config =
                     IndexDocumentsConfig { autogenerate_docids: true, ..Default::default() };
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
                 let documents =
                     utils::documents_from(datasets_paths::SMOL_WIKI_ARTICLES_1_2, "csv");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
@@ -594,9 +801,15 @@ fn indexing_wiki_in_three_batches(c: &mut Criterion) {
                 let indexing_config =
                     IndexDocumentsConfig { autogenerate_docids: true, ..Default::default() };
                 let mut wtxn = index.write_txn().unwrap();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents =
                     utils::documents_from(datasets_paths::SMOL_WIKI_ARTICLES_3_4, "csv");
@@ -606,9 +819,15 @@ fn indexing_wiki_in_three_batches(c: &mut Criterion) {
 
                 let indexing_config =
                     IndexDocumentsConfig { autogenerate_docids: true, ..Default::default() };
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents =
                     utils::documents_from(datasets_paths::SMOL_WIKI_ARTICLES_4_4, "csv");
@@ -646,9 +865,15 @@ fn indexing_movies_default(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let indexing_config = IndexDocumentsConfig::default();
                 let mut wtxn = index.write_txn().unwrap();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents = utils::documents_from(datasets_paths::MOVIES, "json");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
@@ -683,9 +908,15 @@ fn reindexing_movies_default(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let indexing_config = IndexDocumentsConfig::default();
                 let mut wtxn = index.write_txn().unwrap();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents = utils::documents_from(datasets_paths::MOVIES, "json");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
@@ -699,9 +930,15 @@ fn reindexing_movies_default(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let indexing_config = IndexDocumentsConfig::default();
                 let mut wtxn = index.write_txn().unwrap();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents = utils::documents_from(datasets_paths::MOVIES, "json");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
@@ -738,9 +975,15 @@ fn deleting_movies_in_batches_default(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let mut wtxn = index.write_txn().unwrap();
                 let indexing_config = IndexDocumentsConfig::default();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
                 let documents = utils::documents_from(datasets_paths::MOVIES, "json");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
                 user_error.unwrap();
@@ -794,9 +1037,15 @@ fn indexing_movies_in_three_batches(c: &mut Criterion) {
                 // as we don't care about the time it takes.
                 let config = IndexerConfig::default();
                 let indexing_config = IndexDocumentsConfig::default();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents = utils::documents_from(datasets_paths::MOVIES_1_2, "json");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
@@ -811,9 +1060,15 @@ fn indexing_movies_in_three_batches(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let indexing_config = IndexDocumentsConfig::default();
                 let mut wtxn = index.write_txn().unwrap();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents = utils::documents_from(datasets_paths::MOVIES_3_4, "json");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
@@ -821,9 +1076,15 @@ fn indexing_movies_in_three_batches(c: &mut Criterion) {
                 builder.execute().unwrap();
 
                 let indexing_config = IndexDocumentsConfig::default();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents = utils::documents_from(datasets_paths::MOVIES_4_4, "json");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
@@ -883,9 +1144,15 @@ fn indexing_nested_movies_default(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let indexing_config = IndexDocumentsConfig::default();
                 let mut wtxn = index.write_txn().unwrap();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents = utils::documents_from(datasets_paths::NESTED_MOVIES, "json");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
@@ -945,9 +1212,15 @@ fn deleting_nested_movies_in_batches_default(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let mut wtxn = index.write_txn().unwrap();
                 let indexing_config = IndexDocumentsConfig::default();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
                 let documents = utils::documents_from(datasets_paths::NESTED_MOVIES, "json");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
                 user_error.unwrap();
@@ -1008,9 +1281,15 @@ fn indexing_nested_movies_without_faceted_fields(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let indexing_config = IndexDocumentsConfig::default();
                 let mut wtxn = index.write_txn().unwrap();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents = utils::documents_from(datasets_paths::NESTED_MOVIES, "json");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
@@ -1046,9 +1325,15 @@ fn indexing_geo(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let indexing_config = IndexDocumentsConfig::default();
                 let mut wtxn = index.write_txn().unwrap();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents = utils::documents_from(datasets_paths::SMOL_ALL_COUNTRIES, "jsonl");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
@@ -1084,9 +1369,15 @@ fn reindexing_geo(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let indexing_config = IndexDocumentsConfig::default();
                 let mut wtxn = index.write_txn().unwrap();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents = utils::documents_from(datasets_paths::SMOL_ALL_COUNTRIES, "jsonl");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
@@ -1101,9 +1392,15 @@ fn reindexing_geo(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let indexing_config = IndexDocumentsConfig::default();
                 let mut wtxn = index.write_txn().unwrap();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
 
                 let documents = utils::documents_from(datasets_paths::SMOL_ALL_COUNTRIES, "jsonl");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
@@ -1141,9 +1438,15 @@ fn deleting_geo_in_batches_default(c: &mut Criterion) {
                 let config = IndexerConfig::default();
                 let mut wtxn = index.write_txn().unwrap();
                 let indexing_config = IndexDocumentsConfig::default();
-                let builder =
-                    IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ())
-                        .unwrap();
+                let builder = IndexDocuments::new(
+                    &mut wtxn,
+                    &index,
+                    &config,
+                    indexing_config,
+                    |_| (),
+                    || false,
+                )
+                .unwrap();
                 let documents = utils::documents_from(datasets_paths::SMOL_ALL_COUNTRIES, "jsonl");
                 let (builder, user_error) = builder.add_documents(documents).unwrap();
                 user_error.unwrap();
@@ -1176,6 +1479,7 @@ fn deleting_geo_in_batches_default(c: &mut Criterion) {
 
 criterion_group!(
     benches,
+    indexing_songs_from_conf,
     indexing_songs_default,
     reindexing_songs_default,
     deleting_songs_in_batches_default,
@@ -1183,6 +1487,8 @@ criterion_group!(
     indexing_songs_without_faceted_fields,
     indexing_songs_in_three_batches_default,
     indexing_wiki,
+    indexing_wiki_max_proximity_4,
+    indexing_wiki_with_position_bucketing,
     reindexing_wiki,
     deleting_wiki_in_batches_default,
     indexing_wiki_in_three_batches,
@@ -26,6 +26,13 @@ fn base_conf(builder: &mut Settings) {
     builder.set_filterable_fields(faceted_fields);
 }
 
+fn sortable_conf(builder: &mut Settings) {
+    base_conf(builder);
+
+    let sortable_fields = ["released-timestamp"].iter().map(|s| s.to_string()).collect();
+    builder.set_sortable_fields(sortable_fields);
+}
+
 #[rustfmt::skip]
 const BASE_CONF: Conf = Conf {
     dataset: datasets_paths::SMOL_SONGS,
@@ -155,6 +162,13 @@ fn bench_songs(c: &mut criterion::Criterion) {
             ..BASE_CONF
         },
 
+        /* we bench the default request restricted to a single searchable attribute */
+        utils::Conf {
+            group_name: "restricted to artist",
+            searchable_attributes: Some(&["artist"]),
+            ..BASE_CONF
+        },
+
         /* the we bench some global / normal search with all the default criterion in the default
          * order */
         utils::Conf {
@@ -176,6 +190,42 @@ fn bench_songs(c: &mut criterion::Criterion) {
             queries: basic_with_quote,
             ..BASE_CONF
         },
+        utils::Conf {
+            group_name: "basic with docids cache",
+            docids_cache_max_size: Some(64 * 1024 * 1024), // 64 MiB
+            ..BASE_CONF
+        },
+        /* then we bench the placeholder searches and the facet distributions */
+        utils::Conf {
+            group_name: "placeholder filter",
+            queries: &[],
+            filter: Some("released-timestamp 946728000 TO 1262347200"), // year 2000 to 2010
+            placeholder_search: true,
+            ..BASE_CONF
+        },
+        utils::Conf {
+            group_name: "placeholder sort",
+            queries: &[],
+            sort: Some(vec!["released-timestamp:desc"]),
+            configure: sortable_conf,
+            placeholder_search: true,
+            ..BASE_CONF
+        },
+        utils::Conf {
+            group_name: "placeholder sort asc",
+            queries: &[],
+            sort: Some(vec!["released-timestamp:asc"]),
+            configure: sortable_conf,
+            placeholder_search: true,
+            ..BASE_CONF
+        },
+        utils::Conf {
+            group_name: "facet distribution",
+            queries: &[],
+            filter: Some("genre = rock"),
+            facets: Some(vec!["released-timestamp", "duration-float", "genre", "country", "artist"]),
+            ..BASE_CONF
+        },
         utils::Conf {
             group_name: "prefix search",
             queries: &[
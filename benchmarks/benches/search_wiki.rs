@@ -87,6 +87,29 @@ fn bench_songs(c: &mut criterion::Criterion) {
             criterion: Some(&["words"]),
             ..BASE_CONF
         },
+        utils::Conf {
+            group_name: "words with bitmap arena",
+            queries: &[
+                "the black saint and the sinner lady and the good doggo ",
+                "Kameya Tokujirō mingus monk ",
+                "Ulrich Hensel meilisearch milli ",
+                "Idaho Bellevue pizza ",
+                "Abraham machin ",
+            ],
+            criterion: Some(&["words"]),
+            bitmap_arena: true,
+            ..BASE_CONF
+        },
+        utils::Conf {
+            group_name: "basic without quote with bitmap arena",
+            queries: &BASE_CONF
+                .queries
+                .iter()
+                .map(|s| s.trim()) // we remove the space at the end of each request
+                .collect::<Vec<&str>>(),
+            bitmap_arena: true,
+            ..BASE_CONF
+        },
         /* the we bench some global / normal search with all the default criterion in the default
          * order */
         utils::Conf {
@@ -1,25 +1,31 @@
 #![allow(dead_code)]
 
-use std::fs::{create_dir_all, remove_dir_all, File};
+use std::fs::{self, create_dir_all, remove_dir_all, File};
 use std::io::{self, BufRead, BufReader, Cursor, Read, Seek};
 use std::num::ParseFloatError;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use criterion::BenchmarkId;
-use milli::documents::{DocumentsBatchBuilder, DocumentsBatchReader};
+use criterion::{BatchSize, BenchmarkId};
+use flate2::read::GzDecoder;
+use milli::documents::{obkv_to_object, DocumentsBatchBuilder, DocumentsBatchReader};
 use milli::heed::EnvOpenOptions;
 use milli::update::{
-    IndexDocuments, IndexDocumentsConfig, IndexDocumentsMethod, IndexerConfig, Settings,
+    DeleteDocuments, IndexDocuments, IndexDocumentsConfig, IndexDocumentsMethod, IndexerConfig,
+    Settings,
 };
 use milli::{Filter, Index, Object, TermsMatchingStrategy};
+use roaring::RoaringBitmap;
 use serde_json::Value;
+use tempfile::TempDir;
 
 pub struct Conf<'a> {
     /// where we are going to create our database.mmdb directory
     /// each benchmark will first try to delete it and then recreate it
     pub database_name: &'a str,
-    /// the dataset to be used, it must be an uncompressed csv
+    /// the dataset to be used, it can be compressed with gzip (`.gz`) or zstd (`.zst`)
     pub dataset: &'a str,
+    /// where to download the dataset from when it is missing, it is cached in the target directory
+    pub dataset_url: Option<&'a str>,
     /// The format of the dataset
     pub dataset_format: &'a str,
     pub group_name: &'a str,
@@ -32,10 +38,23 @@ pub struct Conf<'a> {
     pub configure: fn(&mut Settings),
     pub filter: Option<&'a str>,
     pub sort: Option<Vec<&'a str>>,
+    /// restrict the queries to these searchable attributes
+    pub searchable_attributes: Option<&'a [&'a str]>,
     /// enable or disable the optional words on the query
     pub optional_words: bool,
     /// primary key, if there is None we'll auto-generate docids for every documents
     pub primary_key: Option<&'a str>,
+    /// the maximum size of the words docids cache, there is no cache if None
+    pub docids_cache_max_size: Option<usize>,
+    /// reuse the bitmaps of the ranking rules from one bucket to the next
+    pub bitmap_arena: bool,
+    /// the number of documents ids each query must return, checked once before
+    /// the measurements to make sure a faster search does not return wrong results
+    pub expected_hits: Option<&'a [(&'a str, usize)]>,
+    /// the facets to compute the distribution of, with and without the filter
+    pub facets: Option<Vec<&'a str>>,
+    /// also measure a placeholder search with the filter and the sort
+    pub placeholder_search: bool,
 }
 
 impl Conf<'_> {
@@ -43,14 +62,21 @@ impl Conf<'_> {
         database_name: "benches.mmdb",
         dataset_format: "csv",
         dataset: "",
+        dataset_url: None,
         group_name: "",
         queries: &[],
         criterion: None,
         configure: |_| (),
         filter: None,
         sort: None,
+        searchable_attributes: None,
         optional_words: true,
         primary_key: None,
+        docids_cache_max_size: None,
+        bitmap_arena: false,
+        expected_hits: None,
+        facets: None,
+        placeholder_search: false,
     };
 }
 
@@ -62,10 +88,20 @@ pub fn base_setup(conf: &Conf) -> Index {
     }
     create_dir_all(&conf.database_name).unwrap();
 
+    let index = setup_index(conf, conf.database_name);
+    let documents = documents_from(dataset_path(conf), conf.dataset_format);
+    index_documents(&index, conf, documents);
+
+    index
+}
+
+/// Opens an index in this directory and applies the settings of the configuration.
+fn setup_index(conf: &Conf, path: impl AsRef<Path>) -> Index {
     let mut options = EnvOpenOptions::new();
     options.map_size(100 * 1024 * 1024 * 1024); // 100 GB
     options.max_readers(10);
-    let index = Index::new(options, conf.database_name).unwrap();
+    let mut index = Index::new(options, path).unwrap();
+    index.set_docids_cache_max_size(conf.docids_cache_max_size);
 
     let config = IndexerConfig::default();
     let mut wtxn = index.write_txn().unwrap();
@@ -89,6 +125,10 @@ pub fn base_setup(conf: &Conf) -> Index {
     builder.execute(|_| ()).unwrap();
     wtxn.commit().unwrap();
 
+    index
+}
+
+fn index_documents<R: Read + Seek>(index: &Index, conf: &Conf, documents: DocumentsBatchReader<R>) {
     let config = IndexerConfig::default();
     let mut wtxn = index.write_txn().unwrap();
     let indexing_config = IndexDocumentsConfig {
@@ -96,14 +136,89 @@ pub fn base_setup(conf: &Conf) -> Index {
         update_method: IndexDocumentsMethod::ReplaceDocuments,
         ..Default::default()
     };
-    let builder = IndexDocuments::new(&mut wtxn, &index, &config, indexing_config, |_| ()).unwrap();
-    let documents = documents_from(conf.dataset, conf.dataset_format);
+    let builder =
+        IndexDocuments::new(&mut wtxn, index, &config, indexing_config, |_| (), || false).unwrap();
     let (builder, user_error) = builder.add_documents(documents).unwrap();
     user_error.unwrap();
     builder.execute().unwrap();
     wtxn.commit().unwrap();
+}
 
-    index
+/// Creates an index with the settings of the configuration in a new temporary directory,
+/// the index comes first to be closed before the directory is removed.
+fn temp_index(conf: &Conf) -> (Index, TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let index = setup_index(conf, dir.path());
+    (index, dir)
+}
+
+/// Benchmarks the indexing of the dataset of each configuration: the addition of all the
+/// documents to an empty index, the addition of the last 10% of the documents to an index
+/// containing the first 90% and the deletion of 10% of the documents of the index.
+///
+/// The datasets are parsed once, the settings are applied and the indexes are prepared
+/// outside of the measured section.
+pub fn run_indexing_benches(c: &mut criterion::Criterion, confs: &[Conf]) {
+    for conf in confs {
+        let documents = documents_bytes_from(dataset_path(conf), conf.dataset_format);
+        let (first_documents, last_documents) = split_documents(&documents, 0.9);
+
+        let file_name = Path::new(conf.dataset).file_name().and_then(|f| f.to_str()).unwrap();
+        let name = format!("indexing {}: {}", file_name, conf.group_name);
+        let mut group = c.benchmark_group(&name);
+        group.sample_size(10);
+
+        group.bench_function("addition", |b| {
+            b.iter_batched(
+                || temp_index(conf),
+                |(index, dir)| {
+                    index_documents(&index, conf, documents_reader(&documents));
+                    (index, dir)
+                },
+                BatchSize::PerIteration,
+            )
+        });
+
+        group.bench_function("incremental addition of 10%", |b| {
+            b.iter_batched(
+                || {
+                    let (index, dir) = temp_index(conf);
+                    index_documents(&index, conf, documents_reader(&first_documents));
+                    (index, dir)
+                },
+                |(index, dir)| {
+                    index_documents(&index, conf, documents_reader(&last_documents));
+                    (index, dir)
+                },
+                BatchSize::PerIteration,
+            )
+        });
+
+        group.bench_function("deletion of 10%", |b| {
+            b.iter_batched(
+                || {
+                    let (index, dir) = temp_index(conf);
+                    index_documents(&index, conf, documents_reader(&documents));
+                    let rtxn = index.read_txn().unwrap();
+                    let documents_ids = index.documents_ids(&rtxn).unwrap();
+                    drop(rtxn);
+                    let to_delete: RoaringBitmap = documents_ids.iter().step_by(10).collect();
+                    (index, dir, to_delete)
+                },
+                |(index, dir, to_delete)| {
+                    let mut wtxn = index.write_txn().unwrap();
+                    let mut builder = DeleteDocuments::new(&mut wtxn, &index).unwrap();
+                    builder.delete_documents(&to_delete);
+                    builder.execute().unwrap();
+                    wtxn.commit().unwrap();
+                    (index, dir)
+                },
+                BatchSize::PerIteration,
+            )
+        });
+
+        group.finish();
+    }
 }
 
 pub fn run_benches(c: &mut criterion::Criterion, confs: &[Conf]) {
@@ -112,45 +227,208 @@ pub fn run_benches(c: &mut criterion::Criterion, confs: &[Conf]) {
 
         let file_name = Path::new(conf.dataset).file_name().and_then(|f| f.to_str()).unwrap();
         let name = format!("{}: {}", file_name, conf.group_name);
-        let mut group = c.benchmark_group(&name);
 
-        for &query in conf.queries {
-            group.bench_with_input(BenchmarkId::from_parameter(query), &query, |b, &query| {
+        if let Some(expected_hits) = conf.expected_hits {
+            check_hits(&index, conf, &name, expected_hits);
+        }
+
+        if !conf.queries.is_empty() {
+            let mut group = c.benchmark_group(&name);
+            for &query in conf.queries {
+                group.bench_with_input(BenchmarkId::from_parameter(query), &query, |b, &query| {
+                    b.iter(|| {
+                        let _ids = search_documents_ids(&index, conf, query);
+                    });
+                });
+            }
+            group.finish();
+        }
+
+        if conf.placeholder_search {
+            let mut group = c.benchmark_group(format!("{}: placeholder", name));
+            let parameter = match (conf.filter, &conf.sort) {
+                (Some(_), Some(_)) => "filtered and sorted",
+                (Some(_), None) => "filtered",
+                (None, Some(_)) => "sorted",
+                (None, None) => "all documents",
+            };
+            group.bench_function(parameter, |b| {
                 b.iter(|| {
-                    let rtxn = index.read_txn().unwrap();
-                    let mut search = index.search(&rtxn);
-                    search.query(query).terms_matching_strategy(TermsMatchingStrategy::default());
-                    if let Some(filter) = conf.filter {
-                        let filter = Filter::from_str(filter).unwrap().unwrap();
-                        search.filter(filter);
-                    }
-                    if let Some(sort) = &conf.sort {
-                        let sort = sort.iter().map(|sort| sort.parse().unwrap()).collect();
-                        search.sort_criteria(sort);
-                    }
-                    let _ids = search.execute().unwrap();
+                    let _ids = search_documents_ids(&index, conf, "");
                 });
             });
+            group.finish();
+        }
+
+        if let Some(facets) = &conf.facets {
+            let mut group = c.benchmark_group(format!("{}: facet distribution", name));
+            let rtxn = index.read_txn().unwrap();
+            group.bench_function("without filter", |b| {
+                b.iter(|| {
+                    let mut distribution = index.facets_distribution(&rtxn);
+                    distribution.facets(facets);
+                    distribution.execute().unwrap()
+                });
+            });
+            if let Some(filter) = conf.filter {
+                // the filter is evaluated once, only the distribution is measured
+                let filter = Filter::from_str(filter).unwrap().unwrap();
+                let candidates = filter.evaluate(&rtxn, &index).unwrap();
+                group.bench_function("with filter", |b| {
+                    b.iter(|| {
+                        let mut distribution = index.facets_distribution(&rtxn);
+                        distribution.facets(facets).candidates(candidates.clone());
+                        distribution.execute().unwrap()
+                    });
+                });
+            }
+            group.finish();
         }
-        group.finish();
 
         index.prepare_for_closing().wait();
     }
 }
 
-pub fn documents_from(filename: &str, filetype: &str) -> DocumentsBatchReader<impl BufRead + Seek> {
-    let reader =
-        File::open(filename).expect(&format!("could not find the dataset in: {}", filename));
-    let reader = BufReader::new(reader);
-    let documents = match filetype {
+/// Returns the path of the dataset, the dataset is downloaded from
+/// the `dataset_url` when it is missing and not already cached.
+pub fn dataset_path(conf: &Conf) -> PathBuf {
+    let path = PathBuf::from(conf.dataset);
+    let url = match conf.dataset_url {
+        Some(url) if !path.exists() => url,
+        _ => return path,
+    };
+
+    let cache_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("datasets");
+    let file_name = url.rsplit('/').next().filter(|name| !name.is_empty()).unwrap();
+    let cached_path = cache_dir.join(file_name);
+    if cached_path.exists() {
+        return cached_path;
+    }
+
+    eprintln!("downloading: {}", url);
+    create_dir_all(&cache_dir).unwrap();
+    let mut response = reqwest::blocking::Client::builder()
+        .timeout(None)
+        .build()
+        .unwrap()
+        .get(url)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .unwrap_or_else(|e| panic!("could not download the dataset from {}: {}", url, e));
+
+    // we download into a temporary file to never cache a partially downloaded dataset
+    let partial_path = cached_path.with_extension("partial");
+    let mut file = File::create(&partial_path).unwrap();
+    io::copy(&mut response, &mut file).unwrap();
+    fs::rename(&partial_path, &cached_path).unwrap();
+    eprintln!("{} downloaded successfully in {}", url, cached_path.display());
+
+    cached_path
+}
+
+fn search_documents_ids(index: &Index, conf: &Conf, query: &str) -> Vec<u32> {
+    let rtxn = index.read_txn().unwrap();
+    let mut search = index.search(&rtxn);
+    search.query(query).terms_matching_strategy(TermsMatchingStrategy::default());
+    search.bitmap_arena(conf.bitmap_arena);
+    if let Some(filter) = conf.filter {
+        let filter = Filter::from_str(filter).unwrap().unwrap();
+        search.filter(filter);
+    }
+    if let Some(sort) = &conf.sort {
+        let sort = sort.iter().map(|sort| sort.parse().unwrap()).collect();
+        search.sort_criteria(sort);
+    }
+    if let Some(searchable_attributes) = conf.searchable_attributes {
+        search.searchable_attributes(searchable_attributes);
+    }
+    search.execute().unwrap().documents_ids
+}
+
+/// Returns the number of documents ids returned by each query of the configuration.
+pub fn observed_hits(index: &Index, conf: &Conf) -> Vec<(String, usize)> {
+    conf.queries
+        .iter()
+        .map(|&query| (query.to_string(), search_documents_ids(index, conf, query).len()))
+        .collect()
+}
+
+/// Prints the observed hits in the format of the `expected_hits` field of a configuration,
+/// to update the expectations when the relevancy is changed on purpose.
+pub fn print_observed_hits(index: &Index, conf: &Conf) {
+    eprintln!("expected_hits: Some(&[");
+    for (query, hits) in observed_hits(index, conf) {
+        eprintln!("    ({:?}, {}),", query, hits);
+    }
+    eprintln!("]),");
+}
+
+/// Panics if a query does not return the expected number of documents ids.
+fn check_hits(index: &Index, conf: &Conf, name: &str, expected_hits: &[(&str, usize)]) {
+    let mismatches: Vec<_> = expected_hits
+        .iter()
+        .filter_map(|&(query, expected)| {
+            let hits = search_documents_ids(index, conf, query).len();
+            (hits != expected).then(|| format!("{:?}: expected {}, got {}", query, expected, hits))
+        })
+        .collect();
+
+    if !mismatches.is_empty() {
+        eprintln!("the observed hits of {} are:", name);
+        print_observed_hits(index, conf);
+        panic!("{}: the queries returned unexpected hits:\n{}", name, mismatches.join("\n"));
+    }
+}
+
+pub fn documents_from(
+    filename: impl AsRef<Path>,
+    filetype: &str,
+) -> DocumentsBatchReader<impl BufRead + Seek> {
+    let documents = documents_bytes_from(filename, filetype);
+    DocumentsBatchReader::from_reader(Cursor::new(documents)).unwrap()
+}
+
+/// Reads the documents of a dataset as the bytes of a `DocumentsBatchReader`.
+pub fn documents_bytes_from(filename: impl AsRef<Path>, filetype: &str) -> Vec<u8> {
+    let filename = filename.as_ref();
+    let file = File::open(filename)
+        .unwrap_or_else(|_| panic!("could not find the dataset in: {}", filename.display()));
+    let reader: Box<dyn BufRead> = match filename.extension().and_then(|ext| ext.to_str()) {
+        Some("gz") => Box::new(BufReader::new(GzDecoder::new(file))),
+        Some("zst") => Box::new(BufReader::new(zstd::Decoder::new(file).unwrap())),
+        _ => Box::new(BufReader::new(file)),
+    };
+    match filetype {
         "csv" => documents_from_csv(reader).unwrap(),
         "json" => documents_from_json(reader).unwrap(),
         "jsonl" => documents_from_jsonl(reader).unwrap(),
         otherwise => panic!("invalid update format {:?}", otherwise),
-    };
+    }
+}
+
+pub fn documents_reader(documents: &[u8]) -> DocumentsBatchReader<Cursor<&[u8]>> {
     DocumentsBatchReader::from_reader(Cursor::new(documents)).unwrap()
 }
 
+/// Splits the documents in two batches, the first one contains this ratio of the documents.
+fn split_documents(documents: &[u8], ratio: f64) -> (Vec<u8>, Vec<u8>) {
+    let reader = documents_reader(documents);
+    let split_at = (reader.documents_count() as f64 * ratio) as usize;
+    let (mut cursor, fields_index) = reader.into_cursor_and_fields_index();
+
+    let mut first = DocumentsBatchBuilder::new(Vec::new());
+    let mut last = DocumentsBatchBuilder::new(Vec::new());
+    let mut count = 0;
+    while let Some(document) = cursor.next_document().unwrap() {
+        let object = obkv_to_object(&document, &fields_index).unwrap();
+        let batch = if count < split_at { &mut first } else { &mut last };
+        batch.append_json_object(&object).unwrap();
+        count += 1;
+    }
+
+    (first.into_inner().unwrap(), last.into_inner().unwrap())
+}
+
 fn documents_from_jsonl(reader: impl BufRead) -> anyhow::Result<Vec<u8>> {
     let mut documents = DocumentsBatchBuilder::new(Vec::new());
 
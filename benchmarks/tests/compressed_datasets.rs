@@ -0,0 +1,56 @@
+#[path = "../benches/utils.rs"]
+mod utils;
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+const SONGS: &str = "\
+id,title,duration:number
+1,Space Oddity,315
+2,Life on Mars?,233
+3,Starman,258
+";
+
+fn fixture_path(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("compressed-datasets");
+    fs::create_dir_all(&dir).unwrap();
+    dir.join(name)
+}
+
+#[test]
+fn gzip_datasets_are_uncompressed() {
+    let path = fixture_path("songs.csv.gz");
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(SONGS.as_bytes()).unwrap();
+    fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+    let documents = utils::documents_from(&path, "csv");
+    assert_eq!(documents.documents_count(), 3);
+}
+
+#[test]
+fn zstd_datasets_are_uncompressed() {
+    let path = fixture_path("songs.csv.zst");
+    fs::write(&path, zstd::encode_all(SONGS.as_bytes(), 0).unwrap()).unwrap();
+
+    let documents = utils::documents_from(&path, "csv");
+    assert_eq!(documents.documents_count(), 3);
+}
+
+#[test]
+fn existing_datasets_are_not_downloaded() {
+    let path = fixture_path("songs.csv");
+    fs::write(&path, SONGS).unwrap();
+
+    let conf = utils::Conf {
+        dataset: path.to_str().unwrap(),
+        dataset_url: Some("http://localhost:0/songs.csv"),
+        ..utils::Conf::BASE
+    };
+    assert_eq!(utils::dataset_path(&conf), path);
+    assert_eq!(utils::documents_from(utils::dataset_path(&conf), "csv").documents_count(), 3);
+}
@@ -10,7 +10,8 @@ use eyre::Result;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use milli::documents::{DocumentsBatchBuilder, DocumentsBatchReader};
 use milli::update::UpdateIndexingStep::{
-    ComputeIdsAndMergeDocuments, IndexDocuments, MergeDataIntoFinalDatabase, RemapDocumentAddition,
+    ComputeFacetLevels, ComputeIdsAndMergeDocuments, ComputePrefixDatabases,
+    ExtractDocumentsChunks, IndexDocuments, MergeDataIntoFinalDatabase, RemapDocumentAddition,
 };
 use milli::update::{self, IndexDocumentsConfig, IndexDocumentsMethod, IndexerConfig};
 use milli::{heed, Index, Object};
@@ -249,7 +250,7 @@ impl Performer for DocumentAddition {
         };
         let mut bars = Vec::new();
         let progesses = MultiProgress::new();
-        for _ in 0..4 {
+        for _ in 0..7 {
             let bar = ProgressBar::hidden();
             let bar = progesses.add(bar);
             bars.push(bar);
@@ -260,6 +261,7 @@ impl Performer for DocumentAddition {
             &config,
             indexing_config,
             |step| indexing_callback(step, &bars),
+            || false,
         )
         .unwrap();
         let (addition, user_error) = addition.add_documents(reader)?;
@@ -293,9 +295,12 @@ fn indexing_callback(step: milli::update::UpdateIndexingStep, bars: &[ProgressBa
         .unwrap();
 
     match step {
-        RemapDocumentAddition { documents_seen } => {
+        RemapDocumentAddition { documents_seen, spilled_chunks } => {
             bar.set_style(ProgressStyle::default_spinner());
-            bar.set_message(format!("remapped {} documents so far.", documents_seen));
+            bar.set_message(format!(
+                "remapped {} documents so far, {} ids chunks written on disk.",
+                documents_seen, spilled_chunks
+            ));
         }
         ComputeIdsAndMergeDocuments { documents_seen, total_documents } => {
             bar.set_style(style);
@@ -303,6 +308,12 @@ fn indexing_callback(step: milli::update::UpdateIndexingStep, bars: &[ProgressBa
             bar.set_message("Merging documents...");
             bar.set_position(documents_seen as u64);
         }
+        ExtractDocumentsChunks { chunks_seen, total_chunks } => {
+            bar.set_style(style);
+            bar.set_length(total_chunks as u64);
+            bar.set_message("Extracting documents chunks...");
+            bar.set_position(chunks_seen as u64);
+        }
         IndexDocuments { documents_seen, total_documents } => {
             bar.set_style(style);
             bar.set_length(total_documents as u64);
@@ -315,6 +326,18 @@ fn indexing_callback(step: milli::update::UpdateIndexingStep, bars: &[ProgressBa
             bar.set_message("Merging databases...");
             bar.set_position(databases_seen as u64);
         }
+        ComputeFacetLevels { fields_seen, total_fields } => {
+            bar.set_style(style);
+            bar.set_length(total_fields as u64);
+            bar.set_message("Computing facet levels...");
+            bar.set_position(fields_seen as u64);
+        }
+        ComputePrefixDatabases { databases_seen, total_databases } => {
+            bar.set_style(style);
+            bar.set_length(total_databases as u64);
+            bar.set_message("Computing prefix databases...");
+            bar.set_position(databases_seen as u64);
+        }
     }
     bar.enable_steady_tick(Duration::from_millis(200));
 }
@@ -449,7 +472,7 @@ impl Search {
         let documents = index.documents(&txn, result.documents_ids)?;
         let mut jsons = Vec::new();
         for (_, obkv) in documents {
-            let json = milli::obkv_to_json(&displayed_fields, &fields_ids_map, obkv)?;
+            let json = milli::obkv_to_json(&displayed_fields, &fields_ids_map, obkv.as_obkv())?;
             jsons.push(json);
         }
 
@@ -511,7 +534,7 @@ impl Performer for SettingsUpdate {
 
         let mut bars = Vec::new();
         let progesses = MultiProgress::new();
-        for _ in 0..4 {
+        for _ in 0..7 {
             let bar = ProgressBar::hidden();
             let bar = progesses.add(bar);
             bars.push(bar);
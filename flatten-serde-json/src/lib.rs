@@ -68,6 +68,84 @@ fn insert_value(base_json: &mut Map<String, Value>, key: &str, to_insert: Value)
     }
 }
 
+/// Returns the nested object with only the values selected by the dotted paths, the objects
+/// and arrays on the way to the selected values are kept, the other values are dropped.
+///
+/// A path ending with a `*` selects all the values whose path starts with the rest of it,
+/// `a.*` selects everything under `a` and `a.b*` selects `a.b`, `a.bc` and their content.
+/// The objects in the arrays are selected like the objects they are in, as when flattening.
+/// The paths that select nothing are ignored.
+pub fn select(json: &Map<String, Value>, selectors: &[&str]) -> Map<String, Value> {
+    select_object(json, None, selectors)
+}
+
+fn select_object(
+    object: &Map<String, Value>,
+    base_key: Option<&str>,
+    selectors: &[&str],
+) -> Map<String, Value> {
+    let mut selected = Map::new();
+    for (key, value) in object {
+        let new_key = base_key.map_or_else(|| key.clone(), |base_key| format!("{base_key}.{key}"));
+        if selectors.iter().any(|selector| is_selected_by(&new_key, selector)) {
+            selected.insert(key.clone(), value.clone());
+        } else if selectors.iter().any(|selector| is_parent_of_selected(&new_key, selector)) {
+            let value = match value {
+                Value::Object(object) => {
+                    Value::Object(select_object(object, Some(&new_key), selectors))
+                }
+                Value::Array(array) => Value::Array(select_array(array, &new_key, selectors)),
+                _ => continue,
+            };
+            let is_empty = match &value {
+                Value::Object(object) => object.is_empty(),
+                Value::Array(array) => array.is_empty(),
+                _ => false,
+            };
+            if !is_empty {
+                selected.insert(key.clone(), value);
+            }
+        }
+    }
+    selected
+}
+
+fn select_array(array: &[Value], base_key: &str, selectors: &[&str]) -> Vec<Value> {
+    let mut selected = Vec::new();
+    for value in array {
+        match value {
+            Value::Object(object) => {
+                let object = select_object(object, Some(base_key), selectors);
+                if !object.is_empty() {
+                    selected.push(Value::Object(object));
+                }
+            }
+            Value::Array(array) => {
+                let array = select_array(array, base_key, selectors);
+                if !array.is_empty() {
+                    selected.push(Value::Array(array));
+                }
+            }
+            _ => (),
+        }
+    }
+    selected
+}
+
+/// Returns `true` if the value at this path is entirely selected by the selector.
+fn is_selected_by(key: &str, selector: &str) -> bool {
+    match selector.strip_suffix('*') {
+        Some(prefix) => key.starts_with(prefix),
+        None => key == selector,
+    }
+}
+
+/// Returns `true` if the selector selects some of the values nested under this path.
+fn is_parent_of_selected(key: &str, selector: &str) -> bool {
+    let selector = selector.strip_suffix('*').unwrap_or(selector);
+    selector.starts_with(key) && selector[key.len()..].starts_with('.')
+}
+
 #[cfg(test)]
 mod tests {
     use serde_json::json;
@@ -302,4 +380,79 @@ mod tests {
             .unwrap()
         );
     }
+
+    #[test]
+    fn select_paths() {
+        let mut base: Value = json!({
+          "id": 1,
+          "a": {
+            "b": "c",
+            "d": { "e": "f", "g": "h" }
+          },
+          "a.i": "j",
+          "k": [
+            { "l": "m", "n": "o" },
+            { "n": "p" },
+            "q",
+            [{ "l": "r" }]
+          ]
+        });
+        let json = std::mem::take(base.as_object_mut().unwrap());
+
+        assert_eq!(
+            &select(&json, &["id", "a.d.e"]),
+            json!({ "id": 1, "a": { "d": { "e": "f" } } }).as_object().unwrap()
+        );
+        // the paths can be in the keys themselves
+        assert_eq!(&select(&json, &["a.i"]), json!({ "a.i": "j" }).as_object().unwrap());
+        // the objects in arrays are selected
+        assert_eq!(
+            &select(&json, &["k.l"]),
+            json!({ "k": [{ "l": "m" }, [{ "l": "r" }]] }).as_object().unwrap()
+        );
+        // the missing paths are ignored
+        assert_eq!(&select(&json, &["id", "a.z", "z"]), json!({ "id": 1 }).as_object().unwrap());
+        assert!(select(&json, &["id.z", "a.b.z"]).is_empty());
+    }
+
+    #[test]
+    fn select_overlapping_paths() {
+        let mut base: Value = json!({
+          "a": { "b": "c", "d": "e" },
+          "f": 1
+        });
+        let json = std::mem::take(base.as_object_mut().unwrap());
+
+        let expected = json!({ "a": { "b": "c", "d": "e" } });
+        assert_eq!(&select(&json, &["a", "a.b"]), expected.as_object().unwrap());
+        assert_eq!(&select(&json, &["a.b", "a"]), expected.as_object().unwrap());
+        assert_eq!(
+            &select(&json, &["a.b", "a.b"]),
+            json!({ "a": { "b": "c" } }).as_object().unwrap()
+        );
+    }
+
+    #[test]
+    fn select_wildcard_paths() {
+        let mut base: Value = json!({
+          "a": { "b": "c", "bd": "e", "f": "g" },
+          "ab": 1,
+          "h": 2
+        });
+        let json = std::mem::take(base.as_object_mut().unwrap());
+
+        assert_eq!(&select(&json, &["*"]), &json);
+        assert_eq!(
+            &select(&json, &["a.*"]),
+            json!({ "a": { "b": "c", "bd": "e", "f": "g" } }).as_object().unwrap()
+        );
+        assert_eq!(
+            &select(&json, &["a.b*"]),
+            json!({ "a": { "b": "c", "bd": "e" } }).as_object().unwrap()
+        );
+        assert_eq!(
+            &select(&json, &["a*"]),
+            json!({ "a": { "b": "c", "bd": "e", "f": "g" }, "ab": 1 }).as_object().unwrap()
+        );
+    }
 }
@@ -7,7 +7,10 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::error::is_reserved_keyword;
-use crate::{CriterionError, Error, UserError};
+use crate::{CriterionError, Error, Locale, UserError};
+
+/// The maximum number of points a `_geoPoint` sort can measure the distance from.
+pub const MAX_GEO_POINTS: usize = 10;
 
 /// This error type is never supposed to be shown to the end user.
 /// You must always cast it to a sort error or a criterion error.
@@ -15,8 +18,11 @@ use crate::{CriterionError, Error, UserError};
 pub enum AscDescError {
     InvalidLatitude,
     InvalidLongitude,
+    InvalidGeoCoordinate { coordinate: String },
+    TooManyGeoPoints,
     InvalidSyntax { name: String },
     ReservedKeyword { name: String },
+    InvalidLocale { name: String, locale: String },
 }
 
 impl fmt::Display for AscDescError {
@@ -28,6 +34,16 @@ impl fmt::Display for AscDescError {
             Self::InvalidLongitude => {
                 write!(f, "Longitude must be contained between -180 and 180 degrees.",)
             }
+            Self::InvalidGeoCoordinate { coordinate } => {
+                write!(
+                    f,
+                    "Invalid coordinate `{}`: expected `[latitude, longitude]` with a latitude contained between -90 and 90 degrees and a longitude contained between -180 and 180 degrees.",
+                    coordinate
+                )
+            }
+            Self::TooManyGeoPoints => {
+                write!(f, "A `_geoPoint` rule can't contain more than {} points.", MAX_GEO_POINTS)
+            }
             Self::InvalidSyntax { name } => {
                 write!(f, "Invalid syntax for the asc/desc parameter: expected expression ending by `:asc` or `:desc`, found `{}`.", name)
             }
@@ -38,6 +54,13 @@ impl fmt::Display for AscDescError {
                     name
                 )
             }
+            Self::InvalidLocale { name, locale } => {
+                write!(
+                    f,
+                    "Invalid locale `{}` in `{}`: expected one of `de`, `fr`, only a field can be sorted with a locale.",
+                    locale, name
+                )
+            }
         }
     }
 }
@@ -45,16 +68,24 @@ impl fmt::Display for AscDescError {
 impl From<AscDescError> for CriterionError {
     fn from(error: AscDescError) -> Self {
         match error {
-            AscDescError::InvalidLatitude | AscDescError::InvalidLongitude => {
+            AscDescError::InvalidLatitude
+            | AscDescError::InvalidLongitude
+            | AscDescError::InvalidGeoCoordinate { .. }
+            | AscDescError::TooManyGeoPoints => {
                 CriterionError::ReservedNameForSort { name: "_geoPoint".to_string() }
             }
-            AscDescError::InvalidSyntax { name } => CriterionError::InvalidName { name },
+            AscDescError::InvalidSyntax { name } | AscDescError::InvalidLocale { name, .. } => {
+                CriterionError::InvalidName { name }
+            }
             AscDescError::ReservedKeyword { name } if name.starts_with("_geoPoint") => {
                 CriterionError::ReservedNameForSort { name: "_geoPoint".to_string() }
             }
             AscDescError::ReservedKeyword { name } if name.starts_with("_geoRadius") => {
                 CriterionError::ReservedNameForFilter { name: "_geoRadius".to_string() }
             }
+            AscDescError::ReservedKeyword { name } if name.starts_with("_geoBoundingBox") => {
+                CriterionError::ReservedNameForFilter { name: "_geoBoundingBox".to_string() }
+            }
             AscDescError::ReservedKeyword { name } => CriterionError::ReservedName { name },
         }
     }
@@ -63,14 +94,39 @@ impl From<AscDescError> for CriterionError {
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum Member {
     Field(String),
+    /// A field whose string values are ordered by the collation of the locale.
+    CollatedField(String, Locale),
     Geo([f64; 2]),
+    /// Sorts by the distance to the nearest of these points.
+    GeoPoints(Vec<[f64; 2]>),
+    /// Sorts in a random order, always the same for the same seed.
+    Random(u64),
 }
 
 impl FromStr for Member {
     type Err = AscDescError;
 
     fn from_str(text: &str) -> Result<Member, Self::Err> {
+        if let Some(seed) = text.strip_prefix("_random(").and_then(|text| text.strip_suffix(')')) {
+            return seed
+                .trim()
+                .parse()
+                .map(Member::Random)
+                .map_err(|_| AscDescError::ReservedKeyword { name: text.to_string() });
+        }
+
         match text.strip_prefix("_geoPoint(").and_then(|text| text.strip_suffix(")")) {
+            Some(points) if points.trim_start().starts_with('[') => {
+                let mut points = parse_geo_points(points)
+                    .ok_or_else(|| AscDescError::ReservedKeyword { name: text.to_string() })??;
+                if points.len() > MAX_GEO_POINTS {
+                    return Err(AscDescError::TooManyGeoPoints);
+                }
+                match points.len() {
+                    1 => Ok(Member::Geo(points.pop().unwrap())),
+                    _ => Ok(Member::GeoPoints(points)),
+                }
+            }
             Some(point) => {
                 let (lat, lng) = point
                     .split_once(',')
@@ -89,7 +145,11 @@ impl FromStr for Member {
                 Ok(Member::Geo([lat, lng]))
             }
             None => {
-                if is_reserved_keyword(text) || text.starts_with("_geoRadius(") {
+                if is_reserved_keyword(text)
+                    || text == "_random"
+                    || text.starts_with("_geoRadius(")
+                    || text.starts_with("_geoBoundingBox(")
+                {
                     return Err(AscDescError::ReservedKeyword { name: text.to_string() })?;
                 }
                 Ok(Member::Field(text.to_string()))
@@ -98,11 +158,53 @@ impl FromStr for Member {
     }
 }
 
+/// Parses a comma separated list of `[lat, lng]` coordinates.
+///
+/// Returns `None` if the list itself is malformed and an error naming the coordinate
+/// if one of the coordinates is invalid.
+fn parse_geo_points(text: &str) -> Option<Result<Vec<[f64; 2]>, AscDescError>> {
+    let mut points = Vec::new();
+    let mut text = text.trim();
+    loop {
+        let (coordinate, rest) = text.strip_prefix('[')?.split_once(']')?;
+        match parse_geo_coordinate(coordinate) {
+            Some(point) => points.push(point),
+            None => {
+                let coordinate = format!("[{}]", coordinate);
+                return Some(Err(AscDescError::InvalidGeoCoordinate { coordinate }));
+            }
+        }
+
+        let rest = rest.trim_start();
+        if rest.is_empty() {
+            return Some(Ok(points));
+        }
+        text = rest.strip_prefix(',')?.trim_start();
+    }
+}
+
+fn parse_geo_coordinate(coordinate: &str) -> Option<[f64; 2]> {
+    let (lat, lng) = coordinate.split_once(',')?;
+    let lat: f64 = lat.trim().parse().ok()?;
+    let lng: f64 = lng.trim().parse().ok()?;
+    if (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lng) {
+        Some([lat, lng])
+    } else {
+        None
+    }
+}
+
 impl fmt::Display for Member {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Member::Field(name) => f.write_str(name),
+            Member::Field(name) | Member::CollatedField(name, _) => f.write_str(name),
             Member::Geo([lat, lng]) => write!(f, "_geoPoint({}, {})", lat, lng),
+            Member::GeoPoints(points) => {
+                let points: Vec<_> =
+                    points.iter().map(|[lat, lng]| format!("[{}, {}]", lat, lng)).collect();
+                write!(f, "_geoPoint({})", points.join(", "))
+            }
+            Member::Random(seed) => write!(f, "_random({})", seed),
         }
     }
 }
@@ -110,15 +212,35 @@ impl fmt::Display for Member {
 impl Member {
     pub fn field(&self) -> Option<&str> {
         match self {
-            Member::Field(field) => Some(field),
-            Member::Geo(_) => None,
+            Member::Field(field) | Member::CollatedField(field, _) => Some(field),
+            Member::Geo(_) | Member::GeoPoints(_) | Member::Random(_) => None,
+        }
+    }
+
+    /// The locale of the collation the string values are ordered by, if any.
+    pub fn locale(&self) -> Option<Locale> {
+        match self {
+            Member::CollatedField(_, locale) => Some(*locale),
+            Member::Field(_) | Member::Geo(_) | Member::GeoPoints(_) | Member::Random(_) => None,
         }
     }
 
     pub fn geo_point(&self) -> Option<&[f64; 2]> {
         match self {
             Member::Geo(point) => Some(point),
-            Member::Field(_) => None,
+            Member::Field(_)
+            | Member::CollatedField(..)
+            | Member::GeoPoints(_)
+            | Member::Random(_) => None,
+        }
+    }
+
+    /// The points the distance of the documents is measured from, if this is a geo member.
+    pub fn geo_points(&self) -> Option<&[[f64; 2]]> {
+        match self {
+            Member::Geo(point) => Some(std::slice::from_ref(point)),
+            Member::GeoPoints(points) => Some(points),
+            Member::Field(_) | Member::CollatedField(..) | Member::Random(_) => None,
         }
     }
 }
@@ -146,6 +268,26 @@ impl FromStr for AscDesc {
     type Err = AscDescError;
 
     fn from_str(text: &str) -> Result<AscDesc, Self::Err> {
+        // The locale of the collation follows the order, e.g. `country:asc@de`.
+        if let Some((sort, locale)) = text.rsplit_once('@') {
+            if let Some((_, "asc" | "desc")) = sort.rsplit_once(':') {
+                let invalid_locale = || AscDescError::InvalidLocale {
+                    name: text.to_string(),
+                    locale: locale.to_string(),
+                };
+                let locale = locale.parse().map_err(|()| invalid_locale())?;
+                return match sort.parse()? {
+                    AscDesc::Asc(Member::Field(field)) => {
+                        Ok(AscDesc::Asc(Member::CollatedField(field, locale)))
+                    }
+                    AscDesc::Desc(Member::Field(field)) => {
+                        Ok(AscDesc::Desc(Member::CollatedField(field, locale)))
+                    }
+                    _ => Err(invalid_locale()),
+                };
+            }
+        }
+
         match text.rsplit_once(':') {
             Some((left, "asc")) => Ok(AscDesc::Asc(left.parse()?)),
             Some((left, "desc")) => Ok(AscDesc::Desc(left.parse()?)),
@@ -160,11 +302,17 @@ pub enum SortError {
     InvalidLatitude,
     #[error("{}", AscDescError::InvalidLongitude)]
     InvalidLongitude,
+    #[error("{}", AscDescError::InvalidGeoCoordinate { coordinate: coordinate.clone() })]
+    InvalidGeoCoordinate { coordinate: String },
+    #[error("{}", AscDescError::TooManyGeoPoints)]
+    TooManyGeoPoints,
     #[error("Invalid syntax for the geo parameter: expected expression formated like \
                     `_geoPoint(latitude, longitude)` and ending by `:asc` or `:desc`, found `{name}`.")]
     BadGeoPointUsage { name: String },
     #[error("Invalid syntax for the sort parameter: expected expression ending by `:asc` or `:desc`, found `{name}`.")]
     InvalidName { name: String },
+    #[error("{}", AscDescError::InvalidLocale { name: name.clone(), locale: locale.clone() })]
+    InvalidLocale { name: String, locale: String },
     #[error("`{name}` is a reserved keyword and thus can't be used as a sort expression.")]
     ReservedName { name: String },
     #[error("`{name}` is a reserved keyword and thus can't be used as a sort expression. \
@@ -180,7 +328,14 @@ impl From<AscDescError> for SortError {
         match error {
             AscDescError::InvalidLatitude => SortError::InvalidLatitude,
             AscDescError::InvalidLongitude => SortError::InvalidLongitude,
+            AscDescError::InvalidGeoCoordinate { coordinate } => {
+                SortError::InvalidGeoCoordinate { coordinate }
+            }
+            AscDescError::TooManyGeoPoints => SortError::TooManyGeoPoints,
             AscDescError::InvalidSyntax { name } => SortError::InvalidName { name },
+            AscDescError::InvalidLocale { name, locale } => {
+                SortError::InvalidLocale { name, locale }
+            }
             AscDescError::ReservedKeyword { name } if name.starts_with("_geoPoint") => {
                 SortError::BadGeoPointUsage { name }
             }
@@ -190,6 +345,9 @@ impl From<AscDescError> for SortError {
             AscDescError::ReservedKeyword { name } if name.starts_with("_geoRadius") => {
                 SortError::ReservedNameForFilter { name: String::from("_geoRadius") }
             }
+            AscDescError::ReservedKeyword { name } if name.starts_with("_geoBoundingBox") => {
+                SortError::ReservedNameForFilter { name: String::from("_geoBoundingBox") }
+            }
             AscDescError::ReservedKeyword { name } => SortError::ReservedName { name },
         }
     }
@@ -228,6 +386,15 @@ mod tests {
             ("_geoPoint(42.0002, 59.895):desc", Desc(Geo([42.0002, 59.895]))),
             ("_geoPoint(42., 59.):desc", Desc(Geo([42., 59.]))),
             ("truc(12, 13):desc", Desc(Field(S("truc(12, 13)")))),
+            ("_geoPoint([42, 59]):asc", Asc(Geo([42., 59.]))),
+            ("_geoPoint([42, 59], [-12.5, 3]):asc", Asc(GeoPoints(vec![[42., 59.], [-12.5, 3.]]))),
+            ("_geoPoint( [42,59] ,[0, 0] ):desc", Desc(GeoPoints(vec![[42., 59.], [0., 0.]]))),
+            ("country:asc@de", Asc(CollatedField(S("country"), Locale::De))),
+            ("country:desc@fr", Desc(CollatedField(S("country"), Locale::Fr))),
+            ("a@b:asc@fr", Asc(CollatedField(S("a@b"), Locale::Fr))),
+            ("e@mail:asc", Asc(Field(S("e@mail")))),
+            ("_random(42):asc", Asc(Random(42))),
+            ("_random( 0 ):desc", Desc(Random(0))),
         ];
 
         for (req, expected) in valid_req {
@@ -273,6 +440,28 @@ mod tests {
             ("_geoPoint(0, -180.000001):desc", InvalidLongitude),
             ("_geoPoint(159.256, 130):asc", InvalidLatitude),
             ("_geoPoint(12, -2021):desc", InvalidLongitude),
+            ("_geoPoint([42, 59], [12]):asc", InvalidGeoCoordinate { coordinate: S("[12]") }),
+            (
+                "_geoPoint([42, 59], [91, 0]):asc",
+                InvalidGeoCoordinate { coordinate: S("[91, 0]") },
+            ),
+            ("_geoPoint([a, 0], [0, 0]):asc", InvalidGeoCoordinate { coordinate: S("[a, 0]") }),
+            ("_geoPoint([42, 59] [0, 0]):asc", ReservedKeyword { name: S("_geoPoint([42, 59] [0, 0])") }),
+            ("_geoPoint([42, 59],):asc", ReservedKeyword { name: S("_geoPoint([42, 59],)") }),
+            (
+                "_geoPoint([0, 0], [0, 1], [0, 2], [0, 3], [0, 4], [0, 5], [0, 6], [0, 7], [0, 8], [0, 9], [0, 10]):asc",
+                TooManyGeoPoints,
+            ),
+            ("country:asc@en", InvalidLocale { name: S("country:asc@en"), locale: S("en") }),
+            ("country:asc@", InvalidLocale { name: S("country:asc@"), locale: S("") }),
+            (
+                "_geoPoint(42, 59):asc@de",
+                InvalidLocale { name: S("_geoPoint(42, 59):asc@de"), locale: S("de") },
+            ),
+            ("country:machin@de", InvalidSyntax { name: S("country:machin@de") }),
+            ("_random:asc", ReservedKeyword { name: S("_random") }),
+            ("_random(-1):asc", ReservedKeyword { name: S("_random(-1)") }),
+            ("_random(seed):desc", ReservedKeyword { name: S("_random(seed)") }),
         ];
 
         for (req, expected_error) in invalid_req {
@@ -0,0 +1,268 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// The locales whose collation can order the string facet values of a sort, e.g. `country:asc@de`.
+///
+/// The strings are compared on their letters first, ignoring the accents and the case, then on
+/// their accents and then on their case. The accents are compared from the end of the strings
+/// in French, so that `côte` comes before `coté`, and from their start in German.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    De,
+    Fr,
+}
+
+impl Locale {
+    /// Returns the key that orders the strings as the collation of this locale.
+    pub fn sort_key(&self, text: &str) -> CollationKey {
+        let mut letters = Vec::with_capacity(text.len());
+        let mut accents = Vec::with_capacity(text.len());
+        let mut cases = Vec::with_capacity(text.len());
+        for c in text.chars() {
+            let case = c.is_uppercase() as u8;
+            for lower in c.to_lowercase() {
+                match fold(lower) {
+                    Some((base, accent)) => {
+                        for base in base.chars() {
+                            letters.push(base);
+                            accents.push(accent);
+                            cases.push(case);
+                        }
+                    }
+                    None => {
+                        letters.push(lower);
+                        accents.push(NONE);
+                        cases.push(case);
+                    }
+                }
+            }
+        }
+
+        if *self == Locale::Fr {
+            accents.reverse();
+        }
+
+        CollationKey { letters, accents, cases, text: text.to_string() }
+    }
+
+    pub fn compare(&self, left: &str, right: &str) -> Ordering {
+        self.sort_key(left).cmp(&self.sort_key(right))
+    }
+}
+
+/// Returns the letter a string starting with this char is first compared on by the collation,
+/// the strings starting with chars of different primary letters are ordered by these letters.
+pub fn primary_letter(c: char) -> char {
+    let lower = c.to_lowercase().next().unwrap_or(c);
+    match fold(lower) {
+        Some((base, _)) => base.chars().next().unwrap_or(lower),
+        None => lower,
+    }
+}
+
+/// Removes the diacritics of the Latin letters of a lowercase text, the ligatures are kept.
+pub(crate) fn remove_diacritics(text: &str) -> String {
+    text.chars()
+        .map(|c| match fold(c) {
+            Some((base, accent)) if accent != LIGATURE => base.chars().next().unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+impl FromStr for Locale {
+    type Err = ();
+
+    fn from_str(text: &str) -> Result<Locale, Self::Err> {
+        match text {
+            "de" => Ok(Locale::De),
+            "fr" => Ok(Locale::Fr),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Locale::De => f.write_str("de"),
+            Locale::Fr => f.write_str("fr"),
+        }
+    }
+}
+
+/// The collation key of a string, see [`Locale::sort_key`].
+///
+/// The strings that are equal for the collation are ordered by their bytes.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CollationKey {
+    letters: Vec<char>,
+    accents: Vec<u8>,
+    cases: Vec<u8>,
+    text: String,
+}
+
+const NONE: u8 = 0;
+const ACUTE: u8 = 1;
+const GRAVE: u8 = 2;
+const BREVE: u8 = 3;
+const CIRCUMFLEX: u8 = 4;
+const CARON: u8 = 5;
+const RING: u8 = 6;
+const DIAERESIS: u8 = 7;
+const DOUBLE_ACUTE: u8 = 8;
+const TILDE: u8 = 9;
+const DOT: u8 = 10;
+const CEDILLA: u8 = 11;
+const OGONEK: u8 = 12;
+const MACRON: u8 = 13;
+const STROKE: u8 = 14;
+const LIGATURE: u8 = 15;
+
+/// Returns the base letters of a lowercase char along with its accent, if it has one.
+///
+/// This is not a full collation table like the one of the Unicode Collation Algorithm: it only
+/// knows the Latin letters with diacritics, which is enough for the German and French locales.
+/// The other chars, e.g. the Greek or Cyrillic letters with diacritics, are their own base letter
+/// and are ordered by their code point.
+fn fold(c: char) -> Option<(&'static str, u8)> {
+    let folded = match c {
+        'á' => ("a", ACUTE),
+        'à' => ("a", GRAVE),
+        'ă' => ("a", BREVE),
+        'â' => ("a", CIRCUMFLEX),
+        'ǎ' => ("a", CARON),
+        'å' => ("a", RING),
+        'ä' => ("a", DIAERESIS),
+        'ã' => ("a", TILDE),
+        'ą' => ("a", OGONEK),
+        'ā' => ("a", MACRON),
+        'æ' => ("ae", LIGATURE),
+        'ć' => ("c", ACUTE),
+        'ĉ' => ("c", CIRCUMFLEX),
+        'č' => ("c", CARON),
+        'ċ' => ("c", DOT),
+        'ç' => ("c", CEDILLA),
+        'ď' => ("d", CARON),
+        'đ' => ("d", STROKE),
+        'é' => ("e", ACUTE),
+        'è' => ("e", GRAVE),
+        'ê' => ("e", CIRCUMFLEX),
+        'ě' => ("e", CARON),
+        'ë' => ("e", DIAERESIS),
+        'ė' => ("e", DOT),
+        'ę' => ("e", OGONEK),
+        'ē' => ("e", MACRON),
+        'ğ' => ("g", BREVE),
+        'ĝ' => ("g", CIRCUMFLEX),
+        'ġ' => ("g", DOT),
+        'ģ' => ("g", CEDILLA),
+        'ĥ' => ("h", CIRCUMFLEX),
+        'ħ' => ("h", STROKE),
+        'í' => ("i", ACUTE),
+        'ì' => ("i", GRAVE),
+        'î' => ("i", CIRCUMFLEX),
+        'ï' => ("i", DIAERESIS),
+        'į' => ("i", OGONEK),
+        'ī' => ("i", MACRON),
+        'ĵ' => ("j", CIRCUMFLEX),
+        'ķ' => ("k", CEDILLA),
+        'ĺ' => ("l", ACUTE),
+        'ļ' => ("l", CEDILLA),
+        'ł' => ("l", STROKE),
+        'ń' => ("n", ACUTE),
+        'ň' => ("n", CARON),
+        'ñ' => ("n", TILDE),
+        'ņ' => ("n", CEDILLA),
+        'ó' => ("o", ACUTE),
+        'ò' => ("o", GRAVE),
+        'ô' => ("o", CIRCUMFLEX),
+        'ö' => ("o", DIAERESIS),
+        'ő' => ("o", DOUBLE_ACUTE),
+        'õ' => ("o", TILDE),
+        'ō' => ("o", MACRON),
+        'ø' => ("o", STROKE),
+        'œ' => ("oe", LIGATURE),
+        'ŕ' => ("r", ACUTE),
+        'ř' => ("r", CARON),
+        'ś' => ("s", ACUTE),
+        'ŝ' => ("s", CIRCUMFLEX),
+        'š' => ("s", CARON),
+        'ş' => ("s", CEDILLA),
+        'ß' => ("ss", LIGATURE),
+        'ť' => ("t", CARON),
+        'ţ' => ("t", CEDILLA),
+        'ú' => ("u", ACUTE),
+        'ù' => ("u", GRAVE),
+        'ŭ' => ("u", BREVE),
+        'û' => ("u", CIRCUMFLEX),
+        'ů' => ("u", RING),
+        'ü' => ("u", DIAERESIS),
+        'ű' => ("u", DOUBLE_ACUTE),
+        'ų' => ("u", OGONEK),
+        'ū' => ("u", MACRON),
+        'ŵ' => ("w", CIRCUMFLEX),
+        'ý' => ("y", ACUTE),
+        'ŷ' => ("y", CIRCUMFLEX),
+        'ÿ' => ("y", DIAERESIS),
+        'ź' => ("z", ACUTE),
+        'ž' => ("z", CARON),
+        'ż' => ("z", DOT),
+        _ => return None,
+    };
+    Some(folded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(locale: Locale, words: &[&str]) -> Vec<String> {
+        let mut words: Vec<_> = words.iter().map(|word| word.to_string()).collect();
+        words.sort_by(|a, b| locale.compare(a, b));
+        words
+    }
+
+    #[test]
+    fn accents_and_case_are_secondary() {
+        let words = ["Zimbabwe", "Österreich", "Ostfriesland", "Oman", "Ägypten", "Albanien"];
+        let expected = ["Ägypten", "Albanien", "Oman", "Österreich", "Ostfriesland", "Zimbabwe"];
+        assert_eq!(sorted(Locale::De, &words), expected);
+        assert_eq!(sorted(Locale::Fr, &words), expected);
+
+        assert_eq!(
+            sorted(Locale::De, &["Straße", "Strasse", "Strand"]),
+            ["Strand", "Strasse", "Straße"]
+        );
+        assert_eq!(sorted(Locale::De, &["apfel", "Apfel", "äpfel"]), ["apfel", "Apfel", "äpfel"]);
+        assert_eq!(sorted(Locale::De, &["ж", "б", "a"]), ["a", "б", "ж"]);
+    }
+
+    #[test]
+    fn primary_letters() {
+        assert_eq!(primary_letter('a'), 'a');
+        assert_eq!(primary_letter('Ä'), 'a');
+        assert_eq!(primary_letter('æ'), 'a');
+        assert_eq!(primary_letter('ß'), 's');
+        assert_eq!(primary_letter('Ж'), 'ж');
+    }
+
+    #[test]
+    fn diacritics_are_removed() {
+        assert_eq!(remove_diacritics("éléphant"), "elephant");
+        assert_eq!(remove_diacritics("łódź"), "lodz");
+        assert_eq!(remove_diacritics("cœur straße"), "cœur straße");
+        assert_eq!(remove_diacritics("жё"), "жё");
+    }
+
+    #[test]
+    fn french_accents_are_compared_backwards() {
+        let words = ["côté", "coté", "côte", "cote"];
+        assert_eq!(sorted(Locale::De, &words), ["cote", "coté", "côte", "côté"]);
+        assert_eq!(sorted(Locale::Fr, &words), ["cote", "côte", "coté", "côté"]);
+    }
+}
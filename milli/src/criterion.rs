@@ -71,9 +71,18 @@ impl FromStr for Criterion {
             text => match AscDesc::from_str(text)? {
                 AscDesc::Asc(Member::Field(field)) => Ok(Criterion::Asc(field)),
                 AscDesc::Desc(Member::Field(field)) => Ok(Criterion::Desc(field)),
-                AscDesc::Asc(Member::Geo(_)) | AscDesc::Desc(Member::Geo(_)) => {
+                // The locales are only given to the sorts of the search requests.
+                AscDesc::Asc(Member::CollatedField(..))
+                | AscDesc::Desc(Member::CollatedField(..)) => {
+                    Err(CriterionError::InvalidName { name: text.to_string() })
+                }
+                AscDesc::Asc(Member::Geo(_) | Member::GeoPoints(_))
+                | AscDesc::Desc(Member::Geo(_) | Member::GeoPoints(_)) => {
                     Err(CriterionError::ReservedNameForSort { name: "_geoPoint".to_string() })?
                 }
+                AscDesc::Asc(Member::Random(_)) | AscDesc::Desc(Member::Random(_)) => {
+                    Err(CriterionError::ReservedNameForSort { name: "_random".to_string() })?
+                }
             },
         }
     }
@@ -157,8 +166,14 @@ mod tests {
             ("_geoDistance:asc", ReservedName { name: S("_geoDistance") }),
             ("_geoPoint:asc", ReservedNameForSort { name: S("_geoPoint") }),
             ("_geoPoint(42, 75):asc", ReservedNameForSort { name: S("_geoPoint") }),
+            ("_random(42):asc", ReservedNameForSort { name: S("_random") }),
             ("_geoRadius:asc", ReservedNameForFilter { name: S("_geoRadius") }),
             ("_geoRadius(42, 75, 59):asc", ReservedNameForFilter { name: S("_geoRadius") }),
+            ("_geoBoundingBox:asc", ReservedNameForFilter { name: S("_geoBoundingBox") }),
+            (
+                "_geoBoundingBox([42, 75], [59, 80]):asc",
+                ReservedNameForFilter { name: S("_geoBoundingBox") },
+            ),
         ];
 
         for (input, expected) in invalid_criteria {
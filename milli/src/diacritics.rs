@@ -0,0 +1,48 @@
+use std::borrow::Cow;
+
+use charabia::{Token, TokenKind};
+
+use crate::collation::remove_diacritics;
+
+/// Gives back their diacritics to the words of the text, the tokenizer removes them from the
+/// Latin letters. It is used by the indexes that count a difference of diacritics as a typo.
+///
+/// A word is only changed when its lemma is its lowercased original without the diacritics,
+/// the other normalizations of the tokenizer, like the ones of the ligatures, are kept.
+pub fn restore_diacritics<'o>(text: &str, tokens: Vec<Token<'o>>) -> Vec<Token<'o>> {
+    tokens
+        .into_iter()
+        .map(|mut token| {
+            if token.kind == TokenKind::Word {
+                if let Some(original) = text.get(token.byte_start..token.byte_end) {
+                    let original = original.to_lowercase();
+                    if original != token.lemma() && remove_diacritics(&original) == token.lemma() {
+                        token.lemma = Cow::Owned(original);
+                    }
+                }
+            }
+            token
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use charabia::Tokenize;
+
+    use super::*;
+
+    fn words(text: &str) -> Vec<String> {
+        restore_diacritics(text, text.tokenize().collect())
+            .into_iter()
+            .filter(|token| token.is_word())
+            .map(|token| token.lemma().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn diacritics_are_restored() {
+        assert_eq!(words("Un Éléphant à Łódź"), ["un", "éléphant", "à", "łódź"]);
+        assert_eq!(words("the elephant"), ["the", "elephant"]);
+    }
+}
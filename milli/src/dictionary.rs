@@ -0,0 +1,153 @@
+use std::borrow::Cow;
+
+use charabia::{Token, TokenKind};
+
+/// The sequences of characters that the tokenizer must keep as single words,
+/// like `C++` or `C#` that would otherwise be split on their separators.
+///
+/// The tokens that a sequence entirely covers, from the start of the first one to the end
+/// of the last one, are merged back into a single word whose lemma is the lowercased sequence.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dictionary {
+    /// The lowercased sequences, the longest first.
+    words: Vec<String>,
+}
+
+impl Dictionary {
+    pub fn new<I, S>(words: I) -> Dictionary
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut words: Vec<_> = words
+            .into_iter()
+            .map(|word| word.as_ref().trim().to_lowercase())
+            .filter(|word| !word.is_empty())
+            .collect();
+        words.sort_unstable_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        words.dedup();
+        Dictionary { words }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Merges the tokens of the text that are covered by a sequence of the dictionary.
+    pub fn merge_tokens<'o>(
+        &self,
+        text: &'o str,
+        tokens: impl Iterator<Item = Token<'o>>,
+    ) -> Vec<Token<'o>> {
+        let tokens: Vec<_> = tokens.collect();
+        if self.words.is_empty() {
+            return tokens;
+        }
+
+        // The merged tokens along with the index of their first token and their number of tokens.
+        let mut merged = Vec::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            match self.longest_match(text, &tokens[i..]) {
+                Some((word, count)) => {
+                    let (first, last) = (&tokens[i], &tokens[i + count - 1]);
+                    let token = Token {
+                        kind: TokenKind::Word,
+                        lemma: Cow::Owned(word.to_string()),
+                        char_start: first.char_start,
+                        char_end: last.char_end,
+                        byte_start: first.byte_start,
+                        byte_end: last.byte_end,
+                        script: first.script,
+                        language: first.language,
+                        ..Default::default()
+                    };
+                    merged.push((i, count, token));
+                    i += count;
+                }
+                None => i += 1,
+            }
+        }
+
+        if merged.is_empty() {
+            return tokens;
+        }
+
+        let mut output = Vec::with_capacity(tokens.len());
+        let mut merged = merged.into_iter().peekable();
+        let mut skip = 0;
+        for (i, token) in tokens.into_iter().enumerate() {
+            if skip > 0 {
+                skip -= 1;
+            } else if merged.peek().map_or(false, |(start, _, _)| *start == i) {
+                let (_, count, token) = merged.next().unwrap();
+                skip = count - 1;
+                output.push(token);
+            } else {
+                output.push(token);
+            }
+        }
+
+        output
+    }
+
+    /// Returns the longest sequence that starts at the first token and ends at the end of
+    /// one of the tokens, along with the number of tokens it covers.
+    fn longest_match(&self, text: &str, tokens: &[Token]) -> Option<(&str, usize)> {
+        let start = tokens[0].byte_start;
+        let rest = text.get(start..)?;
+        let first_char = rest.chars().next()?.to_lowercase().next()?;
+
+        for word in &self.words {
+            if !word.starts_with(first_char) {
+                continue;
+            }
+
+            let candidate = match rest.get(..word.len()) {
+                Some(candidate) => candidate,
+                None => continue,
+            };
+            if candidate.to_lowercase() != *word {
+                continue;
+            }
+
+            let end = start + word.len();
+            if let Some(index) = tokens.iter().position(|token| token.byte_end == end) {
+                return Some((word, index + 1));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use charabia::Tokenize;
+
+    use super::*;
+
+    fn words(dictionary: &Dictionary, text: &str) -> Vec<String> {
+        dictionary
+            .merge_tokens(text, text.tokenize())
+            .into_iter()
+            .filter(|token| token.is_word())
+            .map(|token| token.lemma().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn sequences_are_kept_as_single_words() {
+        let dictionary = Dictionary::new(["C++", " c# ", "c++ code"]);
+        assert_eq!(
+            words(&dictionary, "I code in C++ and C#"),
+            ["i", "code", "in", "c++", "and", "c#"]
+        );
+        // the longest sequence is kept
+        assert_eq!(words(&dictionary, "c++ code, c"), ["c++ code", "c"]);
+        // a sequence must start at the start of a token
+        assert_eq!(words(&dictionary, "abc++"), ["abc"]);
+
+        assert_eq!(words(&Dictionary::default(), "C++"), ["c"]);
+    }
+}
@@ -4,9 +4,12 @@ use grenad::{CompressionType, WriterBuilder};
 use serde::de::Deserializer;
 use serde_json::{to_writer, Value};
 
-use super::{DocumentsBatchIndex, Error, DOCUMENTS_BATCH_INDEX_KEY};
+use super::{
+    DocumentsBatchIndex, Error, DOCUMENTS_BATCH_FOOTER_KEY, DOCUMENTS_BATCH_INDEX_KEY,
+    DOCUMENTS_BATCH_VERSION,
+};
 use crate::documents::serde_impl::DocumentVisitor;
-use crate::Object;
+use crate::{FieldId, FieldsIdsMap, Object};
 
 /// The `DocumentsBatchBuilder` provides a way to build a documents batch in the intermediary
 /// format used by milli.
@@ -39,6 +42,8 @@ pub struct DocumentsBatchBuilder<W> {
     /// A buffer to serialize the values and avoid reallocating,
     /// serialized values are stored in an obkv.
     value_buffer: Vec<u8>,
+    /// The maximum size of a serialized document, the larger documents are rejected.
+    max_document_size_bytes: Option<usize>,
 }
 
 impl<W: Write> DocumentsBatchBuilder<W> {
@@ -49,16 +54,23 @@ impl<W: Write> DocumentsBatchBuilder<W> {
             documents_count: 0,
             obkv_buffer: Vec::new(),
             value_buffer: Vec::new(),
+            max_document_size_bytes: None,
         }
     }
 
+    /// Rejects the documents whose serialized size is larger than the given number of bytes
+    /// with an `Error::DocumentTooLarge`, there is no limit by default.
+    pub fn set_max_document_size_bytes(&mut self, limit: Option<usize>) {
+        self.max_document_size_bytes = limit;
+    }
+
     /// Returns the number of documents inserted into this builder.
     pub fn documents_count(&self) -> u32 {
         self.documents_count
     }
 
     /// Appends a new JSON object into the batch and updates the `DocumentsBatchIndex` accordingly.
-    pub fn append_json_object(&mut self, object: &Object) -> io::Result<()> {
+    pub fn append_json_object(&mut self, object: &Object) -> Result<(), Error> {
         // Make sure that we insert the fields ids in order as the obkv writer has this requirement.
         let mut fields_ids: Vec<_> = object.keys().map(|k| self.fields_index.insert(&k)).collect();
         fields_ids.sort_unstable();
@@ -72,12 +84,43 @@ impl<W: Write> DocumentsBatchBuilder<W> {
             writer.insert(field_id, &self.value_buffer)?;
         }
 
-        let internal_id = self.documents_count.to_be_bytes();
-        let document_bytes = writer.into_inner()?;
-        self.writer.insert(internal_id, &document_bytes)?;
-        self.documents_count += 1;
+        writer.into_inner()?;
+        self.insert_document()
+    }
 
-        Ok(())
+    /// Appends a new obkv document into the batch and updates the `DocumentsBatchIndex` accordingly.
+    ///
+    /// The field ids of the document are remapped from the given `FieldsIdsMap` to the ones of
+    /// the batch, and its values must be serialized JSON values, like the ones stored by an index.
+    pub fn append_obkv(
+        &mut self,
+        obkv: &obkv::KvReader<FieldId>,
+        fields_ids_map: &FieldsIdsMap,
+    ) -> Result<(), Error> {
+        let mut named_fields = Vec::new();
+        for (field_id, value) in obkv.iter() {
+            let name = fields_ids_map.name(field_id).ok_or(Error::UnknownFieldId { field_id })?;
+            // We make sure that the value is valid JSON before writing it as is.
+            serde_json::from_slice::<serde::de::IgnoredAny>(value)?;
+            named_fields.push((name, value));
+        }
+
+        // The fields of the document are only added to the batch once it is known to be valid.
+        let mut fields: Vec<_> = named_fields
+            .into_iter()
+            .map(|(name, value)| (self.fields_index.insert(name), value))
+            .collect();
+        // Make sure that we insert the fields ids in order as the obkv writer has this requirement.
+        fields.sort_unstable_by_key(|(field_id, _)| *field_id);
+
+        self.obkv_buffer.clear();
+        let mut writer = obkv::KvWriter::new(&mut self.obkv_buffer);
+        for (field_id, value) in fields {
+            writer.insert(field_id, value)?;
+        }
+
+        writer.into_inner()?;
+        self.insert_document()
     }
 
     /// Appends a new JSON array of objects into the batch and updates the `DocumentsBatchIndex` accordingly.
@@ -94,23 +137,18 @@ impl<W: Write> DocumentsBatchBuilder<W> {
             .headers()?
             .into_iter()
             .map(parse_csv_header)
-            .map(|(k, t)| (self.fields_index.insert(k), t))
+            .map(|(k, t)| (self.fields_index.insert(k), t, k.to_string()))
             .enumerate()
             .collect();
         // Make sure that we insert the fields ids in order as the obkv writer has this requirement.
-        typed_fields_ids.sort_unstable_by_key(|(_, (fid, _))| *fid);
+        typed_fields_ids.sort_unstable_by_key(|(_, (fid, _, _))| *fid);
 
         let mut record = csv::StringRecord::new();
-        let mut line = 0;
         while reader.read_record(&mut record)? {
-            // We increment here and not at the end of the while loop to take
-            // the header offset into account.
-            line += 1;
-
             self.obkv_buffer.clear();
             let mut writer = obkv::KvWriter::new(&mut self.obkv_buffer);
 
-            for (i, (field_id, type_)) in typed_fields_ids.iter() {
+            for (i, (field_id, type_, field_name)) in typed_fields_ids.iter() {
                 self.value_buffer.clear();
 
                 let value = &record[*i];
@@ -120,15 +158,23 @@ impl<W: Write> DocumentsBatchBuilder<W> {
                             to_writer(&mut self.value_buffer, &Value::Null)?;
                         } else if let Ok(integer) = value.trim().parse::<i64>() {
                             to_writer(&mut self.value_buffer, &integer)?;
+                        } else if let Ok(integer) = value.trim().parse::<u64>() {
+                            // The big positive integers are kept exact, they must not become floats.
+                            to_writer(&mut self.value_buffer, &integer)?;
                         } else {
                             match value.trim().parse::<f64>() {
                                 Ok(float) => {
                                     to_writer(&mut self.value_buffer, &float)?;
                                 }
                                 Err(error) => {
+                                    let position = match record.position() {
+                                        Some(position) => position.clone(),
+                                        None => reader.position().clone(),
+                                    };
                                     return Err(Error::ParseFloat {
                                         error,
-                                        line,
+                                        position,
+                                        column: field_name.clone(),
                                         value: value.to_string(),
                                     });
                                 }
@@ -148,28 +194,70 @@ impl<W: Write> DocumentsBatchBuilder<W> {
                 writer.insert(*field_id, &self.value_buffer)?;
             }
 
-            let internal_id = self.documents_count.to_be_bytes();
-            let document_bytes = writer.into_inner()?;
-            self.writer.insert(internal_id, &document_bytes)?;
-            self.documents_count += 1;
+            writer.into_inner()?;
+            self.insert_document()?;
         }
 
         Ok(())
     }
 
-    /// Flushes the content on disk and stores the final version of the `DocumentsBatchIndex`.
+    /// Inserts the document serialized in the obkv buffer into the batch.
+    fn insert_document(&mut self) -> Result<(), Error> {
+        if let Some(limit) = self.max_document_size_bytes {
+            let size = self.obkv_buffer.len();
+            if size > limit {
+                let partial_external_id = self.partial_external_id();
+                return Err(Error::DocumentTooLarge { size, limit, partial_external_id });
+            }
+        }
+
+        let internal_id = self.documents_count.to_be_bytes();
+        self.writer.insert(internal_id, &self.obkv_buffer)?;
+        self.documents_count += 1;
+
+        Ok(())
+    }
+
+    /// Returns the beginning of the value of the first field of the document in the obkv buffer
+    /// that looks like a primary key, a field whose name contains `id`, if it is a string or a number.
+    fn partial_external_id(&self) -> Option<String> {
+        let document = obkv::KvReader::<FieldId>::new(&self.obkv_buffer);
+        let (_, value) = document.iter().find(|(field_id, _)| {
+            self.fields_index
+                .name(*field_id)
+                .map_or(false, |name| name.to_lowercase().contains("id"))
+        })?;
+        let external_id = match serde_json::from_slice(value).ok()? {
+            Value::String(string) => string,
+            Value::Number(number) => number.to_string(),
+            _ => return None,
+        };
+        Some(external_id.chars().take(PARTIAL_EXTERNAL_ID_MAX_CHARS).collect())
+    }
+
+    /// Flushes the content on disk and stores the final version of the `DocumentsBatchIndex`
+    /// followed by the footer of the batch.
     pub fn into_inner(mut self) -> io::Result<W> {
-        let DocumentsBatchBuilder { mut writer, fields_index, .. } = self;
+        let DocumentsBatchBuilder { mut writer, fields_index, documents_count, .. } = self;
 
-        // We serialize and insert the `DocumentsBatchIndex` as the last key of the grenad writer.
+        // We serialize and insert the `DocumentsBatchIndex` after the documents.
         self.value_buffer.clear();
         to_writer(&mut self.value_buffer, &fields_index)?;
         writer.insert(DOCUMENTS_BATCH_INDEX_KEY, &self.value_buffer)?;
 
+        // The footer is the absolute last key of the grenad writer.
+        self.value_buffer.clear();
+        self.value_buffer.push(DOCUMENTS_BATCH_VERSION);
+        self.value_buffer.extend_from_slice(&documents_count.to_be_bytes());
+        writer.insert(DOCUMENTS_BATCH_FOOTER_KEY, &self.value_buffer)?;
+
         writer.into_inner()
     }
 }
 
+/// The maximum number of characters of the document id reported by an `Error::DocumentTooLarge`.
+const PARTIAL_EXTERNAL_ID_MAX_CHARS: usize = 64;
+
 #[derive(Debug)]
 enum AllowedType {
     String,
@@ -197,6 +285,7 @@ mod test {
 
     use super::*;
     use crate::documents::{obkv_to_object, DocumentsBatchReader};
+    use crate::index::tests::TempIndex;
 
     #[test]
     fn add_single_documents_json() {
@@ -556,6 +645,28 @@ mod test {
         assert!(builder.append_csv(csv).is_err());
     }
 
+    #[test]
+    fn bad_number_reports_its_row_and_column() {
+        let csv_content = r#"city,country,price:number
+"Boston","United States",12.5
+"Paris","France",7
+"Lyon","France","12,5"
+"Berlin","Germany",3"#;
+        let csv = csv::Reader::from_reader(Cursor::new(csv_content));
+
+        let mut builder = DocumentsBatchBuilder::new(Vec::new());
+        let error = builder.append_csv(csv).unwrap_err();
+        assert_eq!(error.to_string(), "row 3, column `price`: could not parse `12,5` as a number");
+        match error {
+            Error::ParseFloat { position, column, .. } => {
+                assert_eq!(position.record(), 3);
+                assert_eq!(position.line(), 4);
+                assert_eq!(column, "price");
+            }
+            error => panic!("unexpected error {:?}", error),
+        }
+    }
+
     #[test]
     fn bad_column_count1() {
         let csv_content = r#"city,country,pop
@@ -576,4 +687,129 @@ mod test {
         let mut builder = DocumentsBatchBuilder::new(Vec::new());
         assert!(builder.append_csv(csv).is_err());
     }
+
+    #[test]
+    fn append_obkv_documents_from_an_index() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 1, "title": "hello", "tags": ["a", "b"] },
+                { "id": 2, "author": { "name": "kevin", "age": 20 }, "title": null },
+                { "id": 3, "rating": 4.5 }
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let fields_ids_map = index.fields_ids_map(&rtxn).unwrap();
+        let mut builder = DocumentsBatchBuilder::new(Vec::new());
+        for result in index.all_documents(&rtxn).unwrap() {
+            let (_id, document) = result.unwrap();
+            builder.append_obkv(&document.as_obkv(), &fields_ids_map).unwrap();
+        }
+        assert_eq!(builder.documents_count(), 3);
+        let vector = builder.into_inner().unwrap();
+
+        let new_index = TempIndex::new();
+        new_index
+            .add_documents(DocumentsBatchReader::from_reader(Cursor::new(vector)).unwrap())
+            .unwrap();
+
+        let new_rtxn = new_index.read_txn().unwrap();
+        let new_fields_ids_map = new_index.fields_ids_map(&new_rtxn).unwrap();
+        let fields: Vec<_> = fields_ids_map.ids().collect();
+        let new_fields: Vec<_> = new_fields_ids_map.ids().collect();
+        let documents: Vec<_> = index
+            .all_documents(&rtxn)
+            .unwrap()
+            .map(|result| {
+                let document = result.unwrap().1;
+                crate::obkv_to_json(&fields, &fields_ids_map, document.as_obkv()).unwrap()
+            })
+            .collect();
+        let new_documents: Vec<_> = new_index
+            .all_documents(&new_rtxn)
+            .unwrap()
+            .map(|result| {
+                let document = result.unwrap().1;
+                crate::obkv_to_json(&new_fields, &new_fields_ids_map, document.as_obkv()).unwrap()
+            })
+            .collect();
+        assert_eq!(documents, new_documents);
+    }
+
+    #[test]
+    fn append_obkv_rejects_unknown_fields_and_invalid_values() {
+        let mut fields_ids_map = FieldsIdsMap::new();
+        let id = fields_ids_map.insert("id").unwrap();
+
+        let mut buffer = Vec::new();
+        let mut writer = obkv::KvWriter::new(&mut buffer);
+        writer.insert(id, b"1").unwrap();
+        writer.insert(id + 1, br#""unknown""#).unwrap();
+        let document = writer.into_inner().unwrap();
+
+        let mut builder = DocumentsBatchBuilder::new(Vec::new());
+        let error = builder.append_obkv(&obkv::KvReader::new(&document), &fields_ids_map);
+        assert!(matches!(error, Err(Error::UnknownFieldId { field_id: 1 })));
+
+        let mut buffer = Vec::new();
+        let mut writer = obkv::KvWriter::new(&mut buffer);
+        writer.insert(id, b"{ not json").unwrap();
+        let document = writer.into_inner().unwrap();
+
+        let error = builder.append_obkv(&obkv::KvReader::new(&document), &fields_ids_map);
+        assert!(matches!(error, Err(Error::Json(_))));
+        assert_eq!(builder.documents_count(), 0);
+
+        // The fields of the rejected documents are not added to the batch.
+        assert_eq!(builder.fields_index.len(), 0);
+    }
+
+    #[test]
+    fn too_large_documents_are_rejected() {
+        let large_text = "a".repeat(2000);
+        let ndjson = format!(
+            "{}\n{}\n{}\n",
+            json!({ "id": 1, "text": "small" }),
+            json!({ "text": large_text, "doc_id": "z".repeat(100) }),
+            json!({ "text": large_text }),
+        );
+
+        let mut builder = DocumentsBatchBuilder::new(Vec::new());
+        builder.set_max_document_size_bytes(Some(1000));
+        let mut objects = serde_json::Deserializer::from_str(&ndjson).into_iter::<Object>();
+        builder.append_json_object(&objects.next().unwrap().unwrap()).unwrap();
+
+        // the detected primary key value is reported, truncated
+        let error = builder.append_json_object(&objects.next().unwrap().unwrap()).unwrap_err();
+        match error {
+            Error::DocumentTooLarge { size, limit: 1000, partial_external_id: Some(id) } => {
+                assert!(size > 2000);
+                assert_eq!(id, "z".repeat(64));
+            }
+            error => panic!("unexpected error {:?}", error),
+        }
+
+        let error = builder.append_json_object(&objects.next().unwrap().unwrap()).unwrap_err();
+        assert!(matches!(error, Error::DocumentTooLarge { partial_external_id: None, .. }));
+        assert_eq!(error.code(), crate::error::ErrorCode::MalformedPayload);
+
+        let json = format!(r#"[{{ "id": 2 }}, {{ "id": 3, "text": "{}" }}]"#, large_text);
+        let error = builder.append_json_array(Cursor::new(json)).unwrap_err();
+        assert!(matches!(
+            error,
+            Error::DocumentTooLarge { partial_external_id: Some(id), .. } if id == "3"
+        ));
+
+        let csv_content = format!("id:number,text\n4,small\n5,{}", large_text);
+        let csv = csv::Reader::from_reader(Cursor::new(csv_content));
+        let error = builder.append_csv(csv).unwrap_err();
+        assert!(matches!(
+            error,
+            Error::DocumentTooLarge { partial_external_id: Some(id), .. } if id == "5"
+        ));
+
+        // the documents appended before the large ones are kept
+        assert_eq!(builder.documents_count(), 3);
+    }
 }
@@ -1,5 +1,6 @@
 mod builder;
 mod enriched;
+mod primary_key;
 mod reader;
 mod serde_impl;
 
@@ -11,16 +12,31 @@ use bimap::BiHashMap;
 pub use builder::DocumentsBatchBuilder;
 pub use enriched::{EnrichedDocument, EnrichedDocumentsBatchCursor, EnrichedDocumentsBatchReader};
 use obkv::KvReader;
+pub use primary_key::PrimaryKeyGuess;
+pub(crate) use primary_key::{DEFAULT_PRIMARY_KEY, PRIMARY_KEY_SPLIT_SYMBOL};
 pub use reader::{DocumentsBatchCursor, DocumentsBatchCursorError, DocumentsBatchReader};
 use serde::{Deserialize, Serialize};
 
-use crate::error::{FieldIdMapMissingEntry, InternalError};
+use crate::error::{ErrorCode, FieldIdMapMissingEntry, InternalError};
 use crate::{FieldId, Object, Result};
 
 /// The key that is used to store the `DocumentsBatchIndex` datastructure,
-/// it is the absolute last key of the list.
+/// it is the last key of the list before the footer.
 const DOCUMENTS_BATCH_INDEX_KEY: [u8; 8] = u64::MAX.to_be_bytes();
 
+/// The key of the footer of the batch, the absolute last key of the list. The footer is the
+/// version of the format of the batch followed by the big-endian number of documents.
+///
+/// It is written after the `DocumentsBatchIndex`, the readers that stop at the index never see
+/// it. The batches written before the footer was introduced are of the version 0.
+const DOCUMENTS_BATCH_FOOTER_KEY: [u8; 9] = [u8::MAX; 9];
+
+/// The version of the format of the batches written by the `DocumentsBatchBuilder`.
+///
+/// In the version 1 the documents are stored under their big-endian position, from zero
+/// and without gap, the block index of the batch is then the offset table of its documents.
+const DOCUMENTS_BATCH_VERSION: u8 = 1;
+
 /// Helper function to convert an obkv reader into a JSON object.
 pub fn obkv_to_object(obkv: &KvReader<FieldId>, index: &DocumentsBatchIndex) -> Result<Object> {
     obkv.iter()
@@ -89,9 +105,24 @@ impl DocumentsBatchIndex {
 
 #[derive(Debug)]
 pub enum Error {
-    ParseFloat { error: std::num::ParseFloatError, line: usize, value: String },
+    /// A value of a CSV column typed as a number is not a number, the row of the
+    /// error is the index of the record in the position, the header being the record 0.
+    ParseFloat {
+        error: std::num::ParseFloatError,
+        position: csv::Position,
+        column: String,
+        value: String,
+    },
     InvalidDocumentFormat,
     InvalidEnrichedData,
+    UnknownFieldId {
+        field_id: FieldId,
+    },
+    DocumentTooLarge {
+        size: usize,
+        limit: usize,
+        partial_external_id: Option<String>,
+    },
     InvalidUtf8(Utf8Error),
     Csv(csv::Error),
     Json(serde_json::Error),
@@ -130,16 +161,52 @@ impl From<Utf8Error> for Error {
     }
 }
 
+impl Error {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::ParseFloat { .. }
+            | Error::InvalidDocumentFormat
+            | Error::DocumentTooLarge { .. }
+            | Error::InvalidUtf8(_)
+            | Error::Csv(_)
+            | Error::Json(_)
+            | Error::Serialize(_) => ErrorCode::MalformedPayload,
+            Error::InvalidEnrichedData | Error::UnknownFieldId { .. } | Error::Grenad(_) => {
+                ErrorCode::Internal
+            }
+            Error::Io(_) => ErrorCode::Io,
+        }
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Error::ParseFloat { error, line, value } => {
-                write!(f, "Error parsing number {:?} at line {}: {}", value, line, error)
-            }
+            Error::ParseFloat { error: _, position, column, value } => write!(
+                f,
+                "row {}, column `{}`: could not parse `{}` as a number",
+                position.record(),
+                column,
+                value
+            ),
             Error::InvalidDocumentFormat => {
                 f.write_str("Invalid document addition format, missing the documents batch index.")
             }
             Error::InvalidEnrichedData => f.write_str("Invalid enriched data."),
+            Error::UnknownFieldId { field_id } => {
+                write!(f, "The field id {} is not part of the fields ids map.", field_id)
+            }
+            Error::DocumentTooLarge { size, limit, partial_external_id } => {
+                match partial_external_id {
+                    Some(id) => write!(f, "The document with the id `{}`", id)?,
+                    None => f.write_str("A document")?,
+                }
+                write!(
+                    f,
+                    " is {} bytes, larger than the maximum document size of {} bytes.",
+                    size, limit
+                )
+            }
             Error::InvalidUtf8(e) => write!(f, "{}", e),
             Error::Io(e) => write!(f, "{}", e),
             Error::Serialize(e) => write!(f, "{}", e),
@@ -198,6 +265,7 @@ pub fn documents_batch_reader_from_objects(
 mod test {
     use std::io::Cursor;
 
+    use big_s::S;
     use serde_json::{json, Value};
 
     use super::*;
@@ -289,4 +357,114 @@ mod test {
 
         DocumentsBatchReader::from_reader(Cursor::new(vector)).unwrap();
     }
+
+    #[test]
+    fn guess_primary_key() {
+        let guess = |documents: Value| {
+            let mut builder = DocumentsBatchBuilder::new(Vec::new());
+            for document in documents.as_array().unwrap() {
+                builder.append_json_object(document.as_object().unwrap()).unwrap();
+            }
+            let vector = builder.into_inner().unwrap();
+            let mut reader = DocumentsBatchReader::from_reader(Cursor::new(vector)).unwrap();
+            reader.guess_primary_key().unwrap()
+        };
+
+        // no field contains "id"
+        let no_candidate = guess(json!([{ "title": "hello" }, { "title": "world" }]));
+        assert_eq!(no_candidate.primary_key(), None);
+        assert!(!no_candidate.is_ambiguous());
+        assert!(guess(json!([])).candidates.is_empty());
+
+        // a single field contains "id", whatever its case
+        let one = guess(json!([{ "title": "hello", "bookId": 1 }]));
+        assert_eq!(one, PrimaryKeyGuess { candidates: vec![S("bookId")], nested: false });
+        assert!(!one.is_ambiguous());
+
+        // several fields contain "id", the first one of the batch is used
+        let several = guess(json!([
+            { "title": "hello", "author_id": 2 },
+            { "id": 1, "author_id": 3, "isbn_ID": "b" },
+        ]));
+        assert_eq!(several.candidates, vec![S("author_id"), S("id"), S("isbn_ID")]);
+        assert_eq!(several.primary_key(), Some("author_id"));
+        assert!(several.is_ambiguous());
+
+        // the nested fields are only looked for when no top-level field contains "id"
+        let nested = guess(json!([{ "title": "hello", "meta": { "id": 1, "kind": "book" } }]));
+        assert_eq!(nested, PrimaryKeyGuess { candidates: vec![S("meta.id")], nested: true });
+    }
+
+    #[test]
+    fn get_documents_by_position() {
+        let mut builder = DocumentsBatchBuilder::new(Vec::new());
+        for i in 0..1000 {
+            builder.append_json_object(json!({ "id": i }).as_object().unwrap()).unwrap();
+        }
+        let vector = builder.into_inner().unwrap();
+
+        let mut reader = DocumentsBatchReader::from_reader(Cursor::new(vector)).unwrap();
+        let id = reader.documents_batch_index().id("id").unwrap();
+        for nth in [999, 0, 500, 501, 42] {
+            let document = reader.get(nth).unwrap().unwrap();
+            let value: Value = serde_json::from_slice(document.get(id).unwrap()).unwrap();
+            assert_eq!(value, json!(nth));
+        }
+        assert!(reader.get(1000).unwrap().is_none());
+
+        // The forward cursor is not impacted by the random accesses.
+        let (mut cursor, _) = reader.into_cursor_and_fields_index();
+        let mut count = 0;
+        while cursor.next_document().unwrap().is_some() {
+            count += 1;
+        }
+        assert_eq!(count, 1000);
+    }
+
+    #[test]
+    fn batches_are_version_tagged() {
+        let mut builder = DocumentsBatchBuilder::new(Vec::new());
+        builder.append_json_object(json!({ "id": 1 }).as_object().unwrap()).unwrap();
+        let vector = builder.into_inner().unwrap();
+
+        let reader = grenad::Reader::new(Cursor::new(vector.as_slice())).unwrap();
+        let mut cursor = reader.into_cursor().unwrap();
+        let (_, footer) = cursor.move_on_key_equal_to(DOCUMENTS_BATCH_FOOTER_KEY).unwrap().unwrap();
+        assert_eq!(footer, [DOCUMENTS_BATCH_VERSION, 0, 0, 0, 1]);
+        // the footer is the last key of the batch
+        assert!(cursor.move_on_next().unwrap().is_none());
+
+        let reader = DocumentsBatchReader::from_reader(Cursor::new(vector)).unwrap();
+        assert_eq!(reader.version(), DOCUMENTS_BATCH_VERSION);
+        assert_eq!(reader.documents_count(), 1);
+        let (mut cursor, _) = reader.into_cursor_and_fields_index();
+        assert!(cursor.next_document().unwrap().is_some());
+        assert!(cursor.next_document().unwrap().is_none());
+    }
+
+    #[test]
+    fn get_documents_from_a_batch_written_by_hand() {
+        // A batch written like the version 0 of the format, without footer,
+        // with small blocks and the documents batch index stored as the last key.
+        let mut fields_index = DocumentsBatchIndex::default();
+        let id = fields_index.insert("id");
+        let mut writer = grenad::WriterBuilder::new().block_size(64).build(Vec::new());
+        for i in 0u32..100 {
+            let mut buffer = Vec::new();
+            let mut document = obkv::KvWriter::new(&mut buffer);
+            document.insert(id, serde_json::to_vec(&i).unwrap()).unwrap();
+            writer.insert(i.to_be_bytes(), document.into_inner().unwrap()).unwrap();
+        }
+        writer
+            .insert(DOCUMENTS_BATCH_INDEX_KEY, serde_json::to_vec(&fields_index).unwrap())
+            .unwrap();
+        let vector = writer.into_inner().unwrap();
+
+        let mut reader = DocumentsBatchReader::from_reader(Cursor::new(vector)).unwrap();
+        assert_eq!(reader.version(), 0);
+        assert_eq!(reader.documents_count(), 100);
+        let document = reader.get(73).unwrap().unwrap();
+        assert_eq!(document.get(id), Some(&b"73"[..]));
+        assert!(reader.get(100).unwrap().is_none());
+    }
 }
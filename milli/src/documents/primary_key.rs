@@ -0,0 +1,63 @@
+use super::DocumentsBatchIndex;
+use crate::{Object, Result};
+
+/// The symbol used to define levels in a nested primary key.
+pub(crate) const PRIMARY_KEY_SPLIT_SYMBOL: char = '.';
+
+/// The default primary key, the fields that contain it may be the primary key.
+pub(crate) const DEFAULT_PRIMARY_KEY: &str = "id";
+
+/// The fields of a batch of documents that may be its primary key when the index has none,
+/// see [`DocumentsBatchReader::guess_primary_key`](super::DocumentsBatchReader::guess_primary_key).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrimaryKeyGuess {
+    /// The names of the fields that contain "id", in the order they appear in the batch.
+    /// The documents are indexed with the first one, unless there are several nested ones.
+    pub candidates: Vec<String>,
+    /// Whether the candidates are fields nested in the objects of the first document,
+    /// they are only looked for when no top-level field of the batch contains "id".
+    pub nested: bool,
+}
+
+impl PrimaryKeyGuess {
+    /// Guesses the primary key from the top-level fields of the batch and,
+    /// when none of them contains "id", from the nested fields of its first document.
+    pub(crate) fn new(
+        fields_index: &DocumentsBatchIndex,
+        first_document: impl FnOnce() -> Result<Option<Object>>,
+    ) -> Result<PrimaryKeyGuess> {
+        let mut fields: Vec<_> =
+            fields_index.iter().filter(|(_, name)| is_candidate(name)).collect();
+        if !fields.is_empty() {
+            fields.sort_unstable_by_key(|(field_id, _)| **field_id);
+            let candidates = fields.into_iter().map(|(_, name)| name.clone()).collect();
+            return Ok(PrimaryKeyGuess { candidates, nested: false });
+        }
+
+        let candidates: Vec<_> = match first_document()? {
+            Some(document) => flatten_serde_json::flatten(&document)
+                .into_iter()
+                .map(|(name, _)| name)
+                .filter(|name| name.contains(PRIMARY_KEY_SPLIT_SYMBOL) && is_candidate(name))
+                .collect(),
+            None => Vec::new(),
+        };
+        let nested = !candidates.is_empty();
+        Ok(PrimaryKeyGuess { candidates, nested })
+    }
+
+    /// Returns the primary key the documents are indexed with, `None` if no field contains "id".
+    pub fn primary_key(&self) -> Option<&str> {
+        self.candidates.first().map(String::as_str)
+    }
+
+    /// Whether several fields may be the primary key, only the first one is used. The indexing
+    /// rejects the batch with the candidates when they are nested fields.
+    pub fn is_ambiguous(&self) -> bool {
+        self.candidates.len() > 1
+    }
+}
+
+fn is_candidate(name: &str) -> bool {
+    name.to_lowercase().contains(DEFAULT_PRIMARY_KEY)
+}
@@ -3,7 +3,10 @@ use std::{error, fmt, io};
 
 use obkv::KvReader;
 
-use super::{DocumentsBatchIndex, Error, DOCUMENTS_BATCH_INDEX_KEY};
+use super::{
+    obkv_to_object, DocumentsBatchIndex, Error, PrimaryKeyGuess, DOCUMENTS_BATCH_FOOTER_KEY,
+    DOCUMENTS_BATCH_INDEX_KEY, DOCUMENTS_BATCH_VERSION,
+};
 use crate::FieldId;
 
 /// The `DocumentsBatchReader` provides a way to iterate over documents that have been created with
@@ -14,17 +17,18 @@ use crate::FieldId;
 pub struct DocumentsBatchReader<R> {
     cursor: grenad::ReaderCursor<R>,
     fields_index: DocumentsBatchIndex,
+    footer: Footer,
 }
 
 impl<R: io::Read + io::Seek> DocumentsBatchReader<R> {
     pub fn new(cursor: DocumentsBatchCursor<R>, fields_index: DocumentsBatchIndex) -> Self {
-        Self { cursor: cursor.cursor, fields_index }
+        Self { cursor: cursor.cursor, fields_index, footer: cursor.footer }
     }
 
     /// Construct a `DocumentsReader` from a reader.
     ///
-    /// It first retrieves the index, then moves to the first document. Use the `into_cursor`
-    /// method to iterator over the documents, from the first to the last.
+    /// It first retrieves the index and the footer, then moves to the first document. Use the
+    /// `into_cursor` method to iterator over the documents, from the first to the last.
     pub fn from_reader(reader: R) -> Result<Self, Error> {
         let reader = grenad::Reader::new(reader)?;
         let mut cursor = reader.into_cursor()?;
@@ -34,33 +38,99 @@ impl<R: io::Read + io::Seek> DocumentsBatchReader<R> {
             None => return Err(Error::InvalidDocumentFormat),
         };
 
-        Ok(DocumentsBatchReader { cursor, fields_index })
+        // The batches written before the footer was introduced end with the index.
+        let footer = match cursor.move_on_next()? {
+            Some((key, value)) if key == DOCUMENTS_BATCH_FOOTER_KEY => match value {
+                [version @ 1..=DOCUMENTS_BATCH_VERSION, count @ ..] => Footer {
+                    version: *version,
+                    documents_count: count
+                        .try_into()
+                        .map(u32::from_be_bytes)
+                        .map_err(|_| Error::InvalidDocumentFormat)?,
+                },
+                _otherwise => return Err(Error::InvalidDocumentFormat),
+            },
+            Some(_) => return Err(Error::InvalidDocumentFormat),
+            None => Footer {
+                version: 0,
+                documents_count: cursor
+                    .len()
+                    .saturating_sub(1)
+                    .try_into()
+                    .expect("Invalid number of documents"),
+            },
+        };
+
+        Ok(DocumentsBatchReader { cursor, fields_index, footer })
+    }
+
+    /// The version of the format of the batch, 0 for the batches written without footer.
+    pub fn version(&self) -> u8 {
+        self.footer.version
     }
 
     pub fn documents_count(&self) -> u32 {
-        self.cursor.len().saturating_sub(1).try_into().expect("Invalid number of documents")
+        self.footer.documents_count
     }
 
     pub fn is_empty(&self) -> bool {
-        self.cursor.len().saturating_sub(1) == 0
+        self.footer.documents_count == 0
     }
 
     pub fn documents_batch_index(&self) -> &DocumentsBatchIndex {
         &self.fields_index
     }
 
+    /// Returns the nth document of the batch, starting from zero, `None` if there is no such document.
+    ///
+    /// The documents are stored under their big-endian position in the batch, the grenad
+    /// block index is used to seek directly to the block containing the requested document.
+    pub fn get(
+        &mut self,
+        nth: u32,
+    ) -> Result<Option<KvReader<FieldId>>, DocumentsBatchCursorError> {
+        if nth >= self.footer.documents_count {
+            return Ok(None);
+        }
+        match self.cursor.move_on_key_equal_to(nth.to_be_bytes())? {
+            Some((_, value)) => Ok(Some(KvReader::new(value))),
+            None => Ok(None),
+        }
+    }
+
+    /// Guesses the primary key of the batch the same way the indexing does when the index
+    /// doesn't have one yet, from the fields that contain "id".
+    pub fn guess_primary_key(&mut self) -> crate::Result<PrimaryKeyGuess> {
+        let fields_index = &self.fields_index;
+        let cursor = &mut self.cursor;
+        PrimaryKeyGuess::new(fields_index, || {
+            match cursor.move_on_key_equal_to(0u32.to_be_bytes())? {
+                Some((_, value)) => obkv_to_object(&KvReader::new(value), fields_index).map(Some),
+                None => Ok(None),
+            }
+        })
+    }
+
     /// This method returns a forward cursor over the documents.
     pub fn into_cursor_and_fields_index(self) -> (DocumentsBatchCursor<R>, DocumentsBatchIndex) {
-        let DocumentsBatchReader { cursor, fields_index } = self;
-        let mut cursor = DocumentsBatchCursor { cursor };
+        let DocumentsBatchReader { cursor, fields_index, footer } = self;
+        let mut cursor = DocumentsBatchCursor { cursor, footer };
         cursor.reset();
         (cursor, fields_index)
     }
 }
 
+/// The version and the number of documents of a batch, see `DOCUMENTS_BATCH_FOOTER_KEY`.
+#[derive(Debug, Clone, Copy)]
+struct Footer {
+    version: u8,
+    documents_count: u32,
+}
+
 /// A forward cursor over the documents in a `DocumentsBatchReader`.
 pub struct DocumentsBatchCursor<R> {
     cursor: grenad::ReaderCursor<R>,
+    footer: Footer,
 }
 
 impl<R> DocumentsBatchCursor<R> {
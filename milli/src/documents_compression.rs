@@ -0,0 +1,158 @@
+/*!
+This module compresses the documents stored in the documents database.
+
+When the documents compression is enabled, every document is compressed with zstd and
+a dictionary trained on a sample of the first batch of documents. The dictionary is stored
+in the main database and its presence means that every document of the documents database
+is compressed with it. Enabling or disabling the compression on an index which already
+contains documents rewrites all of them, the documents database is never partially compressed.
+
+A compressed document is prefixed by the length of the uncompressed document, stored as a
+big-endian `u32`, to be able to allocate the decompression buffer upfront.
+*/
+
+use std::borrow::Cow;
+
+use heed::types::ByteSlice;
+use heed::RwTxn;
+
+use crate::error::SerializationError;
+use crate::index::db_name;
+use crate::{try_split_array_at, Index, Result};
+
+/// The zstd compression level used to compress the documents.
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// The maximum number of documents used to train the compression dictionary.
+pub(crate) const DICTIONARY_SAMPLES: usize = 10_000;
+
+/// The maximum size in bytes of the compression dictionary.
+const DICTIONARY_MAX_SIZE: usize = 64 * 1024;
+
+/// A document read from the documents database, decompressed if the documents are compressed.
+#[derive(Debug, Clone)]
+pub struct StoredDocument<'t>(Cow<'t, [u8]>);
+
+impl StoredDocument<'_> {
+    pub fn as_obkv(&self) -> obkv::KvReaderU16 {
+        obkv::KvReaderU16::new(&self.0)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Trains a compression dictionary on the given documents.
+///
+/// An empty dictionary is returned when there are not enough documents to train one,
+/// the documents are then compressed without a dictionary.
+pub(crate) fn train_dictionary<S: AsRef<[u8]>>(samples: &[S]) -> Vec<u8> {
+    zstd::dict::from_samples(samples, DICTIONARY_MAX_SIZE).unwrap_or_default()
+}
+
+/// Compresses the documents before they are written into the documents database.
+pub(crate) struct DocumentsCompressor(zstd::bulk::Compressor<'static>);
+
+impl DocumentsCompressor {
+    pub fn new(dictionary: &[u8]) -> Result<DocumentsCompressor> {
+        let compressor = zstd::bulk::Compressor::with_dictionary(COMPRESSION_LEVEL, dictionary)?;
+        Ok(DocumentsCompressor(compressor))
+    }
+
+    pub fn compress(&mut self, document: &[u8]) -> Result<Vec<u8>> {
+        let compressed = self.0.compress(document)?;
+        let mut buffer = Vec::with_capacity(4 + compressed.len());
+        buffer.extend_from_slice(&(document.len() as u32).to_be_bytes());
+        buffer.extend_from_slice(&compressed);
+        Ok(buffer)
+    }
+}
+
+/// Decompresses the documents read from the documents database, see [`Index::documents_decompressor`].
+pub struct DocumentsDecompressor(Option<zstd::bulk::Decompressor<'static>>);
+
+impl DocumentsDecompressor {
+    /// Creates a decompressor for documents compressed with the given dictionary,
+    /// or stored uncompressed when there is no dictionary.
+    pub(crate) fn new(dictionary: Option<&[u8]>) -> Result<DocumentsDecompressor> {
+        match dictionary {
+            Some(dictionary) => {
+                let decompressor = zstd::bulk::Decompressor::with_dictionary(dictionary)?;
+                Ok(DocumentsDecompressor(Some(decompressor)))
+            }
+            None => Ok(DocumentsDecompressor(None)),
+        }
+    }
+
+    pub fn decompress<'a>(&mut self, bytes: &'a [u8]) -> Result<StoredDocument<'a>> {
+        match &mut self.0 {
+            Some(decompressor) => {
+                let (length, compressed) = try_split_array_at(bytes)
+                    .ok_or(SerializationError::Decoding { db_name: Some(db_name::DOCUMENTS) })?;
+                let length = u32::from_be_bytes(length) as usize;
+                let document = decompressor.decompress(compressed, length)?;
+                Ok(StoredDocument(Cow::Owned(document)))
+            }
+            None => Ok(StoredDocument(Cow::Borrowed(bytes))),
+        }
+    }
+}
+
+/// Rewrites every document of the documents database, decompressing them with the `from`
+/// dictionary and compressing them with the `to` dictionary, `None` meaning uncompressed.
+pub(crate) fn recompress_documents(
+    wtxn: &mut RwTxn,
+    index: &Index,
+    from: Option<&[u8]>,
+    to: Option<&[u8]>,
+) -> Result<()> {
+    let mut decompressor = DocumentsDecompressor::new(from)?;
+    let mut compressor = to.map(DocumentsCompressor::new).transpose()?;
+
+    let mut iter = index.documents.remap_data_type::<ByteSlice>().iter_mut(wtxn)?;
+    while let Some(result) = iter.next() {
+        let (key, bytes) = result?;
+        let document = decompressor.decompress(bytes)?;
+        let bytes = match &mut compressor {
+            Some(compressor) => compressor.compress(document.as_bytes())?,
+            None => document.as_bytes().to_vec(),
+        };
+        // safety: we don't keep references from inside the LMDB database.
+        unsafe { iter.put_current(&key, &bytes)? };
+    }
+
+    Ok(())
+}
+
+/// Compresses the documents of the documents database with a dictionary trained on a sample of
+/// them and stores this dictionary. Does nothing if the documents are already compressed or if
+/// there are no documents, the dictionary is then trained on the first batch of documents.
+pub(crate) fn compress_documents_database(wtxn: &mut RwTxn, index: &Index) -> Result<()> {
+    if index.documents_compression_dictionary(wtxn)?.is_some() || index.documents.is_empty(wtxn)? {
+        return Ok(());
+    }
+
+    let mut samples = Vec::new();
+    let documents = index.documents.remap_data_type::<ByteSlice>();
+    for result in documents.iter(wtxn)?.take(DICTIONARY_SAMPLES) {
+        let (_docid, bytes) = result?;
+        samples.push(bytes.to_vec());
+    }
+
+    let dictionary = train_dictionary(&samples);
+    recompress_documents(wtxn, index, None, Some(&dictionary))?;
+    index.put_documents_compression_dictionary(wtxn, &dictionary)?;
+
+    Ok(())
+}
+
+/// Decompresses the documents of the documents database and removes the compression dictionary.
+pub(crate) fn decompress_documents_database(wtxn: &mut RwTxn, index: &Index) -> Result<()> {
+    if let Some(dictionary) = index.documents_compression_dictionary(wtxn)?.map(<[u8]>::to_vec) {
+        recompress_documents(wtxn, index, Some(&dictionary), None)?;
+        index.delete_documents_compression_dictionary(wtxn)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,560 @@
+/*!
+This module exports an index into a portable dump and loads it back into a fresh index.
+
+A dump is a stream of JSON values separated by new lines:
+ - a header containing the version of the dump format, the version of milli which
+   created it and the fields ids map of the index,
+ - the settings of the index,
+ - the documents of the index, one per line, with their primary key.
+
+The dump never collects the documents in memory, they are written one by one and loaded
+through a documents batch stored on disk. Loading a dump replays the settings with the
+[`Settings`] builder and the documents with the [`IndexDocuments`] builder, it is therefore
+independent of the LMDB page size and of the internal layout of the databases.
+
+The documents alone can also be exported with [`export_documents`], which reads them in
+batches to never keep a read transaction open for the whole duration of the export.
+*/
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{BufRead, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use fst::IntoStreamer;
+use heed::{EnvOpenOptions, RoTxn};
+use serde::{Deserialize, Serialize};
+
+use crate::documents::{DocumentsBatchBuilder, DocumentsBatchReader};
+use crate::error::{InternalError, UserError};
+use crate::position_bucketing::PositionBucketing;
+use crate::proximity::ProximityPrecision;
+use crate::update::{
+    IndexDocuments, IndexDocumentsConfig, IndexerConfig, PrefixSettings, Settings,
+};
+use crate::{obkv_to_json, FieldsIdsMap, Index, Object, OrderBy, Result};
+
+/// The version of the dump format, increased on every incompatible change of the format.
+pub const DUMP_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DumpHeader {
+    dump_version: u32,
+    milli_version: String,
+    fields_ids_map: FieldsIdsMap,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DumpSettings {
+    primary_key: Option<String>,
+    searchable_fields: Option<Vec<String>>,
+    searchable_fields_weights: BTreeMap<String, u16>,
+    displayed_fields: Option<Vec<String>>,
+    filterable_fields: BTreeSet<String>,
+    no_distribution_fields: BTreeSet<String>,
+    non_stored_fields: BTreeSet<String>,
+    ignored_fields: BTreeSet<String>,
+    sort_facet_values_by: BTreeMap<String, OrderBy>,
+    sortable_fields: BTreeSet<String>,
+    criteria: Vec<String>,
+    stop_words: BTreeSet<String>,
+    dictionary: BTreeSet<String>,
+    distinct_field: Option<String>,
+    synonyms: BTreeMap<String, Vec<String>>,
+    authorize_typos: bool,
+    enable_word_splitting: bool,
+    enable_word_concatenation: bool,
+    enable_negative_terms: bool,
+    min_word_len_one_typo: u8,
+    min_word_len_two_typos: u8,
+    exact_words: BTreeSet<String>,
+    exact_attributes: BTreeSet<String>,
+    max_values_per_facet: Option<usize>,
+    pagination_max_total_hits: Option<usize>,
+    documents_compression: bool,
+    proximity_precision: ProximityPrecision,
+    position_bucketing: PositionBucketing,
+    coerce_numeric_strings: bool,
+    count_diacritics: bool,
+    facet_level_group_size: usize,
+    facet_max_group_size: Option<usize>,
+    facet_min_level_size: usize,
+    prefix_settings: PrefixSettings,
+}
+
+impl DumpSettings {
+    fn from_index(index: &Index, rtxn: &RoTxn) -> Result<DumpSettings> {
+        let stop_words = match index.stop_words(rtxn)? {
+            Some(stop_words) => stop_words.into_stream().into_strs()?.into_iter().collect(),
+            None => BTreeSet::new(),
+        };
+        let exact_words = match index.exact_words(rtxn)? {
+            Some(exact_words) => exact_words.into_stream().into_strs()?.into_iter().collect(),
+            None => BTreeSet::new(),
+        };
+        // The synonyms are stored normalized and split into words.
+        let synonyms = index
+            .synonyms(rtxn)?
+            .into_iter()
+            .map(|(word, synonyms)| {
+                (word.join(" "), synonyms.into_iter().map(|synonym| synonym.join(" ")).collect())
+            })
+            .collect();
+
+        Ok(DumpSettings {
+            primary_key: index.primary_key(rtxn)?.map(String::from),
+            searchable_fields: index
+                .user_defined_searchable_fields(rtxn)?
+                .map(|fields| fields.into_iter().map(String::from).collect()),
+            searchable_fields_weights: index.searchable_fields_weights(rtxn)?,
+            displayed_fields: index
+                .displayed_fields(rtxn)?
+                .map(|fields| fields.into_iter().map(String::from).collect()),
+            filterable_fields: index.filterable_fields(rtxn)?.into_iter().collect(),
+            no_distribution_fields: index.no_distribution_fields(rtxn)?.into_iter().collect(),
+            non_stored_fields: index.non_stored_fields(rtxn)?.into_iter().collect(),
+            ignored_fields: index.ignored_fields(rtxn)?.into_iter().collect(),
+            sort_facet_values_by: index.sort_facet_values_by(rtxn)?.into_iter().collect(),
+            sortable_fields: index.sortable_fields(rtxn)?.into_iter().collect(),
+            criteria: index.criteria(rtxn)?.iter().map(ToString::to_string).collect(),
+            stop_words,
+            dictionary: index.dictionary(rtxn)?,
+            distinct_field: index.distinct_field(rtxn)?.map(String::from),
+            synonyms,
+            authorize_typos: index.authorize_typos(rtxn)?,
+            enable_word_splitting: index.enable_word_splitting(rtxn)?,
+            enable_word_concatenation: index.enable_word_concatenation(rtxn)?,
+            enable_negative_terms: index.enable_negative_terms(rtxn)?,
+            min_word_len_one_typo: index.min_word_len_one_typo(rtxn)?,
+            min_word_len_two_typos: index.min_word_len_two_typos(rtxn)?,
+            exact_words,
+            exact_attributes: index.exact_attributes(rtxn)?.into_iter().map(String::from).collect(),
+            max_values_per_facet: index.max_values_per_facet(rtxn)?,
+            pagination_max_total_hits: index.pagination_max_total_hits(rtxn)?,
+            documents_compression: index.documents_compression(rtxn)?,
+            proximity_precision: index.proximity_precision(rtxn)?,
+            position_bucketing: index.position_bucketing(rtxn)?,
+            coerce_numeric_strings: index.coerce_numeric_strings(rtxn)?,
+            count_diacritics: index.count_diacritics(rtxn)?,
+            facet_level_group_size: index.facet_level_group_size(rtxn)?,
+            facet_max_group_size: index.custom_facet_max_group_size(rtxn)?,
+            facet_min_level_size: index.facet_min_level_size(rtxn)?,
+            prefix_settings: index.prefix_settings(rtxn)?,
+        })
+    }
+
+    fn apply(self, builder: &mut Settings) {
+        let DumpSettings {
+            primary_key,
+            searchable_fields,
+            searchable_fields_weights,
+            displayed_fields,
+            filterable_fields,
+            no_distribution_fields,
+            non_stored_fields,
+            ignored_fields,
+            sort_facet_values_by,
+            sortable_fields,
+            criteria,
+            stop_words,
+            dictionary,
+            distinct_field,
+            synonyms,
+            authorize_typos,
+            enable_word_splitting,
+            enable_word_concatenation,
+            enable_negative_terms,
+            min_word_len_one_typo,
+            min_word_len_two_typos,
+            exact_words,
+            exact_attributes,
+            max_values_per_facet,
+            pagination_max_total_hits,
+            documents_compression,
+            proximity_precision,
+            position_bucketing,
+            coerce_numeric_strings,
+            count_diacritics,
+            facet_level_group_size,
+            facet_max_group_size,
+            facet_min_level_size,
+            prefix_settings,
+        } = self;
+
+        if let Some(primary_key) = primary_key {
+            builder.set_primary_key(primary_key);
+        }
+        if let Some(searchable_fields) = searchable_fields {
+            let fields = searchable_fields
+                .into_iter()
+                .map(|name| {
+                    let weight = searchable_fields_weights.get(&name).copied();
+                    (name, weight)
+                })
+                .collect();
+            builder.set_searchable_fields_with_weights(fields);
+        }
+        if let Some(displayed_fields) = displayed_fields {
+            builder.set_displayed_fields(displayed_fields);
+        }
+        builder.set_filterable_fields(filterable_fields.into_iter().collect());
+        builder.set_no_distribution_fields(no_distribution_fields.into_iter().collect());
+        builder.set_non_stored_fields(non_stored_fields.into_iter().collect());
+        builder.set_ignored_fields(ignored_fields.into_iter().collect());
+        builder.set_sort_facet_values_by(sort_facet_values_by.into_iter().collect());
+        builder.set_sortable_fields(sortable_fields.into_iter().collect());
+        builder.set_criteria(criteria);
+        builder.set_stop_words(stop_words);
+        builder.set_dictionary(dictionary);
+        if let Some(distinct_field) = distinct_field {
+            builder.set_distinct_field(distinct_field);
+        }
+        builder.set_synonyms(synonyms.into_iter().collect());
+        builder.set_autorize_typos(authorize_typos);
+        builder.set_enable_word_splitting(enable_word_splitting);
+        builder.set_enable_word_concatenation(enable_word_concatenation);
+        builder.set_enable_negative_terms(enable_negative_terms);
+        builder.set_min_word_len_one_typo(min_word_len_one_typo);
+        builder.set_min_word_len_two_typos(min_word_len_two_typos);
+        builder.set_exact_words(exact_words);
+        builder.set_exact_attributes(exact_attributes.into_iter().collect());
+        if let Some(max_values_per_facet) = max_values_per_facet {
+            builder.set_max_values_per_facet(max_values_per_facet);
+        }
+        if let Some(pagination_max_total_hits) = pagination_max_total_hits {
+            builder.set_pagination_max_total_hits(pagination_max_total_hits);
+        }
+        builder.set_documents_compression(documents_compression);
+        builder.set_proximity_precision(proximity_precision);
+        builder.set_position_bucketing(position_bucketing);
+        builder.set_coerce_numeric_strings(coerce_numeric_strings);
+        builder.set_count_diacritics(count_diacritics);
+        builder.set_facet_level_group_size(facet_level_group_size);
+        if let Some(facet_max_group_size) = facet_max_group_size {
+            builder.set_facet_max_group_size(facet_max_group_size);
+        }
+        builder.set_facet_min_level_size(facet_min_level_size);
+        let PrefixSettings { threshold, min_prefix_length, max_prefix_length, max_prefixes } =
+            prefix_settings;
+        builder.set_words_prefix_threshold(threshold);
+        builder.set_min_prefix_length(min_prefix_length);
+        builder.set_max_prefix_length(max_prefix_length);
+        if let Some(max_prefixes) = max_prefixes {
+            builder.set_max_prefixes(max_prefixes);
+        }
+    }
+}
+
+impl Index {
+    /// Writes the settings, the fields ids map and the documents of the index into the writer,
+    /// see the [module documentation](crate::dump) for the format.
+    ///
+    /// The writer is written in many small chunks, it should be buffered.
+    pub fn dump(&self, rtxn: &RoTxn, mut writer: impl Write) -> Result<()> {
+        let fields_ids_map = self.fields_ids_map(rtxn)?;
+        let header = DumpHeader {
+            dump_version: DUMP_VERSION,
+            milli_version: env!("CARGO_PKG_VERSION").to_string(),
+            fields_ids_map: fields_ids_map.clone(),
+        };
+        write_json_line(&mut writer, &header)?;
+        write_json_line(&mut writer, &DumpSettings::from_index(self, rtxn)?)?;
+
+        let fields: Vec<_> = fields_ids_map.ids().collect();
+        for result in self.all_documents(rtxn)? {
+            let (_docid, document) = result?;
+            let document = obkv_to_json(&fields, &fields_ids_map, document.as_obkv())?;
+            write_json_line(&mut writer, &document)?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Creates a new index at the given path and loads the dump read from the reader into it,
+    /// the settings are applied first and the documents are then indexed in a single batch.
+    pub fn load_dump<P: AsRef<Path>>(
+        options: EnvOpenOptions,
+        path: P,
+        indexer_config: &IndexerConfig,
+        reader: impl BufRead,
+    ) -> Result<Index> {
+        let mut lines = reader.lines();
+
+        let header: DumpHeader = match lines.next() {
+            Some(line) => serde_json::from_str(&line?).map_err(UserError::SerdeJson)?,
+            None => return Err(UserError::TruncatedDump { missing: "header" }.into()),
+        };
+        if header.dump_version > DUMP_VERSION {
+            return Err(UserError::UnsupportedDumpVersion {
+                dump_version: header.dump_version,
+                milli_version: header.milli_version,
+            }
+            .into());
+        }
+        let settings: DumpSettings = match lines.next() {
+            Some(line) => serde_json::from_str(&line?).map_err(UserError::SerdeJson)?,
+            None => return Err(UserError::TruncatedDump { missing: "settings" }.into()),
+        };
+
+        // The documents are written on disk to avoid keeping them in memory.
+        let mut builder = DocumentsBatchBuilder::new(tempfile::tempfile()?);
+        for line in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let document: Object = serde_json::from_str(&line).map_err(UserError::SerdeJson)?;
+            builder.append_json_object(&document)?;
+        }
+        let mut file = builder.into_inner()?;
+        file.seek(SeekFrom::Start(0))?;
+        let documents = DocumentsBatchReader::from_reader(file)?;
+
+        let index = Index::new(options, path)?;
+        let mut wtxn = index.write_txn()?;
+
+        // We restore the fields ids map first to keep the same field ids.
+        index.put_fields_ids_map(&mut wtxn, &header.fields_ids_map)?;
+
+        let mut builder = Settings::new(&mut wtxn, &index, indexer_config);
+        settings.apply(&mut builder);
+        builder.execute(|_| ())?;
+
+        if !documents.is_empty() {
+            let builder = IndexDocuments::new(
+                &mut wtxn,
+                &index,
+                indexer_config,
+                IndexDocumentsConfig::default(),
+                |_| (),
+                || false,
+            )?;
+            let (builder, user_error) = builder.add_documents(documents)?;
+            user_error?;
+            builder.execute()?;
+        }
+
+        wtxn.commit()?;
+        Ok(index)
+    }
+}
+
+/// Writes the documents of the index into the writer, one JSON object per line, and returns
+/// the number of documents written.
+///
+/// Every batch of `batch_size` documents is read with a new read transaction which is closed
+/// before the batch is written, a long export therefore never prevents the database from
+/// reusing its pages. The documents deleted during the export are skipped if they were not
+/// exported yet and the documents added after the last exported one are exported too.
+pub fn export_documents(index: &Index, mut writer: impl Write, batch_size: usize) -> Result<u64> {
+    let batch_size = batch_size.max(1);
+    let mut after = None;
+    let mut count = 0;
+    let mut buffer = Vec::new();
+
+    loop {
+        // The read transaction only lives for the time of reading the batch.
+        buffer.clear();
+        let next = {
+            let rtxn = index.read_txn()?;
+            let fields_ids_map = index.fields_ids_map(&rtxn)?;
+            let fields: Vec<_> = fields_ids_map.ids().collect();
+            let page = index.documents_page(&rtxn, after, batch_size)?;
+            for (_docid, document) in &page.documents {
+                let document = obkv_to_json(&fields, &fields_ids_map, document.as_obkv())?;
+                write_json_line(&mut buffer, &document)?;
+            }
+            count += page.documents.len() as u64;
+            page.next
+        };
+
+        writer.write_all(&buffer)?;
+        match next {
+            Some(next) => after = Some(next),
+            None => break,
+        }
+    }
+
+    writer.flush()?;
+    Ok(count)
+}
+
+fn write_json_line<W: Write, T: Serialize>(writer: &mut W, value: &T) -> Result<()> {
+    serde_json::to_writer(&mut *writer, value).map_err(InternalError::SerdeJson)?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use big_s::S;
+    use heed::EnvOpenOptions;
+    use maplit::{btreemap, btreeset, hashmap, hashset};
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::index::tests::TempIndex;
+    use crate::{Criterion, Filter, Search};
+
+    fn search_results(index: &Index, configure: impl Fn(&mut Search)) -> Vec<Object> {
+        let rtxn = index.read_txn().unwrap();
+        let mut search = index.search(&rtxn);
+        configure(&mut search);
+        let result = search.execute().unwrap();
+
+        let fields_ids_map = index.fields_ids_map(&rtxn).unwrap();
+        let fields: Vec<_> = fields_ids_map.ids().collect();
+        index
+            .documents(&rtxn, result.documents_ids)
+            .unwrap()
+            .iter()
+            .map(|(_, document)| {
+                obkv_to_json(&fields, &fields_ids_map, document.as_obkv()).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dump_and_load_dump() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|settings| {
+                settings.set_primary_key(S("id"));
+                settings.set_searchable_fields_with_weights(vec![
+                    (S("title"), None),
+                    (S("description"), Some(2)),
+                ]);
+                settings.set_filterable_fields(hashset! { S("genre"), S("year") });
+                settings.set_sortable_fields(hashset! { S("year") });
+                settings.set_criteria(vec![S("words"), S("typo"), S("sort"), S("proximity")]);
+                settings.set_stop_words(btreeset! { S("the") });
+                settings.set_dictionary(btreeset! { S("c++") });
+                settings.set_synonyms(hashmap! { S("scifi") => vec![S("science fiction")] });
+                settings.set_exact_words(btreeset! { S("Alien") });
+                settings.set_max_values_per_facet(42);
+            })
+            .unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 1, "title": "Alien", "description": "A science fiction horror film", "genre": "horror", "year": 1979 },
+                { "id": 2, "title": "The Thing", "description": "Science fiction in the antarctic", "genre": "horror", "year": 1982 },
+                { "id": "three", "title": "Amelie", "description": "A romantic comedy", "genre": "comedy", "year": 2001 },
+                { "id": 4, "title": "Aliens", "description": "The sequel of Alien", "genre": "action", "year": 1986, "extra": { "nested": true } }
+            ]))
+            .unwrap();
+
+        let mut dump = Vec::new();
+        let rtxn = index.read_txn().unwrap();
+        index.dump(&rtxn, &mut dump).unwrap();
+        drop(rtxn);
+
+        let dir = TempDir::new_in(".").unwrap();
+        let mut options = EnvOpenOptions::new();
+        options.map_size(4096 * 1000);
+        let config = IndexerConfig::default();
+        let loaded = Index::load_dump(options, dir.path(), &config, &dump[..]).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let loaded_rtxn = loaded.read_txn().unwrap();
+        assert_eq!(
+            index.fields_ids_map(&rtxn).unwrap().iter().collect::<Vec<_>>(),
+            loaded.fields_ids_map(&loaded_rtxn).unwrap().iter().collect::<Vec<_>>()
+        );
+        assert_eq!(loaded.primary_key(&loaded_rtxn).unwrap(), Some("id"));
+        assert_eq!(loaded.criteria(&loaded_rtxn).unwrap()[3], Criterion::Proximity);
+        assert_eq!(loaded.max_values_per_facet(&loaded_rtxn).unwrap(), Some(42));
+        assert_eq!(
+            loaded.searchable_fields_weights(&loaded_rtxn).unwrap(),
+            btreemap! { S("description") => 2 }
+        );
+        assert_eq!(index.synonyms(&rtxn).unwrap(), loaded.synonyms(&loaded_rtxn).unwrap());
+        assert_eq!(
+            index.external_documents_ids(&rtxn).unwrap().to_hash_map(),
+            loaded.external_documents_ids(&loaded_rtxn).unwrap().to_hash_map()
+        );
+        drop(rtxn);
+        drop(loaded_rtxn);
+
+        let searches: Vec<Box<dyn Fn(&mut Search)>> = vec![
+            Box::new(|search: &mut Search| {
+                search.query("alien");
+            }),
+            Box::new(|search: &mut Search| {
+                search.query("scifi");
+            }),
+            Box::new(|search: &mut Search| {
+                search.query("the thing");
+            }),
+            Box::new(|search: &mut Search| {
+                search.filter(Filter::from_str("genre = horror AND year > 1980").unwrap().unwrap());
+            }),
+            Box::new(|search: &mut Search| {
+                search.sort_criteria(vec!["year:desc".parse().unwrap()]);
+            }),
+        ];
+        for configure in searches {
+            assert_eq!(search_results(&index, &configure), search_results(&loaded, &configure));
+        }
+
+        // Dumping the loaded index gives the same dump.
+        let mut second_dump = Vec::new();
+        let rtxn = loaded.read_txn().unwrap();
+        loaded.dump(&rtxn, &mut second_dump).unwrap();
+        assert_eq!(String::from_utf8(dump).unwrap(), String::from_utf8(second_dump).unwrap());
+    }
+
+    #[test]
+    fn export_documents_in_batches() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 0, "title": "hello" },
+                { "id": 1, "title": "world", "tags": ["a", "b"] },
+                { "id": 2, "title": "kevin" },
+                { "id": 3 },
+                { "id": 4, "title": "bye" },
+            ]))
+            .unwrap();
+
+        for batch_size in [0, 1, 2, 5, 10] {
+            let mut export = Vec::new();
+            let count = export_documents(&index, &mut export, batch_size).unwrap();
+            assert_eq!(count, 5);
+            let ids: Vec<_> = serde_json::Deserializer::from_slice(&export)
+                .into_iter::<Object>()
+                .map(|document| document.unwrap()["id"].clone())
+                .collect();
+            assert_eq!(ids, vec![0, 1, 2, 3, 4]);
+        }
+
+        let empty = TempIndex::new();
+        let mut export = Vec::new();
+        assert_eq!(export_documents(&empty, &mut export, 10).unwrap(), 0);
+        assert!(export.is_empty());
+    }
+
+    #[test]
+    fn load_dump_from_a_newer_version() {
+        let dump = format!(
+            "{}\n{{}}\n",
+            serde_json::json!({
+                "dumpVersion": DUMP_VERSION + 1,
+                "milliVersion": "99.0.0",
+                "fieldsIdsMap": FieldsIdsMap::new(),
+            })
+        );
+
+        let dir = TempDir::new_in(".").unwrap();
+        let mut options = EnvOpenOptions::new();
+        options.map_size(4096 * 1000);
+        let config = IndexerConfig::default();
+        let error = Index::load_dump(options, dir.path(), &config, dump.as_bytes()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The dump has been created by milli v99.0.0 with the dump version 2, \
+             which is not supported by this version of milli."
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::BTreeSet;
 use std::convert::Infallible;
-use std::{io, str};
+use std::{fmt, io, str};
 
 use heed::{Error as HeedError, MdbError};
 use rayon::ThreadPoolBuildError;
@@ -11,7 +11,7 @@ use crate::documents::{self, DocumentsBatchCursorError};
 use crate::{CriterionError, DocumentId, FieldId, Object, SortError};
 
 pub fn is_reserved_keyword(keyword: &str) -> bool {
-    ["_geo", "_geoDistance", "_geoPoint", "_geoRadius"].contains(&keyword)
+    ["_geo", "_geoDistance", "_geoPoint", "_geoRadius", "_geoBoundingBox"].contains(&keyword)
 }
 
 #[derive(Error, Debug)]
@@ -42,8 +42,17 @@ pub enum InternalError {
     GrenadInvalidCompressionType,
     #[error("Invalid grenad file with an invalid version format.")]
     GrenadInvalidFormatVersion,
-    #[error("Invalid merge while processing {process}.")]
-    IndexingMergingKeys { process: &'static str },
+    #[error(
+        "Invalid merge while processing {process} with the `{strategy}` merge strategy{}",
+        .source.as_ref().map_or_else(|| ".".to_string(), |error| format!(": {}", error))
+    )]
+    IndexingMergingKeys {
+        process: &'static str,
+        strategy: &'static str,
+        source: Option<Box<Error>>,
+    },
+    #[error("Keys out of order in the {database} database, the key {key:?} follows the key {previous_key:?}.")]
+    KeysOutOfOrder { database: &'static str, previous_key: Vec<u8>, key: Vec<u8> },
     #[error("{}", HeedError::InvalidDatabaseTyping)]
     InvalidDatabaseTyping,
     #[error(transparent)]
@@ -92,16 +101,28 @@ pub enum UserError {
     CriterionError(#[from] CriterionError),
     #[error("Maximum number of documents reached.")]
     DocumentLimitReached,
+    #[error("The indexing operation has been aborted.")]
+    IndexingAborted,
     #[error(
         "Document identifier `{}` is invalid. \
 A document identifier can be of type integer or string, \
-only composed of alphanumeric characters (a-z A-Z 0-9), hyphens (-) and underscores (_).", .document_id.to_string()
+an integer must be between -9223372036854775808 and 18446744073709551615, \
+larger integers must be sent as strings, \
+a string must be non-empty, at most 512 bytes long and must not start or end with whitespace.", .document_id.to_string()
     )]
     InvalidDocumentId { document_id: Value },
     #[error("Invalid facet distribution, the fields `{}` are not set as filterable.",
         .invalid_facets_name.iter().map(AsRef::as_ref).collect::<Vec<&str>>().join(", ")
      )]
     InvalidFacetsDistribution { invalid_facets_name: BTreeSet<String> },
+    #[error("Invalid facet distribution, the fields `{}` are set as filterable without facet distribution.",
+        .facets_name.iter().map(AsRef::as_ref).collect::<Vec<&str>>().join(", ")
+     )]
+    FacetDistributionDisabled { facets_name: BTreeSet<String> },
+    #[error("Invalid facet distribution, the fields `{}` are not displayed.",
+        .facets_name.iter().map(AsRef::as_ref).collect::<Vec<&str>>().join(", ")
+     )]
+    UndisplayedFacetsDistribution { facets_name: BTreeSet<String> },
     #[error(transparent)]
     InvalidGeoField(#[from] GeoError),
     #[error("{0}")]
@@ -116,6 +137,22 @@ only composed of alphanumeric characters (a-z A-Z 0-9), hyphens (-) and undersco
         }
     )]
     InvalidSortableAttribute { field: String, valid_fields: BTreeSet<String> },
+    #[error("Attribute `{}` is not searchable. Available searchable attributes are: `{}`.",
+        .field,
+        .valid_fields.iter().map(AsRef::as_ref).collect::<Vec<&str>>().join(", ")
+    )]
+    InvalidSearchableAttribute { field: String, valid_fields: BTreeSet<String> },
+    #[error("Attribute `{}` is not filterable, the search results can only be grouped by a filterable attribute. Available filterable attributes are: `{}`.",
+        .field,
+        .valid_fields.iter().map(AsRef::as_ref).collect::<Vec<&str>>().join(", ")
+    )]
+    InvalidGroupByAttribute { field: String, valid_fields: BTreeSet<String> },
+    #[error("The search continuation is invalid, it must be a continuation returned by a previous search.")]
+    InvalidSearchContinuation,
+    #[error("The search continuation has expired because the index has been updated since it was returned, the search must be restarted from the first page.")]
+    ExpiredSearchContinuation,
+    #[error("The search continuation was returned by a search with other parameters, the query, the filter and the sort of the search must be the ones of the first page.")]
+    MismatchedSearchContinuation,
     #[error("{}", HeedError::BadOpenOptions)]
     InvalidLmdbOpenOptions,
     #[error("The sort ranking rule must be specified in the ranking rules settings to use the sort parameter at search time.")]
@@ -128,8 +165,20 @@ only composed of alphanumeric characters (a-z A-Z 0-9), hyphens (-) and undersco
     MissingDocumentId { primary_key: String, document: Object },
     #[error("Document have too many matching `{}` attribute: `{}`.", .primary_key, serde_json::to_string(.document).unwrap())]
     TooManyDocumentIds { primary_key: String, document: Object },
+    #[error("The documents at the positions {first_position} and {position} of the batch have the same id `{document_id}`, the document `{}` is replaced or updated by the document `{}`.", serde_json::to_string(.first_document).unwrap(), serde_json::to_string(.document).unwrap())]
+    DuplicateDocumentId {
+        document_id: String,
+        first_position: u32,
+        first_document: Object,
+        position: u32,
+        document: Object,
+    },
     #[error("The primary key inference process failed because the engine did not find any fields containing `id` substring in their name. If your document identifier does not contain any `id` substring, you can set the primary key of the index.")]
     MissingPrimaryKey,
+    #[error("The primary key inference process failed because the engine found several nested fields containing `id` substring in their name: {}. You can set the primary key of the index to the one that identifies your documents.",
+        .candidates.iter().map(AsRef::as_ref).collect::<Vec<&str>>().join(", ")
+    )]
+    MultiplePrimaryKeyCandidatesFound { candidates: Vec<String> },
     #[error("There is no more space left on the device. Consider increasing the size of the disk/partition.")]
     NoSpaceLeftOnDevice,
     #[error("Index already has a primary key: `{0}`.")]
@@ -140,13 +189,41 @@ only composed of alphanumeric characters (a-z A-Z 0-9), hyphens (-) and undersco
     SortError(#[from] SortError),
     #[error("An unknown internal document id have been used: `{document_id}`.")]
     UnknownInternalDocumentId { document_id: DocumentId },
+    #[error("The dump is truncated, its {missing} is missing.")]
+    TruncatedDump { missing: &'static str },
+    #[error("The dump has been created by milli v{milli_version} with the dump version {dump_version}, which is not supported by this version of milli.")]
+    UnsupportedDumpVersion { dump_version: u32, milli_version: String },
     #[error("`minWordSizeForTypos` setting is invalid. `oneTypo` and `twoTypos` fields should be between `0` and `255`, and `twoTypos` should be greater or equals to `oneTypo` but found `oneTypo: {0}` and twoTypos: {1}`.")]
     InvalidMinTypoWordLenSetting(u8, u8),
+    #[error("The field `{field}` of the document `{document_id}` contains {values} facet values, more than the limit of {limit} values.")]
+    TooManyFacetValues { document_id: Value, field: String, values: usize, limit: usize },
+    #[error("The document `{document_id}` contains {fields} fields, more than the limit of {limit} fields.")]
+    TooManyFields { document_id: Value, fields: usize, limit: usize },
+    #[error("The field `{field}` of the document `{document_id}` cannot be added to the index, it already contains the limit of {limit} fields.")]
+    IndexFieldsLimitReached { document_id: Value, field: String, limit: usize },
+    #[error("The facet levels settings are invalid. The group size should be greater than or equal to `2`, the maximum group size should be greater than or equal to the group size and the minimum level size should be greater than `0` but found a group size of `{group_size}`, a maximum group size of `{max_group_size}` and a minimum level size of `{min_level_size}`.")]
+    InvalidFacetLevelsSetting { group_size: usize, max_group_size: usize, min_level_size: usize },
+    #[error("The prefix settings are invalid. The minimum prefix length should be greater than `0` and lower than or equal to the maximum prefix length but found a minimum prefix length of `{min_prefix_length}` and a maximum prefix length of `{max_prefix_length}`.")]
+    InvalidPrefixSettings { min_prefix_length: usize, max_prefix_length: usize },
+    #[error("The indexes cannot be merged, the primary key `{found}` is not the expected primary key `{expected}`.")]
+    MergePrimaryKeyMismatch { expected: String, found: String },
+    #[error("The indexes cannot be merged, their `{setting}` settings are different.")]
+    MergeSettingsMismatch { setting: &'static str },
+    #[error("The indexes cannot be merged, the external ids cannot be prefixed because the primary key `{primary_key}` is not a top-level field of the documents of a source index.")]
+    MergeInvalidPrimaryKey { primary_key: String },
+    #[error("The document `{document_id}` cannot be partially updated because the index does not store the values of its non-stored attributes `{}`, the whole document must be replaced instead.",
+        .attributes.iter().map(AsRef::as_ref).collect::<Vec<&str>>().join(", ")
+    )]
+    PartialUpdateWithNonStoredAttributes { document_id: Value, attributes: BTreeSet<String> },
+    #[error("The settings update requires reindexing the documents but the values of the non-stored attributes `{}` are not stored in the index and would be lost.",
+        .attributes.iter().map(AsRef::as_ref).collect::<Vec<&str>>().join(", ")
+    )]
+    ReindexWithNonStoredAttributes { attributes: BTreeSet<String> },
 }
 
 #[derive(Error, Debug)]
 pub enum GeoError {
-    #[error("The `_geo` field in the document with the id: `{document_id}` is not an object. Was expecting an object with the `_geo.lat` and `_geo.lng` fields but instead got `{value}`.")]
+    #[error("The `_geo` field in the document with the id: `{document_id}` is not an object nor an array. Was expecting an object with the `_geo.lat` and `_geo.lng` fields, a `[lng, lat]` array or a GeoJSON point but instead got `{value}`.")]
     NotAnObject { document_id: Value, value: Value },
     #[error("Could not find latitude nor longitude in the document with the id: `{document_id}`. Was expecting `_geo.lat` and `_geo.lng` fields.")]
     MissingLatitudeAndLongitude { document_id: Value },
@@ -160,6 +237,178 @@ pub enum GeoError {
     BadLatitude { document_id: Value, value: Value },
     #[error("Could not parse longitude in the document with the id: `{document_id}`. Was expecting a finite number but instead got `{value}`.")]
     BadLongitude { document_id: Value, value: Value },
+    #[error("Could not parse the `_geo` field in the document with the id: `{document_id}`. Was expecting a `[lng, lat]` array or a GeoJSON point like `{{\"type\": \"Point\", \"coordinates\": [lng, lat]}}` but instead got `{value}`.")]
+    BadGeoPoint { document_id: Value, value: Value },
+    #[error("The latitude in the document with the id: `{document_id}` is out of range. Was expecting a latitude between `-90` and `90` but instead got `{value}`.")]
+    LatitudeOutOfRange { document_id: Value, value: f64 },
+    #[error("The longitude in the document with the id: `{document_id}` is out of range. Was expecting a longitude between `-180` and `180` but instead got `{value}`.")]
+    LongitudeOutOfRange { document_id: Value, value: f64 },
+}
+
+/// A stable, machine-readable code identifying the kind of an error,
+/// so that the callers don't have to match on the error messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// An internal document id that is unknown or soft deleted has been used.
+    InvalidInternalDocumentId,
+    /// A document contains more fields than the index can hold
+    /// or than the limit of the indexer configuration.
+    MaxFieldsLimitExceeded,
+    /// A field of a document contains more facet values than the limit of the indexer.
+    MaxFacetValuesLimitExceeded,
+    InvalidRankingRule,
+    MaxDocumentsLimitExceeded,
+    IndexingAborted,
+    InvalidDocumentId,
+    MissingDocumentId,
+    TooManyDocumentIds,
+    /// The primary key could not be inferred from the documents.
+    MissingPrimaryKey,
+    PrimaryKeyCannotBeChanged,
+    /// The documents payload could not be parsed.
+    MalformedPayload,
+    InvalidFacetDistribution,
+    InvalidGeoField,
+    InvalidFilter,
+    InvalidSort,
+    InvalidSortableAttribute,
+    InvalidSearchableAttribute,
+    InvalidGroupByAttribute,
+    SortRankingRuleMissing,
+    InvalidSearchContinuation,
+    ExpiredSearchContinuation,
+    MismatchedSearchContinuation,
+    InvalidMinTypoWordLen,
+    InvalidFacetLevels,
+    InvalidPrefixSettings,
+    IncompatibleIndexes,
+    /// An operation needs the values of attributes that are not stored in the documents database.
+    NonStoredAttributes,
+    InvalidStoreOptions,
+    InvalidStoreFile,
+    MaxDatabaseSizeReached,
+    NoSpaceLeftOnDevice,
+    /// The dump is truncated or malformed.
+    InvalidDump,
+    UnsupportedDumpVersion,
+    /// An I/O error that is not caused by the user.
+    Io,
+    /// An error that is not caused by the user.
+    Internal,
+}
+
+impl ErrorCode {
+    /// Returns the name of the code in snake case, e.g. `invalid_document_id`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorCode::InvalidInternalDocumentId => "invalid_internal_document_id",
+            ErrorCode::MaxFieldsLimitExceeded => "max_fields_limit_exceeded",
+            ErrorCode::MaxFacetValuesLimitExceeded => "max_facet_values_limit_exceeded",
+            ErrorCode::InvalidRankingRule => "invalid_ranking_rule",
+            ErrorCode::MaxDocumentsLimitExceeded => "max_documents_limit_exceeded",
+            ErrorCode::IndexingAborted => "indexing_aborted",
+            ErrorCode::InvalidDocumentId => "invalid_document_id",
+            ErrorCode::MissingDocumentId => "missing_document_id",
+            ErrorCode::TooManyDocumentIds => "too_many_document_ids",
+            ErrorCode::MissingPrimaryKey => "missing_primary_key",
+            ErrorCode::PrimaryKeyCannotBeChanged => "primary_key_cannot_be_changed",
+            ErrorCode::MalformedPayload => "malformed_payload",
+            ErrorCode::InvalidFacetDistribution => "invalid_facet_distribution",
+            ErrorCode::InvalidGeoField => "invalid_geo_field",
+            ErrorCode::InvalidFilter => "invalid_filter",
+            ErrorCode::InvalidSort => "invalid_sort",
+            ErrorCode::InvalidSortableAttribute => "invalid_sortable_attribute",
+            ErrorCode::InvalidSearchableAttribute => "invalid_searchable_attribute",
+            ErrorCode::InvalidGroupByAttribute => "invalid_group_by_attribute",
+            ErrorCode::SortRankingRuleMissing => "sort_ranking_rule_missing",
+            ErrorCode::InvalidSearchContinuation => "invalid_search_continuation",
+            ErrorCode::ExpiredSearchContinuation => "expired_search_continuation",
+            ErrorCode::MismatchedSearchContinuation => "mismatched_search_continuation",
+            ErrorCode::InvalidMinTypoWordLen => "invalid_min_typo_word_len",
+            ErrorCode::InvalidFacetLevels => "invalid_facet_levels",
+            ErrorCode::InvalidPrefixSettings => "invalid_prefix_settings",
+            ErrorCode::IncompatibleIndexes => "incompatible_indexes",
+            ErrorCode::NonStoredAttributes => "non_stored_attributes",
+            ErrorCode::InvalidStoreOptions => "invalid_store_options",
+            ErrorCode::InvalidStoreFile => "invalid_store_file",
+            ErrorCode::MaxDatabaseSizeReached => "max_database_size_reached",
+            ErrorCode::NoSpaceLeftOnDevice => "no_space_left_on_device",
+            ErrorCode::InvalidDump => "invalid_dump",
+            ErrorCode::UnsupportedDumpVersion => "unsupported_dump_version",
+            ErrorCode::Io => "io",
+            ErrorCode::Internal => "internal",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Error {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::UserError(error) => error.code(),
+            Error::InternalError(InternalError::DocumentsError(error)) => error.code(),
+            Error::InternalError(_) => ErrorCode::Internal,
+            Error::IoError(_) => ErrorCode::Io,
+        }
+    }
+}
+
+impl UserError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            UserError::AccessingSoftDeletedDocument { .. } => ErrorCode::InvalidInternalDocumentId,
+            UserError::AttributeLimitReached => ErrorCode::MaxFieldsLimitExceeded,
+            UserError::CriterionError(_) => ErrorCode::InvalidRankingRule,
+            UserError::DocumentLimitReached => ErrorCode::MaxDocumentsLimitExceeded,
+            UserError::IndexingAborted => ErrorCode::IndexingAborted,
+            UserError::InvalidDocumentId { .. } => ErrorCode::InvalidDocumentId,
+            UserError::InvalidFacetsDistribution { .. } => ErrorCode::InvalidFacetDistribution,
+            UserError::FacetDistributionDisabled { .. } => ErrorCode::InvalidFacetDistribution,
+            UserError::UndisplayedFacetsDistribution { .. } => ErrorCode::InvalidFacetDistribution,
+            UserError::InvalidGeoField(_) => ErrorCode::InvalidGeoField,
+            UserError::InvalidFilter(_) => ErrorCode::InvalidFilter,
+            UserError::InvalidSortableAttribute { .. } => ErrorCode::InvalidSortableAttribute,
+            UserError::InvalidSearchableAttribute { .. } => ErrorCode::InvalidSearchableAttribute,
+            UserError::InvalidGroupByAttribute { .. } => ErrorCode::InvalidGroupByAttribute,
+            UserError::InvalidSearchContinuation => ErrorCode::InvalidSearchContinuation,
+            UserError::ExpiredSearchContinuation => ErrorCode::ExpiredSearchContinuation,
+            UserError::MismatchedSearchContinuation => ErrorCode::MismatchedSearchContinuation,
+            UserError::InvalidLmdbOpenOptions => ErrorCode::InvalidStoreOptions,
+            UserError::SortRankingRuleMissing => ErrorCode::SortRankingRuleMissing,
+            UserError::InvalidStoreFile => ErrorCode::InvalidStoreFile,
+            UserError::MaxDatabaseSizeReached => ErrorCode::MaxDatabaseSizeReached,
+            UserError::MissingDocumentId { .. } => ErrorCode::MissingDocumentId,
+            UserError::TooManyDocumentIds { .. } => ErrorCode::TooManyDocumentIds,
+            UserError::DuplicateDocumentId { .. } => ErrorCode::InvalidDocumentId,
+            UserError::MissingPrimaryKey | UserError::MultiplePrimaryKeyCandidatesFound { .. } => {
+                ErrorCode::MissingPrimaryKey
+            }
+            UserError::NoSpaceLeftOnDevice => ErrorCode::NoSpaceLeftOnDevice,
+            UserError::PrimaryKeyCannotBeChanged(_) => ErrorCode::PrimaryKeyCannotBeChanged,
+            UserError::SerdeJson(_) => ErrorCode::MalformedPayload,
+            UserError::SortError(_) => ErrorCode::InvalidSort,
+            UserError::UnknownInternalDocumentId { .. } => ErrorCode::InvalidInternalDocumentId,
+            UserError::TruncatedDump { .. } => ErrorCode::InvalidDump,
+            UserError::UnsupportedDumpVersion { .. } => ErrorCode::UnsupportedDumpVersion,
+            UserError::InvalidMinTypoWordLenSetting(..) => ErrorCode::InvalidMinTypoWordLen,
+            UserError::InvalidFacetLevelsSetting { .. } => ErrorCode::InvalidFacetLevels,
+            UserError::InvalidPrefixSettings { .. } => ErrorCode::InvalidPrefixSettings,
+            UserError::MergePrimaryKeyMismatch { .. }
+            | UserError::MergeSettingsMismatch { .. }
+            | UserError::MergeInvalidPrimaryKey { .. } => ErrorCode::IncompatibleIndexes,
+            UserError::TooManyFacetValues { .. } => ErrorCode::MaxFacetValuesLimitExceeded,
+            UserError::TooManyFields { .. } | UserError::IndexFieldsLimitReached { .. } => {
+                ErrorCode::MaxFieldsLimitExceeded
+            }
+            UserError::PartialUpdateWithNonStoredAttributes { .. }
+            | UserError::ReindexWithNonStoredAttributes { .. } => ErrorCode::NonStoredAttributes,
+        }
+    }
 }
 
 /// A little macro helper to autogenerate From implementation that needs two `Into`.
@@ -266,3 +515,248 @@ fn conditionally_lookup_for_error_message() {
         assert_eq!(err.to_string(), format!("{} {}", prefix, suffix));
     }
 }
+
+#[test]
+fn every_user_error_has_a_code() {
+    use serde_json::json;
+
+    let object = Object::new();
+    let json_error = serde_json::from_str::<Value>("{").unwrap_err();
+    let errors = vec![
+        (
+            UserError::AccessingSoftDeletedDocument { document_id: 1 },
+            ErrorCode::InvalidInternalDocumentId,
+        ),
+        (UserError::AttributeLimitReached, ErrorCode::MaxFieldsLimitExceeded),
+        (
+            UserError::CriterionError(CriterionError::InvalidName { name: "hello".to_string() }),
+            ErrorCode::InvalidRankingRule,
+        ),
+        (UserError::DocumentLimitReached, ErrorCode::MaxDocumentsLimitExceeded),
+        (UserError::IndexingAborted, ErrorCode::IndexingAborted),
+        (UserError::InvalidDocumentId { document_id: json!("a b") }, ErrorCode::InvalidDocumentId),
+        (
+            UserError::InvalidFacetsDistribution { invalid_facets_name: BTreeSet::new() },
+            ErrorCode::InvalidFacetDistribution,
+        ),
+        (
+            UserError::FacetDistributionDisabled { facets_name: BTreeSet::new() },
+            ErrorCode::InvalidFacetDistribution,
+        ),
+        (
+            UserError::UndisplayedFacetsDistribution { facets_name: BTreeSet::new() },
+            ErrorCode::InvalidFacetDistribution,
+        ),
+        (
+            UserError::InvalidGeoField(GeoError::MissingLatitude { document_id: json!(1) }),
+            ErrorCode::InvalidGeoField,
+        ),
+        (UserError::InvalidFilter("hello".to_string()), ErrorCode::InvalidFilter),
+        (
+            UserError::InvalidSortableAttribute {
+                field: "name".to_string(),
+                valid_fields: BTreeSet::new(),
+            },
+            ErrorCode::InvalidSortableAttribute,
+        ),
+        (
+            UserError::InvalidSearchableAttribute {
+                field: "name".to_string(),
+                valid_fields: BTreeSet::new(),
+            },
+            ErrorCode::InvalidSearchableAttribute,
+        ),
+        (
+            UserError::InvalidGroupByAttribute {
+                field: "name".to_string(),
+                valid_fields: BTreeSet::new(),
+            },
+            ErrorCode::InvalidGroupByAttribute,
+        ),
+        (UserError::InvalidSearchContinuation, ErrorCode::InvalidSearchContinuation),
+        (UserError::ExpiredSearchContinuation, ErrorCode::ExpiredSearchContinuation),
+        (UserError::MismatchedSearchContinuation, ErrorCode::MismatchedSearchContinuation),
+        (UserError::InvalidLmdbOpenOptions, ErrorCode::InvalidStoreOptions),
+        (UserError::SortRankingRuleMissing, ErrorCode::SortRankingRuleMissing),
+        (UserError::InvalidStoreFile, ErrorCode::InvalidStoreFile),
+        (UserError::MaxDatabaseSizeReached, ErrorCode::MaxDatabaseSizeReached),
+        (
+            UserError::MissingDocumentId {
+                primary_key: "id".to_string(),
+                document: object.clone(),
+            },
+            ErrorCode::MissingDocumentId,
+        ),
+        (
+            UserError::DuplicateDocumentId {
+                document_id: "1".to_string(),
+                first_position: 0,
+                first_document: object.clone(),
+                position: 1,
+                document: object.clone(),
+            },
+            ErrorCode::InvalidDocumentId,
+        ),
+        (
+            UserError::TooManyDocumentIds { primary_key: "id".to_string(), document: object },
+            ErrorCode::TooManyDocumentIds,
+        ),
+        (UserError::MissingPrimaryKey, ErrorCode::MissingPrimaryKey),
+        (
+            UserError::MultiplePrimaryKeyCandidatesFound {
+                candidates: vec!["meta.id".to_string(), "author.id".to_string()],
+            },
+            ErrorCode::MissingPrimaryKey,
+        ),
+        (UserError::NoSpaceLeftOnDevice, ErrorCode::NoSpaceLeftOnDevice),
+        (
+            UserError::PrimaryKeyCannotBeChanged("id".to_string()),
+            ErrorCode::PrimaryKeyCannotBeChanged,
+        ),
+        (UserError::SerdeJson(json_error), ErrorCode::MalformedPayload),
+        (UserError::SortError(SortError::TooManyGeoPoints), ErrorCode::InvalidSort),
+        (
+            UserError::UnknownInternalDocumentId { document_id: 1 },
+            ErrorCode::InvalidInternalDocumentId,
+        ),
+        (UserError::TruncatedDump { missing: "settings" }, ErrorCode::InvalidDump),
+        (
+            UserError::UnsupportedDumpVersion {
+                dump_version: 0,
+                milli_version: "0.0.0".to_string(),
+            },
+            ErrorCode::UnsupportedDumpVersion,
+        ),
+        (UserError::InvalidMinTypoWordLenSetting(3, 2), ErrorCode::InvalidMinTypoWordLen),
+        (
+            UserError::InvalidFacetLevelsSetting {
+                group_size: 1,
+                max_group_size: 8,
+                min_level_size: 5,
+            },
+            ErrorCode::InvalidFacetLevels,
+        ),
+        (
+            UserError::InvalidPrefixSettings { min_prefix_length: 5, max_prefix_length: 4 },
+            ErrorCode::InvalidPrefixSettings,
+        ),
+        (
+            UserError::MergePrimaryKeyMismatch {
+                expected: "id".to_string(),
+                found: "uid".to_string(),
+            },
+            ErrorCode::IncompatibleIndexes,
+        ),
+        (
+            UserError::MergeSettingsMismatch { setting: "searchableAttributes" },
+            ErrorCode::IncompatibleIndexes,
+        ),
+        (
+            UserError::MergeInvalidPrimaryKey { primary_key: "metadata.id".to_string() },
+            ErrorCode::IncompatibleIndexes,
+        ),
+        (
+            UserError::TooManyFacetValues {
+                document_id: json!(1),
+                field: "tags".to_string(),
+                values: 10,
+                limit: 5,
+            },
+            ErrorCode::MaxFacetValuesLimitExceeded,
+        ),
+        (
+            UserError::TooManyFields { document_id: json!(1), fields: 10, limit: 5 },
+            ErrorCode::MaxFieldsLimitExceeded,
+        ),
+        (
+            UserError::IndexFieldsLimitReached {
+                document_id: json!(1),
+                field: "a.b".to_string(),
+                limit: 5,
+            },
+            ErrorCode::MaxFieldsLimitExceeded,
+        ),
+        (
+            UserError::PartialUpdateWithNonStoredAttributes {
+                document_id: json!(1),
+                attributes: BTreeSet::from(["body".to_string()]),
+            },
+            ErrorCode::NonStoredAttributes,
+        ),
+        (
+            UserError::ReindexWithNonStoredAttributes {
+                attributes: BTreeSet::from(["body".to_string()]),
+            },
+            ErrorCode::NonStoredAttributes,
+        ),
+    ];
+
+    for (error, code) in errors {
+        // Adding a variant to the `UserError` enum breaks this match,
+        // the new variant must then be added to the list above too.
+        match error {
+            UserError::AccessingSoftDeletedDocument { .. }
+            | UserError::AttributeLimitReached
+            | UserError::CriterionError(_)
+            | UserError::DocumentLimitReached
+            | UserError::IndexingAborted
+            | UserError::InvalidDocumentId { .. }
+            | UserError::InvalidFacetsDistribution { .. }
+            | UserError::FacetDistributionDisabled { .. }
+            | UserError::UndisplayedFacetsDistribution { .. }
+            | UserError::InvalidGeoField(_)
+            | UserError::InvalidFilter(_)
+            | UserError::InvalidSortableAttribute { .. }
+            | UserError::InvalidSearchableAttribute { .. }
+            | UserError::InvalidGroupByAttribute { .. }
+            | UserError::InvalidSearchContinuation
+            | UserError::ExpiredSearchContinuation
+            | UserError::MismatchedSearchContinuation
+            | UserError::InvalidLmdbOpenOptions
+            | UserError::SortRankingRuleMissing
+            | UserError::InvalidStoreFile
+            | UserError::MaxDatabaseSizeReached
+            | UserError::MissingDocumentId { .. }
+            | UserError::TooManyDocumentIds { .. }
+            | UserError::DuplicateDocumentId { .. }
+            | UserError::MissingPrimaryKey
+            | UserError::MultiplePrimaryKeyCandidatesFound { .. }
+            | UserError::NoSpaceLeftOnDevice
+            | UserError::PrimaryKeyCannotBeChanged(_)
+            | UserError::SerdeJson(_)
+            | UserError::SortError(_)
+            | UserError::UnknownInternalDocumentId { .. }
+            | UserError::TruncatedDump { .. }
+            | UserError::UnsupportedDumpVersion { .. }
+            | UserError::InvalidMinTypoWordLenSetting(..)
+            | UserError::InvalidFacetLevelsSetting { .. }
+            | UserError::InvalidPrefixSettings { .. }
+            | UserError::MergePrimaryKeyMismatch { .. }
+            | UserError::MergeSettingsMismatch { .. }
+            | UserError::MergeInvalidPrimaryKey { .. }
+            | UserError::TooManyFacetValues { .. }
+            | UserError::TooManyFields { .. }
+            | UserError::IndexFieldsLimitReached { .. }
+            | UserError::PartialUpdateWithNonStoredAttributes { .. }
+            | UserError::ReindexWithNonStoredAttributes { .. } => (),
+        }
+        assert_eq!(error.code(), code, "{}", error);
+        assert_eq!(Error::from(error).code(), code);
+    }
+}
+
+#[test]
+fn documents_errors_codes() {
+    let error = documents::Error::InvalidDocumentFormat;
+    assert_eq!(error.code(), ErrorCode::MalformedPayload);
+    assert_eq!(Error::from(error).code(), ErrorCode::MalformedPayload);
+
+    let error =
+        documents::Error::DocumentTooLarge { size: 2048, limit: 1024, partial_external_id: None };
+    assert_eq!(Error::from(error).code(), ErrorCode::MalformedPayload);
+
+    let error = documents::Error::UnknownFieldId { field_id: 42 };
+    assert_eq!(error.code(), ErrorCode::Internal);
+    assert_eq!(Error::from(io::Error::from(io::ErrorKind::Other)).code(), ErrorCode::Io);
+    assert_eq!(ErrorCode::InvalidDocumentId.to_string(), "invalid_document_id");
+}
@@ -1,7 +1,7 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::convert::TryInto;
-use std::{fmt, str};
+use std::{fmt, iter, str};
 
 use fst::map::IndexedValue;
 use fst::{IntoStreamer, Streamer};
@@ -9,37 +9,73 @@ use roaring::RoaringBitmap;
 
 const DELETED_ID: u64 = u64::MAX;
 
+/// The maximum number of soft tiers, the newest tiers are merged together above it.
+const MAX_SOFT_TIERS: usize = 8;
+
+/// The external documents ids, stored as a hard map and a stack of soft tiers.
+///
+/// Every insertion or deletion pushes a new soft tier that only contains the updated ids,
+/// a deleted id is associated with `DELETED_ID` to hide the older tiers and the hard map.
+/// The lookups consult the tiers from the newest to the oldest one and then the hard map.
+///
+/// A tier is merged with the next one when it is not at least twice as large, the tiers
+/// are then decreasing in size and each id is merged a logarithmic number of times.
+/// The tiers are merged into the hard map once they contain half as many ids.
 pub struct ExternalDocumentsIds<'a> {
     pub(crate) hard: fst::Map<Cow<'a, [u8]>>,
-    pub(crate) soft: fst::Map<Cow<'a, [u8]>>,
+    /// The soft tiers, from the oldest to the newest one.
+    pub(crate) soft: Vec<fst::Map<Cow<'a, [u8]>>>,
+    /// Whether the hard map must be written again in the database.
+    pub(crate) hard_changed: bool,
+    /// The number of oldest soft tiers that are the same as in the database.
+    pub(crate) unchanged_soft: usize,
     soft_deleted_docids: RoaringBitmap,
 }
 
 impl<'a> ExternalDocumentsIds<'a> {
     pub fn new(
         hard: fst::Map<Cow<'a, [u8]>>,
-        soft: fst::Map<Cow<'a, [u8]>>,
+        soft: Vec<fst::Map<Cow<'a, [u8]>>>,
         soft_deleted_docids: RoaringBitmap,
     ) -> ExternalDocumentsIds<'a> {
-        ExternalDocumentsIds { hard, soft, soft_deleted_docids }
+        let unchanged_soft = soft.len();
+        ExternalDocumentsIds {
+            hard,
+            soft,
+            hard_changed: false,
+            unchanged_soft,
+            soft_deleted_docids,
+        }
     }
 
     pub fn into_static(self) -> ExternalDocumentsIds<'static> {
         ExternalDocumentsIds {
             hard: self.hard.map_data(|c| Cow::Owned(c.into_owned())).unwrap(),
-            soft: self.soft.map_data(|c| Cow::Owned(c.into_owned())).unwrap(),
+            soft: self
+                .soft
+                .into_iter()
+                .map(|tier| tier.map_data(|c| Cow::Owned(c.into_owned())).unwrap())
+                .collect(),
+            hard_changed: self.hard_changed,
+            unchanged_soft: self.unchanged_soft,
             soft_deleted_docids: self.soft_deleted_docids,
         }
     }
 
     /// Returns `true` if hard and soft external documents lists are empty.
     pub fn is_empty(&self) -> bool {
-        self.hard.is_empty() && self.soft.is_empty()
+        self.hard.is_empty() && self.soft.iter().all(|tier| tier.is_empty())
     }
 
     pub fn get<A: AsRef<[u8]>>(&self, external_id: A) -> Option<u32> {
         let external_id = external_id.as_ref();
-        match self.soft.get(external_id).or_else(|| self.hard.get(external_id)) {
+        let id = self
+            .soft
+            .iter()
+            .rev()
+            .find_map(|tier| tier.get(external_id))
+            .or_else(|| self.hard.get(external_id));
+        match id {
             Some(id) if id != DELETED_ID && !self.soft_deleted_docids.contains(id as u32) => {
                 Some(id.try_into().unwrap())
             }
@@ -48,44 +84,20 @@ impl<'a> ExternalDocumentsIds<'a> {
     }
 
     pub fn delete_ids<A: AsRef<[u8]>>(&mut self, other: fst::Set<A>) -> fst::Result<()> {
-        let other = fst::Map::from(other.into_fst());
-        let union_op = self.soft.op().add(&other).r#union();
-
-        let mut iter = union_op.into_stream();
-        let mut new_soft_builder = fst::MapBuilder::memory();
-        while let Some((external_id, docids)) = iter.next() {
-            if docids.iter().any(|v| v.index == 1) {
-                // If the `other` set returns a value here it means
-                // that it must be marked as deleted.
-                new_soft_builder.insert(external_id, DELETED_ID)?;
-            } else {
-                let value = docids.iter().find(|v| v.index == 0).unwrap().value;
-                new_soft_builder.insert(external_id, value)?;
-            }
+        let mut new_tier_builder = fst::MapBuilder::memory();
+        let mut iter = other.stream();
+        while let Some(external_id) = iter.next() {
+            new_tier_builder.insert(external_id, DELETED_ID)?;
         }
 
         drop(iter);
 
-        // We save this new map as the new soft map.
-        self.soft = new_soft_builder.into_map().map_data(Cow::Owned)?;
-        self.merge_soft_into_hard()
+        self.push_soft_tier(new_tier_builder.into_map().map_data(Cow::Owned)?)
     }
 
     pub fn insert_ids<A: AsRef<[u8]>>(&mut self, other: &fst::Map<A>) -> fst::Result<()> {
-        let union_op = self.soft.op().add(other).r#union();
-
-        let mut new_soft_builder = fst::MapBuilder::memory();
-        let mut iter = union_op.into_stream();
-        while let Some((external_id, marked_docids)) = iter.next() {
-            let id = indexed_last_value(marked_docids).unwrap();
-            new_soft_builder.insert(external_id, id)?;
-        }
-
-        drop(iter);
-
-        // We save the new map as the new soft map.
-        self.soft = new_soft_builder.into_map().map_data(Cow::Owned)?;
-        self.merge_soft_into_hard()
+        let new_tier = fst::Map::new(other.as_fst().as_bytes().to_vec())?;
+        self.push_soft_tier(new_tier.map_data(Cow::Owned)?)
     }
 
     /// An helper function to debug this type, returns an `HashMap` of both,
@@ -93,7 +105,11 @@ impl<'a> ExternalDocumentsIds<'a> {
     pub fn to_hash_map(&self) -> HashMap<String, u32> {
         let mut map = HashMap::new();
 
-        let union_op = self.hard.op().add(&self.soft).r#union();
+        let mut union_op = self.hard.op();
+        for tier in &self.soft {
+            union_op = union_op.add(tier);
+        }
+        let union_op = union_op.r#union();
         let mut iter = union_op.into_stream();
         while let Some((external_id, marked_docids)) = iter.next() {
             let id = indexed_last_value(marked_docids).unwrap();
@@ -106,23 +122,33 @@ impl<'a> ExternalDocumentsIds<'a> {
         map
     }
 
-    fn merge_soft_into_hard(&mut self) -> fst::Result<()> {
-        if self.soft.len() >= self.hard.len() / 2 {
-            let union_op = self.hard.op().add(&self.soft).r#union();
-
-            let mut iter = union_op.into_stream();
-            let mut new_hard_builder = fst::MapBuilder::memory();
-            while let Some((external_id, marked_docids)) = iter.next() {
-                let value = indexed_last_value(marked_docids).unwrap();
-                if value != DELETED_ID {
-                    new_hard_builder.insert(external_id, value)?;
-                }
+    fn push_soft_tier(&mut self, tier: fst::Map<Cow<'a, [u8]>>) -> fst::Result<()> {
+        if tier.is_empty() {
+            return Ok(());
+        }
+        self.soft.push(tier);
+
+        while let [.., older, newer] = self.soft.as_slice() {
+            if older.len() > newer.len() * 2 && self.soft.len() <= MAX_SOFT_TIERS {
+                break;
             }
+            let merged = merge_maps(&[older, newer], false)?;
+            self.soft.truncate(self.soft.len() - 2);
+            self.soft.push(merged);
+            self.unchanged_soft = self.unchanged_soft.min(self.soft.len() - 1);
+        }
 
-            drop(iter);
+        self.merge_soft_into_hard()
+    }
 
-            self.hard = new_hard_builder.into_map().map_data(Cow::Owned)?;
-            self.soft = fst::Map::default().map_data(Cow::Owned)?;
+    fn merge_soft_into_hard(&mut self) -> fst::Result<()> {
+        let soft_len: usize = self.soft.iter().map(fst::Map::len).sum();
+        if soft_len >= self.hard.len() / 2 {
+            let maps: Vec<_> = iter::once(&self.hard).chain(&self.soft).collect();
+            self.hard = merge_maps(&maps, true)?;
+            self.hard_changed = true;
+            self.soft.clear();
+            self.unchanged_soft = 0;
         }
 
         Ok(())
@@ -139,12 +165,40 @@ impl Default for ExternalDocumentsIds<'static> {
     fn default() -> Self {
         ExternalDocumentsIds {
             hard: fst::Map::default().map_data(Cow::Owned).unwrap(),
-            soft: fst::Map::default().map_data(Cow::Owned).unwrap(),
+            soft: Vec::new(),
+            hard_changed: true,
+            unchanged_soft: 0,
             soft_deleted_docids: RoaringBitmap::new(),
         }
     }
 }
 
+/// Merges the maps, the value of an id is the one of the last map that contains it.
+/// The deleted ids are removed when `remove_deleted` is `true`.
+fn merge_maps<'a, A: AsRef<[u8]>>(
+    maps: &[&fst::Map<A>],
+    remove_deleted: bool,
+) -> fst::Result<fst::Map<Cow<'a, [u8]>>> {
+    let mut union_op = fst::map::OpBuilder::new();
+    for map in maps {
+        union_op.push(*map);
+    }
+    let union_op = union_op.r#union();
+
+    let mut iter = union_op.into_stream();
+    let mut builder = fst::MapBuilder::memory();
+    while let Some((external_id, marked_docids)) = iter.next() {
+        let value = indexed_last_value(marked_docids).unwrap();
+        if !remove_deleted || value != DELETED_ID {
+            builder.insert(external_id, value)?;
+        }
+    }
+
+    drop(iter);
+
+    builder.into_map().map_data(Cow::Owned)
+}
+
 /// Returns the value of the `IndexedValue` with the highest _index_.
 fn indexed_last_value(indexed_values: &[IndexedValue]) -> Option<u64> {
     indexed_values.iter().copied().max_by_key(|iv| iv.index).map(|iv| iv.value)
@@ -221,4 +275,109 @@ mod tests {
         external_documents_ids.insert_ids(&new_ids).unwrap();
         assert_eq!(external_documents_ids.get("30"), Some(2));
     }
+
+    #[test]
+    fn unicode_insert_delete_ids() {
+        let mut external_documents_ids = ExternalDocumentsIds::default();
+
+        let mut ids = vec![
+            ("18446744073709551615", 0),
+            ("brume bleue", 1),
+            ("café ☕", 2),
+            ("caf\u{7f}", 3),
+            ("{6f1a3c5e}", 4),
+            ("東京タワー", 5),
+        ];
+        ids.sort_unstable();
+        let new_ids = fst::Map::from_iter(ids.clone()).unwrap();
+        external_documents_ids.insert_ids(&new_ids).unwrap();
+
+        for (external_id, docid) in &ids {
+            assert_eq!(external_documents_ids.get(external_id), Some(*docid as u32));
+        }
+
+        let del_ids = fst::Set::from_iter(vec!["café ☕", "東京タワー"]).unwrap();
+        external_documents_ids.delete_ids(del_ids).unwrap();
+
+        assert_eq!(external_documents_ids.get("café ☕"), None);
+        assert_eq!(external_documents_ids.get("caf\u{7f}"), Some(3));
+        assert_eq!(external_documents_ids.get("東京タワー"), None);
+
+        let map = external_documents_ids.to_hash_map();
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.get("brume bleue"), Some(&1));
+    }
+
+    #[test]
+    fn random_insert_delete_ids_match_a_hash_map() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut external_documents_ids = ExternalDocumentsIds::default();
+        let mut model = HashMap::new();
+        let mut next_docid = 0;
+
+        for operation in 0..5000 {
+            // Mostly small batches and, sometimes, a large one.
+            let batch_len = if rng.gen_bool(0.05) { 300 } else { 8 };
+            let mut external_ids: Vec<_> = (0..rng.gen_range(1..=batch_len))
+                .map(|_| rng.gen_range(0..3000).to_string())
+                .collect();
+            external_ids.sort_unstable();
+            external_ids.dedup();
+
+            if rng.gen_bool(0.3) {
+                for external_id in &external_ids {
+                    model.remove(external_id);
+                }
+                let del_ids = fst::Set::from_iter(&external_ids).unwrap();
+                external_documents_ids.delete_ids(del_ids).unwrap();
+            } else {
+                let mut new_ids = Vec::new();
+                for external_id in external_ids {
+                    model.insert(external_id.clone(), next_docid);
+                    new_ids.push((external_id, next_docid as u64));
+                    next_docid += 1;
+                }
+                let new_ids = fst::Map::from_iter(new_ids).unwrap();
+                external_documents_ids.insert_ids(&new_ids).unwrap();
+            }
+
+            assert!(external_documents_ids.soft.len() <= MAX_SOFT_TIERS);
+            for _ in 0..10 {
+                let external_id = rng.gen_range(0..3000).to_string();
+                assert_eq!(
+                    external_documents_ids.get(&external_id),
+                    model.get(&external_id).copied()
+                );
+            }
+            if operation % 500 == 0 {
+                assert_eq!(external_documents_ids.to_hash_map(), model);
+            }
+        }
+
+        assert_eq!(external_documents_ids.to_hash_map(), model);
+        for external_id in (0..3000).map(|id| id.to_string()) {
+            assert_eq!(external_documents_ids.get(&external_id), model.get(&external_id).copied());
+        }
+    }
+
+    #[test]
+    fn small_insertions_do_not_rebuild_the_hard_map() {
+        let mut external_documents_ids = ExternalDocumentsIds::default();
+        let ids: Vec<_> = (0..1000).map(|id| (format!("{:04}", id), id)).collect();
+        external_documents_ids.insert_ids(&fst::Map::from_iter(ids).unwrap()).unwrap();
+        let hard = external_documents_ids.hard.as_fst().as_bytes().to_vec();
+
+        for id in 1000..1100 {
+            let new_ids = fst::Map::from_iter(vec![(format!("{:04}", id), id)]).unwrap();
+            external_documents_ids.insert_ids(&new_ids).unwrap();
+        }
+
+        assert_eq!(external_documents_ids.hard.as_fst().as_bytes(), &hard[..]);
+        assert!(external_documents_ids.soft.len() <= MAX_SOFT_TIERS);
+        assert_eq!(external_documents_ids.get("1099"), Some(1099));
+        assert_eq!(external_documents_ids.to_hash_map().len(), 1100);
+    }
 }
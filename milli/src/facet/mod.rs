@@ -1,6 +1,10 @@
 mod facet_type;
 mod facet_value;
+mod string_key;
 pub mod value_encoding;
 
 pub use self::facet_type::FacetType;
 pub use self::facet_value::FacetValue;
+pub use self::string_key::{
+    facet_string_key, facet_string_key_matches, MAX_FACET_STRING_KEY_LENGTH,
+};
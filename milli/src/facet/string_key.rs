@@ -0,0 +1,66 @@
+use std::borrow::Cow;
+
+/// The maximum length, in bytes, of the facet strings stored in the keys of the databases.
+///
+/// The indexed keys are limited to 500 bytes, the field id and the document id that prefix
+/// the facet strings in the `field_id_docid_facet_strings` database use 6 of them.
+pub const MAX_FACET_STRING_KEY_LENGTH: usize = 494;
+
+/// The length of the hexadecimal MD5 digest that suffixes the over-long facet strings.
+const DIGEST_LENGTH: usize = 32;
+
+/// Returns the string stored in the keys of the facet databases for a normalized facet value.
+///
+/// The values longer than [`MAX_FACET_STRING_KEY_LENGTH`] are truncated, on a char boundary,
+/// and suffixed by the hexadecimal MD5 digest of the whole value. Two long values sharing a
+/// prefix therefore do not share a key, the whole value is kept as the original string.
+pub fn facet_string_key(normalized: &str) -> Cow<str> {
+    if normalized.len() <= MAX_FACET_STRING_KEY_LENGTH {
+        return Cow::Borrowed(normalized);
+    }
+
+    let mut end = MAX_FACET_STRING_KEY_LENGTH - DIGEST_LENGTH;
+    while !normalized.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    let digest = md5::compute(normalized.as_bytes());
+    Cow::Owned(format!("{}{:x}", &normalized[..end], digest))
+}
+
+/// Whether the original string of a facet value is the one of the normalized value.
+///
+/// It is always the case for the short values, the key of an over-long value is only
+/// a digest of it that must be verified against its original string.
+pub fn facet_string_key_matches(normalized: &str, original: &str) -> bool {
+    normalized.len() <= MAX_FACET_STRING_KEY_LENGTH || original.trim().to_lowercase() == normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_values_sharing_a_prefix_have_distinct_keys() {
+        assert_eq!(facet_string_key("hello"), "hello");
+
+        let prefix = "a".repeat(500);
+        let first = format!("{}x", prefix);
+        let second = format!("{}y", prefix);
+        let first_key = facet_string_key(&first);
+        let second_key = facet_string_key(&second);
+        assert_ne!(first_key, second_key);
+        assert_eq!(first_key.len(), MAX_FACET_STRING_KEY_LENGTH);
+        assert!(first_key.starts_with(&prefix[..MAX_FACET_STRING_KEY_LENGTH - DIGEST_LENGTH]));
+
+        // The truncation never splits a char.
+        let multibyte = "é".repeat(300);
+        let key = facet_string_key(&multibyte);
+        assert!(key.len() <= MAX_FACET_STRING_KEY_LENGTH);
+        assert!(key.starts_with("éé"));
+
+        assert!(facet_string_key_matches(&first, &first.to_uppercase()));
+        assert!(!facet_string_key_matches(&first, &second));
+        assert!(facet_string_key_matches("hello", "world"));
+    }
+}
@@ -13,6 +13,19 @@ pub fn f64_into_bytes(float: f64) -> Option<[u8; 8]> {
     None
 }
 
+/// Whether the `f64` of an integer is not exactly this integer, the integers
+/// above `2^53`, in absolute value, can't all be represented by a `f64`.
+#[inline]
+pub fn is_lossy_integer(integer: i128) -> bool {
+    integer as f64 as i128 != integer
+}
+
+/// Whether the `f64` of an integer may also be the `f64` of other integers.
+#[inline]
+pub fn may_share_f64(integer: i128) -> bool {
+    integer.unsigned_abs() >= 1 << f64::MANTISSA_DIGITS
+}
+
 #[inline]
 fn xor_first_bit(mut x: [u8; 8]) -> [u8; 8] {
     x[0] ^= 0x80;
@@ -46,4 +59,15 @@ mod tests {
         let vec: Vec<_> = [a, b, c, d, e].iter().cloned().map(f64_into_bytes).collect();
         assert!(is_sorted(&vec), "{:?}", vec);
     }
+
+    #[test]
+    fn lossy_integers() {
+        let max_safe = 1_i128 << 53;
+        assert!(!is_lossy_integer(max_safe - 1) && !may_share_f64(max_safe - 1));
+        assert!(!is_lossy_integer(max_safe) && may_share_f64(max_safe));
+        assert!(is_lossy_integer(max_safe + 1) && may_share_f64(max_safe + 1));
+        assert!(is_lossy_integer(-max_safe - 1) && may_share_f64(-max_safe - 1));
+        assert!(is_lossy_integer(u64::MAX as i128));
+        assert!(!is_lossy_integer(1 << 64));
+    }
 }
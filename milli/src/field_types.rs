@@ -0,0 +1,139 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// The types of values a field has been seen with, stored as a bitmask.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FieldTypes(u8);
+
+impl FieldTypes {
+    pub const NULL: FieldTypes = FieldTypes(1 << 0);
+    pub const BOOLEAN: FieldTypes = FieldTypes(1 << 1);
+    pub const NUMBER: FieldTypes = FieldTypes(1 << 2);
+    pub const STRING: FieldTypes = FieldTypes(1 << 3);
+    pub const ARRAY: FieldTypes = FieldTypes(1 << 4);
+    pub const OBJECT: FieldTypes = FieldTypes(1 << 5);
+
+    const NAMES: [(FieldTypes, &'static str); 6] = [
+        (FieldTypes::NULL, "null"),
+        (FieldTypes::BOOLEAN, "boolean"),
+        (FieldTypes::NUMBER, "number"),
+        (FieldTypes::STRING, "string"),
+        (FieldTypes::ARRAY, "array"),
+        (FieldTypes::OBJECT, "object"),
+    ];
+
+    pub const fn empty() -> FieldTypes {
+        FieldTypes(0)
+    }
+
+    /// Returns the type of a raw JSON value, as stored in the documents database.
+    pub fn from_json_bytes(value: &[u8]) -> FieldTypes {
+        match value.first() {
+            Some(b'n') => FieldTypes::NULL,
+            Some(b't' | b'f') => FieldTypes::BOOLEAN,
+            Some(b'"') => FieldTypes::STRING,
+            Some(b'[') => FieldTypes::ARRAY,
+            Some(b'{') => FieldTypes::OBJECT,
+            Some(_) => FieldTypes::NUMBER,
+            None => FieldTypes::empty(),
+        }
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn contains(self, other: FieldTypes) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: FieldTypes) {
+        self.0 |= other.0;
+    }
+
+    /// Returns `true` if the field has been seen with more than one type of value,
+    /// `null` values are not taken into account.
+    pub const fn is_mixed(self) -> bool {
+        (self.0 & !FieldTypes::NULL.0).count_ones() > 1
+    }
+}
+
+impl fmt::Debug for FieldTypes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = FieldTypes::NAMES.iter().filter(|(types, _)| self.contains(*types));
+        f.debug_set().entries(names.map(|(_, name)| name)).finish()
+    }
+}
+
+/// The number of documents containing a field and the types of values it has been seen with.
+///
+/// The types are only widened as documents are added, they are reset when the field
+/// disappears from the index. Indexes created before the types were tracked have
+/// empty types until the field is seen again.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "StoredFieldDistributionEntry")]
+pub struct FieldDistributionEntry {
+    pub count: u64,
+    pub types: FieldTypes,
+}
+
+/// The field distribution used to only store the number of documents containing the
+/// field, we still accept this format and upgrade it the next time it is written.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredFieldDistributionEntry {
+    Count(u64),
+    Entry { count: u64, types: FieldTypes },
+}
+
+impl From<StoredFieldDistributionEntry> for FieldDistributionEntry {
+    fn from(entry: StoredFieldDistributionEntry) -> Self {
+        match entry {
+            StoredFieldDistributionEntry::Count(count) => {
+                FieldDistributionEntry { count, types: FieldTypes::empty() }
+            }
+            StoredFieldDistributionEntry::Entry { count, types } => {
+                FieldDistributionEntry { count, types }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn types_from_json_bytes() {
+        assert_eq!(FieldTypes::from_json_bytes(b"null"), FieldTypes::NULL);
+        assert_eq!(FieldTypes::from_json_bytes(b"true"), FieldTypes::BOOLEAN);
+        assert_eq!(FieldTypes::from_json_bytes(b"-12.5"), FieldTypes::NUMBER);
+        assert_eq!(FieldTypes::from_json_bytes(br#""12""#), FieldTypes::STRING);
+        assert_eq!(FieldTypes::from_json_bytes(b"[1]"), FieldTypes::ARRAY);
+        assert_eq!(FieldTypes::from_json_bytes(br#"{"a":1}"#), FieldTypes::OBJECT);
+
+        let mut types = FieldTypes::NUMBER;
+        types.insert(FieldTypes::NULL);
+        assert!(!types.is_mixed());
+        types.insert(FieldTypes::STRING);
+        assert!(types.is_mixed());
+        assert_eq!(format!("{:?}", types), r#"{"null", "number", "string"}"#);
+    }
+
+    #[test]
+    fn read_previous_field_distribution_format() {
+        let old: BTreeMap<String, FieldDistributionEntry> =
+            serde_json::from_str(r#"{"id":2,"name":1}"#).unwrap();
+        assert_eq!(old["id"], FieldDistributionEntry { count: 2, types: FieldTypes::empty() });
+
+        let entry = FieldDistributionEntry { count: 3, types: FieldTypes::STRING };
+        let new = serde_json::to_string(&BTreeMap::from([("name", entry)])).unwrap();
+        assert_eq!(new, r#"{"name":{"count":3,"types":8}}"#);
+        let new: BTreeMap<String, FieldDistributionEntry> = serde_json::from_str(&new).unwrap();
+        assert_eq!(new["name"], entry);
+    }
+}
@@ -41,6 +41,23 @@ impl FieldsIdsMap {
         }
     }
 
+    /// Returns the id the next inserted field will get, `None` if the maximum field id has
+    /// been reached.
+    pub(crate) fn next_id(&self) -> Option<FieldId> {
+        self.next_id
+    }
+
+    /// Removes the fields inserted since the given id was returned by [`FieldsIdsMap::next_id`],
+    /// their ids are given again to the next inserted fields.
+    pub(crate) fn truncate(&mut self, next_id: Option<FieldId>) {
+        if let Some(next_id) = next_id {
+            for (_, name) in self.ids_names.split_off(&next_id) {
+                self.names_ids.remove(&name);
+            }
+            self.next_id = Some(next_id);
+        }
+    }
+
     /// Get the id of a field based on its name.
     pub fn id(&self, name: &str) -> Option<FieldId> {
         self.names_ids.get(name).copied()
@@ -120,4 +137,19 @@ mod tests {
         assert_eq!(iter.next(), Some((3, "title")));
         assert_eq!(iter.next(), None);
     }
+
+    #[test]
+    fn truncate() {
+        let mut map = FieldsIdsMap::new();
+        map.insert("id");
+        let next_id = map.next_id();
+        map.insert("title");
+        map.insert("description");
+
+        map.truncate(next_id);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.id("title"), None);
+        assert_eq!(map.name(2), None);
+        assert_eq!(map.insert("description"), Some(1));
+    }
 }
@@ -0,0 +1,33 @@
+use std::borrow::Cow;
+use std::convert::TryInto;
+use std::mem::size_of;
+
+/// The exact integers of a facet number whose `f64` is shared by several integers.
+///
+/// The integers are encoded as big-endian `i128`s. An empty value means that
+/// the facet number is exactly its `f64`, it is the case of almost all of them.
+pub struct FacetExactIntegersCodec;
+
+impl<'a> heed::BytesDecode<'a> for FacetExactIntegersCodec {
+    type DItem = Vec<i128>;
+
+    fn bytes_decode(bytes: &'a [u8]) -> Option<Self::DItem> {
+        if bytes.len() % size_of::<i128>() != 0 {
+            return None;
+        }
+
+        bytes
+            .chunks_exact(size_of::<i128>())
+            .map(|chunk| chunk.try_into().ok().map(i128::from_be_bytes))
+            .collect()
+    }
+}
+
+impl<'a> heed::BytesEncode<'a> for FacetExactIntegersCodec {
+    type EItem = [i128];
+
+    fn bytes_encode(integers: &Self::EItem) -> Option<Cow<[u8]>> {
+        let bytes = integers.iter().flat_map(|integer| integer.to_be_bytes()).collect();
+        Some(Cow::Owned(bytes))
+    }
+}
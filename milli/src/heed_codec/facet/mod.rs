@@ -1,3 +1,4 @@
+mod facet_exact_integers_codec;
 mod facet_level_value_f64_codec;
 mod facet_level_value_u32_codec;
 mod facet_string_level_zero_codec;
@@ -8,6 +9,7 @@ mod field_doc_id_facet_string_codec;
 
 use heed::types::OwnedType;
 
+pub use self::facet_exact_integers_codec::FacetExactIntegersCodec;
 pub use self::facet_level_value_f64_codec::FacetLevelValueF64Codec;
 pub use self::facet_level_value_u32_codec::FacetLevelValueU32Codec;
 pub use self::facet_string_level_zero_codec::FacetStringLevelZeroCodec;
@@ -2,6 +2,7 @@ mod beu32_str_codec;
 pub mod facet;
 mod field_id_word_count_codec;
 mod obkv_codec;
+mod offset_date_time_codec;
 mod roaring_bitmap;
 mod roaring_bitmap_length;
 mod str_beu32_codec;
@@ -10,6 +11,7 @@ mod str_str_u8_codec;
 pub use self::beu32_str_codec::BEU32StrCodec;
 pub use self::field_id_word_count_codec::FieldIdWordCountCodec;
 pub use self::obkv_codec::ObkvCodec;
+pub use self::offset_date_time_codec::OffsetDateTimeCodec;
 pub use self::roaring_bitmap::{BoRoaringBitmapCodec, CboRoaringBitmapCodec, RoaringBitmapCodec};
 pub use self::roaring_bitmap_length::{
     BoRoaringBitmapLenCodec, CboRoaringBitmapLenCodec, RoaringBitmapLenCodec,
@@ -0,0 +1,47 @@
+use std::borrow::Cow;
+use std::str;
+
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+/// Encodes a date as an RFC 3339 string, the dates stored as JSON
+/// by the previous versions of the index are still decoded.
+pub struct OffsetDateTimeCodec;
+
+impl heed::BytesDecode<'_> for OffsetDateTimeCodec {
+    type DItem = OffsetDateTime;
+
+    fn bytes_decode(bytes: &[u8]) -> Option<Self::DItem> {
+        let rfc3339 =
+            str::from_utf8(bytes).ok().and_then(|s| OffsetDateTime::parse(s, &Rfc3339).ok());
+        rfc3339.or_else(|| serde_json::from_slice(bytes).ok())
+    }
+}
+
+impl heed::BytesEncode<'_> for OffsetDateTimeCodec {
+    type EItem = OffsetDateTime;
+
+    fn bytes_encode(date: &Self::EItem) -> Option<Cow<[u8]>> {
+        date.format(&Rfc3339).ok().map(|date| Cow::Owned(date.into_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heed::{BytesDecode, BytesEncode};
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn dates_are_stored_as_rfc3339() {
+        let date = datetime!(2022-06-13 14:31:59.5 UTC);
+        let bytes = OffsetDateTimeCodec::bytes_encode(&date).unwrap();
+        assert_eq!(str::from_utf8(&bytes).unwrap(), "2022-06-13T14:31:59.5Z");
+        assert_eq!(OffsetDateTimeCodec::bytes_decode(&bytes), Some(date));
+
+        // The dates previously stored as JSON can still be read.
+        let json = serde_json::to_vec(&date).unwrap();
+        assert_eq!(OffsetDateTimeCodec::bytes_decode(&json), Some(date));
+    }
+}
@@ -5,6 +5,8 @@ use std::mem::size_of;
 use byteorder::{NativeEndian, ReadBytesExt, WriteBytesExt};
 use roaring::RoaringBitmap;
 
+use crate::heed_codec::RoaringBitmapLenCodec;
+
 /// This is the limit where using a byteorder became less size efficient
 /// than using a direct roaring encoding, it is also the point where we are able
 /// to determine the encoding used only by using the array of bytes length.
@@ -53,6 +55,31 @@ impl CboRoaringBitmapCodec {
         }
     }
 
+    /// Returns the number of integers in the serialized bitmap without decoding it.
+    pub fn deserialize_len_from(bytes: &[u8]) -> io::Result<u64> {
+        if bytes.len() <= THRESHOLD * size_of::<u32>() {
+            Ok((bytes.len() / size_of::<u32>()) as u64)
+        } else {
+            RoaringBitmapLenCodec::deserialize_from_slice(bytes)
+        }
+    }
+
+    /// Returns the number of integers both in the serialized bitmap and in `other`,
+    /// it streams the serialized integers instead of decoding the whole bitmap.
+    pub fn intersection_len(mut bytes: &[u8], other: &RoaringBitmap) -> io::Result<u64> {
+        if bytes.len() <= THRESHOLD * size_of::<u32>() {
+            let mut length = 0;
+            while let Ok(integer) = bytes.read_u32::<NativeEndian>() {
+                if other.contains(integer) {
+                    length += 1;
+                }
+            }
+            Ok(length)
+        } else {
+            RoaringBitmapLenCodec::intersection_len(bytes, other)
+        }
+    }
+
     /// Merge serialized CboRoaringBitmaps in a buffer.
     ///
     /// if the merged values length is under the threshold, values are directly
@@ -118,6 +145,8 @@ mod tests {
     use std::iter::FromIterator;
 
     use heed::{BytesDecode, BytesEncode};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
 
     use super::*;
 
@@ -183,4 +212,33 @@ mod tests {
         let expected = RoaringBitmap::from_sorted_iter(0..23).unwrap();
         assert_eq!(bitmap, expected);
     }
+
+    #[test]
+    fn length_and_intersection_length_from_bytes() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        for _ in 0..200 {
+            // The small bitmaps use the byteorder encoding, the others use the roaring one
+            // with a mix of sparse (array) and dense (bitmap) containers.
+            let bitmap: RoaringBitmap = match rng.gen_range(0..4) {
+                0 => (0..rng.gen_range(0..=THRESHOLD)).map(|_| rng.gen_range(0..200)).collect(),
+                1 => (0..rng.gen_range(0..1000)).map(|_| rng.gen::<u32>()).collect(),
+                2 => (0..rng.gen_range(0..20_000)).map(|_| rng.gen_range(0..200_000)).collect(),
+                _ => (0..rng.gen_range(0..200_000)).map(|_| rng.gen_range(0..300_000)).collect(),
+            };
+            let other: RoaringBitmap = match rng.gen_range(0..3) {
+                0 => RoaringBitmap::new(),
+                1 => (0..rng.gen_range(0..100)).map(|_| rng.gen_range(0..300_000)).collect(),
+                _ => (0..rng.gen_range(0..100_000)).map(|_| rng.gen_range(0..300_000)).collect(),
+            };
+
+            let bytes = CboRoaringBitmapCodec::bytes_encode(&bitmap).unwrap();
+            let decoded = CboRoaringBitmapCodec::deserialize_from(&bytes).unwrap();
+
+            let len = CboRoaringBitmapCodec::deserialize_len_from(&bytes).unwrap();
+            assert_eq!(len, decoded.len());
+            let len = CboRoaringBitmapCodec::intersection_len(&bytes, &other).unwrap();
+            assert_eq!(len, decoded.intersection_len(&other));
+        }
+    }
 }
@@ -1,16 +1,23 @@
+use std::convert::TryInto;
 use std::io::{self, BufRead, Read};
 use std::mem;
 
 use byteorder::{LittleEndian, ReadBytesExt};
+use roaring::RoaringBitmap;
 
 const SERIAL_COOKIE_NO_RUNCONTAINER: u32 = 12346;
 const SERIAL_COOKIE: u16 = 12347;
 
+/// The maximum number of integers stored in an array container,
+/// the bigger containers are stored as bitmaps of 1024 u64s.
+const ARRAY_LIMIT: u64 = 4096;
+const BITMAP_LENGTH: usize = 1024;
+
 pub struct RoaringBitmapLenCodec;
 
 impl RoaringBitmapLenCodec {
     // FIXME should be exported in the RoaringBitmap crate
-    fn deserialize_from_slice(mut bytes: &[u8]) -> io::Result<u64> {
+    pub fn deserialize_from_slice(mut bytes: &[u8]) -> io::Result<u64> {
         let (size, has_offsets) = {
             let cookie = bytes.read_u32::<LittleEndian>()?;
             if cookie == SERIAL_COOKIE_NO_RUNCONTAINER {
@@ -40,10 +47,71 @@ impl RoaringBitmapLenCodec {
             let len = u64::from(description_bytes.read_u16::<LittleEndian>()?) + 1;
             length += len;
 
-            if len <= 4096 {
+            if len <= ARRAY_LIMIT {
                 bytes.consume(len as usize * mem::size_of::<u16>());
             } else {
-                bytes.consume(1024 * mem::size_of::<u64>())
+                bytes.consume(BITMAP_LENGTH * mem::size_of::<u64>())
+            }
+        }
+
+        Ok(length)
+    }
+
+    /// Returns the number of integers both in the serialized RoaringBitmap and in `other`.
+    ///
+    /// The containers are read one by one from the bytes, the serialized bitmap is never decoded.
+    pub fn intersection_len(mut bytes: &[u8], other: &RoaringBitmap) -> io::Result<u64> {
+        let cookie = bytes.read_u32::<LittleEndian>()?;
+        if cookie != SERIAL_COOKIE_NO_RUNCONTAINER {
+            if (cookie as u16) == SERIAL_COOKIE {
+                return Err(io::Error::new(io::ErrorKind::Other, "run containers are unsupported"));
+            } else {
+                return Err(io::Error::new(io::ErrorKind::Other, "unknown cookie value"));
+            }
+        }
+
+        let size = bytes.read_u32::<LittleEndian>()? as usize;
+        if size > u16::max_value() as usize + 1 {
+            return Err(io::Error::new(io::ErrorKind::Other, "size is greater than supported"));
+        }
+
+        // The descriptions are followed by the offsets of the containers, that we ignore.
+        let descriptions = split_at(&mut bytes, size * 4)?;
+        split_at(&mut bytes, size * 4)?;
+
+        let mut length = 0;
+        for description in descriptions.chunks_exact(4) {
+            let key = u16::from_le_bytes([description[0], description[1]]);
+            let len = u64::from(u16::from_le_bytes([description[2], description[3]])) + 1;
+            let high = u32::from(key) << 16;
+
+            if len <= ARRAY_LIMIT {
+                let values = split_at(&mut bytes, len as usize * mem::size_of::<u16>())?;
+                if other.range_cardinality(high..=high | 0xFFFF) == 0 {
+                    continue;
+                }
+                for value in values.chunks_exact(mem::size_of::<u16>()) {
+                    let low = u16::from_le_bytes([value[0], value[1]]);
+                    if other.contains(high | u32::from(low)) {
+                        length += 1;
+                    }
+                }
+            } else {
+                let words = split_at(&mut bytes, BITMAP_LENGTH * mem::size_of::<u64>())?;
+                if other.range_cardinality(high..=high | 0xFFFF) == 0 {
+                    continue;
+                }
+                for (i, word) in words.chunks_exact(mem::size_of::<u64>()).enumerate() {
+                    let mut word = u64::from_le_bytes(word.try_into().unwrap());
+                    let start = high | (i as u32 * 64);
+                    while word != 0 {
+                        if other.contains(start + word.trailing_zeros()) {
+                            length += 1;
+                        }
+                        // We remove the lowest bit set.
+                        word &= word - 1;
+                    }
+                }
             }
         }
 
@@ -51,6 +119,16 @@ impl RoaringBitmapLenCodec {
     }
 }
 
+/// Returns the first `len` bytes and advances the slice after them.
+fn split_at<'a>(bytes: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if bytes.len() < len {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated roaring bitmap"));
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Ok(head)
+}
+
 impl heed::BytesDecode<'_> for RoaringBitmapLenCodec {
     type DItem = u64;
 
@@ -74,4 +152,16 @@ mod tests {
         let len = RoaringBitmapLenCodec::deserialize_from_slice(&bytes).unwrap();
         assert_eq!(bitmap.len(), len);
     }
+
+    #[test]
+    fn serialized_roaring_bitmap_intersection_length() {
+        let bitmap: RoaringBitmap = (0..500).chain(800..800_000).chain(920_056..930_032).collect();
+        let other: RoaringBitmap = (0..1_000_000).step_by(3).chain(70_000..70_100).collect();
+        let bytes = RoaringBitmapCodec::bytes_encode(&bitmap).unwrap();
+        let len = RoaringBitmapLenCodec::intersection_len(&bytes, &other).unwrap();
+        assert_eq!(bitmap.intersection_len(&other), len);
+
+        let truncated = &bytes[..bytes.len() - 1];
+        assert!(RoaringBitmapLenCodec::intersection_len(truncated, &other).is_err());
+    }
 }
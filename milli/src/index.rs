@@ -1,8 +1,11 @@
 use std::borrow::Cow;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::File;
 use std::mem::size_of;
+use std::ops::Bound;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
 use heed::flags::Flags;
 use heed::types::*;
@@ -11,21 +14,37 @@ use roaring::RoaringBitmap;
 use rstar::RTree;
 use time::OffsetDateTime;
 
+use crate::documents_compression::{DocumentsDecompressor, StoredDocument};
 use crate::error::{InternalError, UserError};
 use crate::fields_ids_map::FieldsIdsMap;
 use crate::heed_codec::facet::{
-    FacetLevelValueF64Codec, FacetStringLevelZeroCodec, FacetStringLevelZeroValueCodec,
-    FieldDocIdFacetF64Codec, FieldDocIdFacetStringCodec, FieldIdCodec,
+    FacetExactIntegersCodec, FacetLevelValueF64Codec, FacetStringLevelZeroCodec,
+    FacetStringLevelZeroValueCodec, FieldDocIdFacetF64Codec, FieldDocIdFacetStringCodec,
+    FieldIdCodec,
 };
+use crate::position_bucketing::PositionBucketing;
+use crate::proximity::ProximityPrecision;
+use crate::search::{DocidsCache, SearchableContextCache};
+use crate::update::PrefixSettings;
 use crate::{
     default_criteria, BEU32StrCodec, BoRoaringBitmapCodec, CboRoaringBitmapCodec, Criterion,
-    DocumentId, ExternalDocumentsIds, FacetDistribution, FieldDistribution, FieldId,
-    FieldIdWordCountCodec, GeoPoint, ObkvCodec, Result, RoaringBitmapCodec, RoaringBitmapLenCodec,
-    Search, StrBEU32Codec, U8StrStrCodec, BEU16, BEU32,
+    DocumentId, ExternalDocumentsIds, FacetDistribution, FieldDistribution,
+    FieldDistributionWithTypes, FieldId, FieldIdWordCountCodec, FieldTypes, GeoPoint, MultiSearch,
+    Object, ObkvCodec, OffsetDateTimeCodec, OrderBy, Result, RoaringBitmapCodec,
+    RoaringBitmapLenCodec, Search, SearchQuery, SearchableContext, StrBEU32Codec, U8StrStrCodec,
+    BEU16, BEU32,
 };
 
 pub const DEFAULT_MIN_WORD_LEN_ONE_TYPO: u8 = 5;
 pub const DEFAULT_MIN_WORD_LEN_TWO_TYPOS: u8 = 9;
+/// The maximum proximity stored in the word pair proximity databases by default.
+pub const DEFAULT_MAX_PROXIMITY: u8 = 7;
+/// The weight of the searchable fields that weren't given an explicit weight.
+pub const DEFAULT_SEARCHABLE_FIELD_WEIGHT: u16 = 1;
+/// The number of elements of a facet level that are grouped into a single element of the level above by default.
+pub const DEFAULT_FACET_LEVEL_GROUP_SIZE: usize = 4;
+/// The minimum number of elements of a facet level by default.
+pub const DEFAULT_FACET_MIN_LEVEL_SIZE: usize = 5;
 
 pub mod main_key {
     pub const CRITERIA_KEY: &str = "criteria";
@@ -35,6 +54,10 @@ pub mod main_key {
     pub const SOFT_DELETED_DOCUMENTS_IDS_KEY: &str = "soft-deleted-documents-ids";
     pub const HIDDEN_FACETED_FIELDS_KEY: &str = "hidden-faceted-fields";
     pub const FILTERABLE_FIELDS_KEY: &str = "filterable-fields";
+    pub const NO_DISTRIBUTION_FIELDS_KEY: &str = "no-distribution-fields";
+    pub const NON_STORED_FIELDS_KEY: &str = "non-stored-fields";
+    pub const IGNORED_FIELDS_KEY: &str = "ignored-fields";
+    pub const SORT_FACET_VALUES_BY_KEY: &str = "sort-facet-values-by";
     pub const SORTABLE_FIELDS_KEY: &str = "sortable-fields";
     pub const FIELD_DISTRIBUTION_KEY: &str = "fields-distribution";
     pub const FIELDS_IDS_MAP_KEY: &str = "fields-ids-map";
@@ -45,21 +68,48 @@ pub mod main_key {
     pub const PRIMARY_KEY_KEY: &str = "primary-key";
     pub const SEARCHABLE_FIELDS_KEY: &str = "searchable-fields";
     pub const USER_DEFINED_SEARCHABLE_FIELDS_KEY: &str = "user-defined-searchable-fields";
+    pub const SEARCHABLE_FIELDS_WEIGHTS_KEY: &str = "searchable-fields-weights";
     pub const SOFT_EXTERNAL_DOCUMENTS_IDS_KEY: &str = "soft-external-documents-ids";
     pub const STOP_WORDS_KEY: &str = "stop-words";
+    pub const DICTIONARY_KEY: &str = "dictionary";
     pub const STRING_FACETED_DOCUMENTS_IDS_PREFIX: &str = "string-faceted-documents-ids";
     pub const SYNONYMS_KEY: &str = "synonyms";
     pub const WORDS_FST_KEY: &str = "words-fst";
     pub const WORDS_PREFIXES_FST_KEY: &str = "words-prefixes-fst";
     pub const CREATED_AT_KEY: &str = "created-at";
     pub const UPDATED_AT_KEY: &str = "updated-at";
+    pub const UPDATE_ID_KEY: &str = "update-id";
     pub const AUTHORIZE_TYPOS: &str = "authorize-typos";
+    pub const ENABLE_WORD_SPLITTING: &str = "enable-word-splitting";
+    pub const ENABLE_WORD_CONCATENATION: &str = "enable-word-concatenation";
+    pub const ENABLE_NEGATIVE_TERMS: &str = "enable-negative-terms";
     pub const ONE_TYPO_WORD_LEN: &str = "one-typo-word-len";
     pub const TWO_TYPOS_WORD_LEN: &str = "two-typos-word-len";
     pub const EXACT_WORDS: &str = "exact-words";
     pub const EXACT_ATTRIBUTES: &str = "exact-attributes";
     pub const MAX_VALUES_PER_FACET: &str = "max-values-per-facet";
     pub const PAGINATION_MAX_TOTAL_HITS: &str = "pagination-max-total-hits";
+    pub const MAX_PROXIMITY: &str = "max-proximity";
+    pub const PROXIMITY_PRECISION: &str = "proximity-precision";
+    pub const POSITION_BUCKETING: &str = "position-bucketing";
+    pub const COERCE_NUMERIC_STRINGS: &str = "coerce-numeric-strings";
+    pub const COUNT_DIACRITICS: &str = "count-diacritics";
+    pub const FACET_LEVEL_GROUP_SIZE: &str = "facet-level-group-size";
+    pub const FACET_MAX_GROUP_SIZE: &str = "facet-max-group-size";
+    pub const FACET_MIN_LEVEL_SIZE: &str = "facet-min-level-size";
+    pub const PREFIX_SETTINGS: &str = "prefix-settings";
+    pub const WORDS_PREFIXES_SETTINGS: &str = "words-prefixes-settings";
+    pub const DOCUMENTS_COMPRESSION: &str = "documents-compression";
+    pub const DOCUMENTS_COMPRESSION_DICTIONARY: &str = "documents-compression-dictionary";
+}
+
+/// Returns the key of a soft tier of the external documents ids, the oldest
+/// tier is stored under the key that was used by the single soft map.
+fn soft_external_documents_ids_key(tier: usize) -> Cow<'static, str> {
+    match tier {
+        0 => Cow::Borrowed(main_key::SOFT_EXTERNAL_DOCUMENTS_IDS_KEY),
+        tier => Cow::Owned(format!("{}-{}", main_key::SOFT_EXTERNAL_DOCUMENTS_IDS_KEY, tier)),
+    }
 }
 
 pub mod db_name {
@@ -77,12 +127,53 @@ pub mod db_name {
     pub const FIELD_ID_WORD_COUNT_DOCIDS: &str = "field-id-word-count-docids";
     pub const FACET_ID_F64_DOCIDS: &str = "facet-id-f64-docids";
     pub const FACET_ID_EXISTS_DOCIDS: &str = "facet-id-exists-docids";
+    pub const FACET_ID_IS_NULL_DOCIDS: &str = "facet-id-is-null-docids";
+    pub const FACET_ID_IS_EMPTY_DOCIDS: &str = "facet-id-is-empty-docids";
     pub const FACET_ID_STRING_DOCIDS: &str = "facet-id-string-docids";
     pub const FIELD_ID_DOCID_FACET_F64S: &str = "field-id-docid-facet-f64s";
     pub const FIELD_ID_DOCID_FACET_STRINGS: &str = "field-id-docid-facet-strings";
     pub const DOCUMENTS: &str = "documents";
 }
 
+/// The statistics of a single database of the index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DatabaseStats {
+    /// The number of entries stored in the database.
+    pub number_of_entries: u64,
+    /// The total size in bytes of the keys and values, only computed by an exact scan.
+    pub total_size: Option<u64>,
+}
+
+/// The statistics of an index, see [`Index::stats`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexStats {
+    pub number_of_documents: u64,
+    pub field_distribution: FieldDistribution,
+    /// The statistics of every database, associated with its name.
+    pub databases: BTreeMap<&'static str, DatabaseStats>,
+}
+
+/// A bounded batch of documents, see [`Index::documents_page`].
+pub struct DocumentsPage<'t> {
+    /// The documents of the page, in the order of their internal ids.
+    pub documents: Vec<(DocumentId, StoredDocument<'t>)>,
+    /// The token to give to the next call to resume after this page,
+    /// `None` when there are no documents left.
+    pub next: Option<DocumentId>,
+}
+
+/// The facet values of a field of a document as they were indexed,
+/// see [`Index::facet_values_for_document`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DocumentFacetValues {
+    /// The numbers, in ascending order.
+    pub numbers: Vec<f64>,
+    /// The exact integers of the numbers that can't be represented by an `f64`.
+    pub exact_integers: Vec<i128>,
+    /// The normalized strings along with their original version, in ascending order.
+    pub strings: Vec<(String, String)>,
+}
+
 #[derive(Clone)]
 pub struct Index {
     /// The LMDB environment which this index is associated with.
@@ -122,19 +213,33 @@ pub struct Index {
 
     /// Maps the facet field id and the docids for which this field exists
     pub facet_id_exists_docids: Database<FieldIdCodec, CboRoaringBitmapCodec>,
+    /// Maps the facet field id and the docids for which this field is set as null
+    pub facet_id_is_null_docids: Database<FieldIdCodec, CboRoaringBitmapCodec>,
+    /// Maps the facet field id and the docids for which this field is considered empty
+    pub facet_id_is_empty_docids: Database<FieldIdCodec, CboRoaringBitmapCodec>,
 
     /// Maps the facet field id, level and the number with the docids that corresponds to it.
     pub facet_id_f64_docids: Database<FacetLevelValueF64Codec, CboRoaringBitmapCodec>,
     /// Maps the facet field id and the string with the original string and docids that corresponds to it.
     pub facet_id_string_docids: Database<FacetStringLevelZeroCodec, FacetStringLevelZeroValueCodec>,
 
-    /// Maps the document id, the facet field id and the numbers.
-    pub field_id_docid_facet_f64s: Database<FieldDocIdFacetF64Codec, Unit>,
+    /// Maps the document id, the facet field id and the numbers with
+    /// the exact integers of the numbers a f64 can't represent exactly.
+    pub field_id_docid_facet_f64s: Database<FieldDocIdFacetF64Codec, FacetExactIntegersCodec>,
     /// Maps the document id, the facet field id and the strings.
     pub field_id_docid_facet_strings: Database<FieldDocIdFacetStringCodec, Str>,
 
     /// Maps the document id to the document as an obkv store.
     pub(crate) documents: Database<OwnedType<BEU32>, ObkvCodec>,
+
+    /// The cache of the words docids read by the searches, shared by the clones of the index.
+    pub(crate) docids_cache: Option<Arc<DocidsCache>>,
+    /// The settings read by the searches, shared by the clones of the index.
+    pub(crate) searchable_context: Arc<SearchableContextCache>,
+    /// The last update id given by an update of this index, shared by the clones of the index.
+    /// The id of an aborted update is never given again, the caches above never mistake the
+    /// entries read during it for the ones of the next update.
+    pub(crate) last_update_id: Arc<AtomicU64>,
 }
 
 impl Index {
@@ -146,7 +251,7 @@ impl Index {
     ) -> Result<Index> {
         use db_name::*;
 
-        options.max_dbs(18);
+        options.max_dbs(20);
         unsafe { options.flag(Flags::MdbAlwaysFreePages) };
 
         let env = options.open(path)?;
@@ -167,6 +272,8 @@ impl Index {
         let facet_id_f64_docids = env.create_database(Some(FACET_ID_F64_DOCIDS))?;
         let facet_id_string_docids = env.create_database(Some(FACET_ID_STRING_DOCIDS))?;
         let facet_id_exists_docids = env.create_database(Some(FACET_ID_EXISTS_DOCIDS))?;
+        let facet_id_is_null_docids = env.create_database(Some(FACET_ID_IS_NULL_DOCIDS))?;
+        let facet_id_is_empty_docids = env.create_database(Some(FACET_ID_IS_EMPTY_DOCIDS))?;
 
         let field_id_docid_facet_f64s = env.create_database(Some(FIELD_ID_DOCID_FACET_F64S))?;
         let field_id_docid_facet_strings =
@@ -192,9 +299,14 @@ impl Index {
             facet_id_f64_docids,
             facet_id_string_docids,
             facet_id_exists_docids,
+            facet_id_is_null_docids,
+            facet_id_is_empty_docids,
             field_id_docid_facet_f64s,
             field_id_docid_facet_strings,
             documents,
+            docids_cache: None,
+            searchable_context: Arc::default(),
+            last_update_id: Arc::default(),
         })
     }
 
@@ -211,14 +323,13 @@ impl Index {
     ) -> heed::Result<()> {
         let mut txn = env.write_txn()?;
         // The db was just created, we update its metadata with the relevant information.
-        if main.get::<_, Str, SerdeJson<OffsetDateTime>>(&txn, main_key::CREATED_AT_KEY)?.is_none()
-        {
-            main.put::<_, Str, SerdeJson<OffsetDateTime>>(
+        if main.get::<_, Str, OffsetDateTimeCodec>(&txn, main_key::CREATED_AT_KEY)?.is_none() {
+            main.put::<_, Str, OffsetDateTimeCodec>(
                 &mut txn,
                 main_key::UPDATED_AT_KEY,
                 &updated_at,
             )?;
-            main.put::<_, Str, SerdeJson<OffsetDateTime>>(
+            main.put::<_, Str, OffsetDateTimeCodec>(
                 &mut txn,
                 main_key::CREATED_AT_KEY,
                 &created_at,
@@ -253,6 +364,88 @@ impl Index {
         Ok(self.env.real_disk_size()?)
     }
 
+    /// Returns the name and the untyped handle of every database of the index.
+    pub(crate) fn databases(&self) -> [(&'static str, PolyDatabase); 20] {
+        use db_name::*;
+
+        let Index {
+            env: _,
+            main,
+            word_docids,
+            exact_word_docids,
+            word_prefix_docids,
+            exact_word_prefix_docids,
+            docid_word_positions,
+            word_pair_proximity_docids,
+            word_prefix_pair_proximity_docids,
+            prefix_word_pair_proximity_docids,
+            word_position_docids,
+            field_id_word_count_docids,
+            word_prefix_position_docids,
+            facet_id_exists_docids,
+            facet_id_is_null_docids,
+            facet_id_is_empty_docids,
+            facet_id_f64_docids,
+            facet_id_string_docids,
+            field_id_docid_facet_f64s,
+            field_id_docid_facet_strings,
+            documents,
+        } = self;
+
+        [
+            (MAIN, *main),
+            (WORD_DOCIDS, *word_docids.as_polymorph()),
+            (EXACT_WORD_DOCIDS, *exact_word_docids.as_polymorph()),
+            (WORD_PREFIX_DOCIDS, *word_prefix_docids.as_polymorph()),
+            (EXACT_WORD_PREFIX_DOCIDS, *exact_word_prefix_docids.as_polymorph()),
+            (DOCID_WORD_POSITIONS, *docid_word_positions.as_polymorph()),
+            (WORD_PAIR_PROXIMITY_DOCIDS, *word_pair_proximity_docids.as_polymorph()),
+            (WORD_PREFIX_PAIR_PROXIMITY_DOCIDS, *word_prefix_pair_proximity_docids.as_polymorph()),
+            (PREFIX_WORD_PAIR_PROXIMITY_DOCIDS, *prefix_word_pair_proximity_docids.as_polymorph()),
+            (WORD_POSITION_DOCIDS, *word_position_docids.as_polymorph()),
+            (FIELD_ID_WORD_COUNT_DOCIDS, *field_id_word_count_docids.as_polymorph()),
+            (WORD_PREFIX_POSITION_DOCIDS, *word_prefix_position_docids.as_polymorph()),
+            (FACET_ID_EXISTS_DOCIDS, *facet_id_exists_docids.as_polymorph()),
+            (FACET_ID_IS_NULL_DOCIDS, *facet_id_is_null_docids.as_polymorph()),
+            (FACET_ID_IS_EMPTY_DOCIDS, *facet_id_is_empty_docids.as_polymorph()),
+            (FACET_ID_F64_DOCIDS, *facet_id_f64_docids.as_polymorph()),
+            (FACET_ID_STRING_DOCIDS, *facet_id_string_docids.as_polymorph()),
+            (FIELD_ID_DOCID_FACET_F64S, *field_id_docid_facet_f64s.as_polymorph()),
+            (FIELD_ID_DOCID_FACET_STRINGS, *field_id_docid_facet_strings.as_polymorph()),
+            (DOCUMENTS, *documents.as_polymorph()),
+        ]
+    }
+
+    /// Returns the number of entries of every database of the index along with
+    /// the number of documents and the field distribution.
+    ///
+    /// The keys and values sizes are only computed when `exact` is `true`,
+    /// as every entry of every database must then be read.
+    pub fn stats(&self, rtxn: &RoTxn, exact: bool) -> Result<IndexStats> {
+        let mut databases_stats = BTreeMap::new();
+        for (name, database) in self.databases() {
+            let stats = if exact {
+                let mut number_of_entries = 0;
+                let mut total_size = 0;
+                for result in database.iter::<_, ByteSlice, ByteSlice>(rtxn)? {
+                    let (key, value) = result?;
+                    number_of_entries += 1;
+                    total_size += (key.len() + value.len()) as u64;
+                }
+                DatabaseStats { number_of_entries, total_size: Some(total_size) }
+            } else {
+                DatabaseStats { number_of_entries: database.len(rtxn)?, total_size: None }
+            };
+            databases_stats.insert(name, stats);
+        }
+
+        Ok(IndexStats {
+            number_of_documents: self.number_of_documents(rtxn)?,
+            field_distribution: self.field_distribution(rtxn)?,
+            databases: databases_stats,
+        })
+    }
+
     pub fn copy_to_path<P: AsRef<Path>>(&self, path: P, option: CompactionOption) -> Result<File> {
         self.env.copy_to_path(path, option).map_err(Into::into)
     }
@@ -336,24 +529,33 @@ impl Index {
     /* external documents ids */
 
     /// Writes the external documents ids and internal ids (i.e. `u32`).
+    ///
+    /// Only the hard map and the soft tiers that changed since they were read are written.
     pub(crate) fn put_external_documents_ids<'a>(
         &self,
         wtxn: &mut RwTxn,
         external_documents_ids: &ExternalDocumentsIds<'a>,
     ) -> heed::Result<()> {
-        let ExternalDocumentsIds { hard, soft, .. } = external_documents_ids;
-        let hard = hard.as_fst().as_bytes();
-        let soft = soft.as_fst().as_bytes();
-        self.main.put::<_, Str, ByteSlice>(
-            wtxn,
-            main_key::HARD_EXTERNAL_DOCUMENTS_IDS_KEY,
-            hard,
-        )?;
-        self.main.put::<_, Str, ByteSlice>(
-            wtxn,
-            main_key::SOFT_EXTERNAL_DOCUMENTS_IDS_KEY,
-            soft,
-        )?;
+        let ExternalDocumentsIds { hard, soft, hard_changed, unchanged_soft, .. } =
+            external_documents_ids;
+        if *hard_changed {
+            self.main.put::<_, Str, ByteSlice>(
+                wtxn,
+                main_key::HARD_EXTERNAL_DOCUMENTS_IDS_KEY,
+                hard.as_fst().as_bytes(),
+            )?;
+        }
+        for (tier, map) in soft.iter().enumerate().skip(*unchanged_soft) {
+            let key = soft_external_documents_ids_key(tier);
+            self.main.put::<_, Str, ByteSlice>(wtxn, &key, map.as_fst().as_bytes())?;
+        }
+        // We remove the tiers that have been merged since they were read.
+        for tier in soft.len().. {
+            let key = soft_external_documents_ids_key(tier);
+            if !self.main.delete::<_, Str>(wtxn, &key)? {
+                break;
+            }
+        }
         Ok(())
     }
 
@@ -362,16 +564,18 @@ impl Index {
     pub fn external_documents_ids<'t>(&self, rtxn: &'t RoTxn) -> Result<ExternalDocumentsIds<'t>> {
         let hard =
             self.main.get::<_, Str, ByteSlice>(rtxn, main_key::HARD_EXTERNAL_DOCUMENTS_IDS_KEY)?;
-        let soft =
-            self.main.get::<_, Str, ByteSlice>(rtxn, main_key::SOFT_EXTERNAL_DOCUMENTS_IDS_KEY)?;
         let hard = match hard {
             Some(hard) => fst::Map::new(hard)?.map_data(Cow::Borrowed)?,
             None => fst::Map::default().map_data(Cow::Owned)?,
         };
-        let soft = match soft {
-            Some(soft) => fst::Map::new(soft)?.map_data(Cow::Borrowed)?,
-            None => fst::Map::default().map_data(Cow::Owned)?,
-        };
+        let mut soft = Vec::new();
+        loop {
+            let key = soft_external_documents_ids_key(soft.len());
+            match self.main.get::<_, Str, ByteSlice>(rtxn, &key)? {
+                Some(tier) => soft.push(fst::Map::new(tier)?.map_data(Cow::Borrowed)?),
+                None => break,
+            }
+        }
         let soft_deleted_docids = self.soft_deleted_documents_ids(rtxn)?;
         Ok(ExternalDocumentsIds::new(hard, soft, soft_deleted_docids))
     }
@@ -397,6 +601,12 @@ impl Index {
             .unwrap_or_default())
     }
 
+    /// Returns the number of fields of the index, new fields are rejected once it
+    /// reaches the [`IndexerConfig::max_fields`](crate::update::IndexerConfig::max_fields).
+    pub fn fields_count(&self, rtxn: &RoTxn) -> heed::Result<usize> {
+        Ok(self.fields_ids_map(rtxn)?.len())
+    }
+
     /* geo rtree */
 
     /// Writes the provided `rtree` which associates coordinates to documents ids.
@@ -458,13 +668,13 @@ impl Index {
     /* field distribution */
 
     /// Writes the field distribution which associates every field name with
-    /// the number of times it occurs in the documents.
+    /// the number of times it occurs in the documents and the types of its values.
     pub(crate) fn put_field_distribution(
         &self,
         wtxn: &mut RwTxn,
-        distribution: &FieldDistribution,
+        distribution: &FieldDistributionWithTypes,
     ) -> heed::Result<()> {
-        self.main.put::<_, Str, SerdeJson<FieldDistribution>>(
+        self.main.put::<_, Str, SerdeJson<FieldDistributionWithTypes>>(
             wtxn,
             main_key::FIELD_DISTRIBUTION_KEY,
             distribution,
@@ -472,14 +682,33 @@ impl Index {
     }
 
     /// Returns the field distribution which associates every field name with
-    /// the number of times it occurs in the documents.
-    pub fn field_distribution(&self, rtxn: &RoTxn) -> heed::Result<FieldDistribution> {
+    /// the number of times it occurs in the documents and the types of its values.
+    pub(crate) fn field_distribution_with_types(
+        &self,
+        rtxn: &RoTxn,
+    ) -> heed::Result<FieldDistributionWithTypes> {
         Ok(self
             .main
-            .get::<_, Str, SerdeJson<FieldDistribution>>(rtxn, main_key::FIELD_DISTRIBUTION_KEY)?
+            .get::<_, Str, SerdeJson<FieldDistributionWithTypes>>(
+                rtxn,
+                main_key::FIELD_DISTRIBUTION_KEY,
+            )?
             .unwrap_or_default())
     }
 
+    /// Returns the field distribution which associates every field name with
+    /// the number of times it occurs in the documents.
+    pub fn field_distribution(&self, rtxn: &RoTxn) -> heed::Result<FieldDistribution> {
+        let distribution = self.field_distribution_with_types(rtxn)?;
+        Ok(distribution.into_iter().map(|(name, entry)| (name, entry.count)).collect())
+    }
+
+    /// Returns the types of values every field has been seen with.
+    pub fn field_types(&self, rtxn: &RoTxn) -> heed::Result<BTreeMap<String, FieldTypes>> {
+        let distribution = self.field_distribution_with_types(rtxn)?;
+        Ok(distribution.into_iter().map(|(name, entry)| (name, entry.types)).collect())
+    }
+
     /* displayed fields */
 
     /// Writes the fields that must be displayed in the defined order.
@@ -509,13 +738,23 @@ impl Index {
     }
 
     /// Identical to `displayed_fields`, but returns the ids instead.
+    ///
+    /// The patterns are replaced by the ids of the fields they match, see [`crate::is_field_matched_by`].
     pub fn displayed_fields_ids(&self, rtxn: &RoTxn) -> Result<Option<Vec<FieldId>>> {
         match self.displayed_fields(rtxn)? {
             Some(fields) => {
                 let fields_ids_map = self.fields_ids_map(rtxn)?;
                 let mut fields_ids = Vec::new();
                 for name in fields.into_iter() {
-                    if let Some(field_id) = fields_ids_map.id(name) {
+                    if crate::is_field_pattern(name) {
+                        for (field_id, field) in fields_ids_map.iter() {
+                            if crate::is_field_matched_by(field, name)
+                                && !fields_ids.contains(&field_id)
+                            {
+                                fields_ids.push(field_id);
+                            }
+                        }
+                    } else if let Some(field_id) = fields_ids_map.id(name) {
                         fields_ids.push(field_id);
                     }
                 }
@@ -542,12 +781,15 @@ impl Index {
         // 2. Iterate over the user defined searchable fields.
         // 3. If a user defined field is a subset of a field defined in the fields_ids_map
         // (ie doggo.name is a subset of doggo) then we push it at the end of the fields.
-        let mut real_fields = user_fields.to_vec();
+        // The patterns (ie translations.*.title) are not fields, they are only kept in the
+        // user defined fields and the fields they match are pushed at the end of the fields.
+        let mut real_fields: Vec<_> =
+            user_fields.iter().copied().filter(|field| !crate::is_field_pattern(field)).collect();
 
         for field_from_map in fields_ids_map.names() {
             for user_field in user_fields {
                 if crate::is_faceted_by(field_from_map, user_field)
-                    && !user_fields.contains(&field_from_map)
+                    && !real_fields.contains(&field_from_map)
                 {
                     real_fields.push(field_from_map);
                 }
@@ -559,6 +801,7 @@ impl Index {
 
     pub(crate) fn delete_all_searchable_fields(&self, wtxn: &mut RwTxn) -> heed::Result<bool> {
         self.delete_searchable_fields(wtxn)?;
+        self.delete_searchable_fields_weights(wtxn)?;
         self.delete_user_defined_searchable_fields(wtxn)
     }
 
@@ -629,6 +872,34 @@ impl Index {
             .get::<_, Str, SerdeBincode<Vec<_>>>(rtxn, main_key::USER_DEFINED_SEARCHABLE_FIELDS_KEY)
     }
 
+    /// Writes the weights explicitly given to the user defined searchable fields.
+    pub(crate) fn put_searchable_fields_weights(
+        &self,
+        wtxn: &mut RwTxn,
+        weights: &BTreeMap<String, u16>,
+    ) -> heed::Result<()> {
+        self.main.put::<_, Str, SerdeJson<_>>(
+            wtxn,
+            main_key::SEARCHABLE_FIELDS_WEIGHTS_KEY,
+            weights,
+        )
+    }
+
+    pub(crate) fn delete_searchable_fields_weights(&self, wtxn: &mut RwTxn) -> heed::Result<bool> {
+        self.main.delete::<_, Str>(wtxn, main_key::SEARCHABLE_FIELDS_WEIGHTS_KEY)
+    }
+
+    /// Returns the weights explicitly given to the user defined searchable fields, the attribute
+    /// criterion ranks the fields by decreasing weight, whatever the gaps between the weights. The fields without an explicit weight
+    /// have a weight of [`DEFAULT_SEARCHABLE_FIELD_WEIGHT`], the fields with the same weight
+    /// are ranked in the order of the searchable fields.
+    pub fn searchable_fields_weights(&self, rtxn: &RoTxn) -> heed::Result<BTreeMap<String, u16>> {
+        Ok(self
+            .main
+            .get::<_, Str, SerdeJson<_>>(rtxn, main_key::SEARCHABLE_FIELDS_WEIGHTS_KEY)?
+            .unwrap_or_default())
+    }
+
     /* filterable fields */
 
     /// Writes the filterable fields names in the database.
@@ -654,13 +925,22 @@ impl Index {
     }
 
     /// Identical to `filterable_fields`, but returns ids instead.
+    ///
+    /// The patterns are replaced by the ids of the fields they match, see [`crate::is_field_matched_by`].
     pub fn filterable_fields_ids(&self, rtxn: &RoTxn) -> Result<HashSet<FieldId>> {
         let fields = self.filterable_fields(rtxn)?;
         let fields_ids_map = self.fields_ids_map(rtxn)?;
 
         let mut fields_ids = HashSet::new();
         for name in fields {
-            if let Some(field_id) = fields_ids_map.id(&name) {
+            if crate::is_field_pattern(&name) {
+                fields_ids.extend(
+                    fields_ids_map
+                        .iter()
+                        .filter(|(_, field)| crate::is_field_matched_by(field, &name))
+                        .map(|(id, _)| id),
+                );
+            } else if let Some(field_id) = fields_ids_map.id(&name) {
                 fields_ids.insert(field_id);
             }
         }
@@ -668,6 +948,120 @@ impl Index {
         Ok(fields_ids)
     }
 
+    /* no distribution fields */
+
+    /// Writes the names of the filterable fields that don't support the facet distribution.
+    pub(crate) fn put_no_distribution_fields(
+        &self,
+        wtxn: &mut RwTxn,
+        fields: &HashSet<String>,
+    ) -> heed::Result<()> {
+        self.main.put::<_, Str, SerdeJson<_>>(wtxn, main_key::NO_DISTRIBUTION_FIELDS_KEY, fields)
+    }
+
+    /// Deletes the no distribution fields names in the database.
+    pub(crate) fn delete_no_distribution_fields(&self, wtxn: &mut RwTxn) -> heed::Result<bool> {
+        self.main.delete::<_, Str>(wtxn, main_key::NO_DISTRIBUTION_FIELDS_KEY)
+    }
+
+    /// Returns the names of the filterable fields that don't support the facet distribution.
+    ///
+    /// Only the level 0 of the facet databases is built for those fields.
+    pub fn no_distribution_fields(&self, rtxn: &RoTxn) -> heed::Result<HashSet<String>> {
+        Ok(self
+            .main
+            .get::<_, Str, SerdeJson<_>>(rtxn, main_key::NO_DISTRIBUTION_FIELDS_KEY)?
+            .unwrap_or_default())
+    }
+
+    /* non stored fields */
+
+    /// Writes the names of the fields that are indexed but not stored in the documents database.
+    pub(crate) fn put_non_stored_fields(
+        &self,
+        wtxn: &mut RwTxn,
+        fields: &HashSet<String>,
+    ) -> heed::Result<()> {
+        self.main.put::<_, Str, SerdeJson<_>>(wtxn, main_key::NON_STORED_FIELDS_KEY, fields)
+    }
+
+    /// Deletes the non stored fields names in the database.
+    pub(crate) fn delete_non_stored_fields(&self, wtxn: &mut RwTxn) -> heed::Result<bool> {
+        self.main.delete::<_, Str>(wtxn, main_key::NON_STORED_FIELDS_KEY)
+    }
+
+    /// Returns the names of the fields that are indexed but not stored in the documents database,
+    /// the documents returned by the index don't contain them.
+    pub fn non_stored_fields(&self, rtxn: &RoTxn) -> heed::Result<HashSet<String>> {
+        Ok(self
+            .main
+            .get::<_, Str, SerdeJson<_>>(rtxn, main_key::NON_STORED_FIELDS_KEY)?
+            .unwrap_or_default())
+    }
+
+    /* ignored fields */
+
+    /// Writes the names or patterns of the fields that are dropped from the documents.
+    pub(crate) fn put_ignored_fields(
+        &self,
+        wtxn: &mut RwTxn,
+        fields: &HashSet<String>,
+    ) -> heed::Result<()> {
+        self.main.put::<_, Str, SerdeJson<_>>(wtxn, main_key::IGNORED_FIELDS_KEY, fields)
+    }
+
+    /// Deletes the ignored fields names in the database.
+    pub(crate) fn delete_ignored_fields(&self, wtxn: &mut RwTxn) -> heed::Result<bool> {
+        self.main.delete::<_, Str>(wtxn, main_key::IGNORED_FIELDS_KEY)
+    }
+
+    /// Returns the names or patterns of the fields that are dropped from the documents
+    /// when they are added, along with their nested fields, see [`crate::is_faceted_by`].
+    pub fn ignored_fields(&self, rtxn: &RoTxn) -> heed::Result<HashSet<String>> {
+        Ok(self
+            .main
+            .get::<_, Str, SerdeJson<_>>(rtxn, main_key::IGNORED_FIELDS_KEY)?
+            .unwrap_or_default())
+    }
+
+    /* sort facet values by */
+
+    /// Writes the order in which the values of the facets are kept in the facet distribution.
+    pub(crate) fn put_sort_facet_values_by(
+        &self,
+        wtxn: &mut RwTxn,
+        orders: &HashMap<String, OrderBy>,
+    ) -> heed::Result<()> {
+        self.main.put::<_, Str, SerdeJson<_>>(wtxn, main_key::SORT_FACET_VALUES_BY_KEY, orders)
+    }
+
+    /// Deletes the orders of the values of the facets in the database.
+    pub(crate) fn delete_sort_facet_values_by(&self, wtxn: &mut RwTxn) -> heed::Result<bool> {
+        self.main.delete::<_, Str>(wtxn, main_key::SORT_FACET_VALUES_BY_KEY)
+    }
+
+    /// Returns the order in which the values of each facet are kept in the facet distribution,
+    /// the facets that are not in the map are ordered by [`OrderBy::Alpha`].
+    pub fn sort_facet_values_by(&self, rtxn: &RoTxn) -> heed::Result<HashMap<String, OrderBy>> {
+        Ok(self
+            .main
+            .get::<_, Str, SerdeJson<_>>(rtxn, main_key::SORT_FACET_VALUES_BY_KEY)?
+            .unwrap_or_default())
+    }
+
+    /// Returns the ids of the fields faceted by one of the no distribution fields,
+    /// the nested fields are included.
+    pub fn no_distribution_fields_ids(&self, rtxn: &RoTxn) -> Result<HashSet<FieldId>> {
+        let fields = self.no_distribution_fields(rtxn)?;
+        let fields_ids_map = self.fields_ids_map(rtxn)?;
+
+        Ok(fields_ids_map
+            .iter()
+            .filter(|(_, name)| crate::is_faceted(name, &fields))
+            .map(|(id, _)| id)
+            .collect())
+    }
+
     /* sortable fields */
 
     /// Writes the sortable fields names in the database.
@@ -693,10 +1087,16 @@ impl Index {
     }
 
     /// Identical to `sortable_fields`, but returns ids instead.
+    ///
+    /// The patterns are replaced by the ids of the fields they match, see [`crate::is_field_matched_by`].
     pub fn sortable_fields_ids(&self, rtxn: &RoTxn) -> Result<HashSet<FieldId>> {
         let fields = self.sortable_fields(rtxn)?;
         let fields_ids_map = self.fields_ids_map(rtxn)?;
-        Ok(fields.into_iter().filter_map(|name| fields_ids_map.id(&name)).collect())
+        Ok(fields_ids_map
+            .iter()
+            .filter(|(_, field)| fields.iter().any(|name| crate::is_field_matched_by(field, name)))
+            .map(|(id, _)| id)
+            .collect())
     }
 
     /* faceted fields */
@@ -855,6 +1255,30 @@ impl Index {
         }
     }
 
+    /// Retrieve all the documents which have a null value for this field id
+    pub fn null_faceted_documents_ids(
+        &self,
+        rtxn: &RoTxn,
+        field_id: FieldId,
+    ) -> heed::Result<RoaringBitmap> {
+        match self.facet_id_is_null_docids.get(rtxn, &BEU16::new(field_id))? {
+            Some(docids) => Ok(docids),
+            None => Ok(RoaringBitmap::new()),
+        }
+    }
+
+    /// Retrieve all the documents which have an empty value for this field id
+    pub fn empty_faceted_documents_ids(
+        &self,
+        rtxn: &RoTxn,
+        field_id: FieldId,
+    ) -> heed::Result<RoaringBitmap> {
+        match self.facet_id_is_empty_docids.get(rtxn, &BEU16::new(field_id))? {
+            Some(docids) => Ok(docids),
+            None => Ok(RoaringBitmap::new()),
+        }
+    }
+
     /* distinct field */
 
     pub(crate) fn put_distinct_field(
@@ -934,6 +1358,28 @@ impl Index {
         }
     }
 
+    /* dictionary */
+
+    pub(crate) fn put_dictionary(
+        &self,
+        wtxn: &mut RwTxn,
+        dictionary: &BTreeSet<String>,
+    ) -> heed::Result<()> {
+        self.main.put::<_, Str, SerdeJson<_>>(wtxn, main_key::DICTIONARY_KEY, dictionary)
+    }
+
+    pub(crate) fn delete_dictionary(&self, wtxn: &mut RwTxn) -> heed::Result<bool> {
+        self.main.delete::<_, Str>(wtxn, main_key::DICTIONARY_KEY)
+    }
+
+    /// Returns the sequences of characters that the tokenizer keeps as single words.
+    pub fn dictionary(&self, rtxn: &RoTxn) -> heed::Result<BTreeSet<String>> {
+        Ok(self
+            .main
+            .get::<_, Str, SerdeJson<_>>(rtxn, main_key::DICTIONARY_KEY)?
+            .unwrap_or_default())
+    }
+
     /* synonyms */
 
     pub(crate) fn put_synonyms(
@@ -995,6 +1441,74 @@ impl Index {
         self.word_docids.remap_data_type::<RoaringBitmapLenCodec>().get(rtxn, word)
     }
 
+    /// Returns at most `limit` words of the dictionary starting with the given prefix, in
+    /// lexicographic order, along with the number of documents containing them.
+    ///
+    /// The documents are counted from the serialized bitmaps, without decoding them.
+    pub fn words_with_frequencies(
+        &self,
+        rtxn: &RoTxn,
+        prefix: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<(String, u64)>> {
+        self.words_with_frequencies_inner(rtxn, prefix, None, limit)
+    }
+
+    /// Returns at most `limit` words of the dictionary starting with the given prefix, in
+    /// lexicographic order, along with the number of candidates containing them.
+    ///
+    /// The words that none of the candidates contains are not returned.
+    pub fn words_with_frequencies_in_candidates(
+        &self,
+        rtxn: &RoTxn,
+        prefix: Option<&str>,
+        candidates: &RoaringBitmap,
+        limit: usize,
+    ) -> Result<Vec<(String, u64)>> {
+        self.words_with_frequencies_inner(rtxn, prefix, Some(candidates), limit)
+    }
+
+    fn words_with_frequencies_inner(
+        &self,
+        rtxn: &RoTxn,
+        prefix: Option<&str>,
+        candidates: Option<&RoaringBitmap>,
+        limit: usize,
+    ) -> Result<Vec<(String, u64)>> {
+        let soft_deleted_documents = self.soft_deleted_documents_ids(rtxn)?;
+        let candidates = candidates.map(|candidates| candidates - &soft_deleted_documents);
+
+        let db = self.word_docids.remap_data_type::<ByteSlice>();
+        let iter: Box<dyn Iterator<Item = heed::Result<(&str, &[u8])>>> = match prefix {
+            Some(prefix) if !prefix.is_empty() => Box::new(db.prefix_iter(rtxn, prefix)?),
+            _ => Box::new(db.iter(rtxn)?),
+        };
+
+        let mut words = Vec::new();
+        for result in iter {
+            if words.len() >= limit {
+                break;
+            }
+
+            let (word, bytes) = result?;
+            let count = match &candidates {
+                Some(candidates) => RoaringBitmapLenCodec::intersection_len(bytes, candidates),
+                None => RoaringBitmapLenCodec::deserialize_from_slice(bytes).and_then(|len| {
+                    let deleted =
+                        RoaringBitmapLenCodec::intersection_len(bytes, &soft_deleted_documents)?;
+                    Ok(len - deleted)
+                }),
+            }
+            .map_err(|_| heed::Error::Decoding)?;
+
+            if count != 0 {
+                words.push((word.to_string(), count));
+            }
+        }
+
+        Ok(words)
+    }
+
     /* documents */
 
     /// Returns a [`Vec`] of the requested documents. Returns an error if a document is missing.
@@ -1002,69 +1516,253 @@ impl Index {
         &self,
         rtxn: &'t RoTxn,
         ids: impl IntoIterator<Item = DocumentId>,
-    ) -> Result<Vec<(DocumentId, obkv::KvReaderU16<'t>)>> {
+    ) -> Result<Vec<(DocumentId, StoredDocument<'t>)>> {
         let soft_deleted_documents = self.soft_deleted_documents_ids(rtxn)?;
+        let mut decompressor = self.documents_decompressor(rtxn)?;
         let mut documents = Vec::new();
 
         for id in ids {
             if soft_deleted_documents.contains(id) {
                 return Err(UserError::AccessingSoftDeletedDocument { document_id: id })?;
             }
-            let kv = self
+            let bytes = self
                 .documents
+                .remap_data_type::<ByteSlice>()
                 .get(rtxn, &BEU32::new(id))?
                 .ok_or_else(|| UserError::UnknownInternalDocumentId { document_id: id })?;
-            documents.push((id, kv));
+            documents.push((id, decompressor.decompress(bytes)?));
         }
 
         Ok(documents)
     }
 
+    /// Returns the external ids of the given documents in the order of their internal ids.
+    /// Returns an error if a document is missing.
+    pub fn external_ids_of(
+        &self,
+        rtxn: &RoTxn,
+        ids: &RoaringBitmap,
+    ) -> Result<Vec<(DocumentId, String)>> {
+        self.external_ids_of_iter(rtxn, ids.iter())
+    }
+
+    /// Returns the external ids of the given documents in the order of the slice.
+    /// Returns an error if a document is missing.
+    pub fn external_ids_of_slice(
+        &self,
+        rtxn: &RoTxn,
+        ids: &[DocumentId],
+    ) -> Result<Vec<(DocumentId, String)>> {
+        self.external_ids_of_iter(rtxn, ids.iter().copied())
+    }
+
+    /// Reads the external ids in the primary key field of the stored documents,
+    /// the primary key field and the decompressor are only retrieved once.
+    fn external_ids_of_iter(
+        &self,
+        rtxn: &RoTxn,
+        ids: impl Iterator<Item = DocumentId>,
+    ) -> Result<Vec<(DocumentId, String)>> {
+        let (lower, _) = ids.size_hint();
+        let mut external_ids = Vec::with_capacity(lower);
+        let mut ids = ids.peekable();
+        if ids.peek().is_none() {
+            return Ok(external_ids);
+        }
+
+        let primary_key = self.primary_key(rtxn)?.ok_or(UserError::MissingPrimaryKey)?;
+        let id_field =
+            self.fields_ids_map(rtxn)?.id(primary_key).ok_or(UserError::MissingPrimaryKey)?;
+
+        let soft_deleted_documents = self.soft_deleted_documents_ids(rtxn)?;
+        let mut decompressor = self.documents_decompressor(rtxn)?;
+        for id in ids {
+            if soft_deleted_documents.contains(id) {
+                return Err(UserError::AccessingSoftDeletedDocument { document_id: id })?;
+            }
+            let bytes = self
+                .documents
+                .remap_data_type::<ByteSlice>()
+                .get(rtxn, &BEU32::new(id))?
+                .ok_or(UserError::UnknownInternalDocumentId { document_id: id })?;
+            let document = decompressor.decompress(bytes)?;
+            let content = document.as_obkv().get(id_field).ok_or_else(|| {
+                InternalError::DatabaseMissingEntry { db_name: db_name::DOCUMENTS, key: None }
+            })?;
+            let external_id =
+                match serde_json::from_slice(content).map_err(InternalError::SerdeJson)? {
+                    serde_json::Value::String(string) => string,
+                    serde_json::Value::Number(number) => number.to_string(),
+                    document_id => return Err(UserError::InvalidDocumentId { document_id })?,
+                };
+            external_ids.push((id, external_id));
+        }
+
+        Ok(external_ids)
+    }
+
+    /// Returns the requested documents as JSON objects with only the values at the given
+    /// dotted paths, see [`flatten_serde_json::select`]. Returns an error if a document is missing.
+    ///
+    /// Only the top-level fields that can contain a selected value are deserialized.
+    pub fn documents_projected(
+        &self,
+        rtxn: &RoTxn,
+        ids: impl IntoIterator<Item = DocumentId>,
+        fields: &[&str],
+    ) -> Result<Vec<(DocumentId, Object)>> {
+        let fields_ids_map = self.fields_ids_map(rtxn)?;
+        let top_level_fields: Vec<_> = fields_ids_map
+            .iter()
+            .filter(|(_, name)| {
+                fields.iter().any(|field| {
+                    let field = field.strip_suffix('*').unwrap_or(field);
+                    name.starts_with(field) || field.starts_with(name)
+                })
+            })
+            .map(|(id, _)| id)
+            .collect();
+
+        self.documents(rtxn, ids)?
+            .into_iter()
+            .map(|(id, document)| {
+                let object =
+                    crate::obkv_to_json(&top_level_fields, &fields_ids_map, document.as_obkv())?;
+                Ok((id, flatten_serde_json::select(&object, fields)))
+            })
+            .collect()
+    }
+
     /// Returns an iterator over all the documents in the index.
     pub fn all_documents<'t>(
         &self,
         rtxn: &'t RoTxn,
-    ) -> Result<impl Iterator<Item = heed::Result<(DocumentId, obkv::KvReaderU16<'t>)>>> {
+    ) -> Result<impl Iterator<Item = Result<(DocumentId, StoredDocument<'t>)>>> {
         let soft_deleted_docids = self.soft_deleted_documents_ids(rtxn)?;
+        let mut decompressor = self.documents_decompressor(rtxn)?;
 
         Ok(self
             .documents
+            .remap_data_type::<ByteSlice>()
             .iter(rtxn)?
             // we cast the BEU32 to a DocumentId
-            .map(|document| document.map(|(id, obkv)| (id.get(), obkv)))
+            .map(|document| document.map(|(id, bytes)| (id.get(), bytes)))
             .filter(move |document| {
                 document.as_ref().map_or(true, |(id, _)| !soft_deleted_docids.contains(*id))
+            })
+            .map(move |document| -> Result<_> {
+                let (id, bytes) = document?;
+                Ok((id, decompressor.decompress(bytes)?))
             }))
     }
 
-    pub fn facets_distribution<'a>(&'a self, rtxn: &'a RoTxn) -> FacetDistribution<'a> {
-        FacetDistribution::new(rtxn, self)
+    /// Returns at most `limit` documents with an internal id greater than `after`,
+    /// or from the first document when `after` is `None`.
+    ///
+    /// The returned resume token can be given to a later call made with another read
+    /// transaction, the documents deleted in between are then skipped.
+    pub fn documents_page<'t>(
+        &self,
+        rtxn: &'t RoTxn,
+        after: Option<DocumentId>,
+        limit: usize,
+    ) -> Result<DocumentsPage<'t>> {
+        let soft_deleted_docids = self.soft_deleted_documents_ids(rtxn)?;
+        let mut decompressor = self.documents_decompressor(rtxn)?;
+        let start = match after {
+            Some(after) => Bound::Excluded(BEU32::new(after)),
+            None => Bound::Unbounded,
+        };
+
+        let mut documents = Vec::new();
+        let mut iter = self
+            .documents
+            .remap_data_type::<ByteSlice>()
+            .range(rtxn, &(start, Bound::Unbounded))?;
+        while documents.len() < limit {
+            match iter.next().transpose()? {
+                Some((id, _)) if soft_deleted_docids.contains(id.get()) => continue,
+                Some((id, bytes)) => documents.push((id.get(), decompressor.decompress(bytes)?)),
+                None => return Ok(DocumentsPage { documents, next: None }),
+            }
+        }
+
+        let next = documents.last().map(|(id, _)| *id);
+        Ok(DocumentsPage { documents, next })
     }
 
-    pub fn search<'a>(&'a self, rtxn: &'a RoTxn) -> Search<'a> {
-        Search::new(rtxn, self)
+    /// Returns the decompressor that must be used to read the documents database.
+    pub fn documents_decompressor(&self, rtxn: &RoTxn) -> Result<DocumentsDecompressor> {
+        DocumentsDecompressor::new(self.documents_compression_dictionary(rtxn)?)
+    }
+
+    /// Returns the facet values of a field of a document, after the flattening
+    /// and the normalization of the indexation.
+    pub fn facet_values_for_document(
+        &self,
+        rtxn: &RoTxn,
+        docid: DocumentId,
+        field_id: FieldId,
+    ) -> Result<DocumentFacetValues> {
+        let mut prefix = Vec::with_capacity(size_of::<FieldId>() + size_of::<DocumentId>());
+        prefix.extend_from_slice(&field_id.to_be_bytes());
+        prefix.extend_from_slice(&docid.to_be_bytes());
+
+        let mut values = DocumentFacetValues::default();
+        let iter = self
+            .field_id_docid_facet_f64s
+            .remap_key_type::<ByteSlice>()
+            .prefix_iter(rtxn, &prefix)?
+            .remap_key_type::<FieldDocIdFacetF64Codec>();
+        for result in iter {
+            let ((_, _, number), exact_integers) = result?;
+            values.numbers.push(number);
+            values.exact_integers.extend(exact_integers);
+        }
+
+        let iter = self
+            .field_id_docid_facet_strings
+            .remap_key_type::<ByteSlice>()
+            .prefix_iter(rtxn, &prefix)?
+            .remap_key_type::<FieldDocIdFacetStringCodec>();
+        for result in iter {
+            let ((_, _, normalized), original) = result?;
+            values.strings.push((normalized.to_string(), original.to_string()));
+        }
+
+        Ok(values)
+    }
+
+    pub fn facets_distribution<'a>(&'a self, rtxn: &'a RoTxn) -> FacetDistribution<'a> {
+        FacetDistribution::new(rtxn, self)
+    }
+
+    pub fn search<'a>(&'a self, rtxn: &'a RoTxn) -> Search<'a> {
+        Search::new(rtxn, self)
+    }
+
+    pub fn multi_search<'a>(&'a self, queries: &'a [SearchQuery<'a>]) -> MultiSearch<'a> {
+        MultiSearch::new(self, queries)
     }
 
     /// Returns the index creation time.
     pub fn created_at(&self, rtxn: &RoTxn) -> Result<OffsetDateTime> {
-        Ok(self
-            .main
-            .get::<_, Str, SerdeJson<OffsetDateTime>>(rtxn, main_key::CREATED_AT_KEY)?
-            .ok_or(InternalError::DatabaseMissingEntry {
+        Ok(self.main.get::<_, Str, OffsetDateTimeCodec>(rtxn, main_key::CREATED_AT_KEY)?.ok_or(
+            InternalError::DatabaseMissingEntry {
                 db_name: db_name::MAIN,
                 key: Some(main_key::CREATED_AT_KEY),
-            })?)
+            },
+        )?)
     }
 
     /// Returns the index last updated time.
     pub fn updated_at(&self, rtxn: &RoTxn) -> Result<OffsetDateTime> {
-        Ok(self
-            .main
-            .get::<_, Str, SerdeJson<OffsetDateTime>>(rtxn, main_key::UPDATED_AT_KEY)?
-            .ok_or(InternalError::DatabaseMissingEntry {
+        Ok(self.main.get::<_, Str, OffsetDateTimeCodec>(rtxn, main_key::UPDATED_AT_KEY)?.ok_or(
+            InternalError::DatabaseMissingEntry {
                 db_name: db_name::MAIN,
                 key: Some(main_key::UPDATED_AT_KEY),
-            })?)
+            },
+        )?)
     }
 
     pub(crate) fn set_updated_at(
@@ -1072,7 +1770,45 @@ impl Index {
         wtxn: &mut RwTxn,
         time: &OffsetDateTime,
     ) -> heed::Result<()> {
-        self.main.put::<_, Str, SerdeJson<OffsetDateTime>>(wtxn, main_key::UPDATED_AT_KEY, &time)
+        self.main.put::<_, Str, OffsetDateTimeCodec>(wtxn, main_key::UPDATED_AT_KEY, &time)
+    }
+
+    /// Returns the id of the last successful update of the index, it is increased at the end of
+    /// every document addition, document deletion, documents clear and settings update.
+    ///
+    /// The searches cache what they read from the index by update id, the update id of an
+    /// update aborted by this process is skipped.
+    pub fn update_id(&self, rtxn: &RoTxn) -> heed::Result<u64> {
+        Ok(self
+            .main
+            .get::<_, Str, OwnedType<u64>>(rtxn, main_key::UPDATE_ID_KEY)?
+            .unwrap_or_default())
+    }
+
+    /// Marks the end of a successful update, the update time is refreshed and the update id
+    /// increased.
+    pub(crate) fn end_update(&self, wtxn: &mut RwTxn) -> heed::Result<()> {
+        let current = self.update_id(wtxn)?;
+        let last = self
+            .last_update_id
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| Some(last.max(current) + 1))
+            .unwrap();
+        let update_id = last.max(current) + 1;
+        self.main.put::<_, Str, OwnedType<u64>>(wtxn, main_key::UPDATE_ID_KEY, &update_id)?;
+        self.set_updated_at(wtxn, &OffsetDateTime::now_utc())
+    }
+
+    /// Returns the settings read by the searches at the version of the index seen by the
+    /// transaction, they are only read from the index once per version of the index.
+    pub fn searchable_context(&self, rtxn: &RoTxn) -> Result<Arc<SearchableContext>> {
+        self.searchable_context.get_or_build(self, rtxn)
+    }
+
+    /// Caches the docids of the words read by the searches, up to the given serialized size
+    /// of the cached bitmaps. The cache is shared by the clones of the index made afterward,
+    /// `None` disables it.
+    pub fn set_docids_cache_max_size(&mut self, max_size: Option<usize>) {
+        self.docids_cache = max_size.map(|max_size| Arc::new(DocidsCache::new(max_size)));
     }
 
     pub fn authorize_typos(&self, txn: &RoTxn) -> heed::Result<bool> {
@@ -1094,6 +1830,73 @@ impl Index {
         Ok(())
     }
 
+    /// Returns whether the query words can be split in two words at search time.
+    pub fn enable_word_splitting(&self, txn: &RoTxn) -> heed::Result<bool> {
+        // The absence of a value is true, because by default, we split the words.
+        match self.main.get::<_, Str, OwnedType<u8>>(txn, main_key::ENABLE_WORD_SPLITTING)? {
+            Some(0) => Ok(false),
+            _ => Ok(true),
+        }
+    }
+
+    pub(crate) fn put_enable_word_splitting(
+        &self,
+        txn: &mut RwTxn,
+        flag: bool,
+    ) -> heed::Result<()> {
+        self.main.put::<_, Str, OwnedType<u8>>(txn, main_key::ENABLE_WORD_SPLITTING, &(flag as u8))
+    }
+
+    pub(crate) fn delete_enable_word_splitting(&self, txn: &mut RwTxn) -> heed::Result<bool> {
+        self.main.delete::<_, Str>(txn, main_key::ENABLE_WORD_SPLITTING)
+    }
+
+    /// Returns whether the consecutive query words can be concatenated at search time.
+    pub fn enable_word_concatenation(&self, txn: &RoTxn) -> heed::Result<bool> {
+        // The absence of a value is true, because by default, we concatenate the words.
+        match self.main.get::<_, Str, OwnedType<u8>>(txn, main_key::ENABLE_WORD_CONCATENATION)? {
+            Some(0) => Ok(false),
+            _ => Ok(true),
+        }
+    }
+
+    pub(crate) fn put_enable_word_concatenation(
+        &self,
+        txn: &mut RwTxn,
+        flag: bool,
+    ) -> heed::Result<()> {
+        self.main.put::<_, Str, OwnedType<u8>>(
+            txn,
+            main_key::ENABLE_WORD_CONCATENATION,
+            &(flag as u8),
+        )
+    }
+
+    pub(crate) fn delete_enable_word_concatenation(&self, txn: &mut RwTxn) -> heed::Result<bool> {
+        self.main.delete::<_, Str>(txn, main_key::ENABLE_WORD_CONCATENATION)
+    }
+
+    /// Returns whether the query words and phrases prefixed by a minus exclude documents.
+    pub fn enable_negative_terms(&self, txn: &RoTxn) -> heed::Result<bool> {
+        // The absence of a value is true, because by default, we parse the negative terms.
+        match self.main.get::<_, Str, OwnedType<u8>>(txn, main_key::ENABLE_NEGATIVE_TERMS)? {
+            Some(0) => Ok(false),
+            _ => Ok(true),
+        }
+    }
+
+    pub(crate) fn put_enable_negative_terms(
+        &self,
+        txn: &mut RwTxn,
+        flag: bool,
+    ) -> heed::Result<()> {
+        self.main.put::<_, Str, OwnedType<u8>>(txn, main_key::ENABLE_NEGATIVE_TERMS, &(flag as u8))
+    }
+
+    pub(crate) fn delete_enable_negative_terms(&self, txn: &mut RwTxn) -> heed::Result<bool> {
+        self.main.delete::<_, Str>(txn, main_key::ENABLE_NEGATIVE_TERMS)
+    }
+
     pub fn min_word_len_one_typo(&self, txn: &RoTxn) -> heed::Result<u8> {
         // It is not possible to put a bool in heed with OwnedType, so we put a u8 instead. We
         // identify 0 as being false, and anything else as true. The absence of a value is true,
@@ -1205,6 +2008,259 @@ impl Index {
     pub(crate) fn delete_pagination_max_total_hits(&self, txn: &mut RwTxn) -> heed::Result<bool> {
         self.main.delete::<_, Str>(txn, main_key::PAGINATION_MAX_TOTAL_HITS)
     }
+
+    /// Returns the maximum proximity stored in the word pair proximity databases,
+    /// the pairs of words farther away from each other are not stored.
+    pub fn max_proximity(&self, txn: &RoTxn) -> heed::Result<u8> {
+        Ok(self
+            .main
+            .get::<_, Str, OwnedType<u8>>(txn, main_key::MAX_PROXIMITY)?
+            .unwrap_or(DEFAULT_MAX_PROXIMITY))
+    }
+
+    pub(crate) fn put_max_proximity(&self, txn: &mut RwTxn, val: u8) -> heed::Result<()> {
+        self.main.put::<_, Str, OwnedType<u8>>(txn, main_key::MAX_PROXIMITY, &val)
+    }
+
+    /// Returns how precisely the proximity between the query words is measured,
+    /// the word pair proximity databases are only built with [`ProximityPrecision::ByWord`].
+    pub fn proximity_precision(&self, txn: &RoTxn) -> heed::Result<ProximityPrecision> {
+        Ok(self
+            .main
+            .get::<_, Str, SerdeJson<ProximityPrecision>>(txn, main_key::PROXIMITY_PRECISION)?
+            .unwrap_or_default())
+    }
+
+    pub(crate) fn put_proximity_precision(
+        &self,
+        txn: &mut RwTxn,
+        val: ProximityPrecision,
+    ) -> heed::Result<()> {
+        self.main.put::<_, Str, SerdeJson<ProximityPrecision>>(
+            txn,
+            main_key::PROXIMITY_PRECISION,
+            &val,
+        )
+    }
+
+    pub(crate) fn delete_proximity_precision(&self, txn: &mut RwTxn) -> heed::Result<bool> {
+        self.main.delete::<_, Str>(txn, main_key::PROXIMITY_PRECISION)
+    }
+
+    /// Returns how precisely the positions of the words are stored in the word position
+    /// and field id word count databases.
+    pub fn position_bucketing(&self, txn: &RoTxn) -> heed::Result<PositionBucketing> {
+        Ok(self
+            .main
+            .get::<_, Str, SerdeJson<PositionBucketing>>(txn, main_key::POSITION_BUCKETING)?
+            .unwrap_or_default())
+    }
+
+    pub(crate) fn put_position_bucketing(
+        &self,
+        txn: &mut RwTxn,
+        val: PositionBucketing,
+    ) -> heed::Result<()> {
+        self.main.put::<_, Str, SerdeJson<PositionBucketing>>(
+            txn,
+            main_key::POSITION_BUCKETING,
+            &val,
+        )
+    }
+
+    pub(crate) fn delete_position_bucketing(&self, txn: &mut RwTxn) -> heed::Result<bool> {
+        self.main.delete::<_, Str>(txn, main_key::POSITION_BUCKETING)
+    }
+
+    /// Returns whether the strings of the filterable fields that are finite numbers
+    /// are also indexed as numbers, to be matched by the range filters.
+    pub fn coerce_numeric_strings(&self, txn: &RoTxn) -> heed::Result<bool> {
+        // The absence of a value is false, the strings are not coerced by default.
+        match self.main.get::<_, Str, OwnedType<u8>>(txn, main_key::COERCE_NUMERIC_STRINGS)? {
+            Some(0) | None => Ok(false),
+            _ => Ok(true),
+        }
+    }
+
+    pub(crate) fn put_coerce_numeric_strings(
+        &self,
+        txn: &mut RwTxn,
+        flag: bool,
+    ) -> heed::Result<()> {
+        self.main.put::<_, Str, OwnedType<u8>>(txn, main_key::COERCE_NUMERIC_STRINGS, &(flag as u8))
+    }
+
+    pub(crate) fn delete_coerce_numeric_strings(&self, txn: &mut RwTxn) -> heed::Result<bool> {
+        self.main.delete::<_, Str>(txn, main_key::COERCE_NUMERIC_STRINGS)
+    }
+
+    /// Returns whether the diacritics are kept in the indexed and searched words, a query word
+    /// that only differs from a word of the index by its diacritics then costs typos.
+    pub fn count_diacritics(&self, txn: &RoTxn) -> heed::Result<bool> {
+        // The absence of a value is false, the diacritics are removed by default.
+        match self.main.get::<_, Str, OwnedType<u8>>(txn, main_key::COUNT_DIACRITICS)? {
+            Some(0) | None => Ok(false),
+            _ => Ok(true),
+        }
+    }
+
+    pub(crate) fn put_count_diacritics(&self, txn: &mut RwTxn, flag: bool) -> heed::Result<()> {
+        self.main.put::<_, Str, OwnedType<u8>>(txn, main_key::COUNT_DIACRITICS, &(flag as u8))
+    }
+
+    pub(crate) fn delete_count_diacritics(&self, txn: &mut RwTxn) -> heed::Result<bool> {
+        self.main.delete::<_, Str>(txn, main_key::COUNT_DIACRITICS)
+    }
+
+    /// Returns the parameters of the computation of the words prefixes.
+    pub fn prefix_settings(&self, txn: &RoTxn) -> heed::Result<PrefixSettings> {
+        Ok(self
+            .main
+            .get::<_, Str, SerdeJson<PrefixSettings>>(txn, main_key::PREFIX_SETTINGS)?
+            .unwrap_or_default())
+    }
+
+    pub(crate) fn put_prefix_settings(
+        &self,
+        txn: &mut RwTxn,
+        val: &PrefixSettings,
+    ) -> heed::Result<()> {
+        self.main.put::<_, Str, SerdeJson<PrefixSettings>>(txn, main_key::PREFIX_SETTINGS, val)
+    }
+
+    pub(crate) fn delete_prefix_settings(&self, txn: &mut RwTxn) -> heed::Result<bool> {
+        self.main.delete::<_, Str>(txn, main_key::PREFIX_SETTINGS)
+    }
+
+    /// Returns the parameters the words prefixes and the prefix databases were computed with.
+    pub(crate) fn words_prefixes_settings(&self, txn: &RoTxn) -> heed::Result<PrefixSettings> {
+        Ok(self
+            .main
+            .get::<_, Str, SerdeJson<PrefixSettings>>(txn, main_key::WORDS_PREFIXES_SETTINGS)?
+            .unwrap_or_default())
+    }
+
+    pub(crate) fn put_words_prefixes_settings(
+        &self,
+        txn: &mut RwTxn,
+        val: &PrefixSettings,
+    ) -> heed::Result<()> {
+        self.main.put::<_, Str, SerdeJson<PrefixSettings>>(
+            txn,
+            main_key::WORDS_PREFIXES_SETTINGS,
+            val,
+        )
+    }
+
+    /// Returns the number of elements of a facet level that are grouped into a single element
+    /// of the level above.
+    pub fn facet_level_group_size(&self, txn: &RoTxn) -> heed::Result<usize> {
+        Ok(self
+            .main
+            .get::<_, Str, OwnedType<usize>>(txn, main_key::FACET_LEVEL_GROUP_SIZE)?
+            .unwrap_or(DEFAULT_FACET_LEVEL_GROUP_SIZE))
+    }
+
+    pub(crate) fn put_facet_level_group_size(
+        &self,
+        txn: &mut RwTxn,
+        val: usize,
+    ) -> heed::Result<()> {
+        self.main.put::<_, Str, OwnedType<usize>>(txn, main_key::FACET_LEVEL_GROUP_SIZE, &val)
+    }
+
+    pub(crate) fn delete_facet_level_group_size(&self, txn: &mut RwTxn) -> heed::Result<bool> {
+        self.main.delete::<_, Str>(txn, main_key::FACET_LEVEL_GROUP_SIZE)
+    }
+
+    /// Returns the number of elements above which a facet group is split when the facet levels
+    /// are updated in place, twice the facet level group size when it is not set.
+    pub fn facet_max_group_size(&self, txn: &RoTxn) -> heed::Result<usize> {
+        match self.custom_facet_max_group_size(txn)? {
+            Some(value) => Ok(value),
+            None => Ok(2 * self.facet_level_group_size(txn)?),
+        }
+    }
+
+    /// Returns the maximum facet group size if it was set.
+    pub(crate) fn custom_facet_max_group_size(&self, txn: &RoTxn) -> heed::Result<Option<usize>> {
+        self.main.get::<_, Str, OwnedType<usize>>(txn, main_key::FACET_MAX_GROUP_SIZE)
+    }
+
+    pub(crate) fn put_facet_max_group_size(&self, txn: &mut RwTxn, val: usize) -> heed::Result<()> {
+        self.main.put::<_, Str, OwnedType<usize>>(txn, main_key::FACET_MAX_GROUP_SIZE, &val)
+    }
+
+    pub(crate) fn delete_facet_max_group_size(&self, txn: &mut RwTxn) -> heed::Result<bool> {
+        self.main.delete::<_, Str>(txn, main_key::FACET_MAX_GROUP_SIZE)
+    }
+
+    /// Returns the minimum number of elements of a facet level, the levels that would be smaller
+    /// are not built.
+    pub fn facet_min_level_size(&self, txn: &RoTxn) -> heed::Result<usize> {
+        Ok(self
+            .main
+            .get::<_, Str, OwnedType<usize>>(txn, main_key::FACET_MIN_LEVEL_SIZE)?
+            .unwrap_or(DEFAULT_FACET_MIN_LEVEL_SIZE))
+    }
+
+    pub(crate) fn put_facet_min_level_size(&self, txn: &mut RwTxn, val: usize) -> heed::Result<()> {
+        self.main.put::<_, Str, OwnedType<usize>>(txn, main_key::FACET_MIN_LEVEL_SIZE, &val)
+    }
+
+    pub(crate) fn delete_facet_min_level_size(&self, txn: &mut RwTxn) -> heed::Result<bool> {
+        self.main.delete::<_, Str>(txn, main_key::FACET_MIN_LEVEL_SIZE)
+    }
+
+    /// Returns whether the documents must be compressed when they are written.
+    pub fn documents_compression(&self, txn: &RoTxn) -> heed::Result<bool> {
+        // It is not possible to put a bool in heed with OwnedType, so we put a u8 instead.
+        // The absence of a value is false, because by default, we don't compress the documents.
+        match self.main.get::<_, Str, OwnedType<u8>>(txn, main_key::DOCUMENTS_COMPRESSION)? {
+            Some(0) | None => Ok(false),
+            Some(_) => Ok(true),
+        }
+    }
+
+    pub(crate) fn put_documents_compression(
+        &self,
+        txn: &mut RwTxn,
+        flag: bool,
+    ) -> heed::Result<()> {
+        self.main.put::<_, Str, OwnedType<u8>>(txn, main_key::DOCUMENTS_COMPRESSION, &(flag as u8))
+    }
+
+    pub(crate) fn delete_documents_compression(&self, txn: &mut RwTxn) -> heed::Result<bool> {
+        self.main.delete::<_, Str>(txn, main_key::DOCUMENTS_COMPRESSION)
+    }
+
+    /// Returns the dictionary the documents are compressed with,
+    /// `None` if the documents are not compressed.
+    pub fn documents_compression_dictionary<'t>(
+        &self,
+        txn: &'t RoTxn,
+    ) -> heed::Result<Option<&'t [u8]>> {
+        self.main.get::<_, Str, ByteSlice>(txn, main_key::DOCUMENTS_COMPRESSION_DICTIONARY)
+    }
+
+    pub(crate) fn put_documents_compression_dictionary(
+        &self,
+        txn: &mut RwTxn,
+        dictionary: &[u8],
+    ) -> heed::Result<()> {
+        self.main.put::<_, Str, ByteSlice>(
+            txn,
+            main_key::DOCUMENTS_COMPRESSION_DICTIONARY,
+            dictionary,
+        )
+    }
+
+    pub(crate) fn delete_documents_compression_dictionary(
+        &self,
+        txn: &mut RwTxn,
+    ) -> heed::Result<bool> {
+        self.main.delete::<_, Str>(txn, main_key::DOCUMENTS_COMPRESSION_DICTIONARY)
+    }
 }
 
 #[cfg(test)]
@@ -1212,13 +2268,20 @@ pub(crate) mod tests {
     use std::ops::Deref;
 
     use big_s::S;
+    use heed::types::{SerdeJson, Str};
     use heed::{EnvOpenOptions, RwTxn};
+    use roaring::RoaringBitmap;
     use tempfile::TempDir;
 
     use crate::documents::DocumentsBatchReader;
-    use crate::index::{DEFAULT_MIN_WORD_LEN_ONE_TYPO, DEFAULT_MIN_WORD_LEN_TWO_TYPOS};
-    use crate::update::{self, IndexDocuments, IndexDocumentsConfig, IndexerConfig, Settings};
-    use crate::{db_snap, Index};
+    use crate::index::{
+        main_key, DocumentFacetValues, DEFAULT_MIN_WORD_LEN_ONE_TYPO,
+        DEFAULT_MIN_WORD_LEN_TWO_TYPOS,
+    };
+    use crate::update::{
+        self, DeleteDocuments, IndexDocuments, IndexDocumentsConfig, IndexerConfig, Settings,
+    };
+    use crate::{db_snap, FieldDistribution, FieldTypes, Index};
 
     pub(crate) struct TempIndex {
         pub inner: Index,
@@ -1265,6 +2328,7 @@ pub(crate) mod tests {
                 &self.indexer_config,
                 self.index_documents_config.clone(),
                 |_| (),
+                || false,
             )
             .unwrap();
             let (builder, user_error) = builder.add_documents(documents).unwrap();
@@ -1376,6 +2440,126 @@ pub(crate) mod tests {
         );
     }
 
+    #[test]
+    fn index_stats() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 1, "name": "kevin" },
+                { "id": 2, "name": "bob", "age": 20 },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let stats = index.stats(&rtxn, false).unwrap();
+        assert_eq!(stats.number_of_documents, 2);
+        assert_eq!(stats.field_distribution, index.field_distribution(&rtxn).unwrap());
+        assert_eq!(stats.databases["documents"].number_of_entries, 2);
+        assert!(stats.databases.values().all(|stats| stats.total_size.is_none()));
+
+        let names = stats.databases.keys().copied().collect::<Vec<_>>().join("\n");
+        insta::assert_snapshot!(names, @r###"
+        docid-word-positions
+        documents
+        exact-word-docids
+        exact-word-prefix-docids
+        facet-id-exists-docids
+        facet-id-f64-docids
+        facet-id-is-empty-docids
+        facet-id-is-null-docids
+        facet-id-string-docids
+        field-id-docid-facet-f64s
+        field-id-docid-facet-strings
+        field-id-word-count-docids
+        main
+        prefix-word-pair-proximity-docids
+        word-docids
+        word-pair-proximity-docids
+        word-position-docids
+        word-prefix-docids
+        word-prefix-pair-proximity-docids
+        word-prefix-position-docids
+        "###);
+
+        // The exact scan counts the same entries and also computes their sizes.
+        let exact_stats = index.stats(&rtxn, true).unwrap();
+        for (name, stats) in &stats.databases {
+            let exact = exact_stats.databases[name];
+            assert_eq!(exact.number_of_entries, stats.number_of_entries);
+            assert_eq!(exact.total_size.unwrap() > 0, exact.number_of_entries > 0);
+        }
+    }
+
+    #[test]
+    fn field_types_of_mixed_fields() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 1, "price": 12.5, "name": "kevin" },
+                { "id": 2, "price": "13", "name": "bob" },
+                { "id": 3, "price": null, "name": "bobby", "tags": ["a", "b"] },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let field_types = index.field_types(&rtxn).unwrap();
+        let mut price = FieldTypes::NUMBER;
+        price.insert(FieldTypes::STRING);
+        price.insert(FieldTypes::NULL);
+        assert_eq!(field_types["price"], price);
+        assert!(field_types["price"].is_mixed());
+        assert_eq!(field_types["id"], FieldTypes::NUMBER);
+        assert_eq!(field_types["name"], FieldTypes::STRING);
+        assert_eq!(field_types["tags"], FieldTypes::ARRAY);
+        assert_eq!(index.field_distribution(&rtxn).unwrap()["price"], 3);
+        drop(rtxn);
+
+        // Deleting the documents decrements the counts and forgets
+        // the types of the fields that are no longer present.
+        let mut wtxn = index.write_txn().unwrap();
+        let mut builder = DeleteDocuments::new(&mut wtxn, &index).unwrap();
+        builder.delete_external_id("3");
+        builder.execute().unwrap();
+        wtxn.commit().unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let field_types = index.field_types(&rtxn).unwrap();
+        assert!(field_types.get("tags").is_none());
+        assert_eq!(index.field_distribution(&rtxn).unwrap()["price"], 2);
+    }
+
+    #[test]
+    fn upgrade_field_distribution_without_types() {
+        let index = TempIndex::new();
+        index.add_documents(documents!([{ "id": 1, "name": "kevin" }])).unwrap();
+
+        // We write the field distribution in its previous format, without the types.
+        let mut wtxn = index.write_txn().unwrap();
+        let old_distribution: FieldDistribution =
+            maplit::btreemap! { S("id") => 1, S("name") => 1 };
+        index
+            .main
+            .put::<_, Str, SerdeJson<FieldDistribution>>(
+                &mut wtxn,
+                main_key::FIELD_DISTRIBUTION_KEY,
+                &old_distribution,
+            )
+            .unwrap();
+        wtxn.commit().unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.field_distribution(&rtxn).unwrap(), old_distribution);
+        assert!(index.field_types(&rtxn).unwrap().values().all(|types| types.is_empty()));
+        drop(rtxn);
+
+        // The next document addition stores the types along with the counts.
+        index.add_documents(documents!([{ "id": 2, "name": "bob" }])).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.field_distribution(&rtxn).unwrap()["name"], 2);
+        assert_eq!(index.field_types(&rtxn).unwrap()["name"], FieldTypes::STRING);
+    }
+
     #[test]
     fn put_and_retrieve_disable_typo() {
         let index = TempIndex::new();
@@ -1469,4 +2653,412 @@ pub(crate) mod tests {
         let user_defined = index.user_defined_searchable_fields(&rtxn).unwrap().unwrap();
         assert_eq!(user_defined, &["doggo", "name"]);
     }
+
+    #[test]
+    fn fields_patterns_match_the_fields_added_after_the_settings() {
+        let index = TempIndex::new();
+
+        index
+            .update_settings(|settings| {
+                settings.set_searchable_fields(vec![S("translations.*.title")]);
+                settings.set_filterable_fields(maplit::hashset! { S("translations.*.title") });
+                settings.set_displayed_fields(vec![S("id"), S("translations.*.title")]);
+            })
+            .unwrap();
+
+        index
+            .add_documents(documents!([
+                { "id": 1, "translations": { "en": { "title": "hello", "body": "lorem" } } },
+                { "id": 2, "translations": { "fr": { "title": "bonjour", "body": "ipsum" } } },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let real = index.searchable_fields(&rtxn).unwrap().unwrap();
+        assert_eq!(real, &["translations.en.title", "translations.fr.title"]);
+        let user_defined = index.user_defined_searchable_fields(&rtxn).unwrap().unwrap();
+        assert_eq!(user_defined, &["translations.*.title"]);
+        // the pattern is not a field
+        assert_eq!(index.fields_ids_map(&rtxn).unwrap().id("translations.*.title"), None);
+        drop(rtxn);
+
+        // a new language is added after the settings
+        index
+            .add_documents(documents!([
+                { "id": 3, "translations": { "de": { "title": "hallo", "body": "dolor" } } },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let real = index.searchable_fields(&rtxn).unwrap().unwrap();
+        assert_eq!(
+            real,
+            &["translations.en.title", "translations.fr.title", "translations.de.title"]
+        );
+
+        let crate::SearchResult { documents_ids, .. } =
+            index.search(&rtxn).query("hallo").execute().unwrap();
+        assert_eq!(documents_ids, vec![2]);
+        let crate::SearchResult { documents_ids, .. } =
+            index.search(&rtxn).query("dolor").execute().unwrap();
+        assert!(documents_ids.is_empty());
+
+        let filter = crate::Filter::from_str("translations.de.title = hallo").unwrap().unwrap();
+        assert_eq!(filter.evaluate(&rtxn, &index).unwrap().iter().collect::<Vec<_>>(), vec![2]);
+
+        let fields_ids_map = index.fields_ids_map(&rtxn).unwrap();
+        let displayed: Vec<_> = index
+            .displayed_fields_ids(&rtxn)
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|id| fields_ids_map.name(id).unwrap())
+            .collect();
+        assert_eq!(
+            displayed,
+            &["id", "translations.en.title", "translations.fr.title", "translations.de.title"]
+        );
+    }
+
+    #[test]
+    fn documents_projected() {
+        let index = TempIndex::new();
+
+        index
+            .add_documents(documents!([
+                { "id": 1, "a": { "b": "c", "d": { "e": "f" } }, "g": [{ "h": 1 }, { "i": 2 }] },
+                { "id": 2, "ab": "c", "g": "h" },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let projected = |fields: &[&str]| {
+            index
+                .documents_projected(&rtxn, vec![0, 1], fields)
+                .unwrap()
+                .into_iter()
+                .map(|(_, document)| serde_json::Value::Object(document))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            projected(&["id", "a.d.e", "g.h"]),
+            vec![
+                serde_json::json!({ "id": 1, "a": { "d": { "e": "f" } }, "g": [{ "h": 1 }] }),
+                serde_json::json!({ "id": 2 }),
+            ]
+        );
+        // the overlapping paths select the whole parent
+        assert_eq!(
+            projected(&["a", "a.b"]),
+            vec![
+                serde_json::json!({ "a": { "b": "c", "d": { "e": "f" } } }),
+                serde_json::json!({})
+            ]
+        );
+        assert_eq!(
+            projected(&["a.b", "a"]),
+            vec![
+                serde_json::json!({ "a": { "b": "c", "d": { "e": "f" } } }),
+                serde_json::json!({})
+            ]
+        );
+        assert_eq!(
+            projected(&["a*"]),
+            vec![
+                serde_json::json!({ "a": { "b": "c", "d": { "e": "f" } } }),
+                serde_json::json!({ "ab": "c" }),
+            ]
+        );
+        assert_eq!(
+            projected(&["missing", "g.missing"]),
+            vec![serde_json::json!({}), serde_json::json!({})]
+        );
+    }
+
+    #[test]
+    fn external_ids_of() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": "a", "text": "hello" },
+                { "id": 12, "text": "world" },
+                { "id": "c", "text": "!" },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let ids: RoaringBitmap = [2, 0].iter().copied().collect();
+        assert_eq!(index.external_ids_of(&rtxn, &ids).unwrap(), vec![(0, S("a")), (2, S("c"))]);
+        assert_eq!(
+            index.external_ids_of_slice(&rtxn, &[2, 1, 0]).unwrap(),
+            vec![(2, S("c")), (1, S("12")), (0, S("a"))]
+        );
+        assert!(index.external_ids_of(&rtxn, &RoaringBitmap::new()).unwrap().is_empty());
+        assert!(matches!(
+            index.external_ids_of_slice(&rtxn, &[3]),
+            Err(crate::Error::UserError(crate::error::UserError::UnknownInternalDocumentId {
+                document_id: 3
+            }))
+        ));
+    }
+
+    #[test]
+    fn documents_page_resumes_across_deletions() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 0 }, { "id": 1 }, { "id": 2 }, { "id": 3 }, { "id": 4 },
+                { "id": 5 }, { "id": 6 }, { "id": 7 }, { "id": 8 }, { "id": 9 },
+            ]))
+            .unwrap();
+
+        let page_ids = |after: Option<u32>| {
+            let rtxn = index.read_txn().unwrap();
+            let page = index.documents_page(&rtxn, after, 3).unwrap();
+            let ids: Vec<_> = page.documents.iter().map(|(id, _)| *id).collect();
+            (ids, page.next)
+        };
+
+        let (mut exported, mut after) = page_ids(None);
+        assert_eq!(exported, vec![0, 1, 2]);
+        assert_eq!(after, Some(2));
+
+        // an exported document, the resume token itself and a document not exported yet
+        // are deleted between two pages
+        let delete = |external_ids: &[&str]| {
+            let mut wtxn = index.write_txn().unwrap();
+            let mut builder = DeleteDocuments::new(&mut wtxn, &index).unwrap();
+            for external_id in external_ids {
+                builder.delete_external_id(external_id);
+            }
+            builder.execute().unwrap();
+            wtxn.commit().unwrap();
+        };
+        delete(&["1", "2", "4"]);
+
+        while let Some(token) = after {
+            let (ids, next) = page_ids(Some(token));
+            exported.extend(ids);
+            after = next;
+            if token == 2 {
+                delete(&["8"]);
+            }
+        }
+
+        // every document that was never deleted is exported once,
+        // and only the deleted documents that were already exported are
+        assert_eq!(exported, vec![0, 1, 2, 3, 5, 6, 7, 9]);
+
+        let rtxn = index.read_txn().unwrap();
+        let remaining = index.documents_ids(&rtxn).unwrap();
+        assert!(remaining.iter().all(|id| exported.contains(&id)));
+
+        let page = index.documents_page(&rtxn, Some(9), 3).unwrap();
+        assert!(page.documents.is_empty());
+        assert_eq!(page.next, None);
+    }
+
+    #[test]
+    fn words_with_frequencies() {
+        let index = TempIndex::new();
+        index.update_settings(|settings| settings.set_searchable_fields(vec![S("text")])).unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 0, "text": "the quick brown fox" },
+                { "id": 1, "text": "the quick cat" },
+                { "id": 2, "text": "quiet brown dog" },
+                { "id": 3, "text": "a fox" },
+            ]))
+            .unwrap();
+
+        let words = |prefix: Option<&str>, limit: usize| {
+            let rtxn = index.read_txn().unwrap();
+            index.words_with_frequencies(&rtxn, prefix, limit).unwrap()
+        };
+        let owned = |words: &[(&str, u64)]| -> Vec<(String, u64)> {
+            words.iter().map(|(word, count)| (word.to_string(), *count)).collect()
+        };
+
+        assert_eq!(words(None, 3), owned(&[("a", 1), ("brown", 2), ("cat", 1)]));
+        assert_eq!(words(Some(""), 1), owned(&[("a", 1)]));
+        assert_eq!(words(Some("qu"), 10), owned(&[("quick", 2), ("quiet", 1)]));
+        assert_eq!(words(Some("qu"), 1), owned(&[("quick", 2)]));
+        assert_eq!(words(Some("fox"), 10), owned(&[("fox", 2)]));
+        assert_eq!(words(Some("zebra"), 10), owned(&[]));
+        assert_eq!(words(Some("qu"), 0), owned(&[]));
+
+        let rtxn = index.read_txn().unwrap();
+        let candidates: RoaringBitmap = [1, 2].iter().copied().collect();
+        let in_candidates =
+            index.words_with_frequencies_in_candidates(&rtxn, Some("qu"), &candidates, 10).unwrap();
+        assert_eq!(in_candidates, owned(&[("quick", 1), ("quiet", 1)]));
+        let candidates: RoaringBitmap = [3].iter().copied().collect();
+        let in_candidates =
+            index.words_with_frequencies_in_candidates(&rtxn, None, &candidates, 10).unwrap();
+        assert_eq!(in_candidates, owned(&[("a", 1), ("fox", 1)]));
+        drop(rtxn);
+
+        // The deleted documents are not counted anymore.
+        let mut wtxn = index.write_txn().unwrap();
+        let mut builder = DeleteDocuments::new(&mut wtxn, &index).unwrap();
+        builder.delete_external_id("0");
+        builder.execute().unwrap();
+        wtxn.commit().unwrap();
+
+        assert_eq!(words(Some("qu"), 10), owned(&[("quick", 1), ("quiet", 1)]));
+        assert_eq!(words(Some("b"), 10), owned(&[("brown", 1)]));
+        let rtxn = index.read_txn().unwrap();
+        let in_candidates = index
+            .words_with_frequencies_in_candidates(
+                &rtxn,
+                Some("f"),
+                &[0].iter().copied().collect(),
+                10,
+            )
+            .unwrap();
+        assert!(in_candidates.is_empty());
+    }
+
+    #[test]
+    fn facet_values_for_document() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|settings| {
+                settings
+                    .set_filterable_fields(maplit::hashset! { S("tags"), S("price"), S("size") })
+            })
+            .unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 0, "tags": ["Red ", "blue"], "price": [12.5, 3], "size": 9007199254740993_u64 },
+                { "id": 1, "tags": "green" },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let fields_ids_map = index.fields_ids_map(&rtxn).unwrap();
+        let values = |docid, field| {
+            let field_id = fields_ids_map.id(field).unwrap();
+            index.facet_values_for_document(&rtxn, docid, field_id).unwrap()
+        };
+
+        let tags = values(0, "tags");
+        assert!(tags.numbers.is_empty());
+        assert_eq!(tags.strings, vec![(S("blue"), S("blue")), (S("red"), S("Red "))]);
+        let price = values(0, "price");
+        assert_eq!(price.numbers, vec![3.0, 12.5]);
+        assert!(price.exact_integers.is_empty());
+        assert!(price.strings.is_empty());
+        let size = values(0, "size");
+        assert_eq!(size.numbers, vec![9007199254740992.0]);
+        assert_eq!(size.exact_integers, vec![9007199254740993]);
+
+        assert_eq!(values(1, "tags").strings, vec![(S("green"), S("green"))]);
+        assert_eq!(values(1, "price"), DocumentFacetValues::default());
+        assert_eq!(values(2, "tags"), DocumentFacetValues::default());
+    }
+
+    #[test]
+    fn external_documents_ids_tiers_are_stored_and_compacted() {
+        let index = TempIndex::new();
+        let mut model = std::collections::HashMap::new();
+
+        // Many small batches are stored as soft tiers on top of the hard map,
+        // the merged tiers are removed from the database.
+        for batch in 0..40u32 {
+            let documents =
+                crate::documents::documents_batch_reader_from_objects((0..5).map(|i| {
+                    let id = batch * 3 + i;
+                    serde_json::json!({ "id": id, "batch": batch }).as_object().unwrap().clone()
+                }));
+            index.add_documents(documents).unwrap();
+
+            let rtxn = index.read_txn().unwrap();
+            let external_documents_ids = index.external_documents_ids(&rtxn).unwrap();
+            for i in 0..5 {
+                let id = (batch * 3 + i).to_string();
+                model.insert(id.clone(), external_documents_ids.get(&id).unwrap());
+            }
+            assert_eq!(external_documents_ids.to_hash_map(), model);
+        }
+
+        let rtxn = index.read_txn().unwrap();
+        let external_documents_ids = index.external_documents_ids(&rtxn).unwrap();
+        let stale_tier = super::soft_external_documents_ids_key(external_documents_ids.soft.len());
+        assert!(index
+            .main
+            .get::<_, Str, heed::types::ByteSlice>(&rtxn, &stale_tier)
+            .unwrap()
+            .is_none());
+        drop(rtxn);
+
+        // The hard deletions mark the ids as deleted and then merge them into the hard map.
+        let mut wtxn = index.write_txn().unwrap();
+        let mut builder = DeleteDocuments::new(&mut wtxn, &index).unwrap();
+        for id in (0..60).step_by(2) {
+            let id = id.to_string();
+            builder.delete_external_id(&id);
+            model.remove(&id);
+        }
+        builder.purge().unwrap();
+        wtxn.commit().unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let external_documents_ids = index.external_documents_ids(&rtxn).unwrap();
+        assert_eq!(external_documents_ids.to_hash_map(), model);
+        for id in (0..130).map(|id| id.to_string()) {
+            assert_eq!(external_documents_ids.get(&id), model.get(&id).copied());
+        }
+    }
+
+    #[test]
+    fn update_id_and_dates_move_with_every_update() {
+        use crate::update::ClearDocuments;
+
+        let index = TempIndex::new();
+        let rtxn = index.read_txn().unwrap();
+        let created_at = index.created_at(&rtxn).unwrap();
+        assert_eq!(index.updated_at(&rtxn).unwrap(), created_at);
+        assert_eq!(index.update_id(&rtxn).unwrap(), 0);
+        drop(rtxn);
+
+        let state = || {
+            let rtxn = index.read_txn().unwrap();
+            assert_eq!(index.created_at(&rtxn).unwrap(), created_at);
+            (index.update_id(&rtxn).unwrap(), index.updated_at(&rtxn).unwrap())
+        };
+        let (mut last_id, mut last_updated_at) = state();
+        let mut assert_moved = || {
+            let (id, updated_at) = state();
+            assert!(id > last_id);
+            assert!(updated_at >= last_updated_at);
+            last_id = id;
+            last_updated_at = updated_at;
+        };
+
+        index.add_documents(documents!([{ "id": 0, "name": "kevin" }])).unwrap();
+        assert_moved();
+
+        index.update_settings(|s| s.set_filterable_fields(maplit::hashset! { S("name") })).unwrap();
+        assert_moved();
+
+        let mut wtxn = index.write_txn().unwrap();
+        let mut builder = DeleteDocuments::new(&mut wtxn, &index).unwrap();
+        builder.delete_external_id("0");
+        builder.execute().unwrap();
+        wtxn.commit().unwrap();
+        assert_moved();
+
+        let mut wtxn = index.write_txn().unwrap();
+        ClearDocuments::new(&mut wtxn, &index).execute().unwrap();
+        wtxn.commit().unwrap();
+        assert_moved();
+
+        // An aborted update leaves them untouched.
+        let mut wtxn = index.write_txn().unwrap();
+        ClearDocuments::new(&mut wtxn, &index).execute().unwrap();
+        wtxn.abort().unwrap();
+        assert_eq!(state(), (last_id, last_updated_at));
+    }
 }
@@ -2,13 +2,20 @@
 pub mod documents;
 
 mod asc_desc;
+mod collation;
 mod criterion;
+mod diacritics;
+mod dictionary;
+mod documents_compression;
+pub mod dump;
 mod error;
 mod external_documents_ids;
 pub mod facet;
+mod field_types;
 mod fields_ids_map;
 pub mod heed_codec;
 pub mod index;
+pub mod position_bucketing;
 pub mod proximity;
 mod search;
 pub mod update;
@@ -28,21 +35,30 @@ use serde_json::Value;
 pub use {charabia as tokenizer, heed};
 
 pub use self::asc_desc::{AscDesc, AscDescError, Member, SortError};
+pub use self::collation::{CollationKey, Locale};
 pub use self::criterion::{default_criteria, Criterion, CriterionError};
+pub(crate) use self::diacritics::restore_diacritics;
+pub use self::dictionary::Dictionary;
+pub use self::documents_compression::{DocumentsDecompressor, StoredDocument};
 pub use self::error::{
-    Error, FieldIdMapMissingEntry, InternalError, SerializationError, UserError,
+    Error, ErrorCode, FieldIdMapMissingEntry, InternalError, SerializationError, UserError,
 };
 pub use self::external_documents_ids::ExternalDocumentsIds;
+pub use self::field_types::{FieldDistributionEntry, FieldTypes};
 pub use self::fields_ids_map::FieldsIdsMap;
 pub use self::heed_codec::{
     BEU32StrCodec, BoRoaringBitmapCodec, BoRoaringBitmapLenCodec, CboRoaringBitmapCodec,
-    CboRoaringBitmapLenCodec, FieldIdWordCountCodec, ObkvCodec, RoaringBitmapCodec,
-    RoaringBitmapLenCodec, StrBEU32Codec, U8StrStrCodec, UncheckedU8StrStrCodec,
+    CboRoaringBitmapLenCodec, FieldIdWordCountCodec, ObkvCodec, OffsetDateTimeCodec,
+    RoaringBitmapCodec, RoaringBitmapLenCodec, StrBEU32Codec, U8StrStrCodec,
+    UncheckedU8StrStrCodec,
 };
-pub use self::index::Index;
+pub use self::index::{DatabaseStats, DocumentFacetValues, DocumentsPage, Index, IndexStats};
 pub use self::search::{
-    FacetDistribution, Filter, FormatOptions, MatchBounds, MatcherBuilder, MatchingWord,
-    MatchingWords, Search, SearchResult, TermsMatchingStrategy, DEFAULT_VALUES_PER_FACET,
+    AnalyzedTerm, AnalyzedToken, DocumentsGroup, FacetDistribution, Filter, FormatOptions,
+    MatchBounds, MatcherBuilder, MatchingWord, MatchingWords, MultiSearch, OrderBy,
+    PairExplanation, QueryAnalysis, RankingRuleScore, RuleName, ScoreDetail, ScoreDetails, Search,
+    SearchLimits, SearchQuery, SearchResult, SearchableContext, SortValue, TermsMatchingStrategy,
+    DEFAULT_VALUES_PER_FACET,
 };
 
 pub type Result<T> = std::result::Result<T, error::Error>;
@@ -55,6 +71,7 @@ pub type DocumentId = u32;
 pub type FastMap4<K, V> = HashMap<K, V, BuildHasherDefault<FxHasher32>>;
 pub type FastMap8<K, V> = HashMap<K, V, BuildHasherDefault<FxHasher64>>;
 pub type FieldDistribution = BTreeMap<String, u64>;
+pub type FieldDistributionWithTypes = BTreeMap<String, FieldDistributionEntry>;
 pub type FieldId = u16;
 pub type Object = serde_json::Map<String, serde_json::Value>;
 pub type Position = u32;
@@ -199,6 +216,9 @@ pub fn is_faceted(field: &str, faceted_fields: impl IntoIterator<Item = impl AsR
 }
 
 /// Returns `true` if the field match the facet.
+///
+/// The facet can be a pattern where a `*` segment matches any segment of the field,
+/// a `*` that is only a part of a segment is matched as is.
 /// ```
 /// use milli::is_faceted_by;
 /// // -- the valid basics
@@ -223,10 +243,57 @@ pub fn is_faceted(field: &str, faceted_fields: impl IntoIterator<Item = impl AsR
 /// assert!(!is_faceted_by("animaux.chien", "animaux.ch"));
 /// assert!(!is_faceted_by("animaux.chien", "animaux.chi"));
 /// assert!(!is_faceted_by("animaux.chien", "animaux.chie"));
+///
+/// // -- the patterns
+/// assert!(is_faceted_by("translations.en.title", "translations.*.title"));
+/// assert!(is_faceted_by("translations.fr.title.short", "translations.*.title"));
+/// assert!(is_faceted_by("translations.fr.title", "translations.*"));
+/// assert!(is_faceted_by("translations.fr.title", "*.fr"));
+/// assert!(!is_faceted_by("translations.en.body", "translations.*.title"));
+/// assert!(!is_faceted_by("translations.en", "translations.*.title"));
+/// assert!(!is_faceted_by("translations.en.title", "translations.e*.title"));
 /// ```
 pub fn is_faceted_by(field: &str, facet: &str) -> bool {
-    field.starts_with(facet)
-        && field[facet.len()..].chars().next().map(|c| c == '.').unwrap_or(true)
+    if is_field_pattern(facet) {
+        let mut field_segments = field.split('.');
+        facet.split('.').all(|pattern_segment| match field_segments.next() {
+            Some(segment) => pattern_segment == "*" || pattern_segment == segment,
+            None => false,
+        })
+    } else {
+        field.starts_with(facet)
+            && field[facet.len()..].chars().next().map(|c| c == '.').unwrap_or(true)
+    }
+}
+
+/// Returns `true` if the name is a pattern, a name with at least one `*` segment.
+/// ```
+/// use milli::is_field_pattern;
+/// assert!(is_field_pattern("translations.*.title"));
+/// assert!(is_field_pattern("*"));
+/// assert!(!is_field_pattern("translations.e*.title"));
+/// assert!(!is_field_pattern("translations"));
+/// ```
+pub fn is_field_pattern(name: &str) -> bool {
+    name.split('.').any(|segment| segment == "*")
+}
+
+/// Returns `true` if the field is exactly the given name or, when the name is a pattern,
+/// if every segment of the field is matched by the pattern, the nested fields are not matched.
+/// ```
+/// use milli::is_field_matched_by;
+/// assert!(is_field_matched_by("translations.en.title", "translations.en.title"));
+/// assert!(is_field_matched_by("translations.en.title", "translations.*.title"));
+/// assert!(!is_field_matched_by("translations.en.title.short", "translations.*.title"));
+/// assert!(!is_field_matched_by("translations.en", "translations.*.title"));
+/// assert!(!is_field_matched_by("translations.en.title", "translations"));
+/// ```
+pub fn is_field_matched_by(field: &str, name: &str) -> bool {
+    if is_field_pattern(name) {
+        field.split('.').count() == name.split('.').count() && is_faceted_by(field, name)
+    } else {
+        field == name
+    }
 }
 
 #[cfg(test)]
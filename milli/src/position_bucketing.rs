@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    absolute_from_relative_position, relative_from_absolute_position, Position, RelativePosition,
+};
+
+/// How precisely the positions of the words in their attribute are stored
+/// in the word position and field id word count databases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PositionBucketing {
+    /// Every position is stored as is.
+    Exact,
+    /// The first `exact` positions of an attribute are stored as is, the next ones are grouped
+    /// in buckets of 1, 2, 4, 8... positions and stored as the first position of their bucket.
+    ///
+    /// The attributes with more than `exact` words are not stored in the
+    /// field id word count database, their number of words is not exact.
+    Logarithmic { exact: RelativePosition },
+}
+
+impl Default for PositionBucketing {
+    fn default() -> Self {
+        PositionBucketing::Exact
+    }
+}
+
+impl PositionBucketing {
+    /// Returns the position the given position of an attribute is stored at.
+    pub fn bucket(&self, relative: RelativePosition) -> RelativePosition {
+        match *self {
+            PositionBucketing::Logarithmic { exact } if relative >= exact => {
+                // The bucket of the n-th position after the exact ones starts at 2^log2(n) - 1.
+                let n = (relative - exact) as u32 + 1;
+                let bucket_start = (1u32 << (31 - n.leading_zeros())) - 1;
+                exact + bucket_start as RelativePosition
+            }
+            _ => relative,
+        }
+    }
+
+    /// Returns the absolute position the given absolute position is stored at.
+    pub fn bucket_absolute(&self, position: Position) -> Position {
+        let (field_id, relative) = relative_from_absolute_position(position);
+        absolute_from_relative_position(field_id, self.bucket(relative))
+    }
+
+    /// Whether the given position of an attribute is stored as is and alone.
+    pub fn is_exact(&self, relative: RelativePosition) -> bool {
+        match *self {
+            PositionBucketing::Exact => true,
+            PositionBucketing::Logarithmic { exact } => relative < exact,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logarithmic_buckets() {
+        let bucketing = PositionBucketing::Logarithmic { exact: 4 };
+        let buckets: Vec<_> = (0..20).map(|position| bucketing.bucket(position)).collect();
+        assert_eq!(
+            buckets,
+            vec![0, 1, 2, 3, 4, 5, 5, 7, 7, 7, 7, 11, 11, 11, 11, 11, 11, 11, 11, 19]
+        );
+        assert!(bucketing.is_exact(3));
+        assert!(!bucketing.is_exact(4));
+
+        let last = RelativePosition::MAX;
+        assert!(bucketing.bucket(last) <= last);
+        assert_eq!(bucketing.bucket_absolute(absolute_from_relative_position(2, 6)), (2 << 16) | 5);
+
+        let exact = PositionBucketing::Exact;
+        assert!((0..100).all(|position| exact.bucket(position) == position));
+        assert!(exact.is_exact(last));
+    }
+}
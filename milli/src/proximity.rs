@@ -1,9 +1,31 @@
 use std::cmp;
 
-use crate::{relative_from_absolute_position, Position};
+use heed::RoTxn;
+use roaring::RoaringBitmap;
+use serde::{Deserialize, Serialize};
+
+use crate::position_bucketing::PositionBucketing;
+use crate::{absolute_from_relative_position, relative_from_absolute_position, Index, Position};
 
 pub const MAX_DISTANCE: u32 = 8;
 
+/// How precisely the proximity criterion measures the distance between the query words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProximityPrecision {
+    /// The number of words between the query words, read from the word pair proximity database.
+    ByWord,
+    /// Only whether the query words appear in the same attribute,
+    /// the word pair proximity databases are not built.
+    ByAttribute,
+}
+
+impl Default for ProximityPrecision {
+    fn default() -> Self {
+        ProximityPrecision::ByWord
+    }
+}
+
 pub fn index_proximity(lhs: u32, rhs: u32) -> u32 {
     if lhs <= rhs {
         cmp::min(rhs - lhs, MAX_DISTANCE)
@@ -25,3 +47,83 @@ pub fn positions_proximity(lhs: Position, rhs: Position) -> u32 {
 pub fn path_proximity(path: &[Position]) -> u32 {
     path.windows(2).map(|w| positions_proximity(w[0], w[1])).sum::<u32>()
 }
+
+/// Returns the documents in which the right word appears `proximity` words after the left word,
+/// and not closer.
+///
+/// It is computed from the word position docids, to replace the word pair proximity
+/// database when it isn't built because of the [`ProximityPrecision::ByAttribute`] precision.
+/// The distances between the positions grouped in buckets by the position bucketing are
+/// approximated by the distances between their buckets.
+pub(crate) fn word_pair_proximity_docids_from_positions(
+    index: &Index,
+    rtxn: &RoTxn,
+    bucketing: PositionBucketing,
+    left: &str,
+    right: &str,
+    proximity: u8,
+) -> heed::Result<RoaringBitmap> {
+    let mut docids_by_proximity = vec![RoaringBitmap::new(); proximity as usize];
+    let range = (left, Position::MIN)..=(left, Position::MAX);
+    for result in index.word_position_docids.range(rtxn, &range)? {
+        let ((_, position), left_docids) = result?;
+        let (field_id, relative) = relative_from_absolute_position(position);
+        for (distance, docids) in (1..).zip(docids_by_proximity.iter_mut()) {
+            let relative = match relative.checked_add(distance) {
+                Some(relative) => relative,
+                None => break,
+            };
+            let key =
+                (right, absolute_from_relative_position(field_id, bucketing.bucket(relative)));
+            if let Some(right_docids) = index.word_position_docids.get(rtxn, &key)? {
+                *docids |= right_docids & &left_docids;
+            }
+        }
+    }
+
+    let mut docids = docids_by_proximity.pop().unwrap_or_default();
+    for closer_docids in docids_by_proximity {
+        docids -= closer_docids;
+    }
+    Ok(docids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::tests::TempIndex;
+
+    #[test]
+    fn word_pair_proximity_docids_from_positions_match_the_database() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 0, "text": "the quick brown fox" },
+                { "id": 1, "text": "fox brown quick the" },
+                { "id": 2, "text": "quick fox", "other": "brown" },
+                { "id": 3, "text": "the brown quick fox jumps over the quick dog" },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let words = ["the", "quick", "brown", "fox", "dog"];
+        for proximity in 1..=3 {
+            for left in words {
+                for right in words {
+                    let key = (proximity, left, right);
+                    let expected = index.word_pair_proximity_docids.get(&rtxn, &key).unwrap();
+                    let docids = word_pair_proximity_docids_from_positions(
+                        &index,
+                        &rtxn,
+                        PositionBucketing::default(),
+                        left,
+                        right,
+                        proximity,
+                    )
+                    .unwrap();
+                    assert_eq!(docids, expected.unwrap_or_default(), "{:?}", key);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use charabia::{Language, Script, Token, TokenKind};
+
+use super::query_tree::{typos, PrimitiveQueryPart, TypoConfig};
+use super::{word_derivations, WordDerivationsCache};
+use crate::{Index, Result};
+
+/// How a query is tokenized, normalized and derived into the words searched in the index.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct QueryAnalysis {
+    /// The word tokens of the query, separators are omitted.
+    pub tokens: Vec<AnalyzedToken>,
+    /// The query terms, stop words that aren't at the end of the query are omitted.
+    pub terms: Vec<AnalyzedTerm>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalyzedToken {
+    /// The token as written in the query.
+    pub original: String,
+    /// The token as searched in the index.
+    pub normalized: String,
+    /// The position of the first char of the token in the query.
+    pub char_start: usize,
+    /// The position of the char following the token in the query.
+    pub char_end: usize,
+    pub script: Script,
+    pub language: Option<Language>,
+    pub is_stop_word: bool,
+}
+
+impl AnalyzedToken {
+    pub(crate) fn new(query: &str, token: &Token) -> Self {
+        AnalyzedToken {
+            original: query[token.byte_start..token.byte_end].to_string(),
+            normalized: token.lemma().to_string(),
+            char_start: token.char_start,
+            char_end: token.char_end,
+            script: token.script,
+            language: token.language,
+            is_stop_word: token.kind == TokenKind::StopWord,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnalyzedTerm {
+    /// The normalized word of the query.
+    pub word: String,
+    pub is_prefix: bool,
+    /// Whether the word is part of a phrase, the words of a phrase are never derived.
+    pub in_phrase: bool,
+    /// The maximum number of typos allowed on this word.
+    pub max_typos: u8,
+    /// The words of the index this term matches along with their typo distance.
+    pub derivations: Vec<(String, u8)>,
+}
+
+/// Computes the terms of the primitive query and the words of the index they are derived into.
+pub(crate) fn analyze_terms(
+    index: &Index,
+    rtxn: &heed::RoTxn,
+    authorize_typos: bool,
+    primitive_query: &[PrimitiveQueryPart],
+) -> Result<Vec<AnalyzedTerm>> {
+    let words_fst = index.words_fst(rtxn)?;
+    let exact_words = index.exact_words(rtxn)?;
+    let word_len_one_typo = index.min_word_len_one_typo(rtxn)?;
+    let word_len_two_typo = index.min_word_len_two_typos(rtxn)?;
+    let mut wdcache = WordDerivationsCache::new();
+
+    let mut terms = Vec::new();
+    for part in primitive_query {
+        match part {
+            PrimitiveQueryPart::Word(word, is_prefix) => {
+                let config = TypoConfig {
+                    max_typos: 2,
+                    word_len_one_typo,
+                    word_len_two_typo,
+                    exact_words: exact_words.as_ref(),
+                };
+                let max_typos = typos(word.clone(), authorize_typos, config).typo();
+                let derivations =
+                    word_derivations(word, *is_prefix, max_typos, &words_fst, &mut wdcache)?;
+                terms.push(AnalyzedTerm {
+                    word: word.clone(),
+                    is_prefix: *is_prefix,
+                    in_phrase: false,
+                    max_typos,
+                    derivations: derivations.to_vec(),
+                });
+            }
+            PrimitiveQueryPart::Phrase(words) => {
+                for word in words {
+                    let derivations = word_derivations(word, false, 0, &words_fst, &mut wdcache)?;
+                    terms.push(AnalyzedTerm {
+                        word: word.clone(),
+                        is_prefix: false,
+                        in_phrase: true,
+                        max_typos: 0,
+                        derivations: derivations.to_vec(),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(terms)
+}
@@ -0,0 +1,145 @@
+use std::convert::TryInto;
+use std::mem::size_of;
+
+use roaring::RoaringBitmap;
+
+use super::criteria::BucketPosition;
+use crate::{DocumentId, SortValue};
+
+/// The state of a search after a page of documents, to resume the search from there.
+///
+/// The position is the bucket of every ranking rule the last document of the page, returned
+/// or skipped by the offset, was found in. The criteria skip the buckets ranked before it
+/// without splitting them and the search resumes after this document in its bucket.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchContinuation {
+    /// The update id of the index when the continuation was created,
+    /// the buckets are only the same on this version of the index.
+    pub update_id: u64,
+    /// The hash of the parameters of the search that ranked the documents.
+    pub parameters_hash: u64,
+    pub position: Vec<BucketPosition>,
+    pub last_docid: DocumentId,
+    /// The documents of the previous pages, only kept when there is a distinct attribute
+    /// to exclude the documents sharing their distinct value from the next pages.
+    pub returned: RoaringBitmap,
+}
+
+const NO_SORT_VALUE: u8 = 0;
+const NUMBER_SORT_VALUE: u8 = 1;
+const STRING_SORT_VALUE: u8 = 2;
+
+impl SearchContinuation {
+    /// Encodes the continuation as the update id, the parameters hash, the last document
+    /// id, the number of buckets followed by the buckets and the returned documents bitmap.
+    ///
+    /// A bucket is encoded as its number followed by a tag and the sort value of the tag,
+    /// a string being prefixed by its length.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            size_of::<u64>() * 2
+                + size_of::<u32>() * 2
+                + self.position.len() * (size_of::<u32>() + 1 + size_of::<f64>())
+                + self.returned.serialized_size(),
+        );
+        bytes.extend_from_slice(&self.update_id.to_be_bytes());
+        bytes.extend_from_slice(&self.parameters_hash.to_be_bytes());
+        bytes.extend_from_slice(&self.last_docid.to_be_bytes());
+        bytes.extend_from_slice(&(self.position.len() as u32).to_be_bytes());
+        for BucketPosition { bucket, sort_value } in &self.position {
+            bytes.extend_from_slice(&bucket.to_be_bytes());
+            match sort_value {
+                None => bytes.push(NO_SORT_VALUE),
+                Some(SortValue::Number(number)) => {
+                    bytes.push(NUMBER_SORT_VALUE);
+                    bytes.extend_from_slice(&number.to_be_bytes());
+                }
+                Some(SortValue::String(string)) => {
+                    bytes.push(STRING_SORT_VALUE);
+                    bytes.extend_from_slice(&(string.len() as u32).to_be_bytes());
+                    bytes.extend_from_slice(string.as_bytes());
+                }
+            }
+        }
+        self.returned.serialize_into(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// Decodes a continuation, returns `None` if the bytes are not a valid continuation.
+    pub fn decode(bytes: &[u8]) -> Option<SearchContinuation> {
+        let (update_id, bytes) = split_u64(bytes)?;
+        let (parameters_hash, bytes) = split_u64(bytes)?;
+        let (last_docid, bytes) = split_u32(bytes)?;
+        let (position_len, mut bytes) = split_u32(bytes)?;
+
+        let mut position = Vec::new();
+        for _ in 0..position_len {
+            let (bucket, rest) = split_u32(bytes)?;
+            let (tag, rest) = split_at(rest, 1)?;
+            let (sort_value, rest) = match tag[0] {
+                NO_SORT_VALUE => (None, rest),
+                NUMBER_SORT_VALUE => {
+                    let (number, rest) = split_u64(rest)?;
+                    (Some(SortValue::Number(f64::from_bits(number))), rest)
+                }
+                STRING_SORT_VALUE => {
+                    let (len, rest) = split_u32(rest)?;
+                    let (string, rest) = split_at(rest, len as usize)?;
+                    let string = std::str::from_utf8(string).ok()?;
+                    (Some(SortValue::String(string.to_string())), rest)
+                }
+                _ => return None,
+            };
+            position.push(BucketPosition { bucket, sort_value });
+            bytes = rest;
+        }
+
+        Some(SearchContinuation {
+            update_id,
+            parameters_hash,
+            position,
+            last_docid,
+            returned: RoaringBitmap::deserialize_from(bytes).ok()?,
+        })
+    }
+}
+
+fn split_at(bytes: &[u8], mid: usize) -> Option<(&[u8], &[u8])> {
+    (bytes.len() >= mid).then(|| bytes.split_at(mid))
+}
+
+fn split_u32(bytes: &[u8]) -> Option<(u32, &[u8])> {
+    let (number, bytes) = split_at(bytes, size_of::<u32>())?;
+    Some((u32::from_be_bytes(number.try_into().ok()?), bytes))
+}
+
+fn split_u64(bytes: &[u8]) -> Option<(u64, &[u8])> {
+    let (number, bytes) = split_at(bytes, size_of::<u64>())?;
+    Some((u64::from_be_bytes(number.try_into().ok()?), bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode() {
+        let continuation = SearchContinuation {
+            update_id: 42,
+            parameters_hash: 0xdead_beef,
+            position: vec![
+                BucketPosition { bucket: 2, sort_value: None },
+                BucketPosition { bucket: 0, sort_value: Some(SortValue::Number(-1.5)) },
+                BucketPosition { bucket: 7, sort_value: Some(SortValue::String("été".into())) },
+            ],
+            last_docid: 12,
+            returned: (0..100).collect(),
+        };
+        let bytes = continuation.encode();
+        assert_eq!(SearchContinuation::decode(&bytes), Some(continuation));
+
+        assert_eq!(SearchContinuation::decode(&bytes[..bytes.len() - 1]), None);
+        assert_eq!(SearchContinuation::decode(&bytes[..10]), None);
+        assert_eq!(SearchContinuation::decode(b"hello world"), None);
+    }
+}
@@ -0,0 +1,71 @@
+use std::cell::RefCell;
+
+use roaring::RoaringBitmap;
+
+/// The maximum number of recycled bitmaps kept by an arena, the other ones are dropped.
+const MAX_RECYCLED_BITMAPS: usize = 64;
+
+/// A pool of bitmaps the criteria of a search reuse from one bucket to the next
+/// instead of allocating new bitmaps and dropping the ones they are done with.
+///
+/// A disabled arena never keeps the recycled bitmaps and always returns new ones.
+pub struct SearchArena {
+    enabled: bool,
+    bitmaps: RefCell<Vec<RoaringBitmap>>,
+}
+
+impl SearchArena {
+    pub fn new(enabled: bool) -> SearchArena {
+        SearchArena { enabled, bitmaps: RefCell::default() }
+    }
+
+    /// Returns an empty bitmap, a recycled one if there is any.
+    pub fn take(&self) -> RoaringBitmap {
+        self.bitmaps.borrow_mut().pop().unwrap_or_default()
+    }
+
+    /// Returns a copy of the given bitmap, stored in a recycled bitmap if there is any.
+    pub fn copy(&self, bitmap: &RoaringBitmap) -> RoaringBitmap {
+        let mut copy = self.take();
+        copy |= bitmap;
+        copy
+    }
+
+    /// Gives back a bitmap that is not used anymore, it will be returned by the next calls to `take`.
+    pub fn recycle(&self, mut bitmap: RoaringBitmap) {
+        if self.enabled {
+            let mut bitmaps = self.bitmaps.borrow_mut();
+            if bitmaps.len() < MAX_RECYCLED_BITMAPS {
+                bitmap.clear();
+                bitmaps.push(bitmap);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recycled_bitmaps_are_returned_empty() {
+        let arena = SearchArena::new(true);
+        arena.recycle((0..1000).collect());
+        assert_eq!(arena.bitmaps.borrow().len(), 1);
+
+        assert!(arena.take().is_empty());
+        assert!(arena.bitmaps.borrow().is_empty());
+
+        let bitmap: RoaringBitmap = (10..20).collect();
+        arena.recycle((0..1000).collect());
+        assert_eq!(arena.copy(&bitmap), bitmap);
+    }
+
+    #[test]
+    fn disabled_arena_drops_the_recycled_bitmaps() {
+        let arena = SearchArena::new(false);
+        arena.recycle((0..1000).collect());
+        assert!(arena.bitmaps.borrow().is_empty());
+        assert!(arena.take().is_empty());
+    }
+}
@@ -1,54 +1,108 @@
+use std::collections::BTreeMap;
 use std::mem::take;
+use std::ops::Bound::{Excluded, Included};
 
+use heed::types::{ByteSlice, DecodeIgnore};
+use heed::Database;
 use itertools::Itertools;
 use log::debug;
 use ordered_float::OrderedFloat;
 use roaring::RoaringBitmap;
 
-use super::{Criterion, CriterionParameters, CriterionResult};
-use crate::search::criteria::{resolve_query_tree, CriteriaBuilder};
-use crate::search::facet::{FacetNumberIter, FacetStringIter};
+use super::{
+    BucketPosition, BucketScores, Criterion, CriterionParameters, CriterionResult,
+    RankingRuleScore, ScoreDetails, SortValue,
+};
+use crate::collation::primary_letter;
+use crate::heed_codec::facet::{FacetStringLevelZeroCodec, FacetStringLevelZeroValueCodec};
+use crate::search::criteria::{resolve_query_tree, Context, TimeBudget};
+use crate::search::facet::{Candidates, FacetNumberIter, FacetStringIter};
 use crate::search::query_tree::Operation;
-use crate::{FieldId, Index, Result};
+use crate::{CollationKey, FieldId, Index, Locale, Result};
 
 /// Threshold on the number of candidates that will make
 /// the system to choose between one algorithm or another.
 const CANDIDATES_THRESHOLD: u64 = 1000;
 
+/// The number of documents whose facet values are fetched between two checks of the time budget.
+const TIME_BUDGET_CHECK_INTERVAL: usize = 128;
+
 pub struct AscDesc<'t> {
+    ctx: &'t dyn Context<'t>,
     index: &'t Index,
     rtxn: &'t heed::RoTxn<'t>,
     field_name: String,
     field_id: Option<FieldId>,
     is_ascending: bool,
+    locale: Option<Locale>,
     query_tree: Option<Operation>,
-    candidates: Box<dyn Iterator<Item = heed::Result<RoaringBitmap>> + 't>,
+    candidates: Box<dyn Iterator<Item = heed::Result<(SortValue, RoaringBitmap)>> + 't>,
     allowed_candidates: RoaringBitmap,
     bucket_candidates: RoaringBitmap,
     faceted_candidates: RoaringBitmap,
+    scores: BucketScores,
+    /// The bucket of the last document of the previous page, when the search is continued
+    /// from the current bucket of the parent, until the groups before it are skipped.
+    resume_from: Option<BucketPosition>,
+    /// The candidates of the groups sorted before the bound of the previous page.
+    skipped_candidates: RoaringBitmap,
+    /// The group of the bound, returned after the skipped groups.
+    pending: Option<(SortValue, RoaringBitmap)>,
     parent: Box<dyn Criterion + 't>,
 }
 
 impl<'t> AscDesc<'t> {
     pub fn asc(
+        ctx: &'t dyn Context<'t>,
         index: &'t Index,
         rtxn: &'t heed::RoTxn,
         parent: Box<dyn Criterion + 't>,
         field_name: String,
     ) -> Result<Self> {
-        Self::new(index, rtxn, parent, field_name, true)
+        Self::new(ctx, index, rtxn, parent, field_name, true)
     }
 
     pub fn desc(
+        ctx: &'t dyn Context<'t>,
         index: &'t Index,
         rtxn: &'t heed::RoTxn,
         parent: Box<dyn Criterion + 't>,
         field_name: String,
     ) -> Result<Self> {
-        Self::new(index, rtxn, parent, field_name, false)
+        Self::new(ctx, index, rtxn, parent, field_name, false)
+    }
+
+    /// Orders the string facet values by the collation of the given locale instead of their bytes.
+    pub fn with_locale(mut self, locale: Option<Locale>) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Returns the score details of the next bucket, sorted by the given facet value.
+    fn sort_score(&mut self, value: Option<SortValue>) -> ScoreDetails {
+        self.scores.next(RankingRuleScore::Sort {
+            field_name: self.field_name.clone(),
+            ascending: self.is_ascending,
+            value,
+        })
+    }
+
+    /// Returns the groups sorted before the bound the search is continued from as a single
+    /// bucket, the following buckets are then numbered as they were on the previous pages.
+    fn skipped_bucket(&mut self, position: BucketPosition) -> CriterionResult {
+        let score_details = self.sort_score(None);
+        self.scores.skip_to(position.bucket);
+        CriterionResult {
+            query_tree: self.query_tree.clone(),
+            candidates: Some(take(&mut self.skipped_candidates)),
+            filtered_candidates: None,
+            bucket_candidates: Some(take(&mut self.bucket_candidates)),
+            score_details,
+        }
     }
 
     fn new(
+        ctx: &'t dyn Context<'t>,
         index: &'t Index,
         rtxn: &'t heed::RoTxn,
         parent: Box<dyn Criterion + 't>,
@@ -67,16 +121,22 @@ impl<'t> AscDesc<'t> {
         };
 
         Ok(AscDesc {
+            ctx,
             index,
             rtxn,
             field_name,
             field_id,
             is_ascending,
+            locale: None,
             query_tree: None,
             candidates: Box::new(std::iter::empty()),
             allowed_candidates: RoaringBitmap::new(),
             faceted_candidates,
             bucket_candidates: RoaringBitmap::new(),
+            scores: BucketScores::default(),
+            resume_from: None,
+            skipped_candidates: RoaringBitmap::new(),
+            pending: None,
             parent,
         })
     }
@@ -89,35 +149,58 @@ impl<'t> Criterion for AscDesc<'t> {
         self.allowed_candidates -= params.excluded_candidates;
 
         loop {
+            // The facet values of the candidates may have been partially fetched.
+            if self.ctx.time_budget().exceeded() {
+                return Ok(None);
+            }
+
             debug!(
                 "Facet {}({}) iteration",
                 if self.is_ascending { "Asc" } else { "Desc" },
                 self.field_name
             );
 
-            match self.candidates.next().transpose()? {
+            let group = match self.pending.take() {
+                Some(group) => Some(group),
+                None => self.candidates.next().transpose()?,
+            };
+
+            match group {
+                // The bound of the previous page is the bucket of the documents without value.
+                None if self.resume_from.is_some() => {
+                    let position = self.resume_from.take().unwrap();
+                    if !self.skipped_candidates.is_empty() {
+                        return Ok(Some(self.skipped_bucket(position)));
+                    }
+                    self.scores.skip_to(position.bucket);
+                }
                 None if !self.allowed_candidates.is_empty() => {
                     return Ok(Some(CriterionResult {
                         query_tree: self.query_tree.clone(),
                         candidates: Some(take(&mut self.allowed_candidates)),
                         filtered_candidates: None,
                         bucket_candidates: Some(take(&mut self.bucket_candidates)),
+                        score_details: self.sort_score(None),
                     }));
                 }
                 None => match self.parent.next(params)? {
+                    // The documents of the bucket were all returned by the previous pages.
+                    Some(result) if params.is_skipped(&result.score_details) => {
+                        return Ok(Some(result));
+                    }
                     Some(CriterionResult {
                         query_tree,
                         candidates,
                         filtered_candidates,
                         bucket_candidates,
+                        score_details,
                     }) => {
                         self.query_tree = query_tree;
+                        self.resume_from = params.resumed_bucket(&score_details).cloned();
+                        self.scores.reset(score_details);
                         let mut candidates = match (&self.query_tree, candidates) {
                             (_, Some(candidates)) => candidates,
-                            (Some(qt), None) => {
-                                let context = CriteriaBuilder::new(&self.rtxn, &self.index)?;
-                                resolve_query_tree(&context, qt, params.wdcache)?
-                            }
+                            (Some(qt), None) => resolve_query_tree(self.ctx, qt, params.wdcache)?,
                             (None, None) => self.index.documents_ids(self.rtxn)?,
                         };
 
@@ -135,27 +218,49 @@ impl<'t> Criterion for AscDesc<'t> {
                         }
 
                         self.allowed_candidates = &candidates - params.excluded_candidates;
+                        let all_documents =
+                            candidates.len() == self.index.number_of_documents(self.rtxn)?;
                         self.candidates = match self.field_id {
                             Some(field_id) => facet_ordered(
                                 self.index,
                                 self.rtxn,
                                 field_id,
                                 self.is_ascending,
+                                self.locale,
                                 candidates & &self.faceted_candidates,
+                                all_documents,
+                                self.ctx.time_budget(),
                             )?,
                             None => Box::new(std::iter::empty()),
                         };
                     }
                     None => return Ok(None),
                 },
-                Some(mut candidates) => {
+                Some((value, mut candidates)) => {
                     candidates -= params.excluded_candidates;
                     self.allowed_candidates -= &candidates;
+
+                    // When the search is continued, the groups sorted before the facet value
+                    // of the last document of the previous page are only skipped.
+                    if let Some(position) = &self.resume_from {
+                        if position.sort_value.as_ref() != Some(&value) {
+                            self.skipped_candidates |= candidates;
+                            continue;
+                        }
+                        let position = self.resume_from.take().unwrap();
+                        if !self.skipped_candidates.is_empty() {
+                            self.pending = Some((value, candidates));
+                            return Ok(Some(self.skipped_bucket(position)));
+                        }
+                        self.scores.skip_to(position.bucket);
+                    }
+
                     return Ok(Some(CriterionResult {
                         query_tree: self.query_tree.clone(),
                         candidates: Some(candidates),
                         filtered_candidates: None,
                         bucket_candidates: Some(take(&mut self.bucket_candidates)),
+                        score_details: self.sort_score(Some(value)),
                     }));
                 }
             }
@@ -166,14 +271,25 @@ impl<'t> Criterion for AscDesc<'t> {
 /// Returns an iterator over groups of the given candidates in ascending or descending order.
 ///
 /// It will either use an iterative or a recursive method on the whole facet database depending
-/// on the number of candidates to rank.
+/// on the number of candidates to rank. The iterative method stops fetching the facet values
+/// when the time budget is exceeded, the returned groups must then be ignored.
+///
+/// When all the documents of the index are candidates, the groups of the facet levels are not
+/// intersected with the candidates, the facet values are directly read from the level 0.
+///
+/// When a locale is given, the string groups are ordered by the collation of their original
+/// strings and a document is returned with the first of its strings in this order.
+#[allow(clippy::too_many_arguments)]
 fn facet_ordered<'t>(
     index: &'t Index,
     rtxn: &'t heed::RoTxn,
     field_id: FieldId,
     is_ascending: bool,
+    locale: Option<Locale>,
     candidates: RoaringBitmap,
-) -> Result<Box<dyn Iterator<Item = heed::Result<RoaringBitmap>> + 't>> {
+    all_documents: bool,
+    time_budget: &TimeBudget,
+) -> Result<Box<dyn Iterator<Item = heed::Result<(SortValue, RoaringBitmap)>> + 't>> {
     if candidates.len() <= CANDIDATES_THRESHOLD {
         let number_iter = iterative_facet_number_ordered_iter(
             index,
@@ -181,28 +297,69 @@ fn facet_ordered<'t>(
             field_id,
             is_ascending,
             candidates.clone(),
+            time_budget,
+        )?;
+        let string_iter = iterative_facet_string_ordered_iter(
+            index,
+            rtxn,
+            field_id,
+            is_ascending,
+            locale,
+            candidates,
+            time_budget,
         )?;
-        let string_iter =
-            iterative_facet_string_ordered_iter(index, rtxn, field_id, is_ascending, candidates)?;
         Ok(Box::new(number_iter.chain(string_iter).map(Ok)) as Box<dyn Iterator<Item = _>>)
     } else {
+        // The facet databases still contain the soft deleted documents,
+        // they are removed from the groups read from the level 0.
+        let (facet_candidates, soft_deleted_documents) = if all_documents {
+            (Candidates::All, index.soft_deleted_documents_ids(rtxn)?)
+        } else {
+            (Candidates::Bitmap(candidates), RoaringBitmap::new())
+        };
+
         let facet_number_fn = if is_ascending {
             FacetNumberIter::new_reducing
         } else {
             FacetNumberIter::new_reverse_reducing
         };
-        let number_iter = facet_number_fn(rtxn, index, field_id, candidates.clone())?
-            .map(|res| res.map(|(_, docids)| docids));
+        let number_iter = facet_number_fn(rtxn, index, field_id, facet_candidates.clone())?
+            .map(|res| res.map(|(value, docids)| (SortValue::Number(value), docids)));
 
-        let facet_string_fn = if is_ascending {
-            FacetStringIter::new_reducing
-        } else {
-            FacetStringIter::new_reverse_reducing
+        let string_iter = match locale {
+            Some(locale) => Box::new(CollatedStrings::new(
+                rtxn,
+                index,
+                field_id,
+                locale,
+                is_ascending,
+                facet_candidates,
+            )) as Box<dyn Iterator<Item = _> + 't>,
+            None => {
+                let facet_string_fn = if is_ascending {
+                    FacetStringIter::new_reducing
+                } else {
+                    FacetStringIter::new_reverse_reducing
+                };
+                let iter = facet_string_fn(rtxn, index, field_id, facet_candidates)?;
+                Box::new(iter.map(|res| {
+                    res.map(|(value, _, docids)| (SortValue::String(value.to_string()), docids))
+                }))
+            }
         };
-        let string_iter = facet_string_fn(rtxn, index, field_id, candidates)?
-            .map(|res| res.map(|(_, _, docids)| docids));
 
-        Ok(Box::new(number_iter.chain(string_iter)))
+        let iter = number_iter.chain(string_iter);
+        if soft_deleted_documents.is_empty() {
+            Ok(Box::new(iter))
+        } else {
+            Ok(Box::new(iter.filter_map(move |result| match result {
+                Ok((value, docids)) => {
+                    let docids = docids - &soft_deleted_documents;
+                    (!docids.is_empty()).then(|| Ok((value, docids)))
+                }
+                Err(e) => Some(Err(e)),
+            })))
+        }
     }
 }
 
@@ -215,14 +372,18 @@ fn iterative_facet_number_ordered_iter<'t>(
     field_id: FieldId,
     is_ascending: bool,
     candidates: RoaringBitmap,
-) -> Result<impl Iterator<Item = RoaringBitmap> + 't> {
+    time_budget: &TimeBudget,
+) -> Result<impl Iterator<Item = (SortValue, RoaringBitmap)> + 't> {
     let mut docids_values = Vec::with_capacity(candidates.len() as usize);
-    for docid in candidates.iter() {
+    for (i, docid) in candidates.iter().enumerate() {
+        if i % TIME_BUDGET_CHECK_INTERVAL == 0 && time_budget.exceeded() {
+            break;
+        }
         let left = (field_id, docid, f64::MIN);
         let right = (field_id, docid, f64::MAX);
         let mut iter = index.field_id_docid_facet_f64s.range(rtxn, &(left..=right))?;
         let entry = if is_ascending { iter.next() } else { iter.last() };
-        if let Some(((_, _, value), ())) = entry.transpose()? {
+        if let Some(((_, _, value), _)) = entry.transpose()? {
             docids_values.push((docid, OrderedFloat(value)));
         }
     }
@@ -240,7 +401,7 @@ fn iterative_facet_number_ordered_iter<'t>(
     let vec: Vec<_> = iter
         .group_by(|(_, v)| *v)
         .into_iter()
-        .map(|(_, ids)| ids.map(|(id, _)| id).collect())
+        .map(|(value, ids)| (SortValue::Number(value.0), ids.map(|(id, _)| id).collect()))
         .collect();
 
     Ok(vec.into_iter())
@@ -248,27 +409,56 @@ fn iterative_facet_number_ordered_iter<'t>(
 
 /// Fetch the whole list of candidates facet string values one by one and order them by it.
 ///
+/// The values of a document are ordered by their bytes, when a locale is given all of them
+/// are collated to find the first one in the order of the locale.
 /// This function is fast when the amount of candidates to rank is small.
 fn iterative_facet_string_ordered_iter<'t>(
     index: &'t Index,
     rtxn: &'t heed::RoTxn,
     field_id: FieldId,
     is_ascending: bool,
+    locale: Option<Locale>,
     candidates: RoaringBitmap,
-) -> Result<impl Iterator<Item = RoaringBitmap> + 't> {
+    time_budget: &TimeBudget,
+) -> Result<impl Iterator<Item = (SortValue, RoaringBitmap)> + 't> {
     let mut docids_values = Vec::with_capacity(candidates.len() as usize);
-    for docid in candidates.iter() {
+    for (i, docid) in candidates.iter().enumerate() {
+        if i % TIME_BUDGET_CHECK_INTERVAL == 0 && time_budget.exceeded() {
+            break;
+        }
         let left = (field_id, docid, "");
         let right = (field_id, docid.saturating_add(1), "");
         // FIXME Doing this means that it will never be possible to retrieve
         //       the document with id 2^32, not sure this is a real problem.
         let mut iter = index.field_id_docid_facet_strings.range(rtxn, &(left..right))?;
-        let entry = if is_ascending { iter.next() } else { iter.last() };
-        if let Some(((_, _, value), _)) = entry.transpose()? {
-            docids_values.push((docid, value));
+        let entry = match locale {
+            Some(locale) => {
+                let mut first: Option<(CollationKey, &str)> = None;
+                for result in iter {
+                    let ((_, _, value), original) = result?;
+                    let key = collation_key(locale, original);
+                    let is_first = match &first {
+                        Some((first_key, _)) if is_ascending => key < *first_key,
+                        Some((first_key, _)) => key > *first_key,
+                        None => true,
+                    };
+                    if is_first {
+                        first = Some((key, value));
+                    }
+                }
+                first.map(|(key, value)| (Some(key), value))
+            }
+            None => {
+                let entry = if is_ascending { iter.next() } else { iter.last() };
+                entry.transpose()?.map(|((_, _, value), _)| (None, value))
+            }
+        };
+        if let Some((key, value)) = entry {
+            docids_values.push((docid, key, value));
         }
     }
-    docids_values.sort_unstable_by_key(|(_, v)| *v);
+    // Without a locale there is no collation key and the values are ordered by their bytes.
+    docids_values.sort_unstable_by(|(_, ka, va), (_, kb, vb)| ka.cmp(kb).then(va.cmp(vb)));
     let iter = docids_values.into_iter();
     let iter = if is_ascending {
         Box::new(iter) as Box<dyn Iterator<Item = _>>
@@ -280,10 +470,190 @@ fn iterative_facet_string_ordered_iter<'t>(
     // required to collect the result into an owned collection (a Vec).
     // https://github.com/rust-itertools/itertools/issues/499
     let vec: Vec<_> = iter
-        .group_by(|(_, v)| *v)
+        .group_by(|(_, _, v)| *v)
         .into_iter()
-        .map(|(_, ids)| ids.map(|(id, _)| id).collect())
+        .map(|(value, ids)| {
+            (SortValue::String(value.to_string()), ids.map(|(id, _, _)| id).collect())
+        })
         .collect();
 
     Ok(vec.into_iter())
 }
+
+/// Returns the collation key of an original facet string, which is trimmed like the
+/// normalized strings the level 0 is ordered by.
+fn collation_key(locale: Locale, original: &str) -> CollationKey {
+    locale.sort_key(original.trim())
+}
+
+/// The string groups of the level 0 ordered by the collation of a locale.
+///
+/// The collation compares the primary letters of the strings first, the level 0 is therefore
+/// read one page at a time, a page being the strings whose first char has the same primary
+/// letter, e.g. `a`, `á` and `ä`. Only the strings of the current page are collated and a
+/// document is only returned in the group of its first string in the collation order.
+struct CollatedStrings<'t> {
+    rtxn: &'t heed::RoTxn<'t>,
+    db: Database<ByteSlice, ByteSlice>,
+    field_id: FieldId,
+    locale: Locale,
+    is_ascending: bool,
+    /// The candidates to rank, `None` when all the documents are candidates.
+    candidates: Option<RoaringBitmap>,
+    returned: RoaringBitmap,
+    /// The first chars of the strings of each page, `None` being the empty string,
+    /// they are only listed when the first group is requested.
+    pages: Option<std::vec::IntoIter<Vec<Option<char>>>>,
+    collated: std::vec::IntoIter<(String, RoaringBitmap)>,
+}
+
+impl<'t> CollatedStrings<'t> {
+    fn new(
+        rtxn: &'t heed::RoTxn,
+        index: &'t Index,
+        field_id: FieldId,
+        locale: Locale,
+        is_ascending: bool,
+        candidates: Candidates,
+    ) -> CollatedStrings<'t> {
+        let candidates = match candidates {
+            Candidates::All => None,
+            Candidates::Bitmap(candidates) => Some(candidates),
+        };
+        CollatedStrings {
+            rtxn,
+            db: index.facet_id_string_docids.remap_types::<ByteSlice, ByteSlice>(),
+            field_id,
+            locale,
+            is_ascending,
+            candidates,
+            returned: RoaringBitmap::new(),
+            pages: None,
+            collated: Vec::new().into_iter(),
+        }
+    }
+
+    /// The prefix of the keys of the level 0 of the field.
+    fn level_zero_prefix(&self) -> Vec<u8> {
+        let mut prefix = self.field_id.to_be_bytes().to_vec();
+        prefix.push(0);
+        prefix
+    }
+
+    /// Lists the first chars of the strings of the level 0 by seeking after the strings
+    /// starting with the previous one, and groups them into pages by their primary letter.
+    fn read_pages(&self) -> heed::Result<Vec<Vec<Option<char>>>> {
+        let mut upper = self.field_id.to_be_bytes().to_vec();
+        upper.push(1); // we must only get the level 0
+
+        let mut pages: BTreeMap<Option<char>, Vec<Option<char>>> = BTreeMap::new();
+        let mut lower = self.level_zero_prefix();
+        let mut lower_included = true;
+        loop {
+            let lower_bound =
+                if lower_included { Included(&lower[..]) } else { Excluded(&lower[..]) };
+            let mut iter = self
+                .db
+                .range(self.rtxn, &(lower_bound, Excluded(&upper[..])))?
+                .remap_types::<FacetStringLevelZeroCodec, DecodeIgnore>();
+            let first = match iter.next().transpose()? {
+                Some(((_, value), ())) => value.chars().next(),
+                None => break,
+            };
+
+            pages.entry(first.map(primary_letter)).or_default().push(first);
+            lower = self.level_zero_prefix();
+            match first {
+                // The empty string is the first key of the level 0.
+                None => lower_included = false,
+                Some(c) => {
+                    // No UTF-8 encoded string contains the 0xFF byte, the strings
+                    // starting with this char are all before this bound.
+                    lower.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                    lower.push(0xFF);
+                    lower_included = true;
+                }
+            }
+        }
+
+        let mut pages: Vec<_> = pages.into_values().collect();
+        if !self.is_ascending {
+            pages.reverse();
+        }
+        Ok(pages)
+    }
+
+    /// Reads and collates the strings of the level 0 starting with the given chars.
+    fn collate_page(&mut self, page: Vec<Option<char>>) -> heed::Result<()> {
+        let mut keyed = Vec::new();
+        for first in page {
+            let mut prefix = self.level_zero_prefix();
+            if let Some(c) = first {
+                prefix.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+            let mut upper = prefix.clone();
+            let upper_bound = match first {
+                Some(_) => {
+                    upper.push(0xFF);
+                    Excluded(&upper[..])
+                }
+                // The empty string is the only key equal to the prefix.
+                None => Included(&upper[..]),
+            };
+            let iter = self
+                .db
+                .range(self.rtxn, &(Included(&prefix[..]), upper_bound))?
+                .remap_types::<FacetStringLevelZeroCodec, FacetStringLevelZeroValueCodec>();
+            for result in iter {
+                let ((_, value), (original, mut docids)) = result?;
+                if let Some(candidates) = &self.candidates {
+                    docids &= candidates;
+                }
+                if !docids.is_empty() {
+                    keyed.push((collation_key(self.locale, original), value.to_string(), docids));
+                }
+            }
+        }
+
+        keyed.sort_unstable_by(|(a, _, _), (b, _, _)| a.cmp(b));
+        if !self.is_ascending {
+            keyed.reverse();
+        }
+        self.collated = keyed
+            .into_iter()
+            .map(|(_, value, docids)| (value, docids))
+            .collect::<Vec<_>>()
+            .into_iter();
+        Ok(())
+    }
+}
+
+impl<'t> Iterator for CollatedStrings<'t> {
+    type Item = heed::Result<(SortValue, RoaringBitmap)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // A document with several strings is only returned with the first of them.
+            if let Some((value, mut docids)) = self.collated.next() {
+                docids -= &self.returned;
+                if docids.is_empty() {
+                    continue;
+                }
+                self.returned |= &docids;
+                return Some(Ok((SortValue::String(value), docids)));
+            }
+
+            if self.pages.is_none() {
+                match self.read_pages() {
+                    Ok(pages) => self.pages = Some(pages.into_iter()),
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+
+            let page = self.pages.as_mut().and_then(|pages| pages.next())?;
+            if let Err(e) = self.collate_page(page) {
+                return Some(Err(e));
+            }
+        }
+    }
+}
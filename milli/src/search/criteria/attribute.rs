@@ -6,7 +6,10 @@ use std::mem::take;
 
 use roaring::RoaringBitmap;
 
-use super::{resolve_query_tree, Context, Criterion, CriterionParameters, CriterionResult};
+use super::{
+    resolve_query_tree, BucketScores, Context, Criterion, CriterionParameters, CriterionResult,
+    RankingRuleScore, RuleName,
+};
 use crate::search::criteria::Query;
 use crate::search::query_tree::{Operation, QueryKind};
 use crate::search::{build_dfa, word_derivations, WordDerivationsCache};
@@ -27,6 +30,7 @@ pub struct Attribute<'t> {
     ctx: &'t dyn Context<'t>,
     state: Option<(Operation, FlattenedQueryTree, RoaringBitmap)>,
     bucket_candidates: RoaringBitmap,
+    scores: BucketScores,
     parent: Box<dyn Criterion + 't>,
     linear_buckets: Option<btree_map::IntoIter<u64, RoaringBitmap>>,
     set_buckets: Option<BinaryHeap<Branch<'t>>>,
@@ -38,6 +42,7 @@ impl<'t> Attribute<'t> {
             ctx,
             state: None,
             bucket_candidates: RoaringBitmap::new(),
+            scores: BucketScores::default(),
             parent,
             linear_buckets: None,
             set_buckets: None,
@@ -61,6 +66,7 @@ impl<'t> Criterion for Attribute<'t> {
                         candidates: Some(RoaringBitmap::new()),
                         filtered_candidates: None,
                         bucket_candidates: Some(take(&mut self.bucket_candidates)),
+                        score_details: self.scores.next(RankingRuleScore::Attribute),
                     }));
                 }
                 Some((query_tree, flattened_query_tree, mut allowed_candidates)) => {
@@ -85,6 +91,7 @@ impl<'t> Criterion for Attribute<'t> {
                                     candidates: Some(RoaringBitmap::new()),
                                     filtered_candidates: None,
                                     bucket_candidates: Some(take(&mut self.bucket_candidates)),
+                                    score_details: self.scores.next(RankingRuleScore::Attribute),
                                 }));
                             }
                         }
@@ -105,11 +112,21 @@ impl<'t> Criterion for Attribute<'t> {
                         match set_compute_candidates(&mut set_buckets, &allowed_candidates)? {
                             Some((_score, candidates)) => candidates,
                             None => {
+                                // The candidates missed because of the ignored word derivations
+                                // are returned, unranked, after the ranked buckets.
+                                let candidates =
+                                    if self.ctx.iteration_budget().is_degraded(RuleName::Attribute)
+                                    {
+                                        allowed_candidates
+                                    } else {
+                                        RoaringBitmap::new()
+                                    };
                                 return Ok(Some(CriterionResult {
                                     query_tree: Some(query_tree),
-                                    candidates: Some(RoaringBitmap::new()),
+                                    candidates: Some(candidates),
                                     filtered_candidates: None,
                                     bucket_candidates: Some(take(&mut self.bucket_candidates)),
+                                    score_details: self.scores.next(RankingRuleScore::Attribute),
                                 }));
                             }
                         }
@@ -125,15 +142,22 @@ impl<'t> Criterion for Attribute<'t> {
                         candidates: Some(found_candidates),
                         filtered_candidates: None,
                         bucket_candidates: Some(take(&mut self.bucket_candidates)),
+                        score_details: self.scores.next(RankingRuleScore::Attribute),
                     }));
                 }
                 None => match self.parent.next(params)? {
+                    // The documents of the bucket were all returned by the previous pages.
+                    Some(result) if params.is_skipped(&result.score_details) => {
+                        return Ok(Some(result));
+                    }
                     Some(CriterionResult {
                         query_tree: Some(query_tree),
                         candidates,
                         filtered_candidates,
                         bucket_candidates,
+                        score_details,
                     }) => {
+                        self.scores.reset(score_details);
                         let mut candidates = match candidates {
                             Some(candidates) => candidates,
                             None => {
@@ -153,6 +177,21 @@ impl<'t> Criterion for Attribute<'t> {
                             None => self.bucket_candidates |= &candidates,
                         }
 
+                        // A bucket too large to be ranked is passed through.
+                        if self
+                            .ctx
+                            .iteration_budget()
+                            .too_many_candidates(RuleName::Attribute, candidates.len())
+                        {
+                            return Ok(Some(CriterionResult {
+                                query_tree: Some(query_tree),
+                                candidates: Some(candidates),
+                                filtered_candidates: None,
+                                bucket_candidates: Some(take(&mut self.bucket_candidates)),
+                                score_details: self.scores.next(RankingRuleScore::Attribute),
+                            }));
+                        }
+
                         self.state = Some((query_tree, flattened_query_tree, candidates));
                         self.linear_buckets = None;
                     }
@@ -161,12 +200,14 @@ impl<'t> Criterion for Attribute<'t> {
                         candidates,
                         filtered_candidates,
                         bucket_candidates,
+                        score_details,
                     }) => {
                         return Ok(Some(CriterionResult {
                             query_tree: None,
                             candidates,
                             filtered_candidates,
                             bucket_candidates,
+                            score_details,
                         }));
                     }
                     None => return Ok(None),
@@ -199,7 +240,9 @@ impl<'t> QueryPositionIterator<'t> {
                         let iter = ctx.word_position_iterator(word, in_prefix_cache)?;
                         inner.push(iter.peekable());
                     } else {
-                        for (word, _) in word_derivations(&word, true, 0, ctx.words_fst(), wdcache)?
+                        let words = word_derivations(&word, true, 0, ctx.words_fst(), wdcache)?;
+                        for (word, _) in
+                            ctx.iteration_budget().derivations(RuleName::Attribute, words)
                         {
                             let iter = ctx.word_position_iterator(&word, in_prefix_cache)?;
                             inner.push(iter.peekable());
@@ -207,8 +250,9 @@ impl<'t> QueryPositionIterator<'t> {
                     }
                 }
                 QueryKind::Tolerant { typo, word } => {
-                    for (word, _) in
-                        word_derivations(&word, query.prefix, *typo, ctx.words_fst(), wdcache)?
+                    let words =
+                        word_derivations(&word, query.prefix, *typo, ctx.words_fst(), wdcache)?;
+                    for (word, _) in ctx.iteration_budget().derivations(RuleName::Attribute, words)
                     {
                         let iter = ctx.word_position_iterator(&word, in_prefix_cache)?;
                         inner.push(iter.peekable());
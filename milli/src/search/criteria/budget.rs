@@ -0,0 +1,54 @@
+use std::cell::Cell;
+use std::time::Instant;
+
+/// The deadline of a search, the criteria stop returning buckets once it is exceeded.
+///
+/// Once a check found the deadline exceeded, all the following checks do too. The search
+/// is then stopped between two buckets and returns the complete buckets it already found.
+#[derive(Debug, Default)]
+pub struct TimeBudget {
+    deadline: Option<Instant>,
+    exceeded: Cell<bool>,
+}
+
+impl TimeBudget {
+    pub fn new(deadline: Option<Instant>) -> TimeBudget {
+        TimeBudget { deadline, exceeded: Cell::new(false) }
+    }
+
+    /// Returns `true` if the deadline is exceeded.
+    pub fn exceeded(&self) -> bool {
+        if !self.exceeded.get() {
+            let exceeded = self.deadline.map_or(false, |deadline| Instant::now() >= deadline);
+            self.exceeded.set(exceeded);
+        }
+        self.exceeded.get()
+    }
+
+    /// Returns `true` if a previous check found the deadline exceeded.
+    pub fn was_exceeded(&self) -> bool {
+        self.exceeded.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn exceeded_budgets_stay_exceeded() {
+        let budget = TimeBudget::default();
+        assert!(!budget.exceeded());
+        assert!(!budget.was_exceeded());
+
+        let budget = TimeBudget::new(Instant::now().checked_add(Duration::from_secs(3600)));
+        assert!(!budget.exceeded());
+
+        let budget = TimeBudget::new(Some(Instant::now()));
+        assert!(!budget.was_exceeded());
+        assert!(budget.exceeded());
+        assert!(budget.was_exceeded());
+    }
+}
@@ -7,7 +7,8 @@ use log::debug;
 use roaring::RoaringBitmap;
 
 use crate::search::criteria::{
-    resolve_phrase, resolve_query_tree, Context, Criterion, CriterionParameters, CriterionResult,
+    resolve_phrase, resolve_query_tree, BucketScores, Context, Criterion, CriterionParameters,
+    CriterionResult, RankingRuleScore,
 };
 use crate::search::query_tree::{Operation, PrimitiveQueryPart};
 use crate::{absolute_from_relative_position, FieldId, Result};
@@ -17,6 +18,7 @@ pub struct Exactness<'t> {
     query_tree: Option<Operation>,
     state: Option<State>,
     bucket_candidates: RoaringBitmap,
+    scores: BucketScores,
     parent: Box<dyn Criterion + 't>,
     query: Vec<ExactQueryPart>,
 }
@@ -37,6 +39,7 @@ impl<'t> Exactness<'t> {
             query_tree: None,
             state: None,
             bucket_candidates: RoaringBitmap::new(),
+            scores: BucketScores::default(),
             parent,
             query,
         })
@@ -69,15 +72,22 @@ impl<'t> Criterion for Exactness<'t> {
                         candidates: Some(candidates),
                         filtered_candidates: None,
                         bucket_candidates: Some(take(&mut self.bucket_candidates)),
+                        score_details: self.scores.next(RankingRuleScore::Exactness),
                     }));
                 }
                 None => match self.parent.next(params)? {
+                    // The documents of the bucket were all returned by the previous pages.
+                    Some(result) if params.is_skipped(&result.score_details) => {
+                        return Ok(Some(result));
+                    }
                     Some(CriterionResult {
                         query_tree: Some(query_tree),
                         candidates,
                         filtered_candidates,
                         bucket_candidates,
+                        score_details,
                     }) => {
+                        self.scores.reset(score_details);
                         let mut candidates = match candidates {
                             Some(candidates) => candidates,
                             None => {
@@ -103,12 +113,14 @@ impl<'t> Criterion for Exactness<'t> {
                         candidates,
                         filtered_candidates,
                         bucket_candidates,
+                        score_details,
                     }) => {
                         return Ok(Some(CriterionResult {
                             query_tree: None,
                             candidates,
                             filtered_candidates,
                             bucket_candidates,
+                            score_details,
                         }));
                     }
                     None => return Ok(None),
@@ -1,27 +1,28 @@
+use std::mem::take;
+
 use log::debug;
 use roaring::RoaringBitmap;
 
-use super::{resolve_query_tree, Context, Criterion, CriterionParameters, CriterionResult};
+use super::{
+    resolve_query_tree, BucketPosition, Context, Criterion, CriterionParameters, CriterionResult,
+    ScoreDetails,
+};
 use crate::search::query_tree::Operation;
 use crate::search::WordDerivationsCache;
-use crate::Result;
+use crate::{DocumentId, Result};
 
 /// The result of a call to the fetcher.
 #[derive(Debug, Clone, PartialEq)]
-pub struct FinalResult {
-    /// The query tree corresponding to the current bucket of the last criterion.
-    pub query_tree: Option<Operation>,
-    /// The candidates of the current bucket of the last criterion.
-    pub candidates: RoaringBitmap,
-    /// Candidates that comes from the current bucket of the initial criterion.
-    pub bucket_candidates: RoaringBitmap,
-}
-
 pub struct Final<'t> {
     ctx: &'t dyn Context<'t>,
     parent: Box<dyn Criterion + 't>,
     wdcache: WordDerivationsCache,
     returned_candidates: RoaringBitmap,
+    /// The buckets and the id of the last document of the previous page when the
+    /// search is continued, until the criteria return the bucket of this document.
+    resume_from: Option<(Vec<BucketPosition>, DocumentId)>,
+    /// The bucket candidates of the buckets returned by the previous pages.
+    skipped_bucket_candidates: RoaringBitmap,
 }
 
 impl<'t> Final<'t> {
@@ -31,45 +32,94 @@ impl<'t> Final<'t> {
             parent,
             wdcache: WordDerivationsCache::new(),
             returned_candidates: RoaringBitmap::new(),
+            resume_from: None,
+            skipped_bucket_candidates: RoaringBitmap::new(),
         }
     }
 
+    /// Resumes the ranking after the given document of the bucket with the given position.
+    ///
+    /// The buckets ranked before it are skipped without being split by the following criteria,
+    /// their documents are only excluded from the next buckets.
+    pub fn resume_from(&mut self, position: Vec<BucketPosition>, last_docid: DocumentId) {
+        self.resume_from = Some((position, last_docid));
+    }
+
     #[logging_timer::time("Final::{}")]
     pub fn next(&mut self, excluded_candidates: &RoaringBitmap) -> Result<Option<FinalResult>> {
-        debug!("Final iteration");
-        let excluded_candidates = &self.returned_candidates | excluded_candidates;
-        let mut criterion_parameters = CriterionParameters {
-            wdcache: &mut self.wdcache,
-            // returned_candidates is merged with excluded_candidates to avoid duplicas
-            excluded_candidates: &excluded_candidates,
-        };
+        loop {
+            debug!("Final iteration");
+            if self.ctx.time_budget().exceeded() {
+                return Ok(None);
+            }
+
+            let excluded_candidates = &self.returned_candidates | excluded_candidates;
+            let mut criterion_parameters = CriterionParameters {
+                wdcache: &mut self.wdcache,
+                // returned_candidates is merged with excluded_candidates to avoid duplicas
+                excluded_candidates: &excluded_candidates,
+                position: self.resume_from.as_ref().map(|(position, _)| position.as_slice()),
+            };
 
-        match self.parent.next(&mut criterion_parameters)? {
-            Some(CriterionResult {
+            let CriterionResult {
                 query_tree,
                 candidates,
                 filtered_candidates,
                 bucket_candidates,
-            }) => {
-                let mut candidates = match (candidates, query_tree.as_ref()) {
-                    (Some(candidates), _) => candidates,
-                    (None, Some(qt)) => {
-                        resolve_query_tree(self.ctx, qt, &mut self.wdcache)? - excluded_candidates
-                    }
-                    (None, None) => self.ctx.documents_ids()? - excluded_candidates,
-                };
+                score_details,
+            } = match self.parent.next(&mut criterion_parameters)? {
+                Some(result) => result,
+                // The bucket candidates of the last skipped buckets are still returned.
+                None if !self.skipped_bucket_candidates.is_empty() => {
+                    return Ok(Some(FinalResult {
+                        query_tree: None,
+                        candidates: RoaringBitmap::new(),
+                        bucket_candidates: take(&mut self.skipped_bucket_candidates),
+                        score_details: ScoreDetails::new(),
+                    }));
+                }
+                None => return Ok(None),
+            };
+            let is_skipped = criterion_parameters.is_skipped(&score_details);
 
-                if let Some(filtered_candidates) = filtered_candidates {
-                    candidates &= filtered_candidates;
+            let mut candidates = match (candidates, query_tree.as_ref()) {
+                (Some(candidates), _) => candidates,
+                (None, Some(qt)) => {
+                    resolve_query_tree(self.ctx, qt, &mut self.wdcache)? - excluded_candidates
                 }
+                (None, None) => self.ctx.documents_ids()? - excluded_candidates,
+            };
 
-                let bucket_candidates = bucket_candidates.unwrap_or_else(|| candidates.clone());
+            if let Some(filtered_candidates) = filtered_candidates {
+                candidates &= filtered_candidates;
+            }
 
-                self.returned_candidates |= &candidates;
+            let mut bucket_candidates = bucket_candidates.unwrap_or_else(|| candidates.clone());
 
-                Ok(Some(FinalResult { query_tree, candidates, bucket_candidates }))
+            self.returned_candidates |= &candidates;
+
+            if is_skipped {
+                self.skipped_bucket_candidates |= bucket_candidates;
+                continue;
+            }
+            bucket_candidates |= take(&mut self.skipped_bucket_candidates);
+
+            // The documents of the bucket of the previous page, up to
+            // its last document, have been returned by the previous pages.
+            if let Some((position, last_docid)) = self.resume_from.take() {
+                let is_resumed_bucket = position.len() == score_details.len()
+                    && position.iter().zip(&score_details).all(|(p, d)| p.bucket == d.bucket);
+                if is_resumed_bucket {
+                    candidates.remove_range(..=last_docid);
+                }
             }
-            None => Ok(None),
+
+            return Ok(Some(FinalResult {
+                query_tree,
+                candidates,
+                bucket_candidates,
+                score_details,
+            }));
         }
     }
 }
@@ -1,13 +1,15 @@
 use std::iter;
 
+use ordered_float::OrderedFloat;
 use roaring::RoaringBitmap;
-use rstar::RTree;
+use rstar::{PointDistance, RTree};
 
-use super::{Criterion, CriterionParameters, CriterionResult};
-use crate::search::criteria::{resolve_query_tree, CriteriaBuilder};
+use super::{BucketScores, Criterion, CriterionParameters, CriterionResult, RankingRuleScore};
+use crate::search::criteria::{resolve_query_tree, Context};
 use crate::{lat_lng_to_xyz, GeoPoint, Index, Result};
 
 pub struct Geo<'t> {
+    ctx: &'t dyn Context<'t>,
     index: &'t Index,
     rtxn: &'t heed::RoTxn<'t>,
     ascending: bool,
@@ -15,42 +17,47 @@ pub struct Geo<'t> {
     candidates: Box<dyn Iterator<Item = RoaringBitmap>>,
     allowed_candidates: RoaringBitmap,
     bucket_candidates: RoaringBitmap,
+    scores: BucketScores,
     rtree: Option<RTree<GeoPoint>>,
-    point: [f64; 2],
+    points: Vec<[f64; 2]>,
 }
 
 impl<'t> Geo<'t> {
     pub fn asc(
+        ctx: &'t dyn Context<'t>,
         index: &'t Index,
         rtxn: &'t heed::RoTxn<'t>,
         parent: Box<dyn Criterion + 't>,
-        point: [f64; 2],
+        points: Vec<[f64; 2]>,
     ) -> Result<Self> {
-        Self::new(index, rtxn, parent, point, true)
+        Self::new(ctx, index, rtxn, parent, points, true)
     }
 
     pub fn desc(
+        ctx: &'t dyn Context<'t>,
         index: &'t Index,
         rtxn: &'t heed::RoTxn<'t>,
         parent: Box<dyn Criterion + 't>,
-        point: [f64; 2],
+        points: Vec<[f64; 2]>,
     ) -> Result<Self> {
-        Self::new(index, rtxn, parent, point, false)
+        Self::new(ctx, index, rtxn, parent, points, false)
     }
 
     fn new(
+        ctx: &'t dyn Context<'t>,
         index: &'t Index,
         rtxn: &'t heed::RoTxn<'t>,
         parent: Box<dyn Criterion + 't>,
-        point: [f64; 2],
+        points: Vec<[f64; 2]>,
         ascending: bool,
     ) -> Result<Self> {
         let candidates = Box::new(iter::empty());
-        let allowed_candidates = index.geo_faceted_documents_ids(rtxn)?;
+        let allowed_candidates = RoaringBitmap::new();
         let bucket_candidates = RoaringBitmap::new();
         let rtree = index.geo_rtree(rtxn)?;
 
         Ok(Self {
+            ctx,
             index,
             rtxn,
             ascending,
@@ -58,8 +65,9 @@ impl<'t> Geo<'t> {
             candidates,
             allowed_candidates,
             bucket_candidates,
+            scores: BucketScores::default(),
             rtree,
-            point,
+            points,
         })
     }
 }
@@ -78,21 +86,42 @@ impl Criterion for Geo<'_> {
                         candidates: Some(candidates),
                         filtered_candidates: None,
                         bucket_candidates: Some(self.bucket_candidates.clone()),
+                        score_details: self.scores.next(RankingRuleScore::GeoSort {
+                            points: self.points.clone(),
+                            ascending: self.ascending,
+                        }),
+                    }));
+                }
+                // The documents without geo data are returned in a trailing bucket.
+                None if !self.allowed_candidates.is_empty() => {
+                    let candidates = std::mem::take(&mut self.allowed_candidates);
+                    return Ok(Some(CriterionResult {
+                        query_tree: None,
+                        candidates: Some(candidates),
+                        filtered_candidates: None,
+                        bucket_candidates: Some(self.bucket_candidates.clone()),
+                        score_details: self.scores.next(RankingRuleScore::GeoSort {
+                            points: self.points.clone(),
+                            ascending: self.ascending,
+                        }),
                     }));
                 }
                 None => match self.parent.next(params)? {
+                    // The documents of the bucket were all returned by the previous pages.
+                    Some(result) if params.is_skipped(&result.score_details) => {
+                        return Ok(Some(result));
+                    }
                     Some(CriterionResult {
                         query_tree,
                         candidates,
                         filtered_candidates,
                         bucket_candidates,
+                        score_details,
                     }) => {
+                        self.scores.reset(score_details);
                         let mut candidates = match (&query_tree, candidates) {
                             (_, Some(candidates)) => candidates,
-                            (Some(qt), None) => {
-                                let context = CriteriaBuilder::new(&self.rtxn, &self.index)?;
-                                resolve_query_tree(&context, qt, params.wdcache)?
-                            }
+                            (Some(qt), None) => resolve_query_tree(self.ctx, qt, params.wdcache)?,
                             (None, None) => self.index.documents_ids(self.rtxn)?,
                         };
 
@@ -113,7 +142,7 @@ impl Criterion for Geo<'_> {
                             Some(rtree) => geo_point(
                                 rtree,
                                 self.allowed_candidates.clone(),
-                                self.point,
+                                &self.points,
                                 self.ascending,
                             ),
                             None => Box::new(std::iter::empty()),
@@ -126,22 +155,41 @@ impl Criterion for Geo<'_> {
     }
 }
 
+/// Returns the candidates one by one, ordered by their distance to the nearest of the points.
 fn geo_point(
     rtree: &RTree<GeoPoint>,
     mut candidates: RoaringBitmap,
-    point: [f64; 2],
+    points: &[[f64; 2]],
     ascending: bool,
 ) -> Box<dyn Iterator<Item = RoaringBitmap>> {
-    let point = lat_lng_to_xyz(&point);
-
     let mut results = Vec::new();
-    for point in rtree.nearest_neighbor_iter(&point) {
-        if candidates.remove(point.data.0) {
-            results.push(std::iter::once(point.data.0).collect());
-            if candidates.is_empty() {
-                break;
+    if let [point] = points {
+        let point = lat_lng_to_xyz(point);
+        for point in rtree.nearest_neighbor_iter(&point) {
+            if candidates.remove(point.data.0) {
+                results.push(std::iter::once(point.data.0).collect());
+                if candidates.is_empty() {
+                    break;
+                }
             }
         }
+    } else {
+        let points: Vec<_> = points.iter().map(lat_lng_to_xyz).collect();
+        let mut distances: Vec<_> = rtree
+            .iter()
+            .filter(|geo_point| candidates.contains(geo_point.data.0))
+            .map(|geo_point| {
+                let distance = points
+                    .iter()
+                    .map(|point| OrderedFloat(geo_point.distance_2(point)))
+                    .min()
+                    .unwrap_or_default();
+                (distance, geo_point.data.0)
+            })
+            .collect();
+        // equidistant documents are ordered by their ids
+        distances.sort_unstable();
+        results.extend(distances.into_iter().map(|(_, docid)| std::iter::once(docid).collect()));
     }
 
     if ascending {
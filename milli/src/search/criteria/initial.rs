@@ -3,37 +3,35 @@ use roaring::RoaringBitmap;
 use super::{Criterion, CriterionParameters, CriterionResult};
 use crate::search::criteria::{resolve_query_tree, Context};
 use crate::search::query_tree::Operation;
-use crate::search::Distinct;
 use crate::Result;
 /// Initial is a mandatory criterion, it is always the first
 /// and is meant to initalize the CriterionResult used by the other criteria.
 /// It behave like an [Once Iterator](https://doc.rust-lang.org/std/iter/struct.Once.html) and will return Some(CriterionResult) only one time.
-pub struct Initial<'t, D> {
+pub struct Initial<'t> {
     ctx: &'t dyn Context<'t>,
     answer: Option<CriterionResult>,
     exhaustive_number_hits: bool,
-    distinct: Option<D>,
 }
 
-impl<'t, D> Initial<'t, D> {
+impl<'t> Initial<'t> {
     pub fn new(
         ctx: &'t dyn Context<'t>,
         query_tree: Option<Operation>,
         filtered_candidates: Option<RoaringBitmap>,
         exhaustive_number_hits: bool,
-        distinct: Option<D>,
-    ) -> Initial<D> {
+    ) -> Initial {
         let answer = CriterionResult {
             query_tree,
             candidates: None,
             filtered_candidates,
             bucket_candidates: None,
+            score_details: Vec::new(),
         };
-        Initial { ctx, answer: Some(answer), exhaustive_number_hits, distinct }
+        Initial { ctx, answer: Some(answer), exhaustive_number_hits }
     }
 }
 
-impl<D: Distinct> Criterion for Initial<'_, D> {
+impl Criterion for Initial<'_> {
     #[logging_timer::time("Initial::{}")]
     fn next(&mut self, params: &mut CriterionParameters) -> Result<Option<CriterionResult>> {
         self.answer
@@ -52,21 +50,10 @@ impl<D: Distinct> Criterion for Initial<'_, D> {
                         candidates &= filtered_candidates;
                     }
 
-                    // because the bucket_candidates should be an exhaustive count of the matching documents,
-                    // we precompute the distinct attributes.
-                    let bucket_candidates = match &mut self.distinct {
-                        Some(distinct) => {
-                            let mut bucket_candidates = RoaringBitmap::new();
-                            for c in distinct.distinct(candidates.clone(), RoaringBitmap::new()) {
-                                bucket_candidates.insert(c?);
-                            }
-                            bucket_candidates
-                        }
-                        None => candidates.clone(),
-                    };
-
+                    // The bucket_candidates are an exhaustive list of the matching documents,
+                    // the distinct attribute is applied to all of them once the search is sorted.
+                    answer.bucket_candidates = Some(candidates.clone());
                     answer.candidates = Some(candidates);
-                    answer.bucket_candidates = Some(bucket_candidates);
                 }
                 Ok(answer)
             })
@@ -0,0 +1,115 @@
+use std::cell::RefCell;
+
+/// The caps on the work of the proximity and attribute ranking rules for a single search,
+/// that bound the cost of the queries with many words or many derivations.
+///
+/// When a cap is reached the ranking rule gives up on ranking precisely, it still returns
+/// all the documents it was given and is listed in the `degraded_by` rules of the result.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SearchLimits {
+    /// The maximum number of derivations, prefixes and typos, considered for a query word.
+    pub max_word_derivations: Option<usize>,
+    /// The maximum number of word pairs evaluated to compute a bucket of the proximity rule.
+    pub max_pair_combinations: Option<usize>,
+    /// The maximum number of candidates ranked by the proximity and attribute rules,
+    /// the candidates of a larger bucket of the previous rule are passed through unranked.
+    pub max_bucket_candidates: Option<u64>,
+}
+
+/// A ranking rule that can be degraded by the [`SearchLimits`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleName {
+    Proximity,
+    Attribute,
+}
+
+/// The [`SearchLimits`] of a search along with the ranking rules they degraded.
+#[derive(Debug, Default)]
+pub struct IterationBudget {
+    limits: SearchLimits,
+    degraded_by: RefCell<Vec<RuleName>>,
+}
+
+impl IterationBudget {
+    pub fn new(limits: SearchLimits) -> IterationBudget {
+        IterationBudget { limits, degraded_by: RefCell::new(Vec::new()) }
+    }
+
+    pub fn limits(&self) -> &SearchLimits {
+        &self.limits
+    }
+
+    /// Records that a cap made the rule give up on ranking precisely.
+    pub fn degrade(&self, rule: RuleName) {
+        let mut degraded_by = self.degraded_by.borrow_mut();
+        if !degraded_by.contains(&rule) {
+            degraded_by.push(rule);
+        }
+    }
+
+    pub fn is_degraded(&self, rule: RuleName) -> bool {
+        self.degraded_by.borrow().contains(&rule)
+    }
+
+    /// The rules degraded so far, in the order they were first degraded.
+    pub fn degraded_by(&self) -> Vec<RuleName> {
+        self.degraded_by.borrow().clone()
+    }
+
+    /// Returns the first `max_word_derivations` derivations of a word,
+    /// the rule is degraded if some of them are ignored.
+    pub fn derivations<'a, T>(&self, rule: RuleName, derivations: &'a [T]) -> &'a [T] {
+        match self.limits.max_word_derivations {
+            Some(max) if derivations.len() > max => {
+                self.degrade(rule);
+                &derivations[..max]
+            }
+            _ => derivations,
+        }
+    }
+
+    /// Returns `true` if more than `max_pair_combinations` pairs were evaluated for a bucket.
+    pub fn too_many_pairs(&self, evaluated: usize) -> bool {
+        self.limits.max_pair_combinations.map_or(false, |max| evaluated > max)
+    }
+
+    /// Returns `true`, and degrades the rule, if the bucket has too many candidates to be ranked.
+    pub fn too_many_candidates(&self, rule: RuleName, candidates: u64) -> bool {
+        match self.limits.max_bucket_candidates {
+            Some(max) if candidates > max => {
+                self.degrade(rule);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn degraded_rules_are_recorded_once() {
+        let budget = IterationBudget::default();
+        assert_eq!(budget.derivations(RuleName::Proximity, &[1, 2, 3]), &[1, 2, 3]);
+        assert!(!budget.too_many_candidates(RuleName::Attribute, u64::MAX));
+        assert!(!budget.too_many_pairs(usize::MAX));
+        assert!(budget.degraded_by().is_empty());
+
+        let budget = IterationBudget::new(SearchLimits {
+            max_word_derivations: Some(2),
+            max_bucket_candidates: Some(10),
+            ..Default::default()
+        });
+        assert_eq!(budget.derivations(RuleName::Proximity, &[1, 2]), &[1, 2]);
+        assert!(!budget.too_many_candidates(RuleName::Attribute, 10));
+        assert!(budget.degraded_by().is_empty());
+
+        assert_eq!(budget.derivations(RuleName::Proximity, &[1, 2, 3]), &[1, 2]);
+        assert!(budget.too_many_candidates(RuleName::Attribute, 11));
+        assert_eq!(budget.derivations(RuleName::Proximity, &[1, 2, 3]), &[1, 2]);
+        assert!(budget.is_degraded(RuleName::Attribute));
+        assert_eq!(budget.degraded_by(), vec![RuleName::Proximity, RuleName::Attribute]);
+    }
+}
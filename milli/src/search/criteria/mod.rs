@@ -1,28 +1,45 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
 
+use heed::types::Str;
+use heed::Database;
 use roaring::RoaringBitmap;
 
+pub use self::arena::SearchArena;
 use self::asc_desc::AscDesc;
 use self::attribute::Attribute;
+pub use self::budget::TimeBudget;
 use self::exactness::Exactness;
 use self::initial::Initial;
+pub use self::limits::{IterationBudget, RuleName, SearchLimits};
 use self::proximity::Proximity;
 use self::r#final::Final;
+use self::random::Random;
 use self::typo::Typo;
 use self::words::Words;
 use super::query_tree::{Operation, PrimitiveQueryPart, Query, QueryKind};
+use crate::index::db_name;
+use crate::proximity::{word_pair_proximity_docids_from_positions, ProximityPrecision};
 use crate::search::criteria::geo::Geo;
-use crate::search::{word_derivations, Distinct, WordDerivationsCache};
-use crate::{AscDesc as AscDescName, DocumentId, FieldId, Index, Member, Result};
+use crate::search::{word_derivations, DocidsCache, WordDerivationsCache};
+use crate::{
+    absolute_from_relative_position, relative_from_absolute_position, AscDesc as AscDescName,
+    DocumentId, FieldId, Index, Member, Result, RoaringBitmapCodec, SearchableContext,
+};
 
+mod arena;
 mod asc_desc;
 mod attribute;
+mod budget;
 mod exactness;
 pub mod r#final;
 mod geo;
 mod initial;
+mod limits;
 mod proximity;
+mod random;
 mod typo;
 mod words;
 
@@ -42,12 +59,149 @@ pub struct CriterionResult {
     filtered_candidates: Option<RoaringBitmap>,
     /// Candidates that comes from the current bucket of the initial criterion.
     bucket_candidates: Option<RoaringBitmap>,
+    /// The details of the buckets the candidates come from, one per ranking criterion.
+    score_details: ScoreDetails,
+}
+
+/// The details of the buckets a document has been returned from,
+/// one per ranking rule, from the first ranking rule to the last one.
+pub type ScoreDetails = Vec<ScoreDetail>;
+
+/// The bucket a document has been returned from by a ranking rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreDetail {
+    /// The position of the bucket among the buckets returned by the ranking rule
+    /// for the same bucket of the previous ranking rule, zero being the best one.
+    pub bucket: u32,
+    pub rule: RankingRuleScore,
+}
+
+/// The ranking rule that returned a bucket along with what it knows about this bucket.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RankingRuleScore {
+    /// The maximum number of query words matched by the documents.
+    Words {
+        matching_words: u32,
+    },
+    /// The number of typos in the words matched by the documents.
+    Typo {
+        typos: u8,
+    },
+    /// The proximity between the words matched by the documents.
+    Proximity {
+        proximity: u8,
+    },
+    Attribute,
+    Exactness,
+    /// The facet value the documents are sorted by, `None` for the documents without one.
+    Sort {
+        field_name: String,
+        ascending: bool,
+        value: Option<SortValue>,
+    },
+    /// The points are the ones the distance is measured from, the nearest one is used.
+    GeoSort {
+        points: Vec<[f64; 2]>,
+        ascending: bool,
+    },
+    /// The documents are returned one by one in the random order of the seed.
+    Random {
+        seed: u64,
+    },
+}
+
+/// A facet value used to sort the documents.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SortValue {
+    Number(f64),
+    String(String),
+}
+
+/// Keeps the score details of the current bucket of the parent criterion
+/// and numbers the buckets a criterion returns for it.
+#[derive(Debug, Default)]
+struct BucketScores {
+    parent: ScoreDetails,
+    bucket: u32,
+}
+
+impl BucketScores {
+    /// Starts numbering the buckets returned for a new bucket of the parent criterion.
+    fn reset(&mut self, parent: ScoreDetails) {
+        self.parent = parent;
+        self.bucket = 0;
+    }
+
+    /// Returns the score details of the next bucket returned by the criterion.
+    fn next(&mut self, rule: RankingRuleScore) -> ScoreDetails {
+        let mut score_details = self.parent.clone();
+        score_details.push(ScoreDetail { bucket: self.bucket, rule });
+        self.bucket += 1;
+        score_details
+    }
+
+    /// Numbers the next bucket as the given one, when the previous ones were skipped.
+    fn skip_to(&mut self, bucket: u32) {
+        self.bucket = bucket;
+    }
+}
+
+/// The bucket a document has been returned from by a ranking rule, as stored in the
+/// continuation of a search to resume the ranking after this document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BucketPosition {
+    pub bucket: u32,
+    /// The facet value of the bucket when the ranking rule is a sort, `None` otherwise
+    /// and for the bucket of the documents without value.
+    pub sort_value: Option<SortValue>,
+}
+
+impl BucketPosition {
+    /// Returns the position of the buckets of the given score details.
+    pub fn from_score_details(score_details: &[ScoreDetail]) -> Vec<BucketPosition> {
+        score_details
+            .iter()
+            .map(|detail| {
+                let sort_value = match &detail.rule {
+                    RankingRuleScore::Sort { value, .. } => value.clone(),
+                    _ => None,
+                };
+                BucketPosition { bucket: detail.bucket, sort_value }
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub struct CriterionParameters<'a> {
     wdcache: &'a mut WordDerivationsCache,
     excluded_candidates: &'a RoaringBitmap,
+    /// The buckets of the last document of the previous page when the search is continued.
+    position: Option<&'a [BucketPosition]>,
+}
+
+impl CriterionParameters<'_> {
+    /// Whether the bucket with these score details is ranked before the position the search
+    /// is continued from. Its documents were all returned by the previous pages, the criteria
+    /// return it as is instead of splitting it into their own buckets.
+    fn is_skipped(&self, score_details: &[ScoreDetail]) -> bool {
+        let position = match self.position {
+            Some(position) => position,
+            None => return false,
+        };
+        let buckets = score_details.iter().map(|detail| detail.bucket);
+        let position_buckets = position.iter().map(|position| position.bucket);
+        buckets.lt(position_buckets.take(score_details.len()))
+    }
+
+    /// Returns the position of the bucket of the ranking rule that is given these score details
+    /// for its parent bucket, when the parent bucket is the one the search is continued from.
+    fn resumed_bucket(&self, score_details: &[ScoreDetail]) -> Option<&BucketPosition> {
+        let position = self.position?;
+        let is_parent = score_details.len() < position.len()
+            && score_details.iter().zip(position).all(|(d, p)| d.bucket == p.bucket);
+        is_parent.then(|| &position[score_details.len()])
+    }
 }
 
 /// Either a set of candidates that defines the candidates
@@ -70,7 +224,6 @@ pub trait Context<'c> {
     fn word_docids(&self, word: &str) -> heed::Result<Option<RoaringBitmap>>;
     fn exact_word_docids(&self, word: &str) -> heed::Result<Option<RoaringBitmap>>;
     fn word_prefix_docids(&self, word: &str) -> heed::Result<Option<RoaringBitmap>>;
-    fn exact_word_prefix_docids(&self, word: &str) -> heed::Result<Option<RoaringBitmap>>;
 
     fn word_pair_proximity_docids(
         &self,
@@ -109,6 +262,18 @@ pub trait Context<'c> {
         word_count: u8,
     ) -> heed::Result<Option<RoaringBitmap>>;
     fn word_position_docids(&self, word: &str, pos: u32) -> heed::Result<Option<RoaringBitmap>>;
+    /// The maximum proximity stored in the word pair proximity databases.
+    fn max_proximity(&self) -> u8;
+    /// How precisely the proximity criterion measures the distance between the query words.
+    fn proximity_precision(&self) -> ProximityPrecision;
+    /// The bitmaps the criteria reuse from one bucket to the next.
+    fn arena(&self) -> &SearchArena;
+    /// The deadline after which the criteria stop returning buckets.
+    fn time_budget(&self) -> &TimeBudget;
+    /// The caps on the work of the proximity and attribute criteria.
+    fn iteration_budget(&self) -> &IterationBudget;
+    /// The number of positions allowed between the words of the phrases of the query.
+    fn phrase_slop(&self) -> u8;
 }
 
 pub struct CriteriaBuilder<'t> {
@@ -116,6 +281,16 @@ pub struct CriteriaBuilder<'t> {
     index: &'t Index,
     words_fst: fst::Set<Cow<'t, [u8]>>,
     words_prefixes_fst: fst::Set<Cow<'t, [u8]>>,
+    /// The settings of the index read by the criteria.
+    context: Arc<SearchableContext>,
+    /// The fields the search is restricted to, all the searchable fields if `None`.
+    restricted_fields_ids: Option<Vec<FieldId>>,
+    /// The docids cache of the index along with the update id seen by the transaction.
+    docids_cache: Option<(Arc<DocidsCache>, u64)>,
+    arena: SearchArena,
+    time_budget: TimeBudget,
+    iteration_budget: IterationBudget,
+    phrase_slop: u8,
 }
 
 /// Return the docids for the following word pairs and proximities using [`Context::word_pair_proximity_docids`].
@@ -186,19 +361,20 @@ impl<'c> Context<'c> for CriteriaBuilder<'c> {
     }
 
     fn word_docids(&self, word: &str) -> heed::Result<Option<RoaringBitmap>> {
-        self.index.word_docids.get(self.rtxn, &word)
+        let docids = self.cached_docids(db_name::WORD_DOCIDS, self.index.word_docids, word)?;
+        self.restrict(docids, &[(word, false)])
     }
 
     fn exact_word_docids(&self, word: &str) -> heed::Result<Option<RoaringBitmap>> {
-        self.index.exact_word_docids.get(self.rtxn, &word)
+        let database = self.index.exact_word_docids;
+        let docids = self.cached_docids(db_name::EXACT_WORD_DOCIDS, database, word)?;
+        self.restrict(docids, &[(word, false)])
     }
 
     fn word_prefix_docids(&self, word: &str) -> heed::Result<Option<RoaringBitmap>> {
-        self.index.word_prefix_docids.get(self.rtxn, &word)
-    }
-
-    fn exact_word_prefix_docids(&self, word: &str) -> heed::Result<Option<RoaringBitmap>> {
-        self.index.exact_word_prefix_docids.get(self.rtxn, &word)
+        let database = self.index.word_prefix_docids;
+        let docids = self.cached_docids(db_name::WORD_PREFIX_DOCIDS, database, word)?;
+        self.restrict(docids, &[(word, true)])
     }
 
     fn word_pair_proximity_docids(
@@ -207,7 +383,25 @@ impl<'c> Context<'c> for CriteriaBuilder<'c> {
         right: &str,
         proximity: u8,
     ) -> heed::Result<Option<RoaringBitmap>> {
-        self.index.word_pair_proximity_docids.get(self.rtxn, &(proximity, left, right))
+        let docids = match self.context.proximity_precision {
+            ProximityPrecision::ByWord => {
+                self.index.word_pair_proximity_docids.get(self.rtxn, &(proximity, left, right))?
+            }
+            // The word pair proximity database is not built, the phrases
+            // are still resolved with the positions of their words.
+            ProximityPrecision::ByAttribute => {
+                let docids = word_pair_proximity_docids_from_positions(
+                    self.index,
+                    self.rtxn,
+                    self.context.position_bucketing,
+                    left,
+                    right,
+                    proximity,
+                )?;
+                Some(docids).filter(|docids| !docids.is_empty())
+            }
+        };
+        self.restrict(docids, &[(left, false), (right, false)])
     }
 
     fn word_prefix_pair_proximity_docids(
@@ -216,7 +410,11 @@ impl<'c> Context<'c> for CriteriaBuilder<'c> {
         prefix: &str,
         proximity: u8,
     ) -> heed::Result<Option<RoaringBitmap>> {
-        self.index.word_prefix_pair_proximity_docids.get(self.rtxn, &(proximity, left, prefix))
+        let docids = self
+            .index
+            .word_prefix_pair_proximity_docids
+            .get(self.rtxn, &(proximity, left, prefix))?;
+        self.restrict(docids, &[(left, false), (prefix, true)])
     }
     fn prefix_word_pair_proximity_docids(
         &self,
@@ -224,7 +422,11 @@ impl<'c> Context<'c> for CriteriaBuilder<'c> {
         right: &str,
         proximity: u8,
     ) -> heed::Result<Option<RoaringBitmap>> {
-        self.index.prefix_word_pair_proximity_docids.get(self.rtxn, &(proximity, prefix, right))
+        let docids = self
+            .index
+            .prefix_word_pair_proximity_docids
+            .get(self.rtxn, &(proximity, prefix, right))?;
+        self.restrict(docids, &[(prefix, true), (right, false)])
     }
 
     fn words_fst<'t>(&self) -> &'t fst::Set<Cow<[u8]>> {
@@ -241,7 +443,16 @@ impl<'c> Context<'c> for CriteriaBuilder<'c> {
     ) -> heed::Result<HashMap<String, RoaringBitmap>> {
         let mut words_positions = HashMap::new();
         for result in self.index.docid_word_positions.prefix_iter(self.rtxn, &(docid, ""))? {
-            let ((_, word), positions) = result?;
+            let ((_, word), mut positions) = result?;
+            if let Some(fields_ids) = &self.restricted_fields_ids {
+                positions = positions
+                    .into_iter()
+                    .filter(|&pos| fields_ids.contains(&relative_from_absolute_position(pos).0))
+                    .collect();
+                if positions.is_empty() {
+                    continue;
+                }
+            }
             words_positions.insert(word.to_string(), positions);
         }
         Ok(words_positions)
@@ -265,18 +476,25 @@ impl<'c> Context<'c> for CriteriaBuilder<'c> {
             false => self.index.word_position_docids,
         };
 
-        Ok(Box::new(db.range(self.rtxn, &range)?))
+        let iter = db.range(self.rtxn, &range)?;
+        match self.restricted_fields_ids.clone() {
+            Some(fields_ids) => Ok(Box::new(iter.filter(move |result| match result {
+                Ok(((_, pos), _)) => fields_ids.contains(&relative_from_absolute_position(*pos).0),
+                Err(_) => true,
+            }))),
+            None => Ok(Box::new(iter)),
+        }
     }
 
     fn synonyms(&self, word: &str) -> heed::Result<Option<Vec<Vec<String>>>> {
-        self.index.words_synonyms(self.rtxn, &[word])
+        Ok(self.context.words_synonyms(&[word]))
     }
 
     fn searchable_fields_ids(&self) -> Result<Vec<FieldId>> {
-        match self.index.searchable_fields_ids(self.rtxn)? {
-            Some(searchable_fields_ids) => Ok(searchable_fields_ids),
-            None => Ok(self.index.fields_ids_map(self.rtxn)?.ids().collect()),
+        if let Some(fields_ids) = &self.restricted_fields_ids {
+            return Ok(fields_ids.clone());
         }
+        Ok(self.context.searchable_fields_ids.clone())
     }
 
     fn field_id_word_count_docids(
@@ -289,39 +507,156 @@ impl<'c> Context<'c> for CriteriaBuilder<'c> {
     }
 
     fn word_position_docids(&self, word: &str, pos: u32) -> heed::Result<Option<RoaringBitmap>> {
-        let key = (word, pos);
+        if let Some(fields_ids) = &self.restricted_fields_ids {
+            if !fields_ids.contains(&relative_from_absolute_position(pos).0) {
+                return Ok(None);
+            }
+        }
+        // The position is looked up in the bucket it is stored in.
+        let key = (word, self.context.position_bucketing.bucket_absolute(pos));
         self.index.word_position_docids.get(self.rtxn, &key)
     }
+
+    fn max_proximity(&self) -> u8 {
+        self.context.max_proximity
+    }
+
+    fn proximity_precision(&self) -> ProximityPrecision {
+        self.context.proximity_precision
+    }
+
+    fn arena(&self) -> &SearchArena {
+        &self.arena
+    }
+
+    fn time_budget(&self) -> &TimeBudget {
+        &self.time_budget
+    }
+
+    fn iteration_budget(&self) -> &IterationBudget {
+        &self.iteration_budget
+    }
+
+    fn phrase_slop(&self) -> u8 {
+        self.phrase_slop
+    }
 }
 
 impl<'t> CriteriaBuilder<'t> {
-    pub fn new(rtxn: &'t heed::RoTxn<'t>, index: &'t Index) -> Result<Self> {
+    /// Creates the criteria of a search, their intermediate bitmaps
+    /// are reused between the buckets if `bitmap_arena` is `true`.
+    pub fn new(rtxn: &'t heed::RoTxn<'t>, index: &'t Index, bitmap_arena: bool) -> Result<Self> {
         let words_fst = index.words_fst(rtxn)?;
         let words_prefixes_fst = index.words_prefixes_fst(rtxn)?;
-        Ok(Self { rtxn, index, words_fst, words_prefixes_fst })
+        let context = index.searchable_context(rtxn)?;
+        let docids_cache = match &index.docids_cache {
+            Some(cache) => Some((cache.clone(), context.update_id)),
+            None => None,
+        };
+        Ok(Self {
+            rtxn,
+            index,
+            words_fst,
+            words_prefixes_fst,
+            context,
+            restricted_fields_ids: None,
+            docids_cache,
+            arena: SearchArena::new(bitmap_arena),
+            time_budget: TimeBudget::default(),
+            iteration_budget: IterationBudget::default(),
+            phrase_slop: 0,
+        })
+    }
+
+    /// Reads the docids of the word from the docids cache of the index when there is one.
+    fn cached_docids(
+        &self,
+        database_name: &'static str,
+        database: Database<Str, RoaringBitmapCodec>,
+        word: &str,
+    ) -> heed::Result<Option<RoaringBitmap>> {
+        match &self.docids_cache {
+            Some((cache, update_id)) => {
+                cache.get_or_fetch(*update_id, database_name, word.as_bytes(), || {
+                    database.get(self.rtxn, word)
+                })
+            }
+            None => database.get(self.rtxn, word),
+        }
+    }
+
+    /// Stops returning buckets once the deadline is exceeded.
+    pub fn deadline(&mut self, deadline: Instant) -> &mut Self {
+        self.time_budget = TimeBudget::new(Some(deadline));
+        self
+    }
+
+    /// Caps the work of the proximity and attribute criteria.
+    pub fn limits(&mut self, limits: SearchLimits) -> &mut Self {
+        self.iteration_budget = IterationBudget::new(limits);
+        self
+    }
+
+    /// Allows up to `slop` positions between the words of the phrases, in total.
+    pub fn phrase_slop(&mut self, slop: u8) -> &mut Self {
+        self.phrase_slop = slop;
+        self
+    }
+
+    /// Restricts the words matched by the search to the ones found in the given fields.
+    pub fn restricted_fields_ids(&mut self, fields_ids: Vec<FieldId>) -> &mut Self {
+        self.restricted_fields_ids = Some(fields_ids);
+        self
+    }
+
+    /// Keeps the documents in which the given words, or prefixes, appear in the fields
+    /// the search is restricted to, or all of them when there is no restriction.
+    fn restrict(
+        &self,
+        docids: Option<RoaringBitmap>,
+        words: &[(&str, bool)],
+    ) -> heed::Result<Option<RoaringBitmap>> {
+        let (fields_ids, mut docids) = match (&self.restricted_fields_ids, docids) {
+            (Some(fields_ids), Some(docids)) => (fields_ids, docids),
+            (_, docids) => return Ok(docids),
+        };
+
+        for &(word, is_prefix) in words {
+            let db = match is_prefix {
+                true => self.index.word_prefix_position_docids,
+                false => self.index.word_position_docids,
+            };
+            let mut fields_docids = RoaringBitmap::new();
+            for &field_id in fields_ids {
+                let left = (word, absolute_from_relative_position(field_id, 0));
+                let right = (word, absolute_from_relative_position(field_id, u16::MAX));
+                for result in db.range(self.rtxn, &(left..=right))? {
+                    let (_, field_docids) = result?;
+                    fields_docids |= field_docids;
+                }
+            }
+            docids &= fields_docids;
+        }
+
+        Ok(Some(docids))
     }
 
-    pub fn build<D: 't + Distinct>(
+    pub fn build(
         &'t self,
         query_tree: Option<Operation>,
         primitive_query: Option<Vec<PrimitiveQueryPart>>,
         filtered_candidates: Option<RoaringBitmap>,
         sort_criteria: Option<Vec<AscDescName>>,
         exhaustive_number_hits: bool,
-        distinct: Option<D>,
     ) -> Result<Final<'t>> {
         use crate::criterion::Criterion as Name;
 
         let primitive_query = primitive_query.unwrap_or_default();
 
-        let mut criterion = Box::new(Initial::new(
-            self,
-            query_tree,
-            filtered_candidates,
-            exhaustive_number_hits,
-            distinct,
-        )) as Box<dyn Criterion>;
-        for name in self.index.criteria(&self.rtxn)? {
+        let mut criterion =
+            Box::new(Initial::new(self, query_tree, filtered_candidates, exhaustive_number_hits))
+                as Box<dyn Criterion>;
+        for name in self.context.criteria.clone() {
             criterion = match name {
                 Name::Words => Box::new(Words::new(self, criterion)),
                 Name::Typo => Box::new(Typo::new(self, criterion)),
@@ -329,30 +664,74 @@ impl<'t> CriteriaBuilder<'t> {
                     Some(ref sort_criteria) => {
                         for asc_desc in sort_criteria {
                             criterion = match asc_desc {
-                                AscDescName::Asc(Member::Field(field)) => Box::new(AscDesc::asc(
+                                AscDescName::Asc(
+                                    Member::Field(field) | Member::CollatedField(field, _),
+                                ) => Box::new(
+                                    AscDesc::asc(
+                                        self,
+                                        &self.index,
+                                        &self.rtxn,
+                                        criterion,
+                                        field.to_string(),
+                                    )?
+                                    .with_locale(asc_desc.member().locale()),
+                                ),
+                                AscDescName::Desc(
+                                    Member::Field(field) | Member::CollatedField(field, _),
+                                ) => Box::new(
+                                    AscDesc::desc(
+                                        self,
+                                        &self.index,
+                                        &self.rtxn,
+                                        criterion,
+                                        field.to_string(),
+                                    )?
+                                    .with_locale(asc_desc.member().locale()),
+                                ),
+                                AscDescName::Asc(Member::Geo(point)) => Box::new(Geo::asc(
+                                    self,
                                     &self.index,
                                     &self.rtxn,
                                     criterion,
-                                    field.to_string(),
+                                    vec![*point],
                                 )?),
-                                AscDescName::Desc(Member::Field(field)) => Box::new(AscDesc::desc(
+                                AscDescName::Asc(Member::GeoPoints(points)) => Box::new(Geo::asc(
+                                    self,
                                     &self.index,
                                     &self.rtxn,
                                     criterion,
-                                    field.to_string(),
+                                    points.clone(),
                                 )?),
-                                AscDescName::Asc(Member::Geo(point)) => Box::new(Geo::asc(
+                                AscDescName::Desc(Member::Geo(point)) => Box::new(Geo::desc(
+                                    self,
                                     &self.index,
                                     &self.rtxn,
                                     criterion,
-                                    point.clone(),
+                                    vec![*point],
                                 )?),
-                                AscDescName::Desc(Member::Geo(point)) => Box::new(Geo::desc(
+                                AscDescName::Desc(Member::GeoPoints(points)) => {
+                                    Box::new(Geo::desc(
+                                        self,
+                                        &self.index,
+                                        &self.rtxn,
+                                        criterion,
+                                        points.clone(),
+                                    )?)
+                                }
+                                AscDescName::Asc(Member::Random(seed)) => Box::new(Random::asc(
+                                    self,
                                     &self.index,
                                     &self.rtxn,
                                     criterion,
-                                    point.clone(),
-                                )?),
+                                    *seed,
+                                )),
+                                AscDescName::Desc(Member::Random(seed)) => Box::new(Random::desc(
+                                    self,
+                                    &self.index,
+                                    &self.rtxn,
+                                    criterion,
+                                    *seed,
+                                )),
                             };
                         }
                         criterion
@@ -363,10 +742,10 @@ impl<'t> CriteriaBuilder<'t> {
                 Name::Attribute => Box::new(Attribute::new(self, criterion)),
                 Name::Exactness => Box::new(Exactness::new(self, criterion, &primitive_query)?),
                 Name::Asc(field) => {
-                    Box::new(AscDesc::asc(&self.index, &self.rtxn, criterion, field)?)
+                    Box::new(AscDesc::asc(self, &self.index, &self.rtxn, criterion, field)?)
                 }
                 Name::Desc(field) => {
-                    Box::new(AscDesc::desc(&self.index, &self.rtxn, criterion, field)?)
+                    Box::new(AscDesc::desc(self, &self.index, &self.rtxn, criterion, field)?)
                 }
             };
         }
@@ -424,7 +803,33 @@ pub fn resolve_query_tree(
     resolve_operation(ctx, query_tree, wdcache)
 }
 
+/// Returns the documents that contain any of the negative words or phrases,
+/// the words are only matched exactly, without prefix nor typo derivations.
+pub fn resolve_negative_query(
+    ctx: &dyn Context,
+    negative_query: &[PrimitiveQueryPart],
+) -> Result<RoaringBitmap> {
+    let mut docids = RoaringBitmap::new();
+    for part in negative_query {
+        match part {
+            PrimitiveQueryPart::Word(word, _) => {
+                docids |= ctx.word_docids(word)?.unwrap_or_default();
+                docids |= ctx.exact_word_docids(word)?.unwrap_or_default();
+            }
+            PrimitiveQueryPart::Phrase(words) => docids |= resolve_phrase(ctx, words)?,
+        }
+    }
+    Ok(docids)
+}
+
 pub fn resolve_phrase(ctx: &dyn Context, phrase: &[String]) -> Result<RoaringBitmap> {
+    match ctx.phrase_slop() {
+        0 => resolve_exact_phrase(ctx, phrase),
+        slop => resolve_phrase_with_slop(ctx, phrase, slop),
+    }
+}
+
+fn resolve_exact_phrase(ctx: &dyn Context, phrase: &[String]) -> Result<RoaringBitmap> {
     let mut candidates = RoaringBitmap::new();
     let mut first_iter = true;
     let winsize = phrase.len().min(3);
@@ -475,6 +880,63 @@ pub fn resolve_phrase(ctx: &dyn Context, phrase: &[String]) -> Result<RoaringBit
     Ok(candidates)
 }
 
+/// Returns the documents in which the words of the phrase appear in order in the same
+/// attribute, with at most `slop` other positions between the first and the last word.
+fn resolve_phrase_with_slop(
+    ctx: &dyn Context,
+    phrase: &[String],
+    slop: u8,
+) -> Result<RoaringBitmap> {
+    let mut candidates: Option<RoaringBitmap> = None;
+    for word in phrase {
+        let mut docids = ctx.word_docids(word)?.unwrap_or_default();
+        docids |= ctx.exact_word_docids(word)?.unwrap_or_default();
+        match candidates.as_mut() {
+            Some(candidates) => *candidates &= docids,
+            None => candidates = Some(docids),
+        }
+    }
+
+    let candidates = candidates.unwrap_or_default();
+    if phrase.len() < 2 {
+        return Ok(candidates);
+    }
+
+    let mut docids = RoaringBitmap::new();
+    for docid in candidates {
+        let words_positions = ctx.docid_words_positions(docid)?;
+        let positions: Option<Vec<_>> =
+            phrase.iter().map(|word| words_positions.get(word.as_str())).collect();
+        let (first, others) = match positions.as_ref().and_then(|p| p.split_first()) {
+            Some(split) => split,
+            None => continue,
+        };
+        if first.iter().any(|position| phrase_follows(others, position, slop as u32)) {
+            docids.insert(docid);
+        }
+    }
+
+    Ok(docids)
+}
+
+/// Returns whether the next words of a phrase can be found after the given position,
+/// in the same attribute and with at most `slop` other positions between them.
+fn phrase_follows(words_positions: &[&RoaringBitmap], previous: u32, slop: u32) -> bool {
+    let (positions, others) = match words_positions.split_first() {
+        Some(split) => split,
+        None => return true,
+    };
+
+    let (field_id, _) = relative_from_absolute_position(previous);
+    let max = previous.saturating_add(slop + 1);
+    positions
+        .iter()
+        .skip_while(|&position| position <= previous)
+        .take_while(|&position| position <= max)
+        .filter(|&position| relative_from_absolute_position(position).0 == field_id)
+        .any(|position| phrase_follows(others, position, slop - (position - previous - 1)))
+}
+
 fn all_word_pair_overall_proximity_docids<T: AsRef<str>, U: AsRef<str>>(
     ctx: &dyn Context,
     left_words: &[(T, u8)],
@@ -500,43 +962,33 @@ fn query_docids(
 ) -> Result<RoaringBitmap> {
     match &query.kind {
         QueryKind::Exact { word, original_typo } => {
-            if query.prefix && ctx.in_prefix_cache(&word) {
-                let mut docids = ctx.word_prefix_docids(&word)?.unwrap_or_default();
-                // only add the exact docids if the word hasn't been derived
-                if *original_typo == 0 {
-                    docids |= ctx.exact_word_prefix_docids(&word)?.unwrap_or_default();
-                }
-                Ok(docids)
+            let mut docids = if query.prefix && ctx.in_prefix_cache(&word) {
+                ctx.word_prefix_docids(&word)?.unwrap_or_default()
             } else if query.prefix {
                 let words = word_derivations(&word, true, 0, ctx.words_fst(), wdcache)?;
                 let mut docids = RoaringBitmap::new();
                 for (word, _typo) in words {
                     docids |= ctx.word_docids(&word)?.unwrap_or_default();
-                    // only add the exact docids if the word hasn't been derived
-                    if *original_typo == 0 {
-                        docids |= ctx.exact_word_docids(&word)?.unwrap_or_default();
-                    }
                 }
-                Ok(docids)
+                docids
             } else {
-                let mut docids = ctx.word_docids(&word)?.unwrap_or_default();
-                // only add the exact docids if the word hasn't been derived
-                if *original_typo == 0 {
-                    docids |= ctx.exact_word_docids(&word)?.unwrap_or_default();
-                }
-                Ok(docids)
+                ctx.word_docids(&word)?.unwrap_or_default()
+            };
+            // The words of the exact attributes only ever match the exact query word,
+            // and only if it hasn't been derived.
+            if *original_typo == 0 {
+                docids |= ctx.exact_word_docids(&word)?.unwrap_or_default();
             }
+            Ok(docids)
         }
         QueryKind::Tolerant { typo, word } => {
             let words = word_derivations(&word, query.prefix, *typo, ctx.words_fst(), wdcache)?;
             let mut docids = RoaringBitmap::new();
-            for (word, typo) in words {
-                let mut current_docids = ctx.word_docids(&word)?.unwrap_or_default();
-                if *typo == 0 {
-                    current_docids |= ctx.exact_word_docids(&word)?.unwrap_or_default()
-                }
-                docids |= current_docids;
+            for (word, _typo) in words {
+                docids |= ctx.word_docids(&word)?.unwrap_or_default();
             }
+            // The words of the exact attributes only ever match the exact query word.
+            docids |= ctx.exact_word_docids(&word)?.unwrap_or_default();
             Ok(docids)
         }
     }
@@ -549,13 +1001,16 @@ fn query_pair_proximity_docids(
     proximity: u8,
     wdcache: &mut WordDerivationsCache,
 ) -> Result<RoaringBitmap> {
-    if proximity >= 8 {
+    // The pairs of words farther away than the maximum proximity are not stored,
+    // the documents containing both words are returned instead.
+    if proximity > ctx.max_proximity() {
         let mut candidates = query_docids(ctx, left, wdcache)?;
         let right_candidates = query_docids(ctx, right, wdcache)?;
         candidates &= right_candidates;
         return Ok(candidates);
     }
 
+    let budget = ctx.iteration_budget();
     let prefix = right.prefix;
     match (&left.kind, &right.kind) {
         (QueryKind::Exact { word: left, .. }, QueryKind::Exact { word: right, .. }) => {
@@ -568,7 +1023,10 @@ fn query_pair_proximity_docids(
                 )? {
                     Some(docids) => Ok(docids),
                     None => {
-                        let r_words = word_derivations(&right, true, 0, ctx.words_fst(), wdcache)?;
+                        let r_words = budget.derivations(
+                            RuleName::Proximity,
+                            word_derivations(&right, true, 0, ctx.words_fst(), wdcache)?,
+                        );
                         all_word_pair_overall_proximity_docids(
                             ctx,
                             &[(left, 0)],
@@ -584,8 +1042,12 @@ fn query_pair_proximity_docids(
             }
         }
         (QueryKind::Tolerant { typo, word: left }, QueryKind::Exact { word: right, .. }) => {
-            let l_words =
-                word_derivations(&left, false, *typo, ctx.words_fst(), wdcache)?.to_owned();
+            let l_words = budget
+                .derivations(
+                    RuleName::Proximity,
+                    word_derivations(&left, false, *typo, ctx.words_fst(), wdcache)?,
+                )
+                .to_owned();
             if prefix {
                 let mut docids = RoaringBitmap::new();
                 for (left, _) in l_words {
@@ -597,8 +1059,10 @@ fn query_pair_proximity_docids(
                     )? {
                         Some(docids) => Ok(docids),
                         None => {
-                            let r_words =
-                                word_derivations(&right, true, 0, ctx.words_fst(), wdcache)?;
+                            let r_words = budget.derivations(
+                                RuleName::Proximity,
+                                word_derivations(&right, true, 0, ctx.words_fst(), wdcache)?,
+                            );
                             all_word_pair_overall_proximity_docids(
                                 ctx,
                                 &[(left, 0)],
@@ -615,16 +1079,26 @@ fn query_pair_proximity_docids(
             }
         }
         (QueryKind::Exact { word: left, .. }, QueryKind::Tolerant { typo, word: right }) => {
-            let r_words = word_derivations(&right, prefix, *typo, ctx.words_fst(), wdcache)?;
+            let r_words = budget.derivations(
+                RuleName::Proximity,
+                word_derivations(&right, prefix, *typo, ctx.words_fst(), wdcache)?,
+            );
             all_word_pair_overall_proximity_docids(ctx, &[(left, 0)], &r_words, proximity)
         }
         (
             QueryKind::Tolerant { typo: l_typo, word: left },
             QueryKind::Tolerant { typo: r_typo, word: right },
         ) => {
-            let l_words =
-                word_derivations(&left, false, *l_typo, ctx.words_fst(), wdcache)?.to_owned();
-            let r_words = word_derivations(&right, prefix, *r_typo, ctx.words_fst(), wdcache)?;
+            let l_words = budget
+                .derivations(
+                    RuleName::Proximity,
+                    word_derivations(&left, false, *l_typo, ctx.words_fst(), wdcache)?,
+                )
+                .to_owned();
+            let r_words = budget.derivations(
+                RuleName::Proximity,
+                word_derivations(&right, prefix, *r_typo, ctx.words_fst(), wdcache)?,
+            );
             all_word_pair_overall_proximity_docids(ctx, &l_words, &r_words, proximity)
         }
     }
@@ -649,11 +1123,13 @@ pub mod test {
         word_docids: HashMap<String, RoaringBitmap>,
         exact_word_docids: HashMap<String, RoaringBitmap>,
         word_prefix_docids: HashMap<String, RoaringBitmap>,
-        exact_word_prefix_docids: HashMap<String, RoaringBitmap>,
         word_pair_proximity_docids: HashMap<(String, String, i32), RoaringBitmap>,
         word_prefix_pair_proximity_docids: HashMap<(String, String, i32), RoaringBitmap>,
         prefix_word_pair_proximity_docids: HashMap<(String, String, i32), RoaringBitmap>,
         docid_words: HashMap<u32, Vec<String>>,
+        arena: SearchArena,
+        time_budget: TimeBudget,
+        iteration_budget: IterationBudget,
     }
 
     impl<'c> Context<'c> for TestContext<'c> {
@@ -673,10 +1149,6 @@ pub mod test {
             Ok(self.word_prefix_docids.get(&word.to_string()).cloned())
         }
 
-        fn exact_word_prefix_docids(&self, word: &str) -> heed::Result<Option<RoaringBitmap>> {
-            Ok(self.exact_word_prefix_docids.get(&word.to_string()).cloned())
-        }
-
         fn word_pair_proximity_docids(
             &self,
             left: &str,
@@ -765,6 +1237,30 @@ pub mod test {
         ) -> heed::Result<Option<RoaringBitmap>> {
             todo!()
         }
+
+        fn max_proximity(&self) -> u8 {
+            crate::index::DEFAULT_MAX_PROXIMITY
+        }
+
+        fn proximity_precision(&self) -> ProximityPrecision {
+            ProximityPrecision::ByWord
+        }
+
+        fn arena(&self) -> &SearchArena {
+            &self.arena
+        }
+
+        fn time_budget(&self) -> &TimeBudget {
+            &self.time_budget
+        }
+
+        fn iteration_budget(&self) -> &IterationBudget {
+            &self.iteration_budget
+        }
+
+        fn phrase_slop(&self) -> u8 {
+            0
+        }
     }
 
     impl<'a> Default for TestContext<'a> {
@@ -814,8 +1310,6 @@ pub mod test {
                 s("20")  => &word_docids[&s("2020")]  | &word_docids[&s("2021")],
             };
 
-            let exact_word_prefix_docids = HashMap::new();
-
             let mut word_pair_proximity_docids = HashMap::new();
             let mut word_prefix_pair_proximity_docids = HashMap::new();
             let mut prefix_word_pair_proximity_docids = HashMap::new();
@@ -879,11 +1373,13 @@ pub mod test {
                 word_docids,
                 exact_word_docids,
                 word_prefix_docids,
-                exact_word_prefix_docids,
                 word_pair_proximity_docids,
                 word_prefix_pair_proximity_docids,
                 prefix_word_pair_proximity_docids,
                 docid_words,
+                arena: SearchArena::new(true),
+                time_budget: TimeBudget::default(),
+                iteration_budget: IterationBudget::default(),
             }
         }
     }
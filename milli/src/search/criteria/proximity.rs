@@ -1,17 +1,18 @@
 use std::collections::btree_map::{self, BTreeMap};
 use std::collections::hash_map::HashMap;
-use std::mem::take;
+use std::mem;
 
 use log::debug;
 use roaring::RoaringBitmap;
 
 use super::{
-    query_docids, query_pair_proximity_docids, resolve_phrase, resolve_query_tree, Context,
-    Criterion, CriterionParameters, CriterionResult,
+    query_docids, query_pair_proximity_docids, resolve_phrase, resolve_query_tree, BucketScores,
+    Context, Criterion, CriterionParameters, CriterionResult, RankingRuleScore, RuleName,
 };
+use crate::proximity::ProximityPrecision;
 use crate::search::query_tree::{maximum_proximity, Operation, Query, QueryKind};
-use crate::search::{build_dfa, WordDerivationsCache};
-use crate::{Position, Result};
+use crate::search::{build_dfa, word_derivations, WordDerivationsCache};
+use crate::{relative_from_absolute_position, FieldId, Position, Result};
 
 type Cache = HashMap<(Operation, u8), Vec<(Query, Query, RoaringBitmap)>>;
 
@@ -29,9 +30,11 @@ pub struct Proximity<'t> {
     state: Option<(u8, Operation, RoaringBitmap)>,
     proximity: u8,
     bucket_candidates: RoaringBitmap,
+    scores: BucketScores,
     parent: Box<dyn Criterion + 't>,
     candidates_cache: Cache,
     plane_sweep_cache: Option<btree_map::IntoIter<u8, RoaringBitmap>>,
+    precision: ProximityPrecision,
 }
 
 impl<'t> Proximity<'t> {
@@ -41,9 +44,34 @@ impl<'t> Proximity<'t> {
             state: None,
             proximity: 0,
             bucket_candidates: RoaringBitmap::new(),
+            scores: BucketScores::default(),
             parent,
             candidates_cache: Cache::new(),
             plane_sweep_cache: None,
+            precision: ctx.proximity_precision(),
+        }
+    }
+
+    /// Returns the candidates as the bucket of the current proximity.
+    fn bucket(&mut self, query_tree: Operation, candidates: RoaringBitmap) -> CriterionResult {
+        let score_details =
+            self.scores.next(RankingRuleScore::Proximity { proximity: self.proximity });
+        CriterionResult {
+            query_tree: Some(query_tree),
+            candidates: Some(candidates),
+            filtered_candidates: None,
+            bucket_candidates: Some(mem::replace(
+                &mut self.bucket_candidates,
+                self.ctx.arena().take(),
+            )),
+            score_details,
+        }
+    }
+
+    /// Forgets the current bucket of the parent and gives its candidates back to the arena.
+    fn reset_state(&mut self) {
+        if let Some((_, _, allowed_candidates)) = self.state.take() {
+            self.ctx.arena().recycle(allowed_candidates);
         }
     }
 }
@@ -65,13 +93,30 @@ impl<'t> Criterion for Proximity<'t> {
             );
 
             match &mut self.state {
-                Some((max_prox, _, allowed_candidates))
+                Some((max_prox, query_tree, allowed_candidates))
                     if allowed_candidates.is_empty() || self.proximity > *max_prox =>
                 {
-                    self.state = None; // reset state
+                    // The candidates missed because of the ignored word derivations
+                    // are returned, unranked, after the ranked buckets.
+                    if !allowed_candidates.is_empty()
+                        && self.ctx.iteration_budget().is_degraded(RuleName::Proximity)
+                    {
+                        let candidates = mem::replace(allowed_candidates, self.ctx.arena().take());
+                        let query_tree = query_tree.clone();
+                        return Ok(Some(self.bucket(query_tree, candidates)));
+                    }
+                    self.reset_state();
                 }
                 Some((_, query_tree, allowed_candidates)) => {
-                    let mut new_candidates = if allowed_candidates.len() <= CANDIDATES_THRESHOLD
+                    let mut new_candidates = if self.precision == ProximityPrecision::ByAttribute {
+                        // The documents in which all the query words appear in the same attribute
+                        // are returned first, the remaining ones are returned in a second bucket.
+                        if self.proximity == 0 {
+                            resolve_attribute_candidates(self.ctx, query_tree, params.wdcache)?
+                        } else {
+                            allowed_candidates.clone()
+                        }
+                    } else if allowed_candidates.len() <= CANDIDATES_THRESHOLD
                         && self.proximity > PROXIMITY_THRESHOLD
                     {
                         if let Some(cache) = self.plane_sweep_cache.as_mut() {
@@ -81,7 +126,7 @@ impl<'t> Criterion for Proximity<'t> {
                                     candidates
                                 }
                                 None => {
-                                    self.state = None; // reset state
+                                    self.reset_state();
                                     continue;
                                 }
                             }
@@ -97,33 +142,45 @@ impl<'t> Criterion for Proximity<'t> {
                         }
                     } else {
                         // use set theory based algorithm
-                        resolve_candidates(
+                        match resolve_candidates(
                             self.ctx,
                             &query_tree,
                             self.proximity,
                             &mut self.candidates_cache,
                             params.wdcache,
-                        )?
+                        )? {
+                            Some(candidates) => candidates,
+                            None => {
+                                // Too many pairs of words to evaluate, the remaining
+                                // candidates are returned unranked in a single bucket.
+                                self.ctx.iteration_budget().degrade(RuleName::Proximity);
+                                self.candidates_cache.clear();
+                                allowed_candidates.clone()
+                            }
+                        }
                     };
 
                     new_candidates &= &*allowed_candidates;
                     *allowed_candidates -= &new_candidates;
+                    let query_tree = query_tree.clone();
+                    let bucket = self.bucket(query_tree, new_candidates);
                     self.proximity += 1;
 
-                    return Ok(Some(CriterionResult {
-                        query_tree: Some(query_tree.clone()),
-                        candidates: Some(new_candidates),
-                        filtered_candidates: None,
-                        bucket_candidates: Some(take(&mut self.bucket_candidates)),
-                    }));
+                    return Ok(Some(bucket));
                 }
                 None => match self.parent.next(params)? {
+                    // The documents of the bucket were all returned by the previous pages.
+                    Some(result) if params.is_skipped(&result.score_details) => {
+                        return Ok(Some(result));
+                    }
                     Some(CriterionResult {
                         query_tree: Some(query_tree),
                         candidates,
                         filtered_candidates,
                         bucket_candidates,
+                        score_details,
                     }) => {
+                        self.scores.reset(score_details);
                         let mut candidates = match candidates {
                             Some(candidates) => candidates,
                             None => {
@@ -133,15 +190,34 @@ impl<'t> Criterion for Proximity<'t> {
                         };
 
                         if let Some(filtered_candidates) = filtered_candidates {
-                            candidates &= filtered_candidates;
+                            candidates &= &filtered_candidates;
+                            self.ctx.arena().recycle(filtered_candidates);
                         }
 
                         match bucket_candidates {
-                            Some(bucket_candidates) => self.bucket_candidates |= bucket_candidates,
+                            Some(bucket_candidates) => {
+                                self.bucket_candidates |= &bucket_candidates;
+                                self.ctx.arena().recycle(bucket_candidates);
+                            }
                             None => self.bucket_candidates |= &candidates,
                         }
 
-                        let maximum_proximity = maximum_proximity(&query_tree);
+                        // A bucket too large to be ranked is passed through.
+                        if self
+                            .ctx
+                            .iteration_budget()
+                            .too_many_candidates(RuleName::Proximity, candidates.len())
+                        {
+                            self.proximity = 0;
+                            return Ok(Some(self.bucket(query_tree, candidates)));
+                        }
+
+                        let maximum_proximity = match self.precision {
+                            ProximityPrecision::ByWord => maximum_proximity(&query_tree),
+                            ProximityPrecision::ByAttribute => {
+                                maximum_proximity(&query_tree).min(1)
+                            }
+                        };
                         self.state = Some((maximum_proximity as u8, query_tree, candidates));
                         self.proximity = 0;
                         self.plane_sweep_cache = None;
@@ -151,12 +227,14 @@ impl<'t> Criterion for Proximity<'t> {
                         candidates,
                         filtered_candidates,
                         bucket_candidates,
+                        score_details,
                     }) => {
                         return Ok(Some(CriterionResult {
                             query_tree: None,
                             candidates,
                             filtered_candidates,
                             bucket_candidates,
+                            score_details,
                         }));
                     }
                     None => return Ok(None),
@@ -166,24 +244,148 @@ impl<'t> Criterion for Proximity<'t> {
     }
 }
 
+/// Returns the documents in which all the words of the query tree appear in the same attribute.
+fn resolve_attribute_candidates<'t>(
+    ctx: &'t dyn Context,
+    query_tree: &Operation,
+    wdcache: &mut WordDerivationsCache,
+) -> Result<RoaringBitmap> {
+    /// Returns the documents in which the operation matches, for each attribute.
+    fn resolve_operation<'t>(
+        ctx: &'t dyn Context,
+        query_tree: &Operation,
+        wdcache: &mut WordDerivationsCache,
+    ) -> Result<HashMap<FieldId, RoaringBitmap>> {
+        use Operation::{And, Or, Phrase};
+
+        match query_tree {
+            And(ops) => {
+                let mut fields_docids: Option<HashMap<_, _>> = None;
+                for op in ops {
+                    let op_fields_docids = resolve_operation(ctx, op, wdcache)?;
+                    fields_docids = Some(match fields_docids {
+                        Some(fields_docids) => {
+                            intersect_fields_docids(fields_docids, op_fields_docids)
+                        }
+                        None => op_fields_docids,
+                    });
+                }
+                Ok(fields_docids.unwrap_or_default())
+            }
+            Phrase(words) => {
+                // The words of the phrase must be next to each other,
+                // which is only possible in the same attribute.
+                let phrase_docids = resolve_phrase(ctx, words)?;
+                let mut fields_docids: Option<HashMap<_, _>> = None;
+                for word in words {
+                    let word_docids = word_fields_docids(ctx, word, false)?;
+                    fields_docids = Some(match fields_docids {
+                        Some(fields_docids) => intersect_fields_docids(fields_docids, word_docids),
+                        None => word_docids,
+                    });
+                }
+                let mut fields_docids = fields_docids.unwrap_or_default();
+                fields_docids.retain(|_, docids| {
+                    *docids &= &phrase_docids;
+                    !docids.is_empty()
+                });
+                Ok(fields_docids)
+            }
+            Or(_, ops) => {
+                let mut fields_docids = HashMap::new();
+                for op in ops {
+                    for (field_id, docids) in resolve_operation(ctx, op, wdcache)? {
+                        *fields_docids.entry(field_id).or_insert_with(RoaringBitmap::new) |= docids;
+                    }
+                }
+                Ok(fields_docids)
+            }
+            Operation::Query(query) => {
+                let words = match &query.kind {
+                    QueryKind::Exact { word, .. } if query.prefix && ctx.in_prefix_cache(word) => {
+                        return word_fields_docids(ctx, word, true);
+                    }
+                    QueryKind::Exact { word, .. } if query.prefix => ctx
+                        .iteration_budget()
+                        .derivations(
+                            RuleName::Proximity,
+                            word_derivations(word, true, 0, ctx.words_fst(), wdcache)?,
+                        )
+                        .to_owned(),
+                    QueryKind::Exact { word, .. } => vec![(word.clone(), 0)],
+                    QueryKind::Tolerant { typo, word } => ctx
+                        .iteration_budget()
+                        .derivations(
+                            RuleName::Proximity,
+                            word_derivations(word, query.prefix, *typo, ctx.words_fst(), wdcache)?,
+                        )
+                        .to_owned(),
+                };
+
+                let mut fields_docids = HashMap::new();
+                for (word, _typo) in words {
+                    for (field_id, docids) in word_fields_docids(ctx, &word, false)? {
+                        *fields_docids.entry(field_id).or_insert_with(RoaringBitmap::new) |= docids;
+                    }
+                }
+                Ok(fields_docids)
+            }
+        }
+    }
+
+    fn intersect_fields_docids(
+        mut left: HashMap<FieldId, RoaringBitmap>,
+        right: HashMap<FieldId, RoaringBitmap>,
+    ) -> HashMap<FieldId, RoaringBitmap> {
+        left.retain(|field_id, docids| match right.get(field_id) {
+            Some(right_docids) => {
+                *docids &= right_docids;
+                !docids.is_empty()
+            }
+            None => false,
+        });
+        left
+    }
+
+    fn word_fields_docids<'t>(
+        ctx: &'t dyn Context,
+        word: &str,
+        in_prefix_cache: bool,
+    ) -> Result<HashMap<FieldId, RoaringBitmap>> {
+        let mut fields_docids = HashMap::new();
+        for result in ctx.word_position_iterator(word, in_prefix_cache)? {
+            let ((_, position), docids) = result?;
+            let (field_id, _) = relative_from_absolute_position(position);
+            *fields_docids.entry(field_id).or_insert_with(RoaringBitmap::new) |= docids;
+        }
+        Ok(fields_docids)
+    }
+
+    let fields_docids = resolve_operation(ctx, query_tree, wdcache)?;
+    Ok(fields_docids
+        .into_iter()
+        .fold(RoaringBitmap::new(), |candidates, (_, docids)| candidates | docids))
+}
+
 fn resolve_candidates<'t>(
     ctx: &'t dyn Context,
     query_tree: &Operation,
     proximity: u8,
     cache: &mut Cache,
     wdcache: &mut WordDerivationsCache,
-) -> Result<RoaringBitmap> {
+) -> Result<Option<RoaringBitmap>> {
     fn resolve_operation<'t>(
         ctx: &'t dyn Context,
         query_tree: &Operation,
         proximity: u8,
         cache: &mut Cache,
         wdcache: &mut WordDerivationsCache,
+        evaluated: &mut usize,
     ) -> Result<Vec<(Query, Query, RoaringBitmap)>> {
         use Operation::{And, Or, Phrase};
 
         let result = match query_tree {
-            And(ops) => mdfs(ctx, ops, proximity, cache, wdcache)?,
+            And(ops) => mdfs(ctx, ops, proximity, cache, wdcache, evaluated)?,
             Phrase(words) => {
                 if proximity == 0 {
                     let most_left = words
@@ -204,7 +406,7 @@ fn resolve_candidates<'t>(
             Or(_, ops) => {
                 let mut output = Vec::new();
                 for op in ops {
-                    let result = resolve_operation(ctx, op, proximity, cache, wdcache)?;
+                    let result = resolve_operation(ctx, op, proximity, cache, wdcache, evaluated)?;
                     output.extend(result);
                 }
                 output
@@ -229,12 +431,13 @@ fn resolve_candidates<'t>(
         proximity: u8,
         cache: &mut Cache,
         wdcache: &mut WordDerivationsCache,
+        evaluated: &mut usize,
     ) -> Result<Vec<(Query, Query, RoaringBitmap)>> {
         fn pair_combinations(mana: u8, left_max: u8) -> impl Iterator<Item = (u8, u8)> {
             (0..=mana.min(left_max)).map(move |m| (m, mana - m))
         }
 
-        let pair_max_proximity = 7;
+        let pair_max_proximity = ctx.max_proximity();
 
         let mut output = Vec::new();
 
@@ -242,13 +445,15 @@ fn resolve_candidates<'t>(
             for (left_p, right_p) in pair_combinations(left_right_p, left_right_p) {
                 let left_key = (left.clone(), left_p);
                 if !cache.contains_key(&left_key) {
-                    let candidates = resolve_operation(ctx, left, left_p, cache, wdcache)?;
+                    let candidates =
+                        resolve_operation(ctx, left, left_p, cache, wdcache, evaluated)?;
                     cache.insert(left_key.clone(), candidates);
                 }
 
                 let right_key = (right.clone(), right_p);
                 if !cache.contains_key(&right_key) {
-                    let candidates = resolve_operation(ctx, right, right_p, cache, wdcache)?;
+                    let candidates =
+                        resolve_operation(ctx, right, right_p, cache, wdcache, evaluated)?;
                     cache.insert(right_key.clone(), candidates);
                 }
 
@@ -257,6 +462,11 @@ fn resolve_candidates<'t>(
 
                 for (ll, lr, lcandidates) in lefts {
                     for (rl, rr, rcandidates) in rights {
+                        *evaluated += 1;
+                        if ctx.iteration_budget().too_many_pairs(*evaluated) {
+                            return Ok(output);
+                        }
+
                         let mut candidates =
                             query_pair_proximity_docids(ctx, lr, rl, pair_p + 1, wdcache)?;
                         if lcandidates.len() < rcandidates.len() {
@@ -268,6 +478,8 @@ fn resolve_candidates<'t>(
                         }
                         if !candidates.is_empty() {
                             output.push((ll.clone(), rr.clone(), candidates));
+                        } else {
+                            ctx.arena().recycle(candidates);
                         }
                     }
                 }
@@ -283,6 +495,7 @@ fn resolve_candidates<'t>(
         proximity: u8,
         cache: &mut Cache,
         wdcache: &mut WordDerivationsCache,
+        evaluated: &mut usize,
     ) -> Result<Vec<(Query, Query, RoaringBitmap)>> {
         // Extract the first two elements but gives the tail
         // that is just after the first element.
@@ -291,38 +504,53 @@ fn resolve_candidates<'t>(
 
         match next {
             Some((head1, Some((head2, [_])))) => {
-                mdfs_pair(ctx, head1, head2, proximity, cache, wdcache)
+                mdfs_pair(ctx, head1, head2, proximity, cache, wdcache, evaluated)
             }
             Some((head1, Some((head2, tail)))) => {
                 let mut output = Vec::new();
                 for p in 0..=proximity {
                     for (lhead, _, head_candidates) in
-                        mdfs_pair(ctx, head1, head2, p, cache, wdcache)?
+                        mdfs_pair(ctx, head1, head2, p, cache, wdcache, evaluated)?
                     {
+                        if ctx.iteration_budget().too_many_pairs(*evaluated) {
+                            return Ok(output);
+                        }
                         if !head_candidates.is_empty() {
                             for (_, rtail, mut candidates) in
-                                mdfs(ctx, tail, proximity - p, cache, wdcache)?
+                                mdfs(ctx, tail, proximity - p, cache, wdcache, evaluated)?
                             {
                                 candidates &= &head_candidates;
                                 if !candidates.is_empty() {
                                     output.push((lhead.clone(), rtail, candidates));
+                                } else {
+                                    ctx.arena().recycle(candidates);
                                 }
                             }
                         }
+                        ctx.arena().recycle(head_candidates);
                     }
                 }
                 Ok(output)
             }
-            Some((head1, None)) => resolve_operation(ctx, head1, proximity, cache, wdcache),
+            Some((head1, None)) => {
+                resolve_operation(ctx, head1, proximity, cache, wdcache, evaluated)
+            }
             None => Ok(Default::default()),
         }
     }
 
-    let mut candidates = RoaringBitmap::new();
-    for (_, _, cds) in resolve_operation(ctx, query_tree, proximity, cache, wdcache)? {
+    // The number of pairs of words evaluated for this bucket.
+    let mut evaluated = 0;
+    let resolved = resolve_operation(ctx, query_tree, proximity, cache, wdcache, &mut evaluated)?;
+    if ctx.iteration_budget().too_many_pairs(evaluated) {
+        return Ok(None);
+    }
+
+    let mut candidates = ctx.arena().take();
+    for (_, _, cds) in resolved {
         candidates |= cds;
     }
-    Ok(candidates)
+    Ok(Some(candidates))
 }
 
 fn resolve_plane_sweep_candidates(
@@ -334,10 +562,12 @@ fn resolve_plane_sweep_candidates(
     fn plane_sweep(
         groups_positions: Vec<Vec<(Position, u8, Position)>>,
         consecutive: bool,
+        max_proximity: u8,
     ) -> Result<Vec<(Position, u8, Position)>> {
         fn compute_groups_proximity(
             groups: &[(usize, (Position, u8, Position))],
             consecutive: bool,
+            max_proximity: u8,
         ) -> Option<(Position, u8, Position)> {
             // take the inner proximity of the first group as initial
             let (_, (_, mut proximity, _)) = groups.first()?;
@@ -368,9 +598,13 @@ fn resolve_plane_sweep_candidates(
                     };
 
                     // if groups are in the good order (query order) we remove 1 to the proximity
-                    // the proximity is clamped to 7
-                    let pair_proximity =
-                        if i1 < i2 { (pair_proximity - 1).min(7) } else { pair_proximity.min(7) };
+                    // the proximity is clamped to the maximum proximity stored in the index
+                    let max_proximity = max_proximity as u32;
+                    let pair_proximity = if i1 < i2 {
+                        (pair_proximity - 1).min(max_proximity)
+                    } else {
+                        pair_proximity.min(max_proximity)
+                    };
 
                     proximity += pair_proximity as u8 + prox2;
                 }
@@ -419,7 +653,8 @@ fn resolve_plane_sweep_candidates(
             // If p > r, then the interval [l, r] is minimal and
             // we insert it into the heap according to its size.
             if p.map_or(true, |p| p.1 > rightmost.1) {
-                if let Some(group) = compute_groups_proximity(&current, consecutive) {
+                if let Some(group) = compute_groups_proximity(&current, consecutive, max_proximity)
+                {
                     output.push(group);
                 }
             }
@@ -455,6 +690,7 @@ fn resolve_plane_sweep_candidates(
         query_tree: &'a Operation,
         rocache: &mut HashMap<&'a Operation, Vec<(Position, u8, Position)>>,
         words_positions: &HashMap<String, RoaringBitmap>,
+        max_proximity: u8,
     ) -> Result<Vec<(Position, u8, Position)>> {
         use Operation::{And, Or, Phrase};
 
@@ -466,10 +702,11 @@ fn resolve_plane_sweep_candidates(
             And(ops) => {
                 let mut groups_positions = Vec::with_capacity(ops.len());
                 for operation in ops {
-                    let positions = resolve_operation(operation, rocache, words_positions)?;
+                    let positions =
+                        resolve_operation(operation, rocache, words_positions, max_proximity)?;
                     groups_positions.push(positions);
                 }
-                plane_sweep(groups_positions, false)?
+                plane_sweep(groups_positions, false, max_proximity)?
             }
             Phrase(words) => {
                 let mut groups_positions = Vec::with_capacity(words.len());
@@ -480,12 +717,12 @@ fn resolve_plane_sweep_candidates(
                     };
                     groups_positions.push(positions);
                 }
-                plane_sweep(groups_positions, true)?
+                plane_sweep(groups_positions, true, max_proximity)?
             }
             Or(_, ops) => {
                 let mut result = Vec::new();
                 for op in ops {
-                    result.extend(resolve_operation(op, rocache, words_positions)?)
+                    result.extend(resolve_operation(op, rocache, words_positions, max_proximity)?)
                 }
 
                 result.sort_unstable();
@@ -535,15 +772,21 @@ fn resolve_plane_sweep_candidates(
         })
     }
 
+    let max_proximity = ctx.max_proximity();
     let mut resolve_operation_cache = HashMap::new();
     let mut candidates = BTreeMap::new();
     for docid in allowed_candidates {
         let words_positions = ctx.docid_words_positions(docid)?;
         resolve_operation_cache.clear();
-        let positions =
-            resolve_operation(query_tree, &mut resolve_operation_cache, &words_positions)?;
+        let positions = resolve_operation(
+            query_tree,
+            &mut resolve_operation_cache,
+            &words_positions,
+            max_proximity,
+        )?;
         let best_proximity = positions.into_iter().min_by_key(|(_, proximity, _)| *proximity);
-        let best_proximity = best_proximity.map(|(_, proximity, _)| proximity).unwrap_or(7);
+        let best_proximity =
+            best_proximity.map(|(_, proximity, _)| proximity).unwrap_or(max_proximity);
         candidates.entry(best_proximity).or_insert_with(RoaringBitmap::new).insert(docid);
     }
 
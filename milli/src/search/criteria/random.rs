@@ -0,0 +1,174 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::mem::take;
+
+use roaring::RoaringBitmap;
+
+use super::{BucketScores, Criterion, CriterionParameters, CriterionResult, RankingRuleScore};
+use crate::search::criteria::{resolve_query_tree, Context};
+use crate::{DocumentId, Index, Result};
+
+/// Returns the documents one by one, ordered by the hash of their id with the seed.
+///
+/// The order is random but always the same for the same seed, the pages of a search stay
+/// consistent. The candidates of a bucket of the parent are hashed once and put in a heap,
+/// only the documents that are returned are popped from it.
+pub struct Random<'t> {
+    ctx: &'t dyn Context<'t>,
+    index: &'t Index,
+    rtxn: &'t heed::RoTxn<'t>,
+    seed: u64,
+    ascending: bool,
+    parent: Box<dyn Criterion + 't>,
+    allowed_candidates: RoaringBitmap,
+    bucket_candidates: RoaringBitmap,
+    /// The candidates of the current bucket of the parent with their hash, or its
+    /// complement when descending, the smallest one being the next document.
+    documents: BinaryHeap<Reverse<(u64, DocumentId)>>,
+    scores: BucketScores,
+}
+
+impl<'t> Random<'t> {
+    pub fn asc(
+        ctx: &'t dyn Context<'t>,
+        index: &'t Index,
+        rtxn: &'t heed::RoTxn<'t>,
+        parent: Box<dyn Criterion + 't>,
+        seed: u64,
+    ) -> Self {
+        Self::new(ctx, index, rtxn, parent, seed, true)
+    }
+
+    pub fn desc(
+        ctx: &'t dyn Context<'t>,
+        index: &'t Index,
+        rtxn: &'t heed::RoTxn<'t>,
+        parent: Box<dyn Criterion + 't>,
+        seed: u64,
+    ) -> Self {
+        Self::new(ctx, index, rtxn, parent, seed, false)
+    }
+
+    fn new(
+        ctx: &'t dyn Context<'t>,
+        index: &'t Index,
+        rtxn: &'t heed::RoTxn<'t>,
+        parent: Box<dyn Criterion + 't>,
+        seed: u64,
+        ascending: bool,
+    ) -> Self {
+        Random {
+            ctx,
+            index,
+            rtxn,
+            seed,
+            ascending,
+            parent,
+            allowed_candidates: RoaringBitmap::new(),
+            bucket_candidates: RoaringBitmap::new(),
+            documents: BinaryHeap::new(),
+            scores: BucketScores::default(),
+        }
+    }
+
+    /// Hashes the candidates of a bucket of the parent and orders them in the heap.
+    fn hash_candidates(&mut self) {
+        let (seed, ascending) = (self.seed, self.ascending);
+        let documents: Vec<_> = self
+            .allowed_candidates
+            .iter()
+            .map(|docid| {
+                let hash = random_hash(seed, docid);
+                Reverse((if ascending { hash } else { !hash }, docid))
+            })
+            .collect();
+        self.documents = BinaryHeap::from(documents);
+    }
+}
+
+impl Criterion for Random<'_> {
+    fn next(&mut self, params: &mut CriterionParameters) -> Result<Option<CriterionResult>> {
+        // remove excluded candidates when next is called, instead of doing it in the loop.
+        self.allowed_candidates -= params.excluded_candidates;
+
+        loop {
+            // The documents left in the heap may all have been excluded.
+            if self.allowed_candidates.is_empty() {
+                self.documents.clear();
+            }
+
+            if let Some(Reverse((_, docid))) = self.documents.pop() {
+                if !self.allowed_candidates.remove(docid) {
+                    continue;
+                }
+                return Ok(Some(CriterionResult {
+                    query_tree: None,
+                    candidates: Some(std::iter::once(docid).collect()),
+                    filtered_candidates: None,
+                    bucket_candidates: Some(take(&mut self.bucket_candidates)),
+                    score_details: self.scores.next(RankingRuleScore::Random { seed: self.seed }),
+                }));
+            }
+
+            match self.parent.next(params)? {
+                // The documents of the bucket were all returned by the previous pages.
+                Some(result) if params.is_skipped(&result.score_details) => {
+                    return Ok(Some(result));
+                }
+                Some(CriterionResult {
+                    query_tree,
+                    candidates,
+                    filtered_candidates,
+                    bucket_candidates,
+                    score_details,
+                }) => {
+                    self.scores.reset(score_details);
+                    let mut candidates = match (&query_tree, candidates) {
+                        (_, Some(candidates)) => candidates,
+                        (Some(qt), None) => resolve_query_tree(self.ctx, qt, params.wdcache)?,
+                        (None, None) => self.index.documents_ids(self.rtxn)?,
+                    };
+
+                    if let Some(filtered_candidates) = filtered_candidates {
+                        candidates &= filtered_candidates;
+                    }
+
+                    match bucket_candidates {
+                        Some(bucket_candidates) => self.bucket_candidates |= bucket_candidates,
+                        None => self.bucket_candidates |= &candidates,
+                    }
+
+                    self.allowed_candidates = candidates - params.excluded_candidates;
+                    self.hash_candidates();
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Mixes the document id with the seed, the SplitMix64 finalizer.
+fn random_hash(seed: u64, docid: DocumentId) -> u64 {
+    let mut hash = seed ^ (docid as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_4d6b_1ce4_e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_depend_on_the_seed() {
+        let hashes = |seed| (0..1000).map(|docid| random_hash(seed, docid)).collect::<Vec<_>>();
+
+        let mut unique = hashes(42);
+        unique.sort_unstable();
+        unique.dedup();
+        assert_eq!(unique.len(), 1000);
+
+        assert_eq!(hashes(42), hashes(42));
+        assert_ne!(hashes(42), hashes(43));
+    }
+}
@@ -6,8 +6,8 @@ use log::debug;
 use roaring::RoaringBitmap;
 
 use super::{
-    query_docids, resolve_query_tree, Candidates, Context, Criterion, CriterionParameters,
-    CriterionResult,
+    query_docids, resolve_query_tree, BucketScores, Candidates, Context, Criterion,
+    CriterionParameters, CriterionResult, RankingRuleScore,
 };
 use crate::search::query_tree::{maximum_typo, Operation, Query, QueryKind};
 use crate::search::{word_derivations, WordDerivationsCache};
@@ -22,6 +22,7 @@ pub struct Typo<'t> {
     state: Option<(u8, Operation, Candidates)>,
     typos: u8,
     bucket_candidates: Option<RoaringBitmap>,
+    scores: BucketScores,
     parent: Box<dyn Criterion + 't>,
     candidates_cache: HashMap<(Operation, u8), RoaringBitmap>,
 }
@@ -33,10 +34,20 @@ impl<'t> Typo<'t> {
             state: None,
             typos: 0,
             bucket_candidates: None,
+            scores: BucketScores::default(),
             parent,
             candidates_cache: HashMap::new(),
         }
     }
+
+    /// Forgets the current bucket of the parent and gives its candidates back to the arena.
+    fn reset_state(&mut self) {
+        if let Some((_, _, Candidates::Allowed(candidates) | Candidates::Forbidden(candidates))) =
+            self.state.take()
+        {
+            self.ctx.arena().recycle(candidates);
+        }
+    }
 }
 
 impl<'t> Criterion for Typo<'t> {
@@ -60,10 +71,10 @@ impl<'t> Criterion for Typo<'t> {
 
             match self.state.as_mut() {
                 Some((max_typos, _, _)) if self.typos > *max_typos => {
-                    self.state = None; // reset state
+                    self.reset_state();
                 }
                 Some((_, _, Allowed(allowed_candidates))) if allowed_candidates.is_empty() => {
-                    self.state = None; // reset state
+                    self.reset_state();
                 }
                 Some((_, query_tree, candidates_authorization)) => {
                     let fst = self.ctx.words_fst();
@@ -84,17 +95,20 @@ impl<'t> Criterion for Typo<'t> {
                                 params.wdcache,
                             )?;
                             // we compute the allowed candidates
-                            let query_tree_allowed_candidates =
+                            let mut query_tree_allowed_candidates =
                                 resolve_query_tree(self.ctx, query_tree, params.wdcache)?;
                             // we assign the allowed candidates to the candidates authorization.
-                            *candidates_authorization = match take(candidates_authorization) {
+                            match take(candidates_authorization) {
                                 Allowed(allowed_candidates) => {
-                                    Allowed(query_tree_allowed_candidates & allowed_candidates)
+                                    query_tree_allowed_candidates &= &allowed_candidates;
+                                    self.ctx.arena().recycle(allowed_candidates);
                                 }
                                 Forbidden(forbidden_candidates) => {
-                                    Allowed(query_tree_allowed_candidates - forbidden_candidates)
+                                    query_tree_allowed_candidates -= &forbidden_candidates;
+                                    self.ctx.arena().recycle(forbidden_candidates);
                                 }
-                            };
+                            }
+                            *candidates_authorization = Allowed(query_tree_allowed_candidates);
                             query_tree.clone()
                         }
                         _otherwise => query_tree.clone(),
@@ -121,9 +135,11 @@ impl<'t> Criterion for Typo<'t> {
 
                     let bucket_candidates = match self.bucket_candidates.as_mut() {
                         Some(bucket_candidates) => take(bucket_candidates),
-                        None => candidates.clone(),
+                        None => self.ctx.arena().copy(&candidates),
                     };
 
+                    let score_details =
+                        self.scores.next(RankingRuleScore::Typo { typos: self.typos });
                     self.typos += 1;
 
                     return Ok(Some(CriterionResult {
@@ -131,15 +147,22 @@ impl<'t> Criterion for Typo<'t> {
                         candidates: Some(candidates),
                         filtered_candidates: None,
                         bucket_candidates: Some(bucket_candidates),
+                        score_details,
                     }));
                 }
                 None => match self.parent.next(params)? {
+                    // The documents of the bucket were all returned by the previous pages.
+                    Some(result) if params.is_skipped(&result.score_details) => {
+                        return Ok(Some(result));
+                    }
                     Some(CriterionResult {
                         query_tree: Some(query_tree),
                         candidates,
                         filtered_candidates,
                         bucket_candidates,
+                        score_details,
                     }) => {
+                        self.scores.reset(score_details);
                         self.bucket_candidates =
                             match (self.bucket_candidates.take(), bucket_candidates) {
                                 (Some(self_bc), Some(parent_bc)) => Some(self_bc | parent_bc),
@@ -150,7 +173,9 @@ impl<'t> Criterion for Typo<'t> {
                             Some(candidates) => {
                                 Candidates::Allowed(candidates - params.excluded_candidates)
                             }
-                            None => Candidates::Forbidden(params.excluded_candidates.clone()),
+                            None => Candidates::Forbidden(
+                                self.ctx.arena().copy(params.excluded_candidates),
+                            ),
                         };
 
                         let maximum_typos = maximum_typo(&query_tree) as u8;
@@ -162,12 +187,14 @@ impl<'t> Criterion for Typo<'t> {
                         candidates,
                         filtered_candidates,
                         bucket_candidates,
+                        score_details,
                     }) => {
                         return Ok(Some(CriterionResult {
                             query_tree: None,
                             candidates,
                             filtered_candidates,
                             bucket_candidates,
+                            score_details,
                         }));
                     }
                     None => return Ok(None),
@@ -348,7 +375,6 @@ mod test {
     use super::super::initial::Initial;
     use super::super::test::TestContext;
     use super::*;
-    use crate::search::NoopDistinct;
 
     fn display_criteria(mut criteria: Typo, mut parameters: CriterionParameters) -> String {
         let mut result = String::new();
@@ -367,15 +393,15 @@ mod test {
         let criterion_parameters = CriterionParameters {
             wdcache: &mut WordDerivationsCache::new(),
             excluded_candidates: &RoaringBitmap::new(),
+            position: None,
         };
 
-        let parent =
-            Initial::<NoopDistinct>::new(&context, query_tree, facet_candidates, false, None);
+        let parent = Initial::new(&context, query_tree, facet_candidates, false);
         let criteria = Typo::new(&context, Box::new(parent));
 
         let result = display_criteria(criteria, criterion_parameters);
         insta::assert_snapshot!(result, @r###"
-        CriterionResult { query_tree: None, candidates: None, filtered_candidates: None, bucket_candidates: None }
+        CriterionResult { query_tree: None, candidates: None, filtered_candidates: None, bucket_candidates: None, score_details: [] }
 
         "###);
     }
@@ -406,9 +432,9 @@ mod test {
         let criterion_parameters = CriterionParameters {
             wdcache: &mut WordDerivationsCache::new(),
             excluded_candidates: &RoaringBitmap::new(),
+            position: None,
         };
-        let parent =
-            Initial::<NoopDistinct>::new(&context, Some(query_tree), facet_candidates, false, None);
+        let parent = Initial::new(&context, Some(query_tree), facet_candidates, false);
         let criteria = Typo::new(&context, Box::new(parent));
 
         let result = display_criteria(criteria, criterion_parameters);
@@ -418,7 +444,7 @@ mod test {
             Exact { word: "split" }
             Exact { word: "this" }
             Exact { word: "world" }
-        ), candidates: Some(RoaringBitmap<[]>), filtered_candidates: None, bucket_candidates: Some(RoaringBitmap<[]>) }
+        ), candidates: Some(RoaringBitmap<[]>), filtered_candidates: None, bucket_candidates: Some(RoaringBitmap<[]>), score_details: [ScoreDetail { bucket: 0, rule: Typo { typos: 0 } }] }
 
         CriterionResult { query_tree: Some(OR
           AND
@@ -427,7 +453,7 @@ mod test {
             OR
               Exact { word: "word" }
               Exact { word: "world" }
-        ), candidates: Some(RoaringBitmap<[]>), filtered_candidates: None, bucket_candidates: Some(RoaringBitmap<[]>) }
+        ), candidates: Some(RoaringBitmap<[]>), filtered_candidates: None, bucket_candidates: Some(RoaringBitmap<[]>), score_details: [ScoreDetail { bucket: 1, rule: Typo { typos: 1 } }] }
 
         "###);
     }
@@ -441,19 +467,14 @@ mod test {
         let criterion_parameters = CriterionParameters {
             wdcache: &mut WordDerivationsCache::new(),
             excluded_candidates: &RoaringBitmap::new(),
+            position: None,
         };
-        let parent = Initial::<NoopDistinct>::new(
-            &context,
-            query_tree,
-            Some(facet_candidates.clone()),
-            false,
-            None,
-        );
+        let parent = Initial::new(&context, query_tree, Some(facet_candidates.clone()), false);
         let criteria = Typo::new(&context, Box::new(parent));
 
         let result = display_criteria(criteria, criterion_parameters);
         insta::assert_snapshot!(result, @r###"
-        CriterionResult { query_tree: None, candidates: None, filtered_candidates: Some(RoaringBitmap<8000 values between 986424 and 4294786076>), bucket_candidates: None }
+        CriterionResult { query_tree: None, candidates: None, filtered_candidates: Some(RoaringBitmap<8000 values between 986424 and 4294786076>), bucket_candidates: None, score_details: [] }
 
         "###);
     }
@@ -484,14 +505,10 @@ mod test {
         let criterion_parameters = CriterionParameters {
             wdcache: &mut WordDerivationsCache::new(),
             excluded_candidates: &RoaringBitmap::new(),
+            position: None,
         };
-        let parent = Initial::<NoopDistinct>::new(
-            &context,
-            Some(query_tree),
-            Some(facet_candidates.clone()),
-            false,
-            None,
-        );
+        let parent =
+            Initial::new(&context, Some(query_tree), Some(facet_candidates.clone()), false);
         let criteria = Typo::new(&context, Box::new(parent));
 
         let result = display_criteria(criteria, criterion_parameters);
@@ -501,7 +518,7 @@ mod test {
             Exact { word: "split" }
             Exact { word: "this" }
             Exact { word: "world" }
-        ), candidates: Some(RoaringBitmap<[]>), filtered_candidates: None, bucket_candidates: Some(RoaringBitmap<[]>) }
+        ), candidates: Some(RoaringBitmap<[]>), filtered_candidates: None, bucket_candidates: Some(RoaringBitmap<[]>), score_details: [ScoreDetail { bucket: 0, rule: Typo { typos: 0 } }] }
 
         CriterionResult { query_tree: Some(OR
           AND
@@ -510,7 +527,7 @@ mod test {
             OR
               Exact { word: "word" }
               Exact { word: "world" }
-        ), candidates: Some(RoaringBitmap<[]>), filtered_candidates: None, bucket_candidates: Some(RoaringBitmap<[]>) }
+        ), candidates: Some(RoaringBitmap<[]>), filtered_candidates: None, bucket_candidates: Some(RoaringBitmap<[]>), score_details: [ScoreDetail { bucket: 1, rule: Typo { typos: 1 } }] }
 
         "###);
     }
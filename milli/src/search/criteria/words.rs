@@ -3,7 +3,10 @@ use std::mem::take;
 use log::debug;
 use roaring::RoaringBitmap;
 
-use super::{resolve_query_tree, Context, Criterion, CriterionParameters, CriterionResult};
+use super::{
+    resolve_query_tree, BucketScores, Context, Criterion, CriterionParameters, CriterionResult,
+    RankingRuleScore,
+};
 use crate::search::query_tree::Operation;
 use crate::Result;
 
@@ -13,6 +16,7 @@ pub struct Words<'t> {
     candidates: Option<RoaringBitmap>,
     bucket_candidates: Option<RoaringBitmap>,
     filtered_candidates: Option<RoaringBitmap>,
+    scores: BucketScores,
     parent: Box<dyn Criterion + 't>,
 }
 
@@ -25,6 +29,7 @@ impl<'t> Words<'t> {
             bucket_candidates: None,
             parent,
             filtered_candidates: None,
+            scores: BucketScores::default(),
         }
     }
 }
@@ -58,23 +63,42 @@ impl<'t> Criterion for Words<'t> {
                         None => None,
                     };
 
+                    let filtered_candidates =
+                        self.filtered_candidates.as_ref().map(|fc| self.ctx.arena().copy(fc));
+
+                    let matching_words = matching_words(&query_tree);
                     return Ok(Some(CriterionResult {
                         query_tree: Some(query_tree),
                         candidates,
-                        filtered_candidates: self.filtered_candidates.clone(),
+                        filtered_candidates,
                         bucket_candidates,
+                        score_details: self.scores.next(RankingRuleScore::Words { matching_words }),
                     }));
                 }
                 None => match self.parent.next(params)? {
+                    // The documents of the bucket were all returned by the previous pages.
+                    Some(result) if params.is_skipped(&result.score_details) => {
+                        return Ok(Some(result));
+                    }
                     Some(CriterionResult {
                         query_tree: Some(query_tree),
                         candidates,
                         filtered_candidates,
                         bucket_candidates,
+                        score_details,
                     }) => {
+                        // The bitmaps of the previous bucket of the parent are not used anymore.
+                        if let Some(candidates) = self.candidates.take() {
+                            self.ctx.arena().recycle(candidates);
+                        }
+                        if let Some(filtered_candidates) = self.filtered_candidates.take() {
+                            self.ctx.arena().recycle(filtered_candidates);
+                        }
+
                         self.query_trees = explode_query_tree(query_tree);
                         self.candidates = candidates;
                         self.filtered_candidates = filtered_candidates;
+                        self.scores.reset(score_details);
 
                         self.bucket_candidates =
                             match (self.bucket_candidates.take(), bucket_candidates) {
@@ -87,12 +111,14 @@ impl<'t> Criterion for Words<'t> {
                         candidates,
                         filtered_candidates,
                         bucket_candidates,
+                        score_details,
                     }) => {
                         return Ok(Some(CriterionResult {
                             query_tree: None,
                             candidates,
                             filtered_candidates,
                             bucket_candidates,
+                            score_details,
                         }));
                     }
                     None => return Ok(None),
@@ -102,6 +128,17 @@ impl<'t> Criterion for Words<'t> {
     }
 }
 
+/// Returns the number of query words a query tree matches, the alternatives of an `Or`
+/// operation match the same query words and the biggest number of them is kept.
+fn matching_words(query_tree: &Operation) -> u32 {
+    match query_tree {
+        Operation::And(ops) => ops.iter().map(matching_words).sum(),
+        Operation::Or(_, ops) => ops.iter().map(matching_words).max().unwrap_or_default(),
+        Operation::Phrase(words) => words.len() as u32,
+        Operation::Query(_) => 1,
+    }
+}
+
 fn explode_query_tree(query_tree: Operation) -> Vec<Operation> {
     match query_tree {
         Operation::Or(true, ops) => ops,
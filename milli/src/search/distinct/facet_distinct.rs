@@ -1,7 +1,7 @@
 use std::mem::size_of;
 
 use concat_arrays::concat_arrays;
-use heed::types::{ByteSlice, Str, Unit};
+use heed::types::{ByteSlice, Str};
 use roaring::RoaringBitmap;
 
 use super::{Distinct, DocIter};
@@ -124,12 +124,12 @@ fn facet_values_prefix_key(distinct: FieldId, id: DocumentId) -> [u8; FID_SIZE +
     concat_arrays!(distinct.to_be_bytes(), id.to_be_bytes())
 }
 
-fn facet_number_values<'a>(
+pub(crate) fn facet_number_values<'a>(
     id: DocumentId,
     distinct: FieldId,
     index: &Index,
     txn: &'a heed::RoTxn,
-) -> Result<heed::RoPrefix<'a, FieldDocIdFacetF64Codec, Unit>> {
+) -> Result<heed::RoPrefix<'a, FieldDocIdFacetF64Codec, FacetExactIntegersCodec>> {
     let key = facet_values_prefix_key(distinct, id);
 
     let iter = index
@@ -141,7 +141,7 @@ fn facet_number_values<'a>(
     Ok(iter)
 }
 
-fn facet_string_values<'a>(
+pub(crate) fn facet_string_values<'a>(
     id: DocumentId,
     distinct: FieldId,
     index: &Index,
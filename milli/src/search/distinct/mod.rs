@@ -2,6 +2,7 @@ mod facet_distinct;
 mod noop_distinct;
 
 pub use facet_distinct::FacetDistinct;
+pub(crate) use facet_distinct::{facet_number_values, facet_string_values};
 pub use noop_distinct::NoopDistinct;
 use roaring::RoaringBitmap;
 
@@ -98,7 +99,8 @@ mod test {
             ..Default::default()
         };
         let addition =
-            IndexDocuments::new(&mut txn, &index, &config, indexing_config, |_| ()).unwrap();
+            IndexDocuments::new(&mut txn, &index, &config, indexing_config, |_| (), || false)
+                .unwrap();
 
         let reader =
             crate::documents::DocumentsBatchReader::from_reader(Cursor::new(JSON.as_slice()))
@@ -0,0 +1,169 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use roaring::RoaringBitmap;
+
+/// A least recently used cache of the decoded docids of the words searched in the index,
+/// bounded by the serialized size of the keys and bitmaps it holds.
+///
+/// The entries are tagged with the update id of the index they were read at and
+/// are dropped as soon as an entry is read at a more recent update id, a read
+/// transaction never sees the entries read by a transaction at another update id.
+pub struct DocidsCache {
+    max_size: usize,
+    inner: Mutex<CacheInner>,
+}
+
+#[derive(Default)]
+struct CacheInner {
+    update_id: u64,
+    size: usize,
+    last_tick: u64,
+    /// The entries of every cached database, associated with their key.
+    entries: HashMap<&'static str, HashMap<Box<[u8]>, CacheEntry>>,
+    /// The database and the key of the entries, ordered from the least recently used.
+    recency: BTreeMap<u64, (&'static str, Box<[u8]>)>,
+}
+
+struct CacheEntry {
+    docids: RoaringBitmap,
+    size: usize,
+    tick: u64,
+}
+
+impl DocidsCache {
+    pub fn new(max_size: usize) -> DocidsCache {
+        DocidsCache { max_size, inner: Mutex::default() }
+    }
+
+    /// Returns the cached docids of the key or fetches and caches them.
+    pub fn get_or_fetch<E>(
+        &self,
+        update_id: u64,
+        database: &'static str,
+        key: &[u8],
+        fetch: impl FnOnce() -> Result<Option<RoaringBitmap>, E>,
+    ) -> Result<Option<RoaringBitmap>, E> {
+        if let Some(docids) = self.get(update_id, database, key) {
+            return Ok(Some(docids));
+        }
+
+        let docids = fetch()?;
+        if let Some(docids) = &docids {
+            self.insert(update_id, database, key, docids);
+        }
+        Ok(docids)
+    }
+
+    /// The serialized size of the keys and docids currently cached.
+    pub fn size(&self) -> usize {
+        self.inner.lock().unwrap().size
+    }
+
+    fn get(&self, update_id: u64, database: &'static str, key: &[u8]) -> Option<RoaringBitmap> {
+        let mut inner = self.inner.lock().unwrap();
+        let inner = &mut *inner;
+        if inner.update_id != update_id {
+            return None;
+        }
+
+        let entry = inner.entries.get_mut(database)?.get_mut(key)?;
+        inner.last_tick += 1;
+        let old_tick = std::mem::replace(&mut entry.tick, inner.last_tick);
+        if let Some(key) = inner.recency.remove(&old_tick) {
+            inner.recency.insert(inner.last_tick, key);
+        }
+
+        Some(entry.docids.clone())
+    }
+
+    fn insert(&self, update_id: u64, database: &'static str, key: &[u8], docids: &RoaringBitmap) {
+        let size = key.len() + docids.serialized_size();
+        if size > self.max_size {
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        let inner = &mut *inner;
+        if update_id < inner.update_id {
+            // The docids were read by a transaction that doesn't see the last updates.
+            return;
+        } else if update_id > inner.update_id {
+            *inner = CacheInner { update_id, ..CacheInner::default() };
+        }
+
+        if inner.entries.get(database).map_or(false, |entries| entries.contains_key(key)) {
+            return;
+        }
+
+        while inner.size + size > self.max_size {
+            let oldest_tick = *inner.recency.keys().next().unwrap();
+            let (database, key) = inner.recency.remove(&oldest_tick).unwrap();
+            let entry = inner.entries.get_mut(database).unwrap().remove(&key).unwrap();
+            inner.size -= entry.size;
+        }
+
+        inner.last_tick += 1;
+        let entry = CacheEntry { docids: docids.clone(), size, tick: inner.last_tick };
+        inner.entries.entry(database).or_default().insert(key.into(), entry);
+        inner.recency.insert(inner.last_tick, (database, key.into()));
+        inner.size += size;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fetch(docids: &RoaringBitmap) -> Result<Option<RoaringBitmap>, ()> {
+        Ok(Some(docids.clone()))
+    }
+
+    fn must_be_cached() -> Result<Option<RoaringBitmap>, ()> {
+        panic!("the docids must be read from the cache")
+    }
+
+    #[test]
+    fn least_recently_used_entries_are_evicted_by_serialized_size() {
+        let hello: RoaringBitmap = (0..10).collect();
+        let world: RoaringBitmap = (10..20).collect();
+        let entry_size = b"hello".len() + hello.serialized_size();
+        let cache = DocidsCache::new(entry_size * 2);
+
+        cache.get_or_fetch(1, "word-docids", b"hello", || fetch(&hello)).unwrap();
+        cache.get_or_fetch(1, "word-docids", b"world", || fetch(&world)).unwrap();
+        assert_eq!(cache.size(), entry_size * 2);
+
+        // `hello` is now the most recently used entry, `world` is evicted.
+        let docids = cache.get_or_fetch(1, "word-docids", b"hello", must_be_cached).unwrap();
+        assert_eq!(docids, Some(hello.clone()));
+        cache.get_or_fetch(1, "word-prefix-docids", b"hello", || fetch(&world)).unwrap();
+        assert_eq!(cache.size(), entry_size * 2);
+        assert_eq!(cache.get(1, "word-docids", b"world"), None);
+        assert_eq!(cache.get(1, "word-docids", b"hello"), Some(hello));
+        assert_eq!(cache.get(1, "word-prefix-docids", b"hello"), Some(world));
+
+        // The entries bigger than the whole cache are never cached.
+        let big: RoaringBitmap = (0..1000).step_by(2).collect();
+        cache.get_or_fetch(1, "word-docids", b"big", || fetch(&big)).unwrap();
+        assert_eq!(cache.get(1, "word-docids", b"big"), None);
+        assert_eq!(cache.size(), entry_size * 2);
+    }
+
+    #[test]
+    fn entries_are_only_seen_at_their_update_id() {
+        let old: RoaringBitmap = (0..10).collect();
+        let new: RoaringBitmap = (0..20).collect();
+        let cache = DocidsCache::new(usize::MAX);
+
+        cache.get_or_fetch(1, "word-docids", b"hello", || fetch(&old)).unwrap();
+        let docids = cache.get_or_fetch(2, "word-docids", b"hello", || fetch(&new)).unwrap();
+        assert_eq!(docids, Some(new.clone()));
+
+        // A transaction reading the index before the update doesn't replace the new entries.
+        let docids = cache.get_or_fetch(1, "word-docids", b"hello", || fetch(&old)).unwrap();
+        assert_eq!(docids, Some(old));
+        let docids = cache.get_or_fetch(2, "word-docids", b"hello", must_be_cached).unwrap();
+        assert_eq!(docids, Some(new));
+    }
+}
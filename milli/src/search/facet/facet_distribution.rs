@@ -1,17 +1,20 @@
 use std::collections::{BTreeMap, HashSet};
-use std::ops::Bound::Unbounded;
+use std::ops::{ControlFlow, RangeInclusive};
 use std::{fmt, mem};
 
 use heed::types::ByteSlice;
 use roaring::RoaringBitmap;
+use serde::{Deserialize, Serialize};
 
 use crate::error::UserError;
-use crate::facet::FacetType;
+use crate::facet::{FacetType, FacetValue};
 use crate::heed_codec::facet::{
-    FacetStringLevelZeroCodec, FieldDocIdFacetF64Codec, FieldDocIdFacetStringCodec,
+    decode_prefix_string, FacetStringLevelZeroCodec, FieldDocIdFacetF64Codec,
+    FieldDocIdFacetStringCodec,
 };
-use crate::search::facet::{FacetNumberIter, FacetNumberRange, FacetStringIter};
-use crate::{FieldId, Index, Result};
+use crate::search::facet::{FacetNumberIter, FacetStringIter};
+use crate::search::retain_range;
+use crate::{CboRoaringBitmapCodec, DocumentId, FieldId, Index, Result, RoaringBitmapLenCodec};
 
 /// The default number of values by facets that will
 /// be fetched from the key-value store.
@@ -21,10 +24,29 @@ pub const DEFAULT_VALUES_PER_FACET: usize = 100;
 /// the system to choose between one algorithm or another.
 const CANDIDATES_THRESHOLD: u64 = 3000;
 
+/// The order in which the values of a facet are kept when the
+/// distribution is truncated to the maximum number of values by facet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OrderBy {
+    /// The values that come first in the facet databases, numbers then strings.
+    Alpha,
+    /// The values associated with the highest number of documents.
+    Count,
+}
+
+impl Default for OrderBy {
+    fn default() -> Self {
+        OrderBy::Alpha
+    }
+}
+
 pub struct FacetDistribution<'a> {
     facets: Option<HashSet<String>>,
     candidates: Option<RoaringBitmap>,
+    candidates_range: Option<RangeInclusive<DocumentId>>,
     max_values_per_facet: usize,
+    candidates_threshold: u64,
     rtxn: &'a heed::RoTxn<'a>,
     index: &'a Index,
 }
@@ -34,7 +56,9 @@ impl<'a> FacetDistribution<'a> {
         FacetDistribution {
             facets: None,
             candidates: None,
+            candidates_range: None,
             max_values_per_facet: DEFAULT_VALUES_PER_FACET,
+            candidates_threshold: CANDIDATES_THRESHOLD,
             rtxn,
             index,
         }
@@ -55,6 +79,28 @@ impl<'a> FacetDistribution<'a> {
         self
     }
 
+    /// Only counts the candidates, or all the documents if there are none,
+    /// with an internal id within the range.
+    pub fn candidates_within(&mut self, range: RangeInclusive<DocumentId>) -> &mut Self {
+        self.candidates_range = Some(range);
+        self
+    }
+
+    /// Up to this number of candidates the distribution is computed by looking up the facet
+    /// values of each candidate, above it by iterating over the facet levels.
+    pub fn candidates_threshold(&mut self, threshold: u64) -> &mut Self {
+        self.candidates_threshold = threshold;
+        self
+    }
+
+    /// Returns the documents that have the `false` and `true` strings for this field.
+    fn boolean_docids(&self, field_id: FieldId) -> heed::Result<BooleanDocids> {
+        let db = self.index.facet_id_string_docids;
+        let falses = db.get(self.rtxn, &(field_id, "false"))?.map(|(_, docids)| docids);
+        let trues = db.get(self.rtxn, &(field_id, "true"))?.map(|(_, docids)| docids);
+        Ok(BooleanDocids { falses: falses.unwrap_or_default(), trues: trues.unwrap_or_default() })
+    }
+
     /// There is a small amount of candidates OR we ask for facet string values so we
     /// decide to iterate over the facet values of each one of them, one by one.
     fn facet_distribution_from_documents(
@@ -62,11 +108,13 @@ impl<'a> FacetDistribution<'a> {
         field_id: FieldId,
         facet_type: FacetType,
         candidates: &RoaringBitmap,
+        max_values: usize,
         distribution: &mut BTreeMap<String, u64>,
     ) -> heed::Result<()> {
         match facet_type {
             FacetType::Number => {
                 let mut key_buffer: Vec<_> = field_id.to_be_bytes().iter().copied().collect();
+                let booleans = self.boolean_docids(field_id)?;
 
                 let distribution_prelength = distribution.len();
                 let db = self.index.field_id_docid_facet_f64s;
@@ -79,11 +127,13 @@ impl<'a> FacetDistribution<'a> {
                         .remap_key_type::<FieldDocIdFacetF64Codec>();
 
                     for result in iter {
-                        let ((_, _, value), ()) = result?;
+                        let ((_, _, value), _) = result?;
+                        if booleans.of(value).map_or(false, |docids| docids.contains(docid)) {
+                            continue;
+                        }
                         *distribution.entry(value.to_string()).or_insert(0) += 1;
 
-                        if distribution.len() - distribution_prelength == self.max_values_per_facet
-                        {
+                        if distribution.len() - distribution_prelength == max_values {
                             break;
                         }
                     }
@@ -109,16 +159,22 @@ impl<'a> FacetDistribution<'a> {
                             .or_insert_with(|| (original_value, 0));
                         *count += 1;
 
-                        if normalized_distribution.len() == self.max_values_per_facet {
+                        if normalized_distribution.len() == max_values {
                             break;
                         }
                     }
                 }
 
-                let iter = normalized_distribution
-                    .into_iter()
-                    .map(|(_normalized, (original, count))| (original.to_string(), count));
-                distribution.extend(iter);
+                // The documents may not agree on the casing of a value, we
+                // return the original string kept in the facet database.
+                let facet_db = self.index.facet_id_string_docids;
+                for (normalized, (original, count)) in normalized_distribution {
+                    let original = match facet_db.get(self.rtxn, &(field_id, normalized))? {
+                        Some((original, _)) => original,
+                        None => original,
+                    };
+                    distribution.insert(original.to_string(), count);
+                }
             }
         }
 
@@ -131,18 +187,23 @@ impl<'a> FacetDistribution<'a> {
         &self,
         field_id: FieldId,
         candidates: &RoaringBitmap,
+        max_values: usize,
         distribution: &mut BTreeMap<String, u64>,
     ) -> heed::Result<()> {
+        let booleans = self.boolean_docids(field_id)?;
         let iter =
             FacetNumberIter::new_non_reducing(self.rtxn, self.index, field_id, candidates.clone())?;
 
         for result in iter {
             let (value, mut docids) = result?;
             docids &= candidates;
+            if let Some(boolean_docids) = booleans.of(value) {
+                docids -= boolean_docids;
+            }
             if !docids.is_empty() {
                 distribution.insert(value.to_string(), docids.len());
             }
-            if distribution.len() == self.max_values_per_facet {
+            if distribution.len() == max_values {
                 break;
             }
         }
@@ -154,6 +215,7 @@ impl<'a> FacetDistribution<'a> {
         &self,
         field_id: FieldId,
         candidates: &RoaringBitmap,
+        max_values: usize,
         distribution: &mut BTreeMap<String, u64>,
     ) -> heed::Result<()> {
         let iter =
@@ -165,7 +227,7 @@ impl<'a> FacetDistribution<'a> {
             if !docids.is_empty() {
                 distribution.insert(original.to_string(), docids.len());
             }
-            if distribution.len() == self.max_values_per_facet {
+            if distribution.len() == max_values {
                 break;
             }
         }
@@ -178,16 +240,40 @@ impl<'a> FacetDistribution<'a> {
     fn facet_values_from_raw_facet_database(
         &self,
         field_id: FieldId,
+        soft_deleted_documents: &RoaringBitmap,
+        max_values: usize,
     ) -> heed::Result<BTreeMap<String, u64>> {
         let mut distribution = BTreeMap::new();
+        let booleans = self.boolean_docids(field_id)?;
 
-        let db = self.index.facet_id_f64_docids;
-        let range = FacetNumberRange::new(self.rtxn, db, field_id, 0, Unbounded, Unbounded)?;
+        // We count the documents directly from the serialized bitmaps to avoid decoding them.
+        let db = self.index.facet_id_f64_docids.remap_data_type::<ByteSlice>();
+        let left = (field_id, 0, f64::MIN, f64::MIN);
+        let right = (field_id, 0, f64::MAX, f64::MAX);
 
-        for result in range {
-            let ((_, _, value, _), docids) = result?;
-            distribution.insert(value.to_string(), docids.len());
-            if distribution.len() == self.max_values_per_facet {
+        for result in db.range(self.rtxn, &(left..=right))? {
+            let ((_, _, value, _), bytes) = result?;
+            let count = match booleans.of(value) {
+                Some(boolean_docids) => {
+                    let docids = CboRoaringBitmapCodec::deserialize_from(bytes)
+                        .map_err(|_| heed::Error::Decoding)?;
+                    let docids = docids - boolean_docids;
+                    docids.len() - docids.intersection_len(soft_deleted_documents)
+                }
+                None => {
+                    let len = CboRoaringBitmapCodec::deserialize_len_from(bytes)
+                        .map_err(|_| heed::Error::Decoding)?;
+                    let deleted =
+                        CboRoaringBitmapCodec::intersection_len(bytes, soft_deleted_documents)
+                            .map_err(|_| heed::Error::Decoding)?;
+                    len - deleted
+                }
+            };
+            if count == 0 {
+                continue;
+            }
+            distribution.insert(value.to_string(), count);
+            if distribution.len() == max_values {
                 break;
             }
         }
@@ -197,13 +283,23 @@ impl<'a> FacetDistribution<'a> {
             .facet_id_string_docids
             .remap_key_type::<ByteSlice>()
             .prefix_iter(self.rtxn, &field_id.to_be_bytes())?
-            .remap_key_type::<FacetStringLevelZeroCodec>();
+            .remap_types::<FacetStringLevelZeroCodec, ByteSlice>();
 
         let mut normalized_distribution = BTreeMap::new();
         for result in iter {
-            let ((_, normalized_value), (original_value, docids)) = result?;
-            normalized_distribution.insert(normalized_value, (original_value, docids.len()));
-            if normalized_distribution.len() == self.max_values_per_facet {
+            let ((_, normalized_value), bytes) = result?;
+            let (original_value, bytes) =
+                decode_prefix_string(bytes).ok_or(heed::Error::Decoding)?;
+            let len = RoaringBitmapLenCodec::deserialize_from_slice(bytes)
+                .map_err(|_| heed::Error::Decoding)?;
+            let deleted = RoaringBitmapLenCodec::intersection_len(bytes, soft_deleted_documents)
+                .map_err(|_| heed::Error::Decoding)?;
+            let count = len - deleted;
+            if count == 0 {
+                continue;
+            }
+            normalized_distribution.insert(normalized_value, (original_value, count));
+            if normalized_distribution.len() == max_values {
                 break;
             }
         }
@@ -216,48 +312,217 @@ impl<'a> FacetDistribution<'a> {
         Ok(distribution)
     }
 
-    fn facet_values(&self, field_id: FieldId) -> heed::Result<BTreeMap<String, u64>> {
+    /// Returns the distribution of the values of a facet, truncated to the maximum
+    /// number of values by facet according to the given order.
+    fn facet_values(
+        &self,
+        field_id: FieldId,
+        candidates: Option<&RoaringBitmap>,
+        soft_deleted_documents: &RoaringBitmap,
+        order: OrderBy,
+    ) -> heed::Result<BTreeMap<String, u64>> {
+        match order {
+            OrderBy::Alpha => self.ordered_facet_values(
+                field_id,
+                candidates,
+                soft_deleted_documents,
+                self.max_values_per_facet,
+            ),
+            OrderBy::Count => {
+                // All the values must be counted to know which ones have the highest counts.
+                let distribution = self.ordered_facet_values(
+                    field_id,
+                    candidates,
+                    soft_deleted_documents,
+                    usize::MAX,
+                )?;
+                let mut values: Vec<_> = distribution.into_iter().collect();
+                values.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+                values.truncate(self.max_values_per_facet);
+                Ok(values.into_iter().collect())
+            }
+        }
+    }
+
+    /// Returns the distribution of the first `max_values` numbers
+    /// and the first `max_values` strings of a facet.
+    fn ordered_facet_values(
+        &self,
+        field_id: FieldId,
+        candidates: Option<&RoaringBitmap>,
+        soft_deleted_documents: &RoaringBitmap,
+        max_values: usize,
+    ) -> heed::Result<BTreeMap<String, u64>> {
         use FacetType::{Number, String};
 
-        match self.candidates {
-            Some(ref candidates) => {
+        match candidates {
+            Some(candidates) => {
                 // Classic search, candidates were specified, we must return facet values only related
                 // to those candidates. We also enter here for facet strings for performance reasons.
+                let candidates = &(candidates - soft_deleted_documents);
                 let mut distribution = BTreeMap::new();
-                if candidates.len() <= CANDIDATES_THRESHOLD {
+                if candidates.len() <= self.candidates_threshold {
                     self.facet_distribution_from_documents(
                         field_id,
                         Number,
                         candidates,
+                        max_values,
                         &mut distribution,
                     )?;
                     self.facet_distribution_from_documents(
                         field_id,
                         String,
                         candidates,
+                        max_values,
                         &mut distribution,
                     )?;
                 } else {
                     self.facet_numbers_distribution_from_facet_levels(
                         field_id,
                         candidates,
+                        max_values,
                         &mut distribution,
                     )?;
                     self.facet_strings_distribution_from_facet_levels(
                         field_id,
                         candidates,
+                        max_values,
                         &mut distribution,
                     )?;
                 }
                 Ok(distribution)
             }
-            None => self.facet_values_from_raw_facet_database(field_id),
+            None => self.facet_values_from_raw_facet_database(
+                field_id,
+                soft_deleted_documents,
+                max_values,
+            ),
         }
     }
 
+    /// Returns the id of a field that accepts a facet distribution along with the
+    /// candidates to distribute, `None` if the field doesn't appear in any document.
+    fn distributed_field(&self, field: &str) -> Result<Option<(FieldId, RoaringBitmap)>> {
+        let filterable_fields = self.index.filterable_fields(self.rtxn)?;
+        if !crate::is_faceted(field, &filterable_fields) {
+            let invalid_facets_name = std::iter::once(field.to_string()).collect();
+            return Err(UserError::InvalidFacetsDistribution { invalid_facets_name }.into());
+        }
+        let no_distribution_fields = self.index.no_distribution_fields(self.rtxn)?;
+        if crate::is_faceted(field, &no_distribution_fields) {
+            let facets_name = std::iter::once(field.to_string()).collect();
+            return Err(UserError::FacetDistributionDisabled { facets_name }.into());
+        }
+
+        let field_id = match self.index.fields_ids_map(self.rtxn)?.id(field) {
+            Some(field_id) => field_id,
+            None => return Ok(None),
+        };
+
+        let mut candidates = match &self.candidates {
+            Some(candidates) => candidates - self.index.soft_deleted_documents_ids(self.rtxn)?,
+            None => self.index.documents_ids(self.rtxn)?,
+        };
+        if let Some(range) = &self.candidates_range {
+            retain_range(&mut candidates, range);
+        }
+
+        Ok(Some((field_id, candidates)))
+    }
+
+    /// Calls the callback with the normalized and the original form of every string value
+    /// of the facet in normalized order, along with its number of candidates and the
+    /// smallest of them. The original string is read from the facet database itself,
+    /// no lookup by document is needed.
+    ///
+    /// The iteration stops when the callback returns `ControlFlow::Break`,
+    /// the maximum number of values by facet is ignored.
+    pub fn string_distribution_iter<F>(&self, field: &str, mut callback: F) -> Result<()>
+    where
+        F: FnMut(&str, &str, u64, DocumentId) -> Result<ControlFlow<()>>,
+    {
+        let (field_id, candidates) = match self.distributed_field(field)? {
+            Some(field) => field,
+            None => return Ok(()),
+        };
+        self.iter_string_values(field_id, &candidates, &mut callback)
+    }
+
+    fn iter_string_values<F>(
+        &self,
+        field_id: FieldId,
+        candidates: &RoaringBitmap,
+        callback: &mut F,
+    ) -> Result<()>
+    where
+        F: FnMut(&str, &str, u64, DocumentId) -> Result<ControlFlow<()>>,
+    {
+        let iter =
+            FacetStringIter::new_non_reducing(self.rtxn, self.index, field_id, candidates.clone())?;
+        for result in iter {
+            let (normalized, original, mut docids) = result?;
+            docids &= candidates;
+            if let Some(sample_docid) = docids.min() {
+                if callback(normalized, original, docids.len(), sample_docid)?.is_break() {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the distribution of the values of a single facet, whatever their type, in the
+    /// order of the filters: the numbers in numeric order then the strings in normalized order.
+    ///
+    /// The numbers and the strings share the maximum number of values by facet, the
+    /// `10` number and the `10` string are therefore two distinct values of the result.
+    pub fn merged_distribution(&self, field: &str) -> Result<Vec<(FacetValue, u64)>> {
+        // The candidates are computed once for both databases.
+        let (field_id, candidates) = match self.distributed_field(field)? {
+            Some(field) => field,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut distribution = Vec::new();
+        if self.max_values_per_facet == 0 {
+            return Ok(distribution);
+        }
+
+        let booleans = self.boolean_docids(field_id)?;
+        let iter =
+            FacetNumberIter::new_non_reducing(self.rtxn, self.index, field_id, candidates.clone())?;
+        for result in iter {
+            let (value, mut docids) = result?;
+            docids &= &candidates;
+            if let Some(boolean_docids) = booleans.of(value) {
+                docids -= boolean_docids;
+            }
+            if !docids.is_empty() {
+                distribution.push((FacetValue::from(value), docids.len()));
+                if distribution.len() == self.max_values_per_facet {
+                    return Ok(distribution);
+                }
+            }
+        }
+
+        let max_values = self.max_values_per_facet;
+        self.iter_string_values(field_id, &candidates, &mut |_normalized, original, count, _| {
+            distribution.push((FacetValue::from(original), count));
+            if distribution.len() == max_values {
+                Ok(ControlFlow::Break(()))
+            } else {
+                Ok(ControlFlow::Continue(()))
+            }
+        })?;
+
+        Ok(distribution)
+    }
+
     pub fn execute(&self) -> Result<BTreeMap<String, BTreeMap<String, u64>>> {
         let fields_ids_map = self.index.fields_ids_map(self.rtxn)?;
         let filterable_fields = self.index.filterable_fields(self.rtxn)?;
+        let no_distribution_fields = self.index.no_distribution_fields(self.rtxn)?;
+        let sort_facet_values_by = self.index.sort_facet_values_by(self.rtxn)?;
 
         let fields = match self.facets {
             Some(ref facets) => {
@@ -270,17 +535,50 @@ impl<'a> FacetDistribution<'a> {
                         invalid_facets_name: invalid_fields.into_iter().cloned().collect(),
                     }
                     .into());
-                } else {
-                    facets.clone()
                 }
+
+                let disabled_fields: HashSet<_> = facets
+                    .iter()
+                    .filter(|facet| crate::is_faceted(facet, &no_distribution_fields))
+                    .collect();
+                if !disabled_fields.is_empty() {
+                    return Err(UserError::FacetDistributionDisabled {
+                        facets_name: disabled_fields.into_iter().cloned().collect(),
+                    }
+                    .into());
+                }
+
+                facets.clone()
             }
             None => filterable_fields,
         };
 
+        // The soft deleted documents are still present in the facet databases.
+        let soft_deleted_documents = self.index.soft_deleted_documents_ids(self.rtxn)?;
+
+        // The range restricts the candidates or, if there are none, all the documents.
+        let restricted_candidates = match &self.candidates_range {
+            Some(range) => {
+                let mut candidates = match &self.candidates {
+                    Some(candidates) => candidates.clone(),
+                    None => self.index.documents_ids(self.rtxn)?,
+                };
+                retain_range(&mut candidates, range);
+                Some(candidates)
+            }
+            None => None,
+        };
+        let candidates = restricted_candidates.as_ref().or(self.candidates.as_ref());
+
         let mut distribution = BTreeMap::new();
         for (fid, name) in fields_ids_map.iter() {
-            if crate::is_faceted(name, &fields) {
-                let values = self.facet_values(fid)?;
+            if crate::is_faceted(name, &fields) && !crate::is_faceted(name, &no_distribution_fields)
+            {
+                let order = sort_facet_values_by
+                    .iter()
+                    .find(|(field, _)| crate::is_faceted_by(name, field))
+                    .map_or_else(OrderBy::default, |(_, order)| *order);
+                let values = self.facet_values(fid, candidates, &soft_deleted_documents, order)?;
                 distribution.insert(name.to_string(), values);
             }
         }
@@ -289,15 +587,440 @@ impl<'a> FacetDistribution<'a> {
     }
 }
 
+/// Booleans are indexed as both the `0`/`1` numbers and the `false`/`true` strings,
+/// the numbers are only used to sort and filter and are hidden from the distribution.
+struct BooleanDocids {
+    falses: RoaringBitmap,
+    trues: RoaringBitmap,
+}
+
+impl BooleanDocids {
+    /// Returns the documents for which this number is the representation of a boolean.
+    fn of(&self, value: f64) -> Option<&RoaringBitmap> {
+        if value == 0.0 {
+            Some(&self.falses)
+        } else if value == 1.0 {
+            Some(&self.trues)
+        } else {
+            None
+        }
+    }
+}
+
 impl fmt::Debug for FacetDistribution<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let FacetDistribution { facets, candidates, max_values_per_facet, rtxn: _, index: _ } =
-            self;
+        let FacetDistribution {
+            facets,
+            candidates,
+            candidates_range,
+            max_values_per_facet,
+            candidates_threshold,
+            rtxn: _,
+            index: _,
+        } = self;
 
         f.debug_struct("FacetDistribution")
             .field("facets", facets)
             .field("candidates", candidates)
+            .field("candidates_range", candidates_range)
             .field("max_values_per_facet", max_values_per_facet)
+            .field("candidates_threshold", candidates_threshold)
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use big_s::S;
+    use maplit::{btreemap, hashmap, hashset};
+    use serde_json::json;
+
+    use super::*;
+    use crate::index::tests::TempIndex;
+    use crate::update::DeleteDocuments;
+
+    fn new_index() -> TempIndex {
+        let index = TempIndex::new();
+        index
+            .update_settings(|settings| {
+                settings.set_filterable_fields(hashset! { S("brand") });
+            })
+            .unwrap();
+        index
+    }
+
+    /// Returns the distribution computed from the facet database and from the documents.
+    fn distributions(index: &Index) -> [BTreeMap<String, BTreeMap<String, u64>>; 2] {
+        let rtxn = index.read_txn().unwrap();
+        let candidates = index.documents_ids(&rtxn).unwrap();
+        [
+            FacetDistribution::new(&rtxn, index).execute().unwrap(),
+            FacetDistribution::new(&rtxn, index).candidates(candidates).execute().unwrap(),
+        ]
+    }
+
+    #[test]
+    fn original_facet_string_does_not_depend_on_the_documents_order() {
+        let documents = [
+            json!({ "id": 0, "brand": "iphone" }),
+            json!({ "id": 1, "brand": "iPhone" }),
+            json!({ "id": 2, "brand": "IPhone" }),
+        ];
+        let expected = btreemap! { S("brand") => btreemap! { S("IPhone") => 3 } };
+
+        for order in [[0, 1, 2], [2, 1, 0], [1, 2, 0]] {
+            // one document addition by document
+            let index = new_index();
+            for i in order {
+                let document = documents[i].clone();
+                index.add_documents(documents!(document)).unwrap();
+            }
+            assert_eq!(distributions(&index), [expected.clone(), expected.clone()]);
+
+            // all the documents in the same addition
+            let index = new_index();
+            let batch: Vec<_> = order.iter().map(|&i| documents[i].clone()).collect();
+            index.add_documents(documents!(batch)).unwrap();
+            assert_eq!(distributions(&index), [expected.clone(), expected.clone()]);
+        }
+    }
+
+    #[test]
+    fn original_facet_string_is_recomputed_on_deletion() {
+        let index = new_index();
+        index
+            .add_documents(documents!([
+                { "id": 0, "brand": "iphone" },
+                { "id": 1, "brand": "iPhone" },
+                { "id": 2, "brand": "IPhone" },
+            ]))
+            .unwrap();
+
+        let mut wtxn = index.write_txn().unwrap();
+        let mut builder = DeleteDocuments::new(&mut wtxn, &index).unwrap();
+        builder.soft_deleted_threshold(0.0);
+        builder.delete_external_id("2").unwrap();
+        builder.execute().unwrap();
+        wtxn.commit().unwrap();
+
+        let expected = btreemap! { S("brand") => btreemap! { S("iPhone") => 2 } };
+        assert_eq!(distributions(&index), [expected.clone(), expected]);
+
+        // adding it back gives the same original string as before
+        index.add_documents(documents!([{ "id": 2, "brand": "IPhone" }])).unwrap();
+        let expected = btreemap! { S("brand") => btreemap! { S("IPhone") => 3 } };
+        assert_eq!(distributions(&index), [expected.clone(), expected]);
+    }
+
+    #[test]
+    fn booleans_are_not_counted_as_numbers() {
+        let index = new_index();
+        index
+            .add_documents(documents!([
+                { "id": 0, "brand": true },
+                { "id": 1, "brand": false },
+                { "id": 2, "brand": [true, 3] },
+                { "id": 3, "brand": 1 },
+                { "id": 4, "brand": "true" },
+                { "id": 5, "brand": 0 },
+            ]))
+            .unwrap();
+
+        let expected = btreemap! {
+            S("brand") => btreemap! {
+                S("0") => 1, S("1") => 1, S("3") => 1, S("false") => 1, S("true") => 3,
+            },
+        };
+        assert_eq!(distributions(&index), [expected.clone(), expected]);
+    }
+
+    #[test]
+    fn both_strategies_return_the_same_distribution() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|settings| {
+                settings.set_filterable_fields(hashset! { S("brand"), S("price") });
+            })
+            .unwrap();
+
+        let brands = ["iPhone", "iphone", "Galaxy", "pixel", "Pixel"];
+        let documents: Vec<_> = (0..200)
+            .map(|i| match i % 7 {
+                0 => json!({ "id": i, "brand": brands[i % brands.len()], "price": i % 13 }),
+                1 => json!({ "id": i, "brand": [brands[i % 3], i % 2 == 0], "price": i / 4 }),
+                2 => json!({ "id": i, "price": [i % 5, i as f64 / 8.0] }),
+                3 => json!({ "id": i, "brand": i % 3 == 0 }),
+                _ => json!({ "id": i, "brand": brands[i % 4], "price": i % 17 }),
+            })
+            .collect();
+        index.add_documents(documents!(documents)).unwrap();
+
+        let mut wtxn = index.write_txn().unwrap();
+        let mut builder = DeleteDocuments::new(&mut wtxn, &index).unwrap();
+        builder.soft_deleted_threshold(1.0);
+        for id in ["10", "11", "42", "150"] {
+            builder.delete_external_id(id).unwrap();
+        }
+        builder.execute().unwrap();
+        wtxn.commit().unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let documents_ids = index.documents_ids(&rtxn).unwrap();
+        let soft_deleted = index.soft_deleted_documents_ids(&rtxn).unwrap();
+        assert!(!soft_deleted.is_empty());
+
+        let all: RoaringBitmap = (0..200).collect();
+        let evens: RoaringBitmap = (0..200).filter(|i| i % 2 == 0).collect();
+        let few: RoaringBitmap = [3, 10, 17, 64, 199].iter().copied().collect();
+        let single: RoaringBitmap = [7].iter().copied().collect();
+        for candidates in [documents_ids, all, evens, few, single, RoaringBitmap::new()] {
+            let from_documents = FacetDistribution::new(&rtxn, &index)
+                .candidates(candidates.clone())
+                .candidates_threshold(u64::MAX)
+                .execute()
+                .unwrap();
+            let from_facet_levels = FacetDistribution::new(&rtxn, &index)
+                .candidates(candidates.clone())
+                .candidates_threshold(0)
+                .execute()
+                .unwrap();
+            assert_eq!(from_documents, from_facet_levels, "candidates: {:?}", candidates);
+        }
+    }
+
+    #[test]
+    fn truncation_keeps_the_values_of_the_facet_order() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|settings| {
+                settings.set_filterable_fields(hashset! { S("genres"), S("size") });
+                settings.set_sort_facet_values_by(hashmap! { S("genres") => OrderBy::Count });
+            })
+            .unwrap();
+
+        let genres =
+            [("rock", 44, 5), ("jazz", 42, 4), ("pop", 40, 3), ("acid", 38, 2), ("blues", 36, 1)];
+        let mut documents = Vec::new();
+        for (genre, size, count) in genres {
+            for _ in 0..count {
+                documents.push(json!({ "id": documents.len(), "genres": genre, "size": size }));
+            }
+        }
+        index.add_documents(documents!(documents)).unwrap();
+
+        let distributions = || {
+            let rtxn = index.read_txn().unwrap();
+            let candidates = index.documents_ids(&rtxn).unwrap();
+            let mut distributions = Vec::new();
+            for threshold in [None, Some(u64::MAX), Some(0)] {
+                let mut distribution = FacetDistribution::new(&rtxn, &index);
+                distribution.max_values_per_facet(2);
+                if let Some(threshold) = threshold {
+                    distribution.candidates(candidates.clone()).candidates_threshold(threshold);
+                }
+                distributions.push(distribution.execute().unwrap());
+            }
+            distributions
+        };
+
+        let expected = btreemap! {
+            S("genres") => btreemap! { S("rock") => 5, S("jazz") => 4 },
+            S("size") => btreemap! { S("36") => 1, S("38") => 2 },
+        };
+        assert_eq!(distributions(), vec![expected.clone(), expected.clone(), expected]);
+
+        index
+            .update_settings(|settings| {
+                let orders = hashmap! { S("size") => OrderBy::Count };
+                settings.set_sort_facet_values_by(orders);
+            })
+            .unwrap();
+        let expected = btreemap! {
+            S("genres") => btreemap! { S("acid") => 2, S("blues") => 1 },
+            S("size") => btreemap! { S("44") => 5, S("42") => 4 },
+        };
+        assert_eq!(distributions(), vec![expected.clone(), expected.clone(), expected]);
+    }
+
+    #[test]
+    fn merged_distribution_orders_numbers_before_strings() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|settings| {
+                settings.set_filterable_fields(hashset! { S("value") });
+            })
+            .unwrap();
+        let values = [
+            json!(10),
+            json!("10"),
+            json!(2),
+            json!("b"),
+            json!("A"),
+            json!(1.5),
+            json!("apple"),
+            json!(10),
+            json!("a"),
+            json!(true),
+            json!("10"),
+            json!(2),
+        ];
+        let documents: Vec<_> = values
+            .iter()
+            .enumerate()
+            .map(|(id, value)| json!({ "id": id, "value": value }))
+            .collect();
+        index.add_documents(documents!(documents)).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let merged = |max_values: usize, candidates: Option<RoaringBitmap>| {
+            let mut distribution = FacetDistribution::new(&rtxn, &index);
+            distribution.max_values_per_facet(max_values);
+            if let Some(candidates) = candidates {
+                distribution.candidates(candidates);
+            }
+            distribution.merged_distribution("value").unwrap()
+        };
+
+        let expected = vec![
+            (FacetValue::from(1.5), 1),
+            (FacetValue::from(2.0), 2),
+            (FacetValue::from(10.0), 2),
+            (FacetValue::from("10"), 2),
+            (FacetValue::from("A"), 2),
+            (FacetValue::from("apple"), 1),
+            (FacetValue::from("b"), 1),
+            (FacetValue::from("true"), 1),
+        ];
+        assert_eq!(merged(100, None), expected);
+        assert_eq!(merged(100, Some(index.documents_ids(&rtxn).unwrap())), expected);
+
+        // A single truncation applies to the numbers and the strings.
+        assert_eq!(merged(4, None), expected[..4]);
+        assert_eq!(merged(2, None), expected[..2]);
+
+        // The documents 1, 2 and 9 are the string 10, the number 2 and the boolean.
+        let candidates = [1, 2, 9].iter().copied().collect();
+        let expected = vec![
+            (FacetValue::from(2.0), 1),
+            (FacetValue::from("10"), 1),
+            (FacetValue::from("true"), 1),
+        ];
+        assert_eq!(merged(100, Some(candidates)), expected);
+
+        let error = FacetDistribution::new(&rtxn, &index).merged_distribution("id").unwrap_err();
+        assert!(matches!(
+            error,
+            crate::Error::UserError(UserError::InvalidFacetsDistribution { .. })
+        ));
+    }
+
+    #[test]
+    fn string_distribution_iter_yields_the_normalized_and_original_strings() {
+        let index = new_index();
+        index
+            .add_documents(documents!([
+                { "id": 0, "brand": "blue" },
+                { "id": 1, "brand": "Blue" },
+                { "id": 2, "brand": "BLUE" },
+                { "id": 3, "brand": "red" },
+                { "id": 4, "brand": ["Red", 3] },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let values = |candidates: Option<RoaringBitmap>, limit: usize| {
+            let mut distribution = FacetDistribution::new(&rtxn, &index);
+            if let Some(candidates) = candidates {
+                distribution.candidates(candidates);
+            }
+            let mut values = Vec::new();
+            distribution
+                .string_distribution_iter("brand", |normalized, original, count, docid| {
+                    values.push((normalized.to_string(), original.to_string(), count, docid));
+                    if values.len() == limit {
+                        Ok(ControlFlow::Break(()))
+                    } else {
+                        Ok(ControlFlow::Continue(()))
+                    }
+                })
+                .unwrap();
+            values
+        };
+
+        // The original casing is the same whatever the documents contributing to the value.
+        let expected = vec![(S("blue"), S("BLUE"), 3, 0), (S("red"), S("Red"), 2, 3)];
+        assert_eq!(values(None, usize::MAX), expected);
+        assert_eq!(values(None, 1), expected[..1]);
+
+        let candidates = [1, 4].iter().copied().collect();
+        let expected = vec![(S("blue"), S("BLUE"), 1, 1), (S("red"), S("Red"), 1, 4)];
+        assert_eq!(values(Some(candidates), usize::MAX), expected);
+    }
+
+    #[test]
+    fn sharded_distributions_sum_to_the_unsharded_distribution() {
+        let index = new_index();
+        let brands = ["iPhone", "Galaxy", "Pixel"];
+        let documents: Vec<_> =
+            (0..90).map(|i| json!({ "id": i, "brand": brands[i % 7 % 3] })).collect();
+        index.add_documents(documents!(documents)).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let evens: RoaringBitmap = (0..90).filter(|i| i % 2 == 0).collect();
+        for candidates in [None, Some(evens)] {
+            let distribution = |range: Option<RangeInclusive<DocumentId>>| {
+                let mut distribution = FacetDistribution::new(&rtxn, &index);
+                if let Some(candidates) = candidates.clone() {
+                    distribution.candidates(candidates);
+                }
+                if let Some(range) = range {
+                    distribution.candidates_within(range);
+                }
+                distribution.execute().unwrap()
+            };
+
+            let mut sharded: BTreeMap<String, BTreeMap<String, u64>> = BTreeMap::new();
+            for range in [0..=29, 30..=44, 45..=u32::MAX] {
+                for (field, values) in distribution(Some(range)) {
+                    let counts = sharded.entry(field).or_default();
+                    for (value, count) in values {
+                        *counts.entry(value).or_default() += count;
+                    }
+                }
+            }
+            assert_eq!(sharded, distribution(None), "candidates: {:?}", candidates);
+        }
+    }
+
+    #[test]
+    fn corrupted_facet_databases_return_errors() {
+        let index = new_index();
+        index
+            .add_documents(documents!([
+                { "id": 0, "brand": "Apple" },
+                { "id": 1, "brand": "Samsung" },
+            ]))
+            .unwrap();
+
+        let mut wtxn = index.write_txn().unwrap();
+        let brand = index.fields_ids_map(&wtxn).unwrap().id("brand").unwrap();
+        // The original string of a document is not valid UTF-8.
+        index
+            .field_id_docid_facet_strings
+            .remap_data_type::<ByteSlice>()
+            .put(&mut wtxn, &(brand, 0, "apple"), &[0xff][..])
+            .unwrap();
+        // The value of a facet string is missing its original string and its documents ids.
+        index
+            .facet_id_string_docids
+            .remap_data_type::<ByteSlice>()
+            .put(&mut wtxn, &(brand, "samsung"), &[][..])
+            .unwrap();
+        wtxn.commit().unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let candidates = index.documents_ids(&rtxn).unwrap();
+        assert!(FacetDistribution::new(&rtxn, &index).execute().is_err());
+        assert!(FacetDistribution::new(&rtxn, &index).candidates(candidates).execute().is_err());
+    }
+}
@@ -5,6 +5,7 @@ use heed::types::{ByteSlice, DecodeIgnore};
 use heed::{Database, LazyDecode, RoRange, RoRevRange};
 use roaring::RoaringBitmap;
 
+use super::Candidates;
 use crate::heed_codec::facet::FacetLevelValueF64Codec;
 use crate::heed_codec::CboRoaringBitmapCodec;
 use crate::{FieldId, Index};
@@ -118,6 +119,9 @@ pub struct FacetNumberIter<'t> {
     field_id: FieldId,
     level_iters: Vec<(RoaringBitmap, Either<FacetNumberRange<'t>, FacetNumberRevRange<'t>>)>,
     must_reduce: bool,
+    /// When all the documents are candidates, the documents already returned
+    /// and the iterator over the level 0, the other levels are not used.
+    all: Option<(RoaringBitmap, Either<FacetNumberRange<'t>, FacetNumberRevRange<'t>>)>,
 }
 
 impl<'t> FacetNumberIter<'t> {
@@ -128,14 +132,30 @@ impl<'t> FacetNumberIter<'t> {
         rtxn: &'t heed::RoTxn,
         index: &'t Index,
         field_id: FieldId,
-        documents_ids: RoaringBitmap,
+        candidates: Candidates,
     ) -> heed::Result<FacetNumberIter<'t>> {
         let db = index.facet_id_f64_docids.remap_key_type::<FacetLevelValueF64Codec>();
+        let documents_ids = match candidates {
+            Candidates::All => {
+                let zero_iter = FacetNumberRange::new(rtxn, db, field_id, 0, Unbounded, Unbounded)?;
+                let all = Some((RoaringBitmap::new(), Left(zero_iter)));
+                let level_iters = Vec::new();
+                return Ok(FacetNumberIter {
+                    rtxn,
+                    db,
+                    field_id,
+                    level_iters,
+                    must_reduce: true,
+                    all,
+                });
+            }
+            Candidates::Bitmap(documents_ids) => documents_ids,
+        };
         let highest_level = Self::highest_level(rtxn, db, field_id)?.unwrap_or(0);
         let highest_iter =
             FacetNumberRange::new(rtxn, db, field_id, highest_level, Unbounded, Unbounded)?;
         let level_iters = vec![(documents_ids, Left(highest_iter))];
-        Ok(FacetNumberIter { rtxn, db, field_id, level_iters, must_reduce: true })
+        Ok(FacetNumberIter { rtxn, db, field_id, level_iters, must_reduce: true, all: None })
     }
 
     /// Create a `FacetNumberIter` that will iterate on the different facet entries in reverse
@@ -145,14 +165,31 @@ impl<'t> FacetNumberIter<'t> {
         rtxn: &'t heed::RoTxn,
         index: &'t Index,
         field_id: FieldId,
-        documents_ids: RoaringBitmap,
+        candidates: Candidates,
     ) -> heed::Result<FacetNumberIter<'t>> {
         let db = index.facet_id_f64_docids;
+        let documents_ids = match candidates {
+            Candidates::All => {
+                let zero_iter =
+                    FacetNumberRevRange::new(rtxn, db, field_id, 0, Unbounded, Unbounded)?;
+                let all = Some((RoaringBitmap::new(), Right(zero_iter)));
+                let level_iters = Vec::new();
+                return Ok(FacetNumberIter {
+                    rtxn,
+                    db,
+                    field_id,
+                    level_iters,
+                    must_reduce: true,
+                    all,
+                });
+            }
+            Candidates::Bitmap(documents_ids) => documents_ids,
+        };
         let highest_level = Self::highest_level(rtxn, db, field_id)?.unwrap_or(0);
         let highest_iter =
             FacetNumberRevRange::new(rtxn, db, field_id, highest_level, Unbounded, Unbounded)?;
         let level_iters = vec![(documents_ids, Right(highest_iter))];
-        Ok(FacetNumberIter { rtxn, db, field_id, level_iters, must_reduce: true })
+        Ok(FacetNumberIter { rtxn, db, field_id, level_iters, must_reduce: true, all: None })
     }
 
     /// Create a `FacetNumberIter` that will iterate on the different facet entries
@@ -170,7 +207,7 @@ impl<'t> FacetNumberIter<'t> {
         let highest_iter =
             FacetNumberRange::new(rtxn, db, field_id, highest_level, Unbounded, Unbounded)?;
         let level_iters = vec![(documents_ids, Left(highest_iter))];
-        Ok(FacetNumberIter { rtxn, db, field_id, level_iters, must_reduce: false })
+        Ok(FacetNumberIter { rtxn, db, field_id, level_iters, must_reduce: false, all: None })
     }
 
     fn highest_level<X>(
@@ -193,6 +230,24 @@ impl<'t> Iterator for FacetNumberIter<'t> {
     type Item = heed::Result<(f64, RoaringBitmap)>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some((returned, zero_iter)) = &mut self.all {
+            for result in zero_iter {
+                match result {
+                    Ok(((_fid, _level, left, _right), mut docids)) => {
+                        // A document is only returned with its first facet value.
+                        docids -= &*returned;
+                        if !docids.is_empty() {
+                            *returned |= &docids;
+                            return Some(Ok((left, docids)));
+                        }
+                    }
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            self.all = None;
+            return None;
+        }
+
         'outer: loop {
             let (documents_ids, last) = self.level_iters.last_mut()?;
             let is_ascending = last.is_left();
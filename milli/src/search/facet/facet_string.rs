@@ -134,6 +134,7 @@ use heed::types::{ByteSlice, DecodeIgnore};
 use heed::{Database, LazyDecode, RoRange, RoRevRange};
 use roaring::RoaringBitmap;
 
+use super::Candidates;
 use crate::heed_codec::facet::{
     FacetLevelValueU32Codec, FacetStringLevelZeroCodec, FacetStringLevelZeroValueCodec,
     FacetStringZeroBoundsValueCodec,
@@ -412,6 +413,12 @@ pub struct FacetStringIter<'t> {
     field_id: FieldId,
     level_iters: Vec<(RoaringBitmap, Either<EitherStringRange<'t>, EitherStringRevRange<'t>>)>,
     must_reduce: bool,
+    /// When all the documents are candidates, the documents already returned
+    /// and the iterator over the level 0, the other levels are not used.
+    all: Option<(
+        RoaringBitmap,
+        Either<FacetStringLevelZeroRange<'t>, FacetStringLevelZeroRevRange<'t>>,
+    )>,
 }
 
 impl<'t> FacetStringIter<'t> {
@@ -419,34 +426,68 @@ impl<'t> FacetStringIter<'t> {
         rtxn: &'t heed::RoTxn,
         index: &'t Index,
         field_id: FieldId,
-        documents_ids: RoaringBitmap,
+        candidates: Candidates,
     ) -> heed::Result<FacetStringIter<'t>> {
         let db = index.facet_id_string_docids.remap_types::<ByteSlice, ByteSlice>();
-        let highest_iter = Self::highest_iter(rtxn, index, db, field_id)?;
-        Ok(FacetStringIter {
-            rtxn,
-            db,
-            field_id,
-            level_iters: vec![(documents_ids, Left(highest_iter))],
-            must_reduce: true,
-        })
+        match candidates {
+            Candidates::All => {
+                let zero_iter =
+                    FacetStringLevelZeroRange::new(rtxn, db, field_id, Unbounded, Unbounded)?;
+                Ok(FacetStringIter {
+                    rtxn,
+                    db,
+                    field_id,
+                    level_iters: Vec::new(),
+                    must_reduce: true,
+                    all: Some((RoaringBitmap::new(), Left(zero_iter))),
+                })
+            }
+            Candidates::Bitmap(documents_ids) => {
+                let highest_iter = Self::highest_iter(rtxn, index, db, field_id)?;
+                Ok(FacetStringIter {
+                    rtxn,
+                    db,
+                    field_id,
+                    level_iters: vec![(documents_ids, Left(highest_iter))],
+                    must_reduce: true,
+                    all: None,
+                })
+            }
+        }
     }
 
     pub fn new_reverse_reducing(
         rtxn: &'t heed::RoTxn,
         index: &'t Index,
         field_id: FieldId,
-        documents_ids: RoaringBitmap,
+        candidates: Candidates,
     ) -> heed::Result<FacetStringIter<'t>> {
         let db = index.facet_id_string_docids.remap_types::<ByteSlice, ByteSlice>();
-        let highest_reverse_iter = Self::highest_reverse_iter(rtxn, index, db, field_id)?;
-        Ok(FacetStringIter {
-            rtxn,
-            db,
-            field_id,
-            level_iters: vec![(documents_ids, Right(highest_reverse_iter))],
-            must_reduce: true,
-        })
+        match candidates {
+            Candidates::All => {
+                let zero_iter =
+                    FacetStringLevelZeroRevRange::new(rtxn, db, field_id, Unbounded, Unbounded)?;
+                Ok(FacetStringIter {
+                    rtxn,
+                    db,
+                    field_id,
+                    level_iters: Vec::new(),
+                    must_reduce: true,
+                    all: Some((RoaringBitmap::new(), Right(zero_iter))),
+                })
+            }
+            Candidates::Bitmap(documents_ids) => {
+                let highest_reverse_iter = Self::highest_reverse_iter(rtxn, index, db, field_id)?;
+                Ok(FacetStringIter {
+                    rtxn,
+                    db,
+                    field_id,
+                    level_iters: vec![(documents_ids, Right(highest_reverse_iter))],
+                    must_reduce: true,
+                    all: None,
+                })
+            }
+        }
     }
 
     pub fn new_non_reducing(
@@ -463,6 +504,7 @@ impl<'t> FacetStringIter<'t> {
             field_id,
             level_iters: vec![(documents_ids, Left(highest_iter))],
             must_reduce: false,
+            all: None,
         })
     }
 
@@ -540,6 +582,24 @@ impl<'t> Iterator for FacetStringIter<'t> {
     type Item = heed::Result<(&'t str, &'t str, RoaringBitmap)>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some((returned, zero_iter)) = &mut self.all {
+            for result in zero_iter {
+                match result {
+                    Ok((normalized, original, mut docids)) => {
+                        // A document is only returned with its first facet value.
+                        docids -= &*returned;
+                        if !docids.is_empty() {
+                            *returned |= &docids;
+                            return Some(Ok((normalized, original, docids)));
+                        }
+                    }
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            self.all = None;
+            return None;
+        }
+
         'outer: loop {
             let (documents_ids, last) = self.level_iters.last_mut()?;
             let is_ascending = last.is_left();
@@ -10,6 +10,8 @@ use roaring::RoaringBitmap;
 
 use super::FacetNumberRange;
 use crate::error::{Error, UserError};
+use crate::facet::value_encoding::may_share_f64;
+use crate::facet::{facet_string_key, facet_string_key_matches};
 use crate::heed_codec::facet::FacetLevelValueF64Codec;
 use crate::{
     distance_between_two_points, lat_lng_to_xyz, CboRoaringBitmapCodec, FieldId, Index, Result,
@@ -29,6 +31,7 @@ enum FilterError<'a> {
     BadGeo(&'a str),
     BadGeoLat(f64),
     BadGeoLng(f64),
+    BadGeoBoundingBoxTopIsBelowBottom(f64, f64),
     Reserved(&'a str),
     TooDeep,
 }
@@ -64,9 +67,10 @@ impl<'a> Display for FilterError<'a> {
                 "`{}` is a reserved keyword and thus can't be used as a filter expression.",
                 keyword
             ),
-            Self::BadGeo(keyword) => write!(f, "`{}` is a reserved keyword and thus can't be used as a filter expression. Use the _geoRadius(latitude, longitude, distance) built-in rule to filter on _geo field coordinates.", keyword),
+            Self::BadGeo(keyword) => write!(f, "`{}` is a reserved keyword and thus can't be used as a filter expression. Use the _geoRadius(latitude, longitude, distance) or _geoBoundingBox([latitude, longitude], [latitude, longitude]) built-in rules to filter on _geo field coordinates.", keyword),
             Self::BadGeoLat(lat) => write!(f, "Bad latitude `{}`. Latitude must be contained between -90 and 90 degrees. ", lat),
             Self::BadGeoLng(lng) => write!(f, "Bad longitude `{}`. Longitude must be contained between -180 and 180 degrees. ", lng),
+            Self::BadGeoBoundingBoxTopIsBelowBottom(top, bottom) => write!(f, "The top latitude `{}` is below the bottom latitude `{}`.", top, bottom),
         }
     }
 }
@@ -77,6 +81,14 @@ impl<'a> From<FPError<'a>> for Error {
     }
 }
 
+/// Displays the canonical form of the filter, the expressions that only differ
+/// by their whitespaces, quotes or parentheses are displayed the same way.
+impl<'a> Display for Filter<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.condition, f)
+    }
+}
+
 impl<'a> From<Filter<'a>> for FilterCondition<'a> {
     fn from(f: Filter<'a>) -> Self {
         f.condition
@@ -84,6 +96,10 @@ impl<'a> From<Filter<'a>> for FilterCondition<'a> {
 }
 
 impl<'a> Filter<'a> {
+    pub(crate) fn condition(&self) -> &FilterCondition<'a> {
+        &self.condition
+    }
+
     pub fn from_array<I, J>(array: I) -> Result<Option<Self>>
     where
         I: IntoIterator<Item = Either<J, &'a str>>,
@@ -142,6 +158,24 @@ impl<'a> Filter<'a> {
 
         Ok(Some(Self { condition }))
     }
+
+    /// Parses the filter like [`Filter::from_str`] but returns all the syntax errors that
+    /// can be found in the operands of the top-level `AND`s and `OR`s, the first error
+    /// being the one [`Filter::from_str`] reports.
+    pub fn parse_all_errors(
+        expression: &'a str,
+    ) -> std::result::Result<Option<Self>, Vec<FPError<'a>>> {
+        let condition = match FilterCondition::parse_all_errors(expression)? {
+            Some(condition) => condition,
+            None => return Ok(None),
+        };
+
+        if let Some(token) = condition.token_at_depth(MAX_FILTER_DEPTH) {
+            return Err(vec![token.as_external_error(FilterError::TooDeep)]);
+        }
+
+        Ok(Some(Self { condition }))
+    }
 }
 
 impl<'a> Filter<'a> {
@@ -253,6 +287,32 @@ impl<'a> Filter<'a> {
         Ok(())
     }
 
+    /// Keeps the documents whose facet number of the given `f64` is exactly the given integer.
+    fn retain_exact_integer(
+        rtxn: &heed::RoTxn,
+        index: &Index,
+        field_id: FieldId,
+        number: f64,
+        integer: i128,
+        docids: RoaringBitmap,
+    ) -> Result<RoaringBitmap> {
+        let mut output = RoaringBitmap::new();
+        for docid in docids {
+            let key = (field_id, docid, number);
+            if let Some(integers) = index.field_id_docid_facet_f64s.get(rtxn, &key)? {
+                // The number of a document is exactly its f64 when it has no exact integers.
+                let is_exact = match integers.is_empty() {
+                    true => number as i128 == integer,
+                    false => integers.contains(&integer),
+                };
+                if is_exact {
+                    output.insert(docid);
+                }
+            }
+        }
+        Ok(output)
+    }
+
     fn evaluate_operator(
         rtxn: &heed::RoTxn,
         index: &Index,
@@ -276,14 +336,27 @@ impl<'a> Filter<'a> {
                 let exist = index.exists_faceted_documents_ids(rtxn, field_id)?;
                 return Ok(exist);
             }
+            Condition::Null => {
+                let is_null = index.null_faceted_documents_ids(rtxn, field_id)?;
+                return Ok(is_null);
+            }
+            Condition::Empty => {
+                let is_empty = index.empty_faceted_documents_ids(rtxn, field_id)?;
+                return Ok(is_empty);
+            }
             Condition::Equal(val) => {
-                let (_original_value, string_docids) = strings_db
-                    .get(rtxn, &(field_id, &val.value().to_lowercase()))?
-                    .unwrap_or_default();
+                let normalized = val.value().to_lowercase();
+                let key = facet_string_key(&normalized);
+                let string_docids = match strings_db.get(rtxn, &(field_id, &*key))? {
+                    Some((original, docids)) if facet_string_key_matches(&normalized, original) => {
+                        docids
+                    }
+                    _ => RoaringBitmap::new(),
+                };
                 let number = val.parse::<f64>().ok();
                 let number_docids = match number {
-                    Some(n) => {
-                        let n = Included(n);
+                    Some(number) => {
+                        let n = Included(number);
                         let mut output = RoaringBitmap::new();
                         Self::explore_facet_number_levels(
                             rtxn,
@@ -294,7 +367,13 @@ impl<'a> Filter<'a> {
                             n,
                             &mut output,
                         )?;
-                        output
+                        // The large integers may share their f64 with other integers.
+                        match val.value().trim().parse::<i128>() {
+                            Ok(integer) if may_share_f64(integer) => Self::retain_exact_integer(
+                                rtxn, index, field_id, number, integer, output,
+                            )?,
+                            _ => output,
+                        }
                     }
                     None => RoaringBitmap::new(),
                 };
@@ -483,6 +562,95 @@ impl<'a> Filter<'a> {
                     }))?;
                 }
             }
+            FilterCondition::GeoBoundingBox { top_left_point, bottom_right_point } => {
+                if filterable_fields.contains("_geo") {
+                    let top_left: [f64; 2] =
+                        [top_left_point[0].parse()?, top_left_point[1].parse()?];
+                    let bottom_right: [f64; 2] =
+                        [bottom_right_point[0].parse()?, bottom_right_point[1].parse()?];
+                    for (token, lat) in [
+                        (&top_left_point[0], top_left[0]),
+                        (&bottom_right_point[0], bottom_right[0]),
+                    ] {
+                        if !(-90.0..=90.0).contains(&lat) {
+                            return Err(token.as_external_error(FilterError::BadGeoLat(lat)))?;
+                        }
+                    }
+                    for (token, lng) in [
+                        (&top_left_point[1], top_left[1]),
+                        (&bottom_right_point[1], bottom_right[1]),
+                    ] {
+                        if !(-180.0..=180.0).contains(&lng) {
+                            return Err(token.as_external_error(FilterError::BadGeoLng(lng)))?;
+                        }
+                    }
+                    if top_left[0] < bottom_right[0] {
+                        return Err(bottom_right_point[0].as_external_error(
+                            FilterError::BadGeoBoundingBoxTopIsBelowBottom(
+                                top_left[0],
+                                bottom_right[0],
+                            ),
+                        ))?;
+                    }
+
+                    let field_ids_map = index.fields_ids_map(rtxn)?;
+                    let (lat_fid, lng_fid) =
+                        match (field_ids_map.id("_geo.lat"), field_ids_map.id("_geo.lng")) {
+                            (Some(lat_fid), Some(lng_fid)) => (lat_fid, lng_fid),
+                            _ => return Ok(RoaringBitmap::new()),
+                        };
+
+                    let selected_lat = Self::evaluate_operator(
+                        rtxn,
+                        index,
+                        lat_fid,
+                        &Condition::Between {
+                            from: bottom_right_point[0].clone(),
+                            to: top_left_point[0].clone(),
+                        },
+                    )?;
+                    if selected_lat.is_empty() {
+                        return Ok(selected_lat);
+                    }
+
+                    let selected_lng = if top_left[1] > bottom_right[1] {
+                        // The box crosses the antimeridian, we split it into a box going
+                        // from the left longitude to 180 and another from -180 to the right one.
+                        let left = Self::evaluate_operator(
+                            rtxn,
+                            index,
+                            lng_fid,
+                            &Condition::GreaterThanOrEqual(top_left_point[1].clone()),
+                        )?;
+                        let right = Self::evaluate_operator(
+                            rtxn,
+                            index,
+                            lng_fid,
+                            &Condition::LowerThanOrEqual(bottom_right_point[1].clone()),
+                        )?;
+                        left | right
+                    } else {
+                        Self::evaluate_operator(
+                            rtxn,
+                            index,
+                            lng_fid,
+                            &Condition::Between {
+                                from: top_left_point[1].clone(),
+                                to: bottom_right_point[1].clone(),
+                            },
+                        )?
+                    };
+
+                    Ok(selected_lat & selected_lng)
+                } else {
+                    return Err(top_left_point[0].as_external_error(
+                        FilterError::AttributeNotFilterable {
+                            attribute: "_geo",
+                            filterable_fields: filterable_fields.clone(),
+                        },
+                    ))?;
+                }
+            }
         }
     }
 }
@@ -502,6 +670,7 @@ mod tests {
     use maplit::hashset;
 
     use crate::index::tests::TempIndex;
+    use crate::update::DeleteDocuments;
     use crate::Filter;
 
     #[test]
@@ -526,6 +695,18 @@ mod tests {
         assert!(bitmap.is_empty());
     }
 
+    #[test]
+    fn parse_all_errors() {
+        let filter = Filter::parse_all_errors("channel = mv AND followers > 10").unwrap().unwrap();
+        assert_eq!(filter, Filter::from_str("channel = mv AND followers > 10").unwrap().unwrap());
+        assert!(Filter::parse_all_errors("  ").unwrap().is_none());
+
+        let errors = Filter::parse_all_errors("channel = AND followers >").unwrap_err();
+        assert_eq!(errors.len(), 2);
+        let error = Filter::from_str("channel = AND followers >").unwrap_err();
+        assert_eq!(error.to_string(), errors[0].to_string());
+    }
+
     #[test]
     fn from_array() {
         // Simple array with Left
@@ -725,6 +906,84 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn geo_bounding_box() {
+        let index = TempIndex::new();
+
+        index
+            .update_settings(|settings| {
+                settings.set_filterable_fields(hashset! { S("_geo") });
+            })
+            .unwrap();
+
+        index
+            .add_documents(documents!([
+                { "id": 0, "_geo": { "lat": 10, "lng": 179 } },
+                { "id": 1, "_geo": { "lat": 10, "lng": -179 } },
+                { "id": 2, "_geo": { "lat": 10, "lng": 0 } },
+                { "id": 3, "_geo": { "lat": 89.9, "lng": 45 } },
+                { "id": 4, "_geo": { "lat": 90, "lng": -120 } },
+                { "id": 5, "_geo": { "lat": -90, "lng": 0 } },
+                { "id": 6, "_geo": { "lat": -10, "lng": 180 } },
+                { "id": 7, "_geo": { "lat": -10, "lng": -180 } },
+                { "id": 8 },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let evaluate = |filter: &str| {
+            let filter = Filter::from_str(filter).unwrap().unwrap();
+            filter.evaluate(&rtxn, &index).unwrap().into_iter().collect::<Vec<_>>()
+        };
+
+        // boxes crossing the antimeridian
+        assert_eq!(evaluate("_geoBoundingBox([20, 170], [0, -170])"), vec![0, 1]);
+        assert_eq!(evaluate("_geoBoundingBox([20, 170], [-20, -170])"), vec![0, 1, 6, 7]);
+        assert_eq!(evaluate("_geoBoundingBox([90, 100], [-90, -100])"), vec![0, 1, 4, 6, 7]);
+        // the same boxes without crossing it
+        assert_eq!(evaluate("_geoBoundingBox([20, -170], [0, 170])"), vec![2]);
+        assert_eq!(evaluate("_geoBoundingBox([20, -180], [-20, 180])"), vec![0, 1, 2, 6, 7]);
+
+        // boxes touching the poles
+        assert_eq!(evaluate("_geoBoundingBox([90, -180], [80, 180])"), vec![3, 4]);
+        assert_eq!(evaluate("_geoBoundingBox([90, 0], [89, 90])"), vec![3]);
+        assert_eq!(evaluate("_geoBoundingBox([-80, -180], [-90, 180])"), vec![5]);
+        assert_eq!(
+            evaluate("_geoBoundingBox([90, -180], [-90, 180])"),
+            vec![0, 1, 2, 3, 4, 5, 6, 7]
+        );
+
+        let error_message = |filter: &str| {
+            let filter = Filter::from_str(filter).unwrap().unwrap();
+            filter.evaluate(&rtxn, &index).unwrap_err().to_string()
+        };
+
+        let error = error_message("_geoBoundingBox([91, 0], [0, 10])");
+        assert!(
+            error.starts_with(
+                "Bad latitude `91`. Latitude must be contained between -90 and 90 degrees."
+            ),
+            "{}",
+            error
+        );
+
+        let error = error_message("_geoBoundingBox([10, 0], [0, 180.1])");
+        assert!(
+            error.starts_with(
+                "Bad longitude `180.1`. Longitude must be contained between -180 and 180 degrees."
+            ),
+            "{}",
+            error
+        );
+
+        let error = error_message("_geoBoundingBox([0, 0], [10, 10])");
+        assert!(
+            error.starts_with("The top latitude `0` is below the bottom latitude `10`."),
+            "{}",
+            error
+        );
+    }
+
     #[test]
     fn filter_depth() {
         // generates a big (2 MiB) filter with too much of ORs.
@@ -748,4 +1007,341 @@ mod tests {
         let option = Filter::from_str("     ").unwrap();
         assert_eq!(option, None);
     }
+
+    #[test]
+    fn booleans() {
+        let index = TempIndex::new();
+
+        index
+            .update_settings(|settings| {
+                settings.set_filterable_fields(hashset! { S("available") });
+            })
+            .unwrap();
+
+        index
+            .add_documents(documents!([
+                { "id": 0, "available": true },
+                { "id": 1, "available": false },
+                { "id": 2, "available": "true" },
+                { "id": 3, "available": "False" },
+                { "id": 4, "available": [false, "soon"] },
+                { "id": 5, "available": 2 },
+                { "id": 6 },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let evaluate = |filter: &str| {
+            let filter = Filter::from_str(filter).unwrap().unwrap();
+            filter.evaluate(&rtxn, &index).unwrap().into_iter().collect::<Vec<_>>()
+        };
+
+        // the booleans and the strings are matched alike
+        assert_eq!(evaluate("available = true"), vec![0, 2]);
+        assert_eq!(evaluate("available = \"true\""), vec![0, 2]);
+        assert_eq!(evaluate("available = false"), vec![1, 3, 4]);
+        assert_eq!(evaluate("available != true"), vec![1, 3, 4, 5, 6]);
+        assert_eq!(evaluate("available = soon AND available = false"), vec![4]);
+
+        // the booleans are also numbers
+        assert_eq!(evaluate("available = 1"), vec![0]);
+        assert_eq!(evaluate("available 0 TO 1"), vec![0, 1, 4]);
+        assert_eq!(evaluate("available > 0"), vec![0, 5]);
+    }
+
+    #[test]
+    fn null_and_empty() {
+        let index = TempIndex::new();
+
+        index
+            .update_settings(|settings| {
+                settings.set_filterable_fields(hashset! { S("a") });
+            })
+            .unwrap();
+
+        index
+            .add_documents(documents!([
+                { "id": 0, "a": null },
+                { "id": 1, "a": [] },
+                { "id": 2, "a": "" },
+                { "id": 3 },
+                { "id": 4, "a": "x" },
+                { "id": 5, "a": { "b": 1 } },
+                { "id": 6, "a": {} },
+                { "id": 7, "a": { "b": null } },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let evaluate = |filter: &str| {
+            let filter = Filter::from_str(filter).unwrap().unwrap();
+            filter.evaluate(&rtxn, &index).unwrap().into_iter().collect::<Vec<_>>()
+        };
+
+        assert_eq!(evaluate("a IS NULL"), vec![0]);
+        assert_eq!(evaluate("a IS NOT NULL"), vec![1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(evaluate("NOT a IS NULL"), vec![1, 2, 3, 4, 5, 6, 7]);
+        // the objects flattened into an empty array are not empty unless they have no field
+        assert_eq!(evaluate("a IS EMPTY"), vec![1, 2, 6]);
+        assert_eq!(evaluate("a IS NOT EMPTY"), vec![0, 3, 4, 5, 7]);
+        assert_eq!(evaluate("a EXISTS"), vec![0, 1, 2, 4, 5, 6, 7]);
+        assert_eq!(evaluate("a NOT EXISTS"), vec![3]);
+        assert_eq!(evaluate("a.b IS NULL"), vec![7]);
+        assert_eq!(evaluate("a IS NULL OR a IS EMPTY OR a NOT EXISTS"), vec![0, 1, 2, 3, 6]);
+        drop(rtxn);
+
+        // the deleted documents are removed from the null and empty documents ids
+        let mut wtxn = index.write_txn().unwrap();
+        let mut builder = DeleteDocuments::new(&mut wtxn, &index).unwrap();
+        builder.soft_deleted_threshold(0.0);
+        builder.delete_external_id("0").unwrap();
+        builder.delete_external_id("1").unwrap();
+        builder.execute().unwrap();
+        wtxn.commit().unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let a = index.fields_ids_map(&rtxn).unwrap().id("a").unwrap();
+        assert!(index.null_faceted_documents_ids(&rtxn, a).unwrap().is_empty());
+        let empty = index.empty_faceted_documents_ids(&rtxn, a).unwrap();
+        assert_eq!(empty.into_iter().collect::<Vec<_>>(), vec![2, 6]);
+    }
+
+    #[test]
+    fn range_filters_on_coerced_numeric_strings() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|settings| settings.set_filterable_fields(hashset! { S("price") }))
+            .unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 0, "price": "19.99" },
+                { "id": 1, "price": 5 },
+                { "id": 2, "price": "cheap" },
+                { "id": 3, "price": "12" },
+                { "id": 4, "price": ["3", "30"] },
+                { "id": 5, "price": "inf" },
+                { "id": 6, "price": " 7 " },
+            ]))
+            .unwrap();
+
+        let evaluate = |filter: &str| {
+            let rtxn = index.read_txn().unwrap();
+            let filter = Filter::from_str(filter).unwrap().unwrap();
+            filter.evaluate(&rtxn, &index).unwrap().into_iter().collect::<Vec<_>>()
+        };
+
+        // the strings are not coerced by default
+        assert!(evaluate("price > 10").is_empty());
+        assert_eq!(evaluate("price = 12"), vec![3]);
+
+        index.update_settings(|settings| settings.set_coerce_numeric_strings(true)).unwrap();
+        assert_eq!(evaluate("price > 10"), vec![0, 3, 4]);
+        assert_eq!(evaluate("price 5 TO 10"), vec![1, 6]);
+        assert_eq!(evaluate("price < 4"), vec![4]);
+        assert_eq!(evaluate("price = 12"), vec![3]);
+        assert_eq!(evaluate("price = 19.99"), vec![0]);
+        assert_eq!(evaluate("price = cheap"), vec![2]);
+        // the strings that are not finite numbers are only kept as strings
+        assert!(evaluate("price > 1000").is_empty());
+
+        // the numbers of the replaced and deleted documents are removed
+        index.add_documents(documents!([{ "id": 0, "price": "5" }])).unwrap();
+        let mut wtxn = index.write_txn().unwrap();
+        let mut builder = DeleteDocuments::new(&mut wtxn, &index).unwrap();
+        builder.soft_deleted_threshold(0.0);
+        builder.delete_external_id("3").unwrap();
+        builder.execute().unwrap();
+        wtxn.commit().unwrap();
+        assert_eq!(evaluate("price > 10"), vec![4]);
+        assert_eq!(evaluate("price = 5").len(), 2);
+
+        index.update_settings(|settings| settings.reset_coerce_numeric_strings()).unwrap();
+        assert!(evaluate("price > 10").is_empty());
+        assert_eq!(evaluate("price < 10"), vec![1]);
+    }
+
+    #[test]
+    fn coerced_numeric_strings_of_nested_fields() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|settings| {
+                settings.set_filterable_fields(hashset! { S("meta") });
+                settings.set_coerce_numeric_strings(true);
+            })
+            .unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 0, "meta": { "price": "19.99" } },
+                { "id": 1, "meta": { "price": 5 } },
+                { "id": 2, "meta": { "price": "cheap" } },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let evaluate = |filter: &str| {
+            let filter = Filter::from_str(filter).unwrap().unwrap();
+            filter.evaluate(&rtxn, &index).unwrap().into_iter().collect::<Vec<_>>()
+        };
+        assert_eq!(evaluate("meta.price > 10"), vec![0]);
+        assert_eq!(evaluate("meta.price 1 TO 20"), vec![0, 1]);
+        assert_eq!(evaluate("meta.price = cheap"), vec![2]);
+    }
+
+    #[test]
+    fn long_facet_strings_sharing_a_prefix_do_not_collide() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|settings| settings.set_filterable_fields(hashset! { S("tag") }))
+            .unwrap();
+
+        let prefix = "a".repeat(500);
+        let first = format!("{}X", prefix);
+        let second = format!("{}Y", prefix);
+        index
+            .add_documents(documents!([
+                { "id": 0, "tag": first },
+                { "id": 1, "tag": second },
+                { "id": 2, "tag": [first, "short"] },
+                { "id": 3, "tag": prefix },
+            ]))
+            .unwrap();
+
+        let evaluate = |value: &str| {
+            let rtxn = index.read_txn().unwrap();
+            let filter = format!("tag = \"{}\"", value);
+            let filter = Filter::from_str(&filter).unwrap().unwrap();
+            filter.evaluate(&rtxn, &index).unwrap().into_iter().collect::<Vec<_>>()
+        };
+
+        assert_eq!(evaluate(&first), vec![0, 2]);
+        assert_eq!(evaluate(&first.to_lowercase()), vec![0, 2]);
+        assert_eq!(evaluate(&second), vec![1]);
+        assert_eq!(evaluate(&prefix), vec![3]);
+        assert_eq!(evaluate("short"), vec![2]);
+        assert!(evaluate(&format!("{}Z", prefix)).is_empty());
+
+        // the whole original values are displayed by the facet distribution
+        let rtxn = index.read_txn().unwrap();
+        let distribution = crate::FacetDistribution::new(&rtxn, &index).execute().unwrap();
+        let tags = &distribution["tag"];
+        assert_eq!(tags.len(), 4);
+        assert_eq!(tags[&first], 2);
+        assert_eq!(tags[&second], 1);
+        drop(rtxn);
+
+        // the long values of the deleted documents are removed
+        let mut wtxn = index.write_txn().unwrap();
+        let mut builder = DeleteDocuments::new(&mut wtxn, &index).unwrap();
+        builder.soft_deleted_threshold(0.0);
+        builder.delete_external_id("0").unwrap();
+        builder.execute().unwrap();
+        wtxn.commit().unwrap();
+        assert_eq!(evaluate(&first), vec![2]);
+        assert_eq!(evaluate(&second), vec![1]);
+    }
+
+    #[test]
+    fn equality_filters_distinguish_the_large_integers() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|settings| settings.set_filterable_fields(hashset! { S("n") }))
+            .unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 0, "n": 9_007_199_254_740_991_i64 },
+                { "id": 1, "n": 9_007_199_254_740_992_i64 },
+                { "id": 2, "n": 9_007_199_254_740_993_i64 },
+                { "id": 3, "n": 1.5 },
+                { "id": 4, "n": [2, 18_446_744_073_709_551_615_u64] },
+                { "id": 5, "n": 18_446_744_073_709_551_614_u64 },
+                { "id": 6, "n": -9_007_199_254_740_993_i64 },
+                { "id": 7, "n": 9_007_199_254_740_992.0 },
+            ]))
+            .unwrap();
+
+        let evaluate = |filter: &str| {
+            let rtxn = index.read_txn().unwrap();
+            let filter = Filter::from_str(filter).unwrap().unwrap();
+            filter.evaluate(&rtxn, &index).unwrap().into_iter().collect::<Vec<_>>()
+        };
+
+        // 2^53 + 1 and 2^53 are the same f64
+        assert_eq!(evaluate("n = 9007199254740991"), vec![0]);
+        assert_eq!(evaluate("n = 9007199254740992"), vec![1, 7]);
+        assert_eq!(evaluate("n = 9007199254740993"), vec![2]);
+        assert_eq!(evaluate("n = -9007199254740993"), vec![6]);
+        assert_eq!(evaluate("n = 18446744073709551615"), vec![4]);
+        assert_eq!(evaluate("n = 18446744073709551614"), vec![5]);
+        assert_eq!(evaluate("n = 1.5"), vec![3]);
+        assert_eq!(evaluate("n != 9007199254740993"), vec![0, 1, 3, 4, 5, 6, 7]);
+        assert_eq!(evaluate("n IN [9007199254740993, 18446744073709551614]"), vec![2, 5]);
+
+        // the ranges still use the f64 of the integers
+        assert_eq!(evaluate("n 1 TO 2"), vec![3, 4]);
+        assert_eq!(evaluate("n > 1.5"), vec![0, 1, 2, 4, 5, 7]);
+        assert_eq!(evaluate("n >= 9007199254740992"), vec![1, 2, 4, 5, 7]);
+        assert_eq!(evaluate("n < 0"), vec![6]);
+
+        let mut wtxn = index.write_txn().unwrap();
+        let mut builder = DeleteDocuments::new(&mut wtxn, &index).unwrap();
+        builder.soft_deleted_threshold(0.0);
+        builder.delete_external_id("2").unwrap();
+        builder.execute().unwrap();
+        wtxn.commit().unwrap();
+
+        assert!(evaluate("n = 9007199254740993").is_empty());
+        assert_eq!(evaluate("n = 9007199254740992"), vec![1, 7]);
+    }
+
+    #[test]
+    fn range_filters_descending_the_levels_match_a_linear_scan() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let index = TempIndex::new();
+        index
+            .update_settings(|settings| {
+                settings.set_filterable_fields(hashset! { S("n") });
+                // small groups to build many levels whose bounds are crossed by the ranges
+                settings.set_facet_level_group_size(3);
+                settings.set_facet_min_level_size(2);
+            })
+            .unwrap();
+
+        let values: Vec<f64> = (0..1000).map(|i| (i * 37 % 250) as f64 / 2.0).collect();
+        let documents = values
+            .iter()
+            .enumerate()
+            .map(|(id, n)| serde_json::json!({ "id": id, "n": n }).as_object().unwrap().clone())
+            .collect();
+        index
+            .add_documents(crate::documents::documents_batch_reader_from_objects(documents))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let scan = |keep: &dyn Fn(f64) -> bool| -> Vec<u32> {
+            let ids = values.iter().enumerate().filter(|(_, n)| keep(**n));
+            ids.map(|(id, _)| id as u32).collect()
+        };
+
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..200 {
+            // quarters to fall on, and between, the values of the documents
+            let low = rng.gen_range(-8..520) as f64 / 4.0;
+            let high = rng.gen_range(-8..520) as f64 / 4.0;
+            let expected: [(String, Vec<u32>); 6] = [
+                (format!("n {} TO {}", low, high), scan(&|n| low <= n && n <= high)),
+                (format!("n > {}", low), scan(&|n| n > low)),
+                (format!("n >= {}", low), scan(&|n| n >= low)),
+                (format!("n < {}", high), scan(&|n| n < high)),
+                (format!("n <= {}", high), scan(&|n| n <= high)),
+                (format!("n = {}", low), scan(&|n| n == low)),
+            ];
+            for (expression, expected) in expected.iter() {
+                let filter = Filter::from_str(expression).unwrap().unwrap();
+                let docids = filter.evaluate(&rtxn, &index).unwrap();
+                assert_eq!(&docids.into_iter().collect::<Vec<_>>(), expected, "{}", expression);
+            }
+        }
+    }
 }
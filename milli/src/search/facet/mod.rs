@@ -1,4 +1,6 @@
-pub use self::facet_distribution::{FacetDistribution, DEFAULT_VALUES_PER_FACET};
+use roaring::RoaringBitmap;
+
+pub use self::facet_distribution::{FacetDistribution, OrderBy, DEFAULT_VALUES_PER_FACET};
 pub use self::facet_number::{FacetNumberIter, FacetNumberRange, FacetNumberRevRange};
 pub use self::facet_string::FacetStringIter;
 pub use self::filter::Filter;
@@ -7,3 +9,85 @@ mod facet_distribution;
 mod facet_number;
 mod facet_string;
 mod filter;
+
+/// The documents whose facet values are iterated over by the facet iterators.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Candidates {
+    /// All the documents of the facet database, the groups of the facet levels are not
+    /// intersected with a bitmap and the level 0 is directly iterated over.
+    All,
+    /// Only the given documents, the groups of the facet levels are intersected with them.
+    Bitmap(RoaringBitmap),
+}
+
+#[cfg(test)]
+mod tests {
+    use big_s::S;
+    use maplit::hashset;
+
+    use super::*;
+    use crate::documents::documents_batch_reader_from_objects;
+    use crate::index::tests::TempIndex;
+
+    #[test]
+    fn all_candidates_iterate_like_a_full_bitmap() {
+        let mut index = TempIndex::new();
+        index.index_documents_config.autogenerate_docids = true;
+        index
+            .update_settings(|settings| {
+                settings.set_filterable_fields(hashset! { S("number"), S("string") });
+                settings.set_facet_level_group_size(2);
+                settings.set_facet_min_level_size(2);
+            })
+            .unwrap();
+
+        let mut documents = vec![];
+        for i in 0..200 {
+            let document = match i % 5 {
+                0 => serde_json::json!({ "title": "nothing" }),
+                1 => serde_json::json!({ "number": [i % 37, 100 - i % 13], "string": "multi" }),
+                _ => serde_json::json!({ "number": i % 37, "string": format!("s{:02X}", i % 23) }),
+            };
+            documents.push(document.as_object().unwrap().clone());
+        }
+        index.add_documents(documents_batch_reader_from_objects(documents)).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let fields_ids_map = index.fields_ids_map(&rtxn).unwrap();
+        let number = fields_ids_map.id("number").unwrap();
+        let string = fields_ids_map.id("string").unwrap();
+        let bitmap = Candidates::Bitmap(index.documents_ids(&rtxn).unwrap());
+
+        let numbers = |iter: heed::Result<FacetNumberIter>| {
+            iter.unwrap().collect::<heed::Result<Vec<_>>>().unwrap()
+        };
+        let strings = |iter: heed::Result<FacetStringIter>| {
+            iter.unwrap().collect::<heed::Result<Vec<_>>>().unwrap()
+        };
+
+        let all = numbers(FacetNumberIter::new_reducing(&rtxn, &index, number, Candidates::All));
+        let expected =
+            numbers(FacetNumberIter::new_reducing(&rtxn, &index, number, bitmap.clone()));
+        assert_eq!(all.first().map(|(value, _)| *value), Some(0.0));
+        assert_eq!(all, expected);
+
+        let all =
+            numbers(FacetNumberIter::new_reverse_reducing(&rtxn, &index, number, Candidates::All));
+        let expected =
+            numbers(FacetNumberIter::new_reverse_reducing(&rtxn, &index, number, bitmap.clone()));
+        assert_eq!(all.first().map(|(value, _)| *value), Some(100.0));
+        assert_eq!(all, expected);
+
+        let all = strings(FacetStringIter::new_reducing(&rtxn, &index, string, Candidates::All));
+        let expected =
+            strings(FacetStringIter::new_reducing(&rtxn, &index, string, bitmap.clone()));
+        assert_eq!(all.first().map(|(value, _, _)| *value), Some("multi"));
+        assert_eq!(all, expected);
+
+        let all =
+            strings(FacetStringIter::new_reverse_reducing(&rtxn, &index, string, Candidates::All));
+        let expected =
+            strings(FacetStringIter::new_reverse_reducing(&rtxn, &index, string, bitmap));
+        assert_eq!(all, expected);
+    }
+}
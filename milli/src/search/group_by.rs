@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+
+use super::distinct::{facet_number_values, facet_string_values};
+use crate::facet::FacetValue;
+use crate::{DocumentId, FieldId, Index, Result};
+
+/// The best ranked documents sharing the same value for the field of [`Search::group_by`].
+///
+/// [`Search::group_by`]: super::Search::group_by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentsGroup {
+    /// The value of the best ranked document of the group,
+    /// `None` for the group of the documents without value.
+    pub value: Option<FacetValue>,
+    /// The documents of the group, in ranking order.
+    pub documents_ids: Vec<DocumentId>,
+}
+
+/// Partitions the documents, in ranking order, into at most `groups_limit` groups
+/// of at most `group_limit` documents, plus the group of the documents without value.
+///
+/// A document is grouped by its lowest number for the field or, if it has none, by its
+/// lowest normalized string. The groups are ordered by their best ranked document and
+/// the group of the documents without value doesn't count in the limit of groups.
+pub(crate) struct Grouper<'a> {
+    index: &'a Index,
+    rtxn: &'a heed::RoTxn<'a>,
+    field_id: Option<FieldId>,
+    group_limit: usize,
+    groups_limit: usize,
+    /// The position in the `groups` of the group of each normalized value.
+    positions: HashMap<FacetValue, usize>,
+    groups: Vec<DocumentsGroup>,
+    full_groups: usize,
+    without_value: Vec<DocumentId>,
+}
+
+impl<'a> Grouper<'a> {
+    /// The `field_id` is `None` when no document has the field, the
+    /// documents are then all in the group of the documents without value.
+    pub fn new(
+        index: &'a Index,
+        rtxn: &'a heed::RoTxn<'a>,
+        field_id: Option<FieldId>,
+        group_limit: usize,
+        groups_limit: usize,
+    ) -> Grouper<'a> {
+        Grouper {
+            index,
+            rtxn,
+            field_id,
+            group_limit: group_limit.max(1),
+            groups_limit,
+            positions: HashMap::new(),
+            groups: Vec::new(),
+            full_groups: 0,
+            without_value: Vec::new(),
+        }
+    }
+
+    /// Adds the document to its group, the documents must be inserted in ranking order.
+    ///
+    /// Returns `false` when the document is not kept because its group is full
+    /// or because it would open a group above the limit of groups.
+    pub fn insert(&mut self, docid: DocumentId) -> Result<bool> {
+        let (key, value) = match self.group_of(docid)? {
+            Some(key_value) => key_value,
+            None if self.without_value.len() == self.group_limit => return Ok(false),
+            None => {
+                self.without_value.push(docid);
+                return Ok(true);
+            }
+        };
+
+        let position = match self.positions.get(&key) {
+            Some(&position) => position,
+            None if self.groups.len() >= self.groups_limit => return Ok(false),
+            None => {
+                self.positions.insert(key, self.groups.len());
+                self.groups.push(DocumentsGroup { value: Some(value), documents_ids: Vec::new() });
+                self.groups.len() - 1
+            }
+        };
+
+        let group = &mut self.groups[position];
+        if group.documents_ids.len() == self.group_limit {
+            return Ok(false);
+        }
+        group.documents_ids.push(docid);
+        if group.documents_ids.len() == self.group_limit {
+            self.full_groups += 1;
+        }
+        Ok(true)
+    }
+
+    /// Whether the documents inserted from now on can't change the returned groups: there
+    /// are enough groups with a value to fill the page and they are full, the group of the
+    /// documents without value then always comes after the page.
+    pub fn is_full(&self) -> bool {
+        self.groups.len() >= self.groups_limit && self.full_groups == self.groups.len()
+    }
+
+    /// Returns the groups with a value followed by the group of the documents
+    /// without value, without the first `offset` ones and at most `limit` of them.
+    pub fn into_groups(self, offset: usize, limit: usize) -> Vec<DocumentsGroup> {
+        let without_value = if self.without_value.is_empty() {
+            None
+        } else {
+            Some(DocumentsGroup { value: None, documents_ids: self.without_value })
+        };
+        self.groups.into_iter().chain(without_value).skip(offset).take(limit).collect()
+    }
+
+    /// Returns the normalized value the document is grouped by and its
+    /// original value, `None` if the document has no value for the field.
+    fn group_of(&self, docid: DocumentId) -> Result<Option<(FacetValue, FacetValue)>> {
+        let field_id = match self.field_id {
+            Some(field_id) => field_id,
+            None => return Ok(None),
+        };
+
+        if let Some(result) = facet_number_values(docid, field_id, self.index, self.rtxn)?.next() {
+            let ((_, _, number), _) = result?;
+            let value = FacetValue::from(number);
+            return Ok(Some((value.clone(), value)));
+        }
+
+        if let Some(result) = facet_string_values(docid, field_id, self.index, self.rtxn)?.next() {
+            let ((_, _, normalized), original) = result?;
+            return Ok(Some((FacetValue::from(normalized), FacetValue::from(original))));
+        }
+
+        Ok(None)
+    }
+}
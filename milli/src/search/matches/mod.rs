@@ -5,6 +5,8 @@ use matching_words::{MatchType, PartialMatch, PrimitiveWordId};
 pub use matching_words::{MatchingWord, MatchingWords};
 use serde::Serialize;
 
+use crate::restore_diacritics;
+
 pub mod matching_words;
 
 const DEFAULT_CROP_MARKER: &str = "…";
@@ -18,6 +20,7 @@ pub struct MatcherBuilder<'a, A> {
     crop_marker: Option<String>,
     highlight_prefix: Option<String>,
     highlight_suffix: Option<String>,
+    count_diacritics: bool,
 }
 
 impl<'a, A> MatcherBuilder<'a, A> {
@@ -28,6 +31,7 @@ impl<'a, A> MatcherBuilder<'a, A> {
             crop_marker: None,
             highlight_prefix: None,
             highlight_suffix: None,
+            count_diacritics: false,
         }
     }
 
@@ -46,6 +50,13 @@ impl<'a, A> MatcherBuilder<'a, A> {
         self
     }
 
+    /// Keeps the diacritics of the words of the text, it must be set
+    /// when the index the matching words come from counts them.
+    pub fn count_diacritics(&mut self, count_diacritics: bool) -> &Self {
+        self.count_diacritics = count_diacritics;
+        self
+    }
+
     pub fn build<'t, 'm>(&'m self, text: &'t str) -> Matcher<'t, 'm, A> {
         let crop_marker = match &self.crop_marker {
             Some(marker) => marker.as_str(),
@@ -67,6 +78,7 @@ impl<'a, A> MatcherBuilder<'a, A> {
             crop_marker,
             highlight_prefix,
             highlight_suffix,
+            count_diacritics: self.count_diacritics,
             matches: None,
         }
     }
@@ -110,6 +122,7 @@ pub struct Matcher<'t, 'm, A> {
     crop_marker: &'m str,
     highlight_prefix: &'m str,
     highlight_suffix: &'m str,
+    count_diacritics: bool,
     matches: Option<(Vec<Token<'t>>, Vec<Match>)>,
 }
 
@@ -171,7 +184,10 @@ impl<'t, A: AsRef<[u8]>> Matcher<'t, '_, A> {
             false
         }
 
-        let tokens: Vec<_> = self.tokenizer.tokenize(self.text).collect();
+        let mut tokens: Vec<_> = self.tokenizer.tokenize(self.text).collect();
+        if self.count_diacritics {
+            tokens = restore_diacritics(self.text, tokens);
+        }
         let mut matches = Vec::new();
 
         let mut words_positions = tokens
@@ -1,10 +1,12 @@
 use std::borrow::Cow;
 use std::collections::hash_map::{Entry, HashMap};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 use std::mem::take;
+use std::ops::RangeInclusive;
 use std::result::Result as StdResult;
 use std::str::Utf8Error;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use charabia::TokenizerBuilder;
 use distinct::{Distinct, DocIter, FacetDistinct, NoopDistinct};
@@ -15,39 +17,71 @@ use log::debug;
 use once_cell::sync::Lazy;
 use roaring::bitmap::RoaringBitmap;
 
-pub use self::facet::{FacetDistribution, FacetNumberIter, Filter, DEFAULT_VALUES_PER_FACET};
+pub use self::analysis::{AnalyzedTerm, AnalyzedToken, QueryAnalysis};
+use self::continuation::SearchContinuation;
+pub use self::criteria::{
+    RankingRuleScore, RuleName, ScoreDetail, ScoreDetails, SearchLimits, SortValue,
+};
+pub(crate) use self::docids_cache::DocidsCache;
+pub use self::facet::{
+    Candidates, FacetDistribution, FacetNumberIter, Filter, OrderBy, DEFAULT_VALUES_PER_FACET,
+};
 use self::fst_utils::{Complement, Intersection, StartsWith, Union};
+pub use self::group_by::DocumentsGroup;
+use self::group_by::Grouper;
 pub use self::matches::{
     FormatOptions, MatchBounds, Matcher, MatcherBuilder, MatchingWord, MatchingWords,
 };
+pub use self::multi_search::{MultiSearch, SearchQuery};
 use self::query_tree::QueryTreeBuilder;
+pub use self::searchable_context::SearchableContext;
+pub(crate) use self::searchable_context::SearchableContextCache;
 use crate::error::UserError;
 use crate::search::criteria::r#final::{Final, FinalResult};
-use crate::{AscDesc, Criterion, DocumentId, Index, Member, Result};
+use crate::search::criteria::{BucketPosition, Context, IterationBudget, TimeBudget};
+use crate::{restore_diacritics, AscDesc, Criterion, DocumentId, FieldId, Index, Member, Result};
 
 // Building these factories is not free.
 static LEVDIST0: Lazy<LevBuilder> = Lazy::new(|| LevBuilder::new(0, true));
 static LEVDIST1: Lazy<LevBuilder> = Lazy::new(|| LevBuilder::new(1, true));
 static LEVDIST2: Lazy<LevBuilder> = Lazy::new(|| LevBuilder::new(2, true));
 
+mod analysis;
+mod continuation;
 mod criteria;
 mod distinct;
+mod docids_cache;
 mod facet;
 mod fst_utils;
+mod group_by;
 mod matches;
+mod multi_search;
 mod query_tree;
+mod searchable_context;
 
+#[derive(Clone)]
 pub struct Search<'a> {
     query: Option<String>,
     // this should be linked to the String in the query
     filter: Option<Filter<'a>>,
+    filtered_candidates: Option<RoaringBitmap>,
+    candidates_range: Option<RangeInclusive<DocumentId>>,
+    group_by: Option<(String, usize)>,
     offset: usize,
     limit: usize,
     sort_criteria: Option<Vec<AscDesc>>,
+    searchable_attributes: Option<&'a [&'a str]>,
     terms_matching_strategy: TermsMatchingStrategy,
     authorize_typos: bool,
+    phrase_slop: u8,
     words_limit: usize,
     exhaustive_number_hits: bool,
+    scores: bool,
+    bitmap_arena: bool,
+    with_continuation: bool,
+    continue_from: Option<&'a [u8]>,
+    time_budget: Option<Duration>,
+    limits: SearchLimits,
     rtxn: &'a heed::RoTxn<'a>,
     index: &'a Index,
 }
@@ -57,12 +91,23 @@ impl<'a> Search<'a> {
         Search {
             query: None,
             filter: None,
+            filtered_candidates: None,
+            candidates_range: None,
+            group_by: None,
             offset: 0,
             limit: 20,
             sort_criteria: None,
+            searchable_attributes: None,
             terms_matching_strategy: TermsMatchingStrategy::default(),
             authorize_typos: true,
+            phrase_slop: 0,
             exhaustive_number_hits: false,
+            scores: false,
+            bitmap_arena: false,
+            with_continuation: false,
+            continue_from: None,
+            time_budget: None,
+            limits: SearchLimits::default(),
             words_limit: 10,
             rtxn,
             index,
@@ -89,6 +134,13 @@ impl<'a> Search<'a> {
         self
     }
 
+    /// Restricts the query to the given searchable attributes,
+    /// without changing the searchable attributes of the index.
+    pub fn searchable_attributes(&mut self, searchable: &'a [&'a str]) -> &mut Search<'a> {
+        self.searchable_attributes = Some(searchable);
+        self
+    }
+
     pub fn terms_matching_strategy(&mut self, value: TermsMatchingStrategy) -> &mut Search<'a> {
         self.terms_matching_strategy = value;
         self
@@ -99,6 +151,14 @@ impl<'a> Search<'a> {
         self
     }
 
+    /// Allows up to `slop` other positions between the words of all the phrases of the query,
+    /// e.g. `"quick fox"` matches "quick brown fox" with a slop of `1`. The words must still
+    /// appear in order and in the same attribute, a slop of `0` only matches exact phrases.
+    pub fn phrase_slop(&mut self, slop: u8) -> &mut Search<'a> {
+        self.phrase_slop = slop;
+        self
+    }
+
     pub fn words_limit(&mut self, value: usize) -> &mut Search<'a> {
         self.words_limit = value;
         self
@@ -109,6 +169,31 @@ impl<'a> Search<'a> {
         self
     }
 
+    /// Uses these candidates instead of evaluating the filter, they must be
+    /// the result of the evaluation of the filter in the same read transaction.
+    pub(crate) fn filtered_candidates(&mut self, candidates: RoaringBitmap) -> &mut Search<'a> {
+        self.filtered_candidates = Some(candidates);
+        self
+    }
+
+    /// Only returns the documents with an internal id within the range, the
+    /// searches of the disjoint ranges of a sharded reader can be merged.
+    pub fn candidates_within(&mut self, range: RangeInclusive<DocumentId>) -> &mut Search<'a> {
+        self.candidates_range = Some(range);
+        self
+    }
+
+    /// Groups the ranked documents by their facet value for the given filterable field and
+    /// keeps the `group_limit` best ranked documents of each group, see [`DocumentsGroup`].
+    ///
+    /// The offset and the limit then apply to the groups, ordered by their best ranked document,
+    /// followed by the group of the documents without value for the field. The documents ids of
+    /// the result are the ones of the groups, in order. The continuations are not supported.
+    pub fn group_by(&mut self, field: &str, group_limit: usize) -> &mut Search<'a> {
+        self.group_by = Some((field.to_string(), group_limit));
+        self
+    }
+
     /// Force the search to exhastivelly compute the number of candidates,
     /// this will increase the search time but allows finite pagination.
     pub fn exhaustive_number_hits(&mut self, exhaustive_number_hits: bool) -> &mut Search<'a> {
@@ -116,21 +201,130 @@ impl<'a> Search<'a> {
         self
     }
 
-    fn is_typo_authorized(&self) -> Result<bool> {
-        let index_authorizes_typos = self.index.authorize_typos(self.rtxn)?;
-        // only authorize typos if both the index and the query allow it.
-        Ok(self.authorize_typos && index_authorizes_typos)
+    /// Returns, along with each document id, the details of the bucket
+    /// it was found in for every ranking rule that was applied.
+    pub fn with_scores(&mut self, value: bool) -> &mut Search<'a> {
+        self.scores = value;
+        self
+    }
+
+    /// Reuses the bitmaps the ranking rules are done with from one bucket to the next,
+    /// instead of allocating new ones for every bucket.
+    pub fn bitmap_arena(&mut self, value: bool) -> &mut Search<'a> {
+        self.bitmap_arena = value;
+        self
+    }
+
+    /// Returns, along with a full page of documents, the continuation
+    /// that must be given to [`Search::continue_from`] to get the next page.
+    pub fn with_continuation(&mut self, value: bool) -> &mut Search<'a> {
+        self.with_continuation = value;
+        self
+    }
+
+    /// Resumes the search after the documents of the page that returned this continuation,
+    /// the offset is applied after them. The continuation of this page is returned too.
+    ///
+    /// The search must be the same as the one that returned the continuation, only the limit
+    /// may change, and the index must not have been updated since, otherwise an error is
+    /// returned. The ranking rules resume from the buckets of the last document of that page,
+    /// the buckets ranked before it are not split again.
+    pub fn continue_from(&mut self, continuation: &'a [u8]) -> &mut Search<'a> {
+        self.continue_from = Some(continuation);
+        self
+    }
+
+    /// Stops the search between two buckets of the ranking rules once the budget is spent,
+    /// the documents found so far are returned and the result is marked as `degraded`.
+    pub fn time_budget(&mut self, budget: Duration) -> &mut Search<'a> {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    /// Caps the work of the proximity and attribute ranking rules, the rules that reach
+    /// a cap stop ranking precisely and are listed in the `degraded_by` rules of the result.
+    pub fn limits(&mut self, limits: SearchLimits) -> &mut Search<'a> {
+        self.limits = limits;
+        self
+    }
+
+    /// Returns how the query is tokenized, normalized and derived into the words of the index,
+    /// without executing the search.
+    pub fn analyze(&self) -> Result<QueryAnalysis> {
+        let query = match self.query.as_ref() {
+            Some(query) => query,
+            None => return Ok(QueryAnalysis::default()),
+        };
+
+        let mut tokbuilder = TokenizerBuilder::new();
+        let stop_words = self.index.stop_words(self.rtxn)?;
+        if let Some(ref stop_words) = stop_words {
+            tokbuilder.stop_words(stop_words);
+        }
+        let tokenizer = tokbuilder.build();
+
+        let context = self.index.searchable_context(self.rtxn)?;
+        let mut query_tokens = context.dictionary.merge_tokens(query, tokenizer.tokenize(query));
+        if context.count_diacritics {
+            query_tokens = restore_diacritics(query, query_tokens);
+        }
+        let tokens = query_tokens
+            .iter()
+            .filter(|token| !token.is_separator())
+            .map(|token| AnalyzedToken::new(query, token))
+            .collect();
+
+        let authorize_typos = self.authorize_typos && context.authorize_typos;
+        let mut builder = QueryTreeBuilder::new(self.rtxn, self.index)?;
+        builder.terms_matching_strategy(self.terms_matching_strategy);
+        builder.authorize_typos(authorize_typos);
+        builder.enable_word_splitting(context.enable_word_splitting);
+        builder.enable_word_concatenation(context.enable_word_concatenation);
+        builder.words_limit(self.words_limit);
+        let terms = match builder.build(query_tokens)?.0 {
+            Some((_, primitive_query, _)) => {
+                analysis::analyze_terms(self.index, self.rtxn, authorize_typos, &primitive_query)?
+            }
+            None => Vec::new(),
+        };
+
+        Ok(QueryAnalysis { tokens, terms })
     }
 
     pub fn execute(&self) -> Result<SearchResult> {
+        let deadline = self.time_budget.and_then(|budget| Instant::now().checked_add(budget));
+        let context = self.index.searchable_context(self.rtxn)?;
+
+        // We check that the continuation, if any, can be used on this version of the index.
+        let continuation = match self.continue_from {
+            Some(bytes) => {
+                let continuation = SearchContinuation::decode(bytes)
+                    .ok_or(UserError::InvalidSearchContinuation)?;
+                if continuation.update_id != context.update_id {
+                    return Err(UserError::ExpiredSearchContinuation.into());
+                }
+                if continuation.parameters_hash != self.parameters_hash()? {
+                    return Err(UserError::MismatchedSearchContinuation.into());
+                }
+                Some(continuation)
+            }
+            None => None,
+        };
+
         // We create the query tree by spliting the query into tokens.
         let before = Instant::now();
+        let mut negative_query = Vec::new();
         let (query_tree, primitive_query, matching_words) = match self.query.as_ref() {
             Some(query) => {
                 let mut builder = QueryTreeBuilder::new(self.rtxn, self.index)?;
                 builder.terms_matching_strategy(self.terms_matching_strategy);
 
-                builder.authorize_typos(self.is_typo_authorized()?);
+                // only authorize typos if both the index and the query allow it.
+                builder.authorize_typos(self.authorize_typos && context.authorize_typos);
+                builder.enable_word_splitting(context.enable_word_splitting);
+                builder.enable_word_concatenation(context.enable_word_concatenation);
+                builder.negative_terms(context.enable_negative_terms);
+                builder.phrase_slop(self.phrase_slop);
 
                 builder.words_limit(self.words_limit);
                 // We make sure that the analyzer is aware of the stop words
@@ -142,10 +336,13 @@ impl<'a> Search<'a> {
                 }
 
                 let tokenizer = tokbuilder.build();
-                let tokens = tokenizer.tokenize(query);
-                builder
-                    .build(tokens)?
-                    .map_or((None, None, None), |(qt, pq, mw)| (Some(qt), Some(pq), Some(mw)))
+                let mut tokens = context.dictionary.merge_tokens(query, tokenizer.tokenize(query));
+                if context.count_diacritics {
+                    tokens = restore_diacritics(query, tokens);
+                }
+                let (query, negative) = builder.build(tokens)?;
+                negative_query = negative;
+                query.map_or((None, None, None), |(qt, pq, mw)| (Some(qt), Some(pq), Some(mw)))
             }
             None => (None, None, None),
         };
@@ -154,29 +351,46 @@ impl<'a> Search<'a> {
 
         // We create the original candidates with the facet conditions results.
         let before = Instant::now();
-        let filtered_candidates = match &self.filter {
-            Some(condition) => Some(condition.evaluate(self.rtxn, self.index)?),
-            None => None,
+        let mut filtered_candidates = match (&self.filtered_candidates, &self.filter) {
+            (Some(candidates), _) => Some(candidates.clone()),
+            (None, Some(condition)) => Some(condition.evaluate(self.rtxn, self.index)?),
+            (None, None) => None,
         };
 
+        if let Some(range) = &self.candidates_range {
+            let mut candidates = match filtered_candidates {
+                Some(candidates) => candidates,
+                None => self.index.documents_ids(self.rtxn)?,
+            };
+            retain_range(&mut candidates, range);
+            filtered_candidates = Some(candidates);
+        }
+
         debug!("facet candidates: {:?} took {:.02?}", filtered_candidates, before.elapsed());
 
+        let filtered_universe_len = match &filtered_candidates {
+            Some(candidates) => candidates.len(),
+            None => self.index.number_of_documents(self.rtxn)?,
+        };
+
         // We check that we are allowed to use the sort criteria, we check
         // that they are declared in the sortable fields.
         if let Some(sort_criteria) = &self.sort_criteria {
-            let sortable_fields = self.index.sortable_fields(self.rtxn)?;
+            let sortable_fields = &context.sortable_fields;
             for asc_desc in sort_criteria {
                 match asc_desc.member() {
-                    Member::Field(ref field) if !crate::is_faceted(field, &sortable_fields) => {
+                    Member::Field(ref field) | Member::CollatedField(ref field, _)
+                        if !crate::is_faceted(field, sortable_fields) =>
+                    {
                         return Err(UserError::InvalidSortableAttribute {
                             field: field.to_string(),
-                            valid_fields: sortable_fields.into_iter().collect(),
+                            valid_fields: sortable_fields.iter().cloned().collect(),
                         })?
                     }
-                    Member::Geo(_) if !sortable_fields.contains("_geo") => {
+                    Member::Geo(_) | Member::GeoPoints(_) if !sortable_fields.contains("_geo") => {
                         return Err(UserError::InvalidSortableAttribute {
                             field: "_geo".to_string(),
-                            valid_fields: sortable_fields.into_iter().collect(),
+                            valid_fields: sortable_fields.iter().cloned().collect(),
                         })?
                     }
                     _ => (),
@@ -184,30 +398,71 @@ impl<'a> Search<'a> {
             }
         }
 
+        // We check that the documents can be grouped by the field.
+        if let Some((field, _)) = &self.group_by {
+            let filterable_fields = self.index.filterable_fields(self.rtxn)?;
+            if !crate::is_faceted(field, &filterable_fields) {
+                return Err(UserError::InvalidGroupByAttribute {
+                    field: field.to_string(),
+                    valid_fields: filterable_fields.into_iter().collect(),
+                })?;
+            }
+        }
+
         // We check that the sort ranking rule exists and throw an
         // error if we try to use it and that it doesn't.
-        let sort_ranking_rule_missing = !self.index.criteria(self.rtxn)?.contains(&Criterion::Sort);
+        let sort_ranking_rule_missing = !context.criteria.contains(&Criterion::Sort);
         let empty_sort_criteria = self.sort_criteria.as_ref().map_or(true, |s| s.is_empty());
         if sort_ranking_rule_missing && !empty_sort_criteria {
             return Err(UserError::SortRankingRuleMissing.into());
         }
 
-        let criteria_builder = criteria::CriteriaBuilder::new(self.rtxn, self.index)?;
+        let mut criteria_builder =
+            criteria::CriteriaBuilder::new(self.rtxn, self.index, self.bitmap_arena)?;
+        if let Some(deadline) = deadline {
+            criteria_builder.deadline(deadline);
+        }
+        criteria_builder.limits(self.limits);
+        criteria_builder.phrase_slop(self.phrase_slop);
+        if let Some(searchable_attributes) = self.searchable_attributes {
+            let fields_ids = self.searchable_attributes_ids(&context, searchable_attributes)?;
+            criteria_builder.restricted_fields_ids(fields_ids);
+        }
+
+        // We exclude the documents that contain the negative terms before the criteria run.
+        let filtered_candidates = if negative_query.is_empty() {
+            filtered_candidates
+        } else {
+            let negative_docids =
+                criteria::resolve_negative_query(&criteria_builder, &negative_query)?;
+            debug!("negative terms: {:?} exclude {:?}", negative_query, negative_docids);
+            let candidates = match filtered_candidates {
+                Some(candidates) => candidates,
+                None => self.index.documents_ids(self.rtxn)?,
+            };
+            Some(candidates - negative_docids)
+        };
 
-        match self.index.distinct_field(self.rtxn)? {
+        let mut result = match context.distinct_field.as_deref() {
             None => {
-                let criteria = criteria_builder.build::<NoopDistinct>(
+                let criteria = criteria_builder.build(
                     query_tree,
                     primitive_query,
                     filtered_candidates,
                     self.sort_criteria.clone(),
                     self.exhaustive_number_hits,
-                    None,
                 )?;
-                self.perform_sort(NoopDistinct, matching_words.unwrap_or_default(), criteria)
+                self.perform_sort(
+                    NoopDistinct,
+                    matching_words.unwrap_or_default(),
+                    criteria,
+                    continuation,
+                    criteria_builder.time_budget(),
+                    criteria_builder.iteration_budget(),
+                )
             }
             Some(name) => {
-                let field_ids_map = self.index.fields_ids_map(self.rtxn)?;
+                let field_ids_map = &context.fields_ids_map;
                 match field_ids_map.id(name) {
                     Some(fid) => {
                         let distinct = FacetDistinct::new(fid, self.index, self.rtxn);
@@ -218,14 +473,176 @@ impl<'a> Search<'a> {
                             filtered_candidates,
                             self.sort_criteria.clone(),
                             self.exhaustive_number_hits,
-                            Some(distinct.clone()),
                         )?;
-                        self.perform_sort(distinct, matching_words.unwrap_or_default(), criteria)
+                        self.perform_sort(
+                            distinct,
+                            matching_words.unwrap_or_default(),
+                            criteria,
+                            continuation,
+                            criteria_builder.time_budget(),
+                            criteria_builder.iteration_budget(),
+                        )
                     }
                     None => Ok(SearchResult::default()),
                 }
             }
+        }?;
+
+        result.filtered_universe_len = filtered_universe_len;
+        Ok(result)
+    }
+
+    /// Executes the search and computes the distribution of the values of the given facets
+    /// over its candidates, the documents of all the pages that match the query and the filter.
+    ///
+    /// The facets must be filterable, like with a [`FacetDistribution`], and displayed. The
+    /// number of values of each facet is truncated to the `max_values_per_facet` setting of the
+    /// index. The number of hits is always exhaustive, for the candidates to be all the documents
+    /// matching the search and not only the ones ranked to fill the page.
+    pub fn execute_with_facets(
+        &self,
+        fields: &[&str],
+    ) -> Result<(SearchResult, BTreeMap<String, BTreeMap<String, u64>>)> {
+        if let Some(displayed_fields) = self.index.displayed_fields(self.rtxn)? {
+            let facets_name: BTreeSet<_> = fields
+                .iter()
+                .filter(|field| !crate::is_faceted(field, &displayed_fields))
+                .map(|field| field.to_string())
+                .collect();
+            if !facets_name.is_empty() {
+                return Err(UserError::UndisplayedFacetsDistribution { facets_name }.into());
+            }
+        }
+
+        let mut search = self.clone();
+        search.exhaustive_number_hits(true);
+        let result = search.execute()?;
+        let max_values_per_facet =
+            self.index.max_values_per_facet(self.rtxn)?.unwrap_or(DEFAULT_VALUES_PER_FACET);
+        let distribution = FacetDistribution::new(self.rtxn, self.index)
+            .facets(fields)
+            .max_values_per_facet(max_values_per_facet)
+            .candidates(result.candidates.clone())
+            .execute()?;
+        Ok((result, distribution))
+    }
+
+    /// Explains why a document is ranked before another one by this search, by ranking
+    /// only these two documents and returning the buckets of every ranking rule they fall in.
+    ///
+    /// The offset, the limit and the continuation of the search are ignored.
+    pub fn explain_pair(&self, doc_a: DocumentId, doc_b: DocumentId) -> Result<PairExplanation> {
+        let pair: RoaringBitmap = [doc_a, doc_b].iter().copied().collect();
+        let candidates = match (&self.filtered_candidates, &self.filter) {
+            (Some(candidates), _) => candidates & pair,
+            (None, Some(condition)) => condition.evaluate(self.rtxn, self.index)? & pair,
+            (None, None) => pair,
+        };
+
+        let mut search = Search {
+            query: self.query.clone(),
+            filtered_candidates: Some(candidates),
+            candidates_range: self.candidates_range.clone(),
+            sort_criteria: self.sort_criteria.clone(),
+            searchable_attributes: self.searchable_attributes,
+            terms_matching_strategy: self.terms_matching_strategy,
+            authorize_typos: self.authorize_typos,
+            phrase_slop: self.phrase_slop,
+            words_limit: self.words_limit,
+            bitmap_arena: self.bitmap_arena,
+            limits: self.limits,
+            ..Search::new(self.rtxn, self.index)
+        };
+        search.limit(2).with_scores(true);
+        let SearchResult { documents_ids, score_details, .. } = search.execute()?;
+
+        let details_of = |docid| {
+            documents_ids.iter().zip(&score_details).find(|(id, _)| **id == docid).map(|(_, d)| d)
+        };
+        let doc_a_details = details_of(doc_a).cloned();
+        let doc_b_details = details_of(doc_b).cloned();
+        let diverged_at = match (&doc_a_details, &doc_b_details) {
+            (Some(a), Some(b)) => a.iter().zip(b).position(|(a, b)| a != b),
+            _ => None,
+        };
+
+        Ok(PairExplanation {
+            doc_a,
+            doc_b,
+            first: documents_ids.first().copied(),
+            doc_a_details,
+            doc_b_details,
+            diverged_at,
+        })
+    }
+
+    /// Returns a hash of the parameters of the search that decide the ranking of the documents,
+    /// a continuation is only valid for a search with the parameters of the one that returned it.
+    fn parameters_hash(&self) -> Result<u64> {
+        let mut context = md5::Context::new();
+        let criteria = self.index.criteria(self.rtxn)?;
+        let distinct_field = self.index.distinct_field(self.rtxn)?;
+        let parameters = format!(
+            "{:?}\0{}\0{:?}\0{:?}\0{:?}\0{:?}\0{:?}\0{}\0{}\0{}\0{:?}\0{:?}",
+            self.query,
+            self.filter.as_ref().map(ToString::to_string).unwrap_or_default(),
+            self.candidates_range,
+            self.sort_criteria,
+            self.searchable_attributes,
+            self.terms_matching_strategy,
+            self.authorize_typos,
+            self.phrase_slop,
+            self.words_limit,
+            self.exhaustive_number_hits,
+            criteria,
+            distinct_field,
+        );
+        context.consume(parameters.as_bytes());
+        if let Some(candidates) = &self.filtered_candidates {
+            let mut bytes = Vec::with_capacity(candidates.serialized_size());
+            candidates.serialize_into(&mut bytes)?;
+            context.consume(&bytes);
+        }
+
+        let digest = context.compute();
+        let mut hash = [0; 8];
+        hash.copy_from_slice(&digest[..8]);
+        Ok(u64::from_be_bytes(hash))
+    }
+
+    /// Returns the ids of the given attributes, which must be searchable.
+    fn searchable_attributes_ids(
+        &self,
+        context: &SearchableContext,
+        attributes: &[&str],
+    ) -> Result<Vec<FieldId>> {
+        let fields_ids_map = &context.fields_ids_map;
+        let searchable_fields = &context.searchable_fields;
+
+        let mut fields_ids = Vec::new();
+        for &attribute in attributes {
+            let field_id = fields_ids_map.id(attribute);
+            let is_searchable = match searchable_fields {
+                Some(searchable_fields) => searchable_fields.iter().any(|name| name == attribute),
+                None => field_id.is_some(),
+            };
+
+            if !is_searchable {
+                let valid_fields = match searchable_fields {
+                    Some(searchable_fields) => searchable_fields.iter().cloned().collect(),
+                    None => fields_ids_map.names().map(String::from).collect(),
+                };
+                return Err(UserError::InvalidSearchableAttribute {
+                    field: attribute.to_string(),
+                    valid_fields,
+                })?;
+            }
+
+            // A searchable attribute that no document has yet matches nothing.
+            fields_ids.extend(field_id);
         }
+
+        Ok(fields_ids)
     }
 
     fn perform_sort<D: Distinct>(
@@ -233,42 +650,178 @@ impl<'a> Search<'a> {
         mut distinct: D,
         matching_words: MatchingWords,
         mut criteria: Final,
+        continuation: Option<SearchContinuation>,
+        time_budget: &TimeBudget,
+        iteration_budget: &IterationBudget,
     ) -> Result<SearchResult> {
         let mut offset = self.offset;
         let mut initial_candidates = RoaringBitmap::new();
-        let mut excluded_candidates = self.index.soft_deleted_documents_ids(self.rtxn)?;
+        let soft_deleted_documents = self.index.soft_deleted_documents_ids(self.rtxn)?;
+        let has_distinct_field = self.index.distinct_field(self.rtxn)?.is_some();
+        // The documents excluded by the distinct attribute are not excluded from the buckets
+        // of the criteria, the buckets are then the same on every page of a continued search.
+        let mut distinct_excluded = RoaringBitmap::new();
         let mut documents_ids = Vec::new();
+        let mut score_details = Vec::new();
+
+        // When the documents are grouped, the offset and the limit apply to the groups.
+        let mut grouper = match &self.group_by {
+            Some((field, group_limit)) => {
+                let field_id = self.index.fields_ids_map(self.rtxn)?.id(field);
+                let groups_limit = self.offset.saturating_add(self.limit);
+                Some(Grouper::new(self.index, self.rtxn, field_id, *group_limit, groups_limit))
+            }
+            None => None,
+        };
+        let mut grouped_score_details = HashMap::new();
+
+        // The criteria resume after the last document of the previous page and the documents
+        // sharing their distinct value with the ones of the previous pages are excluded.
+        let previously_returned = match continuation {
+            Some(SearchContinuation { position, last_docid, returned, .. }) => {
+                criteria.resume_from(position, last_docid);
+                if !returned.is_empty() {
+                    let mut returned_iter =
+                        distinct.distinct(returned.clone(), RoaringBitmap::new());
+                    for docid in returned_iter.by_ref() {
+                        docid?;
+                    }
+                    distinct_excluded = returned_iter.into_excluded();
+                }
+                returned
+            }
+            None => RoaringBitmap::new(),
+        };
+        let mut skipped = RoaringBitmap::new();
+        // The score details and the id of the last document returned or skipped by the offset.
+        let mut last_document = None;
 
-        while let Some(FinalResult { candidates, bucket_candidates, .. }) =
-            criteria.next(&excluded_candidates)?
+        while let Some(FinalResult {
+            candidates, bucket_candidates, score_details: scores, ..
+        }) = criteria.next(&soft_deleted_documents)?
         {
             debug!("Number of candidates found {}", candidates.len());
 
-            let excluded = take(&mut excluded_candidates);
+            let excluded = take(&mut distinct_excluded);
             let mut candidates = distinct.distinct(candidates, excluded);
 
             initial_candidates |= bucket_candidates;
 
-            if offset != 0 {
-                let discarded = candidates.by_ref().take(offset).count();
-                offset = offset.saturating_sub(discarded);
-            }
+            if let Some(grouper) = grouper.as_mut() {
+                for candidate in candidates.by_ref() {
+                    let candidate = candidate?;
+                    if grouper.insert(candidate)? && self.scores {
+                        grouped_score_details.insert(candidate, scores.clone());
+                    }
+                    if grouper.is_full() {
+                        break;
+                    }
+                }
+            } else {
+                let mut last_docid = None;
+                if offset != 0 {
+                    let mut discarded = 0;
+                    for candidate in candidates.by_ref().take(offset) {
+                        if let Ok(candidate) = candidate {
+                            skipped.insert(candidate);
+                            last_docid = Some(candidate);
+                        }
+                        discarded += 1;
+                    }
+                    offset = offset.saturating_sub(discarded);
+                }
 
-            for candidate in candidates.by_ref().take(self.limit - documents_ids.len()) {
-                documents_ids.push(candidate?);
+                for candidate in candidates.by_ref().take(self.limit - documents_ids.len()) {
+                    let candidate = candidate?;
+                    documents_ids.push(candidate);
+                    last_docid = Some(candidate);
+                    if self.scores {
+                        score_details.push(scores.clone());
+                    }
+                }
+
+                if let Some(last_docid) = last_docid {
+                    last_document = Some((scores, last_docid));
+                }
             }
 
-            excluded_candidates |= candidates.into_excluded();
+            distinct_excluded = candidates.into_excluded();
 
-            if documents_ids.len() == self.limit {
+            let is_full =
+                grouper.as_ref().map_or(documents_ids.len() == self.limit, Grouper::is_full);
+            if is_full {
                 break;
             }
         }
 
+        let groups = match grouper {
+            Some(grouper) => {
+                let groups = grouper.into_groups(self.offset, self.limit);
+                for docid in groups.iter().flat_map(|group| &group.documents_ids) {
+                    documents_ids.push(*docid);
+                    if let Some(details) = grouped_score_details.remove(docid) {
+                        score_details.push(details);
+                    }
+                }
+                groups
+            }
+            None => Vec::new(),
+        };
+
+        let continuation = match last_document {
+            Some((scores, last_docid))
+                if (self.with_continuation || self.continue_from.is_some())
+                    && self.group_by.is_none()
+                    && documents_ids.len() == self.limit =>
+            {
+                // The documents returned so far are only needed to apply the distinct attribute.
+                let returned = if has_distinct_field {
+                    let mut returned = previously_returned | skipped;
+                    returned.extend(documents_ids.iter().copied());
+                    returned
+                } else {
+                    RoaringBitmap::new()
+                };
+                let continuation = SearchContinuation {
+                    update_id: self.index.update_id(self.rtxn)?,
+                    parameters_hash: self.parameters_hash()?,
+                    position: BucketPosition::from_score_details(&scores),
+                    last_docid,
+                    returned,
+                };
+                Some(continuation.encode())
+            }
+            _ => None,
+        };
+
+        // The buckets of the previous pages are returned by the criteria too, their
+        // candidates are the ones of a search that would have skipped them with the offset.
+        let initial_candidates = initial_candidates - &soft_deleted_documents;
+        let candidates_before_distinct_len =
+            if has_distinct_field { Some(initial_candidates.len()) } else { None };
+        let candidates = if has_distinct_field && self.exhaustive_number_hits {
+            // The candidates are all the matching documents, the distinct attribute
+            // is applied to all of them for the number of hits to be exhaustive too.
+            let mut candidates = RoaringBitmap::new();
+            for docid in distinct.distinct(initial_candidates, RoaringBitmap::new()) {
+                candidates.insert(docid?);
+            }
+            candidates
+        } else {
+            initial_candidates - distinct_excluded
+        };
+
         Ok(SearchResult {
             matching_words,
-            candidates: initial_candidates - excluded_candidates,
+            candidates,
             documents_ids,
+            score_details,
+            groups,
+            continuation,
+            degraded: time_budget.was_exceeded(),
+            degraded_by: iteration_budget.degraded_by(),
+            filtered_universe_len: 0,
+            candidates_before_distinct_len,
         })
     }
 }
@@ -278,26 +831,48 @@ impl fmt::Debug for Search<'_> {
         let Search {
             query,
             filter,
+            filtered_candidates,
+            candidates_range,
+            group_by,
             offset,
             limit,
             sort_criteria,
+            searchable_attributes,
             terms_matching_strategy,
             authorize_typos,
+            phrase_slop,
             words_limit,
             exhaustive_number_hits,
+            scores,
+            bitmap_arena,
+            with_continuation,
+            continue_from,
+            time_budget,
+            limits,
             rtxn: _,
             index: _,
         } = self;
         f.debug_struct("Search")
             .field("query", query)
             .field("filter", filter)
+            .field("filtered_candidates", filtered_candidates)
+            .field("candidates_range", candidates_range)
+            .field("group_by", group_by)
             .field("offset", offset)
             .field("limit", limit)
             .field("sort_criteria", sort_criteria)
+            .field("searchable_attributes", searchable_attributes)
             .field("terms_matching_strategy", terms_matching_strategy)
             .field("authorize_typos", authorize_typos)
+            .field("phrase_slop", phrase_slop)
             .field("exhaustive_number_hits", exhaustive_number_hits)
             .field("words_limit", words_limit)
+            .field("scores", scores)
+            .field("bitmap_arena", bitmap_arena)
+            .field("with_continuation", with_continuation)
+            .field("continue_from", &continue_from.map(|bytes| bytes.len()))
+            .field("time_budget", time_budget)
+            .field("limits", limits)
             .finish()
     }
 }
@@ -306,8 +881,46 @@ impl fmt::Debug for Search<'_> {
 pub struct SearchResult {
     pub matching_words: MatchingWords,
     pub candidates: RoaringBitmap,
-    // TODO those documents ids should be associated with their criteria scores.
     pub documents_ids: Vec<DocumentId>,
+    /// The details of the ranking rules buckets of each document, in the same order
+    /// as the `documents_ids`. Empty unless the search was made `with_scores`.
+    pub score_details: Vec<ScoreDetails>,
+    /// The groups of documents of a search made with [`Search::group_by`], in order.
+    /// Empty when the documents are not grouped.
+    pub groups: Vec<DocumentsGroup>,
+    /// The opaque state to give to [`Search::continue_from`] to get the next page, only returned
+    /// when the page is full and the search was made `with_continuation` or continued.
+    pub continuation: Option<Vec<u8>>,
+    /// Whether the time budget of the search was exceeded, the documents are then
+    /// only the first ones of the documents a search without budget returns.
+    pub degraded: bool,
+    /// The ranking rules that reached one of the [`SearchLimits`] of the search, the documents
+    /// they could not rank are returned after the ones they ranked.
+    pub degraded_by: Vec<RuleName>,
+    /// The number of documents matching the filter, whatever the query,
+    /// or the number of documents of the index when there is no filter.
+    pub filtered_universe_len: u64,
+    /// The number of candidates before the distinct attribute removes the duplicates,
+    /// `None` when there is no distinct attribute.
+    pub candidates_before_distinct_len: Option<u64>,
+}
+
+/// Why a document is ranked before another one, see [`Search::explain_pair`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PairExplanation {
+    pub doc_a: DocumentId,
+    pub doc_b: DocumentId,
+    /// The document ranked first, `None` when the search returns neither of them.
+    pub first: Option<DocumentId>,
+    /// The buckets `doc_a` falls in for every ranking rule,
+    /// `None` when the search doesn't return it.
+    pub doc_a_details: Option<ScoreDetails>,
+    /// The buckets `doc_b` falls in for every ranking rule,
+    /// `None` when the search doesn't return it.
+    pub doc_b_details: Option<ScoreDetails>,
+    /// The position of the first ranking rule that puts the documents in different buckets,
+    /// `None` when they are in the same buckets for every ranking rule or one isn't returned.
+    pub diverged_at: Option<usize>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -395,6 +1008,14 @@ pub fn word_derivations<'c>(
     }
 }
 
+/// Removes the documents ids that are not within the range from the candidates.
+pub(crate) fn retain_range(candidates: &mut RoaringBitmap, range: &RangeInclusive<DocumentId>) {
+    candidates.remove_range(..*range.start());
+    if let Some(after_end) = range.end().checked_add(1) {
+        candidates.remove_range(after_end..);
+    }
+}
+
 fn get_first(s: &str) -> &str {
     match s.chars().next() {
         Some(c) => &s[..c.len_utf8()],
@@ -418,8 +1039,14 @@ pub fn build_dfa(word: &str, typos: u8, is_prefix: bool) -> DFA {
 
 #[cfg(test)]
 mod test {
+    use std::sync::Arc;
+
+    use big_s::S;
+    use maplit::{btreemap, btreeset, hashmap, hashset};
+
     use super::*;
     use crate::index::tests::TempIndex;
+    use crate::proximity::ProximityPrecision;
 
     #[test]
     fn test_is_authorized_typos() {
@@ -446,6 +1073,615 @@ mod test {
         assert!(!search.is_typo_authorized().unwrap());
     }
 
+    #[test]
+    fn disabled_word_splitting_and_concatenation() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 0, "text": "ABC 123" },
+                { "id": 1, "text": "XYZ789" },
+            ]))
+            .unwrap();
+
+        let search_ids = |query: &str| {
+            let rtxn = index.read_txn().unwrap();
+            let mut search = Search::new(&rtxn, &index);
+            search.query(query);
+            search.execute().unwrap().documents_ids
+        };
+
+        assert_eq!(search_ids("ABC123"), vec![0]);
+        assert_eq!(search_ids("XYZ 789"), vec![1]);
+
+        index.update_settings(|s| s.set_enable_word_splitting(false)).unwrap();
+        assert!(search_ids("ABC123").is_empty());
+        assert_eq!(search_ids("XYZ 789"), vec![1]);
+
+        index.update_settings(|s| s.set_enable_word_concatenation(false)).unwrap();
+        assert!(search_ids("ABC123").is_empty());
+        assert!(search_ids("XYZ 789").is_empty());
+
+        index
+            .update_settings(|s| {
+                s.reset_enable_word_splitting();
+                s.reset_enable_word_concatenation();
+            })
+            .unwrap();
+        assert_eq!(search_ids("ABC123"), vec![0]);
+        assert_eq!(search_ids("XYZ 789"), vec![1]);
+    }
+
+    #[test]
+    fn negative_terms_exclude_documents() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 0, "text": "leather jacket" },
+                { "id": 1, "text": "denim jacket" },
+                { "id": 2, "text": "faux leather jacket" },
+                { "id": 3, "text": "leather boots" },
+                { "id": 4, "text": "rain boots" },
+            ]))
+            .unwrap();
+
+        let search_ids = |query: &str| {
+            let rtxn = index.read_txn().unwrap();
+            let mut search = Search::new(&rtxn, &index);
+            search.query(query);
+            let mut ids = search.execute().unwrap().documents_ids;
+            ids.sort_unstable();
+            ids
+        };
+
+        assert_eq!(search_ids("jacket -leather"), vec![1]);
+        assert_eq!(search_ids("jacket -\"faux leather\""), vec![0, 1]);
+        // A query of negative terms only returns all the other documents.
+        assert_eq!(search_ids("-leather"), vec![1, 4]);
+        assert_eq!(search_ids("-jacket -boots"), Vec::<u32>::new());
+        // The negative words are neither prefixes nor derived with typos.
+        assert_eq!(search_ids("-leath"), vec![0, 1, 2, 3, 4]);
+        assert_eq!(search_ids("-lether"), vec![0, 1, 2, 3, 4]);
+        // A minus inside a word is not a negative term.
+        assert_eq!(search_ids("rain-boots"), vec![4]);
+
+        index.update_settings(|s| s.set_enable_negative_terms(false)).unwrap();
+        assert_eq!(search_ids("-leather"), vec![0, 2, 3]);
+
+        index.update_settings(|s| s.reset_enable_negative_terms()).unwrap();
+        assert_eq!(search_ids("-leather"), vec![1, 4]);
+    }
+
+    #[test]
+    fn dictionary_words_are_not_split() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 0, "text": "I write C++ every day" },
+                { "id": 1, "text": "plan c for the weekend" },
+                { "id": 2, "text": "c++ and rust" },
+            ]))
+            .unwrap();
+
+        let search_ids = |query: &str| {
+            let rtxn = index.read_txn().unwrap();
+            let mut search = Search::new(&rtxn, &index);
+            search.query(query);
+            let mut ids = search.execute().unwrap().documents_ids;
+            ids.sort_unstable();
+            ids
+        };
+
+        // the pluses are separators, the query is a single `c`
+        assert_eq!(search_ids("c++"), vec![0, 1, 2]);
+
+        index.update_settings(|s| s.set_dictionary(btreeset! { S("C++") })).unwrap();
+        assert_eq!(search_ids("c++"), vec![0, 2]);
+        assert_eq!(search_ids("C++ rust"), vec![2]);
+        assert_eq!(search_ids("c weekend"), vec![1]);
+
+        index.update_settings(|s| s.reset_dictionary()).unwrap();
+        assert_eq!(search_ids("c++"), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn sharded_searches_return_the_unsharded_documents() {
+        let index = TempIndex::new();
+        let documents: Vec<_> = (0..100)
+            .map(|i| serde_json::json!({ "id": i, "text": format!("hello world {}", i % 3) }))
+            .collect();
+        index.add_documents(documents!(documents)).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let shards = [0..=20, 21..=63, 64..=u32::MAX];
+        for query in [None, Some("hello"), Some("world 1")] {
+            let search_ids = |range: Option<RangeInclusive<DocumentId>>| {
+                let mut search = Search::new(&rtxn, &index);
+                search.limit(1000);
+                if let Some(query) = query {
+                    search.query(query);
+                }
+                if let Some(range) = range {
+                    search.candidates_within(range);
+                }
+                search.execute().unwrap().documents_ids.into_iter().collect::<RoaringBitmap>()
+            };
+
+            let unsharded = search_ids(None);
+            assert!(!unsharded.is_empty());
+            let mut sharded = RoaringBitmap::new();
+            for range in shards.clone() {
+                let shard = search_ids(Some(range.clone()));
+                assert!(shard.iter().all(|id| range.contains(&id)), "{:?}", range);
+                sharded |= shard;
+            }
+            assert_eq!(sharded, unsharded, "query: {:?}", query);
+        }
+    }
+
+    #[test]
+    fn weighted_searchable_fields() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|s| {
+                s.set_searchable_fields(vec![S("overview"), S("title")]);
+                s.set_criteria(vec![S("attribute")]);
+            })
+            .unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 0, "title": "the castle", "overview": "a story about a dragon" },
+                { "id": 1, "title": "a dragon story", "overview": "the castle" },
+                { "id": 2, "title": "the red dragon", "overview": "a dragon in the castle" },
+            ]))
+            .unwrap();
+
+        let search_ids = |query: &str| {
+            let rtxn = index.read_txn().unwrap();
+            let mut search = Search::new(&rtxn, &index);
+            search.query(query);
+            search.execute().unwrap().documents_ids
+        };
+
+        // Without weights the fields are ranked in the order of the settings.
+        assert_eq!(search_ids("dragon"), vec![2, 0, 1]);
+
+        // The weighted title is ranked before the overview, even though it comes after it.
+        index
+            .update_settings(|s| {
+                s.set_searchable_fields_with_weights(vec![
+                    (S("overview"), None),
+                    (S("title"), Some(3)),
+                ]);
+            })
+            .unwrap();
+        assert_eq!(search_ids("dragon"), vec![1, 2, 0]);
+
+        // The weights are ordinal, a much higher weight ranks the fields the same way.
+        index
+            .update_settings(|s| {
+                s.set_searchable_fields_with_weights(vec![
+                    (S("overview"), Some(1)),
+                    (S("title"), Some(100)),
+                ]);
+            })
+            .unwrap();
+        assert_eq!(search_ids("dragon"), vec![1, 2, 0]);
+
+        index
+            .update_settings(|s| s.set_searchable_fields(vec![S("overview"), S("title")]))
+            .unwrap();
+        assert_eq!(search_ids("dragon"), vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn proximity_precision_by_attribute() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|s| {
+                s.set_criteria(vec![S("proximity")]);
+            })
+            .unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 0, "title": "quick brown dog", "text": "the lazy fox" },
+                { "id": 1, "title": "quick brown big fox" },
+                { "id": 2, "title": "the quick fox" },
+                { "id": 3, "title": "fox", "text": "very quick" },
+            ]))
+            .unwrap();
+
+        let search_ids = |query: &str| {
+            let rtxn = index.read_txn().unwrap();
+            let mut search = Search::new(&rtxn, &index);
+            search.query(query);
+            search.terms_matching_strategy(TermsMatchingStrategy::All);
+            search.execute().unwrap().documents_ids
+        };
+
+        assert_eq!(search_ids("quick fox"), vec![2, 1, 0, 3]);
+        assert_eq!(search_ids("\"quick fox\""), vec![2]);
+
+        // The documents with both words in the same attribute are not sorted by proximity.
+        index
+            .update_settings(|s| s.set_proximity_precision(ProximityPrecision::ByAttribute))
+            .unwrap();
+        assert_eq!(search_ids("quick fox"), vec![1, 2, 0, 3]);
+        assert_eq!(search_ids("\"quick fox\""), vec![2]);
+
+        index.update_settings(|s| s.reset_proximity_precision()).unwrap();
+        assert_eq!(search_ids("quick fox"), vec![2, 1, 0, 3]);
+    }
+
+    #[test]
+    fn analyze_a_query_mixing_cjk_and_latin() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|s| {
+                s.set_stop_words(btreeset! { S("the") });
+            })
+            .unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 0, "title": "tokyo tower" },
+                { "id": 1, "title": "kyoto temple" },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let mut search = Search::new(&rtxn, &index);
+        search.query("The 书 Tokio");
+        let QueryAnalysis { tokens, terms } = search.analyze().unwrap();
+
+        // The normalized form of the CJK characters depends on the enabled charabia features.
+        let tokens: Vec<_> = tokens
+            .iter()
+            .map(|t| (t.original.as_str(), t.char_start, t.char_end, t.script, t.is_stop_word))
+            .collect();
+        insta::assert_debug_snapshot!(tokens, @r###"
+        [
+            (
+                "The",
+                0,
+                3,
+                Latin,
+                true,
+            ),
+            (
+                "书",
+                4,
+                5,
+                Cj,
+                false,
+            ),
+            (
+                "Tokio",
+                6,
+                11,
+                Latin,
+                false,
+            ),
+        ]
+        "###);
+
+        // The stop word has been dropped from the terms.
+        assert_eq!(terms.len(), 2);
+        assert!(!terms[0].is_prefix);
+        assert_eq!(terms[0].max_typos, 0);
+        assert!(terms[0].derivations.is_empty());
+        insta::assert_debug_snapshot!(terms[1], @r###"
+        AnalyzedTerm {
+            word: "tokio",
+            is_prefix: true,
+            in_phrase: false,
+            max_typos: 1,
+            derivations: [
+                (
+                    "tokyo",
+                    1,
+                ),
+            ],
+        }
+        "###);
+    }
+
+    #[test]
+    fn restrict_search_to_attributes() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 0, "title": "the lion king", "overview": "a young prince flees his kingdom" },
+                { "id": 1, "title": "the little prince", "overview": "a pilot lost in the desert" },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let mut search = Search::new(&rtxn, &index);
+        search.query("prince");
+        let SearchResult { mut documents_ids, .. } = search.execute().unwrap();
+        documents_ids.sort_unstable();
+        assert_eq!(documents_ids, vec![0, 1]);
+
+        search.searchable_attributes(&["title"]);
+        let SearchResult { documents_ids, .. } = search.execute().unwrap();
+        assert_eq!(documents_ids, vec![1]);
+
+        // `kingdom` is only present in the overview
+        search.query("kingdom");
+        let SearchResult { documents_ids, .. } = search.execute().unwrap();
+        assert!(documents_ids.is_empty());
+
+        search.searchable_attributes(&["overview"]);
+        let SearchResult { documents_ids, .. } = search.execute().unwrap();
+        assert_eq!(documents_ids, vec![0]);
+
+        search.searchable_attributes(&["director"]);
+        let error = search.execute().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Attribute `director` is not searchable. Available searchable attributes are: `id, overview, title`."
+        );
+    }
+
+    #[test]
+    fn score_details_of_the_ranking_rules() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|s| {
+                s.set_criteria(vec![S("typo"), S("sort")]);
+                s.set_sortable_fields(hashset! { S("price") });
+            })
+            .unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 0, "title": "elephant", "price": 20 },
+                { "id": 1, "title": "elephent", "price": 10 },
+                { "id": 2, "title": "elephant", "price": 10 },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let mut search = Search::new(&rtxn, &index);
+        search.query("elephant");
+        search.sort_criteria(vec![AscDesc::Asc(Member::Field(S("price")))]);
+
+        let SearchResult { documents_ids, score_details, .. } = search.execute().unwrap();
+        assert_eq!(documents_ids, vec![2, 0, 1]);
+        assert!(score_details.is_empty());
+
+        search.with_scores(true);
+        let SearchResult { documents_ids, score_details, .. } = search.execute().unwrap();
+        assert_eq!(documents_ids, vec![2, 0, 1]);
+        insta::assert_debug_snapshot!(score_details, @r###"
+        [
+            [
+                ScoreDetail {
+                    bucket: 0,
+                    rule: Typo {
+                        typos: 0,
+                    },
+                },
+                ScoreDetail {
+                    bucket: 0,
+                    rule: Sort {
+                        field_name: "price",
+                        ascending: true,
+                        value: Some(
+                            Number(
+                                10.0,
+                            ),
+                        ),
+                    },
+                },
+            ],
+            [
+                ScoreDetail {
+                    bucket: 0,
+                    rule: Typo {
+                        typos: 0,
+                    },
+                },
+                ScoreDetail {
+                    bucket: 1,
+                    rule: Sort {
+                        field_name: "price",
+                        ascending: true,
+                        value: Some(
+                            Number(
+                                20.0,
+                            ),
+                        ),
+                    },
+                },
+            ],
+            [
+                ScoreDetail {
+                    bucket: 1,
+                    rule: Typo {
+                        typos: 1,
+                    },
+                },
+                ScoreDetail {
+                    bucket: 0,
+                    rule: Sort {
+                        field_name: "price",
+                        ascending: true,
+                        value: Some(
+                            Number(
+                                10.0,
+                            ),
+                        ),
+                    },
+                },
+            ],
+        ]
+        "###);
+    }
+
+    #[test]
+    fn explain_a_pair_of_documents() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|s| {
+                s.set_criteria(vec![S("typo"), S("sort")]);
+                s.set_sortable_fields(hashset! { S("price") });
+                s.set_filterable_fields(hashset! { S("price") });
+            })
+            .unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 0, "title": "elephant", "price": 20 },
+                { "id": 1, "title": "elephent", "price": 10 },
+                { "id": 2, "title": "elephant", "price": 10 },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let mut search = Search::new(&rtxn, &index);
+        search.query("elephant");
+        search.sort_criteria(vec![AscDesc::Asc(Member::Field(S("price")))]);
+
+        insta::assert_debug_snapshot!(search.explain_pair(0, 2).unwrap(), @r###"
+        PairExplanation {
+            doc_a: 0,
+            doc_b: 2,
+            first: Some(
+                2,
+            ),
+            doc_a_details: Some(
+                [
+                    ScoreDetail {
+                        bucket: 0,
+                        rule: Typo {
+                            typos: 0,
+                        },
+                    },
+                    ScoreDetail {
+                        bucket: 1,
+                        rule: Sort {
+                            field_name: "price",
+                            ascending: true,
+                            value: Some(
+                                Number(
+                                    20.0,
+                                ),
+                            ),
+                        },
+                    },
+                ],
+            ),
+            doc_b_details: Some(
+                [
+                    ScoreDetail {
+                        bucket: 0,
+                        rule: Typo {
+                            typos: 0,
+                        },
+                    },
+                    ScoreDetail {
+                        bucket: 0,
+                        rule: Sort {
+                            field_name: "price",
+                            ascending: true,
+                            value: Some(
+                                Number(
+                                    10.0,
+                                ),
+                            ),
+                        },
+                    },
+                ],
+            ),
+            diverged_at: Some(
+                1,
+            ),
+        }
+        "###);
+
+        // the typo rule ranks the document without typo first, whatever its price
+        let explanation = search.explain_pair(1, 0).unwrap();
+        assert_eq!(explanation.first, Some(0));
+        assert_eq!(explanation.diverged_at, Some(0));
+
+        // a document filtered out is not explained
+        search.filter(Filter::from_str("price = 20").unwrap().unwrap());
+        let explanation = search.explain_pair(0, 2).unwrap();
+        assert_eq!(explanation.first, Some(0));
+        assert!(explanation.doc_a_details.is_some());
+        assert_eq!(explanation.doc_b_details, None);
+        assert_eq!(explanation.diverged_at, None);
+    }
+
+    #[test]
+    fn geo_sort_by_multiple_points() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|s| {
+                s.set_criteria(vec![S("sort")]);
+                s.set_sortable_fields(hashset! { S("_geo") });
+            })
+            .unwrap();
+
+        let paris = [48.8566, 2.3522];
+        let tokyo = [35.6762, 139.6503];
+        // the even documents are around Paris, the odd ones around Tokyo
+        let mut documents: Vec<_> = (0..36)
+            .map(|i| {
+                let [lat, lng] = if i % 2 == 0 { paris } else { tokyo };
+                let offset = i as f64 * 0.13;
+                serde_json::json!({ "id": i, "_geo": { "lat": lat + offset, "lng": lng - offset } })
+            })
+            .collect();
+        documents.push(serde_json::json!({ "id": 36 }));
+        documents.push(serde_json::json!({ "id": 37 }));
+        index.add_documents(documents!(documents)).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let sort = |asc_desc: AscDesc| {
+            let mut search = Search::new(&rtxn, &index);
+            search.limit(50);
+            search.sort_criteria(vec![asc_desc]);
+            search.execute().unwrap().documents_ids
+        };
+
+        let from_paris = sort(AscDesc::Asc(Member::Geo(paris)));
+        let from_tokyo = sort(AscDesc::Asc(Member::Geo(tokyo)));
+        let from_both = sort(AscDesc::Asc(Member::GeoPoints(vec![paris, tokyo])));
+        assert_eq!(from_both.len(), 38);
+
+        // the documents without geo data are in the trailing bucket
+        for ids in [&from_paris, &from_tokyo, &from_both] {
+            assert_eq!(ids[36..].iter().copied().collect::<RoaringBitmap>(), (36..38).collect());
+        }
+
+        // each cluster is ordered as if it was sorted by its own point
+        let even = |ids: &[u32]| {
+            ids.iter().copied().filter(|id| id % 2 == 0 && *id < 36).collect::<Vec<_>>()
+        };
+        let odd = |ids: &[u32]| {
+            ids.iter().copied().filter(|id| id % 2 == 1 && *id < 36).collect::<Vec<_>>()
+        };
+        assert_eq!(even(&from_both), even(&from_paris));
+        assert_eq!(odd(&from_both), odd(&from_tokyo));
+
+        // and the clusters are interleaved by the distance to their nearest point
+        let distance = |id: u32| {
+            let i = id as f64 * 0.13;
+            let center = if id % 2 == 0 { paris } else { tokyo };
+            crate::distance_between_two_points(&center, &[center[0] + i, center[1] - i])
+        };
+        assert!(from_both[..36].windows(2).all(|w| distance(w[0]) <= distance(w[1])));
+        assert_eq!(from_both[..2], [0, 1]);
+
+        // repeating a point doesn't change the order of the single point sort
+        let from_paris_twice = sort(AscDesc::Asc(Member::GeoPoints(vec![paris, paris])));
+        assert_eq!(from_paris_twice, from_paris);
+
+        let from_both_desc = sort(AscDesc::Desc(Member::GeoPoints(vec![paris, tokyo])));
+        let mut reversed = from_both[..36].to_vec();
+        reversed.reverse();
+        assert_eq!(from_both_desc[..36], reversed);
+    }
+
     #[test]
     fn test_one_typos_tolerance() {
         let fst = fst::Set::from_iter(["zealand"].iter()).unwrap().map_data(Cow::Owned).unwrap();
@@ -508,4 +1744,915 @@ mod test {
 
         assert_eq!(found, &[("zealand".to_string(), 1)]);
     }
+
+    #[test]
+    fn sort_by_booleans() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|s| {
+                s.set_criteria(vec![S("sort")]);
+                s.set_sortable_fields(hashset! { S("available") });
+            })
+            .unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 0, "available": true },
+                { "id": 1, "available": false },
+                { "id": 2, "available": 5 },
+                { "id": 3, "available": "maybe" },
+                { "id": 4 },
+                { "id": 5, "available": true },
+                { "id": 6, "available": false },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let sort = |asc_desc: AscDesc| {
+            let mut search = Search::new(&rtxn, &index);
+            search.sort_criteria(vec![asc_desc]);
+            search.execute().unwrap().documents_ids
+        };
+
+        // the booleans are sorted as the 0 and 1 numbers, before the strings
+        let ascending = sort(AscDesc::Asc(Member::Field(S("available"))));
+        assert_eq!(ascending, vec![1, 6, 0, 5, 2, 3, 4]);
+        let descending = sort(AscDesc::Desc(Member::Field(S("available"))));
+        assert_eq!(descending, vec![2, 0, 5, 1, 6, 3, 4]);
+    }
+
+    #[test]
+    fn sort_strings_with_a_locale() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|s| {
+                s.set_criteria(vec![S("sort")]);
+                s.set_sortable_fields(hashset! { S("country"), S("word") });
+            })
+            .unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 0, "country": "Zimbabwe", "word": "côté" },
+                { "id": 1, "country": "Österreich", "word": "coté" },
+                { "id": 2, "country": "Ostfriesland", "word": "côte" },
+                { "id": 3, "country": "Oman", "word": "cote" },
+                { "id": 4, "country": "Ägypten" },
+                { "id": 5, "country": "Albanien" },
+                { "id": 6, "country": 12 },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let sort = |text: &str| {
+            let mut search = Search::new(&rtxn, &index);
+            search.sort_criteria(vec![text.parse().unwrap()]);
+            search.execute().unwrap().documents_ids
+        };
+
+        // the umlauts are ordered by their bytes, after the ascii letters
+        assert_eq!(sort("country:asc"), vec![6, 5, 3, 2, 0, 4, 1]);
+        // the numbers are still ordered before the strings
+        assert_eq!(sort("country:asc@de"), vec![6, 4, 5, 3, 1, 2, 0]);
+        assert_eq!(sort("country:desc@de"), vec![6, 0, 2, 1, 3, 5, 4]);
+
+        assert_eq!(sort("word:asc"), vec![3, 1, 2, 0, 4, 5, 6]);
+        assert_eq!(sort("word:asc@de"), vec![3, 1, 2, 0, 4, 5, 6]);
+        // the french accents are compared from the end of the words
+        assert_eq!(sort("word:asc@fr"), vec![3, 2, 1, 0, 4, 5, 6]);
+        assert_eq!(sort("word:desc@fr"), vec![0, 1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn sort_multi_valued_strings_with_a_locale() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|s| {
+                s.set_criteria(vec![S("sort")]);
+                s.set_sortable_fields(hashset! { S("word") });
+            })
+            .unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 0, "word": ["b", "Äpfel"] },
+                { "id": 1, "word": "apfel" },
+                { "id": 2, "word": "Birne" },
+                { "id": 3, "word": ["c", "zebra"] },
+            ]))
+            .unwrap();
+
+        let sort = |index: &TempIndex, text: &str| {
+            let rtxn = index.read_txn().unwrap();
+            let mut search = Search::new(&rtxn, index);
+            search.sort_criteria(vec![text.parse().unwrap()]);
+            search.limit(2000);
+            let documents_ids = search.execute().unwrap().documents_ids;
+            // only keep the documents with strings, the numbers come first
+            documents_ids[documents_ids.len() - 4..].to_vec()
+        };
+
+        // a document is sorted by the first of its values in the order of the locale
+        assert_eq!(sort(&index, "word:asc"), vec![1, 0, 2, 3]);
+        assert_eq!(sort(&index, "word:asc@de"), vec![1, 0, 2, 3]);
+        assert_eq!(sort(&index, "word:desc"), vec![0, 3, 2, 1]);
+        assert_eq!(sort(&index, "word:desc@de"), vec![3, 2, 0, 1]);
+
+        // the same order must be found when the strings are read from the facet database
+        let documents = (4..1104).map(|i| {
+            let document = serde_json::json!({ "id": i, "word": i });
+            document.as_object().unwrap().clone()
+        });
+        index
+            .add_documents(crate::documents::documents_batch_reader_from_objects(documents))
+            .unwrap();
+
+        assert_eq!(sort(&index, "word:asc@de"), vec![1, 0, 2, 3]);
+        assert_eq!(sort(&index, "word:desc@de"), vec![3, 2, 0, 1]);
+    }
+
+    #[test]
+    fn random_sort_is_stable_by_seed() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|s| {
+                s.set_criteria(vec![S("sort")]);
+                s.set_filterable_fields(hashset! { S("even") });
+            })
+            .unwrap();
+        let documents: Vec<_> =
+            (0..300).map(|id| serde_json::json!({ "id": id, "even": id % 2 == 0 })).collect();
+        index.add_documents(documents!(documents)).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let sort = |text: &str, filter: Option<&str>, offset, limit| {
+            let mut search = Search::new(&rtxn, &index);
+            search.sort_criteria(vec![text.parse().unwrap()]);
+            if let Some(filter) = filter {
+                search.filter(Filter::from_str(filter).unwrap().unwrap());
+            }
+            search.offset(offset).limit(limit);
+            search.execute().unwrap().documents_ids
+        };
+
+        let all = sort("_random(42):asc", None, 0, 300);
+        let mut sorted = all.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..300).collect::<Vec<_>>());
+        assert_ne!(all, sorted);
+
+        // the pages of the same seed are the slices of the same order
+        let pages: Vec<_> = (0..300)
+            .step_by(40)
+            .flat_map(|offset| sort("_random(42):asc", None, offset, 40))
+            .collect();
+        assert_eq!(pages, all);
+
+        assert_ne!(sort("_random(43):asc", None, 0, 300), all);
+        let mut desc = sort("_random(42):desc", None, 0, 300);
+        desc.reverse();
+        assert_eq!(desc, all);
+
+        let even = sort("_random(42):asc", Some("even = true"), 0, 300);
+        let expected: Vec<_> = all.iter().copied().filter(|id| id % 2 == 0).collect();
+        assert_eq!(even, expected);
+    }
+
+    #[test]
+    fn search_result_counts_the_filtered_universe() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|s| {
+                s.set_filterable_fields(hashset! { S("kind") });
+            })
+            .unwrap();
+        let documents: Vec<_> = (0..150)
+            .map(|i| {
+                let kind = if i < 100 { "a" } else { "b" };
+                let text = if i % 15 == 0 { "needle" } else { "hay" };
+                serde_json::json!({ "id": i, "kind": kind, "text": text, "group": i % 4 })
+            })
+            .collect();
+        index.add_documents(documents!(documents)).unwrap();
+
+        let search = |filter: Option<&str>, exhaustive_number_hits| {
+            let rtxn = index.read_txn().unwrap();
+            let mut search = Search::new(&rtxn, &index);
+            search.query("needle").limit(20).exhaustive_number_hits(exhaustive_number_hits);
+            if let Some(filter) = filter {
+                search.filter(Filter::from_str(filter).unwrap().unwrap());
+            }
+            search.execute().unwrap()
+        };
+
+        let result = search(Some("kind = a"), false);
+        assert_eq!(result.filtered_universe_len, 100);
+        assert_eq!(result.candidates.len(), 7);
+        assert_eq!(result.candidates_before_distinct_len, None);
+
+        let result = search(None, false);
+        assert_eq!(result.filtered_universe_len, 150);
+        assert_eq!(result.candidates.len(), 10);
+
+        // The needles of the a kind are spread over the four groups.
+        index.update_settings(|s| s.set_distinct_field(S("group"))).unwrap();
+        for exhaustive_number_hits in [false, true] {
+            let result = search(Some("kind = a"), exhaustive_number_hits);
+            assert_eq!(result.filtered_universe_len, 100);
+            assert_eq!(result.documents_ids.len(), 4);
+            assert_eq!(result.candidates_before_distinct_len, Some(7));
+        }
+    }
+
+    #[test]
+    fn execute_with_facets_counts_the_candidates() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|s| {
+                s.set_filterable_fields(hashset! { S("genre"), S("year") });
+                s.set_max_values_per_facet(2);
+            })
+            .unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 0, "title": "the hobbit", "genre": "fantasy", "year": 1937 },
+                { "id": 1, "title": "the silmarillion", "genre": "fantasy", "year": 1977 },
+                { "id": 2, "title": "the dune", "genre": "science fiction", "year": 1965 },
+                { "id": 3, "title": "the shining", "genre": "horror", "year": 1977 },
+                { "id": 4, "title": "the stand", "genre": "horror", "year": 1978 },
+                { "id": 5, "title": "neuromancer", "genre": "cyberpunk", "year": 1984 },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let filter = Filter::from_str("year > 1950").unwrap().unwrap();
+        let mut search = Search::new(&rtxn, &index);
+        search.query("the").filter(filter).limit(1);
+        let (result, distribution) = search.execute_with_facets(&["genre", "year"]).unwrap();
+        assert_eq!(result.documents_ids.len(), 1);
+        assert_eq!(result.candidates, (1..5).collect::<RoaringBitmap>());
+
+        let expected = FacetDistribution::new(&rtxn, &index)
+            .facets(["genre", "year"])
+            .max_values_per_facet(2)
+            .candidates(search.clone().exhaustive_number_hits(true).execute().unwrap().candidates)
+            .execute()
+            .unwrap();
+        assert_eq!(distribution, expected);
+        assert_eq!(
+            distribution,
+            btreemap! {
+                S("genre") => btreemap! { S("fantasy") => 1, S("horror") => 2 },
+                S("year") => btreemap! { S("1965") => 1, S("1977") => 2 },
+            }
+        );
+
+        let error = search.execute_with_facets(&["title"]).unwrap_err();
+        assert!(matches!(
+            error,
+            crate::Error::UserError(UserError::InvalidFacetsDistribution { .. })
+        ));
+        drop(rtxn);
+
+        index.update_settings(|s| s.set_displayed_fields(vec![S("title"), S("genre")])).unwrap();
+        let rtxn = index.read_txn().unwrap();
+        let mut search = Search::new(&rtxn, &index);
+        search.query("the");
+        let error = search.execute_with_facets(&["genre", "year"]).unwrap_err();
+        assert!(matches!(
+            error,
+            crate::Error::UserError(UserError::UndisplayedFacetsDistribution { facets_name })
+                if facets_name == btreeset! { S("year") }
+        ));
+    }
+
+    #[test]
+    fn docids_cache_never_returns_stale_docids() {
+        let mut index = TempIndex::new();
+        index.inner.set_docids_cache_max_size(Some(1024 * 1024));
+        index
+            .add_documents(documents!([
+                { "id": 0, "name": "kevin" },
+                { "id": 1, "name": "kevina" },
+            ]))
+            .unwrap();
+
+        let search = |rtxn: &heed::RoTxn| {
+            let mut search = Search::new(rtxn, &index);
+            search.query("kevin");
+            let mut documents_ids = search.execute().unwrap().documents_ids;
+            documents_ids.sort_unstable();
+            documents_ids
+        };
+
+        let old_rtxn = index.read_txn().unwrap();
+        assert_eq!(search(&old_rtxn), vec![0, 1]);
+        assert!(index.docids_cache.as_ref().unwrap().size() > 0);
+
+        index.add_documents(documents!([{ "id": 2, "name": "kevin" }])).unwrap();
+        assert_eq!(search(&index.read_txn().unwrap()), vec![0, 1, 2]);
+
+        // a transaction started before the update still reads its own docids
+        assert_eq!(search(&old_rtxn), vec![0, 1]);
+        assert_eq!(search(&index.read_txn().unwrap()), vec![0, 1, 2]);
+        drop(old_rtxn);
+
+        // the docids read during an aborted update are never seen afterward
+        let mut wtxn = index.write_txn().unwrap();
+        index
+            .add_documents_using_wtxn(&mut wtxn, documents!([{ "id": 3, "name": "kevin" }]))
+            .unwrap();
+        assert_eq!(search(&wtxn), vec![0, 1, 2, 3]);
+        wtxn.abort().unwrap();
+
+        index.add_documents(documents!([{ "id": 4, "name": "bob" }])).unwrap();
+        assert_eq!(search(&index.read_txn().unwrap()), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn searchable_context_follows_the_settings_updates() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 0, "title": "the quick fox" },
+                { "id": 1, "title": "a fast fox" },
+            ]))
+            .unwrap();
+
+        let search = |rtxn: &heed::RoTxn| {
+            let mut search = Search::new(rtxn, &index);
+            search.query("quick");
+            let mut documents_ids = search.execute().unwrap().documents_ids;
+            documents_ids.sort_unstable();
+            documents_ids
+        };
+
+        // the context is built once and reused by the searches on the same version
+        let old_rtxn = index.read_txn().unwrap();
+        assert_eq!(search(&old_rtxn), vec![0]);
+        let context = index.searchable_context(&old_rtxn).unwrap();
+        assert!(Arc::ptr_eq(&context, &index.searchable_context(&old_rtxn).unwrap()));
+        assert_eq!(index.searchable_context.update_id(), Some(context.update_id));
+
+        index
+            .update_settings(|settings| {
+                settings.set_synonyms(hashmap! { S("quick") => vec![S("fast")] })
+            })
+            .unwrap();
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(search(&rtxn), vec![0, 1]);
+        let new_context = index.searchable_context(&rtxn).unwrap();
+        assert!(new_context.update_id > context.update_id);
+        assert_eq!(new_context.words_synonyms(&["quick"]), Some(vec![vec![S("fast")]]));
+        assert_eq!(index.searchable_context.update_id(), Some(new_context.update_id));
+        drop(rtxn);
+
+        // a transaction started before the update still sees its own settings
+        // and doesn't replace the context of the last version
+        assert_eq!(search(&old_rtxn), vec![0]);
+        assert_eq!(index.searchable_context.update_id(), Some(new_context.update_id));
+        drop(old_rtxn);
+
+        // the settings of an aborted update are never seen afterward
+        let mut wtxn = index.write_txn().unwrap();
+        index.update_settings_using_wtxn(&mut wtxn, |settings| settings.reset_synonyms()).unwrap();
+        assert_eq!(search(&wtxn), vec![0]);
+        wtxn.abort().unwrap();
+        assert_eq!(search(&index.read_txn().unwrap()), vec![0, 1]);
+
+        index.update_settings(|settings| settings.set_searchable_fields(vec![S("id")])).unwrap();
+        assert_eq!(search(&index.read_txn().unwrap()), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn bitmap_arena_does_not_change_the_results() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 0, "title": "the quick brown fox jumps over the lazy dog" },
+                { "id": 1, "title": "the quikc brown fox" },
+                { "id": 2, "title": "a brown dog and a quick fox" },
+                { "id": 3, "title": "quick quick quick" },
+                { "id": 4, "title": "the lazy brown dog sleeps" },
+                { "id": 5, "title": "fox" },
+                { "id": 6, "title": "brown foxes are quick" },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        for query in ["quick brown fox", "the lazy dog", "quikc fox jumps"] {
+            for offset in 0..4 {
+                let search = |bitmap_arena| {
+                    let mut search = Search::new(&rtxn, &index);
+                    search.query(query).offset(offset).limit(2).with_scores(true);
+                    search.bitmap_arena(bitmap_arena);
+                    let SearchResult { documents_ids, score_details, .. } =
+                        search.execute().unwrap();
+                    (documents_ids, score_details)
+                };
+                assert_eq!(search(true), search(false), "{:?} at offset {}", query, offset);
+            }
+        }
+    }
+
+    #[test]
+    fn continuation_pages_are_the_offset_pages() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 0, "genre": "a", "rank": 3, "title": "the quick brown fox jumps over the lazy dog" },
+                { "id": 1, "genre": "b", "rank": 1, "title": "the quikc brown fox" },
+                { "id": 2, "genre": "a", "rank": 3, "title": "a brown dog and a quick fox" },
+                { "id": 3, "genre": "c", "rank": "z", "title": "quick quick quick" },
+                { "id": 4, "genre": "b", "rank": 2, "title": "the lazy brown dog sleeps" },
+                { "id": 5, "genre": "d", "title": "fox" },
+                { "id": 6, "genre": "c", "rank": 1, "title": "brown foxes are quick" },
+                { "id": 7, "genre": "e", "rank": "a", "title": "a dog" },
+            ]))
+            .unwrap();
+        index.update_settings(|s| s.set_sortable_fields(hashset! { S("rank") })).unwrap();
+
+        let assert_same_pages = |index: &TempIndex| {
+            let rtxn = index.read_txn().unwrap();
+            let sorts = [None, Some(AscDesc::Asc(Member::Field(S("rank"))))];
+            let queries = [None, Some("quick brown fox"), Some("the lazy dog")];
+            for (query, sort) in queries.iter().flat_map(|q| sorts.iter().map(move |s| (q, s))) {
+                for limit in 1..4 {
+                    let mut continuation: Option<Vec<u8>> = None;
+                    for page in 0.. {
+                        let mut search = Search::new(&rtxn, index);
+                        search.limit(limit).offset(page * limit);
+                        if let Some(query) = query {
+                            search.query(*query);
+                        }
+                        if let Some(sort) = sort {
+                            search.sort_criteria(vec![sort.clone()]);
+                        }
+                        let expected = search.execute().unwrap();
+
+                        let mut search = Search::new(&rtxn, index);
+                        search.limit(limit).with_continuation(true);
+                        if let Some(query) = query {
+                            search.query(*query);
+                        }
+                        if let Some(sort) = sort {
+                            search.sort_criteria(vec![sort.clone()]);
+                        }
+                        if let Some(continuation) = &continuation {
+                            search.continue_from(continuation);
+                        }
+                        let result = search.execute().unwrap();
+
+                        assert_eq!(
+                            result.documents_ids, expected.documents_ids,
+                            "{:?} sorted by {:?} page {} of {}",
+                            query, sort, page, limit
+                        );
+                        assert_eq!(result.candidates, expected.candidates);
+                        continuation = result.continuation;
+                        if continuation.is_none() {
+                            assert!(expected.documents_ids.len() < limit);
+                            break;
+                        }
+                    }
+                }
+            }
+        };
+
+        assert_same_pages(&index);
+
+        index
+            .update_settings(|settings| {
+                settings.set_distinct_field(S("genre"));
+            })
+            .unwrap();
+        assert_same_pages(&index);
+    }
+
+    #[test]
+    fn continuation_is_invalidated_by_updates() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 0, "title": "hello" },
+                { "id": 1, "title": "hello world" },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let mut search = Search::new(&rtxn, &index);
+        search.query("hello").limit(1).with_continuation(true);
+        let continuation = search.execute().unwrap().continuation.unwrap();
+
+        let mut search = Search::new(&rtxn, &index);
+        search.query("hello").limit(1).continue_from(b"not a continuation");
+        let error = search.execute().unwrap_err();
+        assert!(matches!(error, crate::Error::UserError(UserError::InvalidSearchContinuation)));
+
+        // the continuation only resumes the search that returned it
+        let mut search = Search::new(&rtxn, &index);
+        search.query("world").limit(1).continue_from(&continuation);
+        let error = search.execute().unwrap_err();
+        assert!(matches!(error, crate::Error::UserError(UserError::MismatchedSearchContinuation)));
+        drop(rtxn);
+
+        index.add_documents(documents!([{ "id": 2, "title": "hello" }])).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let mut search = Search::new(&rtxn, &index);
+        search.query("hello").limit(1).continue_from(&continuation);
+        let error = search.execute().unwrap_err();
+        assert!(matches!(error, crate::Error::UserError(UserError::ExpiredSearchContinuation)));
+    }
+
+    #[test]
+    fn phrase_slop_allows_positions_between_the_phrase_words() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 0, "title": "the quick brown fox" },
+                { "id": 1, "title": "quick fox" },
+                { "id": 2, "title": "a fox is quick" },
+                { "id": 3, "title": "quick", "text": "fox" },
+                { "id": 4, "title": "quick red dog quick lazy fox" },
+                { "id": 5, "title": "quick very very lazy fox" },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let search = |slop: u8| {
+            let mut search = Search::new(&rtxn, &index);
+            search.query("\"quick fox\"").phrase_slop(slop);
+            let result = search.execute().unwrap();
+            let external_ids = index.external_documents_ids(&rtxn).unwrap();
+            let mut ids: Vec<_> = (0..6)
+                .filter(|id: &u32| {
+                    let docid = external_ids.get(id.to_string()).unwrap();
+                    result.documents_ids.contains(&docid)
+                })
+                .collect();
+            ids.sort_unstable();
+            (ids, result.matching_words)
+        };
+
+        // a slop of 0 only matches the exact phrase
+        assert_eq!(search(0).0, vec![1]);
+        // the second quick of the document 4 is one position away from fox,
+        // but the words in another order or in different attributes never match
+        assert_eq!(search(1).0, vec![0, 1, 4]);
+        assert_eq!(search(3).0, vec![0, 1, 4, 5]);
+
+        // the words of the phrase are highlighted even when they are not consecutive
+        let (_, matching_words) = search(1);
+        let builder = MatcherBuilder::new(matching_words, TokenizerBuilder::default().build());
+        let format_options = FormatOptions { highlight: true, crop: None };
+        let mut matcher = builder.build("the quick brown fox");
+        assert_eq!(matcher.format(format_options), "the <em>quick</em> brown <em>fox</em>");
+    }
+
+    #[test]
+    fn diacritics_only_differences_cost_no_typo() {
+        fn search(index: &Index, query: &str) -> (Vec<(DocumentId, u8)>, MatchingWords) {
+            let rtxn = index.read_txn().unwrap();
+            let mut search = Search::new(&rtxn, index);
+            search.query(query).with_scores(true);
+            let result = search.execute().unwrap();
+            let mut typos: Vec<_> = result
+                .documents_ids
+                .iter()
+                .zip(&result.score_details)
+                .map(|(docid, details)| match details[0].rule {
+                    RankingRuleScore::Typo { typos } => (*docid, typos),
+                    ref rule => panic!("unexpected ranking rule {:?}", rule),
+                })
+                .collect();
+            typos.sort_unstable();
+            (typos, result.matching_words)
+        }
+
+        let index = TempIndex::new();
+        index
+            .update_settings(|s| {
+                s.set_criteria(vec![S("typo")]);
+                s.set_min_word_len_two_typos(8);
+            })
+            .unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 0, "title": "Éléphant" },
+                { "id": 1, "title": "elephant" },
+                { "id": 2, "title": "elephamt" },
+            ]))
+            .unwrap();
+
+        // The tokenizer removes the diacritics of the latin letters
+        // of the documents and of the query alike.
+        for query in ["elephant", "éléphant", "ÉLÉPHANT"] {
+            assert_eq!(search(&index, query).0, vec![(0, 0), (1, 0), (2, 1)], "{}", query);
+        }
+
+        // the accented words of the documents are highlighted
+        let (_, matching_words) = search(&index, "elephant");
+        let builder = MatcherBuilder::new(matching_words, TokenizerBuilder::default().build());
+        let format_options = FormatOptions { highlight: true, crop: None };
+        let mut matcher = builder.build("un Éléphant rose");
+        assert_eq!(matcher.format(format_options), "un <em>Éléphant</em> rose");
+
+        // the diacritics are kept when they are counted, each differing letter is a typo
+        index.update_settings(|s| s.set_count_diacritics(true)).unwrap();
+        assert_eq!(search(&index, "elephant").0, vec![(0, 2), (1, 0), (2, 1)]);
+        for query in ["éléphant", "ÉLÉPHANT"] {
+            assert_eq!(search(&index, query).0, vec![(0, 0), (1, 2)], "{}", query);
+        }
+
+        // the accented words of the documents are still highlighted
+        let (_, matching_words) = search(&index, "éléphant");
+        let mut builder = MatcherBuilder::new(matching_words, TokenizerBuilder::default().build());
+        builder.count_diacritics(true);
+        let mut matcher = builder.build("un Éléphant rose");
+        assert_eq!(matcher.format(format_options), "un <em>Éléphant</em> rose");
+    }
+
+    #[test]
+    fn exceeded_time_budget_returns_a_prefix_of_the_results() {
+        use std::str::FromStr;
+
+        let index = TempIndex::new();
+        index
+            .update_settings(|settings| {
+                settings.set_sortable_fields(hashset! { S("rank") });
+                settings.set_criteria(vec![
+                    S("words"),
+                    S("typo"),
+                    S("sort"),
+                    S("proximity"),
+                    S("attribute"),
+                    S("exactness"),
+                ]);
+            })
+            .unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 0, "rank": 3, "title": "the quick brown fox jumps over the lazy dog" },
+                { "id": 1, "rank": 1, "title": "the quikc brown fox" },
+                { "id": 2, "rank": 2, "title": "a brown dog and a quick fox" },
+                { "id": 3, "rank": 5, "title": "quick quick quick" },
+                { "id": 4, "rank": 4, "title": "the lazy brown dog sleeps" },
+                { "id": 5, "rank": 0, "title": "fox" },
+                { "id": 6, "rank": 6, "title": "brown foxes are quick" },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        for sort in [None, Some("rank:asc")] {
+            let search = |budget: Option<Duration>| {
+                let mut search = Search::new(&rtxn, &index);
+                search.query("quick brown fox").limit(10);
+                if let Some(sort) = sort {
+                    search.sort_criteria(vec![AscDesc::from_str(sort).unwrap()]);
+                }
+                if let Some(budget) = budget {
+                    search.time_budget(budget);
+                }
+                search.execute().unwrap()
+            };
+
+            let unbudgeted = search(None);
+            assert!(!unbudgeted.degraded);
+            assert!(!unbudgeted.documents_ids.is_empty());
+
+            let result = search(Some(Duration::from_secs(3600)));
+            assert!(!result.degraded);
+            assert_eq!(result.documents_ids, unbudgeted.documents_ids);
+
+            let result = search(Some(Duration::ZERO));
+            assert!(result.degraded);
+            assert!(unbudgeted.documents_ids.starts_with(&result.documents_ids));
+        }
+    }
+
+    #[test]
+    fn reached_limits_return_a_superset_of_the_results() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 0, "title": "the quick brown fox jumps over the lazy dog" },
+                { "id": 1, "title": "the quikc brown fox" },
+                { "id": 2, "title": "a brown dog and a quick fox" },
+                { "id": 3, "title": "quick quick quick" },
+                { "id": 4, "title": "the lazy brown dog sleeps" },
+                { "id": 5, "title": "fox" },
+                { "id": 6, "title": "brown foxes are quick" },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let search = |limits: SearchLimits| {
+            let mut search = Search::new(&rtxn, &index);
+            search.query("quick brown fox").limit(10).limits(limits);
+            search.execute().unwrap()
+        };
+
+        let precise = search(SearchLimits::default());
+        assert!(precise.degraded_by.is_empty());
+        assert!(!precise.documents_ids.is_empty());
+
+        let cases = [
+            (SearchLimits { max_word_derivations: Some(1), ..Default::default() }, &[][..]),
+            (SearchLimits { max_pair_combinations: Some(1), ..Default::default() }, &[][..]),
+            (
+                SearchLimits { max_bucket_candidates: Some(1), ..Default::default() },
+                &[RuleName::Attribute][..],
+            ),
+        ];
+        for (limits, also_degraded) in cases {
+            let result = search(limits);
+            assert!(result.degraded_by.contains(&RuleName::Proximity), "{:?}", limits);
+            for rule in also_degraded {
+                assert!(result.degraded_by.contains(rule), "{:?}", limits);
+            }
+            for id in &precise.documents_ids {
+                assert!(result.documents_ids.contains(id), "{:?} misses {}", limits, id);
+            }
+        }
+    }
+
+    #[test]
+    fn placeholder_sort_of_all_the_documents_skips_the_deleted_ones() {
+        use std::str::FromStr;
+
+        use crate::documents::documents_batch_reader_from_objects;
+        use crate::update::DeleteDocuments;
+
+        let index = TempIndex::new();
+        index
+            .update_settings(|settings| settings.set_sortable_fields(hashset! { S("rank") }))
+            .unwrap();
+
+        // more documents than the number of candidates sorted with the recursive method
+        let mut documents = vec![];
+        for i in 0..1500 {
+            let document = serde_json::json!({ "id": i, "rank": (i * 7) % 1500 });
+            documents.push(document.as_object().unwrap().clone());
+        }
+        index.add_documents(documents_batch_reader_from_objects(documents)).unwrap();
+
+        let search = |sort: &str| {
+            let rtxn = index.read_txn().unwrap();
+            let mut search = Search::new(&rtxn, &index);
+            search.sort_criteria(vec![AscDesc::from_str(sort).unwrap()]).limit(5);
+            search.execute().unwrap().documents_ids
+        };
+        let docids = |ranks: &[u32]| {
+            let rtxn = index.read_txn().unwrap();
+            let external_ids = index.external_documents_ids(&rtxn).unwrap();
+            ranks
+                .iter()
+                .map(|rank| (0..1500).find(|i| (i * 7) % 1500 == *rank).unwrap())
+                .map(|id: u32| external_ids.get(id.to_string()).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(search("rank:asc"), docids(&[0, 1, 2, 3, 4]));
+        assert_eq!(search("rank:desc"), docids(&[1499, 1498, 1497, 1496, 1495]));
+
+        let deleted = docids(&[1, 3, 1498]);
+        let mut wtxn = index.write_txn().unwrap();
+        let mut builder = DeleteDocuments::new(&mut wtxn, &index).unwrap();
+        builder.soft_deleted_threshold(1.0);
+        for docid in deleted {
+            builder.delete_document(docid);
+        }
+        builder.execute().unwrap();
+        wtxn.commit().unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.soft_deleted_documents_ids(&rtxn).unwrap().len(), 3);
+        drop(rtxn);
+
+        assert_eq!(search("rank:asc"), docids(&[0, 2, 4, 5, 6]));
+        assert_eq!(search("rank:desc"), docids(&[1499, 1497, 1496, 1495, 1494]));
+    }
+
+    #[test]
+    fn group_by_keeps_the_best_documents_of_each_group() {
+        use crate::facet::FacetValue;
+
+        let index = TempIndex::new();
+        index
+            .update_settings(|s| {
+                s.set_filterable_fields(hashset! { S("seller"), S("price") });
+                s.set_sortable_fields(hashset! { S("price") });
+            })
+            .unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 0, "seller": "a", "price": 30 },
+                { "id": 1, "seller": "b", "price": 10 },
+                { "id": 2, "seller": "a", "price": 20 },
+                { "id": 3, "seller": "c", "price": 50 },
+                { "id": 4, "seller": "b", "price": 15 },
+                { "id": 5, "price": 5 },
+                { "id": 6, "seller": "a", "price": 40 },
+                { "id": 7, "seller": "b", "price": 12 },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let groups = |search: &Search| {
+            let SearchResult { documents_ids, groups, .. } = search.execute().unwrap();
+            let flattened: Vec<_> =
+                groups.iter().flat_map(|group| group.documents_ids.clone()).collect();
+            assert_eq!(documents_ids, flattened);
+            groups.into_iter().map(|group| (group.value, group.documents_ids)).collect::<Vec<_>>()
+        };
+        let seller = |name: &str| Some(FacetValue::from(name));
+
+        // the groups are ordered by their cheapest document, the one without seller is last
+        let mut search = Search::new(&rtxn, &index);
+        search.sort_criteria(vec![AscDesc::Asc(Member::Field(S("price")))]);
+        search.group_by("seller", 2);
+        assert_eq!(
+            groups(&search),
+            vec![
+                (seller("b"), vec![1, 7]),
+                (seller("a"), vec![2, 0]),
+                (seller("c"), vec![3]),
+                (None, vec![5]),
+            ]
+        );
+
+        search.sort_criteria(vec![AscDesc::Desc(Member::Field(S("price")))]);
+        assert_eq!(
+            groups(&search),
+            vec![
+                (seller("c"), vec![3]),
+                (seller("a"), vec![6, 0]),
+                (seller("b"), vec![4, 7]),
+                (None, vec![5]),
+            ]
+        );
+
+        // the filter applies before the grouping, the limit and the offset to the groups
+        search.sort_criteria(vec![AscDesc::Asc(Member::Field(S("price")))]);
+        search.filter(Filter::from_str("price > 12").unwrap().unwrap());
+        search.limit(2);
+        assert_eq!(groups(&search), vec![(seller("b"), vec![4]), (seller("a"), vec![2, 0])]);
+
+        search.offset(1);
+        assert_eq!(groups(&search), vec![(seller("a"), vec![2, 0])]);
+
+        // the documents can only be grouped by a filterable field
+        search.group_by("title", 2);
+        let error = search.execute().unwrap_err();
+        assert!(matches!(
+            error,
+            crate::Error::UserError(UserError::InvalidGroupByAttribute { ref field, .. })
+                if field == "title"
+        ));
+    }
+
+    #[test]
+    fn group_by_puts_the_documents_without_value_after_the_other_groups() {
+        use crate::facet::FacetValue;
+
+        let index = TempIndex::new();
+        index
+            .update_settings(|s| {
+                s.set_filterable_fields(hashset! { S("seller") });
+                s.set_sortable_fields(hashset! { S("price") });
+            })
+            .unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 0, "seller": "a", "price": 30 },
+                { "id": 1, "price": 5 },
+                { "id": 2, "seller": "b", "price": 10 },
+                { "id": 3, "price": 8 },
+                { "id": 4, "seller": "a", "price": 20 },
+                { "id": 5, "seller": "c", "price": 50 },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let groups = |search: &Search| {
+            let SearchResult { groups, .. } = search.execute().unwrap();
+            groups.into_iter().map(|group| (group.value, group.documents_ids)).collect::<Vec<_>>()
+        };
+        let seller = |name: &str| Some(FacetValue::from(name));
+
+        // the cheapest documents have no seller, but their group doesn't take a slot of the page
+        let mut search = Search::new(&rtxn, &index);
+        search.sort_criteria(vec![AscDesc::Asc(Member::Field(S("price")))]);
+        search.group_by("seller", 2);
+        search.limit(2);
+        assert_eq!(groups(&search), vec![(seller("b"), vec![2]), (seller("a"), vec![4, 0])]);
+
+        // it is appended after the groups with a value, then paginated with them
+        search.offset(2);
+        assert_eq!(groups(&search), vec![(seller("c"), vec![5]), (None, vec![1, 3])]);
+
+        search.offset(3);
+        assert_eq!(groups(&search), vec![(None, vec![1, 3])]);
+
+        search.offset(4);
+        assert_eq!(groups(&search), vec![]);
+    }
 }
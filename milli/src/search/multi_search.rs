@@ -0,0 +1,295 @@
+use std::collections::hash_map::{Entry, HashMap};
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use roaring::RoaringBitmap;
+
+use crate::search::{Filter, Search, SearchResult, TermsMatchingStrategy};
+use crate::{AscDesc, Condition, FilterCondition, Index, Result};
+
+/// One of the queries of a [`MultiSearch`].
+#[derive(Debug, Clone)]
+pub struct SearchQuery<'a> {
+    pub query: Option<String>,
+    pub filter: Option<Filter<'a>>,
+    pub sort_criteria: Option<Vec<AscDesc>>,
+    pub offset: usize,
+    pub limit: usize,
+    /// The optional words of the query, the order in which its words are removed when
+    /// there are not enough results. `TermsMatchingStrategy::All` makes all of them mandatory.
+    pub terms_matching_strategy: TermsMatchingStrategy,
+}
+
+impl Default for SearchQuery<'_> {
+    fn default() -> Self {
+        SearchQuery {
+            query: None,
+            filter: None,
+            sort_criteria: None,
+            offset: 0,
+            limit: 20,
+            terms_matching_strategy: TermsMatchingStrategy::default(),
+        }
+    }
+}
+
+/// Executes several independent queries on the same index.
+///
+/// The filters are evaluated once for all the queries that use them, the filters that only
+/// differ by their whitespaces or quotes are considered the same. The queries are executed
+/// in the same read transaction, unless they are executed in parallel, each thread then
+/// uses its own read transaction.
+pub struct MultiSearch<'a> {
+    queries: &'a [SearchQuery<'a>],
+    parallel: bool,
+    index: &'a Index,
+}
+
+impl<'a> MultiSearch<'a> {
+    pub fn new(index: &'a Index, queries: &'a [SearchQuery<'a>]) -> MultiSearch<'a> {
+        MultiSearch { queries, parallel: false, index }
+    }
+
+    /// Executes the queries in parallel on the current rayon thread pool.
+    pub fn parallel(&mut self, value: bool) -> &mut MultiSearch<'a> {
+        self.parallel = value;
+        self
+    }
+
+    /// Returns the results of the queries, in the same order as the queries.
+    pub fn execute(&self) -> Result<Vec<SearchResult>> {
+        let rtxn = self.index.read_txn()?;
+
+        let mut filters = HashMap::new();
+        for filter in self.queries.iter().filter_map(|query| query.filter.as_ref()) {
+            if let Entry::Vacant(entry) = filters.entry(filter_key(filter)) {
+                entry.insert(filter.evaluate(&rtxn, self.index)?);
+            }
+        }
+
+        if !self.parallel {
+            return self.queries.iter().map(|query| self.search(&rtxn, query, &filters)).collect();
+        }
+
+        // The read transactions of the threads can see a more recent version of the index
+        // than the one the filters were evaluated on, the filters are then evaluated again.
+        let update_id = self.index.update_id(&rtxn)?;
+        drop(rtxn);
+
+        let no_filters = HashMap::new();
+        self.queries
+            .par_iter()
+            .map_init(
+                || None,
+                |rtxn, query| {
+                    if rtxn.is_none() {
+                        *rtxn = Some(self.index.read_txn()?);
+                    }
+                    let rtxn = rtxn.as_ref().unwrap();
+                    if self.index.update_id(rtxn)? == update_id {
+                        self.search(rtxn, query, &filters)
+                    } else {
+                        self.search(rtxn, query, &no_filters)
+                    }
+                },
+            )
+            .collect()
+    }
+
+    fn search<'t>(
+        &'t self,
+        rtxn: &'t heed::RoTxn,
+        query: &'t SearchQuery<'a>,
+        filters: &HashMap<Vec<FilterKeyPart<'a>>, RoaringBitmap>,
+    ) -> Result<SearchResult> {
+        let mut search = Search::new(rtxn, self.index);
+        if let Some(ref text) = query.query {
+            search.query(text.clone());
+        }
+        if let Some(ref filter) = query.filter {
+            match filters.get(&filter_key(filter)) {
+                Some(candidates) => search.filtered_candidates(candidates.clone()),
+                None => search.filter(filter.clone()),
+            };
+        }
+        if let Some(ref sort_criteria) = query.sort_criteria {
+            search.sort_criteria(sort_criteria.clone());
+        }
+        search
+            .offset(query.offset)
+            .limit(query.limit)
+            .terms_matching_strategy(query.terms_matching_strategy);
+        search.execute()
+    }
+}
+
+/// A part of the key of an evaluated filter, the operators are followed by their operands.
+///
+/// The values of the tokens are kept apart from the operators, unlike in the displayed filter
+/// where a value can contain the characters of an operator, two different filters can't
+/// share the same key.
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum FilterKeyPart<'a> {
+    /// An operator and its number of operands.
+    Operator(&'static str, usize),
+    Value(&'a str),
+}
+
+fn filter_key<'a>(filter: &'a Filter) -> Vec<FilterKeyPart<'a>> {
+    let mut key = Vec::new();
+    push_condition_key(filter.condition(), &mut key);
+    key
+}
+
+fn push_condition_key<'a>(condition: &'a FilterCondition, key: &mut Vec<FilterKeyPart<'a>>) {
+    use FilterKeyPart::{Operator, Value};
+
+    match condition {
+        FilterCondition::Not(filter) => {
+            key.push(Operator("NOT", 1));
+            push_condition_key(filter, key);
+        }
+        FilterCondition::Condition { fid, op } => {
+            let (operator, values) = match op {
+                Condition::GreaterThan(value) => (">", vec![value]),
+                Condition::GreaterThanOrEqual(value) => (">=", vec![value]),
+                Condition::Equal(value) => ("=", vec![value]),
+                Condition::NotEqual(value) => ("!=", vec![value]),
+                Condition::Exists => ("EXISTS", vec![]),
+                Condition::Null => ("IS NULL", vec![]),
+                Condition::Empty => ("IS EMPTY", vec![]),
+                Condition::LowerThan(value) => ("<", vec![value]),
+                Condition::LowerThanOrEqual(value) => ("<=", vec![value]),
+                Condition::Between { from, to } => ("TO", vec![from, to]),
+            };
+            key.push(Operator(operator, values.len() + 1));
+            key.push(Value(fid.value()));
+            key.extend(values.into_iter().map(|value| Value(value.value())));
+        }
+        FilterCondition::In { fid, els } => {
+            key.push(Operator("IN", els.len() + 1));
+            key.push(Value(fid.value()));
+            key.extend(els.iter().map(|value| Value(value.value())));
+        }
+        FilterCondition::Or(filters) | FilterCondition::And(filters) => {
+            let operator = if matches!(condition, FilterCondition::Or(_)) { "OR" } else { "AND" };
+            key.push(Operator(operator, filters.len()));
+            filters.iter().for_each(|filter| push_condition_key(filter, key));
+        }
+        FilterCondition::GeoLowerThan { point, radius } => {
+            key.push(Operator("_geoRadius", 3));
+            key.extend(point.iter().chain(Some(radius)).map(|value| Value(value.value())));
+        }
+        FilterCondition::GeoBoundingBox { top_left_point, bottom_right_point } => {
+            key.push(Operator("_geoBoundingBox", 4));
+            let points = top_left_point.iter().chain(bottom_right_point);
+            key.extend(points.map(|value| Value(value.value())));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use big_s::S;
+    use maplit::hashset;
+
+    use super::*;
+    use crate::index::tests::TempIndex;
+    use crate::Member;
+
+    #[test]
+    fn equivalent_filters_share_the_same_key() {
+        let filter = Filter::from_str("color = red AND price > 10").unwrap().unwrap();
+        let same = Filter::from_str("(color='red')   AND price>10").unwrap().unwrap();
+        let other = Filter::from_str("color = red OR price > 10").unwrap().unwrap();
+        assert_eq!(filter_key(&filter), filter_key(&same));
+        assert_ne!(filter_key(&filter), filter_key(&other));
+
+        // The values are not mistaken for operators, even when they are displayed the same way.
+        let filter = Filter::from_str(r#"a IN ["1}, {2"]"#).unwrap().unwrap();
+        let other = Filter::from_str("a IN [1, 2]").unwrap().unwrap();
+        assert_eq!(filter.to_string(), other.to_string());
+        assert_ne!(filter_key(&filter), filter_key(&other));
+    }
+
+    #[test]
+    fn same_results_as_the_individual_searches() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|s| {
+                s.set_filterable_fields(hashset! { S("color"), S("price") });
+                s.set_sortable_fields(hashset! { S("price") });
+            })
+            .unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 0, "title": "red apple", "color": "red", "price": 3 },
+                { "id": 1, "title": "green apple", "color": "green", "price": 2 },
+                { "id": 2, "title": "red cherry", "color": "red", "price": 10 },
+                { "id": 3, "title": "blue berry", "color": "blue", "price": 7 },
+                { "id": 4, "title": "red pepper", "color": "red", "price": 1 },
+                { "id": 5, "title": "apple pie", "color": "brown", "price": 12 },
+            ]))
+            .unwrap();
+
+        let red = "color = red";
+        let queries = vec![
+            SearchQuery { query: Some(S("apple")), ..Default::default() },
+            SearchQuery {
+                query: Some(S("red")),
+                filter: Filter::from_str(red).unwrap(),
+                ..Default::default()
+            },
+            SearchQuery {
+                filter: Filter::from_str("color=red").unwrap(),
+                sort_criteria: Some(vec![AscDesc::Asc(Member::Field(S("price")))]),
+                ..Default::default()
+            },
+            SearchQuery {
+                query: Some(S("apple red")),
+                filter: Filter::from_str("price < 5").unwrap(),
+                terms_matching_strategy: TermsMatchingStrategy::All,
+                ..Default::default()
+            },
+            SearchQuery {
+                filter: Filter::from_str(red).unwrap(),
+                offset: 1,
+                limit: 1,
+                ..Default::default()
+            },
+        ];
+
+        let rtxn = index.read_txn().unwrap();
+        let expected: Vec<_> = queries
+            .iter()
+            .map(|query| {
+                let mut search = index.search(&rtxn);
+                if let Some(text) = &query.query {
+                    search.query(text);
+                }
+                if let Some(filter) = &query.filter {
+                    search.filter(filter.clone());
+                }
+                if let Some(sort_criteria) = &query.sort_criteria {
+                    search.sort_criteria(sort_criteria.clone());
+                }
+                search.offset(query.offset).limit(query.limit);
+                search.terms_matching_strategy(query.terms_matching_strategy);
+                let SearchResult { documents_ids, candidates, .. } = search.execute().unwrap();
+                (documents_ids, candidates)
+            })
+            .collect();
+        drop(rtxn);
+
+        assert_eq!(expected[2].0, vec![4, 0, 2]);
+        assert_eq!(expected[4].0, vec![2]);
+
+        for parallel in [false, true] {
+            let results = index.multi_search(&queries).parallel(parallel).execute().unwrap();
+            let results: Vec<_> = results
+                .into_iter()
+                .map(|SearchResult { documents_ids, candidates, .. }| (documents_ids, candidates))
+                .collect();
+            assert_eq!(results, expected, "parallel: {}", parallel);
+        }
+    }
+}
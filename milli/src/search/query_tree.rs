@@ -1,16 +1,20 @@
 use std::borrow::Cow;
 use std::cmp::max;
+use std::sync::Arc;
 use std::{fmt, mem};
 
-use charabia::classifier::ClassifiedTokenIter;
-use charabia::{SeparatorKind, TokenKind};
+use charabia::{SeparatorKind, Token, TokenKind};
 use fst::Set;
+use heed::types::ByteSlice;
 use roaring::RoaringBitmap;
 use slice_group_by::GroupBy;
 
+use crate::proximity::{word_pair_proximity_docids_from_positions, ProximityPrecision};
 use crate::search::matches::matching_words::{MatchingWord, PrimitiveWordId};
 use crate::search::TermsMatchingStrategy;
-use crate::{CboRoaringBitmapLenCodec, Index, MatchingWords, Result};
+use crate::{
+    CboRoaringBitmapCodec, Index, MatchingWords, Result, RoaringBitmapLenCodec, SearchableContext,
+};
 
 type IsOptionalWord = bool;
 type IsPrefix = bool;
@@ -156,6 +160,10 @@ trait Context {
     /// Returns the minimum word len for 1 and 2 typos.
     fn min_word_len_for_typo(&self) -> heed::Result<(u8, u8)>;
     fn exact_words(&self) -> Option<&fst::Set<Cow<[u8]>>>;
+    /// Returns whether a query word can be split in two words.
+    fn word_splitting_enabled(&self) -> bool;
+    /// Returns whether consecutive query words can be concatenated in one word.
+    fn word_concatenation_enabled(&self) -> bool;
     fn word_pair_frequency(
         &self,
         left_word: &str,
@@ -170,8 +178,14 @@ pub struct QueryTreeBuilder<'a> {
     index: &'a Index,
     terms_matching_strategy: TermsMatchingStrategy,
     authorize_typos: bool,
+    word_splitting: bool,
+    word_concatenation: bool,
+    negative_terms: bool,
+    phrase_slop: u8,
     words_limit: Option<usize>,
     exact_words: Option<fst::Set<Cow<'a, [u8]>>>,
+    soft_deleted_documents: RoaringBitmap,
+    context: Arc<SearchableContext>,
 }
 
 impl<'a> Context for QueryTreeBuilder<'a> {
@@ -180,34 +194,74 @@ impl<'a> Context for QueryTreeBuilder<'a> {
     }
 
     fn synonyms<S: AsRef<str>>(&self, words: &[S]) -> heed::Result<Option<Vec<Vec<String>>>> {
-        self.index.words_synonyms(self.rtxn, words)
+        Ok(self.context.words_synonyms(words))
     }
 
     fn word_documents_count(&self, word: &str) -> heed::Result<Option<u64>> {
-        self.index.word_documents_count(self.rtxn, word)
+        // The documents are counted from the serialized bitmap, without decoding it.
+        let db = self.index.word_docids.remap_data_type::<ByteSlice>();
+        match db.get(self.rtxn, word)? {
+            Some(bytes) => {
+                let len = RoaringBitmapLenCodec::deserialize_from_slice(bytes)
+                    .map_err(|_| heed::Error::Decoding)?;
+                let deleted =
+                    RoaringBitmapLenCodec::intersection_len(bytes, &self.soft_deleted_documents)
+                        .map_err(|_| heed::Error::Decoding)?;
+                Ok(Some(len - deleted))
+            }
+            None => Ok(None),
+        }
     }
 
     fn min_word_len_for_typo(&self) -> heed::Result<(u8, u8)> {
-        let one = self.index.min_word_len_one_typo(&self.rtxn)?;
-        let two = self.index.min_word_len_two_typos(&self.rtxn)?;
-        Ok((one, two))
+        Ok((self.context.min_word_len_one_typo, self.context.min_word_len_two_typos))
     }
 
     fn exact_words(&self) -> Option<&fst::Set<Cow<[u8]>>> {
         self.exact_words.as_ref()
     }
 
+    fn word_splitting_enabled(&self) -> bool {
+        self.word_splitting
+    }
+
+    fn word_concatenation_enabled(&self) -> bool {
+        self.word_concatenation
+    }
+
     fn word_pair_frequency(
         &self,
         left_word: &str,
         right_word: &str,
         proximity: u8,
     ) -> heed::Result<Option<u64>> {
+        if self.context.proximity_precision == ProximityPrecision::ByAttribute {
+            // The word pair proximity database is not built.
+            let mut docids = word_pair_proximity_docids_from_positions(
+                self.index,
+                self.rtxn,
+                self.context.position_bucketing,
+                left_word,
+                right_word,
+                proximity,
+            )?;
+            docids -= &self.soft_deleted_documents;
+            return Ok(Some(docids.len()).filter(|&len| len != 0));
+        }
+
         let key = (proximity, left_word, right_word);
-        self.index
-            .word_pair_proximity_docids
-            .remap_data_type::<CboRoaringBitmapLenCodec>()
-            .get(&self.rtxn, &key)
+        let db = self.index.word_pair_proximity_docids.remap_data_type::<ByteSlice>();
+        match db.get(&self.rtxn, &key)? {
+            Some(bytes) => {
+                let len = CboRoaringBitmapCodec::deserialize_len_from(bytes)
+                    .map_err(|_| heed::Error::Decoding)?;
+                let deleted =
+                    CboRoaringBitmapCodec::intersection_len(bytes, &self.soft_deleted_documents)
+                        .map_err(|_| heed::Error::Decoding)?;
+                Ok(Some(len - deleted))
+            }
+            None => Ok(None),
+        }
     }
 }
 
@@ -220,8 +274,14 @@ impl<'a> QueryTreeBuilder<'a> {
             index,
             terms_matching_strategy: TermsMatchingStrategy::default(),
             authorize_typos: true,
+            word_splitting: true,
+            word_concatenation: true,
+            negative_terms: false,
+            phrase_slop: 0,
             words_limit: None,
             exact_words: index.exact_words(rtxn)?,
+            soft_deleted_documents: index.soft_deleted_documents_ids(rtxn)?,
+            context: index.searchable_context(rtxn)?,
         })
     }
 
@@ -246,6 +306,39 @@ impl<'a> QueryTreeBuilder<'a> {
         self
     }
 
+    /// if `word_splitting` is set to `false` the query words are not split
+    /// in two words found next to each other in the documents.
+    /// default value if not called: `true`
+    pub fn enable_word_splitting(&mut self, word_splitting: bool) -> &mut Self {
+        self.word_splitting = word_splitting;
+        self
+    }
+
+    /// if `word_concatenation` is set to `false` the consecutive query words
+    /// are not concatenated in one word.
+    /// default value if not called: `true`
+    pub fn enable_word_concatenation(&mut self, word_concatenation: bool) -> &mut Self {
+        self.word_concatenation = word_concatenation;
+        self
+    }
+
+    /// if `negative_terms` is set to `true` the query words and phrases directly
+    /// prefixed by a minus, e.g. `-leather` or `-"faux leather"`, are returned
+    /// apart from the query tree to exclude the documents that contain them.
+    /// default value if not called: `false`
+    pub fn negative_terms(&mut self, negative_terms: bool) -> &mut Self {
+        self.negative_terms = negative_terms;
+        self
+    }
+
+    /// if `phrase_slop` is greater than `0` the words of the phrases can be matched
+    /// separately in the documents, as they are not consecutive anymore.
+    /// default value if not called: `0`
+    pub fn phrase_slop(&mut self, phrase_slop: u8) -> &mut Self {
+        self.phrase_slop = phrase_slop;
+        self
+    }
+
     /// Limit words and phrases that will be taken for query building.
     /// Any beyond `words_limit` will be ignored.
     pub fn words_limit(&mut self, words_limit: usize) -> &mut Self {
@@ -260,12 +353,17 @@ impl<'a> QueryTreeBuilder<'a> {
     /// - if `authorize_typos` is set to `false` the query tree will be generated
     ///   forcing all query words to match documents without any typo
     ///   (the criterion `typo` will be ignored)
-    pub fn build<A: AsRef<[u8]>>(
+    /// - if `word_splitting` or `word_concatenation` is set to `false` the query tree
+    ///   will be generated without the splitted or concatenated words
+    /// - if `negative_terms` is set to `true` the negative words and phrases are
+    ///   returned apart, the query tree is `None` if the query only contains them
+    pub fn build<'t>(
         &self,
-        query: ClassifiedTokenIter<A>,
-    ) -> Result<Option<(Operation, PrimitiveQuery, MatchingWords)>> {
+        query: impl IntoIterator<Item = Token<'t>>,
+    ) -> Result<(Option<(Operation, PrimitiveQuery, MatchingWords)>, NegativeQuery)> {
         let stop_words = self.index.stop_words(self.rtxn)?;
-        let primitive_query = create_primitive_query(query, stop_words, self.words_limit);
+        let (primitive_query, negative_query) =
+            create_primitive_query(query, stop_words, self.words_limit, self.negative_terms);
         if !primitive_query.is_empty() {
             let qt = create_query_tree(
                 self,
@@ -273,20 +371,29 @@ impl<'a> QueryTreeBuilder<'a> {
                 self.authorize_typos,
                 &primitive_query,
             )?;
-            let matching_words =
-                create_matching_words(self, self.authorize_typos, &primitive_query)?;
-            Ok(Some((qt, primitive_query, matching_words)))
+            let matching_words = create_matching_words(
+                self,
+                self.authorize_typos,
+                self.phrase_slop,
+                &primitive_query,
+            )?;
+            Ok((Some((qt, primitive_query, matching_words)), negative_query))
         } else {
-            Ok(None)
+            Ok((None, negative_query))
         }
     }
 }
 
-/// Split the word depending on the frequency of pairs near together in the database documents.
+/// Split the word depending on the frequency of pairs near together in the database documents,
+/// the word is never split if the word splitting is disabled.
 fn split_best_frequency<'a>(
     ctx: &impl Context,
     word: &'a str,
 ) -> heed::Result<Option<(&'a str, &'a str)>> {
+    if !ctx.word_splitting_enabled() {
+        return Ok(None);
+    }
+
     let chars = word.char_indices().skip(1);
     let mut best = None;
 
@@ -313,7 +420,7 @@ pub struct TypoConfig<'a> {
 
 /// Return the `QueryKind` of a word depending on `authorize_typos`
 /// and the provided word length.
-fn typos<'a>(word: String, authorize_typos: bool, config: TypoConfig<'a>) -> QueryKind {
+pub(crate) fn typos<'a>(word: String, authorize_typos: bool, config: TypoConfig<'a>) -> QueryKind {
     if authorize_typos && !config.exact_words.map_or(false, |s| s.contains(&word)) {
         let count = word.chars().count().min(u8::MAX as usize) as u8;
         if count < config.word_len_one_typo {
@@ -425,21 +532,27 @@ fn create_query_tree(
                                 })
                                 .collect();
                             let mut operations = synonyms(ctx, &words)?.unwrap_or_default();
-                            let concat = words.concat();
-                            let (word_len_one_typo, word_len_two_typo) =
-                                ctx.min_word_len_for_typo()?;
-                            let exact_words = ctx.exact_words();
-                            let config = TypoConfig {
-                                max_typos: 1,
-                                word_len_one_typo,
-                                word_len_two_typo,
-                                exact_words,
-                            };
-                            let query = Query {
-                                prefix: is_prefix,
-                                kind: typos(concat, authorize_typos, config),
-                            };
-                            operations.push(Operation::Query(query));
+                            if ctx.word_concatenation_enabled() {
+                                let concat = words.concat();
+                                let (word_len_one_typo, word_len_two_typo) =
+                                    ctx.min_word_len_for_typo()?;
+                                let exact_words = ctx.exact_words();
+                                let config = TypoConfig {
+                                    max_typos: 1,
+                                    word_len_one_typo,
+                                    word_len_two_typo,
+                                    exact_words,
+                                };
+                                let query = Query {
+                                    prefix: is_prefix,
+                                    kind: typos(concat, authorize_typos, config),
+                                };
+                                operations.push(Operation::Query(query));
+                            }
+                            // without synonyms nor concatenation this ngram has no branch
+                            if operations.is_empty() {
+                                continue;
+                            }
                             and_op_children.push(Operation::or(false, operations));
                         }
                     }
@@ -538,12 +651,14 @@ fn create_query_tree(
 fn create_matching_words(
     ctx: &impl Context,
     authorize_typos: bool,
+    phrase_slop: u8,
     query: &[PrimitiveQueryPart],
 ) -> Result<MatchingWords> {
     /// Matches on the `PrimitiveQueryPart` and create matchings words from it.
     fn resolve_primitive_part(
         ctx: &impl Context,
         authorize_typos: bool,
+        phrase_slop: u8,
         part: PrimitiveQueryPart,
         matching_words: &mut Vec<(Vec<MatchingWord>, Vec<PrimitiveWordId>)>,
         id: PrimitiveWordId,
@@ -579,6 +694,14 @@ fn create_matching_words(
                 };
                 matching_words.push((vec![matching_word], vec![id]));
             }
+            // with a slop the words of the phrase are not consecutive anymore,
+            // each of them is matched on its own
+            PrimitiveQueryPart::Phrase(words) if phrase_slop > 0 => {
+                for (i, word) in words.into_iter().enumerate() {
+                    let matching_word = MatchingWord::new(word, 0, false);
+                    matching_words.push((vec![matching_word], vec![id + i as PrimitiveWordId]));
+                }
+            }
             // create a CONSECUTIVE matchings words wrapping all word in the phrase
             PrimitiveQueryPart::Phrase(words) => {
                 let ids: Vec<_> =
@@ -596,6 +719,7 @@ fn create_matching_words(
     fn ngrams(
         ctx: &impl Context,
         authorize_typos: bool,
+        phrase_slop: u8,
         query: &[PrimitiveQueryPart],
         matching_words: &mut Vec<(Vec<MatchingWord>, Vec<PrimitiveWordId>)>,
         mut id: PrimitiveWordId,
@@ -613,6 +737,7 @@ fn create_matching_words(
                             resolve_primitive_part(
                                 ctx,
                                 authorize_typos,
+                                phrase_slop,
                                 part.clone(),
                                 matching_words,
                                 id,
@@ -644,30 +769,32 @@ fn create_matching_words(
                                     matching_words.push((synonym, ids.clone()));
                                 }
                             }
-                            let word = words.concat();
-                            let (word_len_one_typo, word_len_two_typo) =
-                                ctx.min_word_len_for_typo()?;
-                            let exact_words = ctx.exact_words();
-                            let config = TypoConfig {
-                                max_typos: 1,
-                                word_len_one_typo,
-                                word_len_two_typo,
-                                exact_words,
-                            };
-                            let matching_word = match typos(word, authorize_typos, config) {
-                                QueryKind::Exact { word, .. } => {
-                                    MatchingWord::new(word, 0, is_prefix)
-                                }
-                                QueryKind::Tolerant { typo, word } => {
-                                    MatchingWord::new(word, typo, is_prefix)
-                                }
-                            };
-                            matching_words.push((vec![matching_word], ids));
+                            if ctx.word_concatenation_enabled() {
+                                let word = words.concat();
+                                let (word_len_one_typo, word_len_two_typo) =
+                                    ctx.min_word_len_for_typo()?;
+                                let exact_words = ctx.exact_words();
+                                let config = TypoConfig {
+                                    max_typos: 1,
+                                    word_len_one_typo,
+                                    word_len_two_typo,
+                                    exact_words,
+                                };
+                                let matching_word = match typos(word, authorize_typos, config) {
+                                    QueryKind::Exact { word, .. } => {
+                                        MatchingWord::new(word, 0, is_prefix)
+                                    }
+                                    QueryKind::Tolerant { typo, word } => {
+                                        MatchingWord::new(word, typo, is_prefix)
+                                    }
+                                };
+                                matching_words.push((vec![matching_word], ids));
+                            }
                         }
                     }
 
                     if !is_last {
-                        ngrams(ctx, authorize_typos, tail, matching_words, id + 1)?;
+                        ngrams(ctx, authorize_typos, phrase_slop, tail, matching_words, id + 1)?;
                     }
                 }
             }
@@ -678,12 +805,16 @@ fn create_matching_words(
     }
 
     let mut matching_words = Vec::new();
-    ngrams(ctx, authorize_typos, query, &mut matching_words, 0)?;
+    ngrams(ctx, authorize_typos, phrase_slop, query, &mut matching_words, 0)?;
     Ok(MatchingWords::new(matching_words))
 }
 
 pub type PrimitiveQuery = Vec<PrimitiveQueryPart>;
 
+/// The words and phrases prefixed by a minus in the query, the documents that
+/// contain them are excluded. The words are never prefixes nor derived with typos.
+pub type NegativeQuery = Vec<PrimitiveQueryPart>;
+
 #[derive(Debug, Clone)]
 pub enum PrimitiveQueryPart {
     Phrase(Vec<String>),
@@ -709,34 +840,57 @@ impl PrimitiveQueryPart {
 
 /// Create primitive query from tokenized query string,
 /// the primitive query is an intermediate state to build the query tree.
-fn create_primitive_query<A>(
-    query: ClassifiedTokenIter<A>,
+///
+/// When `negative_terms` is `true`, the words and phrases directly prefixed by a minus
+/// that follows a whitespace or the start of the query are returned in the negative query.
+fn create_primitive_query<'t>(
+    query: impl IntoIterator<Item = Token<'t>>,
     stop_words: Option<Set<&[u8]>>,
     words_limit: Option<usize>,
-) -> PrimitiveQuery
-where
-    A: AsRef<[u8]>,
-{
+    negative_terms: bool,
+) -> (PrimitiveQuery, NegativeQuery) {
     let mut primitive_query = Vec::new();
+    let mut negative_query = Vec::new();
     let mut phrase = Vec::new();
     let mut quoted = false;
+    let mut negative_phrase = false;
+    // The separators since the previous word and whether there is no previous word.
+    let mut separators = String::new();
+    let mut at_start = true;
 
     let parts_limit = words_limit.unwrap_or(usize::MAX);
 
-    let mut peekable = query.peekable();
+    let mut peekable = query.into_iter().peekable();
     while let Some(token) = peekable.next() {
         // early return if word limit is exceeded
         if primitive_query.len() >= parts_limit {
-            return primitive_query;
+            return (primitive_query, negative_query);
         }
 
         match token.kind {
             TokenKind::Word | TokenKind::StopWord => {
+                let preceding = mem::take(&mut separators);
+                let negative = negative_terms
+                    && if quoted {
+                        // The minus must directly precede the opening quote of the phrase.
+                        phrase.is_empty()
+                            && preceding
+                                .rfind('"')
+                                .map_or(false, |i| is_minus_prefix(&preceding[..i], at_start))
+                    } else {
+                        is_minus_prefix(&preceding, at_start)
+                    };
+                at_start = false;
+
                 // 1. if the word is quoted we push it in a phrase-buffer waiting for the ending quote,
-                // 2. if the word is not the last token of the query and is not a stop_word we push it as a non-prefix word,
-                // 3. if the word is the last token of the query we push it as a prefix word.
+                // 2. if the word is prefixed by a minus we push it as a negative word,
+                // 3. if the word is not the last token of the query and is not a stop_word we push it as a non-prefix word,
+                // 4. if the word is the last token of the query we push it as a prefix word.
                 if quoted {
+                    negative_phrase |= negative;
                     phrase.push(token.lemma().to_string());
+                } else if negative {
+                    negative_query.push(PrimitiveQueryPart::Word(token.lemma().to_string(), false));
                 } else if peekable.peek().is_some() {
                     if !stop_words.as_ref().map_or(false, |swords| swords.contains(token.lemma())) {
                         primitive_query
@@ -747,6 +901,7 @@ where
                 }
             }
             TokenKind::Separator(separator_kind) => {
+                separators.push_str(token.lemma());
                 let quote_count = token.lemma().chars().filter(|&s| s == '"').count();
                 // swap quoted state if we encounter a double quote
                 if quote_count % 2 != 0 {
@@ -755,7 +910,12 @@ where
                 // if there is a quote or a hard separator we close the phrase.
                 if !phrase.is_empty() && (quote_count > 0 || separator_kind == SeparatorKind::Hard)
                 {
-                    primitive_query.push(PrimitiveQueryPart::Phrase(mem::take(&mut phrase)));
+                    let query = if mem::take(&mut negative_phrase) {
+                        &mut negative_query
+                    } else {
+                        &mut primitive_query
+                    };
+                    query.push(PrimitiveQueryPart::Phrase(mem::take(&mut phrase)));
                 }
             }
             _ => (),
@@ -764,10 +924,23 @@ where
 
     // If a quote is never closed, we consider all of the end of the query as a phrase.
     if !phrase.is_empty() {
-        primitive_query.push(PrimitiveQueryPart::Phrase(mem::take(&mut phrase)));
+        let query = if negative_phrase { &mut negative_query } else { &mut primitive_query };
+        query.push(PrimitiveQueryPart::Phrase(mem::take(&mut phrase)));
     }
 
-    primitive_query
+    (primitive_query, negative_query)
+}
+
+/// Returns whether the separators end with a minus that follows
+/// a whitespace or, if there is no previous word, the start of the query.
+fn is_minus_prefix(separators: &str, at_start: bool) -> bool {
+    match separators.strip_suffix('-') {
+        Some(before) => match before.chars().last() {
+            Some(c) => c.is_whitespace(),
+            None => at_start,
+        },
+        None => false,
+    }
 }
 
 /// Returns the maximum number of typos that this Operation allows.
@@ -798,6 +971,7 @@ pub fn maximum_proximity(operation: &Operation) -> usize {
 mod test {
     use std::collections::HashMap;
 
+    use charabia::classifier::ClassifiedTokenIter;
     use charabia::Tokenize;
     use maplit::hashmap;
     use rand::rngs::StdRng;
@@ -811,6 +985,8 @@ mod test {
         synonyms: HashMap<Vec<String>, Vec<Vec<String>>>,
         postings: HashMap<String, RoaringBitmap>,
         exact_words: Option<fst::Set<Cow<'static, [u8]>>>,
+        word_splitting: bool,
+        word_concatenation: bool,
     }
 
     impl TestContext {
@@ -821,7 +997,7 @@ mod test {
             words_limit: Option<usize>,
             query: ClassifiedTokenIter<A>,
         ) -> Result<Option<(Operation, PrimitiveQuery)>> {
-            let primitive_query = create_primitive_query(query, None, words_limit);
+            let (primitive_query, _) = create_primitive_query(query, None, words_limit, false);
             if !primitive_query.is_empty() {
                 let qt = create_query_tree(
                     self,
@@ -854,6 +1030,14 @@ mod test {
             self.exact_words.as_ref()
         }
 
+        fn word_splitting_enabled(&self) -> bool {
+            self.word_splitting
+        }
+
+        fn word_concatenation_enabled(&self) -> bool {
+            self.word_concatenation
+        }
+
         fn word_pair_frequency(
             &self,
             left_word: &str,
@@ -928,10 +1112,29 @@ mod test {
                     String::from("quickbrown fox")  => random_postings(rng,   8000),
                 },
                 exact_words,
+                word_splitting: true,
+                word_concatenation: true,
             }
         }
     }
 
+    #[test]
+    fn negative_terms() {
+        let query = "jacket e-mail -leather -\"faux leather\" -";
+
+        let (positive, negative) = create_primitive_query(query.tokenize(), None, None, true);
+        assert!(matches!(&positive[0], PrimitiveQueryPart::Word(word, false) if word == "jacket"));
+        assert!(!positive.iter().any(|part| format!("{:?}", part).contains("leather")));
+        assert_eq!(
+            format!("{:?}", negative),
+            r#"[Word("leather", false), Phrase(["faux", "leather"])]"#
+        );
+
+        let (positive, negative) = create_primitive_query(query.tokenize(), None, None, false);
+        assert!(negative.is_empty());
+        assert!(positive.iter().any(|part| part.is_phrase()));
+    }
+
     #[test]
     fn prefix() {
         let query = "hey friends";
@@ -1090,6 +1293,93 @@ mod test {
         "###);
     }
 
+    #[test]
+    fn word_split_disabled() {
+        let query = "wordsplit fish ";
+        let tokens = query.tokenize();
+
+        let context = TestContext { word_splitting: false, ..TestContext::default() };
+        let (query_tree, _) =
+            context.build(TermsMatchingStrategy::All, true, None, tokens).unwrap().unwrap();
+
+        insta::assert_debug_snapshot!(query_tree, @r###"
+        OR
+          AND
+            Tolerant { word: "wordsplit", max typo: 2 }
+            Exact { word: "fish" }
+          Tolerant { word: "wordsplitfish", max typo: 1 }
+        "###);
+    }
+
+    #[test]
+    fn word_concatenation_disabled() {
+        let query = "wordsplit fish ";
+        let tokens = query.tokenize();
+
+        let context = TestContext { word_concatenation: false, ..TestContext::default() };
+        let (query_tree, _) =
+            context.build(TermsMatchingStrategy::All, true, None, tokens).unwrap().unwrap();
+
+        insta::assert_debug_snapshot!(query_tree, @r###"
+        AND
+          OR
+            PHRASE ["word", "split"]
+            Tolerant { word: "wordsplit", max typo: 2 }
+          Exact { word: "fish" }
+        "###);
+    }
+
+    #[test]
+    fn word_concatenation_disabled_keeps_synonyms() {
+        let query = "new york city ";
+        let tokens = query.tokenize();
+
+        let context = TestContext { word_concatenation: false, ..TestContext::default() };
+        let (query_tree, _) =
+            context.build(TermsMatchingStrategy::All, true, None, tokens).unwrap().unwrap();
+
+        insta::assert_debug_snapshot!(query_tree, @r###"
+        OR
+          AND
+            Exact { word: "new" }
+            AND
+              Exact { word: "york" }
+              Exact { word: "city" }
+          AND
+            OR
+              Exact { word: "nyc" }
+              AND
+                Exact { word: "new" }
+                Exact { word: "york" }
+                Exact { word: "city" }
+            Exact { word: "city" }
+          Exact { word: "nyc" }
+          AND
+            Exact { word: "new" }
+            Exact { word: "york" }
+        "###);
+    }
+
+    #[test]
+    fn word_split_and_concatenation_disabled() {
+        let query = "wordsplit fish ";
+        let tokens = query.tokenize();
+
+        let context = TestContext {
+            word_splitting: false,
+            word_concatenation: false,
+            ..TestContext::default()
+        };
+        let (query_tree, _) =
+            context.build(TermsMatchingStrategy::All, true, None, tokens).unwrap().unwrap();
+
+        insta::assert_debug_snapshot!(query_tree, @r###"
+        AND
+          Tolerant { word: "wordsplit", max typo: 2 }
+          Exact { word: "fish" }
+        "###);
+    }
+
     #[test]
     fn phrase() {
         let query = "\"hey friends\" \" \" \"wooop";
@@ -0,0 +1,123 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+use heed::RoTxn;
+
+use crate::position_bucketing::PositionBucketing;
+use crate::proximity::ProximityPrecision;
+use crate::{Criterion, Dictionary, FieldId, FieldsIdsMap, Index, Result};
+
+/// The settings of an index read by every search, decoded once per version of the index.
+///
+/// The context is tagged with the update id of the index it was read at, it is only
+/// valid for the read transactions that see the same update id.
+#[derive(Debug)]
+pub struct SearchableContext {
+    pub update_id: u64,
+    pub fields_ids_map: FieldsIdsMap,
+    /// The searchable fields, `None` if all the fields are searchable.
+    pub searchable_fields: Option<Vec<String>>,
+    /// The ids of the searchable fields, of all the fields if they are all searchable.
+    pub searchable_fields_ids: Vec<FieldId>,
+    pub synonyms: HashMap<Vec<String>, Vec<Vec<String>>>,
+    pub dictionary: Dictionary,
+    pub criteria: Vec<Criterion>,
+    pub sortable_fields: HashSet<String>,
+    pub distinct_field: Option<String>,
+    pub authorize_typos: bool,
+    pub enable_word_splitting: bool,
+    pub enable_word_concatenation: bool,
+    pub enable_negative_terms: bool,
+    /// Whether the diacritics are kept in the query words, see [`Index::count_diacritics`].
+    pub count_diacritics: bool,
+    pub min_word_len_one_typo: u8,
+    pub min_word_len_two_typos: u8,
+    pub max_proximity: u8,
+    pub proximity_precision: ProximityPrecision,
+    pub position_bucketing: PositionBucketing,
+}
+
+impl SearchableContext {
+    fn new(index: &Index, rtxn: &RoTxn, update_id: u64) -> Result<SearchableContext> {
+        let fields_ids_map = index.fields_ids_map(rtxn)?;
+        let searchable_fields = index
+            .searchable_fields(rtxn)?
+            .map(|fields| fields.into_iter().map(String::from).collect::<Vec<_>>());
+        let searchable_fields_ids = match &searchable_fields {
+            Some(fields) => fields.iter().filter_map(|name| fields_ids_map.id(name)).collect(),
+            None => fields_ids_map.ids().collect(),
+        };
+
+        Ok(SearchableContext {
+            update_id,
+            searchable_fields,
+            searchable_fields_ids,
+            fields_ids_map,
+            synonyms: index.synonyms(rtxn)?,
+            dictionary: Dictionary::new(index.dictionary(rtxn)?),
+            criteria: index.criteria(rtxn)?,
+            sortable_fields: index.sortable_fields(rtxn)?,
+            distinct_field: index.distinct_field(rtxn)?.map(String::from),
+            authorize_typos: index.authorize_typos(rtxn)?,
+            enable_word_splitting: index.enable_word_splitting(rtxn)?,
+            enable_word_concatenation: index.enable_word_concatenation(rtxn)?,
+            enable_negative_terms: index.enable_negative_terms(rtxn)?,
+            count_diacritics: index.count_diacritics(rtxn)?,
+            min_word_len_one_typo: index.min_word_len_one_typo(rtxn)?,
+            min_word_len_two_typos: index.min_word_len_two_typos(rtxn)?,
+            max_proximity: index.max_proximity(rtxn)?,
+            proximity_precision: index.proximity_precision(rtxn)?,
+            position_bucketing: index.position_bucketing(rtxn)?,
+        })
+    }
+
+    /// Returns the synonyms of the given sequence of words.
+    pub fn words_synonyms<S: AsRef<str>>(&self, words: &[S]) -> Option<Vec<Vec<String>>> {
+        let words: Vec<_> = words.iter().map(|s| s.as_ref().to_owned()).collect();
+        self.synonyms.get(&words).cloned()
+    }
+}
+
+/// The [`SearchableContext`] of the last version of the index seen by a search.
+///
+/// The context is built again, once, by the first search that sees a more recent
+/// update id. The searches on older versions of the index build their own
+/// context without replacing the cached one.
+#[derive(Default)]
+pub(crate) struct SearchableContextCache {
+    context: RwLock<Option<Arc<SearchableContext>>>,
+}
+
+impl SearchableContextCache {
+    pub fn get_or_build(&self, index: &Index, rtxn: &RoTxn) -> Result<Arc<SearchableContext>> {
+        let update_id = index.update_id(rtxn)?;
+        let cached = self.context.read().unwrap().clone();
+        match cached {
+            Some(context) if context.update_id == update_id => return Ok(context),
+            Some(context) if context.update_id > update_id => {
+                return Ok(Arc::new(SearchableContext::new(index, rtxn, update_id)?))
+            }
+            _ => (),
+        }
+
+        let mut cached = self.context.write().unwrap();
+        // Another search may have built the context while we were waiting for the lock.
+        match &*cached {
+            Some(context) if context.update_id == update_id => Ok(context.clone()),
+            Some(context) if context.update_id > update_id => {
+                Ok(Arc::new(SearchableContext::new(index, rtxn, update_id)?))
+            }
+            _ => {
+                let context = Arc::new(SearchableContext::new(index, rtxn, update_id)?);
+                *cached = Some(context.clone());
+                Ok(context)
+            }
+        }
+    }
+
+    /// The update id of the cached context, if any.
+    #[cfg(test)]
+    pub fn update_id(&self) -> Option<u64> {
+        self.context.read().unwrap().as_ref().map(|context| context.update_id)
+    }
+}
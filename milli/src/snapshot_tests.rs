@@ -1,16 +1,23 @@
 use std::borrow::Cow;
-use std::fmt::Write;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fmt::{self, Debug, Write};
 use std::path::Path;
 
-use heed::types::ByteSlice;
+use heed::types::{ByteSlice, Str};
 use heed::BytesDecode;
 use roaring::RoaringBitmap;
 
 use crate::heed_codec::facet::{
-    FacetLevelValueU32Codec, FacetStringLevelZeroCodec, FacetStringLevelZeroValueCodec,
-    FacetStringZeroBoundsValueCodec,
+    FacetExactIntegersCodec, FacetLevelValueF64Codec, FacetLevelValueU32Codec,
+    FacetStringLevelZeroCodec, FacetStringLevelZeroValueCodec, FacetStringZeroBoundsValueCodec,
+    FieldDocIdFacetF64Codec, FieldDocIdFacetStringCodec,
+};
+use crate::{
+    make_db_snap_from_iter, BEU32StrCodec, BoRoaringBitmapCodec, CboRoaringBitmapCodec,
+    DocumentFacetValues, ExternalDocumentsIds, FieldIdWordCountCodec, Index, RoaringBitmapCodec,
+    StrBEU32Codec, U8StrStrCodec,
 };
-use crate::{make_db_snap_from_iter, CboRoaringBitmapCodec, ExternalDocumentsIds, Index};
 
 #[track_caller]
 pub fn default_db_snapshot_settings_for_test(name: Option<&str>) -> insta::Settings {
@@ -59,6 +66,7 @@ Create a snapshot test of the given database.
     - `external_documents_ids`
     - `number_faceted_documents_ids`
     - `string_faceted_documents_ids`
+    - `document_facet_values`
     - `words_fst`
     - `words_prefixes_fst`
 
@@ -319,12 +327,14 @@ pub fn snap_external_documents_ids(index: &Index) -> String {
     let rtxn = index.read_txn().unwrap();
     let ExternalDocumentsIds { soft, hard, .. } = index.external_documents_ids(&rtxn).unwrap();
     let mut snap = String::new();
-    let soft_bytes = soft.into_fst().as_bytes().to_owned();
-    let mut hex_soft = String::new();
-    for byte in soft_bytes {
-        write!(&mut hex_soft, "{:x}", byte).unwrap();
+    for tier in soft {
+        let soft_bytes = tier.into_fst().as_bytes().to_owned();
+        let mut hex_soft = String::new();
+        for byte in soft_bytes {
+            write!(&mut hex_soft, "{:x}", byte).unwrap();
+        }
+        writeln!(&mut snap, "soft: {hex_soft}").unwrap();
     }
-    writeln!(&mut snap, "soft: {hex_soft}").unwrap();
     let hard_bytes = hard.into_fst().as_bytes().to_owned();
     let mut hex_hard = String::new();
     for byte in hard_bytes {
@@ -358,6 +368,30 @@ pub fn snap_string_faceted_documents_ids(index: &Index) -> String {
     }
     snap
 }
+pub fn snap_document_facet_values(index: &Index) -> String {
+    let rtxn = index.read_txn().unwrap();
+    let fields_ids_map = index.fields_ids_map(&rtxn).unwrap();
+    let documents_ids = index.documents_ids(&rtxn).unwrap();
+
+    let mut snap = String::new();
+    for docid in documents_ids {
+        for field_id in fields_ids_map.ids() {
+            let DocumentFacetValues { numbers, exact_integers, strings } =
+                index.facet_values_for_document(&rtxn, docid, field_id).unwrap();
+            for number in numbers {
+                writeln!(&mut snap, "{docid:<6} {field_id:<3} {number}").unwrap();
+            }
+            for integer in exact_integers {
+                writeln!(&mut snap, "{docid:<6} {field_id:<3} {integer} (exact)").unwrap();
+            }
+            for (normalized, original) in strings {
+                writeln!(&mut snap, "{docid:<6} {field_id:<3} {normalized:<8} {original:<8}")
+                    .unwrap();
+            }
+        }
+    }
+    snap
+}
 pub fn snap_words_fst(index: &Index) -> String {
     let rtxn = index.read_txn().unwrap();
     let words_fst = index.words_fst(&rtxn).unwrap();
@@ -379,6 +413,231 @@ pub fn snap_words_prefixes_fst(index: &Index) -> String {
     snap
 }
 
+/// Writes the raw keys and values of every database of the index in hexadecimal,
+/// without the creation and update dates that change on every run.
+pub fn snap_raw_databases(index: &Index) -> String {
+    use crate::index::db_name::MAIN;
+    use crate::index::main_key::{CREATED_AT_KEY, UPDATED_AT_KEY};
+
+    let rtxn = index.read_txn().unwrap();
+    let mut snap = String::new();
+    for (name, database) in index.databases() {
+        writeln!(&mut snap, "{}:", name).unwrap();
+        for result in database.iter::<_, ByteSlice, ByteSlice>(&rtxn).unwrap() {
+            let (key, value) = result.unwrap();
+            let is_date = key == CREATED_AT_KEY.as_bytes() || key == UPDATED_AT_KEY.as_bytes();
+            if name == MAIN && is_date {
+                continue;
+            }
+            for byte in key {
+                write!(&mut snap, "{:02x}", byte).unwrap();
+            }
+            snap.push_str(" => ");
+            for byte in value {
+                write!(&mut snap, "{:02x}", byte).unwrap();
+            }
+            snap.push('\n');
+        }
+    }
+    snap
+}
+
+/// The maximum number of entries of each kind displayed for a single database of an `IndexDiff`.
+const MAX_DISPLAYED_ENTRIES: usize = 8;
+
+/// The differences between the content of two indexes, database by database.
+#[derive(Debug, Default)]
+pub struct IndexDiff {
+    /// The databases that differ, the identical ones are not listed.
+    pub databases: Vec<DatabaseDiff>,
+}
+
+impl IndexDiff {
+    pub fn is_empty(&self) -> bool {
+        self.databases.is_empty()
+    }
+}
+
+/// The entries of a single database that differ between two indexes, decoded with the codecs
+/// of the database when possible and in hexadecimal otherwise.
+#[derive(Debug)]
+pub struct DatabaseDiff {
+    pub name: &'static str,
+    /// The entries only found in the second index.
+    pub added: Vec<String>,
+    /// The entries only found in the first index.
+    pub removed: Vec<String>,
+    /// The entries with the same key but a different value, as found in the first and second index.
+    pub changed: Vec<(String, String)>,
+}
+
+impl fmt::Display for IndexDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "the indexes are identical");
+        }
+
+        for database in &self.databases {
+            let DatabaseDiff { name, added, removed, changed } = database;
+            writeln!(
+                f,
+                "{name}: {} added, {} removed, {} changed",
+                added.len(),
+                removed.len(),
+                changed.len()
+            )?;
+            for entry in added.iter().take(MAX_DISPLAYED_ENTRIES) {
+                writeln!(f, "  + {entry}")?;
+            }
+            display_remaining(f, added.len())?;
+            for entry in removed.iter().take(MAX_DISPLAYED_ENTRIES) {
+                writeln!(f, "  - {entry}")?;
+            }
+            display_remaining(f, removed.len())?;
+            for (before, after) in changed.iter().take(MAX_DISPLAYED_ENTRIES) {
+                writeln!(f, "  - {before}")?;
+                writeln!(f, "  + {after}")?;
+            }
+            display_remaining(f, changed.len())?;
+        }
+
+        Ok(())
+    }
+}
+
+fn display_remaining(f: &mut fmt::Formatter, len: usize) -> fmt::Result {
+    match len.checked_sub(MAX_DISPLAYED_ENTRIES) {
+        Some(remaining) if remaining > 0 => writeln!(f, "  ... and {remaining} more"),
+        _ => Ok(()),
+    }
+}
+
+/// Compares every database of the two indexes entry by entry, ignoring the creation and
+/// update dates that change on every run.
+///
+/// The entries are compared on their raw bytes and only decoded to be displayed.
+pub fn compare_indexes(a: &Index, b: &Index) -> IndexDiff {
+    use crate::index::db_name::MAIN;
+    use crate::index::main_key::{CREATED_AT_KEY, UPDATED_AT_KEY};
+
+    let a_rtxn = a.read_txn().unwrap();
+    let b_rtxn = b.read_txn().unwrap();
+    let mut diff = IndexDiff::default();
+
+    for (&(name, a_database), &(_, b_database)) in a.databases().iter().zip(b.databases().iter()) {
+        let is_date = |key: &[u8]| {
+            name == MAIN && (key == CREATED_AT_KEY.as_bytes() || key == UPDATED_AT_KEY.as_bytes())
+        };
+        let a_entries: BTreeMap<&[u8], &[u8]> = a_database
+            .iter::<_, ByteSlice, ByteSlice>(&a_rtxn)
+            .unwrap()
+            .map(Result::unwrap)
+            .filter(|(key, _)| !is_date(key))
+            .collect();
+        let mut b_entries: BTreeMap<&[u8], &[u8]> = b_database
+            .iter::<_, ByteSlice, ByteSlice>(&b_rtxn)
+            .unwrap()
+            .map(Result::unwrap)
+            .filter(|(key, _)| !is_date(key))
+            .collect();
+
+        let mut database_diff =
+            DatabaseDiff { name, added: Vec::new(), removed: Vec::new(), changed: Vec::new() };
+        for (key, a_value) in a_entries {
+            match b_entries.remove(key) {
+                Some(b_value) if a_value == b_value => (),
+                Some(b_value) => database_diff
+                    .changed
+                    .push((display_entry(name, key, a_value), display_entry(name, key, b_value))),
+                None => database_diff.removed.push(display_entry(name, key, a_value)),
+            }
+        }
+        for (key, b_value) in b_entries {
+            database_diff.added.push(display_entry(name, key, b_value));
+        }
+
+        let DatabaseDiff { added, removed, changed, .. } = &database_diff;
+        if !added.is_empty() || !removed.is_empty() || !changed.is_empty() {
+            diff.databases.push(database_diff);
+        }
+    }
+
+    diff
+}
+
+/// Displays an entry of the given database with its codecs, in hexadecimal if it can't be decoded.
+fn display_entry(database: &str, key: &[u8], value: &[u8]) -> String {
+    use crate::index::db_name::*;
+
+    fn decoded<'a, K>(key: &'a [u8], value: Option<String>) -> Option<String>
+    where
+        K: BytesDecode<'a>,
+        K::DItem: Debug,
+    {
+        Some(format!("{:?} => {}", K::bytes_decode(key)?, value?))
+    }
+
+    let bitmap = |bitmap: Option<RoaringBitmap>| bitmap.map(|b| display_bitmap(&b));
+    let cbo_bitmap = || bitmap(CboRoaringBitmapCodec::bytes_decode(value));
+
+    let displayed = match database {
+        MAIN => decoded::<Str>(key, Some(display_hex(value))),
+        WORD_DOCIDS | EXACT_WORD_DOCIDS | WORD_PREFIX_DOCIDS | EXACT_WORD_PREFIX_DOCIDS => {
+            decoded::<Str>(key, bitmap(RoaringBitmapCodec::bytes_decode(value)))
+        }
+        DOCID_WORD_POSITIONS => {
+            decoded::<BEU32StrCodec>(key, bitmap(BoRoaringBitmapCodec::bytes_decode(value)))
+        }
+        WORD_PAIR_PROXIMITY_DOCIDS
+        | WORD_PREFIX_PAIR_PROXIMITY_DOCIDS
+        | PREFIX_WORD_PAIR_PROXIMITY_DOCIDS => decoded::<U8StrStrCodec>(key, cbo_bitmap()),
+        WORD_POSITION_DOCIDS | WORD_PREFIX_POSITION_DOCIDS => {
+            decoded::<StrBEU32Codec>(key, cbo_bitmap())
+        }
+        FIELD_ID_WORD_COUNT_DOCIDS => decoded::<FieldIdWordCountCodec>(key, cbo_bitmap()),
+        FACET_ID_EXISTS_DOCIDS | FACET_ID_IS_NULL_DOCIDS | FACET_ID_IS_EMPTY_DOCIDS => {
+            let field_id = key.try_into().ok().map(u16::from_be_bytes);
+            field_id.zip(cbo_bitmap()).map(|(field_id, docids)| format!("{field_id} => {docids}"))
+        }
+        FACET_ID_F64_DOCIDS => decoded::<FacetLevelValueF64Codec>(key, cbo_bitmap()),
+        FACET_ID_STRING_DOCIDS => match FacetStringLevelZeroCodec::bytes_decode(key) {
+            Some(_) => decoded::<FacetStringLevelZeroCodec>(
+                key,
+                FacetStringLevelZeroValueCodec::bytes_decode(value)
+                    .map(|(original, docids)| format!("{original:?} {}", display_bitmap(&docids))),
+            ),
+            None => decoded::<FacetLevelValueU32Codec>(
+                key,
+                FacetStringZeroBoundsValueCodec::<CboRoaringBitmapCodec>::bytes_decode(value)
+                    .map(|(bounds, docids)| format!("{bounds:?} {}", display_bitmap(&docids))),
+            ),
+        },
+        FIELD_ID_DOCID_FACET_F64S => decoded::<FieldDocIdFacetF64Codec>(
+            key,
+            FacetExactIntegersCodec::bytes_decode(value).map(|integers| format!("{integers:?}")),
+        ),
+        FIELD_ID_DOCID_FACET_STRINGS => decoded::<FieldDocIdFacetStringCodec>(
+            key,
+            Str::bytes_decode(value).map(|original| format!("{original:?}")),
+        ),
+        DOCUMENTS => {
+            let docid = key.try_into().ok().map(u32::from_be_bytes);
+            docid.map(|docid| format!("{docid} => {}", display_hex(value)))
+        }
+        _ => None,
+    };
+
+    displayed.unwrap_or_else(|| format!("{} => {}", display_hex(key), display_hex(value)))
+}
+
+fn display_hex(bytes: &[u8]) -> String {
+    let mut s = String::new();
+    for byte in bytes {
+        write!(&mut s, "{:02x}", byte).unwrap();
+    }
+    s
+}
+
 pub fn snap_settings(index: &Index) -> String {
     let mut snap = String::new();
     let rtxn = index.read_txn().unwrap();
@@ -397,7 +656,11 @@ pub fn snap_settings(index: &Index) -> String {
     write_setting_to_snap!(filterable_fields);
     write_setting_to_snap!(sortable_fields);
     write_setting_to_snap!(synonyms);
+    write_setting_to_snap!(dictionary);
     write_setting_to_snap!(authorize_typos);
+    write_setting_to_snap!(enable_word_splitting);
+    write_setting_to_snap!(enable_word_concatenation);
+    write_setting_to_snap!(enable_negative_terms);
     write_setting_to_snap!(min_word_len_one_typo);
     write_setting_to_snap!(min_word_len_two_typos);
     write_setting_to_snap!(exact_words);
@@ -481,6 +744,9 @@ macro_rules! full_snap_of_db {
     ($index:ident, string_faceted_documents_ids) => {{
         $crate::snapshot_tests::snap_string_faceted_documents_ids(&$index)
     }};
+    ($index:ident, document_facet_values) => {{
+        $crate::snapshot_tests::snap_document_facet_values(&$index)
+    }};
     ($index:ident, words_fst) => {{
         $crate::snapshot_tests::snap_words_fst(&$index)
     }};
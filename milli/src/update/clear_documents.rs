@@ -1,19 +1,36 @@
+use heed::{Database, RwTxn};
 use roaring::RoaringBitmap;
+use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
-use crate::{ExternalDocumentsIds, FieldDistribution, Index, Result};
+use crate::{ExternalDocumentsIds, FieldDistributionWithTypes, FieldsIdsMap, Index, Result};
 
 pub struct ClearDocuments<'t, 'u, 'i> {
     wtxn: &'t mut heed::RwTxn<'i, 'u>,
     index: &'i Index,
+    keep_fields_ids_map: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClearDocumentsResult {
+    pub deleted_documents: u64,
+    /// The number of databases that were not already empty.
+    pub cleared_databases: usize,
 }
 
 impl<'t, 'u, 'i> ClearDocuments<'t, 'u, 'i> {
     pub fn new(wtxn: &'t mut heed::RwTxn<'i, 'u>, index: &'i Index) -> ClearDocuments<'t, 'u, 'i> {
-        ClearDocuments { wtxn, index }
+        ClearDocuments { wtxn, index, keep_fields_ids_map: true }
     }
 
-    pub fn execute(self) -> Result<u64> {
+    /// Whether to keep the fields ids map and the primary key of the index, the next documents
+    /// are then indexed with the same fields ids. They are kept by default, `false` resets them
+    /// for the next documents to be indexed as in a new index. The settings are always kept.
+    pub fn keep_fields_ids_map(&mut self, keep: bool) {
+        self.keep_fields_ids_map = keep;
+    }
+
+    pub fn execute(self) -> Result<ClearDocumentsResult> {
         self.index.set_updated_at(self.wtxn, &OffsetDateTime::now_utc())?;
         let Index {
             env: _env,
@@ -32,9 +49,13 @@ impl<'t, 'u, 'i> ClearDocuments<'t, 'u, 'i> {
             facet_id_f64_docids,
             facet_id_string_docids,
             facet_id_exists_docids,
+            facet_id_is_null_docids,
+            facet_id_is_empty_docids,
             field_id_docid_facet_f64s,
             field_id_docid_facet_strings,
             documents,
+            docids_cache: _docids_cache,
+            searchable_context: _searchable_context,
         } = self.index;
 
         let empty_roaring = RoaringBitmap::default();
@@ -49,9 +70,15 @@ impl<'t, 'u, 'i> ClearDocuments<'t, 'u, 'i> {
         self.index.put_external_documents_ids(self.wtxn, &ExternalDocumentsIds::default())?;
         self.index.put_documents_ids(self.wtxn, &empty_roaring)?;
         self.index.put_soft_deleted_documents_ids(self.wtxn, &empty_roaring)?;
-        self.index.put_field_distribution(self.wtxn, &FieldDistribution::default())?;
+        self.index.put_field_distribution(self.wtxn, &FieldDistributionWithTypes::default())?;
         self.index.delete_geo_rtree(self.wtxn)?;
         self.index.delete_geo_faceted_documents_ids(self.wtxn)?;
+        // The next documents will train a new compression dictionary.
+        self.index.delete_documents_compression_dictionary(self.wtxn)?;
+        if !self.keep_fields_ids_map {
+            self.index.put_fields_ids_map(self.wtxn, &FieldsIdsMap::new())?;
+            self.index.delete_primary_key(self.wtxn)?;
+        }
 
         // We clean all the faceted documents ids.
         for field_id in faceted_fields {
@@ -60,28 +87,42 @@ impl<'t, 'u, 'i> ClearDocuments<'t, 'u, 'i> {
         }
 
         // Clear the other databases.
-        word_docids.clear(self.wtxn)?;
-        exact_word_docids.clear(self.wtxn)?;
-        word_prefix_docids.clear(self.wtxn)?;
-        exact_word_prefix_docids.clear(self.wtxn)?;
-        docid_word_positions.clear(self.wtxn)?;
-        word_pair_proximity_docids.clear(self.wtxn)?;
-        word_prefix_pair_proximity_docids.clear(self.wtxn)?;
-        prefix_word_pair_proximity_docids.clear(self.wtxn)?;
-        word_position_docids.clear(self.wtxn)?;
-        field_id_word_count_docids.clear(self.wtxn)?;
-        word_prefix_position_docids.clear(self.wtxn)?;
-        facet_id_f64_docids.clear(self.wtxn)?;
-        facet_id_exists_docids.clear(self.wtxn)?;
-        facet_id_string_docids.clear(self.wtxn)?;
-        field_id_docid_facet_f64s.clear(self.wtxn)?;
-        field_id_docid_facet_strings.clear(self.wtxn)?;
-        documents.clear(self.wtxn)?;
-
-        Ok(number_of_documents)
+        let mut cleared_databases = 0;
+        cleared_databases += clear_database(self.wtxn, *word_docids)? as usize;
+        cleared_databases += clear_database(self.wtxn, *exact_word_docids)? as usize;
+        cleared_databases += clear_database(self.wtxn, *word_prefix_docids)? as usize;
+        cleared_databases += clear_database(self.wtxn, *exact_word_prefix_docids)? as usize;
+        cleared_databases += clear_database(self.wtxn, *docid_word_positions)? as usize;
+        cleared_databases += clear_database(self.wtxn, *word_pair_proximity_docids)? as usize;
+        cleared_databases +=
+            clear_database(self.wtxn, *word_prefix_pair_proximity_docids)? as usize;
+        cleared_databases +=
+            clear_database(self.wtxn, *prefix_word_pair_proximity_docids)? as usize;
+        cleared_databases += clear_database(self.wtxn, *word_position_docids)? as usize;
+        cleared_databases += clear_database(self.wtxn, *field_id_word_count_docids)? as usize;
+        cleared_databases += clear_database(self.wtxn, *word_prefix_position_docids)? as usize;
+        cleared_databases += clear_database(self.wtxn, *facet_id_f64_docids)? as usize;
+        cleared_databases += clear_database(self.wtxn, *facet_id_exists_docids)? as usize;
+        cleared_databases += clear_database(self.wtxn, *facet_id_is_null_docids)? as usize;
+        cleared_databases += clear_database(self.wtxn, *facet_id_is_empty_docids)? as usize;
+        cleared_databases += clear_database(self.wtxn, *facet_id_string_docids)? as usize;
+        cleared_databases += clear_database(self.wtxn, *field_id_docid_facet_f64s)? as usize;
+        cleared_databases += clear_database(self.wtxn, *field_id_docid_facet_strings)? as usize;
+        cleared_databases += clear_database(self.wtxn, *documents)? as usize;
+
+        self.index.end_update(self.wtxn)?;
+
+        Ok(ClearDocumentsResult { deleted_documents: number_of_documents, cleared_databases })
     }
 }
 
+/// Clears the database and returns whether it contained entries.
+fn clear_database<KC, DC>(wtxn: &mut RwTxn, database: Database<KC, DC>) -> heed::Result<bool> {
+    let is_empty = database.is_empty(wtxn)?;
+    database.clear(wtxn)?;
+    Ok(!is_empty)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -102,7 +143,13 @@ mod tests {
 
         // Clear all documents from the database.
         let builder = ClearDocuments::new(&mut wtxn, &index);
-        assert_eq!(builder.execute().unwrap(), 3);
+        let result = builder.execute().unwrap();
+        assert_eq!(result.deleted_documents, 3);
+        assert!(result.cleared_databases > 0);
+
+        // Clearing an empty index clears nothing.
+        let result = ClearDocuments::new(&mut wtxn, &index).execute().unwrap();
+        assert_eq!(result, ClearDocumentsResult { deleted_documents: 0, cleared_databases: 0 });
         wtxn.commit().unwrap();
 
         let rtxn = index.read_txn().unwrap();
@@ -126,8 +173,69 @@ mod tests {
         assert!(index.word_prefix_pair_proximity_docids.is_empty(&rtxn).unwrap());
         assert!(index.facet_id_f64_docids.is_empty(&rtxn).unwrap());
         assert!(index.facet_id_string_docids.is_empty(&rtxn).unwrap());
+        assert!(index.facet_id_exists_docids.is_empty(&rtxn).unwrap());
+        assert!(index.facet_id_is_null_docids.is_empty(&rtxn).unwrap());
+        assert!(index.facet_id_is_empty_docids.is_empty(&rtxn).unwrap());
         assert!(index.field_id_docid_facet_f64s.is_empty(&rtxn).unwrap());
         assert!(index.field_id_docid_facet_strings.is_empty(&rtxn).unwrap());
         assert!(index.documents.is_empty(&rtxn).unwrap());
     }
+
+    #[test]
+    fn keep_fields_ids_map() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 0, "name": "kevin", "age": 20 },
+                { "id": 1, "name": "kevina", "country": "France" }
+            ]))
+            .unwrap();
+        let rtxn = index.read_txn().unwrap();
+        let fields_ids = |rtxn: &heed::RoTxn| {
+            let fields_ids_map = index.fields_ids_map(rtxn).unwrap();
+            fields_ids_map.iter().map(|(id, name)| (id, name.to_string())).collect::<Vec<_>>()
+        };
+        let fields_ids_before = fields_ids(&rtxn);
+        drop(rtxn);
+
+        let mut wtxn = index.write_txn().unwrap();
+        let builder = ClearDocuments::new(&mut wtxn, &index);
+        assert_eq!(builder.execute().unwrap().deleted_documents, 2);
+        wtxn.commit().unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(fields_ids(&rtxn), fields_ids_before);
+        assert_eq!(index.primary_key(&rtxn).unwrap(), Some("id"));
+        drop(rtxn);
+
+        // The documents indexed again, with their fields in another order, keep their fields ids.
+        index
+            .add_documents(documents!([
+                { "country": "Italy", "age": 30, "name": "benoit", "id": 2 }
+            ]))
+            .unwrap();
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(fields_ids(&rtxn), fields_ids_before);
+        let results = index.search(&rtxn).query("benoit").execute().unwrap();
+        assert_eq!(results.documents_ids.len(), 1);
+        drop(rtxn);
+
+        // Without them the fields ids are assigned again.
+        let mut wtxn = index.write_txn().unwrap();
+        let mut builder = ClearDocuments::new(&mut wtxn, &index);
+        builder.keep_fields_ids_map(false);
+        builder.execute().unwrap();
+        wtxn.commit().unwrap();
+        let rtxn = index.read_txn().unwrap();
+        assert!(index.fields_ids_map(&rtxn).unwrap().is_empty());
+        assert!(index.primary_key(&rtxn).unwrap().is_none());
+        drop(rtxn);
+        index
+            .add_documents(documents!([
+                { "country": "Italy", "age": 30, "name": "benoit", "id": 2 }
+            ]))
+            .unwrap();
+        let rtxn = index.read_txn().unwrap();
+        assert_ne!(fields_ids(&rtxn), fields_ids_before);
+    }
 }
@@ -1,4 +1,5 @@
 use std::collections::btree_map::Entry;
+use std::collections::BTreeSet;
 
 use fst::IntoStreamer;
 use heed::types::{ByteSlice, Str};
@@ -8,15 +9,17 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use time::OffsetDateTime;
 
+use super::facets::shrink_field_levels;
 use super::ClearDocuments;
 use crate::error::{InternalError, SerializationError, UserError};
 use crate::heed_codec::facet::{
-    FacetLevelValueU32Codec, FacetStringLevelZeroValueCodec, FacetStringZeroBoundsValueCodec,
+    FacetLevelValueU32Codec, FacetStringLevelZeroCodec, FacetStringLevelZeroValueCodec,
+    FacetStringZeroBoundsValueCodec, FieldDocIdFacetStringCodec,
 };
 use crate::heed_codec::CboRoaringBitmapCodec;
 use crate::index::{db_name, main_key};
 use crate::{
-    DocumentId, ExternalDocumentsIds, FieldId, FieldIdMapMissingEntry, Index, Result,
+    DocumentId, ExternalDocumentsIds, FieldId, FieldIdMapMissingEntry, Filter, Index, Result,
     RoaringBitmapCodec, SmallString32, BEU32,
 };
 
@@ -25,6 +28,9 @@ pub struct DeleteDocuments<'t, 'u, 'i> {
     index: &'i Index,
     external_documents_ids: ExternalDocumentsIds<'static>,
     to_delete_docids: RoaringBitmap,
+    soft_deleted_threshold: Option<f64>,
+    purge: bool,
+    pub(crate) validate_ordering: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -45,9 +51,20 @@ impl<'t, 'u, 'i> DeleteDocuments<'t, 'u, 'i> {
             index,
             external_documents_ids,
             to_delete_docids: RoaringBitmap::new(),
+            soft_deleted_threshold: None,
+            purge: false,
+            validate_ordering: cfg!(debug_assertions),
         })
     }
 
+    /// Sets the fraction of soft deleted documents, over all the documents stored,
+    /// above which the soft deleted documents are physically removed from the index.
+    ///
+    /// By default they are removed depending on the disk space they use.
+    pub fn soft_deleted_threshold(&mut self, threshold: f64) {
+        self.soft_deleted_threshold = Some(threshold);
+    }
+
     pub fn delete_document(&mut self, docid: u32) {
         self.to_delete_docids.insert(docid);
     }
@@ -62,7 +79,33 @@ impl<'t, 'u, 'i> DeleteDocuments<'t, 'u, 'i> {
         Some(docid)
     }
 
+    /// Evaluates the filter in the current write transaction and deletes every document
+    /// that matches it. Returns the number of documents that were deleted.
+    ///
+    /// The filter is evaluated before any modification is made to the index,
+    /// an invalid filter (e.g. on a non-filterable attribute) leaves the index untouched.
+    pub fn delete_documents_by_filter(mut self, filter: Filter) -> Result<u64> {
+        let docids = filter.evaluate(self.wtxn, self.index)?;
+        let deleted_documents = docids.len();
+        self.delete_documents(&docids);
+        self.execute()?;
+        Ok(deleted_documents)
+    }
+
+    /// Deletes the documents and physically removes them from the index along with
+    /// all the previously soft deleted documents, whatever the soft deleted threshold.
+    pub fn purge(mut self) -> Result<DocumentDeletionResult> {
+        self.purge = true;
+        self.execute()
+    }
+
     pub fn execute(mut self) -> Result<DocumentDeletionResult> {
+        let result = self.execute_deletion()?;
+        self.index.end_update(self.wtxn)?;
+        Ok(result)
+    }
+
+    fn execute_deletion(&mut self) -> Result<DocumentDeletionResult> {
         self.index.set_updated_at(self.wtxn, &OffsetDateTime::now_utc())?;
         // We retrieve the current documents ids that are in the database.
         let mut documents_ids = self.index.documents_ids(self.wtxn)?;
@@ -78,32 +121,36 @@ impl<'t, 'u, 'i> DeleteDocuments<'t, 'u, 'i> {
             return Ok(DocumentDeletionResult { deleted_documents: 0, remaining_documents: 0 });
         }
 
-        // We remove the documents ids that we want to delete
+        // We ignore the documents that are not in the database or already soft deleted,
+        // then we remove the documents ids that we want to delete
         // from the documents in the database and write them back.
+        self.to_delete_docids &= &documents_ids;
         documents_ids -= &self.to_delete_docids;
         self.index.put_documents_ids(self.wtxn, &documents_ids)?;
 
         // We can execute a ClearDocuments operation when the number of documents
         // to delete is exactly the number of documents in the database.
         if current_documents_ids_len == self.to_delete_docids.len() {
-            let remaining_documents = ClearDocuments::new(self.wtxn, self.index).execute()?;
+            ClearDocuments::new(self.wtxn, self.index).execute()?;
             return Ok(DocumentDeletionResult {
                 deleted_documents: current_documents_ids_len,
-                remaining_documents,
+                remaining_documents: 0,
             });
         }
 
         let fields_ids_map = self.index.fields_ids_map(self.wtxn)?;
-        let mut field_distribution = self.index.field_distribution(self.wtxn)?;
+        let mut field_distribution = self.index.field_distribution_with_types(self.wtxn)?;
 
         // we update the field distribution
+        let mut documents_decompressor = self.index.documents_decompressor(self.wtxn)?;
+        let documents = self.index.documents.remap_data_type::<ByteSlice>();
         for docid in self.to_delete_docids.iter() {
             let key = BEU32::new(docid);
-            let document =
-                self.index.documents.get(self.wtxn, &key)?.ok_or(
-                    InternalError::DatabaseMissingEntry { db_name: "documents", key: None },
-                )?;
-            for (fid, _value) in document.iter() {
+            let bytes = documents
+                .get(self.wtxn, &key)?
+                .ok_or(InternalError::DatabaseMissingEntry { db_name: "documents", key: None })?;
+            let document = documents_decompressor.decompress(bytes)?;
+            for (fid, _value) in document.as_obkv().iter() {
                 let field_name =
                     fields_ids_map.name(fid).ok_or(FieldIdMapMissingEntry::FieldId {
                         field_id: fid,
@@ -111,9 +158,9 @@ impl<'t, 'u, 'i> DeleteDocuments<'t, 'u, 'i> {
                     })?;
                 if let Entry::Occupied(mut entry) = field_distribution.entry(field_name.to_string())
                 {
-                    match entry.get().checked_sub(1) {
-                        Some(0) | None => entry.remove(),
-                        Some(count) => entry.insert(count),
+                    match entry.get().count.checked_sub(1) {
+                        Some(0) | None => drop(entry.remove()),
+                        Some(count) => entry.get_mut().count = count,
                     };
                 }
             }
@@ -123,32 +170,51 @@ impl<'t, 'u, 'i> DeleteDocuments<'t, 'u, 'i> {
 
         soft_deleted_docids |= &self.to_delete_docids;
 
-        // if we have less documents to delete than the threshold we simply save them in
+        let deleted_documents = self.to_delete_docids.len();
+        if soft_deleted_docids.is_empty() {
+            return Ok(DocumentDeletionResult {
+                deleted_documents,
+                remaining_documents: documents_ids.len(),
+            });
+        }
+
+        // if we have less soft deleted documents than the threshold we simply save them in
         // the `soft_deleted_documents_ids` bitmap and early exit.
         let size_used = self.index.used_size()?;
         let map_size = self.index.env.map_size()? as u64;
-        let nb_documents = self.index.number_of_documents(&self.wtxn)?;
+        let nb_documents = documents_ids.len();
         let nb_soft_deleted = soft_deleted_docids.len();
 
         let percentage_available = 100 - (size_used * 100 / map_size);
-        let estimated_document_size = size_used / (nb_documents + nb_soft_deleted);
-        let estimated_size_used_by_soft_deleted = estimated_document_size * nb_soft_deleted;
-        let percentage_used_by_soft_deleted_documents =
-            estimated_size_used_by_soft_deleted * 100 / map_size;
+        let below_threshold = match self.soft_deleted_threshold {
+            // the soft deleted documents are less than the threshold of all the documents stored.
+            Some(threshold) => {
+                nb_soft_deleted as f64 / (nb_documents + nb_soft_deleted) as f64 <= threshold
+            }
+            // the soft deleted documents use less than 10% of the total space available.
+            None => {
+                let estimated_document_size = size_used / (nb_documents + nb_soft_deleted);
+                let estimated_size_used_by_soft_deleted = estimated_document_size * nb_soft_deleted;
+                let percentage_used_by_soft_deleted_documents =
+                    estimated_size_used_by_soft_deleted * 100 / map_size;
+                percentage_used_by_soft_deleted_documents < 10
+            }
+        };
 
         // if we have more than 10% of disk space available and the soft deleted
-        // documents uses less than 10% of the total space available,
-        // we skip the deletion. Eg.
+        // documents are below the threshold, we skip the deletion. Eg.
         // - With 100Go of disk and 20Go used including 5Go of soft-deleted documents
         //   We don’t delete anything.
         // - With 100Go of disk and 95Go used including 1mo of soft-deleted documents
         //   We run the deletion.
         // - With 100Go of disk and 50Go used including 15Go of soft-deleted documents
         //   We run the deletion.
-        if percentage_available > 10 && percentage_used_by_soft_deleted_documents < 10 {
+        // - With a threshold of 10% and 1000 documents including 150 soft-deleted documents
+        //   We run the deletion.
+        if !self.purge && percentage_available > 10 && below_threshold {
             self.index.put_soft_deleted_documents_ids(self.wtxn, &soft_deleted_docids)?;
             return Ok(DocumentDeletionResult {
-                deleted_documents: self.to_delete_docids.len(),
+                deleted_documents,
                 remaining_documents: documents_ids.len(),
             });
         }
@@ -188,6 +254,8 @@ impl<'t, 'u, 'i> DeleteDocuments<'t, 'u, 'i> {
             word_prefix_position_docids,
             facet_id_f64_docids,
             facet_id_exists_docids,
+            facet_id_is_null_docids,
+            facet_id_is_empty_docids,
             facet_id_string_docids,
             field_id_docid_facet_f64s,
             field_id_docid_facet_strings,
@@ -202,9 +270,11 @@ impl<'t, 'u, 'i> DeleteDocuments<'t, 'u, 'i> {
             // content itself. It's faster to acquire a cursor to get and delete,
             // as we avoid traversing the LMDB B-Tree two times but only once.
             let key = BEU32::new(docid);
-            let mut iter = documents.range_mut(self.wtxn, &(key..=key))?;
-            if let Some((_key, obkv)) = iter.next().transpose()? {
-                if let Some(content) = obkv.get(id_field) {
+            let mut iter =
+                documents.remap_data_type::<ByteSlice>().range_mut(self.wtxn, &(key..=key))?;
+            if let Some((_key, bytes)) = iter.next().transpose()? {
+                let document = documents_decompressor.decompress(bytes)?;
+                if let Some(content) = document.as_obkv().get(id_field) {
                     let external_id = match serde_json::from_slice(content).unwrap() {
                         Value::String(string) => SmallString32::from(string.as_str()),
                         Value::Number(number) => SmallString32::from(number.to_string()),
@@ -212,7 +282,12 @@ impl<'t, 'u, 'i> DeleteDocuments<'t, 'u, 'i> {
                             return Err(UserError::InvalidDocumentId { document_id }.into())
                         }
                     };
-                    external_ids.push(external_id);
+                    // A soft deleted document may have been added back under the same
+                    // external id, in which case the external id must be kept.
+                    match self.external_documents_ids.get(external_id.as_str()) {
+                        Some(id) if id != docid => (),
+                        _otherwise => external_ids.push(external_id),
+                    }
                 }
                 // safety: we don't keep references from inside the LMDB database.
                 unsafe { iter.del_current()? };
@@ -233,6 +308,7 @@ impl<'t, 'u, 'i> DeleteDocuments<'t, 'u, 'i> {
 
         // We create the FST map of the external ids that we must delete.
         external_ids.sort_unstable();
+        external_ids.dedup();
         let external_ids_to_delete = fst::Set::from_iter(external_ids)?;
 
         // We acquire the current external documents ids map...
@@ -453,10 +529,21 @@ impl<'t, 'u, 'i> DeleteDocuments<'t, 'u, 'i> {
             facet_id_exists_docids,
             &self.to_delete_docids,
         )?;
+        remove_docids_from_facet_field_id_docids(
+            self.wtxn,
+            facet_id_is_null_docids,
+            &self.to_delete_docids,
+        )?;
+        remove_docids_from_facet_field_id_docids(
+            self.wtxn,
+            facet_id_is_empty_docids,
+            &self.to_delete_docids,
+        )?;
 
-        remove_docids_from_facet_field_id_string_docids(
+        let string_entries_deleted = remove_docids_from_facet_field_id_string_docids(
             self.wtxn,
             facet_id_string_docids,
+            field_id_docid_facet_strings,
             &self.to_delete_docids,
         )?;
 
@@ -487,12 +574,18 @@ impl<'t, 'u, 'i> DeleteDocuments<'t, 'u, 'i> {
                 &self.to_delete_docids,
                 |(_fid, docid, _value)| docid,
             )?;
+
+            // The levels must not keep groups, or levels, for the deleted facet values.
+            shrink_field_levels(
+                self.wtxn,
+                self.index,
+                field_id,
+                string_entries_deleted.contains(&field_id),
+                self.validate_ordering,
+            )?;
         }
 
-        Ok(DocumentDeletionResult {
-            deleted_documents: self.to_delete_docids.len(),
-            remaining_documents: documents_ids.len(),
-        })
+        Ok(DocumentDeletionResult { deleted_documents, remaining_documents: documents_ids.len() })
     }
 }
 
@@ -585,9 +678,15 @@ where
 fn remove_docids_from_facet_field_id_string_docids<'a, C, D>(
     wtxn: &'a mut heed::RwTxn,
     db: &heed::Database<C, D>,
+    field_id_docid_facet_strings: &Database<FieldDocIdFacetStringCodec, Str>,
     to_remove: &RoaringBitmap,
-) -> crate::Result<()> {
+) -> crate::Result<BTreeSet<FieldId>> {
     let db_name = Some(crate::index::db_name::FACET_ID_STRING_DOCIDS);
+    // The fields that lost level zero facet strings, their levels must be rebuilt.
+    let mut fields_ids = BTreeSet::new();
+    // The level zero facet strings that lost documents, their original string
+    // must be recomputed as it may come from one of the deleted documents.
+    let mut level_zero_to_update = Vec::new();
     let mut iter = db.remap_types::<ByteSlice, ByteSlice>().iter_mut(wtxn)?;
     while let Some(result) = iter.next() {
         let (key, val) = result?;
@@ -624,22 +723,67 @@ fn remove_docids_from_facet_field_id_string_docids<'a, C, D>(
                 let previous_len = docids.len();
                 docids -= to_remove;
                 if docids.is_empty() {
+                    let (field_id, _) = FacetStringLevelZeroCodec::bytes_decode(key)
+                        .ok_or_else(|| SerializationError::Decoding { db_name })?;
+                    fields_ids.insert(field_id);
                     // safety: we don't keep references from inside the LMDB database.
                     unsafe { iter.del_current()? };
                 } else if docids.len() != previous_len {
-                    let key = key.to_owned();
-                    let val = &(original_value, docids);
-                    let value_bytes = FacetStringLevelZeroValueCodec::bytes_encode(val)
-                        .ok_or_else(|| SerializationError::Encoding { db_name })?;
-
-                    // safety: we don't keep references from inside the LMDB database.
-                    unsafe { iter.put_current(&key, &value_bytes)? };
+                    level_zero_to_update.push((key.to_owned(), original_value.to_owned(), docids));
                 }
             }
         }
     }
+    drop(iter);
 
-    Ok(())
+    let db = db.remap_types::<ByteSlice, ByteSlice>();
+    for (key, original_value, docids) in level_zero_to_update {
+        let (field_id, normalized_value) = FacetStringLevelZeroCodec::bytes_decode(&key)
+            .ok_or_else(|| SerializationError::Decoding { db_name })?;
+        let original_value = smallest_original_facet_string(
+            wtxn,
+            field_id_docid_facet_strings,
+            field_id,
+            normalized_value,
+            &original_value,
+            &docids,
+        )?;
+
+        let val = &(original_value.as_str(), docids);
+        let value_bytes = FacetStringLevelZeroValueCodec::bytes_encode(val)
+            .ok_or_else(|| SerializationError::Encoding { db_name })?;
+        db.put(wtxn, &key, &value_bytes)?;
+    }
+
+    Ok(fields_ids)
+}
+
+/// Returns the lexicographically smallest original string of the given documents
+/// for this normalized facet value, knowing that `previous` was the smallest one
+/// before some documents were removed.
+fn smallest_original_facet_string(
+    rtxn: &heed::RoTxn,
+    field_id_docid_facet_strings: &Database<FieldDocIdFacetStringCodec, Str>,
+    field_id: FieldId,
+    normalized_value: &str,
+    previous: &str,
+    docids: &RoaringBitmap,
+) -> heed::Result<String> {
+    let mut smallest: Option<&str> = None;
+    for docid in docids {
+        let key = (field_id, docid, normalized_value);
+        if let Some(original) = field_id_docid_facet_strings.get(rtxn, &key)? {
+            // the previous smallest string is still used, it is still the smallest one
+            if original == previous {
+                return Ok(previous.to_string());
+            }
+            if smallest.map_or(true, |smallest| original < smallest) {
+                smallest = Some(original);
+            }
+        }
+    }
+
+    Ok(smallest.unwrap_or(previous).to_string())
 }
 
 fn remove_docids_from_facet_field_id_docids<'a, C>(
@@ -672,11 +816,12 @@ where
 mod tests {
     use big_s::S;
     use heed::RwTxn;
-    use maplit::hashset;
+    use maplit::{btreemap, hashset};
 
     use super::*;
+    use crate::documents::documents_batch_reader_from_objects;
     use crate::index::tests::TempIndex;
-    use crate::Filter;
+    use crate::{FacetDistribution, Search, SearchResult};
 
     fn delete_documents<'t>(
         wtxn: &mut RwTxn<'t, '_>,
@@ -697,6 +842,21 @@ mod tests {
         ids_to_delete
     }
 
+    fn delete_documents_with_threshold<'t>(
+        wtxn: &mut RwTxn<'t, '_>,
+        index: &'t Index,
+        external_ids: &[&str],
+        threshold: f64,
+    ) -> Vec<u32> {
+        let mut builder = DeleteDocuments::new(wtxn, index).unwrap();
+        builder.soft_deleted_threshold(threshold);
+        let ids_to_delete =
+            external_ids.iter().map(|id| builder.delete_external_id(id).unwrap()).collect();
+        builder.execute().unwrap();
+
+        ids_to_delete
+    }
+
     #[test]
     fn delete_documents_with_numbers_as_primary_key() {
         let index = TempIndex::new();
@@ -1088,4 +1248,209 @@ mod tests {
 
         wtxn.commit().unwrap();
     }
+    #[test]
+    fn delete_documents_by_filter() {
+        let index = TempIndex::new();
+
+        let mut wtxn = index.write_txn().unwrap();
+        index
+            .update_settings_using_wtxn(&mut wtxn, |settings| {
+                settings.set_primary_key(S("id"));
+                settings.set_filterable_fields(hashset! { S("price") });
+            })
+            .unwrap();
+
+        index
+            .add_documents_using_wtxn(
+                &mut wtxn,
+                documents!([
+                    { "id": 0, "name": "pen", "price": 2 },
+                    { "id": 1, "name": "notebook", "price": 8 },
+                    { "id": 2, "name": "backpack", "price": 45 },
+                    { "id": 3, "name": "pen case", "price": 12 },
+                    { "id": 4, "name": "stapler", "price": 9.5 }
+                ]),
+            )
+            .unwrap();
+
+        let filter = Filter::from_str("price < 10").unwrap().unwrap();
+        let deleted_internal_ids = filter.evaluate(&wtxn, &index).unwrap();
+        assert_eq!(deleted_internal_ids.len(), 3);
+
+        let builder = DeleteDocuments::new(&mut wtxn, &index).unwrap();
+        let deleted = builder.delete_documents_by_filter(filter).unwrap();
+        assert_eq!(deleted, 3);
+
+        wtxn.commit().unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.number_of_documents(&rtxn).unwrap(), 2);
+
+        // The search must not return the deleted documents.
+        let results = index.search(&rtxn).query("pen").execute().unwrap();
+        assert_eq!(results.documents_ids.len(), 1);
+        let results = index.search(&rtxn).execute().unwrap();
+        for id in results.documents_ids.iter() {
+            assert!(
+                !deleted_internal_ids.contains(*id),
+                "The document {} was supposed to be deleted",
+                id
+            );
+        }
+
+        // The facet databases must not reference the deleted documents anymore,
+        // the documents are either removed or marked as soft deleted.
+        let soft_deleted = index.soft_deleted_documents_ids(&rtxn).unwrap();
+        let price_fid = index.fields_ids_map(&rtxn).unwrap().id("price").unwrap();
+        for result in index.facet_id_f64_docids.iter(&rtxn).unwrap() {
+            let ((fid, _level, _left, _right), docids) = result.unwrap();
+            assert_eq!(fid, price_fid);
+            assert!((docids - &soft_deleted).is_disjoint(&deleted_internal_ids));
+        }
+        let docids = index.number_faceted_documents_ids(&rtxn, price_fid).unwrap();
+        assert!((docids - &soft_deleted).is_disjoint(&deleted_internal_ids));
+
+        let filter = Filter::from_str("price < 10").unwrap().unwrap();
+        assert!(filter.evaluate(&rtxn, &index).unwrap().is_empty());
+    }
+
+    #[test]
+    fn delete_documents_by_filter_on_non_filterable_field() {
+        let index = TempIndex::new();
+
+        index
+            .add_documents(documents!([
+                { "id": 0, "price": 2 },
+                { "id": 1, "price": 8 },
+            ]))
+            .unwrap();
+
+        let mut wtxn = index.write_txn().unwrap();
+        let filter = Filter::from_str("price < 10").unwrap().unwrap();
+        let builder = DeleteDocuments::new(&mut wtxn, &index).unwrap();
+        let error = builder.delete_documents_by_filter(filter).unwrap_err();
+        assert!(matches!(error, crate::Error::UserError(UserError::InvalidFilter(_))));
+        wtxn.commit().unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.number_of_documents(&rtxn).unwrap(), 2);
+    }
+
+    #[test]
+    fn soft_deleted_documents_are_ignored_by_search_and_facets() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|settings| {
+                settings.set_filterable_fields(hashset! { S("color") });
+            })
+            .unwrap();
+        index
+            .add_documents(documents!([
+                { "id": 0, "name": "kevin", "color": "blue" },
+                { "id": 1, "name": "kevina", "color": "blue" },
+                { "id": 2, "name": "benoit", "color": "red" }
+            ]))
+            .unwrap();
+
+        let mut wtxn = index.write_txn().unwrap();
+        let mut builder = DeleteDocuments::new(&mut wtxn, &index).unwrap();
+        builder.soft_deleted_threshold(1.0);
+        let docid = builder.delete_external_id("1").unwrap();
+        builder.execute().unwrap();
+        wtxn.commit().unwrap();
+
+        // The document is only marked as deleted and is still present in the databases.
+        let rtxn = index.read_txn().unwrap();
+        let soft_deleted = index.soft_deleted_documents_ids(&rtxn).unwrap();
+        assert_eq!(soft_deleted.iter().collect::<Vec<_>>(), vec![docid]);
+        assert!(index.word_docids.get(&rtxn, "kevina").unwrap().unwrap().contains(docid));
+
+        let mut search = Search::new(&rtxn, &index);
+        search.query("kevina");
+        let SearchResult { documents_ids, .. } = search.execute().unwrap();
+        assert!(!documents_ids.is_empty());
+        assert!(!documents_ids.contains(&docid));
+
+        let expected = btreemap! { S("color") => btreemap! { S("blue") => 1, S("red") => 1 } };
+        let distribution = FacetDistribution::new(&rtxn, &index).execute().unwrap();
+        assert_eq!(distribution, expected);
+
+        let candidates = index.documents_ids(&rtxn).unwrap() | soft_deleted;
+        let distribution =
+            FacetDistribution::new(&rtxn, &index).candidates(candidates).execute().unwrap();
+        assert_eq!(distribution, expected);
+    }
+
+    #[test]
+    fn soft_deleted_documents_are_purged_above_threshold() {
+        let index = TempIndex::new();
+        let documents = (0..10).map(|id| {
+            serde_json::json!({ "id": id, "name": "kevin" }).as_object().unwrap().clone()
+        });
+        index.add_documents(documents_batch_reader_from_objects(documents)).unwrap();
+
+        // By default the few soft deleted documents use too little space to be purged.
+        let mut wtxn = index.write_txn().unwrap();
+        let deleted = delete_documents(&mut wtxn, &index, &["8", "9"]);
+        let soft_deleted = index.soft_deleted_documents_ids(&wtxn).unwrap();
+        assert_eq!(soft_deleted.iter().collect::<Vec<_>>(), deleted);
+        assert_eq!(index.word_docids.get(&wtxn, "kevin").unwrap().unwrap().len(), 10);
+        wtxn.abort().unwrap();
+
+        // One document out of ten does not exceed a threshold of 10%.
+        let mut wtxn = index.write_txn().unwrap();
+        let deleted = delete_documents_with_threshold(&mut wtxn, &index, &["0"], 0.1);
+        let soft_deleted = index.soft_deleted_documents_ids(&wtxn).unwrap();
+        assert_eq!(soft_deleted.iter().collect::<Vec<_>>(), deleted);
+        assert_eq!(index.word_docids.get(&wtxn, "kevin").unwrap().unwrap().len(), 10);
+
+        // But two documents do, all the soft deleted documents are physically removed.
+        delete_documents_with_threshold(&mut wtxn, &index, &["1"], 0.1);
+        assert!(index.soft_deleted_documents_ids(&wtxn).unwrap().is_empty());
+        assert_eq!(index.word_docids.get(&wtxn, "kevin").unwrap().unwrap().len(), 8);
+        assert_eq!(index.number_of_documents(&wtxn).unwrap(), 8);
+        wtxn.commit().unwrap();
+    }
+
+    #[test]
+    fn purge_keeps_documents_added_back_after_a_soft_deletion() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 0, "name": "kevin" },
+                { "id": 1, "name": "kevina" }
+            ]))
+            .unwrap();
+
+        let mut wtxn = index.write_txn().unwrap();
+        let mut builder = DeleteDocuments::new(&mut wtxn, &index).unwrap();
+        builder.soft_deleted_threshold(1.0);
+        let old_docid = builder.delete_external_id("1").unwrap();
+        builder.execute().unwrap();
+        wtxn.commit().unwrap();
+
+        // The document is added back under the same external id, with another internal id.
+        index.add_documents(documents!([{ "id": 1, "name": "benoit" }])).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let new_docid = index.external_documents_ids(&rtxn).unwrap().get("1").unwrap();
+        assert_ne!(new_docid, old_docid);
+        assert!(index.soft_deleted_documents_ids(&rtxn).unwrap().contains(old_docid));
+        drop(rtxn);
+
+        let mut wtxn = index.write_txn().unwrap();
+        let result = DeleteDocuments::new(&mut wtxn, &index).unwrap().purge().unwrap();
+        assert_eq!(result, DocumentDeletionResult { deleted_documents: 0, remaining_documents: 2 });
+        wtxn.commit().unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert!(index.soft_deleted_documents_ids(&rtxn).unwrap().is_empty());
+        assert!(index.word_docids.get(&rtxn, "kevina").unwrap().is_none());
+        assert_eq!(index.external_documents_ids(&rtxn).unwrap().get("1"), Some(new_docid));
+
+        let mut search = Search::new(&rtxn, &index);
+        search.query("benoit");
+        let SearchResult { documents_ids, .. } = search.execute().unwrap();
+        assert_eq!(documents_ids, vec![new_docid]);
+    }
 }
@@ -51,6 +51,30 @@ You can read more about this structure (for strings) in `[crate::search::facet::
 To create the levels, we use a recursive algorithm which makes sure that we only need to iterate
 over the elements of level 0 once. It is implemented by [`recursive_compute_levels`].
 
+## Incremental updates
+
+When an update only modifies a few level 0 entries of a faceted field, the number levels are
+updated in place instead of being rebuilt. Every modified value is inserted into the group of
+each level that must contain it, extending the bounds of that group when needed. The groups
+containing the modified values are then re-balanced locally: a group with more than
+`max_group_size` elements is split, and a group with less than half `level_group_size`
+elements is merged with one of its siblings under the same parent.
+
+The `level_group_size`, `max_group_size` and `min_level_size` parameters are settings of the
+index, changing them rebuilds the levels of every faceted field from their level 0. When it is
+not set, `max_group_size` is twice `level_group_size`.
+
+The number levels are rebuilt from scratch when the ratio of modified entries exceeds
+`rebuild_ratio` or when the number of levels required by the new size of level 0 changes.
+The string levels are always rebuilt because their bounds are indices inside level 0, which
+are all shifted by the insertion of a new string.
+
+When documents are deleted, the level 0 entries and the groups left without documents are
+deleted. The bounds of the remaining number groups are then reduced to the ones of their
+elements, the undersized groups are merged and the levels that are no longer required by
+the size of level 0 are dropped. The string levels of a field that lost level 0 entries are
+rebuilt.
+
 ## Encoding
 
 ### Numbers
@@ -74,6 +98,9 @@ At level 0, the key is given by `FacetStringLevelZeroCodec`. It consists of:
 2. The height of the level     : u8  <-- always == 0
 3. The normalised string value : &str
 
+The normalised values longer than `MAX_FACET_STRING_KEY_LENGTH` are truncated and suffixed
+by the MD5 digest of the whole value, see `facet_string_key`.
+
 And the value is given by `FacetStringLevelZeroValueCodec`. It consists of:
 1. The original string
 2. A serialised `RoaringBitmap`
@@ -126,31 +153,45 @@ lines are its values.
 use std::cmp;
 use std::fs::File;
 use std::num::{NonZeroU8, NonZeroUsize};
+use std::ops::Bound::{Excluded, Included};
 use std::ops::RangeFrom;
 
 use grenad::{CompressionType, Reader, Writer};
-use heed::types::{ByteSlice, DecodeIgnore};
+use heed::types::{ByteSlice, DecodeIgnore, Unit};
 use heed::{BytesDecode, BytesEncode, Error};
 use log::debug;
 use roaring::RoaringBitmap;
 use time::OffsetDateTime;
 
-use crate::error::InternalError;
+use crate::error::UserError;
 use crate::heed_codec::facet::{
     FacetLevelValueF64Codec, FacetLevelValueU32Codec, FacetStringLevelZeroCodec,
     FacetStringLevelZeroValueCodec, FacetStringZeroBoundsValueCodec,
 };
 use crate::heed_codec::CboRoaringBitmapCodec;
-use crate::update::index_documents::{create_writer, write_into_lmdb_database, writer_into_reader};
+use crate::index::db_name;
+use crate::update::index_documents::{
+    create_writer, write_into_lmdb_database, writer_into_reader, CursorClonableMmap,
+    KeysOrderValidator, MergeStrategy,
+};
+use crate::update::UpdateIndexingStep;
 use crate::{FieldId, Index, Result};
 
+/// The default ratio of facet numbers modified by an update, over all the facet numbers
+/// of a field, above which the facet number levels of this field are rebuilt from scratch.
+pub const DEFAULT_FACET_REBUILD_RATIO: f64 = 0.1;
+
 pub struct Facets<'t, 'u, 'i> {
     wtxn: &'t mut heed::RwTxn<'i, 'u>,
     index: &'i Index,
     pub(crate) chunk_compression_type: CompressionType,
     pub(crate) chunk_compression_level: Option<u32>,
-    level_group_size: NonZeroUsize,
-    min_level_size: NonZeroUsize,
+    pub(crate) validate_ordering: bool,
+    level_group_size: Option<NonZeroUsize>,
+    max_group_size: Option<NonZeroUsize>,
+    min_level_size: Option<NonZeroUsize>,
+    rebuild_ratio: f64,
+    number_changes: Option<grenad::Reader<CursorClonableMmap>>,
 }
 
 impl<'t, 'u, 'i> Facets<'t, 'u, 'i> {
@@ -160,34 +201,110 @@ impl<'t, 'u, 'i> Facets<'t, 'u, 'i> {
             index,
             chunk_compression_type: CompressionType::None,
             chunk_compression_level: None,
-            level_group_size: NonZeroUsize::new(4).unwrap(),
-            min_level_size: NonZeroUsize::new(5).unwrap(),
+            validate_ordering: cfg!(debug_assertions),
+            level_group_size: None,
+            max_group_size: None,
+            min_level_size: None,
+            rebuild_ratio: DEFAULT_FACET_REBUILD_RATIO,
+            number_changes: None,
         }
     }
 
     /// The number of elements from the level below that are represented by a single element in the level above
     ///
-    /// This setting is always greater than or equal to 2.
+    /// This setting is always greater than or equal to 2, it defaults to the one of the index.
     pub fn level_group_size(&mut self, value: NonZeroUsize) -> &mut Self {
-        self.level_group_size = NonZeroUsize::new(cmp::max(value.get(), 2)).unwrap();
+        self.level_group_size = Some(NonZeroUsize::new(cmp::max(value.get(), 2)).unwrap());
+        self
+    }
+
+    /// The number of elements above which a group is split when the levels are updated in place.
+    ///
+    /// This setting defaults to the one of the index, or to twice the level group size
+    /// when the index doesn't set it.
+    pub fn max_group_size(&mut self, value: NonZeroUsize) -> &mut Self {
+        self.max_group_size = Some(value);
         self
     }
 
     /// The minimum number of elements that a level is allowed to have.
+    ///
+    /// This setting defaults to the one of the index.
     pub fn min_level_size(&mut self, value: NonZeroUsize) -> &mut Self {
-        self.min_level_size = value;
+        self.min_level_size = Some(value);
+        self
+    }
+
+    /// The ratio of modified facet numbers, over all the facet numbers of a field, above which
+    /// the number levels of this field are rebuilt from scratch instead of being updated in place.
+    pub fn rebuild_ratio(&mut self, value: f64) -> &mut Self {
+        self.rebuild_ratio = value;
+        self
+    }
+
+    /// The level 0 facet number entries that were merged into the database by the update.
+    ///
+    /// When they are not given, the number levels of every faceted field are rebuilt.
+    pub(crate) fn number_changes(
+        &mut self,
+        changes: grenad::Reader<CursorClonableMmap>,
+    ) -> &mut Self {
+        self.number_changes = Some(changes);
         self
     }
 
     #[logging_timer::time("Facets::{}")]
-    pub fn execute(self) -> Result<()> {
+    pub fn execute<F, FA>(self, progress_callback: F, should_abort: FA) -> Result<()>
+    where
+        F: Fn(UpdateIndexingStep) + Sync,
+        FA: Fn() -> bool + Sync,
+    {
         self.index.set_updated_at(self.wtxn, &OffsetDateTime::now_utc())?;
         // We get the faceted fields to be able to create the facet levels.
         let faceted_fields = self.index.faceted_fields_ids(self.wtxn)?;
+        let no_distribution_fields = self.index.no_distribution_fields_ids(self.wtxn)?;
+        let total_fields = faceted_fields.len();
+
+        // The parameters given to the builder take precedence over the ones of the index.
+        let level_group_size = match self.level_group_size {
+            Some(value) => value,
+            None => {
+                let value = self.index.facet_level_group_size(self.wtxn)?;
+                NonZeroUsize::new(cmp::max(value, 2)).unwrap()
+            }
+        };
+        let max_group_size = match self.max_group_size {
+            Some(value) => cmp::max(value, level_group_size),
+            None => match self.index.custom_facet_max_group_size(self.wtxn)? {
+                Some(value) => NonZeroUsize::new(cmp::max(value, level_group_size.get())).unwrap(),
+                None => NonZeroUsize::new(2 * level_group_size.get()).unwrap(),
+            },
+        };
+        let default_min_level_size = match self.min_level_size {
+            Some(value) => value,
+            None => {
+                let value = self.index.facet_min_level_size(self.wtxn)?;
+                NonZeroUsize::new(cmp::max(value, 1)).unwrap()
+            }
+        };
 
         debug!("Computing and writing the facet values levels docids into LMDB on disk...");
 
-        for field_id in faceted_fields {
+        progress_callback(UpdateIndexingStep::ComputeFacetLevels { fields_seen: 0, total_fields });
+
+        for (i, field_id) in faceted_fields.into_iter().enumerate() {
+            if should_abort() {
+                return Err(UserError::IndexingAborted.into());
+            }
+
+            // Only the level 0 is kept for the fields without facet distribution,
+            // a minimum level size that can't be reached prevents any level from being built.
+            let min_level_size = if no_distribution_fields.contains(&field_id) {
+                NonZeroUsize::new(usize::MAX).unwrap()
+            } else {
+                default_min_level_size
+            };
+
             // Clear the facet string levels.
             clear_field_string_levels(
                 self.wtxn,
@@ -200,8 +317,8 @@ impl<'t, 'u, 'i> Facets<'t, 'u, 'i> {
                 self.index.facet_id_string_docids,
                 self.chunk_compression_type,
                 self.chunk_compression_level,
-                self.level_group_size,
-                self.min_level_size,
+                level_group_size,
+                min_level_size,
                 field_id,
             )?;
 
@@ -215,41 +332,97 @@ impl<'t, 'u, 'i> Facets<'t, 'u, 'i> {
                     self.wtxn,
                     *self.index.facet_id_string_docids.as_polymorph(),
                     facet_strings_level,
-                    |_, _| {
-                        Err(InternalError::IndexingMergingKeys { process: "facet string levels" })?
-                    },
+                    MergeStrategy::REFUSE_MERGING,
+                    KeysOrderValidator::new(
+                        db_name::FACET_ID_STRING_DOCIDS,
+                        self.validate_ordering,
+                    ),
                 )?;
             }
 
-            // Clear the facet number levels.
-            clear_field_number_levels(self.wtxn, self.index.facet_id_f64_docids, field_id)?;
-
-            let (facet_number_levels, number_documents_ids) = compute_facet_number_levels(
-                self.wtxn,
-                self.index.facet_id_f64_docids,
-                self.chunk_compression_type,
-                self.chunk_compression_level,
-                self.level_group_size,
-                self.min_level_size,
-                field_id,
-            )?;
-
-            self.index.put_number_faceted_documents_ids(
-                self.wtxn,
-                field_id,
-                &number_documents_ids,
-            )?;
+            // Update the facet number levels in place when only a few of their level 0 entries
+            // were modified, otherwise clear and rebuild them from level 0.
+            let db = self.index.facet_id_f64_docids;
+            let incremental = match &self.number_changes {
+                Some(changes) => {
+                    let level_0_size = number_level_0_size(self.wtxn, db, field_id)?;
+                    let height = number_levels_height(self.wtxn, db, field_id)?;
+                    let expected_height =
+                        number_of_levels(level_0_size, level_group_size, min_level_size);
+                    if height == 0 || height != expected_height {
+                        None
+                    } else {
+                        let limit = (level_0_size as f64 * self.rebuild_ratio) as usize;
+                        field_number_changes(changes, field_id, limit)?
+                            .map(|field_changes| (height, field_changes))
+                    }
+                }
+                None => None,
+            };
 
-            for facet_number_level in facet_number_levels {
-                write_into_lmdb_database(
-                    self.wtxn,
-                    *self.index.facet_id_f64_docids.as_polymorph(),
-                    facet_number_level,
-                    |_, _| {
-                        Err(InternalError::IndexingMergingKeys { process: "facet number levels" })?
-                    },
-                )?;
+            match incremental {
+                Some((height, changes)) => {
+                    insert_into_number_levels(self.wtxn, db, field_id, height, &changes)?;
+                    balance_number_levels(
+                        self.wtxn,
+                        db,
+                        field_id,
+                        height,
+                        level_group_size,
+                        max_group_size,
+                        &changes,
+                    )?;
+
+                    let mut number_documents_ids =
+                        self.index.number_faceted_documents_ids(self.wtxn, field_id)?;
+                    for (_, docids) in &changes {
+                        number_documents_ids |= docids;
+                    }
+                    self.index.put_number_faceted_documents_ids(
+                        self.wtxn,
+                        field_id,
+                        &number_documents_ids,
+                    )?;
+                }
+                None => {
+                    // Clear the facet number levels.
+                    clear_field_number_levels(self.wtxn, self.index.facet_id_f64_docids, field_id)?;
+
+                    let (facet_number_levels, number_documents_ids) = compute_facet_number_levels(
+                        self.wtxn,
+                        self.index.facet_id_f64_docids,
+                        self.chunk_compression_type,
+                        self.chunk_compression_level,
+                        level_group_size,
+                        min_level_size,
+                        field_id,
+                    )?;
+
+                    self.index.put_number_faceted_documents_ids(
+                        self.wtxn,
+                        field_id,
+                        &number_documents_ids,
+                    )?;
+
+                    for facet_number_level in facet_number_levels {
+                        write_into_lmdb_database(
+                            self.wtxn,
+                            *self.index.facet_id_f64_docids.as_polymorph(),
+                            facet_number_level,
+                            MergeStrategy::REFUSE_MERGING,
+                            KeysOrderValidator::new(
+                                db_name::FACET_ID_F64_DOCIDS,
+                                self.validate_ordering,
+                            ),
+                        )?;
+                    }
+                }
             }
+
+            progress_callback(UpdateIndexingStep::ComputeFacetLevels {
+                fields_seen: i + 1,
+                total_fields,
+            });
         }
 
         Ok(())
@@ -300,16 +473,14 @@ fn compute_facet_number_levels<'t>(
                 &(level_0_start..),
                 first_level_size,
                 level_group_size,
-                &mut |bitmaps, _, _| {
-                    for bitmap in bitmaps {
-                        number_document_ids |= bitmap;
-                    }
+                &mut |bitmap, _, _| {
+                    number_document_ids |= bitmap;
                     Ok(())
                 },
                 &|_i, (_field_id, _level, left, _right)| *left,
                 &|bitmap| bitmap,
                 &|writer, level, left, right, docids| {
-                    write_number_entry(writer, field_id, level.get(), left, right, &docids)?;
+                    write_number_entry(writer, field_id, level.get(), left, right, docids)?;
                     Ok(())
                 },
             )?;
@@ -373,10 +544,8 @@ fn compute_facet_strings_levels<'t>(
             &(level_0_start..),
             first_level_size,
             level_group_size,
-            &mut |bitmaps, _, _| {
-                for bitmap in bitmaps {
-                    strings_document_ids |= bitmap;
-                }
+            &mut |bitmap, _, _| {
+                strings_document_ids |= bitmap;
                 Ok(())
             },
             &|i, (_field_id, value)| (i as u32, *value),
@@ -420,7 +589,7 @@ will contain the new levels
 single element of the new level
 * `computed_group_bitmap` : a callback that is called whenever at most `level_group_size` elements
 from the level below were read/created. Its arguments are:
-    0. the list of bitmaps from each read/created element of the level below
+    0. the union of the bitmaps of the read/created elements of the level below
     1. the start bound corresponding to the first element
     2. the end bound corresponding to the last element
 * `bound_from_db_key` : finds the `Bound` from a key in the database
@@ -446,10 +615,10 @@ fn recursive_compute_levels<'t, KeyCodec, ValueCodec, Bound>(
     level_0_range: &'t RangeFrom<<KeyCodec as BytesDecode<'t>>::DItem>,
     level_0_size: usize,
     level_group_size: NonZeroUsize,
-    computed_group_bitmap: &mut dyn FnMut(&[RoaringBitmap], Bound, Bound) -> Result<()>,
+    computed_group_bitmap: &mut dyn FnMut(&RoaringBitmap, Bound, Bound) -> Result<()>,
     bound_from_db_key: &dyn for<'a> Fn(usize, &'a <KeyCodec as BytesDecode<'t>>::DItem) -> Bound,
     bitmap_from_db_value: &dyn Fn(<ValueCodec as BytesDecode<'t>>::DItem) -> RoaringBitmap,
-    write_entry: &dyn Fn(&mut Writer<File>, NonZeroU8, Bound, Bound, RoaringBitmap) -> Result<()>,
+    write_entry: &dyn Fn(&mut Writer<File>, NonZeroU8, Bound, Bound, &RoaringBitmap) -> Result<()>,
 ) -> Result<Vec<Reader<File>>>
 where
     KeyCodec: for<'a> BytesEncode<'a>
@@ -465,49 +634,50 @@ where
 
         // we read the elements one by one and
         // 1. keep track of the start and end bounds
-        // 2. fill the `bitmaps` vector to give it to level 1 once `level_group_size` elements were read
-        let mut bitmaps = vec![];
+        // 2. union their docids to give them to level 1 once `level_group_size` elements were read
+        let mut group_docids = RoaringBitmap::new();
+        let mut group_len = 0;
 
         let mut start_bound = bound_from_db_key(0, &level_0_start);
         let mut end_bound = bound_from_db_key(0, &level_0_start);
-        let mut first_iteration_for_new_group = true;
         for (i, db_result_item) in db.range(rtxn, level_0_range)?.take(level_0_size).enumerate() {
             let (key, value) = db_result_item?;
 
             let bound = bound_from_db_key(i, &key);
-            let docids = bitmap_from_db_value(value);
+            group_docids |= bitmap_from_db_value(value);
 
-            if first_iteration_for_new_group {
+            if group_len == 0 {
                 start_bound = bound;
-                first_iteration_for_new_group = false;
             }
             end_bound = bound;
-            bitmaps.push(docids);
+            group_len += 1;
 
-            if bitmaps.len() == level_group_size.get() {
-                computed_group_bitmap(&bitmaps, start_bound, end_bound)?;
-                first_iteration_for_new_group = true;
-                bitmaps.clear();
+            if group_len == level_group_size.get() {
+                computed_group_bitmap(&group_docids, start_bound, end_bound)?;
+                group_docids.clear();
+                group_len = 0;
             }
         }
-        // don't forget to give the leftover bitmaps as well
-        if !bitmaps.is_empty() {
-            computed_group_bitmap(&bitmaps, start_bound, end_bound)?;
-            bitmaps.clear();
+        // don't forget to give the leftover elements as well
+        if group_len != 0 {
+            computed_group_bitmap(&group_docids, start_bound, end_bound)?;
         }
         // level 0 is already stored in the DB
         return Ok(vec![]);
     } else {
         // level >= 1
-        // we compute each element of this level based on the elements of the level below it
-        // once we have computed `level_group_size` elements, we give the start and end bounds
-        // of those elements, and their bitmaps, to the level above
+        // every group completed by the level below is an element of this level, it is written
+        // as soon as it is received. Only the union of the docids and the bounds of the current
+        // group of this level are kept, to give them to the level above once `level_group_size`
+        // elements were written.
 
         let mut cur_writer =
             create_writer(compression_type, compression_level, tempfile::tempfile()?);
 
-        let mut range_for_bitmaps = vec![];
-        let mut bitmaps = vec![];
+        let mut group_docids = RoaringBitmap::new();
+        let mut group_len = 0;
+        let mut group_start_bound = None;
+        let mut group_end_bound = None;
 
         // compute the levels below
         // in the callback, we fill `cur_writer` with the correct elements for this level
@@ -521,29 +691,26 @@ where
             level_0_range,
             level_0_size,
             level_group_size,
-            &mut |sub_bitmaps: &[RoaringBitmap], start_range, end_range| {
-                let mut combined_bitmap = RoaringBitmap::default();
-                for bitmap in sub_bitmaps {
-                    combined_bitmap |= bitmap;
-                }
-                range_for_bitmaps.push((start_range, end_range));
-
-                bitmaps.push(combined_bitmap);
-                if bitmaps.len() == level_group_size.get() {
-                    let start_bound = range_for_bitmaps.first().unwrap().0;
-                    let end_bound = range_for_bitmaps.last().unwrap().1;
-                    computed_group_bitmap(&bitmaps, start_bound, end_bound)?;
-                    for (bitmap, (start_bound, end_bound)) in
-                        bitmaps.drain(..).zip(range_for_bitmaps.drain(..))
-                    {
-                        write_entry(
-                            &mut cur_writer,
-                            NonZeroU8::new(level).unwrap(),
-                            start_bound,
-                            end_bound,
-                            bitmap,
-                        )?;
-                    }
+            &mut |docids: &RoaringBitmap, start_bound, end_bound| {
+                write_entry(
+                    &mut cur_writer,
+                    NonZeroU8::new(level).unwrap(),
+                    start_bound,
+                    end_bound,
+                    docids,
+                )?;
+
+                group_docids |= docids;
+                group_start_bound.get_or_insert(start_bound);
+                group_end_bound = Some(end_bound);
+                group_len += 1;
+
+                if group_len == level_group_size.get() {
+                    let (start_bound, end_bound) =
+                        (group_start_bound.take().unwrap(), group_end_bound.take().unwrap());
+                    computed_group_bitmap(&group_docids, start_bound, end_bound)?;
+                    group_docids.clear();
+                    group_len = 0;
                 }
                 Ok(())
             },
@@ -551,14 +718,9 @@ where
             bitmap_from_db_value,
             write_entry,
         )?;
-        // don't forget to insert the leftover elements into the writer as well
-        if !bitmaps.is_empty() {
-            let start_range = range_for_bitmaps.first().unwrap().0;
-            let end_range = range_for_bitmaps.last().unwrap().1;
-            computed_group_bitmap(&bitmaps, start_range, end_range)?;
-            for (bitmap, (left, right)) in bitmaps.drain(..).zip(range_for_bitmaps.drain(..)) {
-                write_entry(&mut cur_writer, NonZeroU8::new(level).unwrap(), left, right, bitmap)?;
-            }
+        // don't forget to give the leftover elements to the level above as well
+        if let (Some(start_bound), Some(end_bound)) = (group_start_bound, group_end_bound) {
+            computed_group_bitmap(&group_docids, start_bound, end_bound)?;
         }
 
         sub_writers.push(writer_into_reader(cur_writer)?);
@@ -588,6 +750,412 @@ fn clear_field_string_levels<'t>(
     db.remap_key_type::<FacetLevelValueU32Codec>().delete_range(wtxn, &range).map(drop)
 }
 
+/// Returns the number of levels above level 0 that are built for a level 0 of the given size.
+fn number_of_levels(
+    level_0_size: usize,
+    level_group_size: NonZeroUsize,
+    min_level_size: NonZeroUsize,
+) -> u8 {
+    (1u32..)
+        .take_while(|l| level_0_size / level_group_size.get().pow(*l) >= min_level_size.get())
+        .count() as u8
+}
+
+/// Returns the number of level 0 entries of the given field.
+fn number_level_0_size(
+    rtxn: &heed::RoTxn,
+    db: heed::Database<FacetLevelValueF64Codec, CboRoaringBitmapCodec>,
+    field_id: FieldId,
+) -> Result<usize> {
+    let [first, second] = field_id.to_be_bytes();
+    let count = db
+        .remap_types::<ByteSlice, DecodeIgnore>()
+        .prefix_iter(rtxn, &[first, second, 0])?
+        .fold(Ok(0usize), |count, result| result.and(count).map(|c| c + 1))?;
+    Ok(count)
+}
+
+/// Returns the height of the highest number level of the given field, `0` if it has no levels.
+fn number_levels_height(
+    rtxn: &heed::RoTxn,
+    db: heed::Database<FacetLevelValueF64Codec, CboRoaringBitmapCodec>,
+    field_id: FieldId,
+) -> Result<u8> {
+    let mut iter = db
+        .remap_types::<ByteSlice, DecodeIgnore>()
+        .rev_prefix_iter(rtxn, &field_id.to_be_bytes())?;
+    match iter.next() {
+        // The height of the level is stored right after the field id.
+        Some(result) => Ok(result?.0[2]),
+        None => Ok(0),
+    }
+}
+
+/// Returns the level 0 entries of the given field that were modified by the update,
+/// or `None` if there are more than `limit` of them.
+fn field_number_changes(
+    changes: &grenad::Reader<CursorClonableMmap>,
+    field_id: FieldId,
+    limit: usize,
+) -> Result<Option<Vec<(f64, RoaringBitmap)>>> {
+    let prefix = field_id.to_be_bytes();
+    let mut field_changes = Vec::new();
+    let mut cursor = changes.clone().into_cursor()?;
+    let mut entry = cursor.move_on_key_greater_than_or_equal_to(&prefix)?;
+    while let Some((key, value)) = entry {
+        if !key.starts_with(&prefix) {
+            break;
+        }
+        if field_changes.len() == limit {
+            return Ok(None);
+        }
+        let (_, _, number, _) =
+            FacetLevelValueF64Codec::bytes_decode(key).ok_or(Error::Decoding)?;
+        field_changes.push((number, CboRoaringBitmapCodec::deserialize_from(value)?));
+        entry = cursor.move_on_next()?;
+    }
+    Ok(Some(field_changes))
+}
+
+/// Returns the group of the given level that must contain the value, that is the last group
+/// starting before the value or, if there is none, the first group of the level.
+fn find_number_group(
+    rtxn: &heed::RoTxn,
+    db: heed::Database<FacetLevelValueF64Codec, CboRoaringBitmapCodec>,
+    field_id: FieldId,
+    level: u8,
+    value: f64,
+) -> Result<Option<((f64, f64), RoaringBitmap)>> {
+    let before = (field_id, level, f64::MIN, f64::MIN)..=(field_id, level, value, f64::MAX);
+    let group = match db.rev_range(rtxn, &before)?.next() {
+        Some(result) => Some(result?),
+        None => {
+            let after = (field_id, level, value, f64::MIN)..=(field_id, level, f64::MAX, f64::MAX);
+            db.range(rtxn, &after)?.next().transpose()?
+        }
+    };
+    Ok(group.map(|((_, _, left, right), docids)| ((left, right), docids)))
+}
+
+/// Returns the bounds of a group adjacent to the given one, at the same level and under
+/// the same parent group, preferring the group that follows it.
+fn find_number_sibling(
+    rtxn: &heed::RoTxn,
+    db: heed::Database<FacetLevelValueF64Codec, CboRoaringBitmapCodec>,
+    field_id: FieldId,
+    level: u8,
+    height: u8,
+    (left, right): (f64, f64),
+) -> Result<Option<(f64, f64)>> {
+    let (parent_left, parent_right) = if level == height {
+        (f64::MIN, f64::MAX)
+    } else {
+        match find_number_group(rtxn, db, field_id, level + 1, left)? {
+            Some((bounds, _)) => bounds,
+            None => (f64::MIN, f64::MAX),
+        }
+    };
+
+    let db = db.lazily_decode_data();
+    let after = (
+        Excluded((field_id, level, left, right)),
+        Included((field_id, level, parent_right, f64::MAX)),
+    );
+    if let Some(result) = db.range(rtxn, &after)?.next() {
+        let ((_, _, left, right), _) = result?;
+        return Ok(Some((left, right)));
+    }
+
+    let before = (
+        Included((field_id, level, parent_left, f64::MIN)),
+        Excluded((field_id, level, left, right)),
+    );
+    match db.rev_range(rtxn, &before)?.next() {
+        Some(result) => {
+            let ((_, _, left, right), _) = result?;
+            Ok(Some((left, right)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Returns the elements of the level below the given level that are inside the given bounds.
+fn number_group_children(
+    rtxn: &heed::RoTxn,
+    db: heed::Database<FacetLevelValueF64Codec, CboRoaringBitmapCodec>,
+    field_id: FieldId,
+    level: u8,
+    (left, right): (f64, f64),
+) -> Result<Vec<(f64, f64, RoaringBitmap)>> {
+    let range = (field_id, level - 1, left, f64::MIN)..=(field_id, level - 1, right, f64::MAX);
+    let mut children = Vec::new();
+    for result in db.range(rtxn, &range)? {
+        let ((_, _, left, right), docids) = result?;
+        children.push((left, right, docids));
+    }
+    Ok(children)
+}
+
+/// Writes a group representing the given elements of the level below and returns its bounds.
+fn put_number_group(
+    wtxn: &mut heed::RwTxn,
+    db: heed::Database<FacetLevelValueF64Codec, CboRoaringBitmapCodec>,
+    field_id: FieldId,
+    level: u8,
+    children: &[(f64, f64, RoaringBitmap)],
+) -> Result<(f64, f64)> {
+    let left = children.first().map_or(f64::MIN, |(left, _, _)| *left);
+    let right = children.last().map_or(f64::MAX, |(_, right, _)| *right);
+    let docids = children.iter().fold(RoaringBitmap::new(), |acc, (_, _, docids)| acc | docids);
+    db.put(wtxn, &(field_id, level, left, right), &docids)?;
+    Ok((left, right))
+}
+
+/// Adds the modified level 0 entries to the groups that must contain them in every level,
+/// extending the bounds of these groups when the entries are outside of them.
+fn insert_into_number_levels(
+    wtxn: &mut heed::RwTxn,
+    db: heed::Database<FacetLevelValueF64Codec, CboRoaringBitmapCodec>,
+    field_id: FieldId,
+    height: u8,
+    changes: &[(f64, RoaringBitmap)],
+) -> Result<()> {
+    for (value, docids) in changes {
+        for level in 1..=height {
+            match find_number_group(wtxn, db, field_id, level, *value)? {
+                Some(((left, right), group_docids)) => {
+                    let bounds = (left.min(*value), right.max(*value));
+                    if bounds != (left, right) {
+                        db.delete(wtxn, &(field_id, level, left, right))?;
+                    }
+                    db.put(wtxn, &(field_id, level, bounds.0, bounds.1), &(group_docids | docids))?;
+                }
+                None => db.put(wtxn, &(field_id, level, *value, *value), docids)?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Re-balances the groups containing the modified level 0 entries, from the lowest level
+/// to the highest one, see [`balance_number_group`].
+fn balance_number_levels(
+    wtxn: &mut heed::RwTxn,
+    db: heed::Database<FacetLevelValueF64Codec, CboRoaringBitmapCodec>,
+    field_id: FieldId,
+    height: u8,
+    level_group_size: NonZeroUsize,
+    max_group_size: NonZeroUsize,
+    changes: &[(f64, RoaringBitmap)],
+) -> Result<()> {
+    for level in 1..=height {
+        let mut previous_bounds = None;
+        for (value, _) in changes {
+            let bounds = match find_number_group(wtxn, db, field_id, level, *value)? {
+                Some((bounds, _)) => bounds,
+                None => continue,
+            };
+            // The changes are sorted, consecutive values often fall into the same group.
+            if previous_bounds == Some(bounds) {
+                continue;
+            }
+            balance_number_group(
+                wtxn,
+                db,
+                field_id,
+                level,
+                height,
+                bounds,
+                level_group_size,
+                max_group_size,
+            )?;
+            previous_bounds = Some(bounds);
+        }
+    }
+
+    Ok(())
+}
+
+/// Merges the group with one of its siblings when it has less than half `level_group_size`
+/// elements and splits it into groups of about `level_group_size` elements when it has more
+/// than `max_group_size` elements.
+///
+/// The parent group is left untouched as its bounds and documents ids do not change.
+#[allow(clippy::too_many_arguments)]
+fn balance_number_group(
+    wtxn: &mut heed::RwTxn,
+    db: heed::Database<FacetLevelValueF64Codec, CboRoaringBitmapCodec>,
+    field_id: FieldId,
+    level: u8,
+    height: u8,
+    bounds: (f64, f64),
+    level_group_size: NonZeroUsize,
+    max_group_size: NonZeroUsize,
+) -> Result<()> {
+    let min_group_size = cmp::max(level_group_size.get() / 2, 1);
+
+    let mut bounds = bounds;
+    let mut children = number_group_children(wtxn, db, field_id, level, bounds)?;
+    if children.is_empty() {
+        return Ok(());
+    }
+
+    if children.len() < min_group_size {
+        if let Some(sibling) = find_number_sibling(wtxn, db, field_id, level, height, bounds)? {
+            db.delete(wtxn, &(field_id, level, bounds.0, bounds.1))?;
+            db.delete(wtxn, &(field_id, level, sibling.0, sibling.1))?;
+            let merged = (bounds.0.min(sibling.0), bounds.1.max(sibling.1));
+            children = number_group_children(wtxn, db, field_id, level, merged)?;
+            bounds = put_number_group(wtxn, db, field_id, level, &children)?;
+        }
+    }
+
+    if children.len() > max_group_size.get() {
+        db.delete(wtxn, &(field_id, level, bounds.0, bounds.1))?;
+        let groups = children.len() / level_group_size.get();
+        let mut remaining = &children[..];
+        for i in 0..groups {
+            let (group, tail) = remaining.split_at(remaining.len() / (groups - i));
+            put_number_group(wtxn, db, field_id, level, group)?;
+            remaining = tail;
+        }
+    }
+
+    Ok(())
+}
+
+/// Shrinks the levels of a faceted field once documents were deleted from its level 0, with
+/// the level group sizes of the index. The string levels are rebuilt when level 0 entries were
+/// deleted, as their bounds are indices inside level 0, see [`shrink_number_levels`].
+pub(crate) fn shrink_field_levels(
+    wtxn: &mut heed::RwTxn,
+    index: &Index,
+    field_id: FieldId,
+    string_entries_deleted: bool,
+    validate_ordering: bool,
+) -> Result<()> {
+    let level_group_size =
+        NonZeroUsize::new(cmp::max(index.facet_level_group_size(wtxn)?, 2)).unwrap();
+    let max_group_size =
+        NonZeroUsize::new(cmp::max(index.facet_max_group_size(wtxn)?, level_group_size.get()))
+            .unwrap();
+    let min_level_size = if index.no_distribution_fields_ids(wtxn)?.contains(&field_id) {
+        NonZeroUsize::new(usize::MAX).unwrap()
+    } else {
+        NonZeroUsize::new(cmp::max(index.facet_min_level_size(wtxn)?, 1)).unwrap()
+    };
+
+    shrink_number_levels(
+        wtxn,
+        index.facet_id_f64_docids,
+        field_id,
+        level_group_size,
+        max_group_size,
+        min_level_size,
+    )?;
+
+    if string_entries_deleted {
+        let db = index.facet_id_string_docids;
+        clear_field_string_levels(wtxn, db.remap_types::<ByteSlice, DecodeIgnore>(), field_id)?;
+        let (facet_string_levels, _) = compute_facet_strings_levels(
+            wtxn,
+            db,
+            CompressionType::None,
+            None,
+            level_group_size,
+            min_level_size,
+            field_id,
+        )?;
+        for facet_strings_level in facet_string_levels {
+            write_into_lmdb_database(
+                wtxn,
+                *db.as_polymorph(),
+                facet_strings_level,
+                MergeStrategy::REFUSE_MERGING,
+                KeysOrderValidator::new(db_name::FACET_ID_STRING_DOCIDS, validate_ordering),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Shrinks the number levels of a field once documents were deleted from its level 0,
+/// the entries left without documents being already deleted from every level.
+///
+/// The levels that a level 0 of the new size does not require are dropped. In the remaining
+/// levels, from the lowest to the highest one, the bounds of every group are reduced to the ones
+/// of its remaining elements and the groups left undersized are merged with a sibling.
+fn shrink_number_levels(
+    wtxn: &mut heed::RwTxn,
+    db: heed::Database<FacetLevelValueF64Codec, CboRoaringBitmapCodec>,
+    field_id: FieldId,
+    level_group_size: NonZeroUsize,
+    max_group_size: NonZeroUsize,
+    min_level_size: NonZeroUsize,
+) -> Result<()> {
+    let height = number_levels_height(wtxn, db, field_id)?;
+    let level_0_size = number_level_0_size(wtxn, db, field_id)?;
+    let expected_height = number_of_levels(level_0_size, level_group_size, min_level_size);
+    if expected_height < height {
+        let range = (field_id, expected_height + 1, f64::MIN, f64::MIN)
+            ..=(field_id, u8::MAX, f64::MAX, f64::MAX);
+        db.delete_range(wtxn, &range)?;
+    }
+    let height = cmp::min(height, expected_height);
+
+    for level in 1..=height {
+        for bounds in number_level_groups(wtxn, db, field_id, level)? {
+            let children = number_group_children(wtxn, db, field_id, level, bounds)?;
+            let shrunk = match (children.first(), children.last()) {
+                (Some((left, _, _)), Some((_, right, _))) => Some((*left, *right)),
+                _ => None,
+            };
+            if shrunk != Some(bounds) {
+                db.delete(wtxn, &(field_id, level, bounds.0, bounds.1))?;
+                if shrunk.is_some() {
+                    put_number_group(wtxn, db, field_id, level, &children)?;
+                }
+            }
+        }
+
+        for bounds in number_level_groups(wtxn, db, field_id, level)? {
+            // The group may have been merged with the previous one.
+            if db.get(wtxn, &(field_id, level, bounds.0, bounds.1))?.is_some() {
+                balance_number_group(
+                    wtxn,
+                    db,
+                    field_id,
+                    level,
+                    height,
+                    bounds,
+                    level_group_size,
+                    max_group_size,
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the bounds of the groups of the given level.
+fn number_level_groups(
+    rtxn: &heed::RoTxn,
+    db: heed::Database<FacetLevelValueF64Codec, CboRoaringBitmapCodec>,
+    field_id: FieldId,
+    level: u8,
+) -> Result<Vec<(f64, f64)>> {
+    let range = (field_id, level, f64::MIN, f64::MIN)..=(field_id, level, f64::MAX, f64::MAX);
+    let mut groups = Vec::new();
+    for result in db.lazily_decode_data().range(rtxn, &range)? {
+        let ((_, _, left, right), _) = result?;
+        groups.push((left, right));
+    }
+    Ok(groups)
+}
+
 fn write_number_entry(
     writer: &mut Writer<File>,
     field_id: FieldId,
@@ -608,16 +1176,22 @@ fn write_string_entry(
     level: NonZeroU8,
     (left_id, left_value): (u32, &str),
     (right_id, right_value): (u32, &str),
-    docids: RoaringBitmap,
+    docids: &RoaringBitmap,
 ) -> Result<()> {
     let key = (field_id, level, left_id, right_id);
     let key = FacetLevelValueU32Codec::bytes_encode(&key).ok_or(Error::Encoding)?;
-    let data = match level.get() {
-        1 => (Some((left_value, right_value)), docids),
-        _ => (None, docids),
+    let bounds = match level.get() {
+        1 => (Some((left_value, right_value)), ()),
+        _ => (None, ()),
     };
-    let data = FacetStringZeroBoundsValueCodec::<CboRoaringBitmapCodec>::bytes_encode(&data)
-        .ok_or(Error::Encoding)?;
+    // The bounds are encoded without value and the docids are serialized right after
+    // them, it gives the same bytes as the codec without cloning the docids.
+    let bounds =
+        FacetStringZeroBoundsValueCodec::<Unit>::bytes_encode(&bounds).ok_or(Error::Encoding)?;
+    let mut data =
+        Vec::with_capacity(bounds.len() + CboRoaringBitmapCodec::serialized_size(docids));
+    data.extend_from_slice(&bounds);
+    CboRoaringBitmapCodec::serialize_into(docids, &mut data);
     writer.insert(&key, &data)?;
     Ok(())
 }
@@ -626,9 +1200,22 @@ fn write_string_entry(
 mod tests {
     use std::num::NonZeroUsize;
 
-    use crate::db_snap;
+    use big_s::S;
+    use maplit::hashset;
+    use roaring::RoaringBitmap;
+
+    use heed::types::ByteSlice;
+    use heed::BytesDecode;
+
+    use super::{
+        number_group_children, number_level_0_size, number_levels_height, number_of_levels,
+    };
     use crate::documents::documents_batch_reader_from_objects;
+    use crate::error::{Error, UserError};
+    use crate::heed_codec::facet::{FacetLevelValueU32Codec, FacetStringZeroBoundsValueCodec};
+    use crate::heed_codec::CboRoaringBitmapCodec;
     use crate::index::tests::TempIndex;
+    use crate::{db_snap, AscDesc, FieldId, Filter, Member};
 
     #[test]
     fn test_facets_number() {
@@ -710,4 +1297,478 @@ mod tests {
         test("default", None, None);
         test("tiny_groups_tiny_levels", NonZeroUsize::new(1), NonZeroUsize::new(1));
     }
+
+    #[test]
+    fn string_levels_of_many_entries() {
+        let mut index = TempIndex::new_with_map_size(4096 * 1000 * 100); // 400MB
+        index.index_documents_config.autogenerate_docids = true;
+        index.index_documents_config.facet_level_group_size = NonZeroUsize::new(2);
+        index.index_documents_config.facet_min_level_size = NonZeroUsize::new(2);
+        index
+            .update_settings(|settings| settings.set_filterable_fields(hashset! { S("facet") }))
+            .unwrap();
+
+        let documents = (0..5_000).map(|i| {
+            serde_json::json!({ "facet": format!("s{i:05}") }).as_object().unwrap().clone()
+        });
+        index.add_documents(documents_batch_reader_from_objects(documents)).unwrap();
+
+        // The levels are the ones written by the previous level by level builder.
+        db_snap!(index, facet_id_string_docids);
+
+        let rtxn = index.read_txn().unwrap();
+        let field_id = index.fields_ids_map(&rtxn).unwrap().id("facet").unwrap();
+        let documents_ids = index.documents_ids(&rtxn).unwrap();
+        let db = index.facet_id_string_docids.remap_types::<ByteSlice, ByteSlice>();
+        let [first, second] = field_id.to_be_bytes();
+        let level_0_size = db.prefix_iter(&rtxn, &[first, second, 0]).unwrap().count();
+        assert_eq!(level_0_size, 5_000);
+
+        // Every level groups the elements of the level below two by two, its groups
+        // follow each other over the ids of the level 0 and contain all the documents.
+        let mut previous_size = level_0_size;
+        let mut height = 0;
+        for level in 1..=u8::MAX {
+            let mut size = 0;
+            let mut next_left = 0;
+            let mut level_docids = RoaringBitmap::new();
+            for result in db.prefix_iter(&rtxn, &[first, second, level]).unwrap() {
+                let (key, value) = result.unwrap();
+                let (_, _, left, right) = FacetLevelValueU32Codec::bytes_decode(key).unwrap();
+                let (bounds, docids) =
+                    FacetStringZeroBoundsValueCodec::<CboRoaringBitmapCodec>::bytes_decode(value)
+                        .unwrap();
+                assert_eq!(left, next_left);
+                assert_eq!(bounds.is_some(), level == 1);
+                if let Some((left_value, right_value)) = bounds {
+                    assert_eq!(left_value, format!("s{left:05}"));
+                    assert_eq!(right_value, format!("s{right:05}"));
+                }
+                assert!(level_docids.is_disjoint(&docids));
+                level_docids |= docids;
+                next_left = right + 1;
+                size += 1;
+            }
+            if size == 0 {
+                break;
+            }
+            assert_eq!(size, (previous_size + 1) / 2);
+            assert_eq!(next_left as usize, level_0_size);
+            assert_eq!(level_docids, documents_ids);
+            previous_size = size;
+            height = level;
+        }
+        assert!(height >= 10, "only {} levels were built", height);
+    }
+
+    /// Checks that the groups of every number level are disjoint, that they contain the
+    /// documents ids of the elements of the level below inside their bounds and that every
+    /// element of the level below is inside a group.
+    fn check_number_levels(index: &TempIndex, field_id: FieldId) {
+        let rtxn = index.read_txn().unwrap();
+        let db = index.facet_id_f64_docids;
+        let height = number_levels_height(&rtxn, db, field_id).unwrap();
+        for level in 1..=height {
+            let mut previous_right = None;
+            let mut covered = 0;
+            let range =
+                (field_id, level, f64::MIN, f64::MIN)..=(field_id, level, f64::MAX, f64::MAX);
+            for result in db.range(&rtxn, &range).unwrap() {
+                let ((_, _, left, right), docids) = result.unwrap();
+                if let Some(previous_right) = previous_right {
+                    assert!(previous_right < left);
+                }
+                let children =
+                    number_group_children(&rtxn, db, field_id, level, (left, right)).unwrap();
+                let children_docids =
+                    children.iter().fold(RoaringBitmap::new(), |acc, (_, _, docids)| acc | docids);
+                assert_eq!(docids, children_docids);
+                covered += children.len();
+                previous_right = Some(right);
+            }
+            let below = (field_id, level - 1, f64::MIN, f64::MIN)
+                ..=(field_id, level - 1, f64::MAX, f64::MAX);
+            assert_eq!(covered, db.range(&rtxn, &below).unwrap().count());
+        }
+    }
+
+    #[test]
+    fn incremental_number_levels_match_bulk_levels() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut incremental = TempIndex::new();
+        incremental.index_documents_config.facet_rebuild_ratio = Some(f64::INFINITY);
+        let mut bulk = TempIndex::new();
+        bulk.index_documents_config.facet_rebuild_ratio = Some(0.0);
+
+        let mut documents = vec![];
+        for i in 0..100 {
+            let document = serde_json::json!({ "id": i, "facet": i * 10 });
+            documents.push(document.as_object().unwrap().clone());
+        }
+
+        for index in [&incremental, &bulk] {
+            index
+                .update_settings(|settings| {
+                    settings.set_filterable_fields(hashset! { S("facet") });
+                })
+                .unwrap();
+            index.add_documents(documents_batch_reader_from_objects(documents.clone())).unwrap();
+        }
+
+        let rtxn = incremental.read_txn().unwrap();
+        let field_id = incremental.fields_ids_map(&rtxn).unwrap().id("facet").unwrap();
+        drop(rtxn);
+
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..30 {
+            let mut documents = vec![];
+            for _ in 0..rng.gen_range(1..10) {
+                let id = rng.gen_range(0..300);
+                let facet = rng.gen_range(0..1000);
+                let document = serde_json::json!({ "id": id, "facet": facet });
+                documents.push(document.as_object().unwrap().clone());
+            }
+
+            incremental
+                .add_documents(documents_batch_reader_from_objects(documents.clone()))
+                .unwrap();
+            bulk.add_documents(documents_batch_reader_from_objects(documents)).unwrap();
+
+            check_number_levels(&incremental, field_id);
+
+            let incremental_rtxn = incremental.read_txn().unwrap();
+            let bulk_rtxn = bulk.read_txn().unwrap();
+            assert_eq!(
+                incremental.number_faceted_documents_ids(&incremental_rtxn, field_id).unwrap(),
+                bulk.number_faceted_documents_ids(&bulk_rtxn, field_id).unwrap(),
+            );
+
+            for _ in 0..10 {
+                let low = rng.gen_range(0..1000);
+                let high = rng.gen_range(low..1000);
+                for expression in [
+                    format!("facet {} TO {}", low, high),
+                    format!("facet > {}", low),
+                    format!("facet <= {}", high),
+                    format!("facet = {}", low),
+                ] {
+                    let filter = Filter::from_str(&expression).unwrap().unwrap();
+                    assert_eq!(
+                        filter.evaluate(&incremental_rtxn, &incremental).unwrap(),
+                        filter.evaluate(&bulk_rtxn, &bulk).unwrap(),
+                        "{}",
+                        expression
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn deleted_documents_shrink_the_levels() {
+        use std::str::FromStr;
+
+        use rand::rngs::StdRng;
+        use rand::seq::SliceRandom;
+        use rand::SeedableRng;
+
+        use crate::update::DeleteDocuments;
+        use crate::Search;
+
+        let index = TempIndex::new();
+        index
+            .update_settings(|settings| {
+                settings.set_sortable_fields(hashset! { S("facet"), S("name") });
+            })
+            .unwrap();
+
+        let mut documents = vec![];
+        for i in 0..200 {
+            let document =
+                serde_json::json!({ "id": i, "facet": i * 10, "name": format!("name{:03}", i) });
+            documents.push(document.as_object().unwrap().clone());
+        }
+        index.add_documents(documents_batch_reader_from_objects(documents)).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let field_id = index.fields_ids_map(&rtxn).unwrap().id("facet").unwrap();
+        assert!(number_levels_height(&rtxn, index.facet_id_f64_docids, field_id).unwrap() > 1);
+        drop(rtxn);
+
+        let mut remaining: Vec<u32> = (0..200).collect();
+        let mut rng = StdRng::seed_from_u64(42);
+        remaining.shuffle(&mut rng);
+        while !remaining.is_empty() {
+            let deleted = remaining.split_off(remaining.len().saturating_sub(25));
+            let mut wtxn = index.write_txn().unwrap();
+            let mut builder = DeleteDocuments::new(&mut wtxn, &index).unwrap();
+            for id in &deleted {
+                builder.delete_external_id(&id.to_string());
+            }
+            builder.purge().unwrap();
+            wtxn.commit().unwrap();
+
+            check_number_levels(&index, field_id);
+
+            let rtxn = index.read_txn().unwrap();
+            let db = index.facet_id_f64_docids;
+            let height = number_levels_height(&rtxn, db, field_id).unwrap();
+            let level_0_size = number_level_0_size(&rtxn, db, field_id).unwrap();
+            assert_eq!(level_0_size, remaining.len());
+            let level_group_size = NonZeroUsize::new(4).unwrap();
+            let min_level_size = NonZeroUsize::new(5).unwrap();
+            assert_eq!(height, number_of_levels(level_0_size, level_group_size, min_level_size));
+
+            // The bounds of every group are the ones of its remaining elements.
+            for level in 1..=height {
+                let range =
+                    (field_id, level, f64::MIN, f64::MIN)..=(field_id, level, f64::MAX, f64::MAX);
+                for result in db.range(&rtxn, &range).unwrap() {
+                    let ((_, _, left, right), docids) = result.unwrap();
+                    assert!(!docids.is_empty());
+                    let children =
+                        number_group_children(&rtxn, db, field_id, level, (left, right)).unwrap();
+                    assert_eq!(children.first().unwrap().0, left);
+                    assert_eq!(children.last().unwrap().1, right);
+                }
+            }
+
+            // The sorts only return the remaining documents, in order.
+            let external_ids = index.external_documents_ids(&rtxn).unwrap();
+            let mut expected: Vec<_> = remaining.clone();
+            expected.sort_unstable();
+            let expected: Vec<_> =
+                expected.into_iter().map(|id| external_ids.get(id.to_string()).unwrap()).collect();
+            for (sort, expected) in [
+                ("name:asc", expected.clone()),
+                ("facet:desc", expected.iter().rev().copied().collect()),
+            ] {
+                let mut search = Search::new(&rtxn, &index);
+                search.sort_criteria(vec![AscDesc::from_str(sort).unwrap()]).limit(200);
+                assert_eq!(search.execute().unwrap().documents_ids, expected, "{}", sort);
+            }
+        }
+
+        // Nothing is left of the trees once every document is deleted.
+        let rtxn = index.read_txn().unwrap();
+        assert!(index.facet_id_f64_docids.is_empty(&rtxn).unwrap());
+        assert!(index.facet_id_string_docids.is_empty(&rtxn).unwrap());
+    }
+
+    #[test]
+    fn no_distribution_fields_only_keep_the_level_0() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|settings| {
+                settings.set_filterable_fields(hashset! { S("user_id"), S("score"), S("kind") });
+            })
+            .unwrap();
+
+        let mut documents = vec![];
+        for i in 0..2000 {
+            let document = serde_json::json!({
+                "id": i,
+                "user_id": format!("user-{:04}", i),
+                "score": i,
+                "kind": if i % 2 == 0 { "even" } else { "odd" },
+            });
+            documents.push(document.as_object().unwrap().clone());
+        }
+        index.add_documents(documents_batch_reader_from_objects(documents)).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let fields_ids_map = index.fields_ids_map(&rtxn).unwrap();
+        let score_id = fields_ids_map.id("score").unwrap();
+        let strings_len = index.facet_id_string_docids.len(&rtxn).unwrap();
+        let numbers_len = index.facet_id_f64_docids.len(&rtxn).unwrap();
+        assert!(number_levels_height(&rtxn, index.facet_id_f64_docids, score_id).unwrap() > 0);
+        drop(rtxn);
+
+        index
+            .update_settings(|settings| {
+                settings.set_no_distribution_fields(hashset! { S("user_id"), S("score") });
+            })
+            .unwrap();
+
+        // The upper levels of the high cardinality fields are gone.
+        let rtxn = index.read_txn().unwrap();
+        assert!(index.facet_id_string_docids.len(&rtxn).unwrap() < strings_len);
+        assert!(index.facet_id_f64_docids.len(&rtxn).unwrap() < numbers_len);
+        assert_eq!(number_levels_height(&rtxn, index.facet_id_f64_docids, score_id).unwrap(), 0);
+
+        // The fields can still be filtered on.
+        let filter = Filter::from_str("user_id = \"user-0042\"").unwrap().unwrap();
+        assert_eq!(filter.evaluate(&rtxn, &index).unwrap().len(), 1);
+        let filter = Filter::from_str("score 100 TO 199 AND kind = even").unwrap().unwrap();
+        assert_eq!(filter.evaluate(&rtxn, &index).unwrap().len(), 50);
+
+        // But they are not part of the facet distribution.
+        let distribution = index.facets_distribution(&rtxn).execute().unwrap();
+        assert_eq!(distribution.keys().collect::<Vec<_>>(), vec!["kind"]);
+        let error =
+            index.facets_distribution(&rtxn).facets(["kind", "score"]).execute().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid facet distribution, the fields `score` are set as filterable without facet distribution."
+        );
+        drop(rtxn);
+
+        // The incremental indexing doesn't build the levels back.
+        let documents =
+            vec![serde_json::json!({ "id": 2000, "user_id": "user-2000", "score": 2000 })
+                .as_object()
+                .unwrap()
+                .clone()];
+        index.add_documents(documents_batch_reader_from_objects(documents)).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(number_levels_height(&rtxn, index.facet_id_f64_docids, score_id).unwrap(), 0);
+        let filter = Filter::from_str("score >= 1990").unwrap().unwrap();
+        assert_eq!(filter.evaluate(&rtxn, &index).unwrap().len(), 11);
+        drop(rtxn);
+
+        // Resetting the setting builds the levels again.
+        index
+            .update_settings(|settings| {
+                settings.reset_no_distribution_fields();
+            })
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert!(number_levels_height(&rtxn, index.facet_id_f64_docids, score_id).unwrap() > 0);
+        assert!(index.facet_id_string_docids.len(&rtxn).unwrap() > strings_len);
+    }
+
+    #[test]
+    fn facet_levels_settings() {
+        type Entries = Vec<(Vec<u8>, Vec<u8>)>;
+        fn facet_entries(index: &TempIndex) -> (Entries, Entries) {
+            let rtxn = index.read_txn().unwrap();
+            let entries = |db: heed::Database<ByteSlice, ByteSlice>| -> Entries {
+                db.iter(&rtxn)
+                    .unwrap()
+                    .map(|result| {
+                        let (key, value) = result.unwrap();
+                        (key.to_vec(), value.to_vec())
+                    })
+                    .collect()
+            };
+            (
+                entries(index.facet_id_f64_docids.remap_types()),
+                entries(index.facet_id_string_docids.remap_types()),
+            )
+        }
+
+        let mut documents = vec![];
+        for i in 0..1_000 {
+            let document = serde_json::json!({
+                "id": i,
+                "facet": (i * 7) % 1000,
+                "facet2": format!("s{:X}", i % 100),
+            });
+            documents.push(document.as_object().unwrap().clone());
+        }
+
+        // The same parameters as the `small_groups_small_levels` snapshot, given to the indexer.
+        let mut reference = TempIndex::new();
+        reference.index_documents_config.facet_level_group_size = NonZeroUsize::new(2);
+        reference.index_documents_config.facet_min_level_size = NonZeroUsize::new(2);
+        // The parameters are set before the documents are added.
+        let configured = TempIndex::new();
+        // The parameters are set after the documents are added.
+        let updated = TempIndex::new();
+        let default = TempIndex::new();
+
+        for index in [&reference, &configured, &updated, &default] {
+            index
+                .update_settings(|settings| {
+                    settings.set_filterable_fields(hashset! { S("facet"), S("facet2") });
+                    settings.set_sortable_fields(hashset! { S("facet") });
+                })
+                .unwrap();
+        }
+        configured
+            .update_settings(|settings| {
+                settings.set_facet_level_group_size(2);
+                settings.set_facet_min_level_size(2);
+            })
+            .unwrap();
+        for index in [&reference, &configured, &updated, &default] {
+            index.add_documents(documents_batch_reader_from_objects(documents.clone())).unwrap();
+        }
+        updated
+            .update_settings(|settings| {
+                settings.set_facet_level_group_size(2);
+                settings.set_facet_min_level_size(2);
+            })
+            .unwrap();
+
+        let rtxn = updated.read_txn().unwrap();
+        assert_eq!(updated.facet_level_group_size(&rtxn).unwrap(), 2);
+        assert_eq!(updated.facet_min_level_size(&rtxn).unwrap(), 2);
+        drop(rtxn);
+
+        let reference_entries = facet_entries(&reference);
+        assert_eq!(facet_entries(&configured), reference_entries);
+        assert_eq!(facet_entries(&updated), reference_entries);
+        assert_ne!(facet_entries(&default).0, reference_entries.0);
+
+        // The shape of the levels doesn't change the results.
+        let default_rtxn = default.read_txn().unwrap();
+        for index in [&configured, &updated] {
+            let rtxn = index.read_txn().unwrap();
+            for criterion in [AscDesc::Asc, AscDesc::Desc] {
+                let sort = vec![criterion(Member::Field(S("facet")))];
+                let mut search = index.search(&rtxn);
+                search.sort_criteria(sort.clone()).limit(50);
+                let mut default_search = default.search(&default_rtxn);
+                default_search.sort_criteria(sort).limit(50);
+                assert_eq!(
+                    search.execute().unwrap().documents_ids,
+                    default_search.execute().unwrap().documents_ids
+                );
+            }
+
+            let filter = Filter::from_str("facet 100 TO 500 AND facet2 = s1").unwrap().unwrap();
+            assert_eq!(
+                filter.evaluate(&rtxn, index).unwrap(),
+                filter.evaluate(&default_rtxn, &default).unwrap()
+            );
+            assert_eq!(
+                index.facets_distribution(&rtxn).execute().unwrap(),
+                default.facets_distribution(&default_rtxn).execute().unwrap()
+            );
+        }
+        drop(default_rtxn);
+
+        // The group size must be at least 2 and the maximum group size at least the group size.
+        let error = configured
+            .update_settings(|settings| settings.set_facet_level_group_size(1))
+            .unwrap_err();
+        assert!(matches!(error, Error::UserError(UserError::InvalidFacetLevelsSetting { .. })));
+        let error = configured
+            .update_settings(|settings| settings.set_facet_max_group_size(1))
+            .unwrap_err();
+        assert!(matches!(error, Error::UserError(UserError::InvalidFacetLevelsSetting { .. })));
+
+        // The maximum group size follows the group size when it is not set.
+        let rtxn = configured.read_txn().unwrap();
+        assert_eq!(configured.facet_max_group_size(&rtxn).unwrap(), 4);
+        drop(rtxn);
+        configured.update_settings(|settings| settings.set_facet_level_group_size(16)).unwrap();
+        let rtxn = configured.read_txn().unwrap();
+        assert_eq!(configured.facet_max_group_size(&rtxn).unwrap(), 32);
+        drop(rtxn);
+
+        // Resetting the parameters rebuilds the default levels.
+        configured
+            .update_settings(|settings| {
+                settings.reset_facet_level_group_size();
+                settings.reset_facet_min_level_size();
+            })
+            .unwrap();
+        assert_eq!(facet_entries(&configured), facet_entries(&default));
+    }
 }
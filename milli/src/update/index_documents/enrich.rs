@@ -1,3 +1,5 @@
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
 use std::io::{Read, Seek};
 use std::result::Result as StdResult;
 use std::{fmt, iter};
@@ -5,16 +7,59 @@ use std::{fmt, iter};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::documents::{DocumentsBatchIndex, DocumentsBatchReader, EnrichedDocumentsBatchReader};
-use crate::error::{GeoError, InternalError, UserError};
-use crate::update::index_documents::{obkv_to_object, writer_into_reader};
+use crate::documents::{
+    DocumentsBatchCursor, DocumentsBatchIndex, DocumentsBatchReader, EnrichedDocumentsBatchReader,
+    PrimaryKeyGuess, DEFAULT_PRIMARY_KEY, PRIMARY_KEY_SPLIT_SYMBOL,
+};
+use crate::error::{FieldIdMapMissingEntry, GeoError, InternalError, UserError};
+use crate::update::index_documents::{obkv_to_object, writer_into_reader, AutogenerateIds};
 use crate::{FieldId, Index, Object, Result};
 
-/// The symbol used to define levels in a nested primary key.
-const PRIMARY_KEY_SPLIT_SYMBOL: char = '.';
+/// The maximum number of document errors kept in a [`ValidationReport`].
+pub const MAX_VALIDATION_ERRORS: usize = 100;
+
+/// The result of the validation of a batch of documents, nothing is written into the index.
+#[derive(Debug)]
+pub struct ValidationReport {
+    /// The number of documents in the batch.
+    pub documents_count: u64,
+    /// The number of documents that can't be indexed.
+    pub invalid_documents: u64,
+    /// The error of the whole batch, e.g. when no primary key can be inferred,
+    /// the documents are then not validated one by one.
+    pub batch_error: Option<UserError>,
+    /// The errors of the first [`MAX_VALIDATION_ERRORS`] invalid documents.
+    pub errors: Vec<DocumentValidationError>,
+    /// The first [`MAX_VALIDATION_ERRORS`] documents that have the same id as a previous
+    /// document of the batch, with a [`UserError::DuplicateDocumentId`] showing both documents.
+    ///
+    /// They don't make the batch invalid: the later versions of a document replace or
+    /// update the previous ones, depending on the indexing method.
+    pub duplicates: Vec<DocumentValidationError>,
+}
+
+impl ValidationReport {
+    /// Returns `true` if all the documents of the batch can be indexed.
+    pub fn is_valid(&self) -> bool {
+        self.batch_error.is_none() && self.invalid_documents == 0
+    }
+}
 
-/// The default primary that is used when not specified.
-const DEFAULT_PRIMARY_KEY: &str = "id";
+/// The reason why a document of a batch can't be indexed.
+#[derive(Debug)]
+pub struct DocumentValidationError {
+    /// The position of the document in the batch.
+    pub position: u32,
+    /// The external id of the document, when it could be extracted.
+    pub external_id: Option<String>,
+    pub error: UserError,
+}
+
+/// The result of the validation of a document of a batch.
+enum DocumentCheck {
+    Valid(DocumentId),
+    Invalid { external_id: Option<String>, error: UserError },
+}
 
 /// This function validates and enrich the documents by checking that:
 ///  - we can infer a primary key,
@@ -25,13 +70,163 @@ pub fn enrich_documents_batch<R: Read + Seek>(
     rtxn: &heed::RoTxn,
     index: &Index,
     autogenerate_docids: bool,
+    autogenerate_ids: AutogenerateIds,
     reader: DocumentsBatchReader<R>,
 ) -> Result<StdResult<EnrichedDocumentsBatchReader<R>, UserError>> {
+    let mut external_ids = tempfile::tempfile().map(grenad::Writer::new)?;
+    let mut document_error = None;
+    let checked = check_documents_batch(
+        rtxn,
+        index,
+        autogenerate_docids,
+        autogenerate_ids,
+        reader,
+        |position, check| match check {
+            DocumentCheck::Valid(document_id) => {
+                let document_id =
+                    serde_json::to_vec(&document_id).map_err(InternalError::SerdeJson)?;
+                external_ids.insert(position.to_be_bytes(), document_id)?;
+                Ok(true)
+            }
+            DocumentCheck::Invalid { error, .. } => {
+                document_error = Some(error);
+                Ok(false)
+            }
+        },
+    )?;
+
+    let (cursor, documents_batch_index, primary_key_name) = match checked {
+        Ok(checked) => checked,
+        Err(user_error) => return Ok(Err(user_error)),
+    };
+    if let Some(user_error) = document_error {
+        return Ok(Err(user_error));
+    }
+
+    let external_ids = writer_into_reader(external_ids)?;
+    let reader = EnrichedDocumentsBatchReader::new(
+        DocumentsBatchReader::new(cursor, documents_batch_index),
+        primary_key_name,
+        external_ids,
+    )?;
+
+    Ok(Ok(reader))
+}
+
+/// Runs the checks of [`enrich_documents_batch`] on all the documents of the batch,
+/// without stopping at the first invalid one and without writing anything.
+pub fn validate_documents_batch<R: Read + Seek>(
+    rtxn: &heed::RoTxn,
+    index: &Index,
+    autogenerate_docids: bool,
+    autogenerate_ids: AutogenerateIds,
+    reader: DocumentsBatchReader<R>,
+) -> Result<ValidationReport> {
+    let mut report = ValidationReport {
+        documents_count: reader.documents_count() as u64,
+        invalid_documents: 0,
+        batch_error: None,
+        errors: Vec::new(),
+        duplicates: Vec::new(),
+    };
+
+    // The position of the first document of each id and the
+    // positions of the duplicates along with the one of their first document.
+    let mut first_positions = HashMap::new();
+    let mut duplicates = Vec::new();
+    let checked = check_documents_batch(
+        rtxn,
+        index,
+        autogenerate_docids,
+        autogenerate_ids,
+        reader,
+        |position, check| {
+            match check {
+                DocumentCheck::Valid(document_id) if !document_id.is_generated() => {
+                    if duplicates.len() < MAX_VALIDATION_ERRORS {
+                        match first_positions.entry(document_id.value().to_string()) {
+                            Entry::Occupied(entry) => {
+                                duplicates.push((position, *entry.get(), entry.key().clone()))
+                            }
+                            Entry::Vacant(entry) => {
+                                entry.insert(position);
+                            }
+                        }
+                    }
+                }
+                DocumentCheck::Valid(_) => (),
+                DocumentCheck::Invalid { external_id, error } => {
+                    report.invalid_documents += 1;
+                    if report.errors.len() < MAX_VALIDATION_ERRORS {
+                        let error = DocumentValidationError { position, external_id, error };
+                        report.errors.push(error);
+                    }
+                }
+            }
+            Ok(true)
+        },
+    )?;
+
+    match checked {
+        Ok((cursor, documents_batch_index, _)) => {
+            // The documents are read again by their position to show both versions.
+            let fields_index = documents_batch_index.clone();
+            let mut reader = DocumentsBatchReader::new(cursor, documents_batch_index);
+            for (position, first_position, document_id) in duplicates {
+                let first_document = document_at(&mut reader, &fields_index, first_position)?;
+                let document = document_at(&mut reader, &fields_index, position)?;
+                let external_id = Some(document_id.clone());
+                let error = UserError::DuplicateDocumentId {
+                    document_id,
+                    first_position,
+                    first_document,
+                    position,
+                    document,
+                };
+                report.duplicates.push(DocumentValidationError { position, external_id, error });
+            }
+        }
+        Err(user_error) => report.batch_error = Some(user_error),
+    }
+
+    Ok(report)
+}
+
+/// Returns the nth document of the batch as a JSON object.
+fn document_at<R: Read + Seek>(
+    reader: &mut DocumentsBatchReader<R>,
+    fields_index: &DocumentsBatchIndex,
+    nth: u32,
+) -> Result<Object> {
+    match reader.get(nth)? {
+        Some(document) => obkv_to_object(&document, fields_index),
+        None => Err(crate::documents::Error::InvalidDocumentFormat.into()),
+    }
+}
+
+/// Infers the primary key of the batch and checks its documents one by one, the given
+/// function is called with the result of each document and returns whether to continue.
+///
+/// Returns the cursor, the fields of the batch and the name of the primary key.
+fn check_documents_batch<R, F>(
+    rtxn: &heed::RoTxn,
+    index: &Index,
+    autogenerate_docids: bool,
+    autogenerate_ids: AutogenerateIds,
+    reader: DocumentsBatchReader<R>,
+    mut on_document: F,
+) -> Result<StdResult<(DocumentsBatchCursor<R>, DocumentsBatchIndex, String), UserError>>
+where
+    R: Read + Seek,
+    F: FnMut(u32, DocumentCheck) -> Result<bool>,
+{
     let (mut cursor, mut documents_batch_index) = reader.into_cursor_and_fields_index();
 
-    let mut external_ids = tempfile::tempfile().map(grenad::Writer::new)?;
     let mut uuid_buffer = [0; uuid::fmt::Hyphenated::LENGTH];
 
+    // The primary key we guessed from the fields of the batch, it must outlive the `PrimaryKey`.
+    let guess;
+
     // The primary key *field id* that has already been set for this index or the one
     // we will guess by searching for the first key that contains "id" as a substring.
     let primary_key = match index.primary_key(rtxn)? {
@@ -54,12 +249,31 @@ pub fn enrich_documents_batch<R: Read + Seek>(
             }
         },
         None => {
-            let guessed = documents_batch_index
-                .iter()
-                .filter(|(_, name)| name.to_lowercase().contains(DEFAULT_PRIMARY_KEY))
-                .min_by_key(|(fid, _)| *fid);
-            match guessed {
-                Some((id, name)) => PrimaryKey::flat(name.as_str(), *id),
+            guess = PrimaryKeyGuess::new(&documents_batch_index, || {
+                let first_document = match cursor.next_document()? {
+                    Some(document) => Some(obkv_to_object(&document, &documents_batch_index)?),
+                    None => None,
+                };
+                cursor.reset();
+                Ok(first_document)
+            })?;
+            match guess.primary_key() {
+                // A nested primary key is only guessed when a single nested field contains "id".
+                Some(_) if guess.nested && guess.is_ambiguous() => {
+                    let candidates = guess.candidates.clone();
+                    return Ok(Err(UserError::MultiplePrimaryKeyCandidatesFound { candidates }));
+                }
+                Some(name) if guess.nested => PrimaryKey::nested(name),
+                Some(name) => match documents_batch_index.id(name) {
+                    Some(id) => PrimaryKey::flat(name, id),
+                    None => {
+                        return Err(FieldIdMapMissingEntry::FieldName {
+                            field_name: name.to_string(),
+                            process: "guess_primary_key",
+                        }
+                        .into())
+                    }
+                },
                 None if autogenerate_docids => PrimaryKey::flat(
                     DEFAULT_PRIMARY_KEY,
                     documents_batch_index.insert(DEFAULT_PRIMARY_KEY),
@@ -78,39 +292,39 @@ pub fn enrich_documents_batch<R: Read + Seek>(
 
     let mut count = 0;
     while let Some(document) = cursor.next_document()? {
-        let document_id = match fetch_or_generate_document_id(
+        let check = match fetch_or_generate_document_id(
             &document,
             &documents_batch_index,
             primary_key,
             autogenerate_docids,
+            autogenerate_ids,
             &mut uuid_buffer,
             count,
         )? {
-            Ok(document_id) => document_id,
-            Err(user_error) => return Ok(Err(user_error)),
+            Ok(document_id) => match geo_field_id.and_then(|fid| document.get(fid)) {
+                Some(geo_value) => match validate_geo_from_json(&document_id, geo_value)? {
+                    Ok(()) => DocumentCheck::Valid(document_id),
+                    Err(geo_error) => DocumentCheck::Invalid {
+                        external_id: Some(document_id)
+                            .filter(|id| !id.is_generated())
+                            .map(|id| id.value().to_string()),
+                        error: UserError::from(geo_error),
+                    },
+                },
+                None => DocumentCheck::Valid(document_id),
+            },
+            Err(user_error) => DocumentCheck::Invalid { external_id: None, error: user_error },
         };
 
-        if let Some(geo_value) = geo_field_id.and_then(|fid| document.get(fid)) {
-            if let Err(user_error) = validate_geo_from_json(&document_id, geo_value)? {
-                return Ok(Err(UserError::from(user_error)));
-            }
+        if !on_document(count, check)? {
+            break;
         }
 
-        let document_id = serde_json::to_vec(&document_id).map_err(InternalError::SerdeJson)?;
-        external_ids.insert(count.to_be_bytes(), document_id)?;
-
         count += 1;
     }
 
-    let external_ids = writer_into_reader(external_ids)?;
     let primary_key_name = primary_key.name().to_string();
-    let reader = EnrichedDocumentsBatchReader::new(
-        DocumentsBatchReader::new(cursor, documents_batch_index),
-        primary_key_name,
-        external_ids,
-    )?;
-
-    Ok(Ok(reader))
+    Ok(Ok((cursor, documents_batch_index, primary_key_name)))
 }
 
 /// Retrieve the document id after validating it, returning a `UserError`
@@ -120,6 +334,7 @@ fn fetch_or_generate_document_id(
     documents_batch_index: &DocumentsBatchIndex,
     primary_key: PrimaryKey,
     autogenerate_docids: bool,
+    autogenerate_ids: AutogenerateIds,
     uuid_buffer: &mut [u8; uuid::fmt::Hyphenated::LENGTH],
     count: u32,
 ) -> Result<StdResult<DocumentId, UserError>> {
@@ -135,7 +350,13 @@ fn fetch_or_generate_document_id(
                     }
                 }
                 None if autogenerate_docids => {
-                    let uuid = uuid::Uuid::new_v4().as_hyphenated().encode_lower(uuid_buffer);
+                    let uuid = match autogenerate_ids {
+                        AutogenerateIds::Uuid => uuid::Uuid::new_v4(),
+                        AutogenerateIds::ContentHash => {
+                            content_hash_uuid(document, documents_batch_index)?
+                        }
+                    };
+                    let uuid = uuid.as_hyphenated().encode_lower(uuid_buffer);
                     Ok(Ok(DocumentId::generated(uuid.to_string(), count)))
                 }
                 None => Ok(Err(UserError::MissingDocumentId {
@@ -177,6 +398,19 @@ fn fetch_or_generate_document_id(
     }
 }
 
+/// Derives a UUID from the MD5 hash of the flattened document. The fields are sorted
+/// before being serialized, the documents that only differ by the order of their
+/// fields are given the same id.
+fn content_hash_uuid(
+    document: &obkv::KvReader<FieldId>,
+    documents_batch_index: &DocumentsBatchIndex,
+) -> Result<uuid::Uuid> {
+    let object = obkv_to_object(document, documents_batch_index)?;
+    let flattened: BTreeMap<_, _> = flatten_serde_json::flatten(&object).into_iter().collect();
+    let bytes = serde_json::to_vec(&flattened).map_err(InternalError::SerdeJson)?;
+    Ok(uuid::Builder::from_md5_bytes(md5::compute(bytes).0).into_uuid())
+}
+
 /// A type that represent the type of primary key that has been set
 /// for this index, a classic flat one or a nested one.
 #[derive(Debug, Clone, Copy)]
@@ -265,7 +499,10 @@ fn starts_with(selector: &str, key: &str) -> bool {
 
 pub fn fetch_matching_values(value: Value, selector: &str, output: &mut Vec<Value>) {
     match value {
-        Value::Object(object) => fetch_matching_values_in_object(object, selector, "", output),
+        // An object that is directly selected is kept as is, it is not a valid document id.
+        Value::Object(object) if !selector.is_empty() => {
+            fetch_matching_values_in_object(object, selector, "", output)
+        }
         otherwise => output.push(otherwise),
     }
 }
@@ -285,7 +522,8 @@ pub fn fetch_matching_values_in_object(
 
         if starts_with(selector, &base_key) {
             match value {
-                Value::Object(object) => {
+                // An object that is directly selected is kept as is, it is not a valid document id.
+                Value::Object(object) if selector != base_key => {
                     fetch_matching_values_in_object(object, selector, &base_key, output)
                 }
                 value => output.push(value),
@@ -294,9 +532,15 @@ pub fn fetch_matching_values_in_object(
     }
 }
 
+/// The maximum length, in bytes, of a document identifier.
+pub const MAX_DOCUMENT_ID_LENGTH: usize = 512;
+
+/// Returns the document id if it is a valid one, a non-empty string of at most
+/// [`MAX_DOCUMENT_ID_LENGTH`] bytes that neither starts nor ends with whitespace.
 pub fn validate_document_id(document_id: &str) -> Option<&str> {
     if !document_id.is_empty()
-        && document_id.chars().all(|c| matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_'))
+        && document_id.len() <= MAX_DOCUMENT_ID_LENGTH
+        && document_id.trim().len() == document_id.len()
     {
         Some(document_id)
     } else {
@@ -305,14 +549,16 @@ pub fn validate_document_id(document_id: &str) -> Option<&str> {
 }
 
 /// Parses a Json encoded document id and validate it, returning a user error when it is one.
+///
+/// The integers are kept as they are written and never go through a float,
+/// the ones that don't fit in an `i64` or an `u64` must be sent as strings.
 pub fn validate_document_id_value(document_id: Value) -> Result<StdResult<String, UserError>> {
     match document_id {
         Value::String(string) => match validate_document_id(&string) {
-            Some(s) if s.len() == string.len() => Ok(Ok(string)),
-            Some(s) => Ok(Ok(s.to_string())),
+            Some(_) => Ok(Ok(string)),
             None => Ok(Err(UserError::InvalidDocumentId { document_id: Value::String(string) })),
         },
-        Value::Number(number) if number.is_i64() => Ok(Ok(number.to_string())),
+        Value::Number(number) if number.is_i64() || number.is_u64() => Ok(Ok(number.to_string())),
         content => Ok(Err(UserError::InvalidDocumentId { document_id: content.clone() })),
     }
 }
@@ -340,24 +586,76 @@ pub fn extract_finite_float_from_value(value: Value) -> StdResult<f64, Value> {
 }
 
 pub fn validate_geo_from_json(id: &DocumentId, bytes: &[u8]) -> Result<StdResult<(), GeoError>> {
-    use GeoError::*;
     let debug_id = || Value::from(id.debug());
-    match serde_json::from_slice(bytes).map_err(InternalError::SerdeJson)? {
-        Value::Object(mut object) => match (object.remove("lat"), object.remove("lng")) {
-            (Some(lat), Some(lng)) => {
-                match (extract_finite_float_from_value(lat), extract_finite_float_from_value(lng)) {
-                    (Ok(_), Ok(_)) => Ok(Ok(())),
-                    (Err(value), Ok(_)) => Ok(Err(BadLatitude { document_id: debug_id(), value })),
-                    (Ok(_), Err(value)) => Ok(Err(BadLongitude { document_id: debug_id(), value })),
-                    (Err(lat), Err(lng)) => {
-                        Ok(Err(BadLatitudeAndLongitude { document_id: debug_id(), lat, lng }))
-                    }
+    let value = serde_json::from_slice(bytes).map_err(InternalError::SerdeJson)?;
+    Ok(extract_geo_point_from_value(debug_id, value).map(drop))
+}
+
+/// Extracts the `(latitude, longitude)` of a `_geo` value, which is either an object with
+/// `lat` and `lng` fields, a `[lng, lat]` array or a GeoJSON point. The arrays follow
+/// the GeoJSON ordering, the longitude comes first.
+pub fn extract_geo_point_from_value(
+    document_id: impl Fn() -> Value,
+    value: Value,
+) -> StdResult<(f64, f64), GeoError> {
+    use GeoError::*;
+    match value {
+        Value::Object(object)
+            if object.contains_key("type") || object.contains_key("coordinates") =>
+        {
+            let point = match (object.get("type"), object.get("coordinates")) {
+                (Some(Value::String(kind)), Some(Value::Array(coordinates)))
+                    if kind == "Point" && coordinates.len() == 2 =>
+                {
+                    Some((coordinates[0].clone(), coordinates[1].clone()))
+                }
+                _otherwise => None,
+            };
+            match point {
+                Some((lng, lat)) => extract_geo_point_from_lat_lng(document_id, lat, lng),
+                None => {
+                    Err(BadGeoPoint { document_id: document_id(), value: Value::Object(object) })
                 }
             }
-            (None, Some(_)) => Ok(Err(MissingLatitude { document_id: debug_id() })),
-            (Some(_), None) => Ok(Err(MissingLongitude { document_id: debug_id() })),
-            (None, None) => Ok(Err(MissingLatitudeAndLongitude { document_id: debug_id() })),
+        }
+        Value::Object(mut object) => match (object.remove("lat"), object.remove("lng")) {
+            (Some(lat), Some(lng)) => extract_geo_point_from_lat_lng(document_id, lat, lng),
+            (None, Some(_)) => Err(MissingLatitude { document_id: document_id() }),
+            (Some(_), None) => Err(MissingLongitude { document_id: document_id() }),
+            (None, None) => Err(MissingLatitudeAndLongitude { document_id: document_id() }),
         },
-        value => Ok(Err(NotAnObject { document_id: debug_id(), value })),
+        Value::Array(mut coordinates) if coordinates.len() == 2 => {
+            let (lat, lng) = (coordinates.pop().unwrap(), coordinates.pop().unwrap());
+            extract_geo_point_from_lat_lng(document_id, lat, lng)
+        }
+        value @ Value::Array(_) => Err(BadGeoPoint { document_id: document_id(), value }),
+        value => Err(NotAnObject { document_id: document_id(), value }),
+    }
+}
+
+/// Extracts the `(latitude, longitude)` from the values of the latitude and the longitude,
+/// they must be finite numbers within the range of the coordinates.
+pub fn extract_geo_point_from_lat_lng(
+    document_id: impl Fn() -> Value,
+    lat: Value,
+    lng: Value,
+) -> StdResult<(f64, f64), GeoError> {
+    use GeoError::*;
+    let (lat, lng) =
+        match (extract_finite_float_from_value(lat), extract_finite_float_from_value(lng)) {
+            (Ok(lat), Ok(lng)) => (lat, lng),
+            (Err(value), Ok(_)) => return Err(BadLatitude { document_id: document_id(), value }),
+            (Ok(_), Err(value)) => return Err(BadLongitude { document_id: document_id(), value }),
+            (Err(lat), Err(lng)) => {
+                return Err(BadLatitudeAndLongitude { document_id: document_id(), lat, lng })
+            }
+        };
+
+    if !(-90.0..=90.0).contains(&lat) {
+        Err(LatitudeOutOfRange { document_id: document_id(), value: lat })
+    } else if !(-180.0..=180.0).contains(&lng) {
+        Err(LongitudeOutOfRange { document_id: document_id(), value: lng })
+    } else {
+        Ok((lat, lng))
     }
 }
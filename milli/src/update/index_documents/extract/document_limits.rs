@@ -0,0 +1,135 @@
+use std::fs::File;
+use std::sync::{Arc, Mutex};
+
+use obkv::{KvReader, KvWriter};
+use serde_json::Value;
+
+use super::helpers::{create_writer, writer_into_reader, GrenadParameters};
+use crate::error::UserError;
+use crate::update::index_documents::IndexingWarning;
+use crate::update::{DocumentLimits, LimitBehavior};
+use crate::{FieldId, FieldsIdsMap, Result};
+
+/// Enforces the [`DocumentLimits`] on the flattened documents before their fields are extracted.
+///
+/// The warnings of the truncated documents are pushed into the shared list, in no particular
+/// order as the chunks of documents are extracted in parallel.
+pub struct DocumentLimitsEnforcer {
+    limits: DocumentLimits,
+    fields_ids_map: FieldsIdsMap,
+    primary_key_id: FieldId,
+    warnings: Arc<Mutex<Vec<IndexingWarning>>>,
+}
+
+impl DocumentLimitsEnforcer {
+    pub fn new(
+        limits: DocumentLimits,
+        fields_ids_map: FieldsIdsMap,
+        primary_key_id: FieldId,
+        warnings: Arc<Mutex<Vec<IndexingWarning>>>,
+    ) -> DocumentLimitsEnforcer {
+        DocumentLimitsEnforcer { limits, fields_ids_map, primary_key_id, warnings }
+    }
+
+    /// Returns the flattened documents with at most `max_fields_per_document` fields each.
+    ///
+    /// A truncated document keeps its primary key and its first fields in the order of
+    /// the fields ids, the other fields are neither searchable nor faceted.
+    pub fn enforce_fields_limit(
+        &self,
+        documents: grenad::Reader<File>,
+        indexer: GrenadParameters,
+    ) -> Result<grenad::Reader<File>> {
+        let limit = match self.limits.max_fields_per_document {
+            Some(limit) => limit,
+            None => return Ok(documents),
+        };
+
+        let mut writer = create_writer(
+            indexer.chunk_compression_type,
+            indexer.chunk_compression_level,
+            tempfile::tempfile()?,
+        );
+
+        let mut buffer = Vec::new();
+        let mut cursor = documents.into_cursor()?;
+        while let Some((docid_bytes, value)) = cursor.move_on_next()? {
+            let obkv = KvReader::<FieldId>::new(value);
+            let fields = obkv.iter().count();
+            if fields <= limit {
+                writer.insert(docid_bytes, value)?;
+                continue;
+            }
+
+            let document_id = self.document_id(obkv);
+            if self.limits.behavior == LimitBehavior::Reject {
+                return Err(UserError::TooManyFields { document_id, fields, limit }.into());
+            }
+
+            buffer.clear();
+            let mut truncated = KvWriter::<_, FieldId>::new(&mut buffer);
+            let mut kept = 0;
+            for (field_id, value) in obkv.iter() {
+                if field_id == self.primary_key_id {
+                    truncated.insert(field_id, value)?;
+                } else if kept < limit.saturating_sub(1) {
+                    truncated.insert(field_id, value)?;
+                    kept += 1;
+                }
+            }
+            truncated.finish()?;
+            writer.insert(docid_bytes, &buffer)?;
+
+            self.warn(IndexingWarning::FieldsTruncated { document_id, fields, limit });
+        }
+
+        writer_into_reader(writer)
+    }
+
+    /// Truncates the facet value of a field of a document to `max_facet_values_per_field`
+    /// values, the elements of an array and a single value are counted as one value each.
+    pub fn enforce_facet_values_limit(
+        &self,
+        obkv: KvReader<FieldId>,
+        field_id: FieldId,
+        value: &mut Value,
+    ) -> Result<()> {
+        let limit = match self.limits.max_facet_values_per_field {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+
+        let values = match value {
+            Value::Array(values) => values.len(),
+            Value::Null => 0,
+            _ => 1,
+        };
+        if values <= limit {
+            return Ok(());
+        }
+
+        let document_id = self.document_id(obkv);
+        let field = self.fields_ids_map.name(field_id).unwrap_or_default().to_string();
+        if self.limits.behavior == LimitBehavior::Reject {
+            return Err(UserError::TooManyFacetValues { document_id, field, values, limit }.into());
+        }
+
+        match value {
+            Value::Array(values) => values.truncate(limit),
+            _ => *value = Value::Null,
+        }
+        self.warn(IndexingWarning::FacetValuesTruncated { document_id, field, values, limit });
+
+        Ok(())
+    }
+
+    fn document_id(&self, obkv: KvReader<FieldId>) -> Value {
+        obkv.get(self.primary_key_id)
+            .and_then(|bytes| serde_json::from_slice(bytes).ok())
+            .unwrap_or(Value::Null)
+    }
+
+    fn warn(&self, warning: IndexingWarning) {
+        self.warnings.lock().unwrap().push(warning);
+    }
+}
@@ -8,13 +8,17 @@ use roaring::RoaringBitmap;
 use serde_json::Value;
 
 use super::helpers::{
-    concat_u32s_array, create_sorter, sorter_into_reader, GrenadParameters, MAX_WORD_LENGTH,
+    create_sorter, sorter_into_reader, GrenadParameters, MergeStrategy, MAX_WORD_LENGTH,
 };
 use crate::error::{InternalError, SerializationError};
-use crate::{absolute_from_relative_position, FieldId, Result, MAX_POSITION_PER_ATTRIBUTE};
+use crate::{
+    absolute_from_relative_position, restore_diacritics, Dictionary, FieldId, Result,
+    MAX_POSITION_PER_ATTRIBUTE,
+};
 
 /// Extracts the word and positions where this word appear and
-/// prefixes it by the document id.
+/// prefixes it by the document id, the words keep their diacritics
+/// when `count_diacritics` is set.
 ///
 /// Returns the generated internal documents ids and a grenad reader
 /// with the list of extracted words from the given chunk of documents.
@@ -24,6 +28,8 @@ pub fn extract_docid_word_positions<R: io::Read + io::Seek>(
     indexer: GrenadParameters,
     searchable_fields: &Option<HashSet<FieldId>>,
     stop_words: Option<&fst::Set<&[u8]>>,
+    dictionary: &Dictionary,
+    count_diacritics: bool,
     max_positions_per_attributes: Option<u32>,
 ) -> Result<(RoaringBitmap, grenad::Reader<File>)> {
     let max_positions_per_attributes = max_positions_per_attributes
@@ -33,7 +39,7 @@ pub fn extract_docid_word_positions<R: io::Read + io::Seek>(
     let mut documents_ids = RoaringBitmap::new();
     let mut docid_word_positions_sorter = create_sorter(
         grenad::SortAlgorithm::Stable,
-        concat_u32s_array,
+        MergeStrategy::CONCAT_U32S_ARRAY,
         indexer.chunk_compression_type,
         indexer.chunk_compression_level,
         indexer.max_nb_chunks,
@@ -66,7 +72,11 @@ pub fn extract_docid_word_positions<R: io::Read + io::Seek>(
                     serde_json::from_slice(field_bytes).map_err(InternalError::SerdeJson)?;
                 field_buffer.clear();
                 if let Some(field) = json_to_string(&value, &mut field_buffer) {
-                    let tokens = process_tokens(tokenizer.tokenize(field))
+                    let mut tokens = dictionary.merge_tokens(field, tokenizer.tokenize(field));
+                    if count_diacritics {
+                        tokens = restore_diacritics(field, tokens);
+                    }
+                    let tokens = process_tokens(tokens.into_iter())
                         .take_while(|(p, _)| (*p as u32) < max_positions_per_attributes);
 
                     for (index, token) in tokens {
@@ -3,9 +3,7 @@ use std::io;
 
 use heed::{BytesDecode, BytesEncode};
 
-use super::helpers::{
-    create_sorter, merge_cbo_roaring_bitmaps, sorter_into_reader, GrenadParameters,
-};
+use super::helpers::{create_sorter, sorter_into_reader, GrenadParameters, MergeStrategy};
 use crate::heed_codec::facet::{FacetLevelValueF64Codec, FieldDocIdFacetF64Codec};
 use crate::Result;
 
@@ -22,7 +20,7 @@ pub fn extract_facet_number_docids<R: io::Read + io::Seek>(
 
     let mut facet_number_docids_sorter = create_sorter(
         grenad::SortAlgorithm::Unstable,
-        merge_cbo_roaring_bitmaps,
+        MergeStrategy::MERGE_CBO_ROARING_BITMAPS,
         indexer.chunk_compression_type,
         indexer.chunk_compression_level,
         indexer.max_nb_chunks,
@@ -5,8 +5,7 @@ use std::{io, str};
 use roaring::RoaringBitmap;
 
 use super::helpers::{
-    create_sorter, keep_first_prefix_value_merge_roaring_bitmaps, sorter_into_reader,
-    try_split_array_at, GrenadParameters,
+    create_sorter, sorter_into_reader, try_split_array_at, GrenadParameters, MergeStrategy,
 };
 use crate::heed_codec::facet::{encode_prefix_string, FacetStringLevelZeroCodec};
 use crate::{FieldId, Result};
@@ -24,7 +23,7 @@ pub fn extract_facet_string_docids<R: io::Read + io::Seek>(
 
     let mut facet_string_docids_sorter = create_sorter(
         grenad::SortAlgorithm::Stable,
-        keep_first_prefix_value_merge_roaring_bitmaps,
+        MergeStrategy::KEEP_SMALLEST_PREFIX_VALUE_MERGE_ROARING_BITMAPS,
         indexer.chunk_compression_type,
         indexer.chunk_compression_level,
         indexer.max_nb_chunks,
@@ -1,17 +1,20 @@
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::TryInto;
 use std::fs::File;
 use std::io;
 use std::mem::size_of;
 
-use heed::zerocopy::AsBytes;
 use heed::BytesEncode;
+use itertools::Itertools;
 use roaring::RoaringBitmap;
 use serde_json::Value;
 
-use super::helpers::{create_sorter, keep_first, sorter_into_reader, GrenadParameters};
+use super::document_limits::DocumentLimitsEnforcer;
+use super::helpers::{create_sorter, sorter_into_reader, GrenadParameters, MergeStrategy};
 use crate::error::InternalError;
-use crate::facet::value_encoding::f64_into_bytes;
+use crate::facet::facet_string_key;
+use crate::facet::value_encoding::{f64_into_bytes, is_lossy_integer};
+use crate::heed_codec::facet::FacetExactIntegersCodec;
 use crate::update::index_documents::{create_writer, writer_into_reader};
 use crate::{CboRoaringBitmapCodec, DocumentId, FieldId, Result, BEU32};
 
@@ -19,17 +22,37 @@ use crate::{CboRoaringBitmapCodec, DocumentId, FieldId, Result, BEU32};
 ///
 /// Returns the generated grenad reader containing the docid the fid and the orginal value as key
 /// and the normalized value as value extracted from the given chunk of documents.
+/// The documents ids for which a field exists, is null or is empty are also returned.
+///
+/// The flattening replaces an object by an empty array, the `nested_fields` of a faceted
+/// field are used to tell such an object apart from a genuine empty array.
+///
+/// The strings of the `coerced_fields` that are finite numbers are also extracted as numbers.
+/// The integers that share their `f64` with another integer are kept exactly in the value
+/// of their number, along with the other integers of the document that share this `f64`.
+/// The values above the limit of facet values per field are never inserted into the sorters.
 #[logging_timer::time]
 pub fn extract_fid_docid_facet_values<R: io::Read + io::Seek>(
     obkv_documents: grenad::Reader<R>,
     indexer: GrenadParameters,
     faceted_fields: &HashSet<FieldId>,
-) -> Result<(grenad::Reader<File>, grenad::Reader<File>, grenad::Reader<File>)> {
+    nested_fields: &HashMap<FieldId, Vec<FieldId>>,
+    coerced_fields: &HashSet<FieldId>,
+    limits: &DocumentLimitsEnforcer,
+) -> Result<(
+    grenad::Reader<File>,
+    grenad::Reader<File>,
+    grenad::Reader<File>,
+    grenad::Reader<File>,
+    grenad::Reader<File>,
+)> {
     let max_memory = indexer.max_memory_by_thread();
 
+    // The keys contain the document id, the values kept are the first ones of
+    // a document and do not depend on the order the chunks are merged in.
     let mut fid_docid_facet_numbers_sorter = create_sorter(
         grenad::SortAlgorithm::Stable,
-        keep_first,
+        MergeStrategy::KEEP_FIRST,
         indexer.chunk_compression_type,
         indexer.chunk_compression_level,
         indexer.max_nb_chunks,
@@ -38,7 +61,7 @@ pub fn extract_fid_docid_facet_values<R: io::Read + io::Seek>(
 
     let mut fid_docid_facet_strings_sorter = create_sorter(
         grenad::SortAlgorithm::Stable,
-        keep_first,
+        MergeStrategy::KEEP_FIRST,
         indexer.chunk_compression_type,
         indexer.chunk_compression_level,
         indexer.max_nb_chunks,
@@ -46,6 +69,8 @@ pub fn extract_fid_docid_facet_values<R: io::Read + io::Seek>(
     );
 
     let mut facet_exists_docids = BTreeMap::<FieldId, RoaringBitmap>::new();
+    let mut facet_is_null_docids = BTreeMap::<FieldId, RoaringBitmap>::new();
+    let mut facet_is_empty_docids = BTreeMap::<FieldId, RoaringBitmap>::new();
 
     let mut key_buffer = Vec::new();
     let mut cursor = obkv_documents.into_cursor()?;
@@ -69,73 +94,146 @@ pub fn extract_fid_docid_facet_values<R: io::Read + io::Seek>(
                 // For the other extraction tasks, prefix the key with the field_id and the document_id
                 key_buffer.extend_from_slice(&docid_bytes);
 
-                let value =
+                let mut value =
                     serde_json::from_slice(field_bytes).map_err(InternalError::SerdeJson)?;
 
-                let (numbers, strings) = extract_facet_values(&value);
+                if value.is_null() {
+                    facet_is_null_docids.entry(field_id).or_default().insert(document);
+                } else if is_empty_value(&value) {
+                    let is_object = nested_fields
+                        .get(&field_id)
+                        .map_or(false, |fids| fids.iter().any(|fid| obkv.get(*fid).is_some()));
+                    if !is_object {
+                        facet_is_empty_docids.entry(field_id).or_default().insert(document);
+                    }
+                }
+
+                limits.enforce_facet_values_limit(obkv, field_id, &mut value)?;
+                let coerce = coerced_fields.contains(&field_id);
+                let (numbers, strings) = extract_facet_values(&value, coerce);
 
-                // insert facet numbers in sorter
-                for number in numbers {
+                // insert facet numbers in sorter, with the exact integers of their f64 if needed
+                let numbers = numbers.into_iter().sorted_by_key(|(number, _)| number.to_bits());
+                for (_, group) in &numbers.group_by(|(number, _)| number.to_bits()) {
+                    let group: Vec<_> = group.collect();
+                    let number = group[0].0;
                     key_buffer.truncate(size_of::<FieldId>() + size_of::<DocumentId>());
                     if let Some(value_bytes) = f64_into_bytes(number) {
                         key_buffer.extend_from_slice(&value_bytes);
                         key_buffer.extend_from_slice(&number.to_be_bytes());
 
-                        fid_docid_facet_numbers_sorter.insert(&key_buffer, ().as_bytes())?;
+                        let mut integers = Vec::new();
+                        if group.iter().any(|(_, integer)| integer.map_or(false, is_lossy_integer))
+                        {
+                            integers = group
+                                .iter()
+                                .map(|(number, integer)| integer.unwrap_or(*number as i128))
+                                .collect();
+                            integers.sort_unstable();
+                            integers.dedup();
+                        }
+                        let integers = FacetExactIntegersCodec::bytes_encode(&integers).unwrap();
+                        fid_docid_facet_numbers_sorter.insert(&key_buffer, integers)?;
                     }
                 }
 
-                // insert  normalized and original facet string in sorter
+                // insert  normalized and original facet string in sorter, the over-long
+                // normalized strings are only kept whole in their original string
                 for (normalized, original) in strings.into_iter().filter(|(n, _)| !n.is_empty()) {
                     key_buffer.truncate(size_of::<FieldId>() + size_of::<DocumentId>());
-                    key_buffer.extend_from_slice(normalized.as_bytes());
+                    key_buffer.extend_from_slice(facet_string_key(&normalized).as_bytes());
                     fid_docid_facet_strings_sorter.insert(&key_buffer, original.as_bytes())?;
                 }
             }
         }
     }
 
-    let mut facet_exists_docids_writer = create_writer(
+    Ok((
+        sorter_into_reader(fid_docid_facet_numbers_sorter, indexer.clone())?,
+        sorter_into_reader(fid_docid_facet_strings_sorter, indexer.clone())?,
+        field_id_docids_into_reader(facet_exists_docids, indexer)?,
+        field_id_docids_into_reader(facet_is_null_docids, indexer)?,
+        field_id_docids_into_reader(facet_is_empty_docids, indexer)?,
+    ))
+}
+
+/// Writes the documents ids of each field id into a grenad reader, keyed by field id.
+fn field_id_docids_into_reader(
+    field_id_docids: BTreeMap<FieldId, RoaringBitmap>,
+    indexer: GrenadParameters,
+) -> Result<grenad::Reader<File>> {
+    let mut writer = create_writer(
         indexer.chunk_compression_type,
         indexer.chunk_compression_level,
         tempfile::tempfile()?,
     );
-    for (fid, bitmap) in facet_exists_docids.into_iter() {
+    for (fid, bitmap) in field_id_docids.into_iter() {
         let bitmap_bytes = CboRoaringBitmapCodec::bytes_encode(&bitmap).unwrap();
-        facet_exists_docids_writer.insert(fid.to_be_bytes(), &bitmap_bytes)?;
+        writer.insert(fid.to_be_bytes(), &bitmap_bytes)?;
     }
-    let facet_exists_docids_reader = writer_into_reader(facet_exists_docids_writer)?;
+    writer_into_reader(writer)
+}
 
-    Ok((
-        sorter_into_reader(fid_docid_facet_numbers_sorter, indexer.clone())?,
-        sorter_into_reader(fid_docid_facet_strings_sorter, indexer.clone())?,
-        facet_exists_docids_reader,
-    ))
+/// An empty string or array, an empty object is considered empty too.
+fn is_empty_value(value: &Value) -> bool {
+    match value {
+        Value::String(string) => string.is_empty(),
+        Value::Array(values) => values.is_empty(),
+        Value::Object(object) => object.is_empty(),
+        Value::Null | Value::Bool(_) | Value::Number(_) => false,
+    }
 }
 
-fn extract_facet_values(value: &Value) -> (Vec<f64>, Vec<(String, String)>) {
+/// Returns the numbers, with their exact value if they are integers, and the strings of a value.
+fn extract_facet_values(
+    value: &Value,
+    coerce: bool,
+) -> (Vec<(f64, Option<i128>)>, Vec<(String, String)>) {
     fn inner_extract_facet_values(
         value: &Value,
         can_recurse: bool,
-        output_numbers: &mut Vec<f64>,
+        coerce: bool,
+        output_numbers: &mut Vec<(f64, Option<i128>)>,
         output_strings: &mut Vec<(String, String)>,
     ) {
         match value {
             Value::Null => (),
-            Value::Bool(b) => output_strings.push((b.to_string(), b.to_string())),
+            // Booleans are stored as numbers to be sorted and as strings to be displayed.
+            Value::Bool(b) => {
+                output_numbers.push(if *b { (1.0, Some(1)) } else { (0.0, Some(0)) });
+                output_strings.push((b.to_string(), b.to_string()));
+            }
             Value::Number(number) => {
                 if let Some(float) = number.as_f64() {
-                    output_numbers.push(float);
+                    let integer =
+                        number.as_i64().map(i128::from).or_else(|| number.as_u64().map(i128::from));
+                    output_numbers.push((float, integer));
                 }
             }
             Value::String(original) => {
+                // The string is kept to be matched by the equality filters.
+                if coerce {
+                    match original.trim().parse::<f64>() {
+                        Ok(float) if float.is_finite() => {
+                            let integer = original.trim().parse::<i128>().ok();
+                            output_numbers.push((float, integer));
+                        }
+                        _ => (),
+                    }
+                }
                 let normalized = original.trim().to_lowercase();
                 output_strings.push((normalized, original.clone()));
             }
             Value::Array(values) => {
                 if can_recurse {
                     for value in values {
-                        inner_extract_facet_values(value, false, output_numbers, output_strings);
+                        inner_extract_facet_values(
+                            value,
+                            false,
+                            coerce,
+                            output_numbers,
+                            output_strings,
+                        );
                     }
                 }
             }
@@ -145,7 +243,13 @@ fn extract_facet_values(value: &Value) -> (Vec<f64>, Vec<(String, String)>) {
 
     let mut facet_number_values = Vec::new();
     let mut facet_string_values = Vec::new();
-    inner_extract_facet_values(value, true, &mut facet_number_values, &mut facet_string_values);
+    inner_extract_facet_values(
+        value,
+        true,
+        coerce,
+        &mut facet_number_values,
+        &mut facet_string_values,
+    );
 
     (facet_number_values, facet_string_values)
 }
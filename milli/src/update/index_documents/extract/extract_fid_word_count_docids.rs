@@ -5,11 +5,12 @@ use std::{cmp, io};
 use grenad::Sorter;
 
 use super::helpers::{
-    create_sorter, merge_cbo_roaring_bitmaps, read_u32_ne_bytes, sorter_into_reader,
-    try_split_array_at, GrenadParameters, MergeFn,
+    create_sorter, read_u32_ne_bytes, sorter_into_reader, try_split_array_at, GrenadParameters,
+    MergeFn, MergeStrategy,
 };
 use crate::error::SerializationError;
 use crate::index::db_name::DOCID_WORD_POSITIONS;
+use crate::position_bucketing::PositionBucketing;
 use crate::{relative_from_absolute_position, DocumentId, FieldId, Result};
 
 /// Extracts the field id word count and the documents ids where
@@ -17,16 +18,18 @@ use crate::{relative_from_absolute_position, DocumentId, FieldId, Result};
 ///
 /// Returns a grenad reader with the list of extracted field id word counts
 /// and documents ids from the given chunk of docid word positions.
+/// The fields with a last position that the position bucketing doesn't store exactly are skipped.
 #[logging_timer::time]
 pub fn extract_fid_word_count_docids<R: io::Read + io::Seek>(
     docid_word_positions: grenad::Reader<R>,
     indexer: GrenadParameters,
+    position_bucketing: PositionBucketing,
 ) -> Result<grenad::Reader<File>> {
     let max_memory = indexer.max_memory_by_thread();
 
     let mut fid_word_count_docids_sorter = create_sorter(
         grenad::SortAlgorithm::Unstable,
-        merge_cbo_roaring_bitmaps,
+        MergeStrategy::MERGE_CBO_ROARING_BITMAPS,
         indexer.chunk_compression_type,
         indexer.chunk_compression_level,
         indexer.max_nb_chunks,
@@ -49,6 +52,7 @@ pub fn extract_fid_word_count_docids<R: io::Read + io::Seek>(
                 &mut fid_word_count_docids_sorter,
                 &mut document_fid_wordcount,
                 curr_document_id,
+                position_bucketing,
             )?;
             current_document_id = Some(document_id);
         }
@@ -69,6 +73,7 @@ pub fn extract_fid_word_count_docids<R: io::Read + io::Seek>(
             &mut fid_word_count_docids_sorter,
             &mut document_fid_wordcount,
             document_id,
+            position_bucketing,
         )?;
     }
 
@@ -79,11 +84,12 @@ fn drain_document_fid_wordcount_into_sorter(
     fid_word_count_docids_sorter: &mut Sorter<MergeFn>,
     document_fid_wordcount: &mut HashMap<FieldId, u32>,
     document_id: DocumentId,
+    position_bucketing: PositionBucketing,
 ) -> Result<()> {
     let mut key_buffer = Vec::new();
 
     for (fid, count) in document_fid_wordcount.drain() {
-        if count <= 10 {
+        if count <= 10 && position_bucketing.is_exact((count - 1) as u16) {
             key_buffer.clear();
             key_buffer.extend_from_slice(&fid.to_be_bytes());
             key_buffer.push(count as u8);
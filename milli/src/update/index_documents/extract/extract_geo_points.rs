@@ -2,14 +2,29 @@ use std::fs::File;
 use std::io;
 
 use concat_arrays::concat_arrays;
-use serde_json::Value;
+use serde_json::{Map, Value};
 
 use super::helpers::{create_writer, writer_into_reader, GrenadParameters};
 use crate::error::GeoError;
-use crate::update::index_documents::extract_finite_float_from_value;
+use crate::update::index_documents::{
+    extract_geo_point_from_lat_lng, extract_geo_point_from_value,
+};
 use crate::{FieldId, InternalError, Result};
 
-/// Extracts the geographical coordinates contained in each document under the `_geo` field.
+/// The ids of the fields of the flattened documents that can contain a `_geo` point.
+#[derive(Debug, Clone, Copy)]
+pub struct GeoFieldsIds {
+    /// The `_geo` field, it contains the `[lng, lat]` arrays.
+    pub geo: FieldId,
+    pub lat: FieldId,
+    pub lng: FieldId,
+    /// The `type` and `coordinates` fields of the GeoJSON points.
+    pub geojson_type: FieldId,
+    pub geojson_coordinates: FieldId,
+}
+
+/// Extracts the geographical coordinates contained in each document under the `_geo` field,
+/// either as an object with `lat` and `lng` fields, a `[lng, lat]` array or a GeoJSON point.
 ///
 /// Returns the generated grenad reader containing the docid as key associated to the (latitude, longitude)
 #[logging_timer::time]
@@ -17,7 +32,7 @@ pub fn extract_geo_points<R: io::Read + io::Seek>(
     obkv_documents: grenad::Reader<R>,
     indexer: GrenadParameters,
     primary_key_id: FieldId,
-    (lat_fid, lng_fid): (FieldId, FieldId),
+    geo_fields_ids: GeoFieldsIds,
 ) -> Result<grenad::Reader<File>> {
     let mut writer = create_writer(
         indexer.chunk_compression_type,
@@ -34,30 +49,51 @@ pub fn extract_geo_points<R: io::Read + io::Seek>(
             let document_id = obkv.get(primary_key_id).unwrap();
             serde_json::from_slice(document_id).unwrap()
         };
+        let get_value = |field_id| -> Result<Option<Value>> {
+            match obkv.get(field_id) {
+                Some(bytes) => {
+                    Ok(Some(serde_json::from_slice(bytes).map_err(InternalError::SerdeJson)?))
+                }
+                None => Ok(None),
+            }
+        };
 
         // first we get the two fields
-        let lat = obkv.get(lat_fid);
-        let lng = obkv.get(lng_fid);
+        let lat = get_value(geo_fields_ids.lat)?;
+        let lng = get_value(geo_fields_ids.lng)?;
 
-        if let Some((lat, lng)) = lat.zip(lng) {
-            // then we extract the values
-            let lat = extract_finite_float_from_value(
-                serde_json::from_slice(lat).map_err(InternalError::SerdeJson)?,
-            )
-            .map_err(|lat| GeoError::BadLatitude { document_id: document_id(), value: lat })?;
-
-            let lng = extract_finite_float_from_value(
-                serde_json::from_slice(lng).map_err(InternalError::SerdeJson)?,
-            )
-            .map_err(|lng| GeoError::BadLongitude { document_id: document_id(), value: lng })?;
+        let point = match (lat, lng) {
+            (Some(lat), Some(lng)) => extract_geo_point_from_lat_lng(document_id, lat, lng)?,
+            (None, Some(_)) => {
+                return Err(GeoError::MissingLatitude { document_id: document_id() })?
+            }
+            (Some(_), None) => {
+                return Err(GeoError::MissingLongitude { document_id: document_id() })?
+            }
+            (None, None) => {
+                // the other shapes are normalized as they were before the flattening
+                let geojson_type = get_value(geo_fields_ids.geojson_type)?;
+                let geojson_coordinates = get_value(geo_fields_ids.geojson_coordinates)?;
+                let value =
+                    match (get_value(geo_fields_ids.geo)?, geojson_type, geojson_coordinates) {
+                        (Some(Value::Null), None, None) | (None, None, None) => continue,
+                        (Some(value), _, _) => value,
+                        (None, kind, coordinates) => {
+                            let mut object = Map::new();
+                            object.extend(kind.map(|kind| ("type".to_string(), kind)));
+                            object.extend(
+                                coordinates.map(|coords| ("coordinates".to_string(), coords)),
+                            );
+                            Value::Object(object)
+                        }
+                    };
+                extract_geo_point_from_value(document_id, value)?
+            }
+        };
 
-            let bytes: [u8; 16] = concat_arrays![lat.to_ne_bytes(), lng.to_ne_bytes()];
-            writer.insert(docid_bytes, bytes)?;
-        } else if lat.is_none() && lng.is_some() {
-            return Err(GeoError::MissingLatitude { document_id: document_id() })?;
-        } else if lat.is_some() && lng.is_none() {
-            return Err(GeoError::MissingLongitude { document_id: document_id() })?;
-        }
+        let (lat, lng) = point;
+        let bytes: [u8; 16] = concat_arrays![lat.to_ne_bytes(), lng.to_ne_bytes()];
+        writer.insert(docid_bytes, bytes)?;
     }
 
     writer_into_reader(writer)
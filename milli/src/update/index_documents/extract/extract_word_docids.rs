@@ -6,8 +6,8 @@ use std::iter::FromIterator;
 use roaring::RoaringBitmap;
 
 use super::helpers::{
-    create_sorter, merge_roaring_bitmaps, serialize_roaring_bitmap, sorter_into_reader,
-    try_split_array_at, GrenadParameters,
+    create_sorter, serialize_roaring_bitmap, sorter_into_reader, try_split_array_at,
+    GrenadParameters, MergeStrategy,
 };
 use crate::error::SerializationError;
 use crate::index::db_name::DOCID_WORD_POSITIONS;
@@ -31,7 +31,7 @@ pub fn extract_word_docids<R: io::Read + io::Seek>(
 
     let mut word_docids_sorter = create_sorter(
         grenad::SortAlgorithm::Unstable,
-        merge_roaring_bitmaps,
+        MergeStrategy::MERGE_ROARING_BITMAPS,
         indexer.chunk_compression_type,
         indexer.chunk_compression_level,
         indexer.max_nb_chunks,
@@ -40,7 +40,7 @@ pub fn extract_word_docids<R: io::Read + io::Seek>(
 
     let mut exact_word_docids_sorter = create_sorter(
         grenad::SortAlgorithm::Unstable,
-        merge_roaring_bitmaps,
+        MergeStrategy::MERGE_ROARING_BITMAPS,
         indexer.chunk_compression_type,
         indexer.chunk_compression_level,
         indexer.max_nb_chunks,
@@ -70,9 +70,11 @@ pub fn extract_word_docids<R: io::Read + io::Seek>(
                     break;
                 }
                 let (fid, _) = relative_from_absolute_position(position);
-                if exact_attributes.contains(&fid) && !added_to_exact {
-                    exact_word_docids_sorter.insert(word_bytes, &value_buffer)?;
-                    added_to_exact = true;
+                if exact_attributes.contains(&fid) {
+                    if !added_to_exact {
+                        exact_word_docids_sorter.insert(word_bytes, &value_buffer)?;
+                        added_to_exact = true;
+                    }
                 } else if !added_to_word_docids {
                     word_docids_sorter.insert(word_bytes, &value_buffer)?;
                     added_to_word_docids = true;
@@ -4,8 +4,8 @@ use std::fs::File;
 use std::{cmp, io, mem, str, vec};
 
 use super::helpers::{
-    create_sorter, merge_cbo_roaring_bitmaps, read_u32_ne_bytes, sorter_into_reader,
-    try_split_array_at, GrenadParameters, MergeFn,
+    create_sorter, read_u32_ne_bytes, sorter_into_reader, try_split_array_at, GrenadParameters,
+    MergeFn, MergeStrategy,
 };
 use crate::error::SerializationError;
 use crate::index::db_name::DOCID_WORD_POSITIONS;
@@ -16,16 +16,18 @@ use crate::{DocumentId, Result};
 ///
 /// Returns a grenad reader with the list of extracted word pairs proximities and
 /// documents ids from the given chunk of docid word positions.
+/// The pairs of words farther away than `max_proximity` are not extracted.
 #[logging_timer::time]
 pub fn extract_word_pair_proximity_docids<R: io::Read + io::Seek>(
     docid_word_positions: grenad::Reader<R>,
     indexer: GrenadParameters,
+    max_proximity: u8,
 ) -> Result<grenad::Reader<File>> {
     let max_memory = indexer.max_memory_by_thread();
 
     let mut word_pair_proximity_docids_sorter = create_sorter(
         grenad::SortAlgorithm::Unstable,
-        merge_cbo_roaring_bitmaps,
+        MergeStrategy::MERGE_CBO_ROARING_BITMAPS,
         indexer.chunk_compression_type,
         indexer.chunk_compression_level,
         indexer.max_nb_chunks,
@@ -50,6 +52,7 @@ pub fn extract_word_pair_proximity_docids<R: io::Read + io::Seek>(
                 curr_document_id,
                 document_word_positions_heap,
                 &mut word_pair_proximity_docids_sorter,
+                max_proximity,
             )?;
             current_document_id = Some(document_id);
         }
@@ -71,13 +74,14 @@ pub fn extract_word_pair_proximity_docids<R: io::Read + io::Seek>(
             document_id,
             document_word_positions_heap,
             &mut word_pair_proximity_docids_sorter,
+            max_proximity,
         )?;
     }
 
     sorter_into_reader(word_pair_proximity_docids_sorter, indexer)
 }
 
-/// Fills the list of all pairs of words with the shortest proximity between 1 and 7 inclusive.
+/// Fills the list of all pairs of words with the shortest proximity between 1 and `max_proximity` inclusive.
 ///
 /// This list is used by the engine to calculate the documents containing words that are
 /// close to each other.
@@ -85,7 +89,9 @@ fn document_word_positions_into_sorter(
     document_id: DocumentId,
     mut word_positions_heap: BinaryHeap<PeekedWordPosition<vec::IntoIter<u32>>>,
     word_pair_proximity_docids_sorter: &mut grenad::Sorter<MergeFn>,
+    max_proximity: u8,
 ) -> Result<()> {
+    let max_proximity = cmp::min(max_proximity as u32, MAX_DISTANCE - 1);
     let mut word_pair_proximity = HashMap::new();
     let mut ordered_peeked_word_positions = Vec::new();
     while !word_positions_heap.is_empty() {
@@ -99,7 +105,7 @@ fn document_word_positions_into_sorter(
         if let Some((head, tail)) = ordered_peeked_word_positions.split_first() {
             for PeekedWordPosition { word, position, .. } in tail {
                 let prox = positions_proximity(head.position, *position);
-                if prox > 0 && prox < MAX_DISTANCE {
+                if prox > 0 && prox <= max_proximity {
                     word_pair_proximity
                         .entry((head.word.clone(), word.clone()))
                         .and_modify(|p| {
@@ -118,7 +124,7 @@ fn document_word_positions_into_sorter(
                 if let Some(next_position) = head.iter.next() {
                     let prox = positions_proximity(head.position, next_position);
 
-                    if prox > 0 && prox < MAX_DISTANCE {
+                    if prox > 0 && prox <= max_proximity {
                         word_pair_proximity
                             .entry((head.word.clone(), head.word.clone()))
                             .and_modify(|p| {
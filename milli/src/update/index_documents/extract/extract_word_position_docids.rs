@@ -2,27 +2,30 @@ use std::fs::File;
 use std::io;
 
 use super::helpers::{
-    create_sorter, merge_cbo_roaring_bitmaps, read_u32_ne_bytes, sorter_into_reader,
-    try_split_array_at, GrenadParameters,
+    create_sorter, read_u32_ne_bytes, sorter_into_reader, try_split_array_at, GrenadParameters,
+    MergeStrategy,
 };
 use crate::error::SerializationError;
 use crate::index::db_name::DOCID_WORD_POSITIONS;
+use crate::position_bucketing::PositionBucketing;
 use crate::{DocumentId, Result};
 
 /// Extracts the word positions and the documents ids where this word appear.
 ///
 /// Returns a grenad reader with the list of extracted words at positions and
 /// documents ids from the given chunk of docid word positions.
+/// The positions are stored in their bucket of the given position bucketing.
 #[logging_timer::time]
 pub fn extract_word_position_docids<R: io::Read + io::Seek>(
     docid_word_positions: grenad::Reader<R>,
     indexer: GrenadParameters,
+    position_bucketing: PositionBucketing,
 ) -> Result<grenad::Reader<File>> {
     let max_memory = indexer.max_memory_by_thread();
 
     let mut word_position_docids_sorter = create_sorter(
         grenad::SortAlgorithm::Unstable,
-        merge_cbo_roaring_bitmaps,
+        MergeStrategy::MERGE_CBO_ROARING_BITMAPS,
         indexer.chunk_compression_type,
         indexer.chunk_compression_level,
         indexer.max_nb_chunks,
@@ -37,6 +40,7 @@ pub fn extract_word_position_docids<R: io::Read + io::Seek>(
         let document_id = DocumentId::from_be_bytes(document_id_bytes);
 
         for position in read_u32_ne_bytes(value) {
+            let position = position_bucketing.bucket_absolute(position);
             key_buffer.clear();
             key_buffer.extend_from_slice(word_bytes);
             key_buffer.extend_from_slice(&position.to_be_bytes());
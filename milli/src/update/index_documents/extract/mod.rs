@@ -1,3 +1,4 @@
+mod document_limits;
 mod extract_docid_word_positions;
 mod extract_facet_number_docids;
 mod extract_facet_string_docids;
@@ -8,84 +9,159 @@ mod extract_word_docids;
 mod extract_word_pair_proximity_docids;
 mod extract_word_position_docids;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
 
 use crossbeam_channel::Sender;
 use log::debug;
 use rayon::prelude::*;
 
+pub(crate) use self::document_limits::DocumentLimitsEnforcer;
 use self::extract_docid_word_positions::extract_docid_word_positions;
 use self::extract_facet_number_docids::extract_facet_number_docids;
 use self::extract_facet_string_docids::extract_facet_string_docids;
 use self::extract_fid_docid_facet_values::extract_fid_docid_facet_values;
 use self::extract_fid_word_count_docids::extract_fid_word_count_docids;
 use self::extract_geo_points::extract_geo_points;
-use self::extract_word_docids::extract_word_docids;
+pub(crate) use self::extract_geo_points::GeoFieldsIds;
+pub(super) use self::extract_word_docids::extract_word_docids;
 use self::extract_word_pair_proximity_docids::extract_word_pair_proximity_docids;
 use self::extract_word_position_docids::extract_word_position_docids;
 use super::helpers::{
-    as_cloneable_grenad, keep_first_prefix_value_merge_roaring_bitmaps, merge_cbo_roaring_bitmaps,
-    merge_roaring_bitmaps, CursorClonableMmap, GrenadParameters, MergeFn, MergeableReader,
+    as_cloneable_grenad, CursorClonableMmap, GrenadParameters, MergeStrategy, MergeableReader,
 };
 use super::{helpers, TypedChunk};
-use crate::{FieldId, Result};
+use crate::error::UserError;
+use crate::position_bucketing::PositionBucketing;
+use crate::proximity::ProximityPrecision;
+use crate::update::UpdateIndexingStep;
+use crate::{Dictionary, FieldId, Result};
 
 /// Extract data for each databases from obkv documents in parallel.
 /// Send data in grenad file over provided Sender.
-pub(crate) fn data_from_obkv_documents(
+/// Reports the number of flattened chunks already extracted through the progress callback.
+/// Stops at the next chunk boundary when `should_abort` returns `true` or `aborted` is raised.
+pub(crate) fn data_from_obkv_documents<F, FA>(
     original_obkv_chunks: impl Iterator<Item = Result<grenad::Reader<File>>> + Send,
-    flattened_obkv_chunks: impl Iterator<Item = Result<grenad::Reader<File>>> + Send,
+    flattened_obkv_chunks: Vec<grenad::Reader<File>>,
+    progress: &F,
+    should_abort: &FA,
+    aborted: Arc<AtomicBool>,
     indexer: GrenadParameters,
     lmdb_writer_sx: Sender<Result<TypedChunk>>,
     searchable_fields: Option<HashSet<FieldId>>,
     faceted_fields: HashSet<FieldId>,
+    nested_fields: HashMap<FieldId, Vec<FieldId>>,
+    coerced_fields: HashSet<FieldId>,
+    limits: &DocumentLimitsEnforcer,
     primary_key_id: FieldId,
-    geo_fields_ids: Option<(FieldId, FieldId)>,
+    geo_fields_ids: Option<GeoFieldsIds>,
     stop_words: Option<fst::Set<&[u8]>>,
+    dictionary: &Dictionary,
+    count_diacritics: bool,
     max_positions_per_attributes: Option<u32>,
     exact_attributes: HashSet<FieldId>,
-) -> Result<()> {
-    original_obkv_chunks
-        .par_bridge()
-        .map(|original_documents_chunk| {
-            send_original_documents_data(original_documents_chunk, lmdb_writer_sx.clone())
-        })
-        .collect::<Result<()>>()?;
+    max_proximity: u8,
+    proximity_precision: ProximityPrecision,
+    position_bucketing: PositionBucketing,
+) -> Result<()>
+where
+    F: Fn(UpdateIndexingStep) + Sync,
+    FA: Fn() -> bool + Sync,
+{
+    let check_abort = || -> Result<()> {
+        if aborted.load(Ordering::Relaxed) || should_abort() {
+            aborted.store(true, Ordering::Relaxed);
+            Err(UserError::IndexingAborted.into())
+        } else {
+            Ok(())
+        }
+    };
 
-    let result: Result<(Vec<_>, (Vec<_>, (Vec<_>, Vec<_>)))> = flattened_obkv_chunks
-        .par_bridge()
-        .map(|flattened_obkv_chunks| {
-            send_and_extract_flattened_documents_data(
-                flattened_obkv_chunks,
-                indexer,
-                lmdb_writer_sx.clone(),
-                &searchable_fields,
-                &faceted_fields,
-                primary_key_id,
-                geo_fields_ids,
-                &stop_words,
-                max_positions_per_attributes,
-            )
-        })
-        .collect();
+    let send_original_documents = |original_documents_chunk| {
+        check_abort()?;
+        send_original_documents_data(original_documents_chunk, lmdb_writer_sx.clone())
+    };
+    if indexer.deterministic {
+        // The documents compression dictionary is trained on the first chunk written.
+        original_obkv_chunks.map(send_original_documents).collect::<Result<()>>()?;
+    } else {
+        original_obkv_chunks.par_bridge().map(send_original_documents).collect::<Result<()>>()?;
+    }
+
+    let total_chunks = flattened_obkv_chunks.len();
+    let chunks_seen = AtomicUsize::new(0);
+    progress(UpdateIndexingStep::ExtractDocumentsChunks { chunks_seen: 0, total_chunks });
+
+    let result: Result<(Vec<_>, (Vec<_>, (Vec<_>, (Vec<_>, (Vec<_>, Vec<_>)))))> =
+        flattened_obkv_chunks
+            .into_par_iter()
+            .map(|flattened_obkv_chunks| {
+                check_abort()?;
+                let result = send_and_extract_flattened_documents_data(
+                    flattened_obkv_chunks,
+                    indexer,
+                    lmdb_writer_sx.clone(),
+                    &searchable_fields,
+                    &faceted_fields,
+                    &nested_fields,
+                    &coerced_fields,
+                    limits,
+                    primary_key_id,
+                    geo_fields_ids,
+                    &stop_words,
+                    dictionary,
+                    count_diacritics,
+                    max_positions_per_attributes,
+                );
+                let chunks_seen = chunks_seen.fetch_add(1, Ordering::Relaxed) + 1;
+                progress(UpdateIndexingStep::ExtractDocumentsChunks { chunks_seen, total_chunks });
+                result
+            })
+            .collect();
 
     let (
         docid_word_positions_chunks,
         (
             docid_fid_facet_numbers_chunks,
-            (docid_fid_facet_strings_chunks, facet_exists_docids_chunks),
+            (
+                docid_fid_facet_strings_chunks,
+                (
+                    facet_exists_docids_chunks,
+                    (facet_is_null_docids_chunks, facet_is_empty_docids_chunks),
+                ),
+            ),
         ),
     ) = result?;
 
-    // merge facet_exists_docids and send them as a typed chunk
-    {
+    // merge facet_exists_docids, facet_is_null_docids and facet_is_empty_docids
+    // and send them as typed chunks
+    let field_id_docids_chunks: [(Vec<_>, fn(grenad::Reader<File>) -> TypedChunk, &str); 3] = [
+        (
+            facet_exists_docids_chunks,
+            TypedChunk::FieldIdFacetExistsDocids,
+            "facet-id-exists-docids",
+        ),
+        (
+            facet_is_null_docids_chunks,
+            TypedChunk::FieldIdFacetIsNullDocids,
+            "facet-id-is-null-docids",
+        ),
+        (
+            facet_is_empty_docids_chunks,
+            TypedChunk::FieldIdFacetIsEmptyDocids,
+            "facet-id-is-empty-docids",
+        ),
+    ];
+    for (chunks, serialize_fn, name) in IntoIterator::into_iter(field_id_docids_chunks) {
         let lmdb_writer_sx = lmdb_writer_sx.clone();
         rayon::spawn(move || {
-            debug!("merge {} database", "facet-id-exists-docids");
-            match facet_exists_docids_chunks.merge(merge_cbo_roaring_bitmaps, &indexer) {
+            debug!("merge {} database", name);
+            match chunks.merge(MergeStrategy::MERGE_CBO_ROARING_BITMAPS, &indexer) {
                 Ok(reader) => {
-                    let _ = lmdb_writer_sx.send(Ok(TypedChunk::FieldIdFacetExistsDocids(reader)));
+                    let _ = lmdb_writer_sx.send(Ok(serialize_fn(reader)));
                 }
                 Err(e) => {
                     let _ = lmdb_writer_sx.send(Err(e));
@@ -94,22 +170,32 @@ pub(crate) fn data_from_obkv_documents(
         });
     }
 
-    spawn_extraction_task::<_, _, Vec<grenad::Reader<File>>>(
-        docid_word_positions_chunks.clone(),
-        indexer,
-        lmdb_writer_sx.clone(),
-        extract_word_pair_proximity_docids,
-        merge_cbo_roaring_bitmaps,
-        TypedChunk::WordPairProximityDocids,
-        "word-pair-proximity-docids",
-    );
+    // The word pair proximity database is not built when only the attributes the words
+    // appear in are used to measure the proximity between them.
+    if proximity_precision == ProximityPrecision::ByWord {
+        spawn_extraction_task::<_, _, Vec<grenad::Reader<File>>>(
+            docid_word_positions_chunks.clone(),
+            indexer,
+            lmdb_writer_sx.clone(),
+            aborted.clone(),
+            move |doc_word_pos, indexer| {
+                extract_word_pair_proximity_docids(doc_word_pos, indexer, max_proximity)
+            },
+            MergeStrategy::MERGE_CBO_ROARING_BITMAPS,
+            TypedChunk::WordPairProximityDocids,
+            "word-pair-proximity-docids",
+        );
+    }
 
     spawn_extraction_task::<_, _, Vec<grenad::Reader<File>>>(
         docid_word_positions_chunks.clone(),
         indexer,
         lmdb_writer_sx.clone(),
-        extract_fid_word_count_docids,
-        merge_cbo_roaring_bitmaps,
+        aborted.clone(),
+        move |doc_word_pos, indexer| {
+            extract_fid_word_count_docids(doc_word_pos, indexer, position_bucketing)
+        },
+        MergeStrategy::MERGE_CBO_ROARING_BITMAPS,
         TypedChunk::FieldIdWordcountDocids,
         "field-id-wordcount-docids",
     );
@@ -118,11 +204,13 @@ pub(crate) fn data_from_obkv_documents(
         docid_word_positions_chunks.clone(),
         indexer,
         lmdb_writer_sx.clone(),
+        aborted.clone(),
         move |doc_word_pos, indexer| extract_word_docids(doc_word_pos, indexer, &exact_attributes),
-        merge_roaring_bitmaps,
+        MergeStrategy::MERGE_ROARING_BITMAPS,
         |(word_docids_reader, exact_word_docids_reader)| TypedChunk::WordDocids {
             word_docids_reader,
             exact_word_docids_reader,
+            words_fst: None,
         },
         "word-docids",
     );
@@ -131,8 +219,11 @@ pub(crate) fn data_from_obkv_documents(
         docid_word_positions_chunks,
         indexer,
         lmdb_writer_sx.clone(),
-        extract_word_position_docids,
-        merge_cbo_roaring_bitmaps,
+        aborted.clone(),
+        move |doc_word_pos, indexer| {
+            extract_word_position_docids(doc_word_pos, indexer, position_bucketing)
+        },
+        MergeStrategy::MERGE_CBO_ROARING_BITMAPS,
         TypedChunk::WordPositionDocids,
         "word-position-docids",
     );
@@ -141,8 +232,9 @@ pub(crate) fn data_from_obkv_documents(
         docid_fid_facet_strings_chunks,
         indexer,
         lmdb_writer_sx.clone(),
+        aborted.clone(),
         extract_facet_string_docids,
-        keep_first_prefix_value_merge_roaring_bitmaps,
+        MergeStrategy::KEEP_SMALLEST_PREFIX_VALUE_MERGE_ROARING_BITMAPS,
         TypedChunk::FieldIdFacetStringDocids,
         "field-id-facet-string-docids",
     );
@@ -151,8 +243,9 @@ pub(crate) fn data_from_obkv_documents(
         docid_fid_facet_numbers_chunks,
         indexer,
         lmdb_writer_sx.clone(),
+        aborted.clone(),
         extract_facet_number_docids,
-        merge_cbo_roaring_bitmaps,
+        MergeStrategy::MERGE_CBO_ROARING_BITMAPS,
         TypedChunk::FieldIdFacetNumberDocids,
         "field-id-facet-number-docids",
     );
@@ -161,15 +254,17 @@ pub(crate) fn data_from_obkv_documents(
 }
 
 /// Spawn a new task to extract data for a specific DB using extract_fn.
-/// Generated grenad chunks are merged using the merge_fn.
+/// Generated grenad chunks are merged using the merge strategy.
 /// The result of merged chunks is serialized as TypedChunk using the serialize_fn
 /// and sent into lmdb_writer_sx.
+/// The task stops extracting chunks as soon as the aborted flag is raised.
 fn spawn_extraction_task<FE, FS, M>(
     chunks: Vec<grenad::Reader<CursorClonableMmap>>,
     indexer: GrenadParameters,
     lmdb_writer_sx: Sender<Result<TypedChunk>>,
+    aborted: Arc<AtomicBool>,
     extract_fn: FE,
-    merge_fn: MergeFn,
+    merge: MergeStrategy,
     serialize_fn: FS,
     name: &'static str,
 ) where
@@ -182,12 +277,23 @@ fn spawn_extraction_task<FE, FS, M>(
     M::Output: Send,
 {
     rayon::spawn(move || {
-        let chunks: Result<M> =
-            chunks.into_par_iter().map(|chunk| extract_fn(chunk, indexer)).collect();
+        let chunks: Result<M> = chunks
+            .into_par_iter()
+            .map(|chunk| {
+                if aborted.load(Ordering::Relaxed) {
+                    Err(UserError::IndexingAborted.into())
+                } else {
+                    extract_fn(chunk, indexer)
+                }
+            })
+            .collect();
         rayon::spawn(move || match chunks {
+            Ok(_) if aborted.load(Ordering::Relaxed) => {
+                let _ = lmdb_writer_sx.send(Err(UserError::IndexingAborted.into()));
+            }
             Ok(chunks) => {
                 debug!("merge {} database", name);
-                let reader = chunks.merge(merge_fn, &indexer);
+                let reader = chunks.merge(merge, &indexer);
                 let _ = lmdb_writer_sx.send(reader.map(serialize_fn));
             }
             Err(e) => {
@@ -217,25 +323,37 @@ fn send_original_documents_data(
 /// - docid_fid_facet_numbers
 /// - docid_fid_facet_strings
 /// - docid_fid_facet_exists
+/// - docid_fid_facet_is_null
+/// - docid_fid_facet_is_empty
 fn send_and_extract_flattened_documents_data(
-    flattened_documents_chunk: Result<grenad::Reader<File>>,
+    flattened_documents_chunk: grenad::Reader<File>,
     indexer: GrenadParameters,
     lmdb_writer_sx: Sender<Result<TypedChunk>>,
     searchable_fields: &Option<HashSet<FieldId>>,
     faceted_fields: &HashSet<FieldId>,
+    nested_fields: &HashMap<FieldId, Vec<FieldId>>,
+    coerced_fields: &HashSet<FieldId>,
+    limits: &DocumentLimitsEnforcer,
     primary_key_id: FieldId,
-    geo_fields_ids: Option<(FieldId, FieldId)>,
+    geo_fields_ids: Option<GeoFieldsIds>,
     stop_words: &Option<fst::Set<&[u8]>>,
+    dictionary: &Dictionary,
+    count_diacritics: bool,
     max_positions_per_attributes: Option<u32>,
 ) -> Result<(
     grenad::Reader<CursorClonableMmap>,
     (
         grenad::Reader<CursorClonableMmap>,
-        (grenad::Reader<CursorClonableMmap>, grenad::Reader<File>),
+        (
+            grenad::Reader<CursorClonableMmap>,
+            (grenad::Reader<File>, (grenad::Reader<File>, grenad::Reader<File>)),
+        ),
     ),
 )> {
+    // The fields above the limit are removed before any of them is extracted.
     let flattened_documents_chunk =
-        flattened_documents_chunk.and_then(|c| unsafe { as_cloneable_grenad(&c) })?;
+        limits.enforce_fields_limit(flattened_documents_chunk, indexer)?;
+    let flattened_documents_chunk = unsafe { as_cloneable_grenad(&flattened_documents_chunk)? };
 
     if let Some(geo_fields_ids) = geo_fields_ids {
         let documents_chunk_cloned = flattened_documents_chunk.clone();
@@ -258,6 +376,8 @@ fn send_and_extract_flattened_documents_data(
                     indexer,
                     searchable_fields,
                     stop_words.as_ref(),
+                    dictionary,
+                    count_diacritics,
                     max_positions_per_attributes,
                 )?;
 
@@ -277,10 +397,15 @@ fn send_and_extract_flattened_documents_data(
                     docid_fid_facet_numbers_chunk,
                     docid_fid_facet_strings_chunk,
                     fid_facet_exists_docids_chunk,
+                    fid_facet_is_null_docids_chunk,
+                    fid_facet_is_empty_docids_chunk,
                 ) = extract_fid_docid_facet_values(
                     flattened_documents_chunk.clone(),
                     indexer,
                     faceted_fields,
+                    nested_fields,
+                    coerced_fields,
+                    limits,
                 )?;
 
                 // send docid_fid_facet_numbers_chunk to DB writer
@@ -301,7 +426,13 @@ fn send_and_extract_flattened_documents_data(
 
                 Ok((
                     docid_fid_facet_numbers_chunk,
-                    (docid_fid_facet_strings_chunk, fid_facet_exists_docids_chunk),
+                    (
+                        docid_fid_facet_strings_chunk,
+                        (
+                            fid_facet_exists_docids_chunk,
+                            (fid_facet_is_null_docids_chunk, fid_facet_is_empty_docids_chunk),
+                        ),
+                    ),
                 ))
             },
         );
@@ -7,7 +7,7 @@ use grenad::{CompressionType, Reader, Sorter};
 use heed::types::ByteSlice;
 use log::debug;
 
-use super::{ClonableMmap, MergeFn};
+use super::{ClonableMmap, MergeFn, MergeStrategy};
 use crate::error::InternalError;
 use crate::Result;
 
@@ -28,13 +28,13 @@ pub fn create_writer<R: io::Write>(
 
 pub fn create_sorter(
     sort_algorithm: grenad::SortAlgorithm,
-    merge: MergeFn,
+    merge: MergeStrategy,
     chunk_compression_type: grenad::CompressionType,
     chunk_compression_level: Option<u32>,
     max_nb_chunks: Option<usize>,
     max_memory: Option<usize>,
 ) -> grenad::Sorter<MergeFn> {
-    let mut builder = grenad::Sorter::builder(merge);
+    let mut builder = grenad::Sorter::builder(merge.function);
     builder.chunk_compression_type(chunk_compression_type);
     if let Some(level) = chunk_compression_level {
         builder.chunk_compression_level(level);
@@ -86,15 +86,22 @@ where
 {
     type Output;
 
-    fn merge(self, merge_fn: MergeFn, indexer: &GrenadParameters) -> Result<Self::Output>;
+    fn merge(self, merge: MergeStrategy, indexer: &GrenadParameters) -> Result<Self::Output>;
 }
 
 impl MergeableReader for Vec<grenad::Reader<File>> {
     type Output = grenad::Reader<File>;
 
-    fn merge(self, merge_fn: MergeFn, params: &GrenadParameters) -> Result<Self::Output> {
-        let mut merger = MergerBuilder::new(merge_fn);
-        self.into_iter().try_for_each(|r| merger.push(r))?;
+    fn merge(self, merge: MergeStrategy, params: &GrenadParameters) -> Result<Self::Output> {
+        let mut readers = self;
+        if params.deterministic {
+            let mut entries = readers.into_iter().map(first_entry).collect::<Result<Vec<_>>>()?;
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            readers = entries.into_iter().map(|(_, reader)| reader).collect();
+        }
+
+        let mut merger = MergerBuilder::new(merge);
+        readers.into_iter().try_for_each(|r| merger.push(r))?;
         merger.finish(params)
     }
 }
@@ -102,10 +109,22 @@ impl MergeableReader for Vec<grenad::Reader<File>> {
 impl MergeableReader for Vec<(grenad::Reader<File>, grenad::Reader<File>)> {
     type Output = (grenad::Reader<File>, grenad::Reader<File>);
 
-    fn merge(self, merge_fn: MergeFn, params: &GrenadParameters) -> Result<Self::Output> {
-        let mut m1 = MergerBuilder::new(merge_fn);
-        let mut m2 = MergerBuilder::new(merge_fn);
-        for (r1, r2) in self.into_iter() {
+    fn merge(self, merge: MergeStrategy, params: &GrenadParameters) -> Result<Self::Output> {
+        let mut readers = self;
+        if params.deterministic {
+            let mut entries = Vec::with_capacity(readers.len());
+            for (r1, r2) in readers {
+                let (e1, r1) = first_entry(r1)?;
+                let (e2, r2) = first_entry(r2)?;
+                entries.push(((e1, e2), (r1, r2)));
+            }
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            readers = entries.into_iter().map(|(_, readers)| readers).collect();
+        }
+
+        let mut m1 = MergerBuilder::new(merge);
+        let mut m2 = MergerBuilder::new(merge);
+        for (r1, r2) in readers {
             m1.push(r1)?;
             m2.push(r2)?;
         }
@@ -113,11 +132,21 @@ impl MergeableReader for Vec<(grenad::Reader<File>, grenad::Reader<File>)> {
     }
 }
 
+/// Returns the first key and value of the reader, the chunks are sorted by them to be merged
+/// in the same order whatever the order the threads extracted them in.
+fn first_entry<R: io::Read + io::Seek>(
+    reader: grenad::Reader<R>,
+) -> Result<(Option<(Vec<u8>, Vec<u8>)>, grenad::Reader<R>)> {
+    let mut cursor = reader.into_cursor()?;
+    let entry = cursor.move_on_first()?.map(|(key, value)| (key.to_vec(), value.to_vec()));
+    Ok((entry, cursor.into_reader()))
+}
+
 struct MergerBuilder<R>(grenad::MergerBuilder<R, MergeFn>);
 
 impl<R: io::Read + io::Seek> MergerBuilder<R> {
-    fn new(merge_fn: MergeFn) -> Self {
-        Self(grenad::MergerBuilder::new(merge_fn))
+    fn new(merge: MergeStrategy) -> Self {
+        Self(grenad::MergerBuilder::new(merge.function))
     }
 
     fn push(&mut self, reader: grenad::Reader<R>) -> Result<()> {
@@ -144,6 +173,11 @@ pub struct GrenadParameters {
     pub chunk_compression_level: Option<u32>,
     pub max_memory: Option<usize>,
     pub max_nb_chunks: Option<usize>,
+    /// The number of threads of the thread pool the extraction runs into,
+    /// the memory budget is divided between them.
+    pub max_threads: Option<usize>,
+    /// Whether the chunks are sorted by their first entry before being merged.
+    pub deterministic: bool,
 }
 
 impl Default for GrenadParameters {
@@ -153,16 +187,19 @@ impl Default for GrenadParameters {
             chunk_compression_level: None,
             max_memory: None,
             max_nb_chunks: None,
+            max_threads: None,
+            deterministic: false,
         }
     }
 }
 
 impl GrenadParameters {
-    /// This function use the number of threads in the current threadpool to compute the value.
-    /// This should be called inside of a rayon thread pool,
-    /// Otherwise, it will take the global number of threads.
+    /// This function use the number of threads of the indexing thread pool to compute the value.
+    /// When it is unknown, it falls back to the number of threads in the current threadpool,
+    /// which is the global one when called outside of a rayon thread pool.
     pub fn max_memory_by_thread(&self) -> Option<usize> {
-        self.max_memory.map(|max_memory| max_memory / rayon::current_num_threads())
+        let threads = self.max_threads.unwrap_or_else(rayon::current_num_threads).max(1);
+        self.max_memory.map(|max_memory| max_memory / threads)
     }
 }
 
@@ -208,22 +245,66 @@ pub fn grenad_obkv_into_chunks<R: io::Read + io::Seek>(
     Ok(std::iter::from_fn(move || transposer().transpose()))
 }
 
+/// Checks that the keys written into a database are strictly increasing.
+///
+/// The readers and the mergers written into LMDB must be sorted, a buggy merge function
+/// emitting out of order keys would otherwise silently break the range scans.
+pub struct KeysOrderValidator {
+    database: &'static str,
+    enabled: bool,
+    previous_key: Option<Vec<u8>>,
+}
+
+impl KeysOrderValidator {
+    pub fn new(database: &'static str, enabled: bool) -> KeysOrderValidator {
+        KeysOrderValidator { database, enabled, previous_key: None }
+    }
+
+    pub fn check(&mut self, key: &[u8]) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        match &mut self.previous_key {
+            Some(previous_key) if previous_key.as_slice() >= key => {
+                Err(InternalError::KeysOutOfOrder {
+                    database: self.database,
+                    previous_key: previous_key.clone(),
+                    key: key.to_vec(),
+                }
+                .into())
+            }
+            Some(previous_key) => {
+                previous_key.clear();
+                previous_key.extend_from_slice(key);
+                Ok(())
+            }
+            None => {
+                self.previous_key = Some(key.to_vec());
+                Ok(())
+            }
+        }
+    }
+}
+
 pub fn write_into_lmdb_database(
     wtxn: &mut heed::RwTxn,
     database: heed::PolyDatabase,
     reader: Reader<File>,
-    merge: MergeFn,
+    merge: MergeStrategy,
+    mut validator: KeysOrderValidator,
 ) -> Result<()> {
     debug!("Writing MTBL stores...");
     let before = Instant::now();
 
     let mut cursor = reader.into_cursor()?;
     while let Some((k, v)) = cursor.move_on_next()? {
+        validator.check(k)?;
         let mut iter = database.prefix_iter_mut::<_, ByteSlice, ByteSlice>(wtxn, k)?;
         match iter.next().transpose()? {
             Some((key, old_val)) if key == k => {
                 let vals = &[Cow::Borrowed(old_val), Cow::Borrowed(v)][..];
-                let val = merge(k, vals)?;
+                let val = merge.merge("get-put-merge", k, vals)?;
                 // safety: we don't keep references from inside the LMDB database.
                 unsafe { iter.put_current(k, &val)? };
             }
@@ -242,7 +323,8 @@ pub fn sorter_into_lmdb_database(
     wtxn: &mut heed::RwTxn,
     database: heed::PolyDatabase,
     sorter: Sorter<MergeFn>,
-    merge: MergeFn,
+    merge: MergeStrategy,
+    mut validator: KeysOrderValidator,
 ) -> Result<()> {
     debug!("Writing MTBL sorter...");
     let before = Instant::now();
@@ -251,19 +333,18 @@ pub fn sorter_into_lmdb_database(
     if database.is_empty(wtxn)? {
         let mut out_iter = database.iter_mut::<_, ByteSlice, ByteSlice>(wtxn)?;
         while let Some((k, v)) = merger_iter.next()? {
+            validator.check(k)?;
             // safety: we don't keep references from inside the LMDB database.
             unsafe { out_iter.append(k, v)? };
         }
     } else {
         while let Some((k, v)) = merger_iter.next()? {
+            validator.check(k)?;
             let mut iter = database.prefix_iter_mut::<_, ByteSlice, ByteSlice>(wtxn, k)?;
             match iter.next().transpose()? {
                 Some((key, old_val)) if key == k => {
                     let vals = vec![Cow::Borrowed(old_val), Cow::Borrowed(v)];
-                    let val = merge(k, &vals).map_err(|_| {
-                        // TODO just wrap this error?
-                        InternalError::IndexingMergingKeys { process: "get-put-merge" }
-                    })?;
+                    let val = merge.merge("get-put-merge", k, &vals)?;
                     // safety: we don't keep references from inside the LMDB database.
                     unsafe { iter.put_current(k, &val)? };
                 }
@@ -283,3 +364,216 @@ pub fn sorter_into_lmdb_database(
 pub fn merge_ignore_values<'a>(_key: &[u8], _values: &[Cow<'a, [u8]>]) -> Result<Cow<'a, [u8]>> {
     Ok(Cow::Owned(Vec::new()))
 }
+
+/// How the keys of a grenad reader are rendered by [`reader_to_ndjson`].
+#[cfg(feature = "debug-tools")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyFormat {
+    /// The key as an UTF-8 string, the invalid sequences are replaced.
+    Utf8Lossy,
+    /// The key as an hexadecimal string.
+    Hex,
+    /// The key as a big-endian `u32` document id.
+    DocumentId,
+    /// The key as a big-endian `u16` field id followed by the facet value, rendered
+    /// as a string when it is valid UTF-8 and as an hexadecimal string otherwise.
+    FacetKey,
+}
+
+/// Writes the entries of a grenad reader as JSON lines, to inspect the intermediate
+/// chunks of an indexing. The values are rendered as hexadecimal strings, along with the
+/// cardinality of the roaring bitmap they encode when they can be decoded as one.
+#[cfg(feature = "debug-tools")]
+pub fn reader_to_ndjson<R: io::Read + io::Seek>(
+    reader: grenad::Reader<R>,
+    key_fmt: KeyFormat,
+    mut out: impl io::Write,
+) -> Result<()> {
+    use std::convert::TryInto;
+
+    use roaring::RoaringBitmap;
+    use serde_json::{json, Value};
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn bitmap_len(bytes: &[u8]) -> Option<u64> {
+        let mut cursor = io::Cursor::new(bytes);
+        let bitmap = RoaringBitmap::deserialize_from(&mut cursor).ok()?;
+        (cursor.position() == bytes.len() as u64).then(|| bitmap.len())
+    }
+
+    let mut cursor = reader.into_cursor()?;
+    while let Some((key, value)) = cursor.move_on_next()? {
+        let key = match key_fmt {
+            KeyFormat::Utf8Lossy => Value::from(String::from_utf8_lossy(key)),
+            KeyFormat::Hex => Value::from(hex(key)),
+            KeyFormat::DocumentId => match key.try_into() {
+                Ok(bytes) => Value::from(u32::from_be_bytes(bytes)),
+                Err(_) => json!({ "invalid": hex(key) }),
+            },
+            KeyFormat::FacetKey => match super::try_split_array_at(key) {
+                Some((field_id, value)) => {
+                    let value = match std::str::from_utf8(value) {
+                        Ok(value) => Value::from(value),
+                        Err(_) => Value::from(hex(value)),
+                    };
+                    json!({ "field_id": u16::from_be_bytes(field_id), "value": value })
+                }
+                None => json!({ "invalid": hex(key) }),
+            },
+        };
+
+        let mut entry = json!({ "key": key, "value": hex(value) });
+        if let Some(len) = bitmap_len(value) {
+            entry["bitmap_len"] = Value::from(len);
+        }
+        serde_json::to_writer(&mut out, &entry).map_err(InternalError::SerdeJson)?;
+        out.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::tests::TempIndex;
+
+    #[test]
+    fn max_memory_is_divided_by_the_indexing_pool_threads() {
+        let indexing_pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+        let params = GrenadParameters {
+            max_memory: Some(16 * 1024),
+            max_threads: Some(indexing_pool.current_num_threads()),
+            ..Default::default()
+        };
+
+        // Emulates a 16-core machine where the budgets are computed from another pool.
+        let other_pool = rayon::ThreadPoolBuilder::new().num_threads(16).build().unwrap();
+        assert_eq!(other_pool.install(|| params.max_memory_by_thread()), Some(8 * 1024));
+        assert_eq!(indexing_pool.install(|| params.max_memory_by_thread()), Some(8 * 1024));
+        assert_eq!(params.max_memory_by_thread(), Some(8 * 1024));
+
+        // Without the indexing pool, the current pool is used.
+        let params = GrenadParameters { max_threads: None, ..params };
+        assert_eq!(other_pool.install(|| params.max_memory_by_thread()), Some(1024));
+    }
+
+    #[test]
+    fn failed_merges_name_the_merge_strategy() {
+        let index = TempIndex::new();
+        let mut wtxn = index.write_txn().unwrap();
+        let database = *index.word_docids.as_polymorph();
+        database.put::<_, ByteSlice, ByteSlice>(&mut wtxn, b"hello", b"world").unwrap();
+
+        let mut writer = create_writer(CompressionType::None, None, tempfile::tempfile().unwrap());
+        writer.insert(b"hello", b"there").unwrap();
+        let reader = writer_into_reader(writer).unwrap();
+
+        let error = write_into_lmdb_database(
+            &mut wtxn,
+            database,
+            reader,
+            MergeStrategy::REFUSE_MERGING,
+            KeysOrderValidator::new("word-docids", true),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "internal: Invalid merge while processing get-put-merge with the `refuse_merging` merge strategy: internal: Invalid merge while processing unique keys with the `refuse_merging` merge strategy..."
+        );
+    }
+
+    #[test]
+    fn out_of_order_keys_are_refused() {
+        // The keys a buggy merge function could emit, out of order.
+        let mut validator = KeysOrderValidator::new("facet-id-string-docids", true);
+        validator.check(b"\x00\x01apple").unwrap();
+        validator.check(b"\x00\x01banana").unwrap();
+        let error = validator.check(b"\x00\x01avocado").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "internal: Keys out of order in the facet-id-string-docids database, the key [0, 1, 97, 118, 111, 99, 97, 100, 111] follows the key [0, 1, 98, 97, 110, 97, 110, 97]."
+        );
+
+        // The same key twice is not strictly increasing either.
+        let mut validator = KeysOrderValidator::new("facet-id-string-docids", true);
+        validator.check(b"apple").unwrap();
+        assert!(validator.check(b"apple").is_err());
+
+        let mut validator = KeysOrderValidator::new("facet-id-string-docids", false);
+        validator.check(b"banana").unwrap();
+        validator.check(b"apple").unwrap();
+    }
+
+    #[test]
+    fn sorted_readers_are_written_with_the_validation() {
+        let index = TempIndex::new();
+        let mut wtxn = index.write_txn().unwrap();
+        let database = *index.word_docids.as_polymorph();
+
+        let mut writer = create_writer(CompressionType::None, None, tempfile::tempfile().unwrap());
+        for key in ["apple", "avocado", "banana"] {
+            writer.insert(key, b"").unwrap();
+        }
+        let reader = writer_into_reader(writer).unwrap();
+        write_into_lmdb_database(
+            &mut wtxn,
+            database,
+            reader,
+            MergeStrategy::REFUSE_MERGING,
+            KeysOrderValidator::new("word-docids", true),
+        )
+        .unwrap();
+        assert_eq!(database.len(&wtxn).unwrap(), 3);
+    }
+
+    #[cfg(feature = "debug-tools")]
+    #[test]
+    fn reader_to_ndjson_renders_every_entry() {
+        use roaring::RoaringBitmap;
+        use serde_json::{json, Value};
+
+        let bitmap: RoaringBitmap = (0..10).collect();
+        let mut bitmap_bytes = Vec::new();
+        bitmap.serialize_into(&mut bitmap_bytes).unwrap();
+
+        let mut writer = create_writer(CompressionType::None, None, tempfile::tempfile().unwrap());
+        writer.insert(1u32.to_be_bytes(), &bitmap_bytes).unwrap();
+        writer.insert(2u32.to_be_bytes(), b"\x00\xff").unwrap();
+        let reader = writer_into_reader(writer).unwrap();
+
+        let mut out = Vec::new();
+        reader_to_ndjson(reader, KeyFormat::DocumentId, &mut out).unwrap();
+        let lines: Vec<Value> = out
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+
+        let bitmap_hex: String = bitmap_bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(
+            lines,
+            vec![
+                json!({ "key": 1, "value": bitmap_hex, "bitmap_len": 10 }),
+                json!({ "key": 2, "value": "00ff" }),
+            ]
+        );
+
+        let mut writer = create_writer(CompressionType::None, None, tempfile::tempfile().unwrap());
+        writer.insert([0, 3, b'r', b'e', b'd'], b"").unwrap();
+        writer.insert([0, 4, 0xff], b"").unwrap();
+        let reader = writer_into_reader(writer).unwrap();
+
+        let mut out = Vec::new();
+        reader_to_ndjson(reader, KeyFormat::FacetKey, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(
+            out,
+            "{\"key\":{\"field_id\":3,\"value\":\"red\"},\"value\":\"\"}\n\
+             {\"key\":{\"field_id\":4,\"value\":\"ff\"},\"value\":\"\"}\n"
+        );
+    }
+}
@@ -1,16 +1,142 @@
 use std::borrow::Cow;
-use std::io;
 use std::result::Result as StdResult;
+use std::{fmt, io};
 
 use roaring::RoaringBitmap;
 
-use super::read_u32_ne_bytes;
+use super::{merge_ignore_values, read_u32_ne_bytes};
+use crate::error::{InternalError, SerializationError};
 use crate::heed_codec::facet::{decode_prefix_string, encode_prefix_string};
 use crate::heed_codec::CboRoaringBitmapCodec;
 use crate::Result;
 
 pub type MergeFn = for<'a> fn(&[u8], &[Cow<'a, [u8]>]) -> Result<Cow<'a, [u8]>>;
 
+/// A merge function along with the name it is reported with when merging keys fails.
+#[derive(Clone, Copy)]
+pub struct MergeStrategy {
+    pub name: &'static str,
+    pub function: MergeFn,
+}
+
+impl MergeStrategy {
+    /// Merges the values of a key, a failure is reported as an invalid merge
+    /// of this strategy while processing the given process, caused by the
+    /// error of the merge function.
+    pub fn merge<'a>(
+        &self,
+        process: &'static str,
+        key: &[u8],
+        values: &[Cow<'a, [u8]>],
+    ) -> Result<Cow<'a, [u8]>> {
+        (self.function)(key, values).map_err(|error| {
+            InternalError::IndexingMergingKeys {
+                process,
+                strategy: self.name,
+                source: Some(Box::new(error)),
+            }
+            .into()
+        })
+    }
+
+    pub fn merging_error(&self, process: &'static str) -> InternalError {
+        InternalError::IndexingMergingKeys { process, strategy: self.name, source: None }
+    }
+}
+
+impl fmt::Debug for MergeStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MergeStrategy").field(&self.name).finish()
+    }
+}
+
+/// Declares a `MergeStrategy` constant named after its merge function.
+///
+/// With the `merge-assertions` feature, the function is wrapped to check
+/// in debug builds that it is associative on a sample of the merged values.
+macro_rules! merge_strategies {
+    ($($strategy:ident => $function:ident),* $(,)?) => {
+        impl MergeStrategy {
+            $(
+                pub const $strategy: MergeStrategy = MergeStrategy {
+                    name: stringify!($function),
+                    #[cfg(not(feature = "merge-assertions"))]
+                    function: $function,
+                    #[cfg(feature = "merge-assertions")]
+                    function: {
+                        fn checked<'a>(
+                            key: &[u8],
+                            values: &[Cow<'a, [u8]>],
+                        ) -> Result<Cow<'a, [u8]>> {
+                            sampled_associativity_check(
+                                stringify!($function),
+                                $function,
+                                key,
+                                values,
+                            );
+                            $function(key, values)
+                        }
+                        checked
+                    },
+                };
+            )*
+        }
+    };
+}
+
+merge_strategies! {
+    CONCAT_U32S_ARRAY => concat_u32s_array,
+    MERGE_ROARING_BITMAPS => merge_roaring_bitmaps,
+    MERGE_CBO_ROARING_BITMAPS => merge_cbo_roaring_bitmaps,
+    KEEP_SMALLEST_PREFIX_VALUE_MERGE_ROARING_BITMAPS => keep_smallest_prefix_value_merge_roaring_bitmaps,
+    KEEP_FIRST => keep_first,
+    KEEP_LATEST_OBKV => keep_latest_obkv,
+    MERGE_OBKVS => merge_obkvs,
+    MERGE_OBKVS_AND_REMOVE_NULLS => merge_obkvs_and_remove_nulls,
+    MERGE_DOCUMENT_VERSIONS => merge_document_versions,
+    MERGE_DOCUMENT_VERSIONS_AND_REMOVE_NULLS => merge_document_versions_and_remove_nulls,
+    MERGE_IGNORE_VALUES => merge_ignore_values,
+    REFUSE_MERGING => refuse_merging,
+}
+
+/// One merge out of this number is checked for associativity.
+#[cfg(feature = "merge-assertions")]
+const ASSOCIATIVITY_CHECK_SAMPLING: usize = 64;
+
+#[cfg(feature = "merge-assertions")]
+fn sampled_associativity_check(name: &str, function: MergeFn, key: &[u8], values: &[Cow<[u8]>]) {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static MERGES: AtomicUsize = AtomicUsize::new(0);
+
+    if cfg!(debug_assertions)
+        && values.len() >= 3
+        && MERGES.fetch_add(1, Ordering::Relaxed) % ASSOCIATIVITY_CHECK_SAMPLING == 0
+    {
+        assert_associative(name, function, key, values);
+    }
+}
+
+/// Checks that merging the merged values but the last one with the last one gives
+/// the same result as merging the first value with the merged values but the first one.
+#[cfg(feature = "merge-assertions")]
+fn assert_associative(name: &str, function: MergeFn, key: &[u8], values: &[Cow<[u8]>]) {
+    let (init, last) = values.split_at(values.len() - 1);
+    let (first, tail) = values.split_at(1);
+    let left = function(key, init).and_then(|init| function(key, &[&[init][..], last].concat()));
+    let right = function(key, tail).and_then(|tail| function(key, &[first, &[tail][..]].concat()));
+
+    // A strategy refusing to merge is associative.
+    if let (Ok(left), Ok(right)) = (left, right) {
+        assert!(
+            left == right,
+            "the `{}` merge strategy is not associative on the values of the key {:?}",
+            name,
+            key,
+        );
+    }
+}
+
 pub fn concat_u32s_array<'a>(_key: &[u8], values: &[Cow<'a, [u8]>]) -> Result<Cow<'a, [u8]>> {
     if values.len() == 1 {
         Ok(values[0].clone())
@@ -49,24 +175,24 @@ pub fn merge_roaring_bitmaps<'a>(_key: &[u8], values: &[Cow<'a, [u8]>]) -> Resul
     }
 }
 
-pub fn keep_first_prefix_value_merge_roaring_bitmaps<'a>(
+/// Merges the bitmaps and keeps the lexicographically smallest prefix string,
+/// so that the kept string doesn't depend on the order of the documents.
+pub fn keep_smallest_prefix_value_merge_roaring_bitmaps<'a>(
     _key: &[u8],
     values: &[Cow<'a, [u8]>],
 ) -> Result<Cow<'a, [u8]>> {
     if values.len() == 1 {
         Ok(values[0].clone())
     } else {
-        let original = decode_prefix_string(&values[0]).unwrap().0;
-        let merged_bitmaps = values
-            .iter()
-            .map(AsRef::as_ref)
-            .map(decode_prefix_string)
-            .map(Option::unwrap)
-            .map(|(_, bitmap_bytes)| bitmap_bytes)
-            .map(RoaringBitmap::deserialize_from)
-            .map(StdResult::unwrap)
-            .reduce(|a, b| a | b)
-            .unwrap();
+        let mut original: Option<&str> = None;
+        let mut merged_bitmaps = RoaringBitmap::new();
+        for value in values {
+            let (string, bitmap_bytes) = decode_prefix_string(value)
+                .ok_or(SerializationError::Decoding { db_name: None })?;
+            original = Some(original.map_or(string, |original| original.min(string)));
+            merged_bitmaps |= RoaringBitmap::deserialize_from(bitmap_bytes)?;
+        }
+        let original = original.unwrap_or_default();
 
         let cap = std::mem::size_of::<u16>() + original.len() + merged_bitmaps.serialized_size();
         let mut buffer = Vec::with_capacity(cap);
@@ -76,6 +202,11 @@ pub fn keep_first_prefix_value_merge_roaring_bitmaps<'a>(
     }
 }
 
+/// Used when the keys are unique and must never be merged.
+pub fn refuse_merging<'a>(_key: &[u8], _values: &[Cow<'a, [u8]>]) -> Result<Cow<'a, [u8]>> {
+    Err(MergeStrategy::REFUSE_MERGING.merging_error("unique keys").into())
+}
+
 pub fn keep_first<'a>(_key: &[u8], values: &[Cow<'a, [u8]>]) -> Result<Cow<'a, [u8]>> {
     Ok(values[0].clone())
 }
@@ -100,7 +231,100 @@ pub fn merge_obkvs<'a>(_key: &[u8], obkvs: &[Cow<'a, [u8]>]) -> Result<Cow<'a, [
         .unwrap())
 }
 
+/// Merge all the obkvs in the order we see them, a field explicitly set to `null`
+/// by a later obkv is removed from the merged obkv instead of being kept as `null`.
+pub fn merge_obkvs_and_remove_nulls<'a>(
+    _key: &[u8],
+    obkvs: &[Cow<'a, [u8]>],
+) -> Result<Cow<'a, [u8]>> {
+    Ok(obkvs
+        .iter()
+        .cloned()
+        .reduce(|acc, current| {
+            let first = obkv::KvReader::new(&acc);
+            let second = obkv::KvReader::new(&current);
+            let mut buffer = Vec::new();
+            merge_two_obkvs_and_remove_nulls(first, second, &mut buffer);
+            Cow::from(buffer)
+        })
+        .unwrap())
+}
+
+/// The first byte of a version of a document that replaces the previous versions.
+pub const REPLACE_DOCUMENT_VERSION: u8 = 0;
+/// The first byte of a version of a document that is merged into the previous versions.
+pub const UPDATE_DOCUMENT_VERSION: u8 = 1;
+
+/// Merges the versions of a document, prefixed by the operation to apply to the previous
+/// versions, in the order we see them. The versions before the last replacing one are ignored.
+pub fn merge_document_versions<'a>(
+    _key: &[u8],
+    versions: &[Cow<'a, [u8]>],
+) -> Result<Cow<'a, [u8]>> {
+    merge_document_versions_inner(versions, false)
+}
+
+/// Merges the versions of a document like [`merge_document_versions`], a field explicitly
+/// set to `null` by an updating version is removed from the replacing version it is merged into.
+pub fn merge_document_versions_and_remove_nulls<'a>(
+    _key: &[u8],
+    versions: &[Cow<'a, [u8]>],
+) -> Result<Cow<'a, [u8]>> {
+    merge_document_versions_inner(versions, true)
+}
+
+fn merge_document_versions_inner<'a>(
+    versions: &[Cow<'a, [u8]>],
+    remove_nulls: bool,
+) -> Result<Cow<'a, [u8]>> {
+    let start = versions
+        .iter()
+        .rposition(|version| version.first() == Some(&REPLACE_DOCUMENT_VERSION))
+        .unwrap_or(0);
+    if start == versions.len() - 1 {
+        return Ok(versions[start].clone());
+    }
+
+    let (&operation, base) = versions[start].split_first().ok_or_else(|| {
+        MergeStrategy::MERGE_DOCUMENT_VERSIONS.merging_error("empty document version")
+    })?;
+    // The nulls of an update merged into another update are kept,
+    // they must unset the fields of the document this update is applied to.
+    let remove_nulls = remove_nulls && operation == REPLACE_DOCUMENT_VERSION;
+
+    let mut document = base.to_vec();
+    let mut buffer = Vec::new();
+    for version in &versions[start + 1..] {
+        let update = version.get(1..).unwrap_or_default();
+        let base = obkv::KvReader::new(&document[..]);
+        merge_two_obkvs_inner(base, obkv::KvReader::new(update), remove_nulls, &mut buffer);
+        std::mem::swap(&mut document, &mut buffer);
+    }
+
+    let mut output = Vec::with_capacity(document.len() + 1);
+    output.push(operation);
+    output.extend_from_slice(&document);
+    Ok(Cow::Owned(output))
+}
+
 pub fn merge_two_obkvs(base: obkv::KvReaderU16, update: obkv::KvReaderU16, buffer: &mut Vec<u8>) {
+    merge_two_obkvs_inner(base, update, false, buffer)
+}
+
+fn merge_two_obkvs_and_remove_nulls(
+    base: obkv::KvReaderU16,
+    update: obkv::KvReaderU16,
+    buffer: &mut Vec<u8>,
+) {
+    merge_two_obkvs_inner(base, update, true, buffer)
+}
+
+fn merge_two_obkvs_inner(
+    base: obkv::KvReaderU16,
+    update: obkv::KvReaderU16,
+    remove_nulls: bool,
+    buffer: &mut Vec<u8>,
+) {
     use itertools::merge_join_by;
     use itertools::EitherOrBoth::{Both, Left, Right};
 
@@ -109,6 +333,8 @@ pub fn merge_two_obkvs(base: obkv::KvReaderU16, update: obkv::KvReaderU16, buffe
     let mut writer = obkv::KvWriter::new(buffer);
     for eob in merge_join_by(base.iter(), update.iter(), |(b, _), (u, _)| b.cmp(u)) {
         match eob {
+            // The update explicitly unsets this field, we do not write it.
+            Both(_, (_, v)) | Right((_, v)) if remove_nulls && is_json_null(v) => (),
             Both(_, (k, v)) | Left((k, v)) | Right((k, v)) => writer.insert(k, v).unwrap(),
         }
     }
@@ -116,6 +342,11 @@ pub fn merge_two_obkvs(base: obkv::KvReaderU16, update: obkv::KvReaderU16, buffe
     writer.finish().unwrap();
 }
 
+/// Returns `true` if the raw JSON value is `null`.
+pub fn is_json_null(value: &[u8]) -> bool {
+    value == b"null"
+}
+
 pub fn merge_cbo_roaring_bitmaps<'a>(
     _key: &[u8],
     values: &[Cow<'a, [u8]>],
@@ -128,3 +359,141 @@ pub fn merge_cbo_roaring_bitmaps<'a>(
         Ok(Cow::from(vec))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merging_errors_name_the_merge_strategy() {
+        let error = MergeStrategy::MERGE_CBO_ROARING_BITMAPS.merging_error("get-put-merge");
+        assert_eq!(
+            error.to_string(),
+            "Invalid merge while processing get-put-merge with the `merge_cbo_roaring_bitmaps` merge strategy."
+        );
+
+        let values = [Cow::Borrowed(&b"hello"[..]), Cow::Borrowed(&b"world"[..])];
+        let error = MergeStrategy::REFUSE_MERGING
+            .merge("facet number levels", b"key", &values)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "internal: Invalid merge while processing facet number levels with the `refuse_merging` merge strategy: internal: Invalid merge while processing unique keys with the `refuse_merging` merge strategy..."
+        );
+    }
+
+    #[test]
+    fn merging_errors_keep_their_source() {
+        let mut prefixed = Vec::new();
+        encode_prefix_string("tea", &mut prefixed).unwrap();
+        (1..3).collect::<RoaringBitmap>().serialize_into(&mut prefixed).unwrap();
+
+        let values = [Cow::Borrowed(&prefixed[..]), Cow::Borrowed(&[0, 42, b't'][..])];
+        let error = MergeStrategy::KEEP_SMALLEST_PREFIX_VALUE_MERGE_ROARING_BITMAPS
+            .merge("get-put-merge", b"key", &values)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "internal: Invalid merge while processing get-put-merge with the `keep_smallest_prefix_value_merge_roaring_bitmaps` merge strategy: internal: decoding failed.."
+        );
+
+        let source = std::error::Error::source(&error).and_then(std::error::Error::source);
+        assert_eq!(source.unwrap().to_string(), "internal: decoding failed.");
+    }
+
+    #[test]
+    fn corrupted_prefixed_values_are_not_merged() {
+        let mut prefixed = Vec::new();
+        encode_prefix_string("tea", &mut prefixed).unwrap();
+        (1..3).collect::<RoaringBitmap>().serialize_into(&mut prefixed).unwrap();
+
+        // The length of the string is longer than the value.
+        let values = [Cow::Borrowed(&prefixed[..]), Cow::Borrowed(&[0, 42, b't'][..])];
+        let error = keep_smallest_prefix_value_merge_roaring_bitmaps(b"key", &values).unwrap_err();
+        assert_eq!(error.to_string(), "internal: decoding failed.");
+    }
+
+    #[cfg(feature = "merge-assertions")]
+    #[test]
+    fn merge_strategies_are_associative() {
+        fn bitmap(docids: &[u32], cbo: bool) -> Vec<u8> {
+            let bitmap: RoaringBitmap = docids.iter().copied().collect();
+            let mut buffer = Vec::new();
+            if cbo {
+                CboRoaringBitmapCodec::serialize_into(&bitmap, &mut buffer);
+            } else {
+                serialize_roaring_bitmap(&bitmap, &mut buffer).unwrap();
+            }
+            buffer
+        }
+
+        fn prefixed_bitmap(original: &str, docids: &[u32]) -> Vec<u8> {
+            let mut buffer = Vec::new();
+            encode_prefix_string(original, &mut buffer).unwrap();
+            buffer.extend(bitmap(docids, false));
+            buffer
+        }
+
+        fn obkv(fields: &[(u16, &str)]) -> Vec<u8> {
+            let mut writer = obkv::KvWriterU16::memory();
+            for (field, value) in fields {
+                writer.insert(*field, value.as_bytes()).unwrap();
+            }
+            writer.into_inner().unwrap()
+        }
+
+        let docids = [&[1, 2][..], &[2, 3, 4, 5, 6, 7, 8, 9], &[0, 100, 1000]];
+        let roaring_bitmaps: Vec<_> = docids.iter().map(|docids| bitmap(docids, false)).collect();
+        let cbo_roaring_bitmaps: Vec<_> =
+            docids.iter().map(|docids| bitmap(docids, true)).collect();
+        let prefixed_bitmaps: Vec<_> = IntoIterator::into_iter(["Tea", "tea", "TEA"])
+            .zip(docids.iter())
+            .map(|(original, docids)| prefixed_bitmap(original, docids))
+            .collect();
+        let obkvs = vec![
+            obkv(&[(0, "1"), (1, "\"kevin\"")]),
+            obkv(&[(1, "\"kevina\""), (2, "\"paris\"")]),
+            obkv(&[(0, "1"), (3, "true")]),
+        ];
+        let document_versions: Vec<Vec<_>> = vec![
+            [&[REPLACE_DOCUMENT_VERSION][..], &obkv(&[(0, "1"), (1, "\"kevin\"")])].concat(),
+            [&[UPDATE_DOCUMENT_VERSION][..], &obkv(&[(1, "null"), (2, "\"paris\"")])].concat(),
+            [&[UPDATE_DOCUMENT_VERSION][..], &obkv(&[(1, "\"kevina\""), (2, "null")])].concat(),
+        ];
+        let u32s_arrays: Vec<_> = docids
+            .iter()
+            .map(|docids| docids.iter().flat_map(|docid| docid.to_ne_bytes()).collect())
+            .collect();
+
+        let cases = [
+            (MergeStrategy::MERGE_ROARING_BITMAPS, &roaring_bitmaps),
+            (MergeStrategy::MERGE_CBO_ROARING_BITMAPS, &cbo_roaring_bitmaps),
+            (MergeStrategy::KEEP_SMALLEST_PREFIX_VALUE_MERGE_ROARING_BITMAPS, &prefixed_bitmaps),
+            (MergeStrategy::CONCAT_U32S_ARRAY, &u32s_arrays),
+            (MergeStrategy::KEEP_FIRST, &obkvs),
+            (MergeStrategy::KEEP_LATEST_OBKV, &obkvs),
+            (MergeStrategy::MERGE_OBKVS, &obkvs),
+            (MergeStrategy::MERGE_DOCUMENT_VERSIONS, &document_versions),
+            (MergeStrategy::MERGE_DOCUMENT_VERSIONS_AND_REMOVE_NULLS, &document_versions),
+            (MergeStrategy::MERGE_IGNORE_VALUES, &obkvs),
+            (MergeStrategy::REFUSE_MERGING, &obkvs),
+        ];
+        for (strategy, values) in IntoIterator::into_iter(cases) {
+            let values: Vec<_> = values.iter().map(|value| Cow::Borrowed(&value[..])).collect();
+            assert_associative(strategy.name, strategy.function, b"key", &values);
+        }
+    }
+
+    #[cfg(feature = "merge-assertions")]
+    #[test]
+    #[should_panic(expected = "the `subtract` merge strategy is not associative")]
+    fn non_associative_merges_are_detected() {
+        fn subtract<'a>(_key: &[u8], values: &[Cow<'a, [u8]>]) -> Result<Cow<'a, [u8]>> {
+            let rest = values[1..].iter().fold(0u8, |sum, value| sum.wrapping_add(value[0]));
+            Ok(Cow::Owned(vec![values[0][0].wrapping_sub(rest)]))
+        }
+
+        let values = [Cow::Borrowed(&[10][..]), Cow::Borrowed(&[3][..]), Cow::Borrowed(&[2][..])];
+        assert_associative("subtract", subtract, b"key", &values);
+    }
+}
@@ -10,12 +10,16 @@ use fst::{IntoStreamer, Streamer};
 pub use grenad_helpers::{
     as_cloneable_grenad, create_sorter, create_writer, grenad_obkv_into_chunks,
     merge_ignore_values, sorter_into_lmdb_database, sorter_into_reader, write_into_lmdb_database,
-    writer_into_reader, GrenadParameters, MergeableReader,
+    writer_into_reader, GrenadParameters, KeysOrderValidator, MergeableReader,
 };
+#[cfg(feature = "debug-tools")]
+pub use grenad_helpers::{reader_to_ndjson, KeyFormat};
 pub use merge_functions::{
-    concat_u32s_array, keep_first, keep_first_prefix_value_merge_roaring_bitmaps, keep_latest_obkv,
-    merge_cbo_roaring_bitmaps, merge_obkvs, merge_roaring_bitmaps, merge_two_obkvs,
-    roaring_bitmap_from_u32s_array, serialize_roaring_bitmap, MergeFn,
+    concat_u32s_array, is_json_null, keep_first, keep_latest_obkv,
+    keep_smallest_prefix_value_merge_roaring_bitmaps, merge_cbo_roaring_bitmaps, merge_obkvs,
+    merge_obkvs_and_remove_nulls, merge_roaring_bitmaps, merge_two_obkvs,
+    roaring_bitmap_from_u32s_array, serialize_roaring_bitmap, MergeFn, MergeStrategy,
+    REPLACE_DOCUMENT_VERSION, UPDATE_DOCUMENT_VERSION,
 };
 
 /// The maximum length a word can be
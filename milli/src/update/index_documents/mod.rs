@@ -4,53 +4,98 @@ mod helpers;
 mod transform;
 mod typed_chunk;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::io::{Cursor, Read, Seek};
 use std::iter::FromIterator;
 use std::num::{NonZeroU32, NonZeroUsize};
 use std::result::Result as StdResult;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 
 use crossbeam_channel::{Receiver, Sender};
-use heed::types::Str;
+use heed::types::{ByteSlice, Str};
 use heed::Database;
 use log::debug;
 use roaring::RoaringBitmap;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use slice_group_by::GroupBy;
-use typed_chunk::{write_typed_chunk_into_index, TypedChunk};
+use typed_chunk::{prepare_typed_chunk, write_typed_chunk_into_index, TypedChunk};
 
 use self::enrich::enrich_documents_batch;
 pub use self::enrich::{
-    extract_finite_float_from_value, validate_document_id, validate_document_id_value,
-    validate_geo_from_json, DocumentId,
+    extract_finite_float_from_value, extract_geo_point_from_lat_lng, extract_geo_point_from_value,
+    validate_document_id, validate_document_id_value, validate_documents_batch,
+    validate_geo_from_json, DocumentId, DocumentValidationError, ValidationReport,
+    MAX_DOCUMENT_ID_LENGTH, MAX_VALIDATION_ERRORS,
 };
+use self::extract::{DocumentLimitsEnforcer, GeoFieldsIds};
 pub use self::helpers::{
     as_cloneable_grenad, create_sorter, create_writer, fst_stream_into_hashset,
     fst_stream_into_vec, merge_cbo_roaring_bitmaps, merge_roaring_bitmaps,
     sorter_into_lmdb_database, valid_lmdb_key, write_into_lmdb_database, writer_into_reader,
-    ClonableMmap, MergeFn,
+    ClonableMmap, KeysOrderValidator, MergeFn, MergeStrategy,
 };
 use self::helpers::{grenad_obkv_into_chunks, GrenadParameters};
+#[cfg(feature = "debug-tools")]
+pub use self::helpers::{reader_to_ndjson, KeyFormat};
 pub use self::transform::{Transform, TransformOutput};
 use crate::documents::{obkv_to_object, DocumentsBatchReader};
 use crate::error::UserError;
+use crate::index::db_name;
 pub use crate::update::index_documents::helpers::CursorClonableMmap;
 use crate::update::{
-    self, Facets, IndexerConfig, PrefixWordPairsProximityDocids, UpdateIndexingStep,
-    WordPrefixDocids, WordPrefixPositionDocids, WordsPrefixesFst,
+    self, ClearDocuments, Facets, IndexerConfig, PrefixWordPairsProximityDocids,
+    UpdateIndexingStep, WordPrefixDocids, WordPrefixPositionDocids, WordsPrefixesFst,
 };
-use crate::{Index, Result, RoaringBitmapCodec};
+use crate::{Dictionary, Index, Result, RoaringBitmapCodec};
 
 static MERGED_DATABASE_COUNT: usize = 7;
-static PREFIX_DATABASE_COUNT: usize = 5;
-static TOTAL_POSTING_DATABASE_COUNT: usize = MERGED_DATABASE_COUNT + PREFIX_DATABASE_COUNT;
+static PREFIX_DATABASE_COUNT: usize = 4;
+
+/// The default ratio of words modified by an update, over all the words of the index,
+/// above which the prefix databases are rebuilt from scratch.
+pub const DEFAULT_PREFIX_REBUILD_RATIO: f64 = 0.5;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DocumentAdditionResult {
     /// The number of documents that were indexed during the update
     pub indexed_documents: u64,
+    /// The number of documents of the update that were identical
+    /// to the stored ones and therefore not indexed again
+    pub unchanged_documents: u64,
     /// The total number of documents in the index after the update
     pub number_of_documents: u64,
+    /// The documents that were only partially indexed, in no particular order
+    #[serde(default)]
+    pub warnings: Vec<IndexingWarning>,
+    /// The documents that were not indexed because they are not in the index, only with
+    /// [`IndexDocumentsMethod::ReplaceExistingDocuments`], or because they would bring
+    /// the index above the maximum number of fields
+    #[serde(default)]
+    pub rejected_documents: Vec<RejectedDocument>,
+}
+
+/// A document that exceeded one of the [`DocumentLimits`](crate::update::DocumentLimits)
+/// and that was indexed without the values above the limit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IndexingWarning {
+    /// Only the first `limit` facet values of the field were indexed.
+    FacetValuesTruncated { document_id: Value, field: String, values: usize, limit: usize },
+    /// Only the primary key and the first fields of the flattened document were indexed.
+    FieldsTruncated { document_id: Value, fields: usize, limit: usize },
+}
+
+/// A document of a [`IndexDocumentsMethod::ReplaceExistingDocuments`] payload
+/// that was not indexed because its id is not in the index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RejectedDocument {
+    /// The position of the payload in the order the payloads were added to the builder.
+    pub payload: usize,
+    /// The position of the document in its payload.
+    pub position: u32,
+    /// The external id of the document.
+    pub document_id: String,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -63,6 +108,10 @@ pub enum IndexDocumentsMethod {
     /// Merge the previous version of the document with the new version,
     /// replacing old attributes values with the new ones and add the new attributes.
     UpdateDocuments,
+
+    /// Replace the previous document with the new one like `ReplaceDocuments`, the documents
+    /// whose id is not already in the index are not indexed and are reported as rejected.
+    ReplaceExistingDocuments,
 }
 
 impl Default for IndexDocumentsMethod {
@@ -71,14 +120,40 @@ impl Default for IndexDocumentsMethod {
     }
 }
 
-pub struct IndexDocuments<'t, 'u, 'i, 'a, F> {
+/// How the ids of the documents without one are generated when `autogenerate_docids` is enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum AutogenerateIds {
+    /// A random UUID v4 is generated for every document, importing
+    /// the same documents twice creates duplicated documents.
+    Uuid,
+
+    /// The id is a UUID derived from the hash of the flattened document with its fields sorted,
+    /// importing the same documents again replaces them instead of creating duplicates.
+    ContentHash,
+}
+
+impl Default for AutogenerateIds {
+    fn default() -> Self {
+        Self::Uuid
+    }
+}
+
+pub struct IndexDocuments<'t, 'u, 'i, 'a, F, FA> {
     wtxn: &'t mut heed::RwTxn<'i, 'u>,
     index: &'i Index,
     config: IndexDocumentsConfig,
     indexer_config: &'a IndexerConfig,
     transform: Option<Transform<'a, 'i>>,
     progress: F,
+    should_abort: FA,
     added_documents: u64,
+    /// The number of documents and of unchanged documents of each payload.
+    payloads: Vec<(u64, u64)>,
+    /// The warnings raised while extracting the documents.
+    warnings: Arc<Mutex<Vec<IndexingWarning>>>,
+    /// The documents rejected by the payloads added to the builder.
+    rejected_documents: Vec<RejectedDocument>,
 }
 
 #[derive(Default, Debug, Clone)]
@@ -86,30 +161,58 @@ pub struct IndexDocumentsConfig {
     pub facet_level_group_size: Option<NonZeroUsize>,
     pub facet_min_level_size: Option<NonZeroUsize>,
     pub words_prefix_threshold: Option<u32>,
+    pub min_prefix_length: Option<usize>,
     pub max_prefix_length: Option<usize>,
+    /// The maximum number of words prefixes, overrides the one of the [`update::PrefixSettings`]
+    /// of the index like the other prefix parameters of this config.
+    pub max_prefixes: Option<usize>,
     pub words_positions_level_group_size: Option<NonZeroU32>,
     pub words_positions_min_level_size: Option<NonZeroU32>,
+    /// The method of the payloads added with [`IndexDocuments::add_documents`].
     pub update_method: IndexDocumentsMethod,
     pub autogenerate_docids: bool,
+    /// How the missing documents ids are generated, only used with `autogenerate_docids`.
+    pub autogenerate_ids: AutogenerateIds,
+    /// When updating documents with `IndexDocumentsMethod::UpdateDocuments`, a top-level
+    /// field explicitly set to `null` is removed from the stored document, and thus from
+    /// all the databases derived from it, instead of being stored as a `null` value.
+    pub unset_fields_on_null: bool,
+    /// The ratio of words modified by the update, over all the words of the index, above which
+    /// the prefix databases are rebuilt from scratch instead of being incrementally updated.
+    /// Defaults to [`DEFAULT_PREFIX_REBUILD_RATIO`].
+    pub prefix_rebuild_ratio: Option<f64>,
+    /// The ratio of facet numbers modified by the update, over all the facet numbers of a field,
+    /// above which the facet number levels of this field are rebuilt from scratch instead of
+    /// being incrementally updated. Defaults to [`crate::update::DEFAULT_FACET_REBUILD_RATIO`].
+    pub facet_rebuild_ratio: Option<f64>,
+    /// The maximum number of fields of the index, overrides the
+    /// [`IndexerConfig::max_fields`] for this documents addition.
+    pub max_fields: Option<usize>,
 }
 
-impl<'t, 'u, 'i, 'a, F> IndexDocuments<'t, 'u, 'i, 'a, F>
+impl<'t, 'u, 'i, 'a, F, FA> IndexDocuments<'t, 'u, 'i, 'a, F, FA>
 where
     F: Fn(UpdateIndexingStep) + Sync,
+    FA: Fn() -> bool + Sync,
 {
+    /// The `should_abort` function is regularly called during the indexing process, when it
+    /// returns `true` the indexing stops and an `UserError::IndexingAborted` error is returned.
+    /// The write transaction must then be aborted by the caller.
     pub fn new(
         wtxn: &'t mut heed::RwTxn<'i, 'u>,
         index: &'i Index,
         indexer_config: &'a IndexerConfig,
         config: IndexDocumentsConfig,
         progress: F,
-    ) -> Result<IndexDocuments<'t, 'u, 'i, 'a, F>> {
+        should_abort: FA,
+    ) -> Result<IndexDocuments<'t, 'u, 'i, 'a, F, FA>> {
         let transform = Some(Transform::new(
             wtxn,
             index,
             indexer_config,
-            config.update_method,
             config.autogenerate_docids,
+            config.unset_fields_on_null,
+            indexer_config.max_fields_overridden_by(config.max_fields),
         )?);
 
         Ok(IndexDocuments {
@@ -117,24 +220,50 @@ where
             config,
             indexer_config,
             progress,
+            should_abort,
             wtxn,
             index,
             added_documents: 0,
+            payloads: Vec::new(),
+            warnings: Arc::default(),
+            rejected_documents: Vec::new(),
         })
     }
 
-    /// Adds a batch of documents to the current builder.
+    /// Adds a batch of documents to the current builder, with the update method of the config.
     ///
     /// Since the documents are progressively added to the writer, a failure will cause only
     /// return an error and not the `IndexDocuments` struct as it is invalid to use it afterward.
     ///
     /// Returns the number of documents added to the builder.
     pub fn add_documents<R: Read + Seek>(
+        self,
+        reader: DocumentsBatchReader<R>,
+    ) -> Result<(Self, StdResult<u64, UserError>)> {
+        let method = self.config.update_method;
+        self.add_documents_with_method(reader, method)
+    }
+
+    /// Adds a batch of documents to the current builder with the given update method.
+    ///
+    /// The payloads are applied in the order they are added: when the same document appears
+    /// in several payloads, the method of each payload applies to the version of the document
+    /// resulting from the previous payloads. A `ReplaceDocuments` payload discards the previous
+    /// versions of the document and an `UpdateDocuments` payload is merged into them, the last
+    /// payload's method wins.
+    ///
+    /// Returns the number of documents added to the builder. The documents that would bring
+    /// the index above the maximum number of fields are rejected and listed in the rejected
+    /// documents of the payload, the others are added and the error of the first rejected
+    /// document is returned.
+    pub fn add_documents_with_method<R: Read + Seek>(
         mut self,
         reader: DocumentsBatchReader<R>,
+        method: IndexDocumentsMethod,
     ) -> Result<(Self, StdResult<u64, UserError>)> {
         // Early return when there is no document to add
         if reader.is_empty() {
+            self.payloads.push((0, 0));
             return Ok((self, Ok(0)));
         }
 
@@ -145,36 +274,98 @@ where
             self.wtxn,
             self.index,
             self.config.autogenerate_docids,
+            self.config.autogenerate_ids,
             reader,
         )? {
             Ok(reader) => reader,
             Err(user_error) => return Ok((self, Err(user_error))),
         };
 
-        let indexed_documents = self
-            .transform
-            .as_mut()
-            .expect("Invalid document addition state")
-            .read_documents(enriched_documents_reader, self.wtxn, &self.progress)?
-            as u64;
+        let transform = self.transform.as_mut().expect("Invalid document addition state");
+        let previously_unchanged = transform.unchanged_documents();
+        let (indexed_documents, fields_limit_error) = transform.read_documents(
+            enriched_documents_reader,
+            method,
+            self.wtxn,
+            &self.progress,
+        )?;
+        let indexed_documents = indexed_documents as u64;
+        let unchanged_documents = (transform.unchanged_documents() - previously_unchanged) as u64;
+
+        let payload = self.payloads.len();
+        let rejected = transform
+            .take_rejected_documents()
+            .into_iter()
+            .map(|(position, document_id)| RejectedDocument { payload, position, document_id });
+        self.rejected_documents.extend(rejected);
 
         self.added_documents += indexed_documents;
+        self.payloads.push((indexed_documents, unchanged_documents));
 
-        Ok((self, Ok(indexed_documents)))
+        match fields_limit_error {
+            Some(error) => Ok((self, Err(error))),
+            None => Ok((self, Ok(indexed_documents))),
+        }
+    }
+
+    /// Runs the checks of [`IndexDocuments::add_documents`] on all the documents of the batch,
+    /// without adding them to the builder and without writing anything into the index.
+    pub fn validate<R: Read + Seek>(
+        &self,
+        reader: DocumentsBatchReader<R>,
+    ) -> Result<ValidationReport> {
+        validate_documents_batch(
+            self.wtxn,
+            self.index,
+            self.config.autogenerate_docids,
+            self.config.autogenerate_ids,
+            reader,
+        )
     }
 
     #[logging_timer::time("IndexDocuments::{}")]
     pub fn execute(mut self) -> Result<DocumentAdditionResult> {
         if self.added_documents == 0 {
+            self.index.end_update(self.wtxn)?;
             let number_of_documents = self.index.number_of_documents(self.wtxn)?;
-            return Ok(DocumentAdditionResult { indexed_documents: 0, number_of_documents });
+            return Ok(DocumentAdditionResult {
+                indexed_documents: 0,
+                unchanged_documents: 0,
+                number_of_documents,
+                warnings: Vec::new(),
+                rejected_documents: self.rejected_documents,
+            });
+        }
+
+        // The word pairs proximities of the documents already in the index must be
+        // recomputed if they were extracted with another maximum proximity.
+        if self.index.max_proximity(self.wtxn)? != self.indexer_config.max_proximity() {
+            self.reindex_documents()?;
         }
+
         let output = self
             .transform
             .take()
             .expect("Invalid document addition state")
             .output_from_sorter(self.wtxn, &self.progress)?;
 
+        let unchanged_documents = output.unchanged_documents as u64;
+        let indexed_documents = output.documents_count as u64 - unchanged_documents;
+
+        // When all the documents are identical to the stored ones
+        // there is nothing to extract nor to write into the index.
+        if output.new_documents_ids.is_empty() {
+            self.index.end_update(self.wtxn)?;
+            let number_of_documents = self.index.number_of_documents(self.wtxn)?;
+            return Ok(DocumentAdditionResult {
+                indexed_documents,
+                unchanged_documents,
+                number_of_documents,
+                warnings: Vec::new(),
+                rejected_documents: self.rejected_documents,
+            });
+        }
+
         let new_facets = output.compute_real_facets(self.wtxn, self.index)?;
         self.index.put_faceted_fields(self.wtxn, &new_facets)?;
 
@@ -190,10 +381,73 @@ where
             )?;
         }
 
-        let indexed_documents = output.documents_count as u64;
+        let warnings = self.warnings.clone();
+        let rejected_documents = std::mem::take(&mut self.rejected_documents);
         let number_of_documents = self.execute_raw(output)?;
+        let warnings = std::mem::take(&mut *warnings.lock().unwrap());
+
+        Ok(DocumentAdditionResult {
+            indexed_documents,
+            unchanged_documents,
+            number_of_documents,
+            warnings,
+            rejected_documents,
+        })
+    }
+
+    /// Executes the update like [`IndexDocuments::execute`] and returns the result of each
+    /// payload successfully added to the builder, in the order they were added.
+    ///
+    /// The warnings are not attributed to the payloads, those of the whole update are
+    /// returned with each payload.
+    pub fn execute_per_payload(mut self) -> Result<Vec<DocumentAdditionResult>> {
+        let payloads = std::mem::take(&mut self.payloads);
+        let DocumentAdditionResult { number_of_documents, warnings, rejected_documents, .. } =
+            self.execute()?;
+        Ok(payloads
+            .into_iter()
+            .enumerate()
+            .map(|(payload, (documents, unchanged_documents))| DocumentAdditionResult {
+                indexed_documents: documents - unchanged_documents,
+                unchanged_documents,
+                number_of_documents,
+                warnings: warnings.clone(),
+                rejected_documents: rejected_documents
+                    .iter()
+                    .filter(|rejected| rejected.payload == payload)
+                    .cloned()
+                    .collect(),
+            })
+            .collect())
+    }
+
+    /// Reindexes all the documents already in the index with the current indexer configuration.
+    fn reindex_documents(&mut self) -> Result<()> {
+        if self.index.number_of_documents(self.wtxn)? == 0 {
+            return Ok(());
+        }
+
+        let fields_ids_map = self.index.fields_ids_map(self.wtxn)?;
+        let max_fields = self.indexer_config.max_fields_overridden_by(self.config.max_fields);
+        let transform =
+            Transform::new(self.wtxn, self.index, self.indexer_config, false, false, max_fields)?;
+        let output =
+            transform.remap_index_documents(self.wtxn, fields_ids_map.clone(), fields_ids_map)?;
+
+        // We clear the full database (words-fst, documents ids and documents content).
+        ClearDocuments::new(self.wtxn, self.index).execute()?;
+
+        let indexing_builder = IndexDocuments::new(
+            self.wtxn,
+            self.index,
+            self.indexer_config,
+            IndexDocumentsConfig { max_fields: self.config.max_fields, ..Default::default() },
+            &self.progress,
+            &self.should_abort,
+        )?;
+        indexing_builder.execute_raw(output)?;
 
-        Ok(DocumentAdditionResult { indexed_documents, number_of_documents })
+        Ok(())
     }
 
     /// Returns the total number of documents in the index after the update.
@@ -201,7 +455,12 @@ where
     pub fn execute_raw(self, output: TransformOutput) -> Result<u64>
     where
         F: Fn(UpdateIndexingStep) + Sync,
+        FA: Fn() -> bool + Sync,
     {
+        if (self.should_abort)() {
+            return Err(UserError::IndexingAborted.into());
+        }
+
         let TransformOutput {
             primary_key,
             fields_ids_map,
@@ -254,20 +513,54 @@ where
             self.index.searchable_fields_ids(self.wtxn)?.map(HashSet::from_iter);
         // get filterable fields for facet databases
         let faceted_fields = self.index.faceted_fields_ids(self.wtxn)?;
-        // get the fid of the `_geo.lat` and `_geo.lng` fields.
+        // get the nested fields of the faceted fields, to tell the empty arrays
+        // from the objects that the flattening replaced by an empty array.
+        let nested_fields: HashMap<_, Vec<_>> = faceted_fields
+            .iter()
+            .filter_map(|&field_id| {
+                let name = fields_ids_map.name(field_id)?;
+                let nested: Vec<_> = fields_ids_map
+                    .iter()
+                    .filter(|&(id, n)| id != field_id && crate::is_faceted_by(n, name))
+                    .map(|(id, _)| id)
+                    .collect();
+                (!nested.is_empty()).then(|| (field_id, nested))
+            })
+            .collect();
+        // get the filterable fields whose numeric strings are also indexed as numbers,
+        // along with the faceted subfields of the filterable objects.
+        let coerced_fields = if self.index.coerce_numeric_strings(self.wtxn)? {
+            let filterable_fields = self.index.filterable_fields(self.wtxn)?;
+            faceted_fields
+                .iter()
+                .copied()
+                .filter(|&field_id| {
+                    fields_ids_map
+                        .name(field_id)
+                        .map_or(false, |name| crate::is_faceted(name, &filterable_fields))
+                })
+                .collect()
+        } else {
+            HashSet::new()
+        };
+        // get the fids of the fields the `_geo` points can be found in.
         let geo_fields_ids = match self.index.fields_ids_map(self.wtxn)?.id("_geo") {
             Some(gfid) => {
                 let is_sortable = self.index.sortable_fields_ids(self.wtxn)?.contains(&gfid);
                 let is_filterable = self.index.filterable_fields_ids(self.wtxn)?.contains(&gfid);
-                // if `_geo` is faceted then we get the `lat` and `lng`
+                // if `_geo` is faceted then we get the `lat` and `lng`, the missing fields are
+                // given ids that no document contains as this fields ids map is not stored.
                 if is_sortable || is_filterable {
-                    let field_ids = self
-                        .index
-                        .fields_ids_map(self.wtxn)?
-                        .insert("_geo.lat")
-                        .zip(self.index.fields_ids_map(self.wtxn)?.insert("_geo.lng"))
-                        .ok_or(UserError::AttributeLimitReached)?;
-                    Some(field_ids)
+                    let mut fields_ids_map = self.index.fields_ids_map(self.wtxn)?;
+                    let mut insert =
+                        |name| fields_ids_map.insert(name).ok_or(UserError::AttributeLimitReached);
+                    Some(GeoFieldsIds {
+                        geo: gfid,
+                        lat: insert("_geo.lat")?,
+                        lng: insert("_geo.lng")?,
+                        geojson_type: insert("_geo.type")?,
+                        geojson_coordinates: insert("_geo.coordinates")?,
+                    })
                 } else {
                     None
                 }
@@ -276,6 +569,8 @@ where
         };
 
         let stop_words = self.index.stop_words(self.wtxn)?;
+        let dictionary = Dictionary::new(self.index.dictionary(self.wtxn)?);
+        let count_diacritics = self.index.count_diacritics(self.wtxn)?;
         let exact_attributes = self.index.exact_attributes_ids(self.wtxn)?;
 
         let pool_params = GrenadParameters {
@@ -283,10 +578,27 @@ where
             chunk_compression_level: self.indexer_config.chunk_compression_level,
             max_memory: self.indexer_config.max_memory,
             max_nb_chunks: self.indexer_config.max_nb_chunks, // default value, may be chosen.
+            // The budgets must be divided by the threads of the pool the extraction runs into,
+            // whatever pool the extraction tasks are spawned from.
+            max_threads: Some(pool.current_num_threads()),
+            deterministic: self.indexer_config.deterministic,
         };
         let documents_chunk_size =
             self.indexer_config.documents_chunk_size.unwrap_or(1024 * 1024 * 4); // 4MiB
         let max_positions_per_attributes = self.indexer_config.max_positions_per_attributes;
+        let max_proximity = self.indexer_config.max_proximity();
+        let proximity_precision = self.index.proximity_precision(self.wtxn)?;
+        let position_bucketing = self.index.position_bucketing(self.wtxn)?;
+        let limits = DocumentLimitsEnforcer::new(
+            self.indexer_config.document_limits,
+            fields_ids_map.clone(),
+            primary_key_id,
+            self.warnings.clone(),
+        );
+
+        // Shared with the extraction tasks that outlive the extraction pipeline,
+        // they stop producing chunks as soon as it is raised.
+        let aborted = Arc::new(AtomicBool::new(false));
 
         // Run extraction pipeline in parallel.
         pool.install(|| {
@@ -299,20 +611,32 @@ where
                 grenad_obkv_into_chunks(flattened_documents, pool_params, documents_chunk_size);
 
             let result = original_chunk_iter.and_then(|original_chunk| {
-                let flattened_chunk = flattened_chunk_iter?;
+                // we collect the flattened chunks to know how many of them must be extracted
+                let flattened_chunks = flattened_chunk_iter?.collect::<Result<Vec<_>>>()?;
                 // extract all databases from the chunked obkv douments
                 extract::data_from_obkv_documents(
                     original_chunk,
-                    flattened_chunk,
+                    flattened_chunks,
+                    &self.progress,
+                    &self.should_abort,
+                    aborted.clone(),
                     pool_params,
                     lmdb_writer_sx.clone(),
                     searchable_fields,
                     faceted_fields,
+                    nested_fields,
+                    coerced_fields,
+                    &limits,
                     primary_key_id,
                     geo_fields_ids,
                     stop_words,
+                    &dictionary,
+                    count_diacritics,
                     max_positions_per_attributes,
                     exact_attributes,
+                    max_proximity,
+                    proximity_precision,
+                    position_bucketing,
                 )
             });
 
@@ -328,6 +652,7 @@ where
         // able to simply insert all the documents even if they already exist in the database.
         if !replaced_documents_ids.is_empty() {
             let mut deletion_builder = update::DeleteDocuments::new(self.wtxn, self.index)?;
+            deletion_builder.validate_ordering = self.indexer_config.validate_ordering;
             debug!("documents to delete {:?}", replaced_documents_ids);
             deletion_builder.delete_documents(&replaced_documents_ids);
             let deleted_documents_count = deletion_builder.execute()?;
@@ -340,23 +665,98 @@ where
         let mut word_pair_proximity_docids = None;
         let mut word_position_docids = None;
         let mut word_docids = None;
-        let mut exact_word_docids = None;
+        let mut facet_number_docids = None;
+
+        (self.progress)(UpdateIndexingStep::IndexDocuments {
+            documents_seen: 0,
+            total_documents: documents_count,
+        });
 
         let mut databases_seen = 0;
         (self.progress)(UpdateIndexingStep::MergeDataIntoFinalDatabase {
             databases_seen,
-            total_databases: TOTAL_POSTING_DATABASE_COUNT,
+            total_databases: MERGED_DATABASE_COUNT,
         });
 
-        for result in lmdb_writer_rx {
+        // The work of the typed chunks that doesn't need the write transaction is done on the
+        // pool while the previous chunks are written. The channel is bounded to not accumulate
+        // the prepared chunks when the writing is slower than the preparation.
+        let documents_dictionary: Option<Arc<[u8]>> =
+            self.index.documents_compression_dictionary(self.wtxn)?.map(Arc::from);
+        let (prepared_chunks_sx, prepared_chunks_rx) =
+            crossbeam_channel::bounded(pool.current_num_threads());
+        let mut typed_chunks_rx = lmdb_writer_rx;
+        let mut extraction_is_done = false;
+        let mut chunks_in_preparation = 0;
+
+        while !extraction_is_done || chunks_in_preparation != 0 {
+            let result = crossbeam_channel::select! {
+                recv(typed_chunks_rx) -> result => match result {
+                    // The chunks are written in the order they are received for the compression
+                    // dictionary to be trained on the same documents from one indexing to the next.
+                    Ok(result) if pool_params.deterministic => Some(result.and_then(|typed_chunk| {
+                        prepare_typed_chunk(typed_chunk, documents_dictionary.as_deref(), pool_params)
+                    })),
+                    Ok(result) => {
+                        let prepared_chunks_sx = prepared_chunks_sx.clone();
+                        let documents_dictionary = documents_dictionary.clone();
+                        let aborted = aborted.clone();
+                        pool.spawn(move || {
+                            let result = match result {
+                                Ok(_) if aborted.load(Ordering::Relaxed) => {
+                                    Err(UserError::IndexingAborted.into())
+                                }
+                                Ok(typed_chunk) => prepare_typed_chunk(
+                                    typed_chunk,
+                                    documents_dictionary.as_deref(),
+                                    pool_params,
+                                ),
+                                Err(e) => Err(e),
+                            };
+                            let _ = prepared_chunks_sx.send(result);
+                        });
+                        chunks_in_preparation += 1;
+                        None
+                    }
+                    Err(_) => {
+                        extraction_is_done = true;
+                        None
+                    }
+                },
+                recv(prepared_chunks_rx) -> result => {
+                    chunks_in_preparation -= 1;
+                    result.ok()
+                }
+            };
+            let result = match result {
+                Some(result) => result,
+                None => {
+                    if extraction_is_done {
+                        // The disconnected channel must not be selected again.
+                        typed_chunks_rx = crossbeam_channel::never();
+                    }
+                    continue;
+                }
+            };
+
+            if (self.should_abort)() {
+                aborted.store(true, Ordering::Relaxed);
+                return Err(UserError::IndexingAborted.into());
+            }
+
             let typed_chunk = match result? {
-                TypedChunk::WordDocids { word_docids_reader, exact_word_docids_reader } => {
+                TypedChunk::WordDocids {
+                    word_docids_reader,
+                    exact_word_docids_reader,
+                    words_fst,
+                } => {
                     let cloneable_chunk = unsafe { as_cloneable_grenad(&word_docids_reader)? };
                     word_docids = Some(cloneable_chunk);
-                    let cloneable_chunk =
-                        unsafe { as_cloneable_grenad(&exact_word_docids_reader)? };
-                    exact_word_docids = Some(cloneable_chunk);
-                    TypedChunk::WordDocids { word_docids_reader, exact_word_docids_reader }
+                    TypedChunk::WordDocids {
+                        word_docids_reader,
+                        exact_word_docids_reader,
+                        words_fst,
+                    }
                 }
                 TypedChunk::WordPairProximityDocids(chunk) => {
                     let cloneable_chunk = unsafe { as_cloneable_grenad(&chunk)? };
@@ -368,11 +768,21 @@ where
                     word_position_docids = Some(cloneable_chunk);
                     TypedChunk::WordPositionDocids(chunk)
                 }
+                TypedChunk::FieldIdFacetNumberDocids(chunk) => {
+                    let cloneable_chunk = unsafe { as_cloneable_grenad(&chunk)? };
+                    facet_number_docids = Some(cloneable_chunk);
+                    TypedChunk::FieldIdFacetNumberDocids(chunk)
+                }
                 otherwise => otherwise,
             };
 
-            let (docids, is_merged_database) =
-                write_typed_chunk_into_index(typed_chunk, self.index, self.wtxn, index_is_empty)?;
+            let (docids, is_merged_database) = write_typed_chunk_into_index(
+                typed_chunk,
+                self.index,
+                self.wtxn,
+                index_is_empty,
+                self.indexer_config.validate_ordering,
+            )?;
             if !docids.is_empty() {
                 final_documents_ids |= docids;
                 let documents_seen_count = final_documents_ids.len();
@@ -389,7 +799,7 @@ where
                 databases_seen += 1;
                 (self.progress)(UpdateIndexingStep::MergeDataIntoFinalDatabase {
                     databases_seen,
-                    total_databases: TOTAL_POSTING_DATABASE_COUNT,
+                    total_databases: MERGED_DATABASE_COUNT,
                 });
             }
         }
@@ -400,17 +810,23 @@ where
         // We write the primary key field id into the main database
         self.index.put_primary_key(self.wtxn, &primary_key)?;
 
+        // We write the maximum proximity stored in the word pair proximity database.
+        self.index.put_max_proximity(self.wtxn, max_proximity)?;
+
         // We write the external documents ids into the main database.
         self.index.put_external_documents_ids(self.wtxn, &external_documents_ids)?;
 
         let all_documents_ids = index_documents_ids | new_documents_ids;
         self.index.put_documents_ids(self.wtxn, &all_documents_ids)?;
 
+        // The prefix databases consume the builder, failing to write them still fails the update.
+        self.index.end_update(self.wtxn)?;
+
         self.execute_prefix_databases(
             word_docids,
-            exact_word_docids,
             word_pair_proximity_docids,
             word_position_docids,
+            facet_number_docids,
         )?;
 
         Ok(all_documents_ids.len())
@@ -420,80 +836,134 @@ where
     pub fn execute_prefix_databases(
         self,
         word_docids: Option<grenad::Reader<CursorClonableMmap>>,
-        exact_word_docids: Option<grenad::Reader<CursorClonableMmap>>,
         word_pair_proximity_docids: Option<grenad::Reader<CursorClonableMmap>>,
         word_position_docids: Option<grenad::Reader<CursorClonableMmap>>,
+        facet_number_docids: Option<grenad::Reader<CursorClonableMmap>>,
     ) -> Result<()>
     where
         F: Fn(UpdateIndexingStep) + Sync,
+        FA: Fn() -> bool + Sync,
     {
-        // Merged databases are already been indexed, we start from this count;
-        let mut databases_seen = MERGED_DATABASE_COUNT;
-
         // Run the facets update operation.
         let mut builder = Facets::new(self.wtxn, self.index);
         builder.chunk_compression_type = self.indexer_config.chunk_compression_type;
         builder.chunk_compression_level = self.indexer_config.chunk_compression_level;
+        builder.validate_ordering = self.indexer_config.validate_ordering;
         if let Some(value) = self.config.facet_level_group_size {
             builder.level_group_size(value);
         }
         if let Some(value) = self.config.facet_min_level_size {
             builder.min_level_size(value);
         }
-        builder.execute()?;
+        if let Some(value) = self.config.facet_rebuild_ratio {
+            builder.rebuild_ratio(value);
+        }
+        if let Some(changes) = facet_number_docids {
+            builder.number_changes(changes);
+        }
+        builder.execute(&self.progress, &self.should_abort)?;
 
-        databases_seen += 1;
-        (self.progress)(UpdateIndexingStep::MergeDataIntoFinalDatabase {
+        let mut databases_seen = 0;
+        (self.progress)(UpdateIndexingStep::ComputePrefixDatabases {
             databases_seen,
-            total_databases: TOTAL_POSTING_DATABASE_COUNT,
+            total_databases: PREFIX_DATABASE_COUNT,
         });
 
+        if (self.should_abort)() {
+            return Err(UserError::IndexingAborted.into());
+        }
+
         let previous_words_prefixes_fst =
             self.index.words_prefixes_fst(self.wtxn)?.map_data(|cow| cow.into_owned())?;
 
         // Run the words prefixes update operation.
+        let previous_prefix_settings = self.index.words_prefixes_settings(self.wtxn)?;
+        let index_prefix_settings = self.index.prefix_settings(self.wtxn)?;
         let mut builder = WordsPrefixesFst::new(self.wtxn, self.index);
+        builder.settings(index_prefix_settings);
         if let Some(value) = self.config.words_prefix_threshold {
             builder.threshold(value);
         }
+        if let Some(value) = self.config.min_prefix_length {
+            builder.min_prefix_length(value);
+        }
         if let Some(value) = self.config.max_prefix_length {
             builder.max_prefix_length(value);
         }
+        if let Some(value) = self.config.max_prefixes {
+            builder.max_prefixes(value);
+        }
+        let prefix_settings = builder.prefix_settings();
         builder.execute()?;
+        self.index.put_words_prefixes_settings(self.wtxn, &prefix_settings)?;
 
         let current_prefix_fst = self.index.words_prefixes_fst(self.wtxn)?;
 
-        // We retrieve the common words between the previous and new prefix word fst.
-        let common_prefix_fst_words = fst_stream_into_vec(
-            previous_words_prefixes_fst.op().add(&current_prefix_fst).intersection(),
-        );
+        // When most of the words were modified by this update it is faster to recompute the
+        // prefix databases from the words databases than to merge the modified words into them.
+        // The prefixes computed with other parameters are not worth merging into.
+        let full_rebuild = prefix_settings != previous_prefix_settings
+            || match word_docids {
+                Some(ref word_docids) => {
+                    let mut cursor = word_docids.clone().into_cursor()?;
+                    let mut modified_words = 0;
+                    while cursor.move_on_next()?.is_some() {
+                        modified_words += 1;
+                    }
+                    let number_of_words = self.index.words_fst(self.wtxn)?.len();
+                    let ratio =
+                        self.config.prefix_rebuild_ratio.unwrap_or(DEFAULT_PREFIX_REBUILD_RATIO);
+                    number_of_words != 0 && modified_words as f64 / number_of_words as f64 > ratio
+                }
+                None => false,
+            };
+
+        let (new_prefix_fst_words, common_prefix_fst_words, del_prefix_fst_words) = if full_rebuild
+        {
+            // Every prefix is computed as a new one in the cleared prefix databases.
+            (fst_stream_into_vec(&current_prefix_fst), Vec::new(), HashSet::new())
+        } else {
+            (
+                // We retrieve the newly added words between the previous and new prefix word fst.
+                fst_stream_into_vec(
+                    current_prefix_fst.op().add(&previous_words_prefixes_fst).difference(),
+                ),
+                // We retrieve the common words between the previous and new prefix word fst.
+                fst_stream_into_vec(
+                    previous_words_prefixes_fst.op().add(&current_prefix_fst).intersection(),
+                ),
+                // We compute the set of prefixes that are no more part of the prefix fst.
+                fst_stream_into_hashset(
+                    previous_words_prefixes_fst.op().add(&current_prefix_fst).difference(),
+                ),
+            )
+        };
+
         let common_prefix_fst_words: Vec<_> = common_prefix_fst_words
             .as_slice()
             .linear_group_by_key(|x| x.chars().next().unwrap())
             .collect();
 
-        // We retrieve the newly added words between the previous and new prefix word fst.
-        let new_prefix_fst_words = fst_stream_into_vec(
-            current_prefix_fst.op().add(&previous_words_prefixes_fst).difference(),
-        );
-
-        // We compute the set of prefixes that are no more part of the prefix fst.
-        let del_prefix_fst_words = fst_stream_into_hashset(
-            previous_words_prefixes_fst.op().add(&current_prefix_fst).difference(),
-        );
-
         databases_seen += 1;
-        (self.progress)(UpdateIndexingStep::MergeDataIntoFinalDatabase {
+        (self.progress)(UpdateIndexingStep::ComputePrefixDatabases {
             databases_seen,
-            total_databases: TOTAL_POSTING_DATABASE_COUNT,
+            total_databases: PREFIX_DATABASE_COUNT,
         });
 
+        if (self.should_abort)() {
+            return Err(UserError::IndexingAborted.into());
+        }
+
         if let Some(word_docids) = word_docids {
+            if full_rebuild {
+                self.index.word_prefix_docids.clear(self.wtxn)?;
+            }
             execute_word_prefix_docids(
                 self.wtxn,
                 word_docids,
                 self.index.word_docids,
                 self.index.word_prefix_docids,
+                db_name::WORD_PREFIX_DOCIDS,
                 self.indexer_config,
                 &new_prefix_fst_words,
                 &common_prefix_fst_words,
@@ -501,26 +971,27 @@ where
             )?;
         }
 
-        if let Some(exact_word_docids) = exact_word_docids {
-            execute_word_prefix_docids(
-                self.wtxn,
-                exact_word_docids,
-                self.index.exact_word_docids,
-                self.index.exact_word_prefix_docids,
-                self.indexer_config,
-                &new_prefix_fst_words,
-                &common_prefix_fst_words,
-                &del_prefix_fst_words,
-            )?;
+        // The words of the exact attributes never match as prefixes, the exact word
+        // prefix docids are no longer computed and the entries of older indexes dropped.
+        if full_rebuild {
+            self.index.exact_word_prefix_docids.clear(self.wtxn)?;
         }
 
         databases_seen += 1;
-        (self.progress)(UpdateIndexingStep::MergeDataIntoFinalDatabase {
+        (self.progress)(UpdateIndexingStep::ComputePrefixDatabases {
             databases_seen,
-            total_databases: TOTAL_POSTING_DATABASE_COUNT,
+            total_databases: PREFIX_DATABASE_COUNT,
         });
 
+        if (self.should_abort)() {
+            return Err(UserError::IndexingAborted.into());
+        }
+
         if let Some(word_pair_proximity_docids) = word_pair_proximity_docids {
+            if full_rebuild {
+                self.index.word_prefix_pair_proximity_docids.clear(self.wtxn)?;
+                self.index.prefix_word_pair_proximity_docids.clear(self.wtxn)?;
+            }
             // Run the word prefix pair proximity docids update operation.
             PrefixWordPairsProximityDocids::new(
                 self.wtxn,
@@ -537,18 +1008,26 @@ where
         }
 
         databases_seen += 1;
-        (self.progress)(UpdateIndexingStep::MergeDataIntoFinalDatabase {
+        (self.progress)(UpdateIndexingStep::ComputePrefixDatabases {
             databases_seen,
-            total_databases: TOTAL_POSTING_DATABASE_COUNT,
+            total_databases: PREFIX_DATABASE_COUNT,
         });
 
+        if (self.should_abort)() {
+            return Err(UserError::IndexingAborted.into());
+        }
+
         if let Some(word_position_docids) = word_position_docids {
+            if full_rebuild {
+                self.index.word_prefix_position_docids.clear(self.wtxn)?;
+            }
             // Run the words prefix position docids update operation.
             let mut builder = WordPrefixPositionDocids::new(self.wtxn, self.index);
             builder.chunk_compression_type = self.indexer_config.chunk_compression_type;
             builder.chunk_compression_level = self.indexer_config.chunk_compression_level;
             builder.max_nb_chunks = self.indexer_config.max_nb_chunks;
             builder.max_memory = self.indexer_config.max_memory;
+            builder.validate_ordering = self.indexer_config.validate_ordering;
             if let Some(value) = self.config.words_positions_level_group_size {
                 builder.level_group_size(value);
             }
@@ -564,21 +1043,89 @@ where
         }
 
         databases_seen += 1;
-        (self.progress)(UpdateIndexingStep::MergeDataIntoFinalDatabase {
+        (self.progress)(UpdateIndexingStep::ComputePrefixDatabases {
             databases_seen,
-            total_databases: TOTAL_POSTING_DATABASE_COUNT,
+            total_databases: PREFIX_DATABASE_COUNT,
         });
 
         Ok(())
     }
 }
 
+/// Rebuilds the word docids and exact word docids databases, along with their prefix
+/// databases, from the docid word positions and the current exact attributes.
+///
+/// The words themselves don't change, there is no need to touch the other databases.
+pub(crate) fn reindex_word_docids(
+    wtxn: &mut heed::RwTxn,
+    index: &Index,
+    indexer_config: &IndexerConfig,
+) -> Result<()> {
+    let exact_attributes = index.exact_attributes_ids(wtxn)?;
+
+    // We write the docid word positions in the format the extraction expects.
+    let mut writer = create_writer(
+        indexer_config.chunk_compression_type,
+        indexer_config.chunk_compression_level,
+        tempfile::tempfile()?,
+    );
+    let mut buffer = Vec::new();
+    for result in index.docid_word_positions.remap_key_type::<ByteSlice>().iter(wtxn)? {
+        let (key, positions) = result?;
+        buffer.clear();
+        positions.iter().for_each(|position| buffer.extend_from_slice(&position.to_ne_bytes()));
+        writer.insert(key, &buffer)?;
+    }
+    let docid_word_positions = writer_into_reader(writer)?;
+
+    let params = GrenadParameters {
+        chunk_compression_type: indexer_config.chunk_compression_type,
+        chunk_compression_level: indexer_config.chunk_compression_level,
+        max_memory: indexer_config.max_memory,
+        max_nb_chunks: indexer_config.max_nb_chunks,
+        max_threads: Some(1),
+        deterministic: indexer_config.deterministic,
+    };
+    let (word_docids, exact_word_docids) =
+        extract::extract_word_docids(docid_word_positions, params, &exact_attributes)?;
+    let word_docids_reader = unsafe { as_cloneable_grenad(&word_docids)? };
+
+    index.word_docids.clear(wtxn)?;
+    index.exact_word_docids.clear(wtxn)?;
+    index.word_prefix_docids.clear(wtxn)?;
+    index.exact_word_prefix_docids.clear(wtxn)?;
+
+    let typed_chunk = TypedChunk::WordDocids {
+        word_docids_reader: word_docids,
+        exact_word_docids_reader: exact_word_docids,
+        words_fst: None,
+    };
+    write_typed_chunk_into_index(typed_chunk, index, wtxn, true, indexer_config.validate_ordering)?;
+
+    // Every prefix is computed as a new one in the cleared prefix databases.
+    let prefix_fst_words = fst_stream_into_vec(&index.words_prefixes_fst(wtxn)?);
+    execute_word_prefix_docids(
+        wtxn,
+        word_docids_reader,
+        index.word_docids,
+        index.word_prefix_docids,
+        db_name::WORD_PREFIX_DOCIDS,
+        indexer_config,
+        &prefix_fst_words,
+        &[],
+        &HashSet::new(),
+    )?;
+
+    Ok(())
+}
+
 /// Run the word prefix docids update operation.
 fn execute_word_prefix_docids(
     txn: &mut heed::RwTxn,
     reader: grenad::Reader<Cursor<ClonableMmap>>,
     word_docids_db: Database<Str, RoaringBitmapCodec>,
     word_prefix_docids_db: Database<Str, RoaringBitmapCodec>,
+    word_prefix_docids_db_name: &'static str,
     indexer_config: &IndexerConfig,
     new_prefix_fst_words: &[String],
     common_prefix_fst_words: &[&[String]],
@@ -590,6 +1137,8 @@ fn execute_word_prefix_docids(
     builder.chunk_compression_level = indexer_config.chunk_compression_level;
     builder.max_nb_chunks = indexer_config.max_nb_chunks;
     builder.max_memory = indexer_config.max_memory;
+    builder.validate_ordering = indexer_config.validate_ordering;
+    builder.database_name = word_prefix_docids_db_name;
     builder.execute(cursor, new_prefix_fst_words, common_prefix_fst_words, del_prefix_fst_words)?;
     Ok(())
 }
@@ -676,8 +1225,9 @@ mod tests {
         // Check that we get only one document from the database.
         let docs = index.documents(&rtxn, Some(0)).unwrap();
         assert_eq!(docs.len(), 1);
-        let (id, doc) = docs[0];
-        assert_eq!(id, 0);
+        let (id, doc) = &docs[0];
+        assert_eq!(*id, 0);
+        let doc = doc.as_obkv();
 
         // Check that this document is equal to the last one sent.
         let mut doc_iter = doc.iter();
@@ -698,8 +1248,9 @@ mod tests {
         // Since the document has been deleted and re-inserted, its internal docid has been incremented to 1
         let docs = index.documents(&rtxn, Some(1)).unwrap();
         assert_eq!(docs.len(), 1);
-        let (id, doc) = docs[0];
-        assert_eq!(id, 1);
+        let (id, doc) = &docs[0];
+        assert_eq!(*id, 1);
+        let doc = doc.as_obkv();
 
         // Check that this document is equal to the last one sent.
         let mut doc_iter = doc.iter();
@@ -710,6 +1261,86 @@ mod tests {
         drop(rtxn);
     }
 
+    #[test]
+    fn document_update_unsets_null_fields() {
+        let mut index = TempIndex::new();
+        index.index_documents_config.update_method = IndexDocumentsMethod::UpdateDocuments;
+        index.index_documents_config.unset_fields_on_null = true;
+
+        index
+            .update_settings(|settings| {
+                settings.set_filterable_fields(hashset! { S("color"), S("size") });
+            })
+            .unwrap();
+
+        index
+            .add_documents(documents!([
+                { "id": 1, "name": "kevin", "color": "red", "size": 12 },
+                { "id": 2, "name": "bob", "color": "red", "size": 42 },
+                { "id": 3, "name": "jean", "color": null }
+            ]))
+            .unwrap();
+
+        // A `null` in a new document has nothing to unset and is not stored.
+        let rtxn = index.read_txn().unwrap();
+        let fields_ids_map = index.fields_ids_map(&rtxn).unwrap();
+        let color_fid = fields_ids_map.id("color").unwrap();
+        let docid = index.external_documents_ids(&rtxn).unwrap().get("3").unwrap();
+        let docs = index.documents(&rtxn, Some(docid)).unwrap();
+        let doc = docs[0].1.as_obkv();
+        assert!(doc.get(color_fid).is_none());
+        drop(rtxn);
+
+        // We unset the color of the first document.
+        index.add_documents(documents!([ { "id": 1, "color": null } ])).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let fields_ids_map = index.fields_ids_map(&rtxn).unwrap();
+        let docid = index.external_documents_ids(&rtxn).unwrap().get("1").unwrap();
+        let docs = index.documents(&rtxn, Some(docid)).unwrap();
+        let doc = docs[0].1.as_obkv();
+        let doc =
+            crate::obkv_to_json(&fields_ids_map.ids().collect::<Vec<_>>(), &fields_ids_map, doc)
+                .unwrap();
+        assert_eq!(
+            doc,
+            serde_json::json!({ "id": 1, "name": "kevin", "size": 12 })
+                .as_object()
+                .unwrap()
+                .clone()
+        );
+
+        // The filters and the facet distribution must not see the removed value anymore.
+        let filter = crate::Filter::from_str("color = red").unwrap().unwrap();
+        let docids = filter.evaluate(&rtxn, &index).unwrap();
+        let bob = index.external_documents_ids(&rtxn).unwrap().get("2").unwrap();
+        assert_eq!(docids.into_iter().collect::<Vec<_>>(), vec![bob]);
+
+        let filter = crate::Filter::from_str("color EXISTS").unwrap().unwrap();
+        let docids = filter.evaluate(&rtxn, &index).unwrap();
+        assert_eq!(docids.into_iter().collect::<Vec<_>>(), vec![bob]);
+
+        let distribution = index
+            .facets_distribution(&rtxn)
+            .candidates(index.documents_ids(&rtxn).unwrap())
+            .execute()
+            .unwrap();
+        assert_eq!(distribution["color"].get("red"), Some(&1));
+
+        // The other fields must be untouched.
+        let filter = crate::Filter::from_str("size = 12").unwrap().unwrap();
+        let docids = filter.evaluate(&rtxn, &index).unwrap();
+        assert_eq!(docids.into_iter().collect::<Vec<_>>(), vec![docid]);
+
+        let crate::SearchResult { documents_ids, .. } =
+            index.search(&rtxn).query("kevin").execute().unwrap();
+        assert_eq!(documents_ids, vec![docid]);
+
+        let field_distribution = index.field_distribution(&rtxn).unwrap();
+        assert_eq!(field_distribution.get("color"), Some(&1));
+        assert_eq!(field_distribution.get("size"), Some(&2));
+    }
+
     #[test]
     fn not_auto_generated_documents_ids() {
         let index = TempIndex::new();
@@ -747,8 +1378,9 @@ mod tests {
         assert_eq!(count, 3);
 
         let docs = index.documents(&rtxn, vec![0, 1, 2]).unwrap();
-        let (_id, obkv) = docs.iter().find(|(_id, kv)| kv.get(0) == Some(br#""kevin""#)).unwrap();
-        let kevin_uuid: String = serde_json::from_slice(&obkv.get(1).unwrap()).unwrap();
+        let (_id, obkv) =
+            docs.iter().find(|(_id, kv)| kv.as_obkv().get(0) == Some(br#""kevin""#)).unwrap();
+        let kevin_uuid: String = serde_json::from_slice(&obkv.as_obkv().get(1).unwrap()).unwrap();
         drop(rtxn);
 
         // Second we send 1 document with the generated uuid, to erase the previous ones.
@@ -761,10 +1393,12 @@ mod tests {
 
         // the document 0 has been deleted and reinserted with the id 3
         let docs = index.documents(&rtxn, vec![1, 2, 3]).unwrap();
-        let kevin_position =
-            docs.iter().position(|(_, d)| d.get(0).unwrap() == br#""updated kevin""#).unwrap();
+        let kevin_position = docs
+            .iter()
+            .position(|(_, d)| d.as_obkv().get(0).unwrap() == br#""updated kevin""#)
+            .unwrap();
         assert_eq!(kevin_position, 2);
-        let (_, doc) = docs[kevin_position];
+        let doc = docs[kevin_position].1.as_obkv();
 
         // Check that this document is equal to the last
         // one sent and that an UUID has been generated.
@@ -804,6 +1438,76 @@ mod tests {
         drop(rtxn);
     }
 
+    #[test]
+    fn content_hash_auto_generated_documents_ids() {
+        use std::io::Cursor;
+
+        use crate::documents::{DocumentsBatchBuilder, DocumentsBatchReader};
+
+        let mut index = TempIndex::new();
+        index.index_documents_config.autogenerate_docids = true;
+        index.index_documents_config.autogenerate_ids = AutogenerateIds::ContentHash;
+
+        let ndjson_documents = |ndjson_content: &str| {
+            let mut builder = DocumentsBatchBuilder::new(Vec::new());
+            for object in
+                serde_json::Deserializer::from_str(ndjson_content).into_iter::<crate::Object>()
+            {
+                builder.append_json_object(&object.unwrap()).unwrap();
+            }
+            let vector = builder.into_inner().unwrap();
+            DocumentsBatchReader::from_reader(Cursor::new(vector)).unwrap()
+        };
+        let generated_ids = |index: &TempIndex, rtxn: &heed::RoTxn| {
+            let external_ids = index.external_documents_ids(rtxn).unwrap().to_hash_map();
+            external_ids.into_keys().collect::<HashSet<_>>()
+        };
+
+        let ndjson_content = r#"
+            { "name": "kevin", "metadata": { "age": 23, "tags": ["a", "b"] } }
+            { "name": "kevina", "metadata": { "age": 21 } }
+            { "name": "benoit" }
+        "#;
+
+        index.add_documents(ndjson_documents(ndjson_content)).unwrap();
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.number_of_documents(&rtxn).unwrap(), 3);
+        let first_generated_ids = generated_ids(&index, &rtxn);
+        drop(rtxn);
+
+        // Importing the same documents again replaces them.
+        index.add_documents(ndjson_documents(ndjson_content)).unwrap();
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.number_of_documents(&rtxn).unwrap(), 3);
+        assert_eq!(generated_ids(&index, &rtxn), first_generated_ids);
+        drop(rtxn);
+
+        // The order of the fields, even nested ones, doesn't change the generated ids.
+        let reordered_ndjson_content = r#"
+            { "metadata": { "tags": ["a", "b"], "age": 23 }, "name": "kevin" }
+            { "metadata": { "age": 21 }, "name": "kevina" }
+            { "name": "benoit" }
+        "#;
+        index.add_documents(ndjson_documents(reordered_ndjson_content)).unwrap();
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.number_of_documents(&rtxn).unwrap(), 3);
+        assert_eq!(generated_ids(&index, &rtxn), first_generated_ids);
+        drop(rtxn);
+
+        // A modified document is a new document.
+        index.add_documents(ndjson_documents(r#"{ "name": "benoit", "age": 30 }"#)).unwrap();
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.number_of_documents(&rtxn).unwrap(), 4);
+        drop(rtxn);
+
+        // With random ids the same documents are duplicated.
+        index.index_documents_config.autogenerate_ids = AutogenerateIds::Uuid;
+        index.add_documents(ndjson_documents(ndjson_content)).unwrap();
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.number_of_documents(&rtxn).unwrap(), 7);
+        drop(rtxn);
+    }
+
     #[test]
     fn empty_update() {
         let index = TempIndex::new();
@@ -823,8 +1527,9 @@ mod tests {
         let index = TempIndex::new();
 
         // First we send 1 document with an invalid id.
-        // There is a space in the document id.
-        index.add_documents(documents!([ { "id": "brume bleue", "name": "kevin" } ])).unwrap_err();
+        // The document id is longer than the maximum length.
+        let document_id = "brume-bleue".repeat(50);
+        index.add_documents(documents!([ { "id": document_id, "name": "kevin" } ])).unwrap_err();
 
         // Then we send 1 document with a valid id.
         index.add_documents(documents!([ { "id": 32, "name": "kevin" } ])).unwrap();
@@ -1020,6 +1725,117 @@ mod tests {
         );
     }
 
+    #[test]
+    fn all_geo_shapes_are_filtered_and_sorted_identically() {
+        use std::str::FromStr;
+
+        let points = [(48.8566, 2.3522), (45.764, 4.8357), (43.2965, 5.3698), (40.7128, -74.006)];
+        let shapes: [fn(f64, f64) -> serde_json::Value; 3] = [
+            |lat, lng| serde_json::json!({ "lat": lat, "lng": lng }),
+            |lat, lng| serde_json::json!([lng, lat]),
+            |lat, lng| serde_json::json!({ "type": "Point", "coordinates": [lng, lat] }),
+        ];
+
+        let mut results = Vec::new();
+        for shape in shapes {
+            let index = TempIndex::new();
+            index
+                .update_settings(|settings| {
+                    settings.set_filterable_fields(hashset!(S("_geo")));
+                    settings.set_sortable_fields(hashset!(S("_geo")));
+                })
+                .unwrap();
+
+            let documents: Vec<_> = points
+                .iter()
+                .enumerate()
+                .map(|(id, &(lat, lng))| serde_json::json!({ "id": id, "_geo": shape(lat, lng) }))
+                .map(|document| document.as_object().unwrap().clone())
+                .collect();
+            index.add_documents(documents_batch_reader_from_objects(documents)).unwrap();
+
+            let rtxn = index.read_txn().unwrap();
+            let mut search = crate::Search::new(&rtxn, &index);
+            let filter = "_geoRadius(48.8566, 2.3522, 500000)";
+            search.filter(crate::Filter::from_str(filter).unwrap().unwrap());
+            let filtered = search.execute().unwrap().documents_ids;
+
+            let mut search = crate::Search::new(&rtxn, &index);
+            let sort = crate::AscDesc::from_str("_geoPoint(43.2965, 5.3698):asc").unwrap();
+            search.sort_criteria(vec![sort]);
+            let sorted = search.execute().unwrap().documents_ids;
+            results.push((filtered, sorted));
+        }
+
+        assert_eq!(results[0], (vec![0, 1], vec![2, 1, 0, 3]));
+        assert_eq!(results[1], results[0]);
+        assert_eq!(results[2], results[0]);
+    }
+
+    #[test]
+    fn invalid_geo_shapes_error() {
+        let mut index = TempIndex::new();
+        index.index_documents_config.update_method = IndexDocumentsMethod::ReplaceDocuments;
+
+        index
+            .update_settings(|settings| {
+                settings.set_filterable_fields(hashset!(S("_geo")));
+            })
+            .unwrap();
+
+        let error = index.add_documents(documents!([{ "id": 0, "_geo": "12,42" }])).unwrap_err();
+        assert_eq!(
+            &error.to_string(),
+            r#"The `_geo` field in the document with the id: `0` is not an object nor an array. Was expecting an object with the `_geo.lat` and `_geo.lng` fields, a `[lng, lat]` array or a GeoJSON point but instead got `"12,42"`."#
+        );
+
+        let error =
+            index.add_documents(documents!([{ "id": 1, "_geo": [42, 12, 3] }])).unwrap_err();
+        assert_eq!(
+            &error.to_string(),
+            r#"Could not parse the `_geo` field in the document with the id: `1`. Was expecting a `[lng, lat]` array or a GeoJSON point like `{"type": "Point", "coordinates": [lng, lat]}` but instead got `[42,12,3]`."#
+        );
+
+        let error = index
+            .add_documents(documents!([
+              { "id": 2, "_geo": { "type": "LineString", "coordinates": [42, 12] } }
+            ]))
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            crate::Error::UserError(UserError::InvalidGeoField(
+                crate::error::GeoError::BadGeoPoint { .. }
+            ))
+        ));
+
+        // the GeoJSON ordering puts the longitude first
+        let error = index
+            .add_documents(documents!([
+              { "id": 3, "_geo": { "type": "Point", "coordinates": [12, 91] } }
+            ]))
+            .unwrap_err();
+        assert_eq!(
+            &error.to_string(),
+            r#"The latitude in the document with the id: `3` is out of range. Was expecting a latitude between `-90` and `90` but instead got `91`."#
+        );
+
+        let error = index.add_documents(documents!([{ "id": 4, "_geo": [181, 12] }])).unwrap_err();
+        assert!(matches!(
+            error,
+            crate::Error::UserError(UserError::InvalidGeoField(
+                crate::error::GeoError::LongitudeOutOfRange { .. }
+            ))
+        ));
+
+        let error = index
+            .add_documents(documents!([{ "id": 5, "_geo": { "lat": "NaN", "lng": 42 } }]))
+            .unwrap_err();
+        assert_eq!(
+            &error.to_string(),
+            r#"Could not parse latitude in the document with the id: `5`. Was expecting a finite number but instead got `"NaN"`."#
+        );
+    }
+
     #[test]
     fn delete_documents_then_insert() {
         let index = TempIndex::new();
@@ -1353,14 +2169,130 @@ mod tests {
     }
 
     #[test]
-    fn test_facets_generation() {
-        let index = TempIndex::new();
+    fn nested_primary_key_from_csv_and_ndjson() {
+        use std::io::Cursor;
 
-        index
-            .add_documents(documents!([
-                {
-                    "id": 0,
-                    "dog": {
+        use crate::documents::{DocumentsBatchBuilder, DocumentsBatchReader};
+
+        fn external_ids(index: &Index) -> Vec<String> {
+            let rtxn = index.read_txn().unwrap();
+            let external_documents_ids = index.external_documents_ids(&rtxn).unwrap();
+            let mut ids: Vec<_> = external_documents_ids
+                .to_hash_map()
+                .into_iter()
+                .map(|(external_id, _)| external_id)
+                .collect();
+            ids.sort_unstable();
+            ids
+        }
+
+        // The header of the CSV is literally `metadata.id`.
+        let csv_content = "metadata.id,title\n1,hello\n2,world\n";
+        let mut builder = DocumentsBatchBuilder::new(Vec::new());
+        builder.append_csv(csv::Reader::from_reader(Cursor::new(csv_content))).unwrap();
+        let vector = builder.into_inner().unwrap();
+        let csv_documents = DocumentsBatchReader::from_reader(Cursor::new(vector)).unwrap();
+
+        // The ndjson documents contain a nested `metadata` object.
+        let ndjson_content = r#"
+            { "metadata": { "id": 1, "kind": "greeting" }, "title": "hello" }
+            { "metadata": { "id": 2, "kind": "place" }, "title": "world" }
+        "#;
+        let ndjson_documents = || {
+            let mut builder = DocumentsBatchBuilder::new(Vec::new());
+            for object in
+                serde_json::Deserializer::from_str(ndjson_content).into_iter::<crate::Object>()
+            {
+                builder.append_json_object(&object.unwrap()).unwrap();
+            }
+            let vector = builder.into_inner().unwrap();
+            DocumentsBatchReader::from_reader(Cursor::new(vector)).unwrap()
+        };
+
+        let csv_index = TempIndex::new();
+        csv_index.add_documents(csv_documents).unwrap();
+        let rtxn = csv_index.read_txn().unwrap();
+        assert_eq!(csv_index.primary_key(&rtxn).unwrap(), Some("metadata.id"));
+        drop(rtxn);
+
+        let ndjson_index = TempIndex::new();
+        ndjson_index.add_documents(ndjson_documents()).unwrap();
+        let rtxn = ndjson_index.read_txn().unwrap();
+        assert_eq!(ndjson_index.primary_key(&rtxn).unwrap(), Some("metadata.id"));
+        drop(rtxn);
+
+        assert_eq!(external_ids(&csv_index), vec![S("1"), S("2")]);
+        assert_eq!(external_ids(&csv_index), external_ids(&ndjson_index));
+
+        // Sending the nested documents to the index created from the CSV replaces them.
+        csv_index.add_documents(ndjson_documents()).unwrap();
+        let rtxn = csv_index.read_txn().unwrap();
+        assert_eq!(csv_index.number_of_documents(&rtxn).unwrap(), 2);
+        drop(rtxn);
+        assert_eq!(external_ids(&csv_index), vec![S("1"), S("2")]);
+    }
+
+    #[test]
+    fn nested_primary_key_must_not_be_an_array_or_an_object() {
+        let index = TempIndex::new();
+
+        index
+            .update_settings(|settings| {
+                settings.set_primary_key("metadata.id".to_owned());
+            })
+            .unwrap();
+
+        let error = index
+            .add_documents(documents!([{ "metadata": { "id": [1, 2] }, "title": "hello" }]))
+            .unwrap_err();
+        assert!(matches!(error, crate::Error::UserError(UserError::InvalidDocumentId { .. })));
+
+        let error = index
+            .add_documents(documents!([{ "metadata": { "id": { "value": 1 } }, "title": "hello" }]))
+            .unwrap_err();
+        assert!(matches!(error, crate::Error::UserError(UserError::InvalidDocumentId { .. })));
+
+        let error = index
+            .add_documents(documents!([{ "metadata.id": { "value": 1 }, "title": "hello" }]))
+            .unwrap_err();
+        assert!(matches!(error, crate::Error::UserError(UserError::InvalidDocumentId { .. })));
+    }
+
+    #[test]
+    fn ambiguous_nested_primary_key_lists_the_candidates() {
+        let index = TempIndex::new();
+
+        let error = index
+            .add_documents(documents!([
+                { "metadata": { "id": 1 }, "author": { "id": 2 }, "title": "hello" }
+            ]))
+            .unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains("author.id") && message.contains("metadata.id"), "{}", message);
+        match error {
+            crate::Error::UserError(UserError::MultiplePrimaryKeyCandidatesFound {
+                mut candidates,
+            }) => {
+                candidates.sort_unstable();
+                assert_eq!(candidates, vec![S("author.id"), S("metadata.id")]);
+            }
+            error => panic!("unexpected error: {}", error),
+        }
+
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.primary_key(&rtxn).unwrap(), None);
+        assert_eq!(index.number_of_documents(&rtxn).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_facets_generation() {
+        let index = TempIndex::new();
+
+        index
+            .add_documents(documents!([
+                {
+                    "id": 0,
+                    "dog": {
                         "race": {
                             "bernese mountain": "zeroth",
                         },
@@ -1702,6 +2634,123 @@ mod tests {
         index.add_documents(doc4).unwrap_err();
     }
 
+    #[test]
+    fn huge_integers_and_unicode_documents_ids_round_trip() {
+        let index = TempIndex::new();
+
+        let ids = vec![
+            serde_json::json!(9007199254740992u64),
+            serde_json::json!(9007199254740993u64),
+            serde_json::json!(u64::MAX),
+            serde_json::json!(i64::MIN),
+            serde_json::json!("{6f1a3c5e-2b7d-4e8f-9a0b-1c2d3e4f5a6b}"),
+            serde_json::json!("brume bleue"),
+            serde_json::json!("café ☕"),
+            serde_json::json!("東京タワー"),
+        ];
+        let external_ids = [
+            "9007199254740992",
+            "9007199254740993",
+            "18446744073709551615",
+            "-9223372036854775808",
+            "{6f1a3c5e-2b7d-4e8f-9a0b-1c2d3e4f5a6b}",
+            "brume bleue",
+            "café ☕",
+            "東京タワー",
+        ];
+        let documents = |title: &str| {
+            let documents_iter = ids
+                .iter()
+                .map(|id| serde_json::json!({ "id": id, "title": title }))
+                .filter_map(|json| match json {
+                    serde_json::Value::Object(object) => Some(object),
+                    _ => None,
+                });
+            documents_batch_reader_from_objects(documents_iter)
+        };
+        let titles = |index: &TempIndex| {
+            let rtxn = index.read_txn().unwrap();
+            let title = index.fields_ids_map(&rtxn).unwrap().id("title").unwrap();
+            let external_documents_ids = index.external_documents_ids(&rtxn).unwrap();
+            external_ids
+                .iter()
+                .map(|external_id| {
+                    let docid = external_documents_ids.get(external_id)?;
+                    let docs = index.documents(&rtxn, Some(docid)).unwrap();
+                    let value = docs[0].1.as_obkv().get(title).unwrap();
+                    Some(serde_json::from_slice::<String>(value).unwrap())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Every id is kept exact, the neighboring integers don't collide.
+        index.add_documents(documents("hello")).unwrap();
+        assert_eq!(titles(&index), vec![Some(S("hello")); external_ids.len()]);
+
+        // The documents are replaced under the same ids.
+        index.add_documents(documents("world")).unwrap();
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.number_of_documents(&rtxn).unwrap(), external_ids.len() as u64);
+        drop(rtxn);
+        assert_eq!(titles(&index), vec![Some(S("world")); external_ids.len()]);
+
+        // The documents are deleted by their ids.
+        let mut wtxn = index.write_txn().unwrap();
+        let mut builder = DeleteDocuments::new(&mut wtxn, &index).unwrap();
+        for external_id in &external_ids[..4] {
+            builder.delete_external_id(external_id).unwrap();
+        }
+        builder.execute().unwrap();
+        wtxn.commit().unwrap();
+
+        let mut expected = vec![None; 4];
+        expected.extend(vec![Some(S("world")); 4]);
+        assert_eq!(titles(&index), expected);
+    }
+
+    #[test]
+    fn huge_integers_documents_ids_from_csv_are_kept_exact() {
+        use std::io::Cursor;
+
+        use crate::documents::{DocumentsBatchBuilder, DocumentsBatchReader};
+
+        let index = TempIndex::new();
+        let csv_content =
+            "id:number,title\n18446744073709551615,hello\n18446744073709551614,world\n";
+        let mut builder = DocumentsBatchBuilder::new(Vec::new());
+        builder.append_csv(csv::Reader::from_reader(Cursor::new(csv_content))).unwrap();
+        let vector = builder.into_inner().unwrap();
+        let documents = DocumentsBatchReader::from_reader(Cursor::new(vector)).unwrap();
+        index.add_documents(documents).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let external_documents_ids = index.external_documents_ids(&rtxn).unwrap();
+        assert_eq!(external_documents_ids.get("18446744073709551615"), Some(0));
+        assert_eq!(external_documents_ids.get("18446744073709551614"), Some(1));
+    }
+
+    #[test]
+    fn documents_ids_must_not_be_too_long() {
+        let index = TempIndex::new();
+
+        let longest = "a".repeat(MAX_DOCUMENT_ID_LENGTH);
+        index.add_documents(documents!([{ "id": longest, "title": "hello" }])).unwrap();
+
+        let too_long = "a".repeat(MAX_DOCUMENT_ID_LENGTH + 1);
+        let error =
+            index.add_documents(documents!([{ "id": too_long, "title": "hello" }])).unwrap_err();
+        assert!(matches!(error, crate::Error::UserError(UserError::InvalidDocumentId { .. })));
+
+        // Integers that don't fit in an i64 or an u64 are floats and must be sent as strings.
+        let error = index
+            .add_documents(documents!([{ "id": 18446744073709551616.0, "title": "hello" }]))
+            .unwrap_err();
+        assert!(matches!(error, crate::Error::UserError(UserError::InvalidDocumentId { .. })));
+        index
+            .add_documents(documents!([{ "id": "18446744073709551616", "title": "hello" }]))
+            .unwrap();
+    }
+
     #[test]
     fn primary_key_must_not_contain_whitespace() {
         let index = TempIndex::new();
@@ -1749,4 +2798,1205 @@ mod tests {
         let words_fst = index.words_fst(&rtxn).unwrap();
         assert!(!words_fst.contains(&long_word));
     }
+
+    #[test]
+    fn progress_callback_phases_ordering() {
+        use std::sync::Mutex;
+
+        let index = TempIndex::new();
+        index
+            .update_settings(|settings| {
+                settings.set_filterable_fields(hashset! { S("color") });
+            })
+            .unwrap();
+
+        let steps = Mutex::new(Vec::new());
+        let mut wtxn = index.write_txn().unwrap();
+        let builder = IndexDocuments::new(
+            &mut wtxn,
+            &index,
+            &index.indexer_config,
+            index.index_documents_config.clone(),
+            |step| steps.lock().unwrap().push(step),
+            || false,
+        )
+        .unwrap();
+        let (builder, user_error) = builder
+            .add_documents(documents!([
+                { "id": 1, "name": "kevin", "color": "red" },
+                { "id": 2, "name": "kevina", "color": "blue" },
+                { "id": 3, "name": "benoit", "color": "red" }
+            ]))
+            .unwrap();
+        user_error.unwrap();
+        builder.execute().unwrap();
+        wtxn.commit().unwrap();
+
+        let steps = steps.into_inner().unwrap();
+
+        // Every phase starts after the previous one, in the documented order.
+        let mut phases: Vec<usize> = Vec::new();
+        for step in &steps {
+            if !phases.contains(&step.step()) {
+                phases.push(step.step());
+            }
+        }
+        assert_eq!(phases, (0..steps[0].number_of_steps()).collect::<Vec<_>>());
+
+        // The counters of the phases reach their totals.
+        let last_of = |step_index: usize| *steps.iter().rfind(|s| s.step() == step_index).unwrap();
+        match last_of(2) {
+            UpdateIndexingStep::ExtractDocumentsChunks { chunks_seen, total_chunks } => {
+                assert!(total_chunks > 0);
+                assert_eq!(chunks_seen, total_chunks);
+            }
+            otherwise => panic!("unexpected step {:?}", otherwise),
+        }
+        match last_of(3) {
+            UpdateIndexingStep::IndexDocuments { documents_seen, total_documents } => {
+                assert_eq!((documents_seen, total_documents), (3, 3));
+            }
+            otherwise => panic!("unexpected step {:?}", otherwise),
+        }
+        match last_of(5) {
+            UpdateIndexingStep::ComputeFacetLevels { fields_seen, total_fields } => {
+                assert_eq!((fields_seen, total_fields), (1, 1));
+            }
+            otherwise => panic!("unexpected step {:?}", otherwise),
+        }
+        match last_of(6) {
+            UpdateIndexingStep::ComputePrefixDatabases { databases_seen, total_databases } => {
+                assert_eq!((databases_seen, total_databases), (4, 4));
+            }
+            otherwise => panic!("unexpected step {:?}", otherwise),
+        }
+    }
+
+    #[test]
+    fn unchanged_documents_are_not_indexed_again() {
+        use std::sync::Mutex;
+
+        let index = TempIndex::new();
+        let add_documents = |documents: DocumentsBatchReader<Cursor<Vec<u8>>>| {
+            let steps = Mutex::new(Vec::new());
+            let mut wtxn = index.write_txn().unwrap();
+            let builder = IndexDocuments::new(
+                &mut wtxn,
+                &index,
+                &index.indexer_config,
+                index.index_documents_config.clone(),
+                |step| steps.lock().unwrap().push(step),
+                || false,
+            )
+            .unwrap();
+            let (builder, user_error) = builder.add_documents(documents).unwrap();
+            user_error.unwrap();
+            let result = builder.execute().unwrap();
+            wtxn.commit().unwrap();
+            (result, steps.into_inner().unwrap())
+        };
+        let search_ids = |query: &str| {
+            let rtxn = index.read_txn().unwrap();
+            index.search(&rtxn).query(query).execute().unwrap().documents_ids
+        };
+        let extracted_chunks = |steps: &[UpdateIndexingStep]| {
+            steps
+                .iter()
+                .filter_map(|step| match step {
+                    UpdateIndexingStep::ExtractDocumentsChunks { total_chunks, .. } => {
+                        Some(*total_chunks)
+                    }
+                    _ => None,
+                })
+                .max()
+                .unwrap_or(0)
+        };
+
+        let (result, steps) = add_documents(documents!([
+            { "id": 1, "name": "kevin" },
+            { "id": 2, "name": "kevina" },
+            { "id": 3, "name": "benoit" }
+        ]));
+        assert_eq!((result.indexed_documents, result.unchanged_documents), (3, 0));
+        assert!(extracted_chunks(&steps) > 0);
+        let kevin_ids = search_ids("kevin");
+        let benoit_ids = search_ids("benoit");
+
+        // Pushing the same documents again doesn't extract anything.
+        let (result, steps) = add_documents(documents!([
+            { "id": 1, "name": "kevin" },
+            { "id": 2, "name": "kevina" },
+            { "id": 3, "name": "benoit" }
+        ]));
+        assert_eq!((result.indexed_documents, result.unchanged_documents), (0, 3));
+        assert_eq!(result.number_of_documents, 3);
+        assert_eq!(extracted_chunks(&steps), 0);
+        assert_eq!(search_ids("kevin"), kevin_ids);
+        assert_eq!(search_ids("benoit"), benoit_ids);
+
+        // Only the modified document is indexed again.
+        let (result, _) = add_documents(documents!([
+            { "id": 1, "name": "kevin" },
+            { "id": 2, "name": "kevina" },
+            { "id": 3, "name": "bernard" }
+        ]));
+        assert_eq!((result.indexed_documents, result.unchanged_documents), (1, 2));
+        assert_eq!(result.number_of_documents, 3);
+        assert_eq!(search_ids("kevin"), kevin_ids);
+        assert!(search_ids("benoit").is_empty());
+        assert_eq!(search_ids("bernard").len(), 1);
+    }
+
+    #[test]
+    fn validate_documents_without_indexing_them() {
+        let index = TempIndex::new();
+        index.update_settings(|s| s.set_sortable_fields(hashset! { S("_geo") })).unwrap();
+
+        let mut wtxn = index.write_txn().unwrap();
+        let builder = IndexDocuments::new(
+            &mut wtxn,
+            &index,
+            &index.indexer_config,
+            index.index_documents_config.clone(),
+            |_| (),
+            || false,
+        )
+        .unwrap();
+
+        let report = builder
+            .validate(documents!([
+                { "id": 1, "_geo": { "lat": 12, "lng": 42 } },
+                { "id": true },
+                { "name": "kevin" },
+                { "id": 4, "_geo": { "lat": 12 } },
+                { "id": { "nested": 5 } },
+                { "id": 6 },
+            ]))
+            .unwrap();
+        assert!(!report.is_valid());
+        assert_eq!(report.documents_count, 6);
+        assert_eq!(report.invalid_documents, 4);
+        assert!(report.batch_error.is_none());
+
+        let errors: Vec<_> = report
+            .errors
+            .iter()
+            .map(|error| (error.position, error.external_id.as_deref()))
+            .collect();
+        assert_eq!(errors, vec![(1, None), (2, None), (3, Some("4")), (4, None)]);
+        assert!(matches!(report.errors[0].error, UserError::InvalidDocumentId { .. }));
+        assert!(matches!(report.errors[1].error, UserError::MissingDocumentId { .. }));
+        assert!(matches!(
+            report.errors[2].error,
+            UserError::InvalidGeoField(crate::error::GeoError::MissingLongitude { .. })
+        ));
+        assert!(matches!(report.errors[3].error, UserError::InvalidDocumentId { .. }));
+
+        // The errors are capped but all the invalid documents are counted.
+        let documents = (0..150).map(|_| match serde_json::json!({ "id": true }) {
+            serde_json::Value::Object(object) => object,
+            _ => unreachable!(),
+        });
+        let report = builder.validate(documents_batch_reader_from_objects(documents)).unwrap();
+        assert_eq!(report.invalid_documents, 150);
+        assert_eq!(report.errors.len(), MAX_VALIDATION_ERRORS);
+
+        // A batch without any primary key can't be validated document by document.
+        let report = builder.validate(documents!([{ "name": "kevin" }])).unwrap();
+        assert!(matches!(report.batch_error, Some(UserError::MissingPrimaryKey)));
+        assert!(report.errors.is_empty());
+
+        let report = builder.validate(documents!([{ "id": 1 }, { "id": "2" }])).unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.documents_count, 2);
+        assert!(report.duplicates.is_empty());
+
+        // The duplicates don't invalidate the batch but are shown with their first version.
+        let report = builder
+            .validate(documents!([
+                { "id": 1, "name": "kevin" },
+                { "id": 2, "name": "kevina" },
+                { "id": 1, "name": "benoit" },
+            ]))
+            .unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.duplicates.len(), 1);
+        let duplicate = &report.duplicates[0];
+        assert_eq!((duplicate.position, duplicate.external_id.as_deref()), (2, Some("1")));
+        match &duplicate.error {
+            UserError::DuplicateDocumentId { first_position, first_document, document, .. } => {
+                assert_eq!(*first_position, 0);
+                assert_eq!(first_document["name"], "kevin");
+                assert_eq!(document["name"], "benoit");
+            }
+            error => panic!("unexpected error: {}", error),
+        }
+
+        // Nothing was written into the index.
+        builder.execute().unwrap();
+        wtxn.commit().unwrap();
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.number_of_documents(&rtxn).unwrap(), 0);
+        assert_eq!(index.primary_key(&rtxn).unwrap(), None);
+    }
+
+    #[test]
+    fn payloads_of_different_methods_in_one_builder() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 1, "name": "kevin", "age": 20 },
+                { "id": 2, "name": "bob", "age": 30 },
+                { "id": 4, "name": "jean" }
+            ]))
+            .unwrap();
+
+        let mut wtxn = index.write_txn().unwrap();
+        let mut builder = IndexDocuments::new(
+            &mut wtxn,
+            &index,
+            &index.indexer_config,
+            index.index_documents_config.clone(),
+            |_| (),
+            || false,
+        )
+        .unwrap();
+        let replace = IndexDocumentsMethod::ReplaceDocuments;
+        let update = IndexDocumentsMethod::UpdateDocuments;
+        let payloads = vec![
+            (replace, documents!([{ "id": 1, "name": "kevina" }, { "id": 3, "name": "benoit" }])),
+            (
+                update,
+                documents!([
+                    { "id": 1, "city": "paris" },
+                    { "id": 2, "age": 31 },
+                    { "id": 3, "age": 25 }
+                ]),
+            ),
+            (replace, documents!([{ "id": 2, "name": "bobby" }])),
+            (update, documents!([{ "id": 4, "name": "jean" }])),
+        ];
+        for (method, documents) in payloads {
+            let (new_builder, user_error) =
+                builder.add_documents_with_method(documents, method).unwrap();
+            user_error.unwrap();
+            builder = new_builder;
+        }
+        let results = builder.execute_per_payload().unwrap();
+        wtxn.commit().unwrap();
+
+        let result = |indexed_documents, unchanged_documents| DocumentAdditionResult {
+            indexed_documents,
+            unchanged_documents,
+            number_of_documents: 4,
+            warnings: Vec::new(),
+            rejected_documents: Vec::new(),
+        };
+        assert_eq!(results, vec![result(2, 0), result(3, 0), result(1, 0), result(0, 1)]);
+
+        let rtxn = index.read_txn().unwrap();
+        let fields_ids_map = index.fields_ids_map(&rtxn).unwrap();
+        let external_documents_ids = index.external_documents_ids(&rtxn).unwrap();
+        let document = |id: &str| {
+            let docid = external_documents_ids.get(id).unwrap();
+            let docs = index.documents(&rtxn, Some(docid)).unwrap();
+            let fields: Vec<_> = fields_ids_map.ids().collect();
+            serde_json::Value::Object(
+                crate::obkv_to_json(&fields, &fields_ids_map, docs[0].1.as_obkv()).unwrap(),
+            )
+        };
+
+        // The partial update is merged into the replacing version of the document.
+        assert_eq!(
+            document("1"),
+            serde_json::json!({ "id": 1, "name": "kevina", "city": "paris" })
+        );
+        // The replacing version discards the previous partial update.
+        assert_eq!(document("2"), serde_json::json!({ "id": 2, "name": "bobby" }));
+        assert_eq!(document("3"), serde_json::json!({ "id": 3, "name": "benoit", "age": 25 }));
+        assert_eq!(document("4"), serde_json::json!({ "id": 4, "name": "jean" }));
+
+        let field_distribution = index.field_distribution(&rtxn).unwrap();
+        assert_eq!(field_distribution.get("age"), Some(&1));
+        assert_eq!(field_distribution.get("city"), Some(&1));
+        assert_eq!(field_distribution.get("name"), Some(&4));
+
+        let crate::SearchResult { documents_ids, .. } =
+            index.search(&rtxn).query("paris").execute().unwrap();
+        assert_eq!(documents_ids, vec![external_documents_ids.get("1").unwrap()]);
+        let crate::SearchResult { documents_ids, .. } =
+            index.search(&rtxn).query("31").execute().unwrap();
+        assert!(documents_ids.is_empty());
+    }
+
+    #[test]
+    fn prepared_chunks_write_identical_databases() {
+        use crate::snapshot_tests::*;
+
+        let documents = |ids: std::ops::Range<usize>, text: &str| {
+            documents_batch_reader_from_objects(ids.map(|id| {
+                let colors = ["red", "blue", "green"];
+                let document = serde_json::json!({
+                    "id": id,
+                    "text": format!("{} {} number{} modulo{}", text, id, id, id % 7),
+                    "color": colors[id % colors.len()],
+                });
+                document.as_object().unwrap().clone()
+            }))
+        };
+
+        let databases_checksum = |num_threads: usize| {
+            let mut index = TempIndex::new();
+            index.indexer_config.thread_pool =
+                Some(rayon::ThreadPoolBuilder::new().num_threads(num_threads).build().unwrap());
+            // Small documents chunks to prepare and write many chunks of every kind.
+            index.indexer_config.documents_chunk_size = Some(256);
+            index
+                .update_settings(|settings| {
+                    settings.set_filterable_fields(hashset! { S("color") });
+                    settings.set_documents_compression(true);
+                })
+                .unwrap();
+
+            // The first addition writes into empty databases, the second one
+            // replaces some of the documents and merges with the stored entries.
+            index.add_documents(documents(0..60, "hello world")).unwrap();
+            index.add_documents(documents(30..90, "goodbye moon")).unwrap();
+
+            let rtxn = index.read_txn().unwrap();
+            let mut snaps = vec![
+                snap_word_docids(&index),
+                snap_exact_word_docids(&index),
+                snap_word_prefix_docids(&index),
+                snap_docid_word_positions(&index),
+                snap_word_pair_proximity_docids(&index),
+                snap_word_position_docids(&index),
+                snap_field_id_word_count_docids(&index),
+                snap_facet_id_string_docids(&index),
+                snap_documents_ids(&index),
+                snap_external_documents_ids(&index),
+                snap_words_fst(&index),
+                snap_words_prefixes_fst(&index),
+            ];
+            // The compression dictionary is trained on the first documents chunk written,
+            // the documents are therefore compared once decompressed.
+            for result in index.all_documents(&rtxn).unwrap() {
+                let (id, document) = result.unwrap();
+                snaps.push(format!("{} {:?}", id, document.as_bytes()));
+            }
+            format!("{:x}", md5::compute(snaps.concat()))
+        };
+
+        assert_eq!(databases_checksum(1), databases_checksum(4));
+    }
+
+    #[test]
+    fn spilled_external_ids_give_the_same_internal_ids() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use crate::snapshot_tests::{snap_documents_ids, snap_external_documents_ids};
+
+        // The ids 0 to 19 are seen twice in the second update, the ids 10 to 19 are replaced
+        // and the ids 0 to 9 are sent again with the same content.
+        let documents = |ids: Vec<usize>, text: &str| {
+            documents_batch_reader_from_objects(ids.into_iter().map(|id| {
+                let text =
+                    if id < 10 { "unchanged".to_string() } else { format!("{} {}", text, id) };
+                let document = serde_json::json!({ "id": format!("doc-{}", id), "text": text });
+                document.as_object().unwrap().clone()
+            }))
+        };
+
+        let index_with_memory = |max_external_ids_memory: Option<usize>| {
+            let mut index = TempIndex::new();
+            index.indexer_config.max_external_ids_memory = max_external_ids_memory;
+            index.add_documents(documents((0..30).collect(), "hello")).unwrap();
+
+            let spilled_chunks = AtomicUsize::new(0);
+            let mut wtxn = index.write_txn().unwrap();
+            let builder = IndexDocuments::new(
+                &mut wtxn,
+                &index,
+                &index.indexer_config,
+                index.index_documents_config.clone(),
+                |step| {
+                    if let UpdateIndexingStep::RemapDocumentAddition { spilled_chunks: n, .. } =
+                        step
+                    {
+                        spilled_chunks.store(n, Ordering::Relaxed);
+                    }
+                },
+                || false,
+            )
+            .unwrap();
+            let ids = (10..20).chain(0..20).chain(40..50).collect();
+            let (builder, user_error) = builder.add_documents(documents(ids, "world")).unwrap();
+            user_error.unwrap();
+            builder.execute().unwrap();
+            wtxn.commit().unwrap();
+
+            let rtxn = index.read_txn().unwrap();
+            let documents: Vec<_> = index
+                .all_documents(&rtxn)
+                .unwrap()
+                .map(|result| {
+                    let (docid, document) = result.unwrap();
+                    (docid, document.as_bytes().to_vec())
+                })
+                .collect();
+            let snap = (snap_documents_ids(&index), snap_external_documents_ids(&index), documents);
+            (snap, spilled_chunks.into_inner())
+        };
+
+        let (expected, spilled_chunks) = index_with_memory(None);
+        assert_eq!(spilled_chunks, 0);
+        let (snap, spilled_chunks) = index_with_memory(Some(1));
+        assert!(spilled_chunks > 1);
+        assert_eq!(snap, expected);
+    }
+
+    #[test]
+    fn deterministic_indexing_writes_identical_databases() {
+        use crate::snapshot_tests::snap_raw_databases;
+
+        let documents = |ids: std::ops::Range<usize>, text: &str| {
+            documents_batch_reader_from_objects(ids.map(|id| {
+                let colors = ["red", "Red", "blue", "BLUE", "green"];
+                let document = serde_json::json!({
+                    "id": id,
+                    "text": format!("{} {} number{} modulo{}", text, id, id, id % 7),
+                    "color": colors[id % colors.len()],
+                    "price": id % 13,
+                });
+                document.as_object().unwrap().clone()
+            }))
+        };
+
+        let raw_databases = || {
+            let mut index = TempIndex::new();
+            index.indexer_config.thread_pool =
+                Some(rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap());
+            index.indexer_config.documents_chunk_size = Some(256);
+            index.indexer_config.deterministic = true;
+            index
+                .update_settings(|settings| {
+                    settings.set_filterable_fields(hashset! { S("color"), S("price") });
+                    settings.set_documents_compression(true);
+                })
+                .unwrap();
+
+            index.add_documents(documents(0..80, "hello world")).unwrap();
+            index.add_documents(documents(40..120, "goodbye moon")).unwrap();
+            snap_raw_databases(&index)
+        };
+
+        assert_eq!(raw_databases(), raw_databases());
+    }
+
+    #[test]
+    fn abort_indexing_leaves_index_unchanged() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 1, "name": "kevin" },
+                { "id": 2, "name": "kevina" }
+            ]))
+            .unwrap();
+
+        let should_abort = AtomicBool::new(false);
+        let mut wtxn = index.write_txn().unwrap();
+        let builder = IndexDocuments::new(
+            &mut wtxn,
+            &index,
+            &index.indexer_config,
+            index.index_documents_config.clone(),
+            |step| {
+                // The indexing is aborted while the documents are being extracted.
+                if let UpdateIndexingStep::ExtractDocumentsChunks { .. } = step {
+                    should_abort.store(true, Ordering::Relaxed);
+                }
+            },
+            || should_abort.load(Ordering::Relaxed),
+        )
+        .unwrap();
+        let (builder, user_error) = builder
+            .add_documents(documents!([
+                { "id": 2, "name": "benoit" },
+                { "id": 3, "name": "bernard" }
+            ]))
+            .unwrap();
+        user_error.unwrap();
+
+        let error = builder.execute().unwrap_err();
+        assert!(matches!(error, crate::Error::UserError(UserError::IndexingAborted)));
+        assert!(should_abort.load(Ordering::Relaxed));
+        wtxn.abort().unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.number_of_documents(&rtxn).unwrap(), 2);
+        let external_documents_ids = index.external_documents_ids(&rtxn).unwrap();
+        assert!(external_documents_ids.get("3").is_none());
+        let docid = external_documents_ids.get("2").unwrap();
+        let docs = index.documents(&rtxn, Some(docid)).unwrap();
+        let doc = docs[0].1.as_obkv();
+        let fields_ids_map = index.fields_ids_map(&rtxn).unwrap();
+        let name = doc.get(fields_ids_map.id("name").unwrap()).unwrap();
+        assert_eq!(serde_json::from_slice::<String>(name).unwrap(), "kevina");
+    }
+
+    #[test]
+    fn max_proximity_limits_word_pairs_and_reindexes() {
+        let mut index = TempIndex::new();
+        index.add_documents(documents!([{ "id": 1, "text": "the quick brown fox" }])).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.max_proximity(&rtxn).unwrap(), 7);
+        let max = index.word_pair_proximity_docids.iter(&rtxn).unwrap().map(|r| r.unwrap().0 .0);
+        assert_eq!(max.max(), Some(3));
+        drop(rtxn);
+
+        // The previous document must be reindexed with the new maximum proximity.
+        index.indexer_config.max_proximity = Some(2);
+        index.add_documents(documents!([{ "id": 2, "text": "the lazy dog" }])).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.max_proximity(&rtxn).unwrap(), 2);
+        let max = index.word_pair_proximity_docids.iter(&rtxn).unwrap().map(|r| r.unwrap().0 .0);
+        assert_eq!(max.max(), Some(2));
+        let docid = index.external_documents_ids(&rtxn).unwrap().get("1").unwrap();
+        let docids = index.word_pair_proximity_docids.get(&rtxn, &(2, "the", "brown")).unwrap();
+        assert!(docids.unwrap().contains(docid));
+        assert!(index.word_pair_proximity_docids.get(&rtxn, &(3, "the", "fox")).unwrap().is_none());
+
+        // The words farther away are still found by the search.
+        let mut search = crate::Search::new(&rtxn, &index);
+        search.query("the fox");
+        search.terms_matching_strategy(TermsMatchingStrategy::All);
+        let crate::SearchResult { documents_ids, .. } = search.execute().unwrap();
+        assert_eq!(documents_ids, vec![docid]);
+    }
+
+    #[test]
+    fn prefix_settings_are_stored_and_rebuild_the_prefix_databases() {
+        use fst::IntoStreamer;
+
+        let index = TempIndex::new();
+        index
+            .update_settings(|settings| {
+                settings.set_searchable_fields(vec![S("text")]);
+                settings.set_words_prefix_threshold(50);
+            })
+            .unwrap();
+
+        // 130 words start with `ab` and 52 with `cd`.
+        let mut words = Vec::new();
+        for (prefix, last) in [("ab", 'e'), ("cd", 'b')] {
+            for x in 'a'..=last {
+                for y in 'a'..='z' {
+                    words.push(format!("{prefix}{x}{y}"));
+                }
+            }
+        }
+        let documents = words
+            .iter()
+            .enumerate()
+            .map(|(id, word)| serde_json::json!({ "id": id, "text": word }))
+            .map(|document| document.as_object().unwrap().clone())
+            .collect();
+        index.add_documents(documents_batch_reader_from_objects(documents)).unwrap();
+
+        let prefixes = || {
+            let rtxn = index.read_txn().unwrap();
+            let fst = index.words_prefixes_fst(&rtxn).unwrap();
+            fst.into_stream().into_strs().unwrap()
+        };
+        let search = |query: &str| {
+            let rtxn = index.read_txn().unwrap();
+            let mut search = crate::Search::new(&rtxn, &index);
+            search.query(query).limit(200);
+            search.execute().unwrap().documents_ids.len()
+        };
+        assert_eq!(prefixes(), vec!["a", "ab", "c", "cd"]);
+        assert_eq!(search("cd"), 52);
+
+        // The prefix databases are only rebuilt by the next documents addition.
+        index.update_settings(|settings| settings.set_words_prefix_threshold(100)).unwrap();
+        assert_eq!(prefixes(), vec!["a", "ab", "c", "cd"]);
+        index.add_documents(documents!([{ "id": 1000, "text": "zebra" }])).unwrap();
+        assert_eq!(prefixes(), vec!["a", "ab"]);
+
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.prefix_settings(&rtxn).unwrap().threshold, 100);
+        assert!(index.word_prefix_docids.get(&rtxn, "ab").unwrap().is_some());
+        assert!(index.word_prefix_docids.get(&rtxn, "cd").unwrap().is_none());
+        drop(rtxn);
+
+        // The prefixes that are not materialized anymore are found through the words.
+        assert_eq!(search("ab"), 130);
+        assert_eq!(search("cd"), 52);
+
+        index
+            .update_settings(|settings| {
+                settings.set_min_prefix_length(2);
+                settings.set_words_prefix_threshold(50);
+                settings.set_max_prefixes(1);
+            })
+            .unwrap();
+        index.add_documents(documents!([{ "id": 1001, "text": "zebra" }])).unwrap();
+        assert_eq!(prefixes(), vec!["ab"]);
+        assert_eq!(search("cd"), 52);
+
+        let error =
+            index.update_settings(|settings| settings.set_max_prefix_length(1)).unwrap_err();
+        assert!(matches!(error, crate::Error::UserError(UserError::InvalidPrefixSettings { .. })));
+    }
+
+    #[test]
+    fn incremental_prefix_databases_match_a_full_rebuild() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        use crate::snapshot_tests::{
+            snap_prefix_word_pair_proximity_docids, snap_word_prefix_docids,
+            snap_word_prefix_pair_proximity_docids, snap_word_prefix_position_docids,
+            snap_words_prefixes_fst,
+        };
+
+        const WORDS: &[&str] = &[
+            "am",
+            "amazing",
+            "an",
+            "and",
+            "ant",
+            "at",
+            "attic",
+            "be",
+            "bee",
+            "bell",
+            "belly",
+            "beautiful",
+        ];
+
+        let mut incremental = TempIndex::new();
+        incremental.index_documents_config.words_prefix_threshold = Some(2);
+        incremental.index_documents_config.prefix_rebuild_ratio = Some(f64::INFINITY);
+        let mut rebuilt = TempIndex::new();
+        rebuilt.index_documents_config.words_prefix_threshold = Some(2);
+        rebuilt.index_documents_config.prefix_rebuild_ratio = Some(0.0);
+
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..20 {
+            let mut documents = Vec::new();
+            for _ in 0..rng.gen_range(1..6) {
+                let mut text = Vec::new();
+                for _ in 0..rng.gen_range(1..8) {
+                    text.push(WORDS[rng.gen_range(0..WORDS.len())]);
+                }
+                let id = rng.gen_range(0..30);
+                let document = serde_json::json!({ "id": id, "text": text.join(" ") });
+                documents.push(document.as_object().unwrap().clone());
+            }
+
+            incremental
+                .add_documents(documents_batch_reader_from_objects(documents.clone()))
+                .unwrap();
+            rebuilt.add_documents(documents_batch_reader_from_objects(documents)).unwrap();
+
+            assert_eq!(snap_words_prefixes_fst(&incremental), snap_words_prefixes_fst(&rebuilt));
+            assert_eq!(snap_word_prefix_docids(&incremental), snap_word_prefix_docids(&rebuilt));
+            assert_eq!(
+                snap_word_prefix_pair_proximity_docids(&incremental),
+                snap_word_prefix_pair_proximity_docids(&rebuilt)
+            );
+            assert_eq!(
+                snap_prefix_word_pair_proximity_docids(&incremental),
+                snap_prefix_word_pair_proximity_docids(&rebuilt)
+            );
+            assert_eq!(
+                snap_word_prefix_position_docids(&incremental),
+                snap_word_prefix_position_docids(&rebuilt)
+            );
+        }
+    }
+
+    #[test]
+    fn documents_exceeding_the_limits() {
+        use serde_json::json;
+
+        use crate::update::{DocumentLimits, LimitBehavior};
+
+        let mut index = TempIndex::new();
+        index.indexer_config.document_limits = DocumentLimits {
+            max_facet_values_per_field: Some(10),
+            max_fields_per_document: Some(4),
+            behavior: LimitBehavior::Reject,
+        };
+        index.update_settings(|s| s.set_filterable_fields(hashset! { S("tags") })).unwrap();
+
+        let tags: Vec<_> = (0..1000).map(|i| format!("tag{}", i)).collect();
+        let oversized_tags = || documents!([{ "id": 1, "tags": tags.clone() }]);
+        let oversized_fields = || {
+            documents!([{
+                "id": 2, "a": "alpha", "b": "bravo", "c": "charlie", "d": "delta", "e": "echo"
+            }])
+        };
+
+        // the documents are rejected and nothing is indexed
+        let error = index.add_documents(oversized_tags()).unwrap_err();
+        match error {
+            crate::Error::UserError(UserError::TooManyFacetValues {
+                document_id,
+                field,
+                values,
+                limit,
+            }) => {
+                assert_eq!(
+                    (document_id, field.as_str(), values, limit),
+                    (json!(1), "tags", 1000, 10)
+                );
+            }
+            error => panic!("unexpected error: {}", error),
+        }
+        let error = index.add_documents(oversized_fields()).unwrap_err();
+        match error {
+            crate::Error::UserError(UserError::TooManyFields { document_id, fields, limit }) => {
+                assert_eq!((document_id, fields, limit), (json!(2), 6, 4));
+            }
+            error => panic!("unexpected error: {}", error),
+        }
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.number_of_documents(&rtxn).unwrap(), 0);
+        drop(rtxn);
+
+        // the documents are truncated and a warning is returned for each of them
+        index.indexer_config.document_limits.behavior = LimitBehavior::Truncate;
+        let mut wtxn = index.write_txn().unwrap();
+        let builder = IndexDocuments::new(
+            &mut wtxn,
+            &index,
+            &index.indexer_config,
+            index.index_documents_config.clone(),
+            |_| (),
+            || false,
+        )
+        .unwrap();
+        let (builder, user_error) = builder.add_documents(oversized_tags()).unwrap();
+        user_error.unwrap();
+        let (builder, user_error) = builder.add_documents(oversized_fields()).unwrap();
+        user_error.unwrap();
+        let result = builder.execute().unwrap();
+        wtxn.commit().unwrap();
+
+        assert_eq!(result.indexed_documents, 2);
+        assert_eq!(result.warnings.len(), 2);
+        assert!(result.warnings.contains(&IndexingWarning::FacetValuesTruncated {
+            document_id: json!(1),
+            field: S("tags"),
+            values: 1000,
+            limit: 10,
+        }));
+        assert!(result.warnings.contains(&IndexingWarning::FieldsTruncated {
+            document_id: json!(2),
+            fields: 6,
+            limit: 4,
+        }));
+
+        let rtxn = index.read_txn().unwrap();
+        let filter = |filter: &str| {
+            let filter = crate::Filter::from_str(filter).unwrap().unwrap();
+            filter.evaluate(&rtxn, &index).unwrap().len()
+        };
+        assert_eq!(filter("tags = tag9"), 1);
+        assert_eq!(filter("tags = tag10"), 0);
+        assert_eq!(filter("tags = tag999"), 0);
+
+        // the fields above the limit are not indexed but are still stored
+        let search = |query: &str| {
+            let mut search = index.search(&rtxn);
+            search.query(query);
+            search.execute().unwrap().documents_ids.len()
+        };
+        assert_eq!(search("charlie"), 1);
+        assert_eq!(search("delta"), 0);
+        assert_eq!(search("echo"), 0);
+        let external_documents_ids = index.external_documents_ids(&rtxn).unwrap();
+        let docid = external_documents_ids.get("2").unwrap();
+        let docs = index.documents(&rtxn, Some(docid)).unwrap();
+        let fields_ids_map = index.fields_ids_map(&rtxn).unwrap();
+        assert!(docs[0].1.as_obkv().get(fields_ids_map.id("e").unwrap()).is_some());
+    }
+
+    #[test]
+    fn non_stored_attributes_are_indexed_but_not_stored() {
+        let index = TempIndex::new();
+
+        let body = "lorem ipsum dolor sit amet ".repeat(200);
+        let documents = || {
+            let documents: Vec<_> = (0..50)
+                .map(|id| {
+                    let body = if id == 7 { format!("{}unicorn", body) } else { body.clone() };
+                    serde_json::json!({ "id": id, "title": "hello", "body": body })
+                })
+                .map(|document| document.as_object().unwrap().clone())
+                .collect();
+            documents_batch_reader_from_objects(documents)
+        };
+        let documents_size = |index: &TempIndex| {
+            let rtxn = index.read_txn().unwrap();
+            let stats = index.stats(&rtxn, true).unwrap();
+            stats.databases[db_name::DOCUMENTS].total_size.unwrap()
+        };
+
+        index.add_documents(documents()).unwrap();
+        let stored_size = documents_size(&index);
+
+        let mut non_stored_index = TempIndex::new();
+        non_stored_index
+            .update_settings(|settings| settings.set_non_stored_fields(hashset! { S("body") }))
+            .unwrap();
+        non_stored_index.add_documents(documents()).unwrap();
+        let non_stored_size = documents_size(&non_stored_index);
+        assert!(non_stored_size * 10 < stored_size, "{} {}", non_stored_size, stored_size);
+
+        // the body is searchable but not returned with the documents
+        let rtxn = non_stored_index.read_txn().unwrap();
+        let mut search = non_stored_index.search(&rtxn);
+        search.query("unicorn");
+        let result = search.execute().unwrap();
+        assert_eq!(result.documents_ids.len(), 1);
+        let fields_ids_map = non_stored_index.fields_ids_map(&rtxn).unwrap();
+        let body_id = fields_ids_map.id("body").unwrap();
+        let (_, document) = &non_stored_index.documents(&rtxn, result.documents_ids).unwrap()[0];
+        assert!(document.as_obkv().get(body_id).is_none());
+        assert!(document.as_obkv().get(fields_ids_map.id("title").unwrap()).is_some());
+        let field_distribution = non_stored_index.field_distribution(&rtxn).unwrap();
+        assert!(!field_distribution.contains_key("body"));
+        drop(rtxn);
+
+        // a partial update that doesn't send the body again would lose it
+        non_stored_index.index_documents_config.update_method =
+            IndexDocumentsMethod::UpdateDocuments;
+        let mut wtxn = non_stored_index.write_txn().unwrap();
+        let builder = IndexDocuments::new(
+            &mut wtxn,
+            &non_stored_index,
+            &non_stored_index.indexer_config,
+            non_stored_index.index_documents_config.clone(),
+            |_| (),
+            || false,
+        )
+        .unwrap();
+        let error =
+            builder.add_documents(documents!([{ "id": 7, "title": "world" }])).err().unwrap();
+        assert!(matches!(
+            error,
+            crate::Error::UserError(UserError::PartialUpdateWithNonStoredAttributes { .. })
+        ));
+        drop(wtxn);
+
+        // sending the body again is allowed
+        non_stored_index
+            .add_documents(documents!([{ "id": 7, "title": "world", "body": "unicorn" }]))
+            .unwrap();
+        let rtxn = non_stored_index.read_txn().unwrap();
+        let mut search = non_stored_index.search(&rtxn);
+        search.query("world");
+        assert_eq!(search.execute().unwrap().documents_ids.len(), 1);
+        assert_eq!(non_stored_index.number_of_documents(&rtxn).unwrap(), 50);
+        drop(rtxn);
+
+        // the documents can't be reindexed from their stored version
+        let error = non_stored_index
+            .update_settings(|settings| settings.set_searchable_fields(vec![S("body")]))
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            crate::Error::UserError(UserError::ReindexWithNonStoredAttributes { .. })
+        ));
+    }
+
+    #[test]
+    fn ignored_attributes_are_dropped_before_indexing() {
+        let index = TempIndex::new();
+        index
+            .update_settings(|settings| {
+                settings.set_ignored_fields(hashset! { S("blob"), S("meta.*.raw") });
+                settings.set_filterable_fields(hashset! { S("meta") });
+            })
+            .unwrap();
+
+        index
+            .add_documents(documents!([
+                { "id": 0, "title": "kevin", "blob": "secret", "meta": { "a": { "raw": "secret", "kind": "x" } } },
+                { "id": 1, "blob": { "nested": "secret" } },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.number_of_documents(&rtxn).unwrap(), 2);
+
+        // the ignored fields are neither in the fields ids map nor counted
+        let fields_ids_map = index.fields_ids_map(&rtxn).unwrap();
+        assert_eq!(fields_ids_map.id("blob"), None);
+        assert_eq!(fields_ids_map.id("blob.nested"), None);
+        assert_eq!(fields_ids_map.id("meta.a.raw"), None);
+        assert!(fields_ids_map.id("meta.a.kind").is_some());
+        let field_distribution = index.field_distribution(&rtxn).unwrap();
+        assert_eq!(field_distribution.keys().collect::<Vec<_>>(), vec!["id", "meta", "title"]);
+
+        // nor stored
+        let fields: Vec<_> = fields_ids_map.ids().collect();
+        let documents: Vec<_> = index
+            .documents(&rtxn, vec![0, 1])
+            .unwrap()
+            .into_iter()
+            .map(|(_, document)| {
+                crate::obkv_to_json(&fields, &fields_ids_map, document.as_obkv()).unwrap()
+            })
+            .collect();
+        assert_eq!(
+            serde_json::to_value(&documents).unwrap(),
+            serde_json::json!([
+                { "id": 0, "title": "kevin", "meta": { "a": { "kind": "x" } } },
+                { "id": 1 },
+            ])
+        );
+
+        // nor indexed
+        let mut search = index.search(&rtxn);
+        search.query("secret");
+        assert!(search.execute().unwrap().documents_ids.is_empty());
+        let filter = crate::Filter::from_str("meta.a.raw = secret").unwrap().unwrap();
+        assert!(filter.evaluate(&rtxn, &index).unwrap().is_empty());
+        let filter = crate::Filter::from_str("meta.a.kind = x").unwrap().unwrap();
+        assert_eq!(filter.evaluate(&rtxn, &index).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn the_guessed_primary_key_is_the_one_indexed_with() {
+        let batches = [
+            documents!([{ "title": "hello", "author_id": 2, "id": 1 }]),
+            documents!([{ "title": "hello", "meta": { "id": 1, "kind": "book" } }]),
+        ];
+        for mut documents in batches {
+            let guess = documents.guess_primary_key().unwrap();
+            let index = TempIndex::new();
+            index.add_documents(documents).unwrap();
+
+            let rtxn = index.read_txn().unwrap();
+            assert_eq!(index.primary_key(&rtxn).unwrap(), guess.primary_key());
+            assert_eq!(index.number_of_documents(&rtxn).unwrap(), 1);
+        }
+    }
+
+    #[test]
+    fn replace_existing_documents_rejects_the_unknown_ids() {
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([
+                { "id": 1, "name": "kevin" },
+                { "id": 2, "name": "bob" }
+            ]))
+            .unwrap();
+
+        let mut wtxn = index.write_txn().unwrap();
+        let mut config = index.index_documents_config.clone();
+        config.update_method = IndexDocumentsMethod::ReplaceExistingDocuments;
+        let builder =
+            IndexDocuments::new(&mut wtxn, &index, &index.indexer_config, config, |_| (), || false)
+                .unwrap();
+        let (builder, user_error) = builder
+            .add_documents(documents!([
+                { "id": 3, "name": "typo" },
+                { "id": 1, "name": "kevina" },
+                { "id": 4, "name": "another typo" },
+                { "id": 2, "name": "bobby" }
+            ]))
+            .unwrap();
+        assert_eq!(user_error.unwrap(), 2);
+        let result = builder.execute().unwrap();
+        wtxn.commit().unwrap();
+
+        assert_eq!(result.indexed_documents, 2);
+        assert_eq!(result.number_of_documents, 2);
+        let rejected = |position, document_id: &str| RejectedDocument {
+            payload: 0,
+            position,
+            document_id: document_id.to_string(),
+        };
+        assert_eq!(result.rejected_documents, vec![rejected(0, "3"), rejected(2, "4")]);
+
+        let rtxn = index.read_txn().unwrap();
+        let external_documents_ids = index.external_documents_ids(&rtxn).unwrap();
+        assert!(external_documents_ids.get("3").is_none());
+        assert!(external_documents_ids.get("4").is_none());
+        for name in ["kevina", "bobby"] {
+            let result = index.search(&rtxn).query(name).execute().unwrap();
+            assert_eq!(result.documents_ids.len(), 1);
+        }
+    }
+
+    #[test]
+    fn documents_adding_fields_above_the_limit_are_rejected() {
+        use serde_json::json;
+
+        let index = TempIndex::new();
+        index.add_documents(documents!([{ "id": 1, "a": 1 }])).unwrap();
+
+        let add_documents = |max_fields, documents| {
+            let mut wtxn = index.write_txn().unwrap();
+            let mut config = index.index_documents_config.clone();
+            config.max_fields = max_fields;
+            let builder = IndexDocuments::new(
+                &mut wtxn,
+                &index,
+                &index.indexer_config,
+                config,
+                |_| (),
+                || false,
+            )
+            .unwrap();
+            let (builder, user_result) = builder.add_documents(documents).unwrap();
+            let error = match user_result {
+                Err(UserError::IndexFieldsLimitReached { document_id, field, limit }) => {
+                    (document_id, field, limit)
+                }
+                Err(error) => panic!("unexpected error {}", error),
+                Ok(_) => panic!("the documents were not rejected"),
+            };
+            let result = builder.execute().unwrap();
+            wtxn.commit().unwrap();
+            (error, result)
+        };
+
+        // only the document adding a field above the limit is rejected
+        let documents = documents!([{ "id": 2, "a": 2 }, { "id": 3, "b": 3, "c": 3 }]);
+        let ((document_id, field, limit), result) = add_documents(Some(3), documents);
+        assert_eq!(document_id, json!("3"));
+        assert_eq!(field, "c");
+        assert_eq!(limit, 3);
+        assert_eq!(result.indexed_documents, 1);
+        let rejected: Vec<_> =
+            result.rejected_documents.iter().map(|rejected| &rejected.document_id).collect();
+        assert_eq!(rejected, [&S("3")]);
+
+        // the nested fields are counted once flattened, with the default limit
+        let nested: serde_json::Map<_, _> =
+            (0..70_000).map(|i| (format!("k{}", i), json!(i))).collect();
+        let document = json!({ "id": 4, "nested": nested }).as_object().unwrap().clone();
+        let documents = documents_batch_reader_from_objects(vec![document]);
+        let ((document_id, field, limit), result) = add_documents(None, documents);
+        assert_eq!(document_id, json!("4"));
+        assert!(field.starts_with("nested.k"), "{}", field);
+        assert_eq!(limit, crate::update::DEFAULT_MAX_FIELDS);
+        assert_eq!(result.indexed_documents, 0);
+
+        // the limit of a documents addition can't exceed the number of field ids either
+        let document = json!({ "id": 5, "nested": nested }).as_object().unwrap().clone();
+        let documents = documents_batch_reader_from_objects(vec![document]);
+        let ((document_id, _, limit), result) = add_documents(Some(usize::MAX), documents);
+        assert_eq!(document_id, json!("5"));
+        assert_eq!(limit, crate::FieldId::MAX as usize + 1);
+        assert_eq!(result.indexed_documents, 0);
+
+        // none of the fields of the rejected documents were added
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.fields_count(&rtxn).unwrap(), 2);
+        let external_documents_ids = index.external_documents_ids(&rtxn).unwrap();
+        assert!(external_documents_ids.get("2").is_some());
+        assert!(external_documents_ids.get("3").is_none());
+        assert!(external_documents_ids.get("4").is_none());
+    }
+
+    #[test]
+    fn ndjson_and_csv_documents_produce_identical_indexes() {
+        use std::io::Cursor;
+
+        use crate::documents::{DocumentsBatchBuilder, DocumentsBatchReader};
+        use crate::index::db_name::DOCUMENTS;
+        use crate::snapshot_tests::compare_indexes;
+
+        let ndjson_content = r#"
+            { "id": 1, "title": "the quick brown fox", "genre": "fable", "price": 10 }
+            { "id": 2, "title": "jumps over the lazy dog", "genre": "poem", "price": 25 }
+            { "id": 3, "title": "the fox and the grapes", "genre": "fable", "price": 7 }
+        "#;
+        let csv_content = "id:number,title,genre,price:number\n\
+            1,the quick brown fox,fable,10\n\
+            2,jumps over the lazy dog,poem,25\n\
+            3,the fox and the grapes,fable,7\n";
+
+        let ndjson_documents = || {
+            let mut builder = DocumentsBatchBuilder::new(Vec::new());
+            for object in
+                serde_json::Deserializer::from_str(ndjson_content).into_iter::<crate::Object>()
+            {
+                builder.append_json_object(&object.unwrap()).unwrap();
+            }
+            let vector = builder.into_inner().unwrap();
+            DocumentsBatchReader::from_reader(Cursor::new(vector)).unwrap()
+        };
+        let mut builder = DocumentsBatchBuilder::new(Vec::new());
+        builder.append_csv(csv::Reader::from_reader(Cursor::new(csv_content))).unwrap();
+        let vector = builder.into_inner().unwrap();
+        let csv_documents = DocumentsBatchReader::from_reader(Cursor::new(vector)).unwrap();
+
+        let ndjson_index = TempIndex::new();
+        let csv_index = TempIndex::new();
+        for index in [&ndjson_index, &csv_index] {
+            index
+                .update_settings(|settings| {
+                    settings.set_filterable_fields(hashset! { S("genre"), S("price") });
+                })
+                .unwrap();
+        }
+        ndjson_index.add_documents(ndjson_documents()).unwrap();
+        csv_index.add_documents(csv_documents).unwrap();
+
+        let diff = compare_indexes(&ndjson_index, &csv_index);
+        assert!(diff.is_empty(), "{diff}");
+
+        // and the comparison does catch a difference
+        ndjson_index
+            .add_documents(documents!([{ "id": 4, "title": "the lion", "genre": "fable" }]))
+            .unwrap();
+        let diff = compare_indexes(&ndjson_index, &csv_index);
+        let documents_diff = diff.databases.iter().find(|db| db.name == DOCUMENTS).unwrap();
+        assert_eq!(documents_diff.removed.len(), 1);
+        assert!(documents_diff.added.is_empty() && documents_diff.changed.is_empty());
+    }
+
+    #[test]
+    fn indexing_validates_the_keys_order_of_the_typed_chunks() {
+        let mut index = TempIndex::new();
+        index.indexer_config.validate_ordering = true;
+        index
+            .update_settings(|settings| {
+                settings.set_filterable_fields(hashset! { S("genre"), S("price") });
+            })
+            .unwrap();
+
+        // The first batch is appended into the empty databases...
+        index
+            .add_documents(documents!([
+                { "id": 1, "title": "the quick brown fox", "genre": "tale", "price": 12 },
+                { "id": 2, "title": "the lazy dog", "genre": "fable", "price": 3.5 },
+                { "id": 3, "title": "a brown dog", "genre": null, "price": [] },
+            ]))
+            .unwrap();
+        // ...and the second one merged into the existing entries.
+        index
+            .add_documents(documents!([
+                { "id": 4, "title": "the quick dog", "genre": "tale", "price": 12 },
+                { "id": 1, "title": "an old fox", "genre": "novel", "price": 8 },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.number_of_documents(&rtxn).unwrap(), 4);
+        let docids = index.word_docids.get(&rtxn, "dog").unwrap().unwrap();
+        assert_eq!(docids.len(), 3);
+    }
 }
@@ -1,8 +1,10 @@
 use std::borrow::Cow;
-use std::collections::hash_map::Entry;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::convert::TryInto;
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom};
+use std::mem;
+use std::result::Result as StdResult;
 
 use fxhash::FxHashMap;
 use heed::RoTxn;
@@ -12,25 +14,30 @@ use roaring::RoaringBitmap;
 use serde_json::Value;
 use smartstring::SmartString;
 
-use super::helpers::{create_sorter, create_writer, keep_latest_obkv, merge_obkvs, MergeFn};
+use super::helpers::{
+    create_sorter, create_writer, is_json_null, MergeFn, MergeStrategy, REPLACE_DOCUMENT_VERSION,
+    UPDATE_DOCUMENT_VERSION,
+};
 use super::{IndexDocumentsMethod, IndexerConfig};
 use crate::documents::{DocumentsBatchIndex, EnrichedDocument, EnrichedDocumentsBatchReader};
-use crate::error::{Error, InternalError, UserError};
+use crate::error::{Error, InternalError, SerializationError, UserError};
 use crate::index::db_name;
 use crate::update::{AvailableDocumentsIds, UpdateIndexingStep};
 use crate::{
-    ExternalDocumentsIds, FieldDistribution, FieldId, FieldIdMapMissingEntry, FieldsIdsMap, Index,
-    Result, BEU32,
+    ExternalDocumentsIds, FieldDistributionWithTypes, FieldId, FieldIdMapMissingEntry, FieldTypes,
+    FieldsIdsMap, Index, Result, BEU32,
 };
 
 pub struct TransformOutput {
     pub primary_key: String,
     pub fields_ids_map: FieldsIdsMap,
-    pub field_distribution: FieldDistribution,
+    pub field_distribution: FieldDistributionWithTypes,
     pub external_documents_ids: ExternalDocumentsIds<'static>,
     pub new_documents_ids: RoaringBitmap,
     pub replaced_documents_ids: RoaringBitmap,
     pub documents_count: usize,
+    /// The number of documents identical to the stored ones, they are not indexed again.
+    pub unchanged_documents: usize,
     pub original_documents: File,
     pub flattened_documents: File,
 }
@@ -47,39 +54,220 @@ pub struct Transform<'a, 'i> {
 
     indexer_settings: &'a IndexerConfig,
     pub autogenerate_docids: bool,
-    pub index_documents_method: IndexDocumentsMethod,
+    /// Whether a top-level `null` removes the field from the document
+    /// when updating it, only used with `IndexDocumentsMethod::UpdateDocuments`.
+    unset_fields_on_null: bool,
+    /// The fields that are indexed but removed from the documents written in the database.
+    non_stored_fields: HashSet<String>,
+    /// The names and patterns of the fields dropped from the documents before anything else.
+    ignored_fields: HashSet<String>,
     available_documents_ids: AvailableDocumentsIds,
 
     original_sorter: grenad::Sorter<MergeFn>,
     flattened_sorter: grenad::Sorter<MergeFn>,
     replaced_documents_ids: RoaringBitmap,
     new_documents_ids: RoaringBitmap,
-    // To increase the cache locality and decrease the heap usage we use compact smartstring.
-    new_external_documents_ids_builder: FxHashMap<SmartString<smartstring::Compact>, u64>,
+    new_external_documents_ids_builder: NewExternalIds,
     documents_count: usize,
+    unchanged_documents: usize,
+    /// The position in their payload and the external id of the documents rejected
+    /// since the last call to [`Transform::take_rejected_documents`].
+    rejected_documents: Vec<(u32, String)>,
+    /// The maximum number of fields of the index, the documents adding a field above it
+    /// are rejected with an error.
+    max_fields: usize,
+}
+
+/// The internal ids given to the external ids of the documents of the update.
+///
+/// The ids are kept in memory up to the `max_external_ids_memory` of the indexer config,
+/// they are then written, sorted, into a temporary file and looked up from there.
+struct NewExternalIds {
+    // To increase the cache locality and decrease the heap usage we use compact smartstring.
+    in_memory: FxHashMap<SmartString<smartstring::Compact>, u64>,
+    in_memory_size: usize,
+    max_memory: Option<usize>,
+    spilled: Vec<grenad::ReaderCursor<File>>,
+}
+
+impl NewExternalIds {
+    fn new(max_memory: Option<usize>) -> NewExternalIds {
+        NewExternalIds {
+            in_memory: FxHashMap::default(),
+            in_memory_size: 0,
+            max_memory,
+            spilled: Vec::new(),
+        }
+    }
+
+    /// The number of times the ids were written on disk.
+    fn spilled_chunks(&self) -> usize {
+        self.spilled.len()
+    }
+
+    fn get(&mut self, external_id: &str) -> Result<Option<u32>> {
+        if let Some(docid) = self.in_memory.get(external_id) {
+            return Ok(Some(*docid as u32));
+        }
+        for cursor in &mut self.spilled {
+            if let Some((_, docid)) = cursor.move_on_key_equal_to(external_id.as_bytes())? {
+                return decode_docid(docid).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    fn insert(&mut self, external_id: &str, docid: u32) {
+        self.in_memory_size += entry_size(external_id);
+        self.in_memory.insert(external_id.into(), docid as u64);
+    }
+
+    /// Removes the id given to the current document, it is always in memory.
+    fn remove(&mut self, external_id: &str) {
+        if self.in_memory.remove(external_id).is_some() {
+            self.in_memory_size -= entry_size(external_id);
+        }
+    }
+
+    /// Writes the ids held in memory on disk when they exceed the memory budget.
+    fn spill_if_needed(&mut self, indexer_settings: &IndexerConfig) -> Result<()> {
+        match self.max_memory {
+            Some(max_memory) if self.in_memory_size > max_memory => self.spill(indexer_settings),
+            _ => Ok(()),
+        }
+    }
+
+    fn spill(&mut self, indexer_settings: &IndexerConfig) -> Result<()> {
+        let mut writer = create_writer(
+            indexer_settings.chunk_compression_type,
+            indexer_settings.chunk_compression_level,
+            tempfile::tempfile()?,
+        );
+        for (external_id, docid) in self.take_sorted_in_memory() {
+            writer.insert(external_id.as_bytes(), (docid as u32).to_be_bytes())?;
+        }
+        self.spilled.push(writer_into_reader(writer)?.into_cursor()?);
+        Ok(())
+    }
+
+    /// Empties the ids held in memory and returns them sorted by external id.
+    fn take_sorted_in_memory(&mut self) -> Vec<(SmartString<smartstring::Compact>, u64)> {
+        self.in_memory_size = 0;
+        let mut ids: Vec<_> = mem::take(&mut self.in_memory).into_iter().collect();
+        ids.sort_unstable_by(|(left, _), (right, _)| left.cmp(right));
+        ids
+    }
+
+    /// Returns the map of all the ids, the ones written on disk are merged with the ones in memory.
+    fn into_fst(mut self, indexer_settings: &IndexerConfig) -> Result<fst::Map<Vec<u8>>> {
+        let mut builder = fst::MapBuilder::memory();
+        if self.spilled.is_empty() {
+            for (external_id, docid) in self.take_sorted_in_memory() {
+                builder.insert(external_id, docid)?;
+            }
+            return Ok(builder.into_map());
+        }
+
+        // An external id is never written in two chunks, the keys are never merged.
+        self.spill(indexer_settings)?;
+        let mut merger = grenad::MergerBuilder::new(MergeStrategy::REFUSE_MERGING.function);
+        for cursor in self.spilled {
+            merger.push(cursor.into_reader().into_cursor()?);
+        }
+        let mut iter = merger.build().into_stream_merger_iter()?;
+        while let Some((external_id, docid)) = iter.next()? {
+            builder.insert(external_id, decode_docid(docid)? as u64)?;
+        }
+        Ok(builder.into_map())
+    }
+}
+
+/// The approximate memory used by an id held in memory.
+fn entry_size(external_id: &str) -> usize {
+    external_id.len() + mem::size_of::<(SmartString<smartstring::Compact>, u64)>()
+}
+
+fn decode_docid(bytes: &[u8]) -> Result<u32> {
+    let bytes = bytes.try_into().map_err(|_| SerializationError::Decoding { db_name: None })?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+/// Prefixes a version of a document with the operation to apply to its previous versions.
+fn document_version(method: IndexDocumentsMethod, obkv: &[u8]) -> Vec<u8> {
+    let operation = match method {
+        IndexDocumentsMethod::ReplaceDocuments | IndexDocumentsMethod::ReplaceExistingDocuments => {
+            REPLACE_DOCUMENT_VERSION
+        }
+        IndexDocumentsMethod::UpdateDocuments => UPDATE_DOCUMENT_VERSION,
+    };
+    let mut version = Vec::with_capacity(obkv.len() + 1);
+    version.push(operation);
+    version.extend_from_slice(obkv);
+    version
 }
 
 /// Create a mapping between the field ids found in the document batch and the one that were
 /// already present in the index.
 ///
-/// If new fields are present in the addition, they are added to the index field ids map.
+/// If new fields are present in the addition, they are added to the index field ids map,
+/// the ignored fields are not. The fields that would bring the index above `max_fields`
+/// fields are not added and are missing from the mapping.
 fn create_fields_mapping(
     index_field_map: &mut FieldsIdsMap,
     batch_field_map: &DocumentsBatchIndex,
+    ignored_fields: &HashSet<FieldId>,
+    max_fields: usize,
 ) -> Result<HashMap<FieldId, FieldId>> {
-    batch_field_map
+    let mut mapping = HashMap::new();
+    let fields = batch_field_map
         .iter()
+        .filter(|(field, _)| !ignored_fields.contains(field))
         // we sort by id here to ensure a deterministic mapping of the fields, that preserves
         // the original ordering.
-        .sorted_by_key(|(&id, _)| id)
-        .map(|(field, name)| match index_field_map.id(name) {
-            Some(id) => Ok((*field, id)),
-            None => index_field_map
-                .insert(name)
-                .ok_or(Error::UserError(UserError::AttributeLimitReached))
-                .map(|id| (*field, id)),
-        })
-        .collect()
+        .sorted_by_key(|(&id, _)| id);
+    for (&field, name) in fields {
+        if let Some(id) = insert_field(index_field_map, name, max_fields)? {
+            mapping.insert(field, id);
+        }
+    }
+    Ok(mapping)
+}
+
+/// Inserts a field into the fields ids map of the index, returns `None` when
+/// the field is new and the index already contains `max_fields` fields.
+fn insert_field(
+    fields_ids_map: &mut FieldsIdsMap,
+    name: &str,
+    max_fields: usize,
+) -> Result<Option<FieldId>> {
+    match fields_ids_map.id(name) {
+        Some(id) => Ok(Some(id)),
+        None if fields_ids_map.len() >= max_fields => Ok(None),
+        None => match fields_ids_map.insert(name) {
+            Some(id) => Ok(Some(id)),
+            None => Err(Error::UserError(UserError::AttributeLimitReached)),
+        },
+    }
+}
+
+/// Removes the nested fields of the value, named after the given field, that are
+/// matched by one of the ignored fields. The objects in arrays are traversed too.
+fn remove_ignored_fields(field: &str, value: &mut Value, ignored_fields: &HashSet<String>) {
+    match value {
+        Value::Object(object) => object.retain(|key, value| {
+            let nested = format!("{}.{}", field, key);
+            if crate::is_faceted(&nested, ignored_fields) {
+                false
+            } else {
+                remove_ignored_fields(&nested, value, ignored_fields);
+                true
+            }
+        }),
+        Value::Array(values) => {
+            values.iter_mut().for_each(|value| remove_ignored_fields(field, value, ignored_fields))
+        }
+        _otherwise => (),
+    }
 }
 
 impl<'a, 'i> Transform<'a, 'i> {
@@ -87,20 +275,22 @@ impl<'a, 'i> Transform<'a, 'i> {
         wtxn: &mut heed::RwTxn,
         index: &'i Index,
         indexer_settings: &'a IndexerConfig,
-        index_documents_method: IndexDocumentsMethod,
         autogenerate_docids: bool,
+        unset_fields_on_null: bool,
+        max_fields: usize,
     ) -> Result<Self> {
-        // We must choose the appropriate merge function for when two or more documents
-        // with the same user id must be merged or fully replaced in the same batch.
-        let merge_function = match index_documents_method {
-            IndexDocumentsMethod::ReplaceDocuments => keep_latest_obkv,
-            IndexDocumentsMethod::UpdateDocuments => merge_obkvs,
+        // The versions of a document are prefixed by the method of the payload they come from,
+        // they are merged or fully replaced when the same user id is seen in the same batch.
+        let merge_strategy = if unset_fields_on_null {
+            MergeStrategy::MERGE_DOCUMENT_VERSIONS_AND_REMOVE_NULLS
+        } else {
+            MergeStrategy::MERGE_DOCUMENT_VERSIONS
         };
 
         // We initialize the sorter with the user indexing settings.
         let original_sorter = create_sorter(
             grenad::SortAlgorithm::Stable,
-            merge_function,
+            merge_strategy,
             indexer_settings.chunk_compression_type,
             indexer_settings.chunk_compression_level,
             indexer_settings.max_nb_chunks,
@@ -110,7 +300,7 @@ impl<'a, 'i> Transform<'a, 'i> {
         // We initialize the sorter with the user indexing settings.
         let flattened_sorter = create_sorter(
             grenad::SortAlgorithm::Stable,
-            merge_function,
+            merge_strategy,
             indexer_settings.chunk_compression_type,
             indexer_settings.chunk_compression_level,
             indexer_settings.max_nb_chunks,
@@ -130,20 +320,45 @@ impl<'a, 'i> Transform<'a, 'i> {
             ),
             original_sorter,
             flattened_sorter,
-            index_documents_method,
+            unset_fields_on_null,
+            non_stored_fields: index.non_stored_fields(wtxn)?,
+            ignored_fields: index.ignored_fields(wtxn)?,
             replaced_documents_ids: RoaringBitmap::new(),
             new_documents_ids: RoaringBitmap::new(),
-            new_external_documents_ids_builder: FxHashMap::default(),
+            new_external_documents_ids_builder: NewExternalIds::new(
+                indexer_settings.max_external_ids_memory(),
+            ),
             documents_count: 0,
+            unchanged_documents: 0,
+            rejected_documents: Vec::new(),
+            max_fields,
         })
     }
 
+    /// The number of documents read so far that are identical to the stored ones.
+    pub fn unchanged_documents(&self) -> usize {
+        self.unchanged_documents
+    }
+
+    /// Returns the position in their payload and the external id of the documents
+    /// rejected by the payloads read since the last call.
+    pub fn take_rejected_documents(&mut self) -> Vec<(u32, String)> {
+        std::mem::take(&mut self.rejected_documents)
+    }
+
+    /// Reads the documents of a payload, the versions of the documents already read are
+    /// replaced or updated by the versions of this payload depending on the given method.
+    ///
+    /// The documents that would bring the index above `max_fields` fields are rejected without
+    /// adding any of their fields to the index. Returns the number of documents read along with
+    /// the error of the first rejected document, if any.
     pub fn read_documents<R, F>(
         &mut self,
         reader: EnrichedDocumentsBatchReader<R>,
+        method: IndexDocumentsMethod,
         wtxn: &mut heed::RwTxn,
         progress_callback: F,
-    ) -> Result<usize>
+    ) -> Result<(usize, Option<UserError>)>
     where
         R: Read + Seek,
         F: Fn(UpdateIndexingStep) + Sync,
@@ -152,18 +367,76 @@ impl<'a, 'i> Transform<'a, 'i> {
 
         let external_documents_ids = self.index.external_documents_ids(wtxn)?;
 
-        let mapping = create_fields_mapping(&mut self.fields_ids_map, &fields_index)?;
-
         let primary_key = cursor.primary_key().to_string();
+
+        // The fields matched by an ignored field are dropped, the others may still contain
+        // ignored nested fields. The field containing the primary key is never dropped.
+        let mut ignored_fields = HashSet::new();
+        let mut partially_ignored_fields = HashSet::new();
+        for (&field_id, name) in fields_index.iter() {
+            if primary_key == *name || crate::is_faceted_by(&primary_key, name) {
+                continue;
+            } else if crate::is_faceted(name, &self.ignored_fields) {
+                ignored_fields.insert(field_id);
+            } else if self.ignored_fields.iter().any(|ignored| {
+                ignored
+                    .split_once('.')
+                    .map_or(false, |(first, _)| first == "*" || first == name.as_str())
+            }) {
+                partially_ignored_fields.insert(field_id);
+            }
+        }
+
+        let fields_checkpoint = self.fields_ids_map.next_id();
+        let mapping = create_fields_mapping(
+            &mut self.fields_ids_map,
+            &fields_index,
+            &ignored_fields,
+            self.max_fields,
+        )?;
+
+        // Every document needs the primary key, they are all rejected when it can't be added.
         let primary_key_id =
-            self.fields_ids_map.insert(&primary_key).ok_or(UserError::AttributeLimitReached)?;
+            match insert_field(&mut self.fields_ids_map, &primary_key, self.max_fields)? {
+                Some(primary_key_id) => primary_key_id,
+                None => {
+                    self.fields_ids_map.truncate(fields_checkpoint);
+                    let mut document_id = Value::Null;
+                    let mut position = 0;
+                    while let Some(EnrichedDocument { document_id: id, .. }) =
+                        cursor.next_enriched_document()?
+                    {
+                        if position == 0 {
+                            document_id = Value::from(id.value());
+                        }
+                        self.rejected_documents.push((position, id.value().to_string()));
+                        position += 1;
+                    }
+                    let (field, limit) = (primary_key, self.max_fields);
+                    let error = UserError::IndexFieldsLimitReached { document_id, field, limit };
+                    return Ok((0, Some(error)));
+                }
+            };
+
+        // The fields added for this payload are removed when only rejected documents use them.
+        let mut unused_new_fields: HashSet<FieldId> = mapping
+            .values()
+            .copied()
+            .filter(|id| fields_checkpoint.map_or(false, |next_id| *id >= next_id))
+            .filter(|id| *id != primary_key_id)
+            .collect();
+        let mut fields_limit_error = None;
 
         let mut obkv_buffer = Vec::new();
+        let mut documents_decompressor = self.index.documents_decompressor(wtxn)?;
         let mut documents_count = 0;
+        let mut position = 0;
         let mut docid_buffer: Vec<u8> = Vec::new();
         let mut field_buffer: Vec<(u16, Cow<[u8]>)> = Vec::new();
         while let Some(enriched_document) = cursor.next_enriched_document()? {
             let EnrichedDocument { document, document_id } = enriched_document;
+            let document_position = position;
+            position += 1;
 
             // drop_and_reuse is called instead of .clear() to communicate to the compiler that field_buffer
             // does not keep references from the cursor between loop iterations
@@ -171,43 +444,130 @@ impl<'a, 'i> Transform<'a, 'i> {
             if self.indexer_settings.log_every_n.map_or(false, |len| documents_count % len == 0) {
                 progress_callback(UpdateIndexingStep::RemapDocumentAddition {
                     documents_seen: documents_count,
+                    spilled_chunks: self.new_external_documents_ids_builder.spilled_chunks(),
                 });
             }
 
+            let external_id = document_id.value();
+
+            // Only the documents already in the index, or added by a previous document
+            // of the update, can be replaced, the others are rejected and reported.
+            if method == IndexDocumentsMethod::ReplaceExistingDocuments
+                && self.new_external_documents_ids_builder.get(external_id)?.is_none()
+                && external_documents_ids.get(external_id).is_none()
+            {
+                self.rejected_documents.push((document_position, external_id.to_string()));
+                field_buffer = drop_and_reuse(field_buffer_cache);
+                continue;
+            }
+
             // When the document id has been auto-generated by the `enrich_documents_batch`
             // we must insert this document id into the remaped document.
-            let external_id = document_id.value();
             if document_id.is_generated() {
                 serde_json::to_writer(&mut docid_buffer, external_id)
                     .map_err(InternalError::SerdeJson)?;
                 field_buffer_cache.push((primary_key_id, Cow::from(&docid_buffer)));
             }
 
+            let mut unmapped_field = None;
             for (k, v) in document.iter() {
-                let mapped_id =
-                    *mapping.get(&k).ok_or(InternalError::FieldIdMappingMissingEntry { key: k })?;
-                field_buffer_cache.push((mapped_id, Cow::from(v)));
+                if ignored_fields.contains(&k) {
+                    continue;
+                }
+                let mapped_id = match mapping.get(&k) {
+                    Some(mapped_id) => *mapped_id,
+                    // The field wasn't added, the index already contains `max_fields` fields.
+                    None => {
+                        unmapped_field = Some(k);
+                        break;
+                    }
+                };
+                match fields_index.name(k) {
+                    Some(name) if partially_ignored_fields.contains(&k) => {
+                        let mut value: Value =
+                            serde_json::from_slice(v).map_err(InternalError::SerdeJson)?;
+                        remove_ignored_fields(name, &mut value, &self.ignored_fields);
+                        let value = serde_json::to_vec(&value).map_err(InternalError::SerdeJson)?;
+                        field_buffer_cache.push((mapped_id, Cow::Owned(value)));
+                    }
+                    _otherwise => field_buffer_cache.push((mapped_id, Cow::from(v))),
+                }
             }
 
+            let batch_docid = self.new_external_documents_ids_builder.get(external_id)?;
+            let stored_docid = match batch_docid {
+                Some(_) => None,
+                None => external_documents_ids.get(external_id),
+            };
+
             // Insertion in a obkv need to be done with keys ordered. For now they are ordered
             // according to the document addition key order, so we sort it according to the
             // fieldids map keys order.
             field_buffer_cache.sort_unstable_by(|(f1, _), (f2, _)| f1.cmp(f2));
 
-            // Build the new obkv document.
-            let mut writer = obkv::KvWriter::new(&mut obkv_buffer);
-            for (k, v) in field_buffer_cache.iter() {
-                writer.insert(*k, v)?;
+            // When there is no previous version of this document the `null` values
+            // have nothing to unset, we can directly drop them.
+            let drop_nulls = self.unset_fields_on_null
+                && method == IndexDocumentsMethod::UpdateDocuments
+                && batch_docid.is_none()
+                && stored_docid.is_none();
+
+            // The document is rejected, before anything is done with it, when one of its fields
+            // isn't in the index and can't be added, or when flattening it would add a field
+            // above the limit. The flattened fields it added are then removed.
+            let document_checkpoint = self.fields_ids_map.next_id();
+            let flattened = match unmapped_field {
+                Some(field_id) => Err(UserError::IndexFieldsLimitReached {
+                    document_id: Value::from(external_id),
+                    field: fields_index.name(field_id).map(ToString::to_string).unwrap_or_default(),
+                    limit: self.max_fields,
+                }),
+                None => {
+                    // Build the new obkv document.
+                    let mut writer = obkv::KvWriter::new(&mut obkv_buffer);
+                    for (k, v) in field_buffer_cache.iter() {
+                        if drop_nulls && is_json_null(v) {
+                            continue;
+                        }
+                        writer.insert(*k, v)?;
+                    }
+                    writer.finish()?;
+
+                    // When the fields can be unset, the document is only flattened here to add
+                    // its fields, its flattened version is generated from the merged versions.
+                    Self::flatten_from_fields_ids_map(
+                        &mut self.fields_ids_map,
+                        self.max_fields,
+                        KvReader::new(&obkv_buffer),
+                        || Value::from(external_id),
+                    )?
+                }
+            };
+            let flattened = match flattened {
+                Ok(flattened) => flattened,
+                Err(error) => {
+                    self.fields_ids_map.truncate(document_checkpoint);
+                    self.rejected_documents.push((document_position, external_id.to_string()));
+                    fields_limit_error.get_or_insert(error);
+                    field_buffer = drop_and_reuse(field_buffer_cache);
+                    docid_buffer.clear();
+                    obkv_buffer.clear();
+                    continue;
+                }
+            };
+            if !unused_new_fields.is_empty() {
+                for (field_id, _) in &field_buffer_cache {
+                    unused_new_fields.remove(field_id);
+                }
             }
 
             let mut original_docid = None;
-
-            let docid = match self.new_external_documents_ids_builder.entry((*external_id).into()) {
-                Entry::Occupied(entry) => *entry.get() as u32,
-                Entry::Vacant(entry) => {
+            let docid = match batch_docid {
+                Some(docid) => docid,
+                None => {
                     // If the document was already in the db we mark it as a replaced document.
                     // It'll be deleted later. We keep its original docid to insert it in the grenad.
-                    if let Some(docid) = external_documents_ids.get(entry.key()) {
+                    if let Some(docid) = stored_docid {
                         self.replaced_documents_ids.insert(docid);
                         original_docid = Some(docid);
                     }
@@ -215,11 +575,33 @@ impl<'a, 'i> Transform<'a, 'i> {
                         .available_documents_ids
                         .next()
                         .ok_or(UserError::DocumentLimitReached)?;
-                    entry.insert(docid as u64);
+                    self.new_external_documents_ids_builder.insert(external_id, docid);
                     docid
                 }
             };
 
+            // The stored version of the document lacks its non-stored fields, merging the
+            // update into it would remove them from the search databases.
+            if method == IndexDocumentsMethod::UpdateDocuments && original_docid.is_some() {
+                let attributes: BTreeSet<_> = self
+                    .non_stored_fields
+                    .iter()
+                    .filter(|name| {
+                        let field_id = self.fields_ids_map.id(name);
+                        !field_buffer_cache.iter().any(|(fid, _)| Some(*fid) == field_id)
+                    })
+                    .cloned()
+                    .collect();
+                if !attributes.is_empty() {
+                    let document_id = Value::from(external_id);
+                    return Err(UserError::PartialUpdateWithNonStoredAttributes {
+                        document_id,
+                        attributes,
+                    }
+                    .into());
+                }
+            }
+
             let mut skip_insertion = false;
             if let Some(original_docid) = original_docid {
                 let original_key = BEU32::new(original_docid);
@@ -232,6 +614,8 @@ impl<'a, 'i> Transform<'a, 'i> {
                         db_name: db_name::DOCUMENTS,
                         key: None,
                     })?;
+                let base_document = documents_decompressor.decompress(base_obkv)?;
+                let base_obkv = base_document.as_bytes();
 
                 // we check if the two documents are exactly equal. If it's the case we can skip this document entirely
                 if base_obkv == obkv_buffer {
@@ -239,15 +623,24 @@ impl<'a, 'i> Transform<'a, 'i> {
                     self.replaced_documents_ids.remove(original_docid);
                     // and we need to put back the original id as it was before
                     self.new_external_documents_ids_builder.remove(external_id);
+                    self.unchanged_documents += 1;
                     skip_insertion = true;
                 } else {
                     // we associate the base document with the new key, everything will get merged later.
-                    self.original_sorter.insert(&docid.to_be_bytes(), base_obkv)?;
-                    match self.flatten_from_fields_ids_map(KvReader::new(base_obkv))? {
-                        Some(buffer) => {
-                            self.flattened_sorter.insert(docid.to_be_bytes(), &buffer)?
-                        }
-                        None => self.flattened_sorter.insert(docid.to_be_bytes(), base_obkv)?,
+                    let replace = IndexDocumentsMethod::ReplaceDocuments;
+                    self.original_sorter
+                        .insert(&docid.to_be_bytes(), document_version(replace, base_obkv))?;
+                    if !self.unset_fields_on_null {
+                        // The fields of the stored document are already in the fields ids map.
+                        let flattened = Self::flatten_from_fields_ids_map(
+                            &mut self.fields_ids_map,
+                            self.max_fields,
+                            KvReader::new(base_obkv),
+                            || Value::from(external_id),
+                        )??;
+                        let flattened = flattened.as_deref().unwrap_or(base_obkv);
+                        self.flattened_sorter
+                            .insert(docid.to_be_bytes(), document_version(replace, flattened))?;
                     }
                 }
             }
@@ -255,19 +648,23 @@ impl<'a, 'i> Transform<'a, 'i> {
             if !skip_insertion {
                 self.new_documents_ids.insert(docid);
                 // We use the extracted/generated user id as the key for this document.
-                self.original_sorter.insert(&docid.to_be_bytes(), obkv_buffer.clone())?;
-
-                match self.flatten_from_fields_ids_map(KvReader::new(&obkv_buffer))? {
-                    Some(buffer) => self.flattened_sorter.insert(docid.to_be_bytes(), &buffer)?,
-                    None => {
-                        self.flattened_sorter.insert(docid.to_be_bytes(), obkv_buffer.clone())?
-                    }
+                self.original_sorter
+                    .insert(&docid.to_be_bytes(), document_version(method, &obkv_buffer))?;
+
+                // When the fields can be unset, the flattened documents are generated
+                // from the merged original documents, once all the versions are known.
+                if !self.unset_fields_on_null {
+                    let flattened = flattened.as_deref().unwrap_or(&obkv_buffer[..]);
+                    self.flattened_sorter
+                        .insert(docid.to_be_bytes(), document_version(method, flattened))?;
                 }
             }
             documents_count += 1;
 
+            self.new_external_documents_ids_builder.spill_if_needed(self.indexer_settings)?;
             progress_callback(UpdateIndexingStep::RemapDocumentAddition {
                 documents_seen: documents_count,
+                spilled_chunks: self.new_external_documents_ids_builder.spilled_chunks(),
             });
 
             field_buffer = drop_and_reuse(field_buffer_cache);
@@ -277,24 +674,39 @@ impl<'a, 'i> Transform<'a, 'i> {
 
         progress_callback(UpdateIndexingStep::RemapDocumentAddition {
             documents_seen: documents_count,
+            spilled_chunks: self.new_external_documents_ids_builder.spilled_chunks(),
         });
 
+        if fields_limit_error.is_some() {
+            for field_id in unused_new_fields {
+                if let Some(name) = self.fields_ids_map.name(field_id).map(ToString::to_string) {
+                    self.fields_ids_map.remove(&name);
+                }
+            }
+        }
+
         self.index.put_fields_ids_map(wtxn, &self.fields_ids_map)?;
         self.index.put_primary_key(wtxn, &primary_key)?;
         self.documents_count += documents_count;
         // Now that we have a valid sorter that contains the user id and the obkv we
         // give it to the last transforming function which returns the TransformOutput.
-        Ok(documents_count)
+        Ok((documents_count, fields_limit_error))
     }
 
-    // Flatten a document from the fields ids map contained in self and insert the new
+    // Flatten a document from the given fields ids map and insert the new
     // created fields. Returns `None` if the document doesn't need to be flattened.
-    fn flatten_from_fields_ids_map(&mut self, obkv: KvReader<FieldId>) -> Result<Option<Vec<u8>>> {
+    // The `document_id` is only retrieved to report a field above the `max_fields`.
+    fn flatten_from_fields_ids_map(
+        fields_ids_map: &mut FieldsIdsMap,
+        max_fields: usize,
+        obkv: KvReader<FieldId>,
+        document_id: impl FnOnce() -> Value,
+    ) -> Result<StdResult<Option<Vec<u8>>, UserError>> {
         if obkv
             .iter()
             .all(|(_, value)| !json_depth_checker::should_flatten_from_unchecked_slice(value))
         {
-            return Ok(None);
+            return Ok(Ok(None));
         }
 
         // store the keys and values the original obkv + the flattened json
@@ -310,7 +722,7 @@ impl<'a, 'i> Transform<'a, 'i> {
         // all the raw values get inserted directly in the `key_value` vec.
         for (key, value) in obkv.iter() {
             if json_depth_checker::should_flatten_from_unchecked_slice(value) {
-                let key = self.fields_ids_map.name(key).ok_or(FieldIdMapMissingEntry::FieldId {
+                let key = fields_ids_map.name(key).ok_or(FieldIdMapMissingEntry::FieldId {
                     field_id: key,
                     process: "Flatten from fields ids map.",
                 })?;
@@ -328,7 +740,17 @@ impl<'a, 'i> Transform<'a, 'i> {
         // Once we have the flattened version we insert all the new generated fields_ids
         // (if any) in the fields ids map and serialize the value.
         for (key, value) in flattened.into_iter() {
-            let fid = self.fields_ids_map.insert(&key).ok_or(UserError::AttributeLimitReached)?;
+            let fid = match insert_field(fields_ids_map, &key, max_fields)? {
+                Some(fid) => fid,
+                None => {
+                    let (document_id, limit) = (document_id(), max_fields);
+                    return Ok(Err(UserError::IndexFieldsLimitReached {
+                        document_id,
+                        field: key,
+                        limit,
+                    }));
+                }
+            };
             let value = serde_json::to_vec(&value).map_err(InternalError::SerdeJson)?;
             key_value.push((fid, value.into()));
         }
@@ -339,7 +761,7 @@ impl<'a, 'i> Transform<'a, 'i> {
 
         let mut buffer = Vec::new();
         Self::create_obkv_from_key_value(&mut key_value, &mut buffer)?;
-        Ok(Some(buffer))
+        Ok(Ok(Some(buffer)))
     }
 
     /// Generate an obkv from a slice of key / value sorted by key.
@@ -409,14 +831,17 @@ impl<'a, 'i> Transform<'a, 'i> {
     fn remove_deleted_documents_from_field_distribution(
         &self,
         rtxn: &RoTxn,
-        field_distribution: &mut FieldDistribution,
+        field_distribution: &mut FieldDistributionWithTypes,
     ) -> Result<()> {
+        let mut documents_decompressor = self.index.documents_decompressor(rtxn)?;
+        let documents = self.index.documents.remap_data_type::<heed::types::ByteSlice>();
         for deleted_docid in self.replaced_documents_ids.iter() {
-            let obkv = self.index.documents.get(rtxn, &BEU32::new(deleted_docid))?.ok_or(
+            let bytes = documents.get(rtxn, &BEU32::new(deleted_docid))?.ok_or(
                 InternalError::DatabaseMissingEntry { db_name: db_name::DOCUMENTS, key: None },
             )?;
+            let document = documents_decompressor.decompress(bytes)?;
 
-            for (key, _) in obkv.iter() {
+            for (key, _) in document.as_obkv().iter() {
                 let name =
                     self.fields_ids_map.name(key).ok_or(FieldIdMapMissingEntry::FieldId {
                         field_id: key,
@@ -429,8 +854,8 @@ impl<'a, 'i> Transform<'a, 'i> {
                         field_id: key,
                         process: "Accessing field distribution in transform.",
                     })?;
-                *field -= 1;
-                if *field == 0 {
+                field.count -= 1;
+                if field.count == 0 {
                     // since we were able to get the field right before it's safe to unwrap here
                     field_distribution.remove(name).unwrap();
                 }
@@ -443,7 +868,7 @@ impl<'a, 'i> Transform<'a, 'i> {
     /// format like CSV, JSON or JSON stream. This sorter must contain a key that is the document
     /// id for the user side and the value must be an obkv where keys are valid fields ids.
     pub(crate) fn output_from_sorter<F>(
-        self,
+        mut self,
         wtxn: &mut heed::RwTxn,
         progress_callback: F,
     ) -> Result<TransformOutput>
@@ -468,16 +893,44 @@ impl<'a, 'i> Transform<'a, 'i> {
         // To compute the field distribution we need to;
         // 1. Remove all the deleted documents from the field distribution
         // 2. Add all the new documents to the field distribution
-        let mut field_distribution = self.index.field_distribution(wtxn)?;
+        let mut field_distribution = self.index.field_distribution_with_types(wtxn)?;
 
         self.remove_deleted_documents_from_field_distribution(wtxn, &mut field_distribution)?;
 
+        // We create a final writer to write the new flattened documents in order.
+        let mut flattened_writer = create_writer(
+            self.indexer_settings.chunk_compression_type,
+            self.indexer_settings.chunk_compression_level,
+            tempfile::tempfile()?,
+        );
+
         // Here we are going to do the document count + field distribution + `write_into_stream_writer`
         let mut iter = self.original_sorter.into_stream_merger_iter()?;
         // used only for the callback
         let mut documents_count = 0;
+        let mut obkv_buffer = Vec::new();
+        let mut stripped_buffer = Vec::new();
+        let non_stored_fields_ids: HashSet<FieldId> =
+            self.non_stored_fields.iter().filter_map(|name| self.fields_ids_map.id(name)).collect();
+        let primary_key_id = self.fields_ids_map.id(&primary_key);
+
+        while let Some((key, version)) = iter.next()? {
+            let (&operation, mut val) =
+                version.split_first().expect("Invalid document version, missing its operation");
+            // A document only made of updating versions is a new document, the fields
+            // its last versions set to `null` have no previous value to unset.
+            if self.unset_fields_on_null && operation == UPDATE_DOCUMENT_VERSION {
+                obkv_buffer.clear();
+                let mut writer = KvWriter::new(&mut obkv_buffer);
+                for (field, value) in KvReader::<FieldId>::new(val).iter() {
+                    if !is_json_null(value) {
+                        writer.insert(field, value)?;
+                    }
+                }
+                writer.finish()?;
+                val = &obkv_buffer[..];
+            }
 
-        while let Some((key, val)) = iter.next()? {
             // send a callback to show at which step we are
             documents_count += 1;
             progress_callback(UpdateIndexingStep::ComputeIdsAndMergeDocuments {
@@ -488,43 +941,75 @@ impl<'a, 'i> Transform<'a, 'i> {
             // We increment all the field of the current document in the field distribution.
             let obkv = KvReader::new(val);
 
-            for (key, _) in obkv.iter() {
+            for (key, value) in obkv.iter() {
+                // The deletion of a document only sees its stored fields.
+                if non_stored_fields_ids.contains(&key) {
+                    continue;
+                }
                 let name =
                     self.fields_ids_map.name(key).ok_or(FieldIdMapMissingEntry::FieldId {
                         field_id: key,
                         process: "Computing field distribution in transform.",
                     })?;
-                *field_distribution.entry(name.to_string()).or_insert(0) += 1;
+                let entry = field_distribution.entry(name.to_string()).or_default();
+                entry.count += 1;
+                entry.types.insert(FieldTypes::from_json_bytes(value));
+            }
+
+            // The flattened version of the document must be computed
+            // from the merged document where the fields were unset.
+            if self.unset_fields_on_null {
+                let external_id = primary_key_id.and_then(|id| obkv.get(id));
+                let document_id = || {
+                    let external_id = external_id.and_then(|id| serde_json::from_slice(id).ok());
+                    external_id.unwrap_or(Value::Null)
+                };
+                // The fields of the versions of the document were added when they were read.
+                match Self::flatten_from_fields_ids_map(
+                    &mut self.fields_ids_map,
+                    self.max_fields,
+                    obkv,
+                    document_id,
+                )?? {
+                    Some(buffer) => flattened_writer.insert(key, &buffer)?,
+                    None => flattened_writer.insert(key, val)?,
+                }
+            }
+
+            // The non-stored fields are only removed from the documents written in
+            // the database, the flattened documents they are extracted from keep them.
+            if non_stored_fields_ids.is_empty() {
+                writer.insert(key, val)?;
+            } else {
+                stripped_buffer.clear();
+                let mut stripped = KvWriter::new(&mut stripped_buffer);
+                for (field, value) in obkv.iter() {
+                    if !non_stored_fields_ids.contains(&field) {
+                        stripped.insert(field, value)?;
+                    }
+                }
+                stripped.finish()?;
+                writer.insert(key, &stripped_buffer)?;
             }
-            writer.insert(key, val)?;
         }
 
         let mut original_documents = writer.into_inner()?;
         // We then extract the file and reset the seek to be able to read it again.
         original_documents.seek(SeekFrom::Start(0))?;
 
-        // We create a final writer to write the new documents in order from the sorter.
-        let mut writer = create_writer(
-            self.indexer_settings.chunk_compression_type,
-            self.indexer_settings.chunk_compression_level,
-            tempfile::tempfile()?,
-        );
         // Once we have written all the documents into the final sorter, we write the documents
         // into this writer, extract the file and reset the seek to be able to read it again.
-        self.flattened_sorter.write_into_stream_writer(&mut writer)?;
-        let mut flattened_documents = writer.into_inner()?;
+        if !self.unset_fields_on_null {
+            let mut iter = self.flattened_sorter.into_stream_merger_iter()?;
+            while let Some((key, version)) = iter.next()? {
+                flattened_writer.insert(key, version.get(1..).unwrap_or_default())?;
+            }
+        }
+        let mut flattened_documents = flattened_writer.into_inner()?;
         flattened_documents.seek(SeekFrom::Start(0))?;
 
-        let mut new_external_documents_ids_builder: Vec<_> =
-            self.new_external_documents_ids_builder.into_iter().collect();
-
-        new_external_documents_ids_builder
-            .sort_unstable_by(|(left, _), (right, _)| left.cmp(right));
-        let mut fst_new_external_documents_ids_builder = fst::MapBuilder::memory();
-        new_external_documents_ids_builder.into_iter().try_for_each(|(key, value)| {
-            fst_new_external_documents_ids_builder.insert(key, value)
-        })?;
-        let new_external_documents_ids = fst_new_external_documents_ids_builder.into_map();
+        let new_external_documents_ids =
+            self.new_external_documents_ids_builder.into_fst(self.indexer_settings)?;
         external_documents_ids.insert_ids(&new_external_documents_ids)?;
 
         Ok(TransformOutput {
@@ -535,6 +1020,7 @@ impl<'a, 'i> Transform<'a, 'i> {
             new_documents_ids: self.new_documents_ids,
             replaced_documents_ids: self.replaced_documents_ids,
             documents_count: self.documents_count,
+            unchanged_documents: self.unchanged_documents,
             original_documents,
             flattened_documents,
         })
@@ -552,7 +1038,7 @@ impl<'a, 'i> Transform<'a, 'i> {
         // There already has been a document addition, the primary key should be set by now.
         let primary_key =
             self.index.primary_key(wtxn)?.ok_or(UserError::MissingPrimaryKey)?.to_string();
-        let field_distribution = self.index.field_distribution(wtxn)?;
+        let field_distribution = self.index.field_distribution_with_types(wtxn)?;
         let external_documents_ids = self.index.external_documents_ids(wtxn)?;
         let documents_ids = self.index.documents_ids(wtxn)?;
         let documents_count = documents_ids.len() as usize;
@@ -572,9 +1058,13 @@ impl<'a, 'i> Transform<'a, 'i> {
         );
 
         let mut obkv_buffer = Vec::new();
-        for result in self.index.documents.iter(wtxn)? {
-            let (docid, obkv) = result?;
+        let mut documents_decompressor = self.index.documents_decompressor(wtxn)?;
+        let documents = self.index.documents.remap_data_type::<heed::types::ByteSlice>();
+        for result in documents.iter(wtxn)? {
+            let (docid, bytes) = result?;
             let docid = docid.get();
+            let document = documents_decompressor.decompress(bytes)?;
+            let obkv = document.as_obkv();
 
             obkv_buffer.clear();
             let mut obkv_writer = obkv::KvWriter::<_, FieldId>::new(&mut obkv_buffer);
@@ -617,8 +1107,20 @@ impl<'a, 'i> Transform<'a, 'i> {
             });
 
             for (key, value) in flattened {
-                let fid =
-                    new_fields_ids_map.insert(&key).ok_or(UserError::AttributeLimitReached)?;
+                let fid = match insert_field(&mut new_fields_ids_map, &key, self.max_fields)? {
+                    Some(fid) => fid,
+                    None => {
+                        let document_id = new_fields_ids_map
+                            .id(&primary_key)
+                            .and_then(|id| reader.get(id))
+                            .and_then(|id| serde_json::from_slice(id).ok())
+                            .unwrap_or(Value::Null);
+                        let limit = self.max_fields;
+                        let error =
+                            UserError::IndexFieldsLimitReached { document_id, field: key, limit };
+                        return Err(error.into());
+                    }
+                };
                 let value = serde_json::to_vec(&value).map_err(InternalError::SerdeJson)?;
                 writer.insert(fid, &value)?;
             }
@@ -641,6 +1143,7 @@ impl<'a, 'i> Transform<'a, 'i> {
             new_documents_ids: documents_ids,
             replaced_documents_ids: RoaringBitmap::default(),
             documents_count,
+            unchanged_documents: 0,
             original_documents,
             flattened_documents,
         })
@@ -9,11 +9,16 @@ use heed::{BytesDecode, RwTxn};
 use roaring::RoaringBitmap;
 
 use super::helpers::{
-    self, merge_ignore_values, roaring_bitmap_from_u32s_array, serialize_roaring_bitmap,
-    valid_lmdb_key, CursorClonableMmap,
+    self, create_writer, roaring_bitmap_from_u32s_array, serialize_roaring_bitmap, valid_lmdb_key,
+    writer_into_reader, CursorClonableMmap, GrenadParameters, KeysOrderValidator, MergeStrategy,
 };
-use super::{ClonableMmap, MergeFn};
+use super::ClonableMmap;
+use crate::documents_compression::{
+    recompress_documents, train_dictionary, DocumentsCompressor, DICTIONARY_SAMPLES,
+};
+use crate::error::SerializationError;
 use crate::heed_codec::facet::{decode_prefix_string, encode_prefix_string};
+use crate::index::db_name;
 use crate::update::index_documents::helpers::as_cloneable_grenad;
 use crate::{
     lat_lng_to_xyz, BoRoaringBitmapCodec, CboRoaringBitmapCodec, DocumentId, GeoPoint, Index,
@@ -22,30 +27,103 @@ use crate::{
 
 pub(crate) enum TypedChunk {
     DocidWordPositions(grenad::Reader<CursorClonableMmap>),
+    /// The positions of the words serialized as bitmaps by [`prepare_typed_chunk`].
+    DocidWordPositionsBitmaps(grenad::Reader<File>),
     FieldIdDocidFacetStrings(grenad::Reader<CursorClonableMmap>),
     FieldIdDocidFacetNumbers(grenad::Reader<CursorClonableMmap>),
     Documents(grenad::Reader<CursorClonableMmap>),
+    /// The documents compressed by [`prepare_typed_chunk`] with the dictionary of the index.
+    CompressedDocuments(grenad::Reader<File>),
     FieldIdWordcountDocids(grenad::Reader<File>),
     NewDocumentsIds(RoaringBitmap),
     WordDocids {
         word_docids_reader: grenad::Reader<File>,
         exact_word_docids_reader: grenad::Reader<File>,
+        /// The words of both readers, built by [`prepare_typed_chunk`].
+        words_fst: Option<fst::Set<Vec<u8>>>,
     },
     WordPositionDocids(grenad::Reader<File>),
     WordPairProximityDocids(grenad::Reader<File>),
     FieldIdFacetStringDocids(grenad::Reader<File>),
     FieldIdFacetNumberDocids(grenad::Reader<File>),
     FieldIdFacetExistsDocids(grenad::Reader<File>),
+    FieldIdFacetIsNullDocids(grenad::Reader<File>),
+    FieldIdFacetIsEmptyDocids(grenad::Reader<File>),
     GeoPoints(grenad::Reader<File>),
 }
 
+/// Does the work of a typed chunk that doesn't need the write transaction: serializes the
+/// positions of the words, builds the words fst and compresses the documents when the index
+/// already has a compression dictionary. It runs on the rayon pool while the previous chunks
+/// are written, the other chunks are returned as is.
+pub(crate) fn prepare_typed_chunk(
+    typed_chunk: TypedChunk,
+    documents_dictionary: Option<&[u8]>,
+    indexer: GrenadParameters,
+) -> Result<TypedChunk> {
+    match typed_chunk {
+        TypedChunk::DocidWordPositions(docid_word_positions_iter) => {
+            let mut writer = create_writer(
+                indexer.chunk_compression_type,
+                indexer.chunk_compression_level,
+                tempfile::tempfile()?,
+            );
+            let mut buffer = Vec::new();
+            let mut cursor = docid_word_positions_iter.into_cursor()?;
+            while let Some((key, value)) = cursor.move_on_next()? {
+                buffer.clear();
+                // ensure that values are unique and ordered
+                let positions = roaring_bitmap_from_u32s_array(value);
+                BoRoaringBitmapCodec::serialize_into(&positions, &mut buffer);
+                writer.insert(key, &buffer)?;
+            }
+            Ok(TypedChunk::DocidWordPositionsBitmaps(writer_into_reader(writer)?))
+        }
+        TypedChunk::Documents(obkv_documents_iter) => match documents_dictionary {
+            Some(dictionary) => {
+                let mut compressor = DocumentsCompressor::new(dictionary)?;
+                let mut writer = create_writer(
+                    indexer.chunk_compression_type,
+                    indexer.chunk_compression_level,
+                    tempfile::tempfile()?,
+                );
+                let mut cursor = obkv_documents_iter.into_cursor()?;
+                while let Some((key, value)) = cursor.move_on_next()? {
+                    writer.insert(key, compressor.compress(value)?)?;
+                }
+                Ok(TypedChunk::CompressedDocuments(writer_into_reader(writer)?))
+            }
+            // The dictionary is trained on the first documents written.
+            None => Ok(TypedChunk::Documents(obkv_documents_iter)),
+        },
+        TypedChunk::WordDocids {
+            word_docids_reader,
+            exact_word_docids_reader,
+            words_fst: None,
+        } => {
+            let words_fst = merge_word_docids_reader_into_fst(
+                unsafe { as_cloneable_grenad(&word_docids_reader) }?,
+                unsafe { as_cloneable_grenad(&exact_word_docids_reader) }?,
+            )?;
+            Ok(TypedChunk::WordDocids {
+                word_docids_reader,
+                exact_word_docids_reader,
+                words_fst: Some(words_fst),
+            })
+        }
+        otherwise => Ok(otherwise),
+    }
+}
+
 /// Write typed chunk in the corresponding LMDB database of the provided index.
+/// The order of the written keys is checked when `validate_ordering` is set.
 /// Return new documents seen.
 pub(crate) fn write_typed_chunk_into_index(
     typed_chunk: TypedChunk,
     index: &Index,
     wtxn: &mut RwTxn,
     index_is_empty: bool,
+    validate_ordering: bool,
 ) -> Result<(RoaringBitmap, bool)> {
     let mut is_merged_database = false;
     match typed_chunk {
@@ -55,6 +133,7 @@ pub(crate) fn write_typed_chunk_into_index(
                 &index.docid_word_positions,
                 wtxn,
                 index_is_empty,
+                KeysOrderValidator::new(db_name::DOCID_WORD_POSITIONS, validate_ordering),
                 |value, buffer| {
                     // ensure that values are unique and ordered
                     let positions = roaring_bitmap_from_u32s_array(value);
@@ -72,10 +151,65 @@ pub(crate) fn write_typed_chunk_into_index(
                 },
             )?;
         }
+        TypedChunk::DocidWordPositionsBitmaps(docid_word_positions_iter) => {
+            write_entries_into_database(
+                docid_word_positions_iter,
+                &index.docid_word_positions,
+                wtxn,
+                index_is_empty,
+                KeysOrderValidator::new(db_name::DOCID_WORD_POSITIONS, validate_ordering),
+                |value, _buffer| Ok(value),
+                |new_values, db_values, buffer| {
+                    let new_values = BoRoaringBitmapCodec::bytes_decode(new_values).ok_or(
+                        SerializationError::Decoding {
+                            db_name: Some(db_name::DOCID_WORD_POSITIONS),
+                        },
+                    )?;
+                    let positions = match BoRoaringBitmapCodec::bytes_decode(db_values) {
+                        Some(db_values) => new_values | db_values,
+                        None => new_values, // should not happen
+                    };
+                    BoRoaringBitmapCodec::serialize_into(&positions, buffer);
+                    Ok(())
+                },
+            )?;
+        }
         TypedChunk::Documents(obkv_documents_iter) => {
+            let dictionary = match index.documents_compression_dictionary(wtxn)? {
+                Some(dictionary) => Some(dictionary.to_vec()),
+                None if index.documents_compression(wtxn)? => {
+                    // The compression dictionary is trained on the first batch of documents.
+                    let mut samples = Vec::new();
+                    let mut cursor = obkv_documents_iter.clone().into_cursor()?;
+                    while let Some((_key, value)) = cursor.move_on_next()? {
+                        if samples.len() == DICTIONARY_SAMPLES {
+                            break;
+                        }
+                        samples.push(value.to_vec());
+                    }
+                    let dictionary = train_dictionary(&samples);
+                    recompress_documents(wtxn, index, None, Some(&dictionary))?;
+                    index.put_documents_compression_dictionary(wtxn, &dictionary)?;
+                    Some(dictionary)
+                }
+                None => None,
+            };
+            let mut compressor = dictionary.as_deref().map(DocumentsCompressor::new).transpose()?;
+
+            let documents = index.documents.remap_types::<ByteSlice, ByteSlice>();
             let mut cursor = obkv_documents_iter.into_cursor()?;
             while let Some((key, value)) = cursor.move_on_next()? {
-                index.documents.remap_types::<ByteSlice, ByteSlice>().put(wtxn, key, value)?;
+                match &mut compressor {
+                    Some(compressor) => documents.put(wtxn, key, &compressor.compress(value)?)?,
+                    None => documents.put(wtxn, key, value)?,
+                }
+            }
+        }
+        TypedChunk::CompressedDocuments(compressed_documents_iter) => {
+            let documents = index.documents.remap_types::<ByteSlice, ByteSlice>();
+            let mut cursor = compressed_documents_iter.into_cursor()?;
+            while let Some((key, value)) = cursor.move_on_next()? {
+                documents.put(wtxn, key, value)?;
             }
         }
         TypedChunk::FieldIdWordcountDocids(fid_word_count_docids_iter) => {
@@ -84,6 +218,7 @@ pub(crate) fn write_typed_chunk_into_index(
                 &index.field_id_word_count_docids,
                 wtxn,
                 index_is_empty,
+                KeysOrderValidator::new(db_name::FIELD_ID_WORD_COUNT_DOCIDS, validate_ordering),
                 |value, _buffer| Ok(value),
                 merge_cbo_roaring_bitmaps,
             )?;
@@ -92,13 +227,14 @@ pub(crate) fn write_typed_chunk_into_index(
         TypedChunk::NewDocumentsIds(documents_ids) => {
             return Ok((documents_ids, is_merged_database))
         }
-        TypedChunk::WordDocids { word_docids_reader, exact_word_docids_reader } => {
+        TypedChunk::WordDocids { word_docids_reader, exact_word_docids_reader, words_fst } => {
             let word_docids_iter = unsafe { as_cloneable_grenad(&word_docids_reader) }?;
             append_entries_into_database(
                 word_docids_iter.clone(),
                 &index.word_docids,
                 wtxn,
                 index_is_empty,
+                KeysOrderValidator::new(db_name::WORD_DOCIDS, validate_ordering),
                 |value, _buffer| Ok(value),
                 merge_roaring_bitmaps,
             )?;
@@ -109,12 +245,18 @@ pub(crate) fn write_typed_chunk_into_index(
                 &index.exact_word_docids,
                 wtxn,
                 index_is_empty,
+                KeysOrderValidator::new(db_name::EXACT_WORD_DOCIDS, validate_ordering),
                 |value, _buffer| Ok(value),
                 merge_roaring_bitmaps,
             )?;
 
-            // create fst from word docids
-            let fst = merge_word_docids_reader_into_fst(word_docids_iter, exact_word_docids_iter)?;
+            // create fst from word docids, unless it was already created
+            let fst = match words_fst {
+                Some(fst) => fst,
+                None => {
+                    merge_word_docids_reader_into_fst(word_docids_iter, exact_word_docids_iter)?
+                }
+            };
             let db_fst = index.words_fst(wtxn)?;
 
             // merge new fst with database fst
@@ -131,6 +273,7 @@ pub(crate) fn write_typed_chunk_into_index(
                 &index.word_position_docids,
                 wtxn,
                 index_is_empty,
+                KeysOrderValidator::new(db_name::WORD_POSITION_DOCIDS, validate_ordering),
                 |value, _buffer| Ok(value),
                 merge_cbo_roaring_bitmaps,
             )?;
@@ -142,6 +285,7 @@ pub(crate) fn write_typed_chunk_into_index(
                 &index.facet_id_f64_docids,
                 wtxn,
                 index_is_empty,
+                KeysOrderValidator::new(db_name::FACET_ID_F64_DOCIDS, validate_ordering),
                 |value, _buffer| Ok(value),
                 merge_cbo_roaring_bitmaps,
             )?;
@@ -153,6 +297,31 @@ pub(crate) fn write_typed_chunk_into_index(
                 &index.facet_id_exists_docids,
                 wtxn,
                 index_is_empty,
+                KeysOrderValidator::new(db_name::FACET_ID_EXISTS_DOCIDS, validate_ordering),
+                |value, _buffer| Ok(value),
+                merge_cbo_roaring_bitmaps,
+            )?;
+            is_merged_database = true;
+        }
+        TypedChunk::FieldIdFacetIsNullDocids(facet_id_is_null_docids) => {
+            append_entries_into_database(
+                facet_id_is_null_docids,
+                &index.facet_id_is_null_docids,
+                wtxn,
+                index_is_empty,
+                KeysOrderValidator::new(db_name::FACET_ID_IS_NULL_DOCIDS, validate_ordering),
+                |value, _buffer| Ok(value),
+                merge_cbo_roaring_bitmaps,
+            )?;
+            is_merged_database = true;
+        }
+        TypedChunk::FieldIdFacetIsEmptyDocids(facet_id_is_empty_docids) => {
+            append_entries_into_database(
+                facet_id_is_empty_docids,
+                &index.facet_id_is_empty_docids,
+                wtxn,
+                index_is_empty,
+                KeysOrderValidator::new(db_name::FACET_ID_IS_EMPTY_DOCIDS, validate_ordering),
                 |value, _buffer| Ok(value),
                 merge_cbo_roaring_bitmaps,
             )?;
@@ -164,6 +333,7 @@ pub(crate) fn write_typed_chunk_into_index(
                 &index.word_pair_proximity_docids,
                 wtxn,
                 index_is_empty,
+                KeysOrderValidator::new(db_name::WORD_PAIR_PROXIMITY_DOCIDS, validate_ordering),
                 |value, _buffer| Ok(value),
                 merge_cbo_roaring_bitmaps,
             )?;
@@ -195,14 +365,21 @@ pub(crate) fn write_typed_chunk_into_index(
                 &index.facet_id_string_docids,
                 wtxn,
                 index_is_empty,
+                KeysOrderValidator::new(db_name::FACET_ID_STRING_DOCIDS, validate_ordering),
                 |value, _buffer| Ok(value),
                 |new_values, db_values, buffer| {
-                    let (_, new_values) = decode_prefix_string(new_values).unwrap();
+                    let decoding_error = || SerializationError::Decoding {
+                        db_name: Some(db_name::FACET_ID_STRING_DOCIDS),
+                    };
+                    let (new_original, new_values) =
+                        decode_prefix_string(new_values).ok_or_else(decoding_error)?;
                     let new_values = RoaringBitmap::deserialize_from(new_values)?;
-                    let (db_original, db_values) = decode_prefix_string(db_values).unwrap();
+                    let (db_original, db_values) =
+                        decode_prefix_string(db_values).ok_or_else(decoding_error)?;
                     let db_values = RoaringBitmap::deserialize_from(db_values)?;
                     let values = new_values | db_values;
-                    encode_prefix_string(db_original, buffer)?;
+                    // we keep the smallest original string to not depend on the documents order
+                    encode_prefix_string(db_original.min(new_original), buffer)?;
                     Ok(values.serialize_into(buffer)?)
                 },
             )?;
@@ -238,7 +415,7 @@ fn merge_word_docids_reader_into_fst(
     word_docids_iter: grenad::Reader<io::Cursor<ClonableMmap>>,
     exact_word_docids_iter: grenad::Reader<io::Cursor<ClonableMmap>>,
 ) -> Result<fst::Set<Vec<u8>>> {
-    let mut merger_builder = MergerBuilder::new(merge_ignore_values as MergeFn);
+    let mut merger_builder = MergerBuilder::new(MergeStrategy::MERGE_IGNORE_VALUES.function);
     merger_builder.push(word_docids_iter.into_cursor()?);
     merger_builder.push(exact_word_docids_iter.into_cursor()?);
     let mut iter = merger_builder.build().into_stream_merger_iter()?;
@@ -276,6 +453,7 @@ fn write_entries_into_database<R, K, V, FS, FM>(
     database: &heed::Database<K, V>,
     wtxn: &mut RwTxn,
     index_is_empty: bool,
+    mut validator: KeysOrderValidator,
     serialize_value: FS,
     merge_values: FM,
 ) -> Result<()>
@@ -290,6 +468,7 @@ where
     let mut cursor = data.into_cursor()?;
     while let Some((key, value)) = cursor.move_on_next()? {
         if valid_lmdb_key(key) {
+            validator.check(key)?;
             buffer.clear();
             let value = if index_is_empty {
                 serialize_value(value, &mut buffer)?
@@ -318,6 +497,7 @@ fn append_entries_into_database<R, K, V, FS, FM>(
     database: &heed::Database<K, V>,
     wtxn: &mut RwTxn,
     index_is_empty: bool,
+    mut validator: KeysOrderValidator,
     serialize_value: FS,
     merge_values: FM,
 ) -> Result<()>
@@ -332,6 +512,7 @@ where
             database,
             wtxn,
             false,
+            validator,
             serialize_value,
             merge_values,
         );
@@ -343,6 +524,7 @@ where
     let mut cursor = data.into_cursor()?;
     while let Some((key, value)) = cursor.move_on_next()? {
         if valid_lmdb_key(key) {
+            validator.check(key)?;
             buffer.clear();
             let value = serialize_value(value, &mut buffer)?;
             unsafe { database.append(key, value)? };
@@ -1,6 +1,12 @@
 use grenad::CompressionType;
 use rayon::ThreadPool;
 
+use crate::index::DEFAULT_MAX_PROXIMITY;
+use crate::FieldId;
+
+/// The default maximum number of fields of an index, below the 65,536 field ids available.
+pub const DEFAULT_MAX_FIELDS: usize = 65_000;
+
 #[derive(Debug)]
 pub struct IndexerConfig {
     pub log_every_n: Option<usize>,
@@ -11,6 +17,25 @@ pub struct IndexerConfig {
     pub chunk_compression_level: Option<u32>,
     pub thread_pool: Option<ThreadPool>,
     pub max_positions_per_attributes: Option<u32>,
+    /// The maximum proximity between two words stored in the word pair proximity database,
+    /// it is clamped between 2 and 7, the default. Changing it reindexes all the documents
+    /// during the next documents addition.
+    pub max_proximity: Option<u8>,
+    /// The limits on the size of each indexed document.
+    pub document_limits: DocumentLimits,
+    /// Whether the keys written into the facet and prefix databases are checked to be
+    /// strictly increasing, enabled by default in the debug builds only.
+    pub validate_ordering: bool,
+    /// Whether the chunks extracted in parallel are merged in an order that only depends
+    /// on their content, for the databases to be identical from one indexing to the next.
+    pub deterministic: bool,
+    /// The memory the ids given to the documents of an update can use before being written
+    /// on disk, a quarter of the `max_memory` by default and unbounded without it.
+    pub max_external_ids_memory: Option<usize>,
+    /// The maximum number of fields of an index, the flattened nested fields included,
+    /// the documents that would add a field above it are rejected. Defaults to
+    /// [`DEFAULT_MAX_FIELDS`] and can be overriden by each documents addition.
+    pub max_fields: Option<usize>,
 }
 
 impl Default for IndexerConfig {
@@ -24,6 +49,64 @@ impl Default for IndexerConfig {
             chunk_compression_level: None,
             thread_pool: None,
             max_positions_per_attributes: None,
+            max_proximity: None,
+            document_limits: DocumentLimits::default(),
+            validate_ordering: cfg!(debug_assertions),
+            deterministic: false,
+            max_external_ids_memory: None,
+            max_fields: None,
         }
     }
 }
+
+impl IndexerConfig {
+    /// Returns the maximum proximity that must be stored in the word pair proximity database.
+    pub fn max_proximity(&self) -> u8 {
+        self.max_proximity.map_or(DEFAULT_MAX_PROXIMITY, |max| max.clamp(2, DEFAULT_MAX_PROXIMITY))
+    }
+
+    /// Returns the memory the new external ids can use in the transform step.
+    pub fn max_external_ids_memory(&self) -> Option<usize> {
+        self.max_external_ids_memory.or_else(|| self.max_memory.map(|mem| mem / 4))
+    }
+
+    /// Returns the maximum number of fields of an index, it can't exceed the number of field ids.
+    pub fn max_fields(&self) -> usize {
+        self.max_fields_overridden_by(None)
+    }
+
+    /// Returns the maximum number of fields of an index with the one of a documents addition
+    /// taking precedence, none of them can exceed the number of field ids.
+    pub(crate) fn max_fields_overridden_by(&self, max_fields: Option<usize>) -> usize {
+        let field_ids = FieldId::MAX as usize + 1;
+        max_fields.or(self.max_fields).map_or(DEFAULT_MAX_FIELDS, |max| max.min(field_ids))
+    }
+}
+
+/// Limits on the size of the documents, enforced when their fields are extracted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DocumentLimits {
+    /// The maximum number of facet values extracted from a field of a document,
+    /// the elements of an array are counted as one value each.
+    pub max_facet_values_per_field: Option<usize>,
+    /// The maximum number of fields of a document once flattened, the primary key included.
+    pub max_fields_per_document: Option<usize>,
+    /// What happens to the documents that exceed one of the limits.
+    pub behavior: LimitBehavior,
+}
+
+/// What happens to a document that exceeds one of the [`DocumentLimits`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitBehavior {
+    /// The values above the limit are not indexed, the document is still stored entirely
+    /// and an [`IndexingWarning`](crate::update::IndexingWarning) is returned.
+    Truncate,
+    /// The whole update fails with an error naming the document and the field.
+    Reject,
+}
+
+impl Default for LimitBehavior {
+    fn default() -> Self {
+        LimitBehavior::Reject
+    }
+}
@@ -0,0 +1,282 @@
+use std::io::{Seek, SeekFrom};
+
+use serde_json::Value;
+
+use crate::documents::{DocumentsBatchBuilder, DocumentsBatchReader};
+use crate::error::{InternalError, UserError};
+use crate::update::{
+    DocumentAdditionResult, IndexDocuments, IndexDocumentsConfig, IndexerConfig, Settings,
+};
+use crate::{Index, Result};
+
+/// Adds the documents of the source indexes to the destination index in a single documents
+/// addition, the documents of a source replace the ones of the destination with the same id.
+///
+/// The sources must have the same primary key and searchable attributes as the destination,
+/// the primary key of the first source is given to a destination that doesn't have one yet.
+/// When `prefix_external_ids` is given it is prepended to the ids of the documents of the
+/// sources to avoid collisions, the primary key must then be a top-level field of all the
+/// documents of the sources or the merge is rejected.
+///
+/// The documents are copied as they are stored, their field ids are remapped with the
+/// fields ids maps of the indexes and they are never deserialized into JSON objects.
+pub fn merge_indexes(
+    dest: &Index,
+    sources: &[&Index],
+    prefix_external_ids: Option<&str>,
+    indexer_config: &IndexerConfig,
+) -> Result<DocumentAdditionResult> {
+    let mut wtxn = dest.write_txn()?;
+
+    let mut primary_key = dest.primary_key(&wtxn)?.map(String::from);
+    let searchable_fields = dest
+        .user_defined_searchable_fields(&wtxn)?
+        .map(|fields| fields.into_iter().map(String::from).collect::<Vec<_>>());
+
+    // The conflicts are reported before any document is copied.
+    for source in sources {
+        let rtxn = source.read_txn()?;
+        match (primary_key.as_deref(), source.primary_key(&rtxn)?) {
+            (Some(expected), Some(found)) if expected != found => {
+                return Err(UserError::MergePrimaryKeyMismatch {
+                    expected: expected.to_string(),
+                    found: found.to_string(),
+                }
+                .into());
+            }
+            (None, Some(found)) => primary_key = Some(found.to_string()),
+            _ => (),
+        }
+
+        let source_searchable_fields = source.user_defined_searchable_fields(&rtxn)?;
+        let source_searchable_fields = source_searchable_fields
+            .map(|fields| fields.into_iter().map(String::from).collect::<Vec<_>>());
+        if source_searchable_fields != searchable_fields {
+            return Err(UserError::MergeSettingsMismatch { setting: "searchableAttributes" }.into());
+        }
+    }
+
+    // The documents are written on disk to avoid keeping them in memory.
+    let mut builder = DocumentsBatchBuilder::new(tempfile::tempfile()?);
+    let mut obkv_buffer = Vec::new();
+    for source in sources {
+        let rtxn = source.read_txn()?;
+        let fields_ids_map = source.fields_ids_map(&rtxn)?;
+        // The primary key of a source with documents is missing
+        // from its fields when it isn't a top-level field.
+        let invalid_primary_key = || UserError::MergeInvalidPrimaryKey {
+            primary_key: primary_key.clone().unwrap_or_default(),
+        };
+        let primary_key_id = match (prefix_external_ids, primary_key.as_deref()) {
+            (Some(_), Some(primary_key)) => fields_ids_map.id(primary_key),
+            _ => None,
+        };
+        if prefix_external_ids.is_some()
+            && primary_key_id.is_none()
+            && source.number_of_documents(&rtxn)? != 0
+        {
+            return Err(invalid_primary_key().into());
+        }
+
+        for result in source.all_documents(&rtxn)? {
+            let (_docid, document) = result?;
+            let obkv = document.as_obkv();
+            match (prefix_external_ids, primary_key_id) {
+                (Some(prefix), Some(primary_key_id)) => {
+                    if obkv.get(primary_key_id).is_none() {
+                        return Err(invalid_primary_key().into());
+                    }
+                    obkv_buffer.clear();
+                    let mut writer = obkv::KvWriter::new(&mut obkv_buffer);
+                    for (field_id, value) in obkv.iter() {
+                        if field_id == primary_key_id {
+                            let value: Value =
+                                serde_json::from_slice(value).map_err(InternalError::SerdeJson)?;
+                            let id = match value {
+                                Value::String(id) => id,
+                                value => value.to_string(),
+                            };
+                            let prefixed = Value::String(format!("{}{}", prefix, id));
+                            let prefixed =
+                                serde_json::to_vec(&prefixed).map_err(InternalError::SerdeJson)?;
+                            writer.insert(field_id, prefixed)?;
+                        } else {
+                            writer.insert(field_id, value)?;
+                        }
+                    }
+                    writer.into_inner()?;
+                    let obkv = obkv::KvReader::new(&obkv_buffer);
+                    builder.append_obkv(&obkv, &fields_ids_map)?;
+                }
+                _ => builder.append_obkv(&obkv, &fields_ids_map)?,
+            }
+        }
+    }
+    let mut file = builder.into_inner()?;
+    file.seek(SeekFrom::Start(0))?;
+    let documents = DocumentsBatchReader::from_reader(file)?;
+
+    if let Some(primary_key) = primary_key {
+        if dest.primary_key(&wtxn)?.is_none() {
+            let mut builder = Settings::new(&mut wtxn, dest, indexer_config);
+            builder.set_primary_key(primary_key);
+            builder.execute(|_| ())?;
+        }
+    }
+
+    let builder = IndexDocuments::new(
+        &mut wtxn,
+        dest,
+        indexer_config,
+        IndexDocumentsConfig::default(),
+        |_| (),
+        || false,
+    )?;
+    let (builder, user_error) = builder.add_documents(documents)?;
+    user_error?;
+    let result = builder.execute()?;
+
+    wtxn.commit()?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use big_s::S;
+
+    use super::*;
+    use crate::index::tests::TempIndex;
+    use crate::{obkv_to_json, Object, Search};
+
+    fn search(index: &Index, query: &str) -> Vec<Object> {
+        let rtxn = index.read_txn().unwrap();
+        let mut search = Search::new(&rtxn, index);
+        search.query(query);
+        let documents_ids = search.execute().unwrap().documents_ids;
+
+        let fields_ids_map = index.fields_ids_map(&rtxn).unwrap();
+        let fields: Vec<_> = fields_ids_map.ids().collect();
+        index
+            .documents(&rtxn, documents_ids)
+            .unwrap()
+            .iter()
+            .map(|(_, document)| {
+                obkv_to_json(&fields, &fields_ids_map, document.as_obkv()).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merged_index_matches_a_combined_ingestion() {
+        let first = TempIndex::new();
+        first
+            .add_documents(documents!([
+                { "id": 1, "title": "the quick brown fox" },
+                { "id": 2, "title": "the lazy dog", "year": 2001 },
+            ]))
+            .unwrap();
+        let second = TempIndex::new();
+        second
+            .add_documents(documents!([
+                { "year": 1999, "id": "a", "title": "a quick dog" },
+                { "id": 3, "tags": ["fox", "cat"], "title": "a brown cat" },
+            ]))
+            .unwrap();
+
+        let merged = TempIndex::new();
+        let result =
+            merge_indexes(&merged, &[&first, &second], None, &merged.indexer_config).unwrap();
+        assert_eq!(result.number_of_documents, 4);
+
+        let combined = TempIndex::new();
+        combined
+            .add_documents(documents!([
+                { "id": 1, "title": "the quick brown fox" },
+                { "id": 2, "title": "the lazy dog", "year": 2001 },
+                { "year": 1999, "id": "a", "title": "a quick dog" },
+                { "id": 3, "tags": ["fox", "cat"], "title": "a brown cat" },
+            ]))
+            .unwrap();
+
+        for query in ["quick", "brown", "dog", "fox", "cat", "the", ""] {
+            assert_eq!(search(&merged, query), search(&combined, query), "{}", query);
+        }
+        let rtxn = merged.read_txn().unwrap();
+        assert_eq!(merged.primary_key(&rtxn).unwrap(), Some("id"));
+    }
+
+    #[test]
+    fn prefixed_external_ids_avoid_collisions() {
+        let first = TempIndex::new();
+        first.add_documents(documents!([{ "id": 1, "title": "first" }])).unwrap();
+        let second = TempIndex::new();
+        second.add_documents(documents!([{ "id": 1, "title": "second" }])).unwrap();
+
+        let merged = TempIndex::new();
+        merged.add_documents(documents!([{ "id": 1, "title": "kept" }])).unwrap();
+        merge_indexes(&merged, &[&first, &second], Some("tenant-"), &merged.indexer_config)
+            .unwrap();
+
+        let rtxn = merged.read_txn().unwrap();
+        let external_ids = merged.external_documents_ids(&rtxn).unwrap();
+        assert_eq!(merged.number_of_documents(&rtxn).unwrap(), 2);
+        assert!(external_ids.get("1").is_some());
+        assert!(external_ids.get("tenant-1").is_some());
+        drop(rtxn);
+
+        // Without a prefix the documents of the sources replace the one of the destination.
+        merge_indexes(&merged, &[&first, &second], None, &merged.indexer_config).unwrap();
+        assert!(search(&merged, "kept").is_empty());
+        assert_eq!(search(&merged, "second").len(), 2);
+    }
+
+    #[test]
+    fn conflicting_indexes_are_rejected() {
+        let dest = TempIndex::new();
+        dest.update_settings(|settings| settings.set_primary_key(S("id"))).unwrap();
+
+        let other_key = TempIndex::new();
+        other_key.update_settings(|settings| settings.set_primary_key(S("uid"))).unwrap();
+        let error = merge_indexes(&dest, &[&other_key], None, &dest.indexer_config).unwrap_err();
+        assert!(matches!(
+            error,
+            crate::Error::UserError(UserError::MergePrimaryKeyMismatch { .. })
+        ));
+
+        let other_searchable = TempIndex::new();
+        other_searchable
+            .update_settings(|settings| settings.set_searchable_fields(vec![S("title")]))
+            .unwrap();
+        let error =
+            merge_indexes(&dest, &[&other_searchable], None, &dest.indexer_config).unwrap_err();
+        assert!(matches!(
+            error,
+            crate::Error::UserError(UserError::MergeSettingsMismatch {
+                setting: "searchableAttributes"
+            })
+        ));
+    }
+
+    #[test]
+    fn prefixed_external_ids_require_a_top_level_primary_key() {
+        let source = TempIndex::new();
+        source.update_settings(|settings| settings.set_primary_key(S("metadata.id"))).unwrap();
+        source.add_documents(documents!([{ "metadata": { "id": 1 }, "title": "nested" }])).unwrap();
+
+        let merged = TempIndex::new();
+        let error = merge_indexes(&merged, &[&source], Some("tenant-"), &merged.indexer_config)
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            crate::Error::UserError(UserError::MergeInvalidPrimaryKey { primary_key })
+                if primary_key == "metadata.id"
+        ));
+        let rtxn = merged.read_txn().unwrap();
+        assert_eq!(merged.number_of_documents(&rtxn).unwrap(), 0);
+        drop(rtxn);
+
+        // Without a prefix the documents are copied as they are.
+        merge_indexes(&merged, &[&source], None, &merged.indexer_config).unwrap();
+        assert_eq!(search(&merged, "nested").len(), 1);
+    }
+}
@@ -1,17 +1,23 @@
 pub use self::available_documents_ids::AvailableDocumentsIds;
-pub use self::clear_documents::ClearDocuments;
+pub use self::clear_documents::{ClearDocuments, ClearDocumentsResult};
 pub use self::delete_documents::{DeleteDocuments, DocumentDeletionResult};
-pub use self::facets::Facets;
+pub use self::facets::{Facets, DEFAULT_FACET_REBUILD_RATIO};
+#[cfg(feature = "debug-tools")]
+pub use self::index_documents::{reader_to_ndjson, KeyFormat};
 pub use self::index_documents::{
-    DocumentAdditionResult, DocumentId, IndexDocuments, IndexDocumentsConfig, IndexDocumentsMethod,
+    validate_documents_batch, AutogenerateIds, DocumentAdditionResult, DocumentId,
+    DocumentValidationError, IndexDocuments, IndexDocumentsConfig, IndexDocumentsMethod,
+    IndexingWarning, RejectedDocument, ValidationReport, DEFAULT_PREFIX_REBUILD_RATIO,
+    MAX_VALIDATION_ERRORS,
 };
-pub use self::indexer_config::IndexerConfig;
+pub use self::indexer_config::{DocumentLimits, IndexerConfig, LimitBehavior, DEFAULT_MAX_FIELDS};
+pub use self::merge_indexes::merge_indexes;
 pub use self::prefix_word_pairs::PrefixWordPairsProximityDocids;
 pub use self::settings::{Setting, Settings};
 pub use self::update_step::UpdateIndexingStep;
 pub use self::word_prefix_docids::WordPrefixDocids;
 pub use self::words_prefix_position_docids::WordPrefixPositionDocids;
-pub use self::words_prefixes_fst::WordsPrefixesFst;
+pub use self::words_prefixes_fst::{PrefixSettings, WordsPrefixesFst};
 
 mod available_documents_ids;
 mod clear_documents;
@@ -19,6 +25,7 @@ mod delete_documents;
 mod facets;
 mod index_documents;
 mod indexer_config;
+mod merge_indexes;
 mod prefix_word_pairs;
 mod settings;
 mod update_step;
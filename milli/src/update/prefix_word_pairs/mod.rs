@@ -5,7 +5,7 @@ use std::io::BufReader;
 use grenad::CompressionType;
 use heed::types::ByteSlice;
 
-use super::index_documents::{merge_cbo_roaring_bitmaps, CursorClonableMmap};
+use super::index_documents::{CursorClonableMmap, MergeStrategy};
 use crate::{Index, Result};
 
 mod prefix_word;
@@ -108,14 +108,11 @@ pub fn insert_into_database(
     let mut iter = database.prefix_iter_mut::<_, ByteSlice, ByteSlice>(wtxn, new_key)?;
     match iter.next().transpose()? {
         Some((key, old_val)) if new_key == key => {
-            let val =
-                merge_cbo_roaring_bitmaps(key, &[Cow::Borrowed(old_val), Cow::Borrowed(new_value)])
-                    .map_err(|_| {
-                        // TODO just wrap this error?
-                        crate::error::InternalError::IndexingMergingKeys {
-                            process: "get-put-merge",
-                        }
-                    })?;
+            let val = MergeStrategy::MERGE_CBO_ROARING_BITMAPS.merge(
+                "get-put-merge",
+                key,
+                &[Cow::Borrowed(old_val), Cow::Borrowed(new_value)],
+            )?;
             // safety: we use the new_key, not the one from the database iterator, to avoid undefined behaviour
             unsafe { iter.put_current(new_key, &val)? };
         }
@@ -1,4 +1,5 @@
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::result::Result as StdResult;
 
 use charabia::{Tokenizer, TokenizerBuilder};
@@ -6,14 +7,19 @@ use itertools::Itertools;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use time::OffsetDateTime;
 
-use super::index_documents::{IndexDocumentsConfig, Transform};
+use super::index_documents::{reindex_word_docids, IndexDocumentsConfig, Transform};
 use super::IndexerConfig;
 use crate::criterion::Criterion;
+use crate::documents_compression::{compress_documents_database, decompress_documents_database};
 use crate::error::UserError;
-use crate::index::{DEFAULT_MIN_WORD_LEN_ONE_TYPO, DEFAULT_MIN_WORD_LEN_TWO_TYPOS};
-use crate::update::index_documents::IndexDocumentsMethod;
-use crate::update::{ClearDocuments, IndexDocuments, UpdateIndexingStep};
-use crate::{FieldsIdsMap, Index, Result};
+use crate::index::{
+    DEFAULT_FACET_LEVEL_GROUP_SIZE, DEFAULT_FACET_MIN_LEVEL_SIZE, DEFAULT_MIN_WORD_LEN_ONE_TYPO,
+    DEFAULT_MIN_WORD_LEN_TWO_TYPOS, DEFAULT_SEARCHABLE_FIELD_WEIGHT,
+};
+use crate::position_bucketing::PositionBucketing;
+use crate::proximity::ProximityPrecision;
+use crate::update::{ClearDocuments, Facets, IndexDocuments, PrefixSettings, UpdateIndexingStep};
+use crate::{FieldsIdsMap, Index, OrderBy, Result};
 
 #[derive(Debug, Clone, PartialEq, Eq, Copy)]
 pub enum Setting<T> {
@@ -89,16 +95,36 @@ pub struct Settings<'a, 't, 'u, 'i> {
 
     indexer_config: &'a IndexerConfig,
 
-    searchable_fields: Setting<Vec<String>>,
+    /// The searchable fields along with the weights explicitly given to them.
+    searchable_fields: Setting<Vec<(String, Option<u16>)>>,
     displayed_fields: Setting<Vec<String>>,
     filterable_fields: Setting<HashSet<String>>,
+    /// Filterable attributes for which only the level 0 of the facet databases is built,
+    /// they can't be used in a facet distribution.
+    no_distribution_fields: Setting<HashSet<String>>,
+    /// Top-level attributes that are indexed but not stored in the documents database.
+    non_stored_fields: Setting<HashSet<String>>,
+    /// Attributes, or patterns, dropped from the documents before they are indexed and stored.
+    ignored_fields: Setting<HashSet<String>>,
+    /// The order in which the values of each facet are kept in the facet distribution.
+    sort_facet_values_by: Setting<HashMap<String, OrderBy>>,
     sortable_fields: Setting<HashSet<String>>,
     criteria: Setting<Vec<String>>,
     stop_words: Setting<BTreeSet<String>>,
+    /// The sequences of characters that the tokenizer keeps as single words.
+    dictionary: Setting<BTreeSet<String>>,
     distinct_field: Setting<String>,
     synonyms: Setting<HashMap<String, Vec<String>>>,
     primary_key: Setting<String>,
     authorize_typos: Setting<bool>,
+    /// Whether the query words can be split in two words at search time.
+    enable_word_splitting: Setting<bool>,
+    /// Whether the consecutive query words can be concatenated at search time.
+    enable_word_concatenation: Setting<bool>,
+    /// Whether the query words and phrases prefixed by a minus exclude documents.
+    enable_negative_terms: Setting<bool>,
+    /// Whether the diacritics are kept in the words, a difference of diacritics is then a typo.
+    count_diacritics: Setting<bool>,
     min_word_len_two_typos: Setting<u8>,
     min_word_len_one_typo: Setting<u8>,
     exact_words: Setting<BTreeSet<String>>,
@@ -106,6 +132,18 @@ pub struct Settings<'a, 't, 'u, 'i> {
     exact_attributes: Setting<HashSet<String>>,
     max_values_per_facet: Setting<usize>,
     pagination_max_total_hits: Setting<usize>,
+    documents_compression: Setting<bool>,
+    proximity_precision: Setting<ProximityPrecision>,
+    position_bucketing: Setting<PositionBucketing>,
+    /// Whether the numeric strings of the filterable fields are also indexed as numbers.
+    coerce_numeric_strings: Setting<bool>,
+    facet_level_group_size: Setting<usize>,
+    facet_max_group_size: Setting<usize>,
+    facet_min_level_size: Setting<usize>,
+    words_prefix_threshold: Setting<u32>,
+    min_prefix_length: Setting<usize>,
+    max_prefix_length: Setting<usize>,
+    max_prefixes: Setting<usize>,
 }
 
 impl<'a, 't, 'u, 'i> Settings<'a, 't, 'u, 'i> {
@@ -120,19 +158,39 @@ impl<'a, 't, 'u, 'i> Settings<'a, 't, 'u, 'i> {
             searchable_fields: Setting::NotSet,
             displayed_fields: Setting::NotSet,
             filterable_fields: Setting::NotSet,
+            no_distribution_fields: Setting::NotSet,
+            non_stored_fields: Setting::NotSet,
+            ignored_fields: Setting::NotSet,
+            sort_facet_values_by: Setting::NotSet,
             sortable_fields: Setting::NotSet,
             criteria: Setting::NotSet,
             stop_words: Setting::NotSet,
+            dictionary: Setting::NotSet,
             distinct_field: Setting::NotSet,
             synonyms: Setting::NotSet,
             primary_key: Setting::NotSet,
             authorize_typos: Setting::NotSet,
+            enable_word_splitting: Setting::NotSet,
+            enable_word_concatenation: Setting::NotSet,
+            enable_negative_terms: Setting::NotSet,
+            count_diacritics: Setting::NotSet,
             exact_words: Setting::NotSet,
             min_word_len_two_typos: Setting::NotSet,
             min_word_len_one_typo: Setting::NotSet,
             exact_attributes: Setting::NotSet,
             max_values_per_facet: Setting::NotSet,
             pagination_max_total_hits: Setting::NotSet,
+            documents_compression: Setting::NotSet,
+            proximity_precision: Setting::NotSet,
+            position_bucketing: Setting::NotSet,
+            coerce_numeric_strings: Setting::NotSet,
+            facet_level_group_size: Setting::NotSet,
+            facet_max_group_size: Setting::NotSet,
+            facet_min_level_size: Setting::NotSet,
+            words_prefix_threshold: Setting::NotSet,
+            min_prefix_length: Setting::NotSet,
+            max_prefix_length: Setting::NotSet,
+            max_prefixes: Setting::NotSet,
             indexer_config,
         }
     }
@@ -142,7 +200,18 @@ impl<'a, 't, 'u, 'i> Settings<'a, 't, 'u, 'i> {
     }
 
     pub fn set_searchable_fields(&mut self, names: Vec<String>) {
-        self.searchable_fields = Setting::Set(names);
+        self.searchable_fields = Setting::Set(names.into_iter().map(|name| (name, None)).collect());
+    }
+
+    /// Sets the searchable fields along with their weights, the attribute criterion ranks the
+    /// fields by decreasing weight and the fields with the same weight in the given order.
+    /// The fields without a weight have a weight of [`DEFAULT_SEARCHABLE_FIELD_WEIGHT`].
+    ///
+    /// The weights are ordinal, only their order matters: weights of 1 and 100 rank
+    /// the fields exactly like weights of 1 and 2, a match in a field with a higher weight
+    /// always ranks before a match in a field with a lower weight.
+    pub fn set_searchable_fields_with_weights(&mut self, fields: Vec<(String, Option<u16>)>) {
+        self.searchable_fields = Setting::Set(fields);
     }
 
     pub fn reset_displayed_fields(&mut self) {
@@ -161,6 +230,49 @@ impl<'a, 't, 'u, 'i> Settings<'a, 't, 'u, 'i> {
         self.filterable_fields = Setting::Set(names);
     }
 
+    pub fn reset_no_distribution_fields(&mut self) {
+        self.no_distribution_fields = Setting::Reset;
+    }
+
+    pub fn set_no_distribution_fields(&mut self, names: HashSet<String>) {
+        self.no_distribution_fields = Setting::Set(names);
+    }
+
+    /// Stores all the attributes of the documents added from now on, the documents
+    /// added before keep missing their non-stored attributes.
+    pub fn reset_non_stored_fields(&mut self) {
+        self.non_stored_fields = Setting::Reset;
+    }
+
+    /// Sets the top-level attributes that are indexed but removed from the documents
+    /// written in the database, the documents added before keep their stored attributes.
+    ///
+    /// The documents with non-stored attributes can only be partially updated when the
+    /// update sends all of them again, and the settings that need to reindex the documents
+    /// can't be changed anymore as the values of those attributes are lost.
+    pub fn set_non_stored_fields(&mut self, names: HashSet<String>) {
+        self.non_stored_fields = Setting::Set(names);
+    }
+
+    pub fn reset_ignored_fields(&mut self) {
+        self.ignored_fields = Setting::Reset;
+    }
+
+    /// Sets the attributes dropped from the documents added from now on, they are neither
+    /// indexed nor stored. The names can be nested fields or patterns, e.g. `metadata.*.raw`,
+    /// and the nested fields of a matched field are dropped too. The primary key is never dropped.
+    pub fn set_ignored_fields(&mut self, names: HashSet<String>) {
+        self.ignored_fields = Setting::Set(names);
+    }
+
+    pub fn reset_sort_facet_values_by(&mut self) {
+        self.sort_facet_values_by = Setting::Reset;
+    }
+
+    pub fn set_sort_facet_values_by(&mut self, orders: HashMap<String, OrderBy>) {
+        self.sort_facet_values_by = Setting::Set(orders);
+    }
+
     pub fn set_sortable_fields(&mut self, names: HashSet<String>) {
         self.sortable_fields = Setting::Set(names);
     }
@@ -186,6 +298,15 @@ impl<'a, 't, 'u, 'i> Settings<'a, 't, 'u, 'i> {
             if stop_words.is_empty() { Setting::Reset } else { Setting::Set(stop_words) }
     }
 
+    pub fn reset_dictionary(&mut self) {
+        self.dictionary = Setting::Reset;
+    }
+
+    pub fn set_dictionary(&mut self, dictionary: BTreeSet<String>) {
+        self.dictionary =
+            if dictionary.is_empty() { Setting::Reset } else { Setting::Set(dictionary) }
+    }
+
     pub fn reset_distinct_field(&mut self) {
         self.distinct_field = Setting::Reset;
     }
@@ -218,6 +339,46 @@ impl<'a, 't, 'u, 'i> Settings<'a, 't, 'u, 'i> {
         self.authorize_typos = Setting::Reset;
     }
 
+    pub fn set_enable_word_splitting(&mut self, value: bool) {
+        self.enable_word_splitting = Setting::Set(value);
+    }
+
+    pub fn reset_enable_word_splitting(&mut self) {
+        self.enable_word_splitting = Setting::Reset;
+    }
+
+    pub fn set_enable_word_concatenation(&mut self, value: bool) {
+        self.enable_word_concatenation = Setting::Set(value);
+    }
+
+    pub fn reset_enable_word_concatenation(&mut self) {
+        self.enable_word_concatenation = Setting::Reset;
+    }
+
+    pub fn set_enable_negative_terms(&mut self, value: bool) {
+        self.enable_negative_terms = Setting::Set(value);
+    }
+
+    pub fn reset_enable_negative_terms(&mut self) {
+        self.enable_negative_terms = Setting::Reset;
+    }
+
+    pub fn set_count_diacritics(&mut self, value: bool) {
+        self.count_diacritics = Setting::Set(value);
+    }
+
+    pub fn reset_count_diacritics(&mut self) {
+        self.count_diacritics = Setting::Reset;
+    }
+
+    pub fn set_coerce_numeric_strings(&mut self, value: bool) {
+        self.coerce_numeric_strings = Setting::Set(value);
+    }
+
+    pub fn reset_coerce_numeric_strings(&mut self) {
+        self.coerce_numeric_strings = Setting::Reset;
+    }
+
     pub fn set_min_word_len_two_typos(&mut self, val: u8) {
         self.min_word_len_two_typos = Setting::Set(val);
     }
@@ -266,6 +427,100 @@ impl<'a, 't, 'u, 'i> Settings<'a, 't, 'u, 'i> {
         self.pagination_max_total_hits = Setting::Reset;
     }
 
+    pub fn set_documents_compression(&mut self, value: bool) {
+        self.documents_compression = Setting::Set(value);
+    }
+
+    pub fn reset_documents_compression(&mut self) {
+        self.documents_compression = Setting::Reset;
+    }
+
+    pub fn set_proximity_precision(&mut self, value: ProximityPrecision) {
+        self.proximity_precision = Setting::Set(value);
+    }
+
+    pub fn reset_proximity_precision(&mut self) {
+        self.proximity_precision = Setting::Reset;
+    }
+
+    pub fn set_position_bucketing(&mut self, value: PositionBucketing) {
+        self.position_bucketing = Setting::Set(value);
+    }
+
+    pub fn reset_position_bucketing(&mut self) {
+        self.position_bucketing = Setting::Reset;
+    }
+
+    /// Sets the number of elements of a facet level grouped into a single element of the
+    /// level above, it must be greater than or equal to 2.
+    pub fn set_facet_level_group_size(&mut self, value: usize) {
+        self.facet_level_group_size = Setting::Set(value);
+    }
+
+    pub fn reset_facet_level_group_size(&mut self) {
+        self.facet_level_group_size = Setting::Reset;
+    }
+
+    /// Sets the number of elements above which a facet group is split when the facet levels
+    /// are updated in place, it must be greater than or equal to the group size.
+    ///
+    /// It is twice the group size when it is not set.
+    pub fn set_facet_max_group_size(&mut self, value: usize) {
+        self.facet_max_group_size = Setting::Set(value);
+    }
+
+    pub fn reset_facet_max_group_size(&mut self) {
+        self.facet_max_group_size = Setting::Reset;
+    }
+
+    /// Sets the minimum number of elements of a facet level, it must be greater than 0.
+    pub fn set_facet_min_level_size(&mut self, value: usize) {
+        self.facet_min_level_size = Setting::Set(value);
+    }
+
+    pub fn reset_facet_min_level_size(&mut self) {
+        self.facet_min_level_size = Setting::Reset;
+    }
+
+    /// Sets the number of words of the dictionary a prefix must match to be materialized
+    /// in the prefix databases, it is raised to 50 when the prefixes are computed.
+    pub fn set_words_prefix_threshold(&mut self, value: u32) {
+        self.words_prefix_threshold = Setting::Set(value);
+    }
+
+    pub fn reset_words_prefix_threshold(&mut self) {
+        self.words_prefix_threshold = Setting::Reset;
+    }
+
+    /// Sets the minimum length in bytes of the materialized prefixes, it must be greater than 0.
+    pub fn set_min_prefix_length(&mut self, value: usize) {
+        self.min_prefix_length = Setting::Set(value);
+    }
+
+    pub fn reset_min_prefix_length(&mut self) {
+        self.min_prefix_length = Setting::Reset;
+    }
+
+    /// Sets the maximum length in bytes of the materialized prefixes,
+    /// it must be greater than or equal to the minimum length.
+    pub fn set_max_prefix_length(&mut self, value: usize) {
+        self.max_prefix_length = Setting::Set(value);
+    }
+
+    pub fn reset_max_prefix_length(&mut self) {
+        self.max_prefix_length = Setting::Reset;
+    }
+
+    /// Sets the maximum number of materialized prefixes, the ones matching the most words
+    /// are kept. The searches for the other prefixes go through the words dictionary.
+    pub fn set_max_prefixes(&mut self, value: usize) {
+        self.max_prefixes = Setting::Set(value);
+    }
+
+    pub fn reset_max_prefixes(&mut self) {
+        self.max_prefixes = Setting::Reset;
+    }
+
     fn reindex<F>(&mut self, cb: &F, old_fields_ids_map: FieldsIdsMap) -> Result<()>
     where
         F: Fn(UpdateIndexingStep) + Sync,
@@ -277,13 +532,9 @@ impl<'a, 't, 'u, 'i> Settings<'a, 't, 'u, 'i> {
             return Ok(());
         }
 
-        let transform = Transform::new(
-            self.wtxn,
-            self.index,
-            self.indexer_config,
-            IndexDocumentsMethod::ReplaceDocuments,
-            false,
-        )?;
+        let max_fields = self.indexer_config.max_fields();
+        let transform =
+            Transform::new(self.wtxn, self.index, self.indexer_config, false, false, max_fields)?;
 
         // We remap the documents fields based on the new `FieldsIdsMap`.
         let output =
@@ -303,6 +554,7 @@ impl<'a, 't, 'u, 'i> Settings<'a, 't, 'u, 'i> {
             self.indexer_config,
             IndexDocumentsConfig::default(),
             &cb,
+            || false,
         )?;
         indexing_builder.execute_raw(output)?;
 
@@ -348,12 +600,35 @@ impl<'a, 't, 'u, 'i> Settings<'a, 't, 'u, 'i> {
 
                 let mut new_fields_ids_map = FieldsIdsMap::new();
                 // fields are deduplicated, only the first occurrence is taken into account
-                let names = fields.iter().unique().map(String::as_str).collect::<Vec<_>>();
+                let fields: Vec<_> = fields.iter().unique_by(|(name, _)| name.clone()).collect();
+                let names = fields.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+                let weights: BTreeMap<_, _> = fields
+                    .iter()
+                    .filter_map(|(name, weight)| weight.map(|weight| (name.clone(), weight)))
+                    .collect();
+
+                // The attribute criterion ranks the fields by their ids, the searchable
+                // fields are given their ids by decreasing weight, in the given order.
+                let mut ranked_names = names.clone();
+                ranked_names.sort_by_key(|name| {
+                    Reverse(weights.get(*name).copied().unwrap_or(DEFAULT_SEARCHABLE_FIELD_WEIGHT))
+                });
 
                 // Add all the searchable attributes to the field map, and then add the
-                // remaining fields from the old field map to the new one
-                for name in names.iter() {
-                    new_fields_ids_map.insert(name).ok_or(UserError::AttributeLimitReached)?;
+                // remaining fields from the old field map to the new one. The patterns
+                // are not fields, the known fields they match are added in their place.
+                for name in ranked_names.iter() {
+                    if crate::is_field_pattern(name) {
+                        for (_, field) in old_fields_ids_map.iter() {
+                            if crate::is_faceted_by(field, name) {
+                                new_fields_ids_map
+                                    .insert(field)
+                                    .ok_or(UserError::AttributeLimitReached)?;
+                            }
+                        }
+                    } else {
+                        new_fields_ids_map.insert(name).ok_or(UserError::AttributeLimitReached)?;
+                    }
                 }
 
                 for (_, name) in old_fields_ids_map.iter() {
@@ -366,6 +641,11 @@ impl<'a, 't, 'u, 'i> Settings<'a, 't, 'u, 'i> {
                     &new_fields_ids_map,
                 )?;
                 self.index.put_fields_ids_map(self.wtxn, &new_fields_ids_map)?;
+                if weights.is_empty() {
+                    self.index.delete_searchable_fields_weights(self.wtxn)?;
+                } else {
+                    self.index.put_searchable_fields_weights(self.wtxn, &weights)?;
+                }
             }
             Setting::Reset => {
                 self.index.delete_all_searchable_fields(self.wtxn)?;
@@ -399,6 +679,21 @@ impl<'a, 't, 'u, 'i> Settings<'a, 't, 'u, 'i> {
         }
     }
 
+    fn update_dictionary(&mut self) -> Result<bool> {
+        match self.dictionary {
+            Setting::Set(ref dictionary) => {
+                if self.index.dictionary(self.wtxn)? != *dictionary {
+                    self.index.put_dictionary(self.wtxn, dictionary)?;
+                    Ok(true)
+                } else {
+                    Ok(false)
+                }
+            }
+            Setting::Reset => Ok(self.index.delete_dictionary(self.wtxn)?),
+            Setting::NotSet => Ok(false),
+        }
+    }
+
     fn update_synonyms(&mut self) -> Result<bool> {
         match self.synonyms {
             Setting::Set(ref synonyms) => {
@@ -456,15 +751,17 @@ impl<'a, 't, 'u, 'i> Settings<'a, 't, 'u, 'i> {
     }
 
     fn update_exact_attributes(&mut self) -> Result<bool> {
+        let old_attrs: HashSet<String> =
+            self.index.exact_attributes(self.wtxn)?.into_iter().map(String::from).collect();
         match self.exact_attributes {
             Setting::Set(ref attrs) => {
-                let attrs = attrs.iter().map(String::as_str).collect::<Vec<_>>();
-                self.index.put_exact_attributes(self.wtxn, &attrs)?;
-                Ok(true)
+                let new_attrs = attrs.iter().map(String::as_str).collect::<Vec<_>>();
+                self.index.put_exact_attributes(self.wtxn, &new_attrs)?;
+                Ok(attrs != &old_attrs)
             }
             Setting::Reset => {
                 self.index.delete_exact_attributes(self.wtxn)?;
-                Ok(true)
+                Ok(!old_attrs.is_empty())
             }
             Setting::NotSet => Ok(false),
         }
@@ -487,6 +784,60 @@ impl<'a, 't, 'u, 'i> Settings<'a, 't, 'u, 'i> {
         Ok(())
     }
 
+    fn update_no_distribution_fields(&mut self) -> Result<bool> {
+        let old_fields = self.index.no_distribution_fields(self.wtxn)?;
+        match self.no_distribution_fields {
+            Setting::Set(ref fields) => {
+                self.index.put_no_distribution_fields(self.wtxn, fields)?;
+                Ok(fields != &old_fields)
+            }
+            Setting::Reset => {
+                self.index.delete_no_distribution_fields(self.wtxn)?;
+                Ok(!old_fields.is_empty())
+            }
+            Setting::NotSet => Ok(false),
+        }
+    }
+
+    fn update_non_stored_fields(&mut self) -> Result<()> {
+        match self.non_stored_fields {
+            Setting::Set(ref fields) => {
+                self.index.put_non_stored_fields(self.wtxn, fields)?;
+            }
+            Setting::Reset => {
+                self.index.delete_non_stored_fields(self.wtxn)?;
+            }
+            Setting::NotSet => (),
+        }
+        Ok(())
+    }
+
+    fn update_ignored_fields(&mut self) -> Result<()> {
+        match self.ignored_fields {
+            Setting::Set(ref fields) => {
+                self.index.put_ignored_fields(self.wtxn, fields)?;
+            }
+            Setting::Reset => {
+                self.index.delete_ignored_fields(self.wtxn)?;
+            }
+            Setting::NotSet => (),
+        }
+        Ok(())
+    }
+
+    fn update_sort_facet_values_by(&mut self) -> Result<()> {
+        match self.sort_facet_values_by {
+            Setting::Set(ref orders) => {
+                self.index.put_sort_facet_values_by(self.wtxn, orders)?;
+            }
+            Setting::Reset => {
+                self.index.delete_sort_facet_values_by(self.wtxn)?;
+            }
+            Setting::NotSet => (),
+        }
+        Ok(())
+    }
+
     fn update_sortable(&mut self) -> Result<()> {
         match self.sortable_fields {
             Setting::Set(ref fields) => {
@@ -563,6 +914,48 @@ impl<'a, 't, 'u, 'i> Settings<'a, 't, 'u, 'i> {
         }
     }
 
+    fn update_enable_word_splitting(&mut self) -> Result<()> {
+        match self.enable_word_splitting {
+            Setting::Set(flag) => {
+                self.index.put_enable_word_splitting(self.wtxn, flag)?;
+            }
+            Setting::Reset => {
+                self.index.delete_enable_word_splitting(self.wtxn)?;
+            }
+            Setting::NotSet => (),
+        }
+
+        Ok(())
+    }
+
+    fn update_enable_word_concatenation(&mut self) -> Result<()> {
+        match self.enable_word_concatenation {
+            Setting::Set(flag) => {
+                self.index.put_enable_word_concatenation(self.wtxn, flag)?;
+            }
+            Setting::Reset => {
+                self.index.delete_enable_word_concatenation(self.wtxn)?;
+            }
+            Setting::NotSet => (),
+        }
+
+        Ok(())
+    }
+
+    fn update_enable_negative_terms(&mut self) -> Result<()> {
+        match self.enable_negative_terms {
+            Setting::Set(flag) => {
+                self.index.put_enable_negative_terms(self.wtxn, flag)?;
+            }
+            Setting::Reset => {
+                self.index.delete_enable_negative_terms(self.wtxn)?;
+            }
+            Setting::NotSet => (),
+        }
+
+        Ok(())
+    }
+
     fn update_min_typo_word_len(&mut self) -> Result<()> {
         let one = self.min_word_len_one_typo.or_reset(DEFAULT_MIN_WORD_LEN_ONE_TYPO);
         let two = self.min_word_len_two_typos.or_reset(DEFAULT_MIN_WORD_LEN_TWO_TYPOS);
@@ -657,6 +1050,204 @@ impl<'a, 't, 'u, 'i> Settings<'a, 't, 'u, 'i> {
         Ok(())
     }
 
+    fn update_documents_compression(&mut self) -> Result<()> {
+        let compression = match self.documents_compression {
+            Setting::Set(compression) => {
+                self.index.put_documents_compression(self.wtxn, compression)?;
+                compression
+            }
+            Setting::Reset => {
+                self.index.delete_documents_compression(self.wtxn)?;
+                false
+            }
+            Setting::NotSet => return Ok(()),
+        };
+
+        // The documents already stored are rewritten to keep the whole
+        // documents database either compressed or uncompressed.
+        if compression {
+            compress_documents_database(self.wtxn, self.index)
+        } else {
+            decompress_documents_database(self.wtxn, self.index)
+        }
+    }
+
+    fn update_proximity_precision(&mut self) -> Result<bool> {
+        let old_precision = self.index.proximity_precision(self.wtxn)?;
+        match self.proximity_precision {
+            Setting::Set(precision) => {
+                self.index.put_proximity_precision(self.wtxn, precision)?;
+            }
+            Setting::Reset => {
+                self.index.delete_proximity_precision(self.wtxn)?;
+            }
+            Setting::NotSet => return Ok(false),
+        }
+
+        Ok(self.index.proximity_precision(self.wtxn)? != old_precision)
+    }
+
+    fn update_position_bucketing(&mut self) -> Result<bool> {
+        let old_bucketing = self.index.position_bucketing(self.wtxn)?;
+        match self.position_bucketing {
+            Setting::Set(bucketing) => {
+                self.index.put_position_bucketing(self.wtxn, bucketing)?;
+            }
+            Setting::Reset => {
+                self.index.delete_position_bucketing(self.wtxn)?;
+            }
+            Setting::NotSet => return Ok(false),
+        }
+
+        Ok(self.index.position_bucketing(self.wtxn)? != old_bucketing)
+    }
+
+    /// Returns `true` if the parameters of the facet levels changed.
+    fn update_coerce_numeric_strings(&mut self) -> Result<bool> {
+        let old_flag = self.index.coerce_numeric_strings(self.wtxn)?;
+        match self.coerce_numeric_strings {
+            Setting::Set(flag) => {
+                self.index.put_coerce_numeric_strings(self.wtxn, flag)?;
+            }
+            Setting::Reset => {
+                self.index.delete_coerce_numeric_strings(self.wtxn)?;
+            }
+            Setting::NotSet => return Ok(false),
+        }
+
+        Ok(old_flag != self.index.coerce_numeric_strings(self.wtxn)?)
+    }
+
+    /// Returns `true` if the words must be extracted again with or without their diacritics.
+    fn update_count_diacritics(&mut self) -> Result<bool> {
+        let old_flag = self.index.count_diacritics(self.wtxn)?;
+        match self.count_diacritics {
+            Setting::Set(flag) => {
+                self.index.put_count_diacritics(self.wtxn, flag)?;
+            }
+            Setting::Reset => {
+                self.index.delete_count_diacritics(self.wtxn)?;
+            }
+            Setting::NotSet => return Ok(false),
+        }
+
+        Ok(old_flag != self.index.count_diacritics(self.wtxn)?)
+    }
+
+    fn update_facet_levels(&mut self) -> Result<bool> {
+        fn resolve(setting: Setting<usize>, current: usize, default: usize) -> usize {
+            match setting {
+                Setting::Set(value) => value,
+                Setting::Reset => default,
+                Setting::NotSet => current,
+            }
+        }
+
+        let old_group_size = self.index.facet_level_group_size(self.wtxn)?;
+        let old_max_group_size = self.index.facet_max_group_size(self.wtxn)?;
+        let old_min_level_size = self.index.facet_min_level_size(self.wtxn)?;
+
+        let group_size =
+            resolve(self.facet_level_group_size, old_group_size, DEFAULT_FACET_LEVEL_GROUP_SIZE);
+        // The maximum group size follows the group size when it is not set.
+        let max_group_size = match self.facet_max_group_size {
+            Setting::Set(value) => value,
+            Setting::Reset => 2 * group_size,
+            Setting::NotSet => match self.index.custom_facet_max_group_size(self.wtxn)? {
+                Some(value) => value,
+                None => 2 * group_size,
+            },
+        };
+        let min_level_size =
+            resolve(self.facet_min_level_size, old_min_level_size, DEFAULT_FACET_MIN_LEVEL_SIZE);
+
+        if group_size < 2 || max_group_size < group_size || min_level_size == 0 {
+            return Err(UserError::InvalidFacetLevelsSetting {
+                group_size,
+                max_group_size,
+                min_level_size,
+            }
+            .into());
+        }
+
+        match self.facet_level_group_size {
+            Setting::Set(value) => self.index.put_facet_level_group_size(self.wtxn, value)?,
+            Setting::Reset => {
+                self.index.delete_facet_level_group_size(self.wtxn)?;
+            }
+            Setting::NotSet => (),
+        }
+        match self.facet_max_group_size {
+            Setting::Set(value) => self.index.put_facet_max_group_size(self.wtxn, value)?,
+            Setting::Reset => {
+                self.index.delete_facet_max_group_size(self.wtxn)?;
+            }
+            Setting::NotSet => (),
+        }
+        match self.facet_min_level_size {
+            Setting::Set(value) => self.index.put_facet_min_level_size(self.wtxn, value)?,
+            Setting::Reset => {
+                self.index.delete_facet_min_level_size(self.wtxn)?;
+            }
+            Setting::NotSet => (),
+        }
+
+        Ok(group_size != old_group_size
+            || max_group_size != old_max_group_size
+            || min_level_size != old_min_level_size)
+    }
+
+    /// Stores the prefix settings, the prefix databases are rebuilt
+    /// with them by the next documents addition.
+    fn update_prefix_settings(&mut self) -> Result<()> {
+        fn resolve<T: Copy>(setting: Setting<T>, current: T, default: T) -> T {
+            match setting {
+                Setting::Set(value) => value,
+                Setting::Reset => default,
+                Setting::NotSet => current,
+            }
+        }
+
+        let current = self.index.prefix_settings(self.wtxn)?;
+        let default = PrefixSettings::default();
+        let settings = PrefixSettings {
+            threshold: resolve(self.words_prefix_threshold, current.threshold, default.threshold),
+            min_prefix_length: resolve(
+                self.min_prefix_length,
+                current.min_prefix_length,
+                default.min_prefix_length,
+            ),
+            max_prefix_length: resolve(
+                self.max_prefix_length,
+                current.max_prefix_length,
+                default.max_prefix_length,
+            ),
+            max_prefixes: match self.max_prefixes {
+                Setting::Set(value) => Some(value),
+                Setting::Reset => default.max_prefixes,
+                Setting::NotSet => current.max_prefixes,
+            },
+        };
+
+        if settings.min_prefix_length == 0
+            || settings.max_prefix_length < settings.min_prefix_length
+        {
+            return Err(UserError::InvalidPrefixSettings {
+                min_prefix_length: settings.min_prefix_length,
+                max_prefix_length: settings.max_prefix_length,
+            }
+            .into());
+        }
+
+        if settings == default {
+            self.index.delete_prefix_settings(self.wtxn)?;
+        } else if settings != current {
+            self.index.put_prefix_settings(self.wtxn, &settings)?;
+        }
+
+        Ok(())
+    }
+
     pub fn execute<F>(mut self, progress_callback: F) -> Result<()>
     where
         F: Fn(UpdateIndexingStep) + Sync,
@@ -665,18 +1256,31 @@ impl<'a, 't, 'u, 'i> Settings<'a, 't, 'u, 'i> {
 
         let old_faceted_fields = self.index.user_defined_faceted_fields(self.wtxn)?;
         let old_fields_ids_map = self.index.fields_ids_map(self.wtxn)?;
+        let old_non_stored_fields = self.index.non_stored_fields(self.wtxn)?;
 
         self.update_displayed()?;
         self.update_filterable()?;
+        let no_distribution_updated = self.update_no_distribution_fields()?;
+        self.update_non_stored_fields()?;
+        self.update_ignored_fields()?;
+        self.update_sort_facet_values_by()?;
         self.update_sortable()?;
         self.update_distinct_field()?;
         self.update_criteria()?;
         self.update_primary_key()?;
         self.update_authorize_typos()?;
+        self.update_enable_word_splitting()?;
+        self.update_enable_word_concatenation()?;
+        self.update_enable_negative_terms()?;
         self.update_min_typo_word_len()?;
         self.update_exact_words()?;
         self.update_max_values_per_facet()?;
         self.update_pagination_max_total_hits()?;
+        self.update_documents_compression()?;
+        let facet_levels_updated = self.update_facet_levels()?;
+        self.update_prefix_settings()?;
+        let coerce_numeric_strings_updated = self.update_coerce_numeric_strings()?;
+        let count_diacritics_updated = self.update_count_diacritics()?;
 
         // If there is new faceted fields we indicate that we must reindex as we must
         // index new fields as facets. It means that the distinct attribute,
@@ -685,19 +1289,63 @@ impl<'a, 't, 'u, 'i> Settings<'a, 't, 'u, 'i> {
         let faceted_updated = old_faceted_fields != new_faceted_fields;
 
         let stop_words_updated = self.update_stop_words()?;
+        let dictionary_updated = self.update_dictionary()?;
         let synonyms_updated = self.update_synonyms()?;
         let searchable_updated = self.update_searchable()?;
         let exact_attributes_updated = self.update_exact_attributes()?;
-
-        if stop_words_updated
+        let proximity_precision_updated = self.update_proximity_precision()?;
+        let proximity_precision = self.index.proximity_precision(self.wtxn)?;
+        let position_bucketing_updated = self.update_position_bucketing()?;
+
+        // The documents are reindexed to build the word pair proximity
+        // databases again when the proximity precision goes back to by-word,
+        // to index the numeric strings again when their coercion changes,
+        // to store the positions again when their bucketing changes and to
+        // extract the words with or without their diacritics.
+        let must_reindex = stop_words_updated
+            || position_bucketing_updated
+            || dictionary_updated
             || faceted_updated
+            || coerce_numeric_strings_updated
+            || count_diacritics_updated
             || synonyms_updated
             || searchable_updated
-            || exact_attributes_updated
-        {
+            || (proximity_precision_updated && proximity_precision == ProximityPrecision::ByWord);
+
+        if must_reindex {
+            // The documents are reindexed from their stored version,
+            // which lacks the values of the non-stored fields.
+            if !old_non_stored_fields.is_empty() && self.index.number_of_documents(self.wtxn)? != 0
+            {
+                let attributes = old_non_stored_fields.into_iter().collect();
+                return Err(UserError::ReindexWithNonStoredAttributes { attributes }.into());
+            }
             self.reindex(&progress_callback, old_fields_ids_map)?;
+        } else if exact_attributes_updated {
+            // The exact attributes only decide in which of the word docids
+            // databases the words are stored, the other databases are left untouched.
+            reindex_word_docids(self.wtxn, self.index, self.indexer_config)?;
+        }
+
+        if proximity_precision_updated && proximity_precision == ProximityPrecision::ByAttribute {
+            // The word pair proximity databases are not used anymore.
+            self.index.word_pair_proximity_docids.clear(self.wtxn)?;
+            self.index.word_prefix_pair_proximity_docids.clear(self.wtxn)?;
+            self.index.prefix_word_pair_proximity_docids.clear(self.wtxn)?;
+        }
+
+        if (no_distribution_updated || facet_levels_updated) && !must_reindex {
+            // The facet levels of the fields are either dropped or built again
+            // from their level 0, which is left untouched.
+            let mut builder = Facets::new(self.wtxn, self.index);
+            builder.chunk_compression_type = self.indexer_config.chunk_compression_type;
+            builder.chunk_compression_level = self.indexer_config.chunk_compression_level;
+            builder.validate_ordering = self.indexer_config.validate_ordering;
+            builder.execute(&progress_callback, || false)?;
         }
 
+        self.index.end_update(self.wtxn)?;
+
         Ok(())
     }
 }
@@ -706,7 +1354,7 @@ impl<'a, 't, 'u, 'i> Settings<'a, 't, 'u, 'i> {
 mod tests {
     use big_s::S;
     use heed::types::ByteSlice;
-    use maplit::{btreeset, hashmap, hashset};
+    use maplit::{btreemap, btreeset, hashmap, hashset};
 
     use super::*;
     use crate::error::Error;
@@ -752,7 +1400,7 @@ mod tests {
         let result = index.search(&rtxn).query(r#""kevin""#).execute().unwrap();
         let documents = index.documents(&rtxn, result.documents_ids).unwrap();
         assert_eq!(documents.len(), 1);
-        assert_eq!(documents[0].1.get(0), Some(&br#""kevin""#[..]));
+        assert_eq!(documents[0].1.as_obkv().get(0), Some(&br#""kevin""#[..]));
         drop(rtxn);
 
         // We change the searchable fields to be the "name" field only.
@@ -769,7 +1417,7 @@ mod tests {
         let result = index.search(&rtxn).query("23").execute().unwrap();
         assert_eq!(result.documents_ids.len(), 1);
         let documents = index.documents(&rtxn, result.documents_ids).unwrap();
-        assert_eq!(documents[0].1.get(0), Some(&br#""kevin""#[..]));
+        assert_eq!(documents[0].1.as_obkv().get(0), Some(&br#""kevin""#[..]));
     }
 
     #[test]
@@ -911,8 +1559,12 @@ mod tests {
         let fidmap = index.fields_ids_map(&rtxn).unwrap();
         for document in index.all_documents(&rtxn).unwrap() {
             let document = document.unwrap();
-            let json = crate::obkv_to_json(&fidmap.ids().collect::<Vec<_>>(), &fidmap, document.1)
-                .unwrap();
+            let json = crate::obkv_to_json(
+                &fidmap.ids().collect::<Vec<_>>(),
+                &fidmap,
+                document.1.as_obkv(),
+            )
+            .unwrap();
             println!("json: {:?}", json);
         }
         let count = index
@@ -974,8 +1626,8 @@ mod tests {
 
         // Fetch the documents "age" field in the ordre in which the documents appear.
         let age_field_id = index.fields_ids_map(&rtxn).unwrap().id("age").unwrap();
-        let iter = documents.into_iter().map(|(_, doc)| {
-            let bytes = doc.get(age_field_id).unwrap();
+        let iter = documents.iter().map(|(_, doc)| {
+            let bytes = doc.as_obkv().get(age_field_id).unwrap();
             let string = std::str::from_utf8(bytes).unwrap();
             string.parse::<u32>().unwrap()
         });
@@ -1359,7 +2011,7 @@ mod tests {
         let (_, content) = documents.iter().find(|(id, _)| *id == first_id).unwrap();
 
         let fid = index.fields_ids_map(&rtxn).unwrap().id("title").unwrap();
-        let line = std::str::from_utf8(content.get(fid).unwrap()).unwrap();
+        let line = std::str::from_utf8(content.as_obkv().get(fid).unwrap()).unwrap();
         assert_eq!(line, r#""Star Wars""#);
     }
 
@@ -1439,6 +2091,184 @@ mod tests {
         }
     }
 
+    #[test]
+    fn update_documents_compression() {
+        let index = TempIndex::new();
+
+        index
+            .add_documents(documents!([
+                { "id": 0, "name": "kevin" },
+                { "id": 1, "name": "kevina" },
+                { "id": 2, "name": "benoit" }
+            ]))
+            .unwrap();
+
+        // Enabling the compression compresses the documents already in the index.
+        index.update_settings(|settings| settings.set_documents_compression(true)).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert!(index.documents_compression(&rtxn).unwrap());
+        assert!(index.documents_compression_dictionary(&rtxn).unwrap().is_some());
+        let documents = index.documents(&rtxn, vec![0, 1, 2]).unwrap();
+        assert_eq!(documents[1].1.as_obkv().get(1), Some(&br#""kevina""#[..]));
+        drop(rtxn);
+
+        // The new documents are compressed with the same dictionary.
+        index.add_documents(documents!([{ "id": 3, "name": "bernard" }])).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        let documents = index.documents(&rtxn, Some(3)).unwrap();
+        assert_eq!(documents[0].1.as_obkv().get(1), Some(&br#""bernard""#[..]));
+        drop(rtxn);
+
+        // Disabling the compression decompresses all the documents.
+        index.update_settings(|settings| settings.reset_documents_compression()).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert!(!index.documents_compression(&rtxn).unwrap());
+        assert!(index.documents_compression_dictionary(&rtxn).unwrap().is_none());
+        let names: Vec<_> = index
+            .all_documents(&rtxn)
+            .unwrap()
+            .map(|result| result.unwrap().1.as_obkv().get(1).unwrap().to_vec())
+            .collect();
+        assert_eq!(names.len(), 4);
+        assert_eq!(names[0], br#""kevin""#);
+        assert_eq!(names[3], br#""bernard""#);
+    }
+
+    #[test]
+    fn set_searchable_fields_with_weights() {
+        let index = TempIndex::new();
+
+        index
+            .update_settings(|settings| {
+                settings.set_searchable_fields_with_weights(vec![
+                    (S("overview"), None),
+                    (S("tags"), Some(0)),
+                    (S("title"), Some(3)),
+                    (S("genre"), None),
+                ]);
+            })
+            .unwrap();
+
+        // The fields are given their ids by decreasing weight, in the order of the settings.
+        let rtxn = index.read_txn().unwrap();
+        let fields_ids_map = index.fields_ids_map(&rtxn).unwrap();
+        let names: Vec<_> = fields_ids_map.iter().map(|(_, name)| name).collect();
+        assert_eq!(names, vec!["title", "overview", "genre", "tags"]);
+        let user_defined = index.user_defined_searchable_fields(&rtxn).unwrap();
+        assert_eq!(user_defined, Some(vec!["overview", "tags", "title", "genre"]));
+        let weights = index.searchable_fields_weights(&rtxn).unwrap();
+        assert_eq!(weights, btreemap! { S("tags") => 0, S("title") => 3 });
+        drop(rtxn);
+
+        index.update_settings(|settings| settings.reset_searchable_fields()).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert!(index.searchable_fields_weights(&rtxn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn update_proximity_precision() {
+        let index = TempIndex::new();
+
+        index
+            .add_documents(documents!([
+                { "id": 0, "name": "kevin the cat" },
+                { "id": 1, "name": "kevina the dog" },
+            ]))
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert!(!index.word_pair_proximity_docids.is_empty(&rtxn).unwrap());
+        drop(rtxn);
+
+        // The word pair proximity databases are cleared and not built anymore.
+        index
+            .update_settings(|settings| {
+                settings.set_proximity_precision(ProximityPrecision::ByAttribute)
+            })
+            .unwrap();
+        index.add_documents(documents!([{ "id": 2, "name": "kevinos the bird" }])).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.proximity_precision(&rtxn).unwrap(), ProximityPrecision::ByAttribute);
+        assert!(index.word_pair_proximity_docids.is_empty(&rtxn).unwrap());
+        assert!(index.word_prefix_pair_proximity_docids.is_empty(&rtxn).unwrap());
+        assert!(index.prefix_word_pair_proximity_docids.is_empty(&rtxn).unwrap());
+        drop(rtxn);
+
+        // Going back to the by-word precision builds them again for all the documents.
+        index.update_settings(|settings| settings.reset_proximity_precision()).unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(index.proximity_precision(&rtxn).unwrap(), ProximityPrecision::ByWord);
+        let docids = index.word_pair_proximity_docids.get(&rtxn, &(1, "the", "bird")).unwrap();
+        assert_eq!(docids.unwrap().iter().collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[test]
+    fn update_position_bucketing() {
+        use crate::{absolute_from_relative_position, relative_from_absolute_position, Position};
+
+        let index = TempIndex::new();
+        index
+            .add_documents(documents!([{ "id": 0, "text": "one two three four five six" }]))
+            .unwrap();
+
+        let positions = |word: &str| {
+            let rtxn = index.read_txn().unwrap();
+            let range = (word, Position::MIN)..=(word, Position::MAX);
+            index
+                .word_position_docids
+                .range(&rtxn, &range)
+                .unwrap()
+                .map(|result| relative_from_absolute_position(result.unwrap().0 .1).1)
+                .collect::<Vec<_>>()
+        };
+        let has_word_count = |count: u8| {
+            let rtxn = index.read_txn().unwrap();
+            let text = index.fields_ids_map(&rtxn).unwrap().id("text").unwrap();
+            index.field_id_word_count_docids.get(&rtxn, &(text, count)).unwrap().is_some()
+        };
+
+        assert_eq!(positions("five"), vec![4]);
+        assert!(has_word_count(6));
+
+        // The positions after the second one are stored in buckets of 1, 2, 4... positions
+        // and the number of words of the attribute isn't stored anymore.
+        index
+            .update_settings(|settings| {
+                settings.set_position_bucketing(PositionBucketing::Logarithmic { exact: 2 })
+            })
+            .unwrap();
+
+        let rtxn = index.read_txn().unwrap();
+        assert_eq!(
+            index.position_bucketing(&rtxn).unwrap(),
+            PositionBucketing::Logarithmic { exact: 2 }
+        );
+        drop(rtxn);
+        assert_eq!(positions("one"), vec![0]);
+        assert_eq!(positions("three"), vec![2]);
+        assert_eq!(positions("four"), vec![3]);
+        assert_eq!(positions("five"), vec![3]);
+        assert_eq!(positions("six"), vec![5]);
+        assert!(!has_word_count(6));
+
+        let rtxn = index.read_txn().unwrap();
+        let mut search = index.search(&rtxn);
+        search.query("five six");
+        assert_eq!(search.execute().unwrap().documents_ids, vec![0]);
+        drop(rtxn);
+
+        // Going back to the exact positions stores them all again.
+        index.update_settings(|settings| settings.reset_position_bucketing()).unwrap();
+        assert_eq!(positions("five"), vec![4]);
+        assert!(has_word_count(6));
+    }
+
     #[test]
     fn test_correct_settings_init() {
         let index = TempIndex::new();
@@ -1453,36 +2283,76 @@ mod tests {
                     searchable_fields,
                     displayed_fields,
                     filterable_fields,
+                    no_distribution_fields,
+                    non_stored_fields,
+                    ignored_fields,
+                    sort_facet_values_by,
                     sortable_fields,
                     criteria,
                     stop_words,
+                    dictionary,
                     distinct_field,
                     synonyms,
                     primary_key,
                     authorize_typos,
+                    enable_word_splitting,
+                    enable_word_concatenation,
+                    enable_negative_terms,
+                    count_diacritics,
                     min_word_len_two_typos,
                     min_word_len_one_typo,
                     exact_words,
                     exact_attributes,
                     max_values_per_facet,
                     pagination_max_total_hits,
+                    documents_compression,
+                    proximity_precision,
+                    position_bucketing,
+                    coerce_numeric_strings,
+                    facet_level_group_size,
+                    facet_max_group_size,
+                    facet_min_level_size,
+                    words_prefix_threshold,
+                    min_prefix_length,
+                    max_prefix_length,
+                    max_prefixes,
                 } = settings;
                 assert!(matches!(searchable_fields, Setting::NotSet));
                 assert!(matches!(displayed_fields, Setting::NotSet));
                 assert!(matches!(filterable_fields, Setting::NotSet));
+                assert!(matches!(no_distribution_fields, Setting::NotSet));
+                assert!(matches!(non_stored_fields, Setting::NotSet));
+                assert!(matches!(ignored_fields, Setting::NotSet));
+                assert!(matches!(sort_facet_values_by, Setting::NotSet));
                 assert!(matches!(sortable_fields, Setting::NotSet));
                 assert!(matches!(criteria, Setting::NotSet));
                 assert!(matches!(stop_words, Setting::NotSet));
+                assert!(matches!(dictionary, Setting::NotSet));
                 assert!(matches!(distinct_field, Setting::NotSet));
                 assert!(matches!(synonyms, Setting::NotSet));
                 assert!(matches!(primary_key, Setting::NotSet));
                 assert!(matches!(authorize_typos, Setting::NotSet));
+                assert!(matches!(enable_word_splitting, Setting::NotSet));
+                assert!(matches!(enable_word_concatenation, Setting::NotSet));
+                assert!(matches!(enable_negative_terms, Setting::NotSet));
+                assert!(matches!(count_diacritics, Setting::NotSet));
                 assert!(matches!(min_word_len_two_typos, Setting::NotSet));
                 assert!(matches!(min_word_len_one_typo, Setting::NotSet));
                 assert!(matches!(exact_words, Setting::NotSet));
                 assert!(matches!(exact_attributes, Setting::NotSet));
                 assert!(matches!(max_values_per_facet, Setting::NotSet));
                 assert!(matches!(pagination_max_total_hits, Setting::NotSet));
+                assert!(matches!(documents_compression, Setting::NotSet));
+                assert!(matches!(proximity_precision, Setting::NotSet));
+                assert!(matches!(position_bucketing, Setting::NotSet));
+                assert!(matches!(coerce_numeric_strings, Setting::NotSet));
+                assert!(matches!(facet_level_group_size, Setting::NotSet));
+                assert!(matches!(facet_max_group_size, Setting::NotSet));
+                assert!(matches!(facet_min_level_size, Setting::NotSet));
+                assert!(matches!(words_prefix_threshold, Setting::NotSet));
+                assert!(matches!(min_prefix_length, Setting::NotSet));
+                assert!(matches!(max_prefix_length, Setting::NotSet));
+                assert!(matches!(max_prefixes, Setting::NotSet));
             })
             .unwrap();
     }
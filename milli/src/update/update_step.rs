@@ -3,20 +3,32 @@ use UpdateIndexingStep::*;
 #[derive(Debug, Clone, Copy)]
 pub enum UpdateIndexingStep {
     /// Remap document addition fields the one present in the database, adding new fields in to the
-    /// schema on the go.
-    RemapDocumentAddition { documents_seen: usize },
+    /// schema on the go. The ids given to the documents are written on disk in `spilled_chunks`
+    /// chunks when they exceed their memory budget.
+    RemapDocumentAddition { documents_seen: usize, spilled_chunks: usize },
 
     /// This step check the external document id, computes the internal ids and merge
     /// the documents that are already present in the database.
     ComputeIdsAndMergeDocuments { documents_seen: usize, total_documents: usize },
 
     /// Extract the documents words using the tokenizer and compute the documents
-    /// facets. Stores those words, facets and documents ids on disk.
+    /// facets, one chunk of documents at a time. Stores those words, facets and
+    /// documents ids on disk.
+    ExtractDocumentsChunks { chunks_seen: usize, total_chunks: usize },
+
+    /// Write the extracted documents into the LMDB database.
     IndexDocuments { documents_seen: usize, total_documents: usize },
 
     /// Merge the previously extracted data (words and facets) into the final LMDB database.
     /// These extracted data are split into multiple databases.
     MergeDataIntoFinalDatabase { databases_seen: usize, total_databases: usize },
+
+    /// Compute the facet levels of every faceted field, one field at a time.
+    ComputeFacetLevels { fields_seen: usize, total_fields: usize },
+
+    /// Compute the prefix databases (words prefixes fst, word prefix docids, word prefix
+    /// pair proximity docids and word prefix position docids) from the merged databases.
+    ComputePrefixDatabases { databases_seen: usize, total_databases: usize },
 }
 
 impl UpdateIndexingStep {
@@ -24,12 +36,15 @@ impl UpdateIndexingStep {
         match self {
             RemapDocumentAddition { .. } => 0,
             ComputeIdsAndMergeDocuments { .. } => 1,
-            IndexDocuments { .. } => 2,
-            MergeDataIntoFinalDatabase { .. } => 3,
+            ExtractDocumentsChunks { .. } => 2,
+            IndexDocuments { .. } => 3,
+            MergeDataIntoFinalDatabase { .. } => 4,
+            ComputeFacetLevels { .. } => 5,
+            ComputePrefixDatabases { .. } => 6,
         }
     }
 
     pub const fn number_of_steps(&self) -> usize {
-        4
+        7
     }
 }
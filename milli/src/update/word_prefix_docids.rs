@@ -4,9 +4,10 @@ use grenad::CompressionType;
 use heed::types::{ByteSlice, Str};
 use heed::Database;
 
+use crate::index::db_name;
 use crate::update::index_documents::{
-    create_sorter, merge_roaring_bitmaps, sorter_into_lmdb_database, valid_lmdb_key,
-    CursorClonableMmap, MergeFn,
+    create_sorter, sorter_into_lmdb_database, valid_lmdb_key, CursorClonableMmap,
+    KeysOrderValidator, MergeFn, MergeStrategy,
 };
 use crate::{Result, RoaringBitmapCodec};
 
@@ -18,6 +19,8 @@ pub struct WordPrefixDocids<'t, 'u, 'i> {
     pub(crate) chunk_compression_level: Option<u32>,
     pub(crate) max_nb_chunks: Option<usize>,
     pub(crate) max_memory: Option<usize>,
+    pub(crate) validate_ordering: bool,
+    pub(crate) database_name: &'static str,
 }
 
 impl<'t, 'u, 'i> WordPrefixDocids<'t, 'u, 'i> {
@@ -34,6 +37,8 @@ impl<'t, 'u, 'i> WordPrefixDocids<'t, 'u, 'i> {
             chunk_compression_level: None,
             max_nb_chunks: None,
             max_memory: None,
+            validate_ordering: cfg!(debug_assertions),
+            database_name: db_name::WORD_PREFIX_DOCIDS,
         }
     }
 
@@ -49,7 +54,7 @@ impl<'t, 'u, 'i> WordPrefixDocids<'t, 'u, 'i> {
         // and write into it at the same time, therefore we write into another file.
         let mut prefix_docids_sorter = create_sorter(
             grenad::SortAlgorithm::Unstable,
-            merge_roaring_bitmaps,
+            MergeStrategy::MERGE_ROARING_BITMAPS,
             self.chunk_compression_type,
             self.chunk_compression_level,
             self.max_nb_chunks,
@@ -113,7 +118,8 @@ impl<'t, 'u, 'i> WordPrefixDocids<'t, 'u, 'i> {
             self.wtxn,
             *self.word_prefix_docids.as_polymorph(),
             prefix_docids_sorter,
-            merge_roaring_bitmaps,
+            MergeStrategy::MERGE_ROARING_BITMAPS,
+            KeysOrderValidator::new(self.database_name, self.validate_ordering),
         )?;
 
         Ok(())
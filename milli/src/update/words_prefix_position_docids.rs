@@ -9,10 +9,11 @@ use log::debug;
 
 use crate::error::SerializationError;
 use crate::heed_codec::StrBEU32Codec;
+use crate::index::db_name;
 use crate::index::main_key::WORDS_PREFIXES_FST_KEY;
 use crate::update::index_documents::{
-    create_sorter, merge_cbo_roaring_bitmaps, sorter_into_lmdb_database, valid_lmdb_key,
-    CursorClonableMmap, MergeFn,
+    create_sorter, sorter_into_lmdb_database, valid_lmdb_key, CursorClonableMmap,
+    KeysOrderValidator, MergeFn, MergeStrategy,
 };
 use crate::{Index, Result};
 
@@ -23,6 +24,7 @@ pub struct WordPrefixPositionDocids<'t, 'u, 'i> {
     pub(crate) chunk_compression_level: Option<u32>,
     pub(crate) max_nb_chunks: Option<usize>,
     pub(crate) max_memory: Option<usize>,
+    pub(crate) validate_ordering: bool,
     level_group_size: NonZeroU32,
     min_level_size: NonZeroU32,
 }
@@ -39,6 +41,7 @@ impl<'t, 'u, 'i> WordPrefixPositionDocids<'t, 'u, 'i> {
             chunk_compression_level: None,
             max_nb_chunks: None,
             max_memory: None,
+            validate_ordering: cfg!(debug_assertions),
             level_group_size: NonZeroU32::new(4).unwrap(),
             min_level_size: NonZeroU32::new(5).unwrap(),
         }
@@ -66,7 +69,7 @@ impl<'t, 'u, 'i> WordPrefixPositionDocids<'t, 'u, 'i> {
 
         let mut prefix_position_docids_sorter = create_sorter(
             grenad::SortAlgorithm::Unstable,
-            merge_cbo_roaring_bitmaps,
+            MergeStrategy::MERGE_CBO_ROARING_BITMAPS,
             self.chunk_compression_type,
             self.chunk_compression_level,
             self.max_nb_chunks,
@@ -155,7 +158,8 @@ impl<'t, 'u, 'i> WordPrefixPositionDocids<'t, 'u, 'i> {
             self.wtxn,
             *self.index.word_prefix_position_docids.as_polymorph(),
             prefix_position_docids_sorter,
-            merge_cbo_roaring_bitmaps,
+            MergeStrategy::MERGE_CBO_ROARING_BITMAPS,
+            KeysOrderValidator::new(db_name::WORD_PREFIX_POSITION_DOCIDS, self.validate_ordering),
         )?;
 
         Ok(())
@@ -1,15 +1,42 @@
-use std::iter::{repeat_with, FromIterator};
+use std::cmp::Reverse;
 use std::str;
 
 use fst::{SetBuilder, Streamer};
+use serde::{Deserialize, Serialize};
 
 use crate::{Index, Result, SmallString32};
 
+/// The parameters of the computation of the words prefixes, stored in the index by the
+/// [`Settings`](crate::update::Settings). The prefix databases are rebuilt from scratch
+/// by the next documents addition when they were computed with other parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrefixSettings {
+    /// The number of words of the dictionary a prefix must match to be materialized.
+    pub threshold: u32,
+    /// The minimum length in bytes of the materialized prefixes.
+    pub min_prefix_length: usize,
+    /// The maximum length in bytes of the materialized prefixes.
+    pub max_prefix_length: usize,
+    /// The maximum number of materialized prefixes, the ones matching the most words are kept.
+    pub max_prefixes: Option<usize>,
+}
+
+impl Default for PrefixSettings {
+    fn default() -> Self {
+        PrefixSettings {
+            threshold: 100,
+            min_prefix_length: 1,
+            max_prefix_length: 4,
+            max_prefixes: None,
+        }
+    }
+}
+
 pub struct WordsPrefixesFst<'t, 'u, 'i> {
     wtxn: &'t mut heed::RwTxn<'i, 'u>,
     index: &'i Index,
-    threshold: u32,
-    max_prefix_length: usize,
+    settings: PrefixSettings,
 }
 
 impl<'t, 'u, 'i> WordsPrefixesFst<'t, 'u, 'i> {
@@ -17,7 +44,21 @@ impl<'t, 'u, 'i> WordsPrefixesFst<'t, 'u, 'i> {
         wtxn: &'t mut heed::RwTxn<'i, 'u>,
         index: &'i Index,
     ) -> WordsPrefixesFst<'t, 'u, 'i> {
-        WordsPrefixesFst { wtxn, index, threshold: 100, max_prefix_length: 4 }
+        WordsPrefixesFst { wtxn, index, settings: PrefixSettings::default() }
+    }
+
+    /// Set all the parameters of the computation at once, they are clamped by their setters.
+    pub fn settings(&mut self, settings: PrefixSettings) -> &mut Self {
+        self.threshold(settings.threshold)
+            .min_prefix_length(settings.min_prefix_length)
+            .max_prefix_length(settings.max_prefix_length);
+        self.settings.max_prefixes = settings.max_prefixes;
+        self
+    }
+
+    /// Returns the parameters the prefixes will be computed with.
+    pub fn prefix_settings(&self) -> PrefixSettings {
+        self.settings
     }
 
     /// Set the number of words required to make a prefix be part of the words prefixes
@@ -27,7 +68,16 @@ impl<'t, 'u, 'i> WordsPrefixesFst<'t, 'u, 'i> {
     /// Default value is 100. This value must be higher than 50 and will be clamped
     /// to this bound otherwise.
     pub fn threshold(&mut self, value: u32) -> &mut Self {
-        self.threshold = value.max(50);
+        self.settings.threshold = value.max(50);
+        self
+    }
+
+    /// Set the minimum length of prefixes in bytes.
+    ///
+    /// Default value is `1` byte. This value must be between 1 and 25 will be clamped
+    /// to these bounds, otherwise.
+    pub fn min_prefix_length(&mut self, value: usize) -> &mut Self {
+        self.settings.min_prefix_length = value.min(25).max(1); // clamp [1, 25]
         self
     }
 
@@ -36,25 +86,33 @@ impl<'t, 'u, 'i> WordsPrefixesFst<'t, 'u, 'i> {
     /// Default value is `4` bytes. This value must be between 1 and 25 will be clamped
     /// to these bounds, otherwise.
     pub fn max_prefix_length(&mut self, value: usize) -> &mut Self {
-        self.max_prefix_length = value.min(25).max(1); // clamp [1, 25]
+        self.settings.max_prefix_length = value.min(25).max(1); // clamp [1, 25]
+        self
+    }
+
+    /// Set the maximum number of prefixes, the prefixes matching the most words are kept.
+    ///
+    /// There is no maximum by default.
+    pub fn max_prefixes(&mut self, value: usize) -> &mut Self {
+        self.settings.max_prefixes = Some(value);
         self
     }
 
     #[logging_timer::time("WordsPrefixesFst::{}")]
     pub fn execute(self) -> Result<()> {
+        let PrefixSettings { threshold, min_prefix_length, max_prefix_length, max_prefixes } =
+            self.settings;
         let words_fst = self.index.words_fst(self.wtxn)?;
 
-        let mut current_prefix = vec![SmallString32::new(); self.max_prefix_length];
-        let mut current_prefix_count = vec![0; self.max_prefix_length];
-        let mut builders =
-            repeat_with(SetBuilder::memory).take(self.max_prefix_length).collect::<Vec<_>>();
+        let mut current_prefix = vec![SmallString32::new(); max_prefix_length];
+        let mut current_prefix_count = vec![0; max_prefix_length];
+        let mut prefixes = Vec::new();
 
         let mut stream = words_fst.stream();
         while let Some(bytes) = stream.next() {
-            for n in 0..self.max_prefix_length {
+            for n in min_prefix_length - 1..max_prefix_length {
                 let current_prefix = &mut current_prefix[n];
                 let current_prefix_count = &mut current_prefix_count[n];
-                let builder = &mut builders[n];
 
                 // We try to get the first n bytes out of this string but we only want
                 // to split at valid characters bounds. If we try to split in the middle of
@@ -68,24 +126,36 @@ impl<'t, 'u, 'i> WordsPrefixesFst<'t, 'u, 'i> {
                 // This is the first iteration of the loop,
                 // or the current word doesn't starts with the current prefix.
                 if *current_prefix_count == 0 || prefix != current_prefix.as_str() {
+                    if *current_prefix_count >= threshold {
+                        prefixes.push((current_prefix.clone(), *current_prefix_count));
+                    }
                     *current_prefix = SmallString32::from(prefix);
                     *current_prefix_count = 0;
                 }
 
                 *current_prefix_count += 1;
+            }
+        }
 
-                // There is enough words corresponding to this prefix to add it to the cache.
-                if *current_prefix_count >= self.threshold {
-                    builder.insert(prefix)?;
-                }
+        // The last prefixes of every length are not followed by another one.
+        for (prefix, count) in current_prefix.into_iter().zip(current_prefix_count) {
+            if count >= threshold {
+                prefixes.push((prefix, count));
             }
         }
 
-        // We merge all of the previously computed prefixes into on final set.
-        let prefix_fsts: Vec<_> = builders.into_iter().map(|sb| sb.into_set()).collect();
-        let op = fst::set::OpBuilder::from_iter(prefix_fsts.iter());
-        let mut builder = fst::SetBuilder::memory();
-        builder.extend_stream(op.r#union())?;
+        // We only keep the prefixes matching the most words, the shortest ones first.
+        if let Some(max_prefixes) = max_prefixes {
+            prefixes.sort_unstable_by(|(a, a_count), (b, b_count)| {
+                (Reverse(a_count), a.len(), a).cmp(&(Reverse(b_count), b.len(), b))
+            });
+            prefixes.truncate(max_prefixes);
+        }
+
+        let mut prefixes: Vec<_> = prefixes.into_iter().map(|(prefix, _)| prefix).collect();
+        prefixes.sort_unstable();
+        let mut builder = SetBuilder::memory();
+        builder.extend_iter(prefixes.iter().map(|prefix| prefix.as_str()))?;
         let prefix_fst = builder.into_set();
 
         // Set the words prefixes FST in the dtabase.
@@ -127,7 +127,8 @@ fn test_typo_disabled_on_word() {
     let mut txn = index.write_txn().unwrap();
     let config = IndexerConfig::default();
     let indexing_config = IndexDocumentsConfig::default();
-    let builder = IndexDocuments::new(&mut txn, &index, &config, indexing_config, |_| ()).unwrap();
+    let builder =
+        IndexDocuments::new(&mut txn, &index, &config, indexing_config, |_| (), || false).unwrap();
 
     let (builder, user_error) = builder.add_documents(documents).unwrap();
     user_error.unwrap();
@@ -205,3 +206,71 @@ fn test_disable_typo_on_attribute() {
     let result = search.execute().unwrap();
     assert_eq!(result.documents_ids.len(), 0);
 }
+
+#[test]
+fn test_exact_attribute_only_matches_exactly() {
+    let tmp = tempdir().unwrap();
+    let mut options = EnvOpenOptions::new();
+    options.map_size(4096 * 100);
+    let index = Index::new(options, tmp.path()).unwrap();
+
+    let mut builder = milli::documents::DocumentsBatchBuilder::new(Vec::new());
+    let doc1 = json!({
+        "id": 1usize,
+        "barcode": "barcode123",
+        "title": "blue pen",
+    });
+
+    let doc2 = json!({
+        "id": 2usize,
+        "barcode": "xyz789",
+        "title": "barcode reader",
+    });
+
+    builder.append_json_object(doc1.as_object().unwrap()).unwrap();
+    builder.append_json_object(doc2.as_object().unwrap()).unwrap();
+    let vector = builder.into_inner().unwrap();
+
+    let documents =
+        milli::documents::DocumentsBatchReader::from_reader(std::io::Cursor::new(vector)).unwrap();
+
+    let mut txn = index.write_txn().unwrap();
+    let config = IndexerConfig::default();
+    let indexing_config = IndexDocumentsConfig::default();
+    let builder =
+        IndexDocuments::new(&mut txn, &index, &config, indexing_config, |_| (), || false).unwrap();
+
+    let (builder, user_error) = builder.add_documents(documents).unwrap();
+    user_error.unwrap();
+    builder.execute().unwrap();
+
+    // `barcod` is both a prefix of `barcode123` and a typo of `barcode`
+    assert_eq!(search_ids(&txn, &index, "barcod"), vec![0, 1]);
+
+    let mut builder = Settings::new(&mut txn, &index, &config);
+    builder.set_exact_attributes(vec!["barcode".to_string()].into_iter().collect());
+    builder.execute(|_| ()).unwrap();
+
+    // the barcodes must now be matched exactly, the titles still tolerate typos
+    assert_eq!(search_ids(&txn, &index, "barcod"), vec![1]);
+    assert_eq!(search_ids(&txn, &index, "barcode123"), vec![0]);
+    assert_eq!(search_ids(&txn, &index, "pen"), vec![0]);
+
+    let mut builder = Settings::new(&mut txn, &index, &config);
+    builder.reset_exact_attributes();
+    builder.execute(|_| ()).unwrap();
+
+    assert_eq!(search_ids(&txn, &index, "barcod"), vec![0, 1]);
+}
+
+fn search_ids(txn: &heed::RoTxn, index: &Index, query: &str) -> Vec<u32> {
+    let mut search = Search::new(txn, index);
+    search.query(query);
+    search.limit(10);
+    search.authorize_typos(true);
+    search.terms_matching_strategy(TermsMatchingStrategy::default());
+
+    let mut documents_ids = search.execute().unwrap().documents_ids;
+    documents_ids.sort_unstable();
+    documents_ids
+}